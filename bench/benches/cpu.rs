@@ -0,0 +1,84 @@
+// pathfinder/bench/benches/cpu.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Benchmarks for the CPU side of Pathfinder: scene building, stroking, and tiling.
+
+use criterion::{BatchSize, Criterion, black_box, criterion_group, criterion_main};
+use pathfinder_bench as scenes;
+use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+use pathfinder_renderer::scene::{DrawPathId, Scene};
+
+fn scene_building(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("scene_building");
+    let tiger = scenes::tiger_svg_tree();
+    group.bench_function("tiger", |bencher| bencher.iter(|| scenes::svg_scene(&tiger)));
+    group.bench_function("text", |bencher| bencher.iter(scenes::text_scene));
+    group.bench_function("map_tile", |bencher| bencher.iter(scenes::map_tile_scene));
+    group.finish();
+}
+
+fn stroking(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("stroking");
+
+    // Stroke every outline of the tiger, which exercises curves and joins heavily.
+    let tiger = scenes::svg_scene(&scenes::tiger_svg_tree());
+    let tiger_outlines: Vec<_> = (0..tiger.draw_path_count()).map(|index| {
+        tiger.get_draw_path(DrawPathId(index)).outline().clone()
+    }).collect();
+    let style = StrokeStyle {
+        line_width: 2.0,
+        line_cap: LineCap::Butt,
+        line_join: LineJoin::Miter(10.0),
+    };
+    group.bench_function("tiger", |bencher| {
+        bencher.iter(|| {
+            for outline in &tiger_outlines {
+                let mut stroke_to_fill = OutlineStrokeToFill::new(outline, style);
+                stroke_to_fill.offset();
+                black_box(stroke_to_fill.into_outline());
+            }
+        })
+    });
+
+    let roads = scenes::map_tile_road_outlines();
+    let style = scenes::map_road_stroke_style();
+    group.bench_function("map_tile_roads", |bencher| {
+        bencher.iter(|| {
+            for outline in &roads {
+                let mut stroke_to_fill = OutlineStrokeToFill::new(outline, style);
+                stroke_to_fill.offset();
+                black_box(stroke_to_fill.into_outline());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn tiling(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("tiling");
+    bench_tiling(&mut group, "tiger", scenes::svg_scene(&scenes::tiger_svg_tree()));
+    bench_tiling(&mut group, "text", scenes::text_scene());
+    bench_tiling(&mut group, "map_tile", scenes::map_tile_scene());
+    group.finish();
+}
+
+fn bench_tiling(group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>,
+                name: &str,
+                scene: Scene) {
+    group.bench_function(name, |bencher| {
+        bencher.iter_batched_ref(|| scene.clone(),
+                                 |scene| scenes::tile_on_cpu(scene),
+                                 BatchSize::LargeInput)
+    });
+}
+
+criterion_group!(benches, scene_building, stroking, tiling);
+criterion_main!(benches);
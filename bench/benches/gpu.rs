@@ -0,0 +1,38 @@
+// pathfinder/bench/benches/gpu.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Benchmarks full frames, from scene building through GPU rendering to an offscreen texture.
+//!
+//! These are skipped if no GPU adapter is available.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use pathfinder_bench::{self as scenes, GpuBench};
+
+fn full_frame(criterion: &mut Criterion) {
+    let mut gpu = match GpuBench::new() {
+        Some(gpu) => gpu,
+        None => {
+            eprintln!("No GPU adapter available; skipping GPU benchmarks.");
+            return;
+        }
+    };
+
+    let mut group = criterion.benchmark_group("full_frame");
+    let mut tiger = scenes::svg_scene(&scenes::tiger_svg_tree());
+    group.bench_function("tiger", |bencher| bencher.iter(|| gpu.render(&mut tiger)));
+    let mut text = scenes::text_scene();
+    group.bench_function("text", |bencher| bencher.iter(|| gpu.render(&mut text)));
+    let mut map_tile = scenes::map_tile_scene();
+    group.bench_function("map_tile", |bencher| bencher.iter(|| gpu.render(&mut map_tile)));
+    group.finish();
+}
+
+criterion_group!(benches, full_frame);
+criterion_main!(benches);
@@ -0,0 +1,254 @@
+// pathfinder/bench/src/lib.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Canonical scenes and helpers shared by the Pathfinder benchmarks.
+//!
+//! Every scene here is deterministic, so that numbers are comparable from run to run and from
+//! machine to machine. Run the benchmarks with `cargo bench -p pathfinder_bench`.
+
+use font_kit::handle::Handle;
+use pathfinder_canvas::{Canvas, CanvasFontContext};
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f, vec2i};
+use pathfinder_gpu::Device;
+use pathfinder_renderer::concurrent::executor::SequentialExecutor;
+use pathfinder_renderer::gpu::options::{DestFramebuffer, RendererLevel, RendererMode};
+use pathfinder_renderer::gpu::options::{AlphaMode, LoadAction, RendererOptions};
+use pathfinder_renderer::gpu::renderer::Renderer;
+use pathfinder_renderer::options::{BuildOptions, RenderCommandListener};
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{DrawPath, Scene, SceneSink};
+use pathfinder_resources::ResourceLoader;
+use pathfinder_resources::embedded::EmbeddedResourceLoader;
+use pathfinder_svg::SVGScene;
+use std::iter;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use usvg::{Options as UsvgOptions, Tree};
+
+/// The size of the viewport that all scenes are built for, in device pixels.
+pub const VIEWPORT_SIZE: i32 = 1024;
+
+const TIGER_SVG_PATH: &str = "svg/Ghostscript_Tiger.svg";
+static ROBOTO_REGULAR: &[u8] = include_bytes!("../../resources/fonts/Roboto-Regular.ttf");
+
+const PARAGRAPH: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod \
+                         tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim \
+                         veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea \
+                         commodo consequat. Duis aute irure dolor in reprehenderit in voluptate \
+                         velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint \
+                         occaecat cupidatat non proident, sunt in culpa qui officia deserunt \
+                         mollit anim id est laborum.";
+
+const MAP_BUILDING_COUNT: usize = 2000;
+const MAP_ROAD_COUNT: usize = 200;
+const MAP_WATER_COUNT: usize = 8;
+
+/// Parses the Ghostscript tiger.
+pub fn tiger_svg_tree() -> Tree {
+    let data = EmbeddedResourceLoader::new().slurp(TIGER_SVG_PATH).unwrap();
+    Tree::from_data(&data, &UsvgOptions::default().to_ref()).unwrap()
+}
+
+/// Builds a scene from an already-parsed SVG.
+pub fn svg_scene(tree: &Tree) -> Scene {
+    let mut scene = SVGScene::from_tree(tree).scene;
+    scene.set_view_box(viewport_rect());
+    scene
+}
+
+/// Builds a scene containing a paragraph of text, laid out in lines.
+pub fn text_scene() -> Scene {
+    let font = Handle::from_memory(Arc::new(ROBOTO_REGULAR.to_vec()), 0);
+    let font_context = CanvasFontContext::from_fonts(iter::once(font));
+    let mut canvas = Canvas::new(viewport_rect().size()).get_context_2d(font_context);
+    canvas.set_font("Roboto-Regular").unwrap();
+    canvas.set_font_size(18.0);
+    canvas.set_fill_style(ColorU::black());
+
+    let mut line = String::new();
+    let mut y = 32.0;
+    for word in PARAGRAPH.split_whitespace() {
+        if line.len() + word.len() > 96 {
+            canvas.fill_text(&line, vec2f(16.0, y));
+            line.clear();
+            y += 24.0;
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    canvas.fill_text(&line, vec2f(16.0, y));
+
+    let mut scene = canvas.into_canvas().into_scene();
+    scene.set_view_box(viewport_rect());
+    scene
+}
+
+/// Builds a scene resembling a city map tile: water bodies, building footprints, and stroked
+/// roads.
+pub fn map_tile_scene() -> Scene {
+    let mut scene = Scene::new();
+    scene.set_view_box(viewport_rect());
+    let mut random = Random::new(0x5eed);
+
+    let land = scene.push_paint(&Paint::from_color(ColorU::new(242, 239, 233, 255)));
+    scene.push_draw_path(DrawPath::new(Outline::from_rect(viewport_rect()), land));
+
+    let water = scene.push_paint(&Paint::from_color(ColorU::new(170, 211, 223, 255)));
+    for _ in 0..MAP_WATER_COUNT {
+        let center = random.point();
+        let radius = 32.0 + random.next_f32() * 96.0;
+        let mut contour = Contour::new();
+        contour.push_endpoint(center + vec2f(radius, 0.0));
+        for step in 1..=8 {
+            let angle = step as f32 * std::f32::consts::PI / 4.0;
+            let wobble = radius * (0.75 + random.next_f32() * 0.5);
+            let ctrl = center + vec2f((angle - 0.4).cos(), (angle - 0.4).sin()) * wobble;
+            let to = center + vec2f(angle.cos(), angle.sin()) * radius;
+            contour.push_quadratic(ctrl, to);
+        }
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        scene.push_draw_path(DrawPath::new(outline, water));
+    }
+
+    let building = scene.push_paint(&Paint::from_color(ColorU::new(217, 208, 201, 255)));
+    for _ in 0..MAP_BUILDING_COUNT {
+        let size = vec2f(4.0 + random.next_f32() * 16.0, 4.0 + random.next_f32() * 16.0);
+        let rect = RectF::new(random.point(), size);
+        scene.push_draw_path(DrawPath::new(Outline::from_rect(rect), building));
+    }
+
+    let road = scene.push_paint(&Paint::from_color(ColorU::white()));
+    for outline in map_tile_road_outlines() {
+        let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, map_road_stroke_style());
+        stroke_to_fill.offset();
+        scene.push_draw_path(DrawPath::new(stroke_to_fill.into_outline(), road));
+    }
+
+    scene
+}
+
+/// Returns the unstroked center lines of the roads in `map_tile_scene()`.
+pub fn map_tile_road_outlines() -> Vec<Outline> {
+    let mut random = Random::new(0x70ad);
+    (0..MAP_ROAD_COUNT).map(|_| {
+        let mut contour = Contour::new();
+        let mut position = random.point();
+        contour.push_endpoint(position);
+        for _ in 0..8 {
+            position += vec2f(random.next_f32() - 0.5, random.next_f32() - 0.5) * 128.0;
+            contour.push_endpoint(position);
+        }
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }).collect()
+}
+
+/// The stroke style used for the roads in `map_tile_scene()`.
+pub fn map_road_stroke_style() -> StrokeStyle {
+    StrokeStyle { line_width: 6.0, line_cap: LineCap::Round, line_join: LineJoin::Round }
+}
+
+/// Builds the given scene with CPU tiling, discarding the resulting commands.
+///
+/// Returns the number of render commands produced.
+pub fn tile_on_cpu(scene: &mut Scene) -> usize {
+    let command_count = AtomicUsize::new(0);
+    let listener = RenderCommandListener::new(Box::new(|_| {
+        command_count.fetch_add(1, Ordering::Relaxed);
+    }));
+    let mut sink = SceneSink::new(listener, RendererLevel::D3D9);
+    scene.build(BuildOptions::default(), &mut sink, &SequentialExecutor);
+    command_count.load(Ordering::Relaxed)
+}
+
+/// A renderer drawing into an offscreen texture, for measuring full frames on the GPU.
+pub struct GpuBench {
+    renderer: Renderer,
+}
+
+impl GpuBench {
+    /// Creates a renderer on the default adapter.
+    ///
+    /// Returns `None` if no adapter is available, e.g. on headless CI machines.
+    pub fn new() -> Option<GpuBench> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        })).ok()?;
+
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::default(),
+            memory_hints: Default::default(),
+            experimental_features: wgpu::ExperimentalFeatures::disabled(),
+            trace: wgpu::Trace::default(),
+        })).ok()?;
+
+        let device = Device::new(Arc::new(device),
+                                 Arc::new(queue),
+                                 adapter.get_info().name,
+                                 adapter.get_info().backend.to_str().to_string());
+        let dest_texture = device.create_texture(wgpu::TextureFormat::Rgba8Unorm,
+                                                 vec2i(VIEWPORT_SIZE, VIEWPORT_SIZE),
+                                                 wgpu::TextureUsages::RENDER_ATTACHMENT |
+                                                 wgpu::TextureUsages::TEXTURE_BINDING |
+                                                 wgpu::TextureUsages::COPY_SRC);
+        let options = RendererOptions {
+            dest: DestFramebuffer::Other(dest_texture),
+            background_color: Some(ColorF::white()),
+            load_action: LoadAction::Clear,
+            alpha_mode: AlphaMode::Premultiplied,
+            show_debug_ui: false,
+        };
+        let mode = RendererMode { level: RendererLevel::D3D9 };
+        let renderer = Renderer::new(device, &EmbeddedResourceLoader::new(), mode, options);
+        Some(GpuBench { renderer })
+    }
+
+    /// Builds and renders the scene, and waits for the GPU to finish.
+    pub fn render(&mut self, scene: &mut Scene) {
+        scene.build_and_render(&mut self.renderer, BuildOptions::default(), SequentialExecutor);
+        self.renderer.device().device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+    }
+}
+
+fn viewport_rect() -> RectF {
+    RectF::new(Vector2F::zero(), Vector2I::splat(VIEWPORT_SIZE).to_f32())
+}
+
+// A tiny linear congruential generator, so that scenes don't depend on an RNG crate's algorithm.
+struct Random(u32);
+
+impl Random {
+    fn new(seed: u32) -> Random {
+        Random(seed)
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        self.0 = self.0.wrapping_mul(1664525).wrapping_add(1013904223);
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn point(&mut self) -> Vector2F {
+        vec2f(self.next_f32(), self.next_f32()) * VIEWPORT_SIZE as f32
+    }
+}
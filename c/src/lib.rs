@@ -29,7 +29,8 @@ use pathfinder_resources::embedded::EmbeddedResourceLoader;
 use pathfinder_renderer::concurrent::rayon::RayonExecutor;
 use pathfinder_renderer::concurrent::scene_proxy::SceneProxy;
 use pathfinder_renderer::gpu::options::{DestFramebuffer, RendererLevel};
-use pathfinder_renderer::gpu::options::{RendererMode, RendererOptions};
+use pathfinder_renderer::gpu::options::{AlphaMode, LoadAction, RendererMode};
+use pathfinder_renderer::gpu::options::RendererOptions;
 use pathfinder_renderer::gpu::renderer::Renderer;
 use pathfinder_renderer::options::{BuildOptions, RenderTransform};
 use pathfinder_renderer::scene::Scene;
@@ -90,6 +91,8 @@ pub const PF_GL_VERSION_GL4:    u8 = 2;
 
 pub const PF_RENDERER_OPTIONS_FLAGS_HAS_BACKGROUND_COLOR: u8 = 0x1;
 pub const PF_RENDERER_OPTIONS_FLAGS_SHOW_DEBUG_UI: u8 = 0x2;
+pub const PF_RENDERER_OPTIONS_FLAGS_PRESERVE_DEST: u8 = 0x4;
+pub const PF_RENDERER_OPTIONS_FLAGS_STRAIGHT_ALPHA: u8 = 0x8;
 
 pub const PF_RENDERER_LEVEL_D3D9: u8 = 0x1;
 pub const PF_RENDERER_LEVEL_D3D11: u8 = 0x2;
@@ -783,6 +786,12 @@ pub unsafe extern "C" fn PFBuildOptionsSetSubpixelAAEnabled(options: PFBuildOpti
     (*options).subpixel_aa_enabled = subpixel_aa_enabled
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn PFBuildOptionsSetDeterministic(options: PFBuildOptionsRef,
+                                                        deterministic: bool) {
+    (*options).deterministic = deterministic
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn PFSceneDestroy(scene: PFSceneRef) {
     drop(Box::from_raw(scene))
@@ -954,6 +963,16 @@ impl PFRendererOptions {
     pub fn to_rust(&self) -> RendererOptions {
         let has_background_color = self.flags & PF_RENDERER_OPTIONS_FLAGS_HAS_BACKGROUND_COLOR;
         let show_debug_ui = (self.flags & PF_RENDERER_OPTIONS_FLAGS_SHOW_DEBUG_UI) != 0;
+        let load_action = if (self.flags & PF_RENDERER_OPTIONS_FLAGS_PRESERVE_DEST) != 0 {
+            LoadAction::Load
+        } else {
+            LoadAction::Clear
+        };
+        let alpha_mode = if (self.flags & PF_RENDERER_OPTIONS_FLAGS_STRAIGHT_ALPHA) != 0 {
+            AlphaMode::Straight
+        } else {
+            AlphaMode::Premultiplied
+        };
         unsafe {
             RendererOptions {
                 background_color: if has_background_color != 0 {
@@ -962,6 +981,8 @@ impl PFRendererOptions {
                     None
                 },
                 dest: *Box::from_raw(self.dest as *mut DestFramebuffer),
+                load_action,
+                alpha_mode,
                 show_debug_ui,
             }
         }
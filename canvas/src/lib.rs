@@ -12,16 +12,21 @@
 
 pub use pathfinder_color::{ColorF, ColorU, rgbaf, rgbau, rgbf, rgbu};
 pub use pathfinder_color::{color_slice_to_u8_slice, u8_slice_to_color_slice, u8_vec_to_color_vec};
+pub use pathfinder_content::dash::DashDirection;
 pub use pathfinder_content::fill::FillRule;
 pub use pathfinder_content::stroke::LineCap;
 pub use pathfinder_content::outline::ArcDirection;
 pub use pathfinder_geometry::rect::{RectF, RectI};
 pub use pathfinder_geometry::transform2d::Transform2F;
 pub use pathfinder_geometry::vector::{IntoVector2F, Vector2F, Vector2I, vec2f, vec2i};
+pub use pathfinder_renderer::accessibility::{AccessibilityNodeId, AccessibilityRole};
 
+use instant::Instant;
+use pathfinder_content::arc_length::ArcLengthStroke;
 use pathfinder_content::dash::OutlineDash;
 use pathfinder_content::effects::{BlendMode, BlurDirection, PatternFilter};
 use pathfinder_content::gradient::Gradient;
+use pathfinder_content::orientation::Orientation;
 use pathfinder_content::outline::{Contour, Outline};
 use pathfinder_content::pattern::{Image, Pattern};
 use pathfinder_content::render_target::RenderTargetId;
@@ -51,6 +56,10 @@ pub use text::TextMetrics;
 const HAIRLINE_STROKE_WIDTH: f32 = 0.0333;
 const DEFAULT_FONT_SIZE: f32 = 10.0;
 
+// The maximum distance, in device pixels, between curves and the lines that replace them when
+// stroking with arc length gradients.
+const ARC_LENGTH_FLATTENING_TOLERANCE: f32 = 0.25;
+
 #[cfg(feature = "pf-text")]
 mod text;
 
@@ -69,6 +78,9 @@ mod text {
     pub struct FontCollection;
 }
 
+#[cfg(feature = "pf-recording")]
+pub mod recording;
+
 #[cfg(test)]
 mod tests;
 
@@ -170,6 +182,23 @@ impl CanvasRenderingContext2D {
         drop(self.canvas.take_scene())
     }
 
+    /// Starts tagging what's drawn with what it means, for assistive technology, until the
+    /// matching `pop_accessibility_node()`.
+    ///
+    /// Text drawn from strings is tagged as text on its own, so this is mostly for shapes and for
+    /// controls that contain text. See `Scene::accessibility_tree()`.
+    #[inline]
+    pub fn push_accessibility_node(&mut self, role: AccessibilityRole, label: String)
+                                   -> AccessibilityNodeId {
+        self.canvas.scene.push_accessibility_node(role, label)
+    }
+
+    /// Ends the accessibility node started by the most recent `push_accessibility_node()`.
+    #[inline]
+    pub fn pop_accessibility_node(&mut self) {
+        self.canvas.scene.pop_accessibility_node()
+    }
+
     // Drawing rectangles
 
     #[inline]
@@ -234,6 +263,18 @@ impl CanvasRenderingContext2D {
         self.current_state.line_join = new_line_join
     }
 
+    #[inline]
+    pub fn stroke_alignment(&self) -> StrokeAlignment {
+        self.current_state.stroke_alignment
+    }
+
+    /// Sets where strokes are drawn relative to the path: centered on it, as in the HTML canvas,
+    /// or entirely inside or outside it, as in design tools.
+    #[inline]
+    pub fn set_stroke_alignment(&mut self, new_stroke_alignment: StrokeAlignment) {
+        self.current_state.stroke_alignment = new_stroke_alignment
+    }
+
     #[inline]
     pub fn miter_limit(&self) -> f32 {
         self.current_state.miter_limit
@@ -271,6 +312,31 @@ impl CanvasRenderingContext2D {
         self.current_state.line_dash_offset = new_line_dash_offset
     }
 
+    #[inline]
+    pub fn line_dash_direction(&self) -> DashDirection {
+        self.current_state.line_dash_direction
+    }
+
+    /// Sets which way line dashes run around closed subpaths, whichever way they were drawn.
+    ///
+    /// Increasing the line dash offset then moves the dashes the same way around every closed
+    /// subpath, as for animated selection outlines.
+    #[inline]
+    pub fn set_line_dash_direction(&mut self, new_line_dash_direction: DashDirection) {
+        self.current_state.line_dash_direction = new_line_dash_direction
+    }
+
+    #[inline]
+    pub fn line_trim(&self) -> Option<LineTrim> {
+        self.current_state.line_trim
+    }
+
+    /// Sets the part of each stroked path that's drawn, or `None` to draw all of it.
+    #[inline]
+    pub fn set_line_trim(&mut self, new_line_trim: Option<LineTrim>) {
+        self.current_state.line_trim = new_line_trim
+    }
+
     // Fill and stroke styles
 
     #[inline]
@@ -315,6 +381,73 @@ impl CanvasRenderingContext2D {
         self.current_state.shadow_offset = new_shadow_offset;
     }
 
+    /// Draws only the shadow that `fill_path()` would cast, without filling the path itself, for
+    /// elevation shadows under surfaces rendered elsewhere.
+    ///
+    /// As with CSS `box-shadow`, the shadow is cut out from inside the path, so it doesn't show
+    /// through translucent surfaces. The shadow takes its opacity from the fill style.
+    pub fn shadow_path(&mut self, path: Path2D, fill_rule: FillRule) {
+        if self.current_state.shadow_color.is_fully_transparent() {
+            return;
+        }
+
+        let mut outline = path.into_outline();
+        outline.transform(&self.current_state.transform);
+
+        let mut knockout = ClipPath::new(outline.clone());
+        knockout.set_fill_rule(fill_rule);
+        knockout.set_inverted(true);
+        knockout.set_clip_path(self.current_state.clip_path);
+        let knockout_id = self.canvas.scene.push_clip_path(knockout);
+
+        let paint = self.current_state.resolve_paint(&self.current_state.fill_paint);
+        push_shadow(&mut self.canvas.scene,
+                    &self.current_state,
+                    &outline,
+                    &paint,
+                    fill_rule,
+                    Some(knockout_id));
+    }
+
+    /// Draws the shadow that the current shadow settings cast inside `path`, as though the path
+    /// were a hole cut into a surface, for inset and neumorphic styles.
+    ///
+    /// Call this after filling the path. The shadow falls inward from the edges opposite the
+    /// shadow offset and is clipped to the inside of the path.
+    pub fn inner_shadow_path(&mut self, path: Path2D, fill_rule: FillRule) {
+        if self.current_state.shadow_color.is_fully_transparent() {
+            return;
+        }
+
+        let mut outline = path.into_outline();
+        outline.transform(&self.current_state.transform);
+
+        // The shadow is cast by everything around the path, out to where the offset and blur can
+        // no longer carry it inside.
+        let margin = self.current_state.shadow_offset.length() +
+            self.current_state.shadow_blur * 1.5 + 1.0;
+        let mut surround = Contour::from_rect(outline.bounds().dilate(margin));
+        if fill_rule == FillRule::Winding &&
+                Orientation::from_outline(&outline) == Orientation::from_contour(&surround) {
+            surround.reverse();
+        }
+        let mut caster = outline.clone();
+        caster.push_contour(surround);
+
+        let mut clip_path = ClipPath::new(outline);
+        clip_path.set_fill_rule(fill_rule);
+        clip_path.set_clip_path(self.current_state.clip_path);
+        let clip_path_id = self.canvas.scene.push_clip_path(clip_path);
+
+        let paint = self.current_state.resolve_paint(&Paint::black()).into_owned();
+        push_shadow(&mut self.canvas.scene,
+                    &self.current_state,
+                    &caster,
+                    &paint,
+                    fill_rule,
+                    Some(clip_path_id));
+    }
+
     // Drawing paths
 
     #[inline]
@@ -322,9 +455,176 @@ impl CanvasRenderingContext2D {
         self.push_path(path.into_outline(), PathOp::Fill, fill_rule);
     }
 
+    /// Strokes a path with the current stroke style, line trim, and line dash.
+    ///
+    /// Simple dash patterns on open paths, with butt caps and a solid color, are evaluated per
+    /// pixel from the distance along the path instead of splitting it into dashes, so changing the
+    /// dash offset, as for marching ants, is cheap. Such strokes always use round joins. Without a
+    /// line dash, the line trim is evaluated the same way, so a path can be animated drawing
+    /// itself.
     #[inline]
     pub fn stroke_path(&mut self, path: Path2D) {
+        let start_time = Instant::now();
+        let saved_clip_path = self.push_stroke_alignment_clip(&path);
+        let stroke_style = self.stroke_style_for_stroking();
+        let trimmed = self.trim_outline(path.into_outline());
+        let (mut outline, line_dash, line_dash_offset) = match trimmed {
+            Some(trimmed) => trimmed,
+            None => {
+                self.current_state.clip_path = saved_clip_path;
+                self.canvas.scene.add_stroke_time(Instant::now() - start_time);
+                return;
+            }
+        };
+        if self.can_dash_in_shader(&outline, &stroke_style, &line_dash) {
+            self.stroke_with_dash_gradients(&outline, &stroke_style, &line_dash, line_dash_offset);
+        } else {
+            let line_dash_direction = self.current_state.line_dash_direction;
+            outline = dash_outline(outline, &line_dash, line_dash_offset, line_dash_direction);
+            let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, stroke_style);
+            stroke_to_fill.offset();
+            outline = stroke_to_fill.into_outline();
+            self.push_path(outline, PathOp::Stroke, FillRule::Winding);
+        }
+        self.current_state.clip_path = saved_clip_path;
+        self.canvas.scene.add_stroke_time(Instant::now() - start_time);
+    }
+
+    /// Strokes a path with colors that follow the distance along it, rather than position, as for
+    /// tracks colored by speed or progress rings.
+    ///
+    /// The stop offsets of `ramp` are fractions of the total length of the path, and its geometry
+    /// is ignored. The path is split into pieces that always meet with round joins.
+    pub fn stroke_path_with_arc_length_gradient(&mut self, path: Path2D, ramp: &Gradient) {
+        let start_time = Instant::now();
+        let saved_clip_path = self.push_stroke_alignment_clip(&path);
+        let stroke_style = self.stroke_style_for_stroking();
+        let (outline, line_dash, line_dash_offset) = match self.trim_outline(path.into_outline()) {
+            Some(trimmed) => trimmed,
+            None => {
+                self.current_state.clip_path = saved_clip_path;
+                self.canvas.scene.add_stroke_time(Instant::now() - start_time);
+                return;
+            }
+        };
+        let line_dash_direction = self.current_state.line_dash_direction;
+        let outline = dash_outline(outline, &line_dash, line_dash_offset, line_dash_direction);
+        let stroke = ArcLengthStroke::new(&outline, &stroke_style, self.arc_length_tolerance());
+
+        let stroke_paint = self.current_state.stroke_paint.clone();
+        for piece in stroke.pieces() {
+            let gradient = piece.gradient(ramp, stroke.length());
+            self.current_state.stroke_paint = Paint::from_gradient(gradient);
+            self.push_path(piece.outline.clone(), PathOp::Stroke, FillRule::Winding);
+        }
+        self.current_state.stroke_paint = stroke_paint;
+        self.current_state.clip_path = saved_clip_path;
+        self.canvas.scene.add_stroke_time(Instant::now() - start_time);
+    }
+
+    // Clips to the side of `path` that the stroke alignment keeps, if any, and returns the clip to
+    // restore afterward. Strokes that aren't centered are drawn twice as wide, so that the half
+    // that's kept has the full line width.
+    fn push_stroke_alignment_clip(&mut self, path: &Path2D) -> Option<ClipPathId> {
+        let saved_clip_path = self.current_state.clip_path;
+        match self.current_state.stroke_alignment {
+            StrokeAlignment::Center => {}
+            StrokeAlignment::Inside => self.push_clip_path(path.clone(), FillRule::Winding, false),
+            StrokeAlignment::Outside => self.push_clip_path(path.clone(), FillRule::Winding, true),
+        }
+        saved_clip_path
+    }
+
+    // Strokes each straight piece of `outline` with a gradient that's transparent in the gaps of
+    // `line_dash`. Pieces that lie entirely in a gap are skipped, and pieces that lie entirely in
+    // a dash are drawn with the plain stroke color.
+    fn stroke_with_dash_gradients(&mut self,
+                                  outline: &Outline,
+                                  stroke_style: &StrokeStyle,
+                                  line_dash: &[f32],
+                                  line_dash_offset: f32) {
+        let stroke = ArcLengthStroke::new(outline, stroke_style, self.arc_length_tolerance());
+
+        let stroke_paint = self.current_state.stroke_paint.clone();
+        let base_color = stroke_paint.base_color();
+        for piece in stroke.pieces() {
+            let gradient = piece.dash_gradient(base_color, line_dash, line_dash_offset);
+            let stops = gradient.stops();
+            if stops.iter().all(|stop| stop.color == base_color) {
+                self.current_state.stroke_paint = stroke_paint.clone();
+            } else if stops.iter().all(|stop| stop.color.is_fully_transparent()) {
+                continue;
+            } else {
+                self.current_state.stroke_paint = Paint::from_gradient(gradient);
+            }
+            self.push_path(piece.outline.clone(), PathOp::Stroke, FillRule::Winding);
+        }
+        self.current_state.stroke_paint = stroke_paint;
+    }
+
+    // Whether `line_dash` can be applied to `outline` in the shaders. Dash gradients cut dashes
+    // square, and their pieces meet with round joins, so other caps and joins need the dashes to
+    // be split out geometrically. Closed paths are left to `OutlineDash` too.
+    fn can_dash_in_shader(&self, outline: &Outline, stroke_style: &StrokeStyle, line_dash: &[f32])
+                          -> bool {
+        let has_corners = outline.contours().iter().any(|contour| contour.len() > 2);
+        !line_dash.is_empty() &&
+            line_dash.iter().all(|&dash| dash.is_finite() && dash >= 0.0) &&
+            line_dash.iter().sum::<f32>() > 0.0 &&
+            self.current_state.stroke_paint.is_color() &&
+            stroke_style.line_cap == LineCap::Butt &&
+            (stroke_style.line_join == StrokeLineJoin::Round || !has_corners) &&
+            outline.contours().iter().all(|contour| !contour.is_closed())
+    }
+
+    // The maximum distance, in scene units, between curves and the lines that replace them when
+    // stroking pieces by arc length, so that curves look smooth after the current transform.
+    fn arc_length_tolerance(&self) -> f32 {
+        let transform_scales = self.current_state.transform.extract_scale();
+        let transform_scale = f32::max(transform_scales.x(), transform_scales.y());
+        ARC_LENGTH_FLATTENING_TOLERANCE / transform_scale.max(f32::EPSILON)
+    }
+
+    // Applies the current line trim to an outline to be stroked, returning the outline along with
+    // the line dash and dash offset to stroke it with, or `None` if nothing is left to draw.
+    //
+    // A trim keeps one stretch of the path per its length, so it's a line dash with a single dash.
+    // Without a real line dash, that dash is returned to be applied like one, and in the shaders
+    // if possible. Otherwise, the outline is trimmed here and the real line dash is returned.
+    fn trim_outline(&self, outline: Outline) -> Option<(Outline, Vec<f32>, f32)> {
+        let line_dash = self.current_state.line_dash.clone();
+        let line_dash_offset = self.current_state.line_dash_offset;
+        let line_trim = match self.current_state.line_trim {
+            None => return Some((outline, line_dash, line_dash_offset)),
+            Some(line_trim) => line_trim,
+        };
+
+        let (start, end) = (line_trim.start.max(0.0).min(1.0), line_trim.end.max(0.0).min(1.0));
+        let (start, end) = (f32::min(start, end), f32::max(start, end));
+        let length = outline.arc_length();
+        if !(end > start) {
+            return None;
+        }
+        if end - start >= 1.0 || !(length > 0.0) || !line_trim.offset.is_finite() {
+            return Some((outline, line_dash, line_dash_offset));
+        }
+
+        let trim_dash = vec![(end - start) * length, (1.0 - (end - start)) * length];
+        let trim_offset = (-(start + line_trim.offset) * length).rem_euclid(length);
+        if line_dash.is_empty() {
+            return Some((outline, trim_dash, trim_offset));
+        }
+        let trimmed = dash_outline(outline, &trim_dash, trim_offset, DashDirection::Path);
+        Some((trimmed, line_dash, line_dash_offset))
+    }
+
+    // Returns the stroke style to stroke paths with, adjusted for the stroke alignment and the
+    // minimum line width.
+    fn stroke_style_for_stroking(&self) -> StrokeStyle {
         let mut stroke_style = self.current_state.resolve_stroke_style();
+        if self.current_state.stroke_alignment != StrokeAlignment::Center {
+            stroke_style.line_width *= 2.0;
+        }
 
         // The smaller scale is relevant here, as we multiply by it and want to ensure it is always
         // bigger than `HAIRLINE_STROKE_WIDTH`.
@@ -336,28 +636,27 @@ impl CanvasRenderingContext2D {
             stroke_style.line_width = HAIRLINE_STROKE_WIDTH / transform_scale;
         }
 
-        let mut outline = path.into_outline();
-        if !self.current_state.line_dash.is_empty() {
-            let mut dash = OutlineDash::new(&outline,
-                                            &self.current_state.line_dash,
-                                            self.current_state.line_dash_offset);
-            dash.dash();
-            outline = dash.into_outline();
-        }
+        stroke_style
+    }
 
-        let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, stroke_style);
-        stroke_to_fill.offset();
-        outline = stroke_to_fill.into_outline();
+    pub fn clip_path(&mut self, path: Path2D, fill_rule: FillRule) {
+        self.push_clip_path(path, fill_rule, false)
+    }
 
-        self.push_path(outline, PathOp::Stroke, FillRule::Winding);
+    /// Restricts drawing to everything outside `path`, the inverse of `clip_path()`.
+    ///
+    /// Like `clip_path()`, this intersects with the current clip, so it can cut several holes.
+    pub fn clip_out_path(&mut self, path: Path2D, fill_rule: FillRule) {
+        self.push_clip_path(path, fill_rule, true)
     }
 
-    pub fn clip_path(&mut self, path: Path2D, fill_rule: FillRule) {
+    fn push_clip_path(&mut self, path: Path2D, fill_rule: FillRule, inverted: bool) {
         let mut outline = path.into_outline();
         outline.transform(&self.current_state.transform);
 
         let mut clip_path = ClipPath::new(outline);
         clip_path.set_fill_rule(fill_rule);
+        clip_path.set_inverted(inverted);
         if let Some(existing_clip_path) = self.current_state.clip_path.take() {
             clip_path.set_clip_path(Some(existing_clip_path));
         }
@@ -380,108 +679,20 @@ impl CanvasRenderingContext2D {
         outline.transform(&transform);
 
         if !self.current_state.shadow_color.is_fully_transparent() {
-            let mut outline = outline.clone();
-            outline.transform(&Transform2F::from_translation(self.current_state.shadow_offset));
-
-            let shadow_blur_info =
-                push_shadow_blur_render_targets_if_needed(&mut self.canvas.scene,
-                                                          &self.current_state,
-                                                          outline.bounds());
-
-            if let Some(ref shadow_blur_info) = shadow_blur_info {
-                outline.transform(&Transform2F::from_translation(-shadow_blur_info.bounds
-                                                                                  .origin()
-                                                                                  .to_f32()));
-            }
-
-            // Per spec the shadow must respect the alpha of the shadowed path, but otherwise have
-            // the color of the shadow paint.
-            let mut shadow_paint = (*paint).clone();
-            let shadow_base_alpha = shadow_paint.base_color().a;
-            let mut shadow_color = self.current_state.shadow_color.to_f32();
-            shadow_color.set_a(shadow_color.a() * shadow_base_alpha as f32 / 255.0);
-            shadow_paint.set_base_color(shadow_color.to_u8());
-            if let &mut Some(ref mut shadow_paint_overlay) = shadow_paint.overlay_mut() {
-                shadow_paint_overlay.set_composite_op(PaintCompositeOp::DestIn);
-            }
-            let shadow_paint_id = self.canvas.scene.push_paint(&shadow_paint);
-
-            let mut path = DrawPath::new(outline, shadow_paint_id);
-            if shadow_blur_info.is_none() {
-                path.set_clip_path(clip_path);
-            }
-            path.set_fill_rule(fill_rule);
-            path.set_blend_mode(blend_mode);
-            self.canvas.scene.push_draw_path(path);
-
-            composite_shadow_blur_render_targets_if_needed(&mut self.canvas.scene,
-                                                           shadow_blur_info,
-                                                           clip_path);
+            push_shadow(&mut self.canvas.scene,
+                        &self.current_state,
+                        &outline,
+                        &paint,
+                        fill_rule,
+                        clip_path);
         }
 
         let mut path = DrawPath::new(outline, paint_id);
         path.set_clip_path(clip_path);
         path.set_fill_rule(fill_rule);
         path.set_blend_mode(blend_mode);
+        path.set_antialiased(self.current_state.antialiasing_enabled);
         self.canvas.scene.push_draw_path(path);
-
-        fn push_shadow_blur_render_targets_if_needed(scene: &mut Scene,
-                                                     current_state: &State,
-                                                     outline_bounds: RectF)
-                                                    -> Option<ShadowBlurRenderTargetInfo> {
-            if current_state.shadow_blur == 0.0 {
-                return None;
-            }
-
-            let sigma = current_state.shadow_blur * 0.5;
-            let bounds = outline_bounds.dilate(sigma * 3.0).round_out().to_i32();
-
-            let render_target_y = RenderTarget::new(bounds.size(), String::new());
-            let render_target_id_y = scene.push_render_target(render_target_y);
-            let render_target_x = RenderTarget::new(bounds.size(), String::new());
-            let render_target_id_x = scene.push_render_target(render_target_x);
-
-            Some(ShadowBlurRenderTargetInfo {
-                id_x: render_target_id_x,
-                id_y: render_target_id_y,
-                bounds,
-                sigma,
-            })
-        }
-
-        fn composite_shadow_blur_render_targets_if_needed(scene: &mut Scene,
-                                                          info: Option<ShadowBlurRenderTargetInfo>,
-                                                          clip_path: Option<ClipPathId>) {
-            let info = match info {
-                None => return,
-                Some(info) => info,
-            };
-
-            let mut paint_x = Pattern::from_render_target(info.id_x, info.bounds.size());
-            let mut paint_y = Pattern::from_render_target(info.id_y, info.bounds.size());
-            paint_y.apply_transform(Transform2F::from_translation(info.bounds.origin().to_f32()));
-
-            let sigma = info.sigma;
-            paint_x.set_filter(Some(PatternFilter::Blur { direction: BlurDirection::X, sigma }));
-            paint_y.set_filter(Some(PatternFilter::Blur { direction: BlurDirection::Y, sigma }));
-
-            let paint_id_x = scene.push_paint(&Paint::from_pattern(paint_x));
-            let paint_id_y = scene.push_paint(&Paint::from_pattern(paint_y));
-
-            // TODO(pcwalton): Apply clip as necessary.
-            let outline_x = Outline::from_rect(RectF::new(vec2f(0.0, 0.0),
-                                                        info.bounds.size().to_f32()));
-            let path_x = DrawPath::new(outline_x, paint_id_x);
-            let outline_y = Outline::from_rect(info.bounds.to_f32());
-            let mut path_y = DrawPath::new(outline_y, paint_id_y);
-            path_y.set_clip_path(clip_path);
-
-            scene.pop_render_target();
-            scene.push_draw_path(path_x);
-            scene.pop_render_target();
-            scene.push_draw_path(path_y);
-        }
-
     }
 
     // Transformations
@@ -596,6 +807,23 @@ impl CanvasRenderingContext2D {
         self.current_state.image_smoothing_quality = new_quality
     }
 
+    // Antialiasing
+
+    /// Returns true if fills and strokes are drawn with antialiased edges. This is an extension to
+    /// the HTML canvas API.
+    #[inline]
+    pub fn antialiasing_enabled(&self) -> bool {
+        self.current_state.antialiasing_enabled
+    }
+
+    /// Sets whether fills and strokes are drawn with antialiased edges. Turning antialiasing off
+    /// gives crisp, pixel-snapped edges, for pixel art and hairline grids. This is an extension to
+    /// the HTML canvas API.
+    #[inline]
+    pub fn set_antialiasing_enabled(&mut self, enabled: bool) {
+        self.current_state.antialiasing_enabled = enabled
+    }
+
     // The canvas state
 
     #[inline]
@@ -635,9 +863,12 @@ struct State {
     line_width: f32,
     line_cap: LineCap,
     line_join: LineJoin,
+    stroke_alignment: StrokeAlignment,
     miter_limit: f32,
     line_dash: Vec<f32>,
     line_dash_offset: f32,
+    line_dash_direction: DashDirection,
+    line_trim: Option<LineTrim>,
     fill_paint: Paint,
     stroke_paint: Paint,
     shadow_color: ColorU,
@@ -647,6 +878,7 @@ struct State {
     text_baseline: TextBaseline,
     image_smoothing_enabled: bool,
     image_smoothing_quality: ImageSmoothingQuality,
+    antialiasing_enabled: bool,
     global_alpha: f32,
     global_composite_operation: CompositeOperation,
     clip_path: Option<ClipPathId>,
@@ -661,9 +893,12 @@ impl State {
             line_width: 1.0,
             line_cap: LineCap::Butt,
             line_join: LineJoin::Miter,
+            stroke_alignment: StrokeAlignment::Center,
             miter_limit: 10.0,
             line_dash: vec![],
             line_dash_offset: 0.0,
+            line_dash_direction: DashDirection::Path,
+            line_trim: None,
             fill_paint: Paint::black(),
             stroke_paint: Paint::black(),
             shadow_color: ColorU::transparent_black(),
@@ -673,6 +908,7 @@ impl State {
             text_baseline: TextBaseline::Alphabetic,
             image_smoothing_enabled: true,
             image_smoothing_quality: ImageSmoothingQuality::Low,
+            antialiasing_enabled: true,
             global_alpha: 1.0,
             global_composite_operation: CompositeOperation::SourceOver,
             clip_path: None,
@@ -712,12 +948,14 @@ impl State {
                 LineJoin::Miter => StrokeLineJoin::Miter(self.miter_limit),
                 LineJoin::Bevel => StrokeLineJoin::Bevel,
                 LineJoin::Round => StrokeLineJoin::Round,
+                LineJoin::MiterClip => StrokeLineJoin::MiterClip(self.miter_limit),
+                LineJoin::Arcs => StrokeLineJoin::Arcs(self.miter_limit),
             },
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Path2D {
     outline: Outline,
     current_contour: Contour,
@@ -877,6 +1115,38 @@ pub enum LineJoin {
     Miter,
     Bevel,
     Round,
+    /// SVG 2's `miter-clip`, an extension: miters that exceed the limit are cut off at it.
+    MiterClip,
+    /// SVG 2's `arcs`, an extension: edges are extended by arcs following their curvature.
+    Arcs,
+}
+
+/// Where strokes are drawn relative to the path.
+///
+/// Inside and outside alignment clip a stroke of twice the line width to the path's interior or
+/// exterior under the nonzero fill rule, so they're meant for closed paths; open contours are
+/// treated as though they were closed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StrokeAlignment {
+    /// The stroke is centered on the path.
+    Center,
+    /// The stroke lies entirely inside the path.
+    Inside,
+    /// The stroke lies entirely outside the path.
+    Outside,
+}
+
+/// The part of a path that strokes draw, like Lottie's trim paths.
+///
+/// `start` and `end` are fractions of the total length of the path, and are clamped to 0 to 1 and
+/// swapped if reversed. `offset` shifts both of them along the path, wrapping around past the
+/// end, and is also a fraction of the length; animating it spins the drawn part around a closed
+/// path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineTrim {
+    pub start: f32,
+    pub end: f32,
+    pub offset: f32,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -1041,6 +1311,126 @@ impl From<Pattern> for FillStyle {
     }
 }
 
+// Applies a line dash to an outline to be stroked.
+fn dash_outline(outline: Outline,
+                line_dash: &[f32],
+                line_dash_offset: f32,
+                line_dash_direction: DashDirection)
+                -> Outline {
+    if line_dash.is_empty() {
+        return outline;
+    }
+    let mut dash = OutlineDash::new(&outline, line_dash, line_dash_offset);
+    dash.set_direction(line_dash_direction);
+    dash.dash();
+    dash.into_outline()
+}
+
+// Draws the shadow that filling `outline`, in canvas space, with `paint` casts.
+fn push_shadow(scene: &mut Scene,
+               current_state: &State,
+               outline: &Outline,
+               paint: &Paint,
+               fill_rule: FillRule,
+               clip_path: Option<ClipPathId>) {
+    let blend_mode = current_state.global_composite_operation.to_blend_mode();
+
+    let mut outline = (*outline).clone();
+    outline.transform(&Transform2F::from_translation(current_state.shadow_offset));
+
+    let shadow_blur_info =
+        push_shadow_blur_render_targets_if_needed(scene,
+                                                  current_state,
+                                                  outline.bounds());
+
+    if let Some(ref shadow_blur_info) = shadow_blur_info {
+        outline.transform(&Transform2F::from_translation(-shadow_blur_info.bounds
+                                                                          .origin()
+                                                                          .to_f32()));
+    }
+
+    // Per spec the shadow must respect the alpha of the shadowed path, but otherwise have
+    // the color of the shadow paint.
+    let mut shadow_paint = (*paint).clone();
+    let shadow_base_alpha = shadow_paint.base_color().a;
+    let mut shadow_color = current_state.shadow_color.to_f32();
+    shadow_color.set_a(shadow_color.a() * shadow_base_alpha as f32 / 255.0);
+    shadow_paint.set_base_color(shadow_color.to_u8());
+    if let &mut Some(ref mut shadow_paint_overlay) = shadow_paint.overlay_mut() {
+        shadow_paint_overlay.set_composite_op(PaintCompositeOp::DestIn);
+    }
+    let shadow_paint_id = scene.push_paint(&shadow_paint);
+
+    let mut path = DrawPath::new(outline, shadow_paint_id);
+    if shadow_blur_info.is_none() {
+        path.set_clip_path(clip_path);
+    }
+    path.set_fill_rule(fill_rule);
+    path.set_blend_mode(blend_mode);
+    scene.push_draw_path(path);
+
+    composite_shadow_blur_render_targets_if_needed(scene,
+                                                   shadow_blur_info,
+                                                   clip_path);
+
+    fn push_shadow_blur_render_targets_if_needed(scene: &mut Scene,
+                                                 current_state: &State,
+                                                 outline_bounds: RectF)
+                                                -> Option<ShadowBlurRenderTargetInfo> {
+        if current_state.shadow_blur == 0.0 {
+            return None;
+        }
+
+        let sigma = current_state.shadow_blur * 0.5;
+        let bounds = outline_bounds.dilate(sigma * 3.0).round_out().to_i32();
+
+        let render_target_y = RenderTarget::new(bounds.size(), String::new());
+        let render_target_id_y = scene.push_render_target(render_target_y);
+        let render_target_x = RenderTarget::new(bounds.size(), String::new());
+        let render_target_id_x = scene.push_render_target(render_target_x);
+
+        Some(ShadowBlurRenderTargetInfo {
+            id_x: render_target_id_x,
+            id_y: render_target_id_y,
+            bounds,
+            sigma,
+        })
+    }
+
+    fn composite_shadow_blur_render_targets_if_needed(scene: &mut Scene,
+                                                      info: Option<ShadowBlurRenderTargetInfo>,
+                                                      clip_path: Option<ClipPathId>) {
+        let info = match info {
+            None => return,
+            Some(info) => info,
+        };
+
+        let mut paint_x = Pattern::from_render_target(info.id_x, info.bounds.size());
+        let mut paint_y = Pattern::from_render_target(info.id_y, info.bounds.size());
+        paint_y.apply_transform(Transform2F::from_translation(info.bounds.origin().to_f32()));
+
+        let sigma = info.sigma;
+        paint_x.set_filter(Some(PatternFilter::Blur { direction: BlurDirection::X, sigma }));
+        paint_y.set_filter(Some(PatternFilter::Blur { direction: BlurDirection::Y, sigma }));
+
+        let paint_id_x = scene.push_paint(&Paint::from_pattern(paint_x));
+        let paint_id_y = scene.push_paint(&Paint::from_pattern(paint_y));
+
+        // TODO(pcwalton): Apply clip as necessary.
+        let outline_x = Outline::from_rect(RectF::new(vec2f(0.0, 0.0),
+                                                    info.bounds.size().to_f32()));
+        let path_x = DrawPath::new(outline_x, paint_id_x);
+        let outline_y = Outline::from_rect(info.bounds.to_f32());
+        let mut path_y = DrawPath::new(outline_y, paint_id_y);
+        path_y.set_clip_path(clip_path);
+
+        scene.pop_render_target();
+        scene.push_draw_path(path_x);
+        scene.pop_render_target();
+        scene.push_draw_path(path_y);
+    }
+}
+
 struct ShadowBlurRenderTargetInfo {
     id_x: RenderTargetId,
     id_y: RenderTargetId,
@@ -0,0 +1,1508 @@
+// pathfinder/canvas/src/recording.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Replays recordings of browser `CanvasRenderingContext2D` calls, for comparing Pathfinder's
+//! output against a browser's.
+//!
+//! A recording is a JSON object holding the canvas size and the commands in the order they were
+//! issued:
+//!
+//! ```json
+//! {
+//!     "width": 300,
+//!     "height": 150,
+//!     "commands": [
+//!         { "set": "fillStyle", "value": "rebeccapurple" },
+//!         { "call": "fillRect", "args": [10, 10, 50, 50] },
+//!         { "call": "createLinearGradient", "args": [0, 0, 100, 0], "result": 1 },
+//!         { "call": "addColorStop", "target": 1, "args": [0, "white"] },
+//!         { "set": "strokeStyle", "value": { "object": 1 } },
+//!         { "call": "Path2D", "args": [], "result": 2 },
+//!         { "call": "arc", "target": 2, "args": [50, 50, 20, 0, 6.283185307179586] },
+//!         { "call": "stroke", "args": [{ "object": 2 }] }
+//!     ]
+//! }
+//! ```
+//!
+//! A `set` command assigns a property of the context. A `call` command calls a method with the
+//! given arguments: on the context, or on the object numbered `target` if present. Gradients,
+//! patterns, and `Path2D`s are numbered by the `result` of the call that created them and passed
+//! as `{ "object": id }`. `Path2D` constructors are recorded as calls named `Path2D`. Images and
+//! `ImageData` are passed as `{ "width": w, "height": h, "data": [r, g, b, a, ...] }`, and
+//! `DOMMatrix` arguments as `{ "a": a, "b": b, "c": c, "d": d, "e": e, "f": f }`. `JSON.stringify`
+//! turns non-finite numbers into `null`, so `null` arguments make a call do nothing, as the
+//! non-finite numbers would in the browser.
+//!
+//! The following wraps a context so that everything drawn with it is recorded into `commands`:
+//!
+//! ```js
+//! function recordCanvas(context, commands) {
+//!     const raw = new Map(), ids = new Map();
+//!     const encode = (value) => {
+//!         if (ids.has(value))
+//!             return { object: ids.get(value) };
+//!         if (value instanceof ImageData)
+//!             return { width: value.width, height: value.height, data: Array.from(value.data) };
+//!         if (value instanceof DOMMatrix) {
+//!             const { a, b, c, d, e, f } = value;
+//!             return { a, b, c, d, e, f };
+//!         }
+//!         return value;
+//!     };
+//!     const unwrap = (value) => raw.has(value) ? raw.get(value) : value;
+//!     const track = (object, id) => {
+//!         const proxy = wrap(object, id);
+//!         raw.set(proxy, object);
+//!         ids.set(proxy, id);
+//!         return proxy;
+//!     };
+//!     const wrap = (object, target) => new Proxy(object, {
+//!         get(object, name) {
+//!             const member = object[name];
+//!             if (typeof member != "function")
+//!                 return member;
+//!             return (...args) => {
+//!                 let result = member.apply(object, args.map(unwrap));
+//!                 const command = { call: name, args: args.map(encode) };
+//!                 if (target != null)
+//!                     command.target = target;
+//!                 if (result instanceof CanvasGradient || result instanceof CanvasPattern) {
+//!                     command.result = ids.size + 1;
+//!                     result = track(result, command.result);
+//!                 }
+//!                 commands.push(command);
+//!                 return result;
+//!             };
+//!         },
+//!         set(object, name, value) {
+//!             object[name] = unwrap(value);
+//!             commands.push({ set: name, value: encode(value) });
+//!             return true;
+//!         },
+//!     });
+//!     const NativePath2D = window.Path2D;
+//!     window.Path2D = function (...args) {
+//!         const id = ids.size + 1;
+//!         commands.push({ call: "Path2D", args: args.map(encode), result: id });
+//!         return track(new NativePath2D(...args.map(unwrap)), id);
+//!     };
+//!     return wrap(context, null);
+//! }
+//! ```
+//!
+//! Images drawn with `drawImage` must be converted to `ImageData` first, for example by drawing
+//! them to a scratch canvas and calling `getImageData`. Calls that only read state, like
+//! `measureText` and `getImageData`, are ignored. Calls and properties the player doesn't know
+//! about, like `drawFocusIfNeeded` and `filter`, are errors rather than being skipped, so that a
+//! comparison against the browser never silently passes over missing content.
+//!
+//! Text is drawn only with the `pf-text` feature. The font size is taken from `font`, but the
+//! family is ignored, so text is drawn in the context's default font.
+
+use crate::{ArcDirection, Canvas, CanvasFontContext, CanvasRenderingContext2D};
+use crate::{CompositeOperation, FillRule, FillStyle, ImageData, ImageSmoothingQuality};
+use crate::{LineCap, LineJoin, Path2D};
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::gradient::Gradient;
+use pathfinder_content::pattern::{Image, Pattern};
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f, vec2i};
+use pathfinder_simd::default::F32x2;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+use std::sync::Arc;
+
+#[cfg(feature = "pf-text")]
+use crate::{TextAlign, TextBaseline};
+
+/// A recording of calls made to a browser's 2D canvas context.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    /// The size of the canvas in pixels.
+    pub size: Vector2F,
+    /// The recorded commands, in order.
+    pub commands: Vec<Value>,
+}
+
+/// The ways loading or replaying a recording can fail.
+///
+/// The errors that come from replaying hold the index of the offending command.
+#[derive(Debug)]
+pub enum RecordingError {
+    /// The recording isn't valid JSON.
+    Json(serde_json::Error),
+    /// The recording isn't an object with a `commands` array.
+    InvalidRecording,
+    /// A command is neither a `set` nor a `call`.
+    InvalidCommand(usize),
+    /// A call had arguments that would make the browser throw.
+    InvalidArguments(usize),
+    /// A call to a method the player doesn't implement.
+    UnsupportedCall(usize, String),
+    /// An assignment to a property the player doesn't implement.
+    UnsupportedProperty(usize, String),
+    /// A command referred to an object that was never created, or is of the wrong kind.
+    UnknownObject(usize, u64),
+}
+
+impl Recording {
+    /// Parses a recording.
+    pub fn from_slice(data: &[u8]) -> Result<Recording, RecordingError> {
+        let value: Value = serde_json::from_slice(data).map_err(RecordingError::Json)?;
+        Recording::from_value(&value)
+    }
+
+    /// Converts an already-parsed JSON value. The size defaults to the browser's default canvas
+    /// size of 300×150 if it's missing.
+    pub fn from_value(value: &Value) -> Result<Recording, RecordingError> {
+        let commands = value.get("commands")
+                            .and_then(Value::as_array)
+                            .ok_or(RecordingError::InvalidRecording)?;
+        let width = value.get("width").and_then(Value::as_f64).unwrap_or(300.0);
+        let height = value.get("height").and_then(Value::as_f64).unwrap_or(150.0);
+        Ok(Recording { size: vec2f(width as f32, height as f32), commands: commands.clone() })
+    }
+
+    /// Replays the commands onto `context`.
+    ///
+    /// Replay stops at the first command that fails. Everything before it has been drawn.
+    pub fn replay(&self, context: &mut CanvasRenderingContext2D) -> Result<(), RecordingError> {
+        let mut player = Player {
+            path_transform: context.transform(),
+            context,
+            objects: HashMap::new(),
+            path: Path2D::new(),
+            index: 0,
+        };
+        for (index, command) in self.commands.iter().enumerate() {
+            player.index = index;
+            player.run(command)?;
+        }
+        Ok(())
+    }
+
+    /// Replays the commands onto a new canvas of the recorded size.
+    pub fn to_canvas(&self, font_context: CanvasFontContext) -> Result<Canvas, RecordingError> {
+        let mut context = Canvas::new(self.size).get_context_2d(font_context);
+        self.replay(&mut context)?;
+        Ok(context.into_canvas())
+    }
+}
+
+enum Object {
+    Gradient(Gradient),
+    Pattern(Pattern),
+    Path(Path2D),
+}
+
+struct Player<'a> {
+    context: &'a mut CanvasRenderingContext2D,
+    objects: HashMap<u64, Object>,
+    // The current default path, in the user space of `path_transform`.
+    //
+    // The browser transforms points as they're added to the current path, but `Path2D` keeps them
+    // untransformed until it's filled. So that the two agree, the path is moved into the new user
+    // space whenever the transform changes.
+    path: Path2D,
+    path_transform: Transform2F,
+    index: usize,
+}
+
+impl<'a> Player<'a> {
+    fn run(&mut self, command: &Value) -> Result<(), RecordingError> {
+        if let Some(name) = command.get("set").and_then(Value::as_str) {
+            return self.set(name, command.get("value").unwrap_or(&Value::Null));
+        }
+
+        let name = command.get("call")
+                          .and_then(Value::as_str)
+                          .ok_or(RecordingError::InvalidCommand(self.index))?;
+        let args = match command.get("args") {
+            None => &[][..],
+            Some(args) => {
+                args.as_array()
+                    .map(Vec::as_slice)
+                    .ok_or(RecordingError::InvalidCommand(self.index))?
+            }
+        };
+        let result = command.get("result").and_then(Value::as_u64);
+        match command.get("target") {
+            None => self.call(name, args, result),
+            Some(target) => {
+                let id = target.as_u64().ok_or(RecordingError::InvalidCommand(self.index))?;
+                self.call_on_object(id, name, args)
+            }
+        }
+    }
+
+    // Assigning an invalid value to a property is ignored, as it is in the browser.
+    fn set(&mut self, name: &str, value: &Value) -> Result<(), RecordingError> {
+        let number = value.as_f64().map(|number| number as f32);
+        match name {
+            "fillStyle" | "strokeStyle" => {
+                let style = match (value.as_str(), object_id(value)) {
+                    (Some(string), _) => parse_color(string).map(FillStyle::Color),
+                    (None, Some(id)) => Some(self.paint_object(id)?),
+                    (None, None) => None,
+                };
+                match style {
+                    None => {}
+                    Some(style) if name == "fillStyle" => self.context.set_fill_style(style),
+                    Some(style) => self.context.set_stroke_style(style),
+                }
+            }
+            "lineWidth" => {
+                if let Some(line_width) = number.filter(|&number| number > 0.0) {
+                    self.context.set_line_width(line_width);
+                }
+            }
+            "miterLimit" => {
+                if let Some(miter_limit) = number.filter(|&number| number > 0.0) {
+                    self.context.set_miter_limit(miter_limit);
+                }
+            }
+            "lineDashOffset" => {
+                if let Some(line_dash_offset) = number {
+                    self.context.set_line_dash_offset(line_dash_offset);
+                }
+            }
+            "lineCap" => {
+                match value.as_str() {
+                    Some("butt") => self.context.set_line_cap(LineCap::Butt),
+                    Some("round") => self.context.set_line_cap(LineCap::Round),
+                    Some("square") => self.context.set_line_cap(LineCap::Square),
+                    _ => {}
+                }
+            }
+            "lineJoin" => {
+                match value.as_str() {
+                    Some("miter") => self.context.set_line_join(LineJoin::Miter),
+                    Some("bevel") => self.context.set_line_join(LineJoin::Bevel),
+                    Some("round") => self.context.set_line_join(LineJoin::Round),
+                    _ => {}
+                }
+            }
+            "globalAlpha" => {
+                if let Some(alpha) = number.filter(|number| (0.0..=1.0).contains(number)) {
+                    self.context.set_global_alpha(alpha);
+                }
+            }
+            "globalCompositeOperation" => {
+                if let Some(operation) = value.as_str().and_then(parse_composite_operation) {
+                    self.context.set_global_composite_operation(operation);
+                }
+            }
+            "shadowBlur" => {
+                if let Some(shadow_blur) = number.filter(|&number| number >= 0.0) {
+                    self.context.set_shadow_blur(shadow_blur);
+                }
+            }
+            "shadowColor" => {
+                if let Some(color) = value.as_str().and_then(parse_color) {
+                    self.context.set_shadow_color(color);
+                }
+            }
+            "shadowOffsetX" | "shadowOffsetY" => {
+                if let Some(number) = number {
+                    let offset = self.context.shadow_offset();
+                    self.context.set_shadow_offset(if name == "shadowOffsetX" {
+                        vec2f(number, offset.y())
+                    } else {
+                        vec2f(offset.x(), number)
+                    });
+                }
+            }
+            "imageSmoothingEnabled" => {
+                if let Some(enabled) = value.as_bool() {
+                    self.context.set_image_smoothing_enabled(enabled);
+                }
+            }
+            "imageSmoothingQuality" => {
+                let quality = match value.as_str() {
+                    Some("low") => ImageSmoothingQuality::Low,
+                    Some("medium") => ImageSmoothingQuality::Medium,
+                    Some("high") => ImageSmoothingQuality::High,
+                    _ => return Ok(()),
+                };
+                self.context.set_image_smoothing_quality(quality);
+            }
+            _ => {
+                if !self.set_text_property(name, value) {
+                    return Err(RecordingError::UnsupportedProperty(self.index, name.to_owned()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, name: &str, args: &[Value], result: Option<u64>)
+            -> Result<(), RecordingError> {
+        match name {
+            "save" => self.context.save(),
+            "restore" => self.context.restore(),
+            "beginPath" => {
+                self.path = Path2D::new();
+                self.path_transform = self.context.transform();
+            }
+            "resetTransform" => self.context.reset_transform(),
+            "setTransform" if args.is_empty() => self.context.reset_transform(),
+            "setTransform" if args.len() == 1 => {
+                let transform = self.matrix(&args[0])?;
+                self.context.set_transform(&transform);
+            }
+            "setTransform" | "transform" => {
+                if let Some(numbers) = self.numbers(args, 6)? {
+                    let transform = Transform2F::row_major(numbers[0],
+                                                           numbers[2],
+                                                           numbers[4],
+                                                           numbers[1],
+                                                           numbers[3],
+                                                           numbers[5]);
+                    if name == "transform" {
+                        let transform = self.context.transform() * transform;
+                        self.context.set_transform(&transform);
+                    } else {
+                        self.context.set_transform(&transform);
+                    }
+                }
+            }
+            "translate" => {
+                if let Some(numbers) = self.numbers(args, 2)? {
+                    self.context.translate(vec2f(numbers[0], numbers[1]));
+                }
+            }
+            "scale" => {
+                if let Some(numbers) = self.numbers(args, 2)? {
+                    self.context.scale(vec2f(numbers[0], numbers[1]));
+                }
+            }
+            "rotate" => {
+                if let Some(numbers) = self.numbers(args, 1)? {
+                    self.context.rotate(numbers[0]);
+                }
+            }
+            "setLineDash" => {
+                let dashes = match args.first().and_then(Value::as_array) {
+                    None => return Err(RecordingError::InvalidArguments(self.index)),
+                    Some(dashes) => dashes,
+                };
+                let dashes: Option<Vec<f32>> = dashes.iter().map(|dash| {
+                    dash.as_f64().map(|dash| dash as f32).filter(|&dash| dash >= 0.0)
+                }).collect();
+                if let Some(dashes) = dashes {
+                    self.context.set_line_dash(dashes);
+                }
+            }
+            "fill" | "clip" => {
+                let (path, fill_rule) = match args.first().and_then(object_id) {
+                    Some(id) => (self.path_object(id)?.clone(), args.get(1)),
+                    None => (self.current_path().clone(), args.first()),
+                };
+                let fill_rule = match fill_rule.and_then(Value::as_str) {
+                    None | Some("nonzero") => FillRule::Winding,
+                    Some("evenodd") => FillRule::EvenOdd,
+                    Some(_) => return Err(RecordingError::InvalidArguments(self.index)),
+                };
+                if name == "fill" {
+                    self.context.fill_path(path, fill_rule);
+                } else {
+                    self.context.clip_path(path, fill_rule);
+                }
+            }
+            "stroke" => {
+                let path = match args.first().and_then(object_id) {
+                    Some(id) => self.path_object(id)?.clone(),
+                    None => self.current_path().clone(),
+                };
+                self.context.stroke_path(path);
+            }
+            "fillRect" | "strokeRect" | "clearRect" => {
+                if let Some(numbers) = self.numbers(args, 4)? {
+                    let rect = RectF::new(vec2f(numbers[0], numbers[1]),
+                                          vec2f(numbers[2], numbers[3]));
+                    match name {
+                        "fillRect" => self.context.fill_rect(rect),
+                        "strokeRect" => self.context.stroke_rect(rect),
+                        _ => self.context.clear_rect(rect),
+                    }
+                }
+            }
+            "createLinearGradient" => {
+                if let Some(numbers) = self.numbers(args, 4)? {
+                    let gradient = Gradient::linear_from_points(vec2f(numbers[0], numbers[1]),
+                                                                vec2f(numbers[2], numbers[3]));
+                    self.create_object(result, Object::Gradient(gradient));
+                }
+            }
+            "createRadialGradient" => {
+                if let Some(numbers) = self.numbers(args, 6)? {
+                    if numbers[2] < 0.0 || numbers[5] < 0.0 {
+                        return Err(RecordingError::InvalidArguments(self.index));
+                    }
+                    let line = LineSegment2F::new(vec2f(numbers[0], numbers[1]),
+                                                  vec2f(numbers[3], numbers[4]));
+                    let gradient = Gradient::radial(line, F32x2::new(numbers[2], numbers[5]));
+                    self.create_object(result, Object::Gradient(gradient));
+                }
+            }
+            "createPattern" => {
+                let image = self.image(args.first().unwrap_or(&Value::Null))?;
+                let (repeat_x, repeat_y) = match args.get(1).and_then(Value::as_str) {
+                    None | Some("") | Some("repeat") => (true, true),
+                    Some("repeat-x") => (true, false),
+                    Some("repeat-y") => (false, true),
+                    Some("no-repeat") => (false, false),
+                    Some(_) => return Err(RecordingError::InvalidArguments(self.index)),
+                };
+                let mut pattern = Pattern::from_image(image);
+                pattern.set_repeat_x(repeat_x);
+                pattern.set_repeat_y(repeat_y);
+                self.create_object(result, Object::Pattern(pattern));
+            }
+            "drawImage" => {
+                let pattern = Pattern::from_image(self.image(args.first()
+                                                                  .unwrap_or(&Value::Null))?);
+                match args.len() {
+                    3 => {
+                        if let Some(numbers) = self.numbers(&args[1..], 2)? {
+                            self.context.draw_image(pattern, vec2f(numbers[0], numbers[1]));
+                        }
+                    }
+                    5 => {
+                        if let Some(numbers) = self.numbers(&args[1..], 4)? {
+                            let dest = RectF::new(vec2f(numbers[0], numbers[1]),
+                                                  vec2f(numbers[2], numbers[3]));
+                            self.context.draw_image(pattern, dest);
+                        }
+                    }
+                    9 => {
+                        if let Some(numbers) = self.numbers(&args[1..], 8)? {
+                            let src = RectF::new(vec2f(numbers[0], numbers[1]),
+                                                 vec2f(numbers[2], numbers[3]));
+                            let dest = RectF::new(vec2f(numbers[4], numbers[5]),
+                                                  vec2f(numbers[6], numbers[7]));
+                            self.context.draw_subimage(pattern, src, dest);
+                        }
+                    }
+                    _ => return Err(RecordingError::InvalidArguments(self.index)),
+                }
+            }
+            "putImageData" => {
+                // The dirty rectangle arguments aren't supported.
+                if args.len() != 3 {
+                    return Err(RecordingError::UnsupportedCall(self.index, name.to_owned()));
+                }
+                let image = self.image(&args[0])?;
+                if let Some(numbers) = self.numbers(&args[1..], 2)? {
+                    let image_data = ImageData {
+                        data: (**image.pixels()).clone(),
+                        size: image.size(),
+                    };
+                    self.context.put_image_data(image_data, vec2f(numbers[0], numbers[1]));
+                }
+            }
+            "Path2D" => {
+                let path = match args.first() {
+                    None => Path2D::new(),
+                    Some(value) => {
+                        match (object_id(value), value.as_str()) {
+                            (Some(id), _) => self.path_object(id)?.clone(),
+                            (None, Some(data)) => parse_path_data(data),
+                            (None, None) => {
+                                return Err(RecordingError::InvalidArguments(self.index));
+                            }
+                        }
+                    }
+                };
+                self.create_object(result, Object::Path(path));
+            }
+            "getTransform" | "getLineDash" | "measureText" | "isPointInPath" |
+            "isPointInStroke" | "getImageData" | "createImageData" | "getContextAttributes" => {}
+            _ => {
+                let mut path = mem::replace(self.current_path(), Path2D::new());
+                let result = self.call_on_path(&mut path, name, args);
+                self.path = path;
+                if !result? && !self.call_text(name, args)? {
+                    return Err(RecordingError::UnsupportedCall(self.index, name.to_owned()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn call_on_object(&mut self, id: u64, name: &str, args: &[Value])
+                      -> Result<(), RecordingError> {
+        // Take the object out of the table while it's changed, so that other objects can be
+        // looked up.
+        let mut object = match self.objects.remove(&id) {
+            None => return Err(RecordingError::UnknownObject(self.index, id)),
+            Some(object) => object,
+        };
+        let result = self.call_on_removed_object(id, &mut object, name, args);
+        self.objects.insert(id, object);
+
+        if !result? {
+            return Err(RecordingError::UnsupportedCall(self.index, name.to_owned()));
+        }
+        Ok(())
+    }
+
+    // Returns false if `object` has no method called `name`.
+    fn call_on_removed_object(&self, id: u64, object: &mut Object, name: &str, args: &[Value])
+                              -> Result<bool, RecordingError> {
+        match *object {
+            Object::Path(ref mut path) if name == "addPath" => {
+                let transform = match args.get(1) {
+                    None => Transform2F::default(),
+                    Some(matrix) => self.matrix(matrix)?,
+                };
+                let other_path = match args.first().and_then(object_id) {
+                    Some(other_id) if other_id == id => path.clone(),
+                    Some(other_id) => self.path_object(other_id)?.clone(),
+                    None => return Err(RecordingError::InvalidArguments(self.index)),
+                };
+                path.add_path(other_path, &transform);
+            }
+            Object::Path(ref mut path) => return self.call_on_path(path, name, args),
+            Object::Gradient(ref mut gradient) if name == "addColorStop" => {
+                let offset = args.first().and_then(Value::as_f64).map(|offset| offset as f32);
+                let color = args.get(1).and_then(Value::as_str).and_then(parse_color);
+                match (offset, color) {
+                    (Some(offset), Some(color)) if (0.0..=1.0).contains(&offset) => {
+                        gradient.add_color_stop(color, offset);
+                    }
+                    _ => return Err(RecordingError::InvalidArguments(self.index)),
+                }
+            }
+            Object::Pattern(ref mut pattern) if name == "setTransform" => {
+                let transform = match args.first() {
+                    None => Transform2F::default(),
+                    Some(matrix) => self.matrix(matrix)?,
+                };
+                pattern.apply_transform(transform * pattern.transform().inverse());
+            }
+            Object::Gradient(_) | Object::Pattern(_) => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Returns false if `name` isn't a method of `Path2D`.
+    fn call_on_path(&self, path: &mut Path2D, name: &str, args: &[Value])
+                    -> Result<bool, RecordingError> {
+        match name {
+            "closePath" => path.close_path(),
+            "moveTo" => {
+                if let Some(numbers) = self.numbers(args, 2)? {
+                    path.move_to(vec2f(numbers[0], numbers[1]));
+                }
+            }
+            "lineTo" => {
+                if let Some(numbers) = self.numbers(args, 2)? {
+                    path.line_to(vec2f(numbers[0], numbers[1]));
+                }
+            }
+            "quadraticCurveTo" => {
+                if let Some(numbers) = self.numbers(args, 4)? {
+                    path.quadratic_curve_to(vec2f(numbers[0], numbers[1]),
+                                            vec2f(numbers[2], numbers[3]));
+                }
+            }
+            "bezierCurveTo" => {
+                if let Some(numbers) = self.numbers(args, 6)? {
+                    path.bezier_curve_to(vec2f(numbers[0], numbers[1]),
+                                         vec2f(numbers[2], numbers[3]),
+                                         vec2f(numbers[4], numbers[5]));
+                }
+            }
+            "arc" => {
+                if let Some(numbers) = self.numbers(args, 5)? {
+                    if numbers[2] < 0.0 {
+                        return Err(RecordingError::InvalidArguments(self.index));
+                    }
+                    let center = vec2f(numbers[0], numbers[1]);
+                    let (start_angle, end_angle) = (numbers[3], numbers[4]);
+                    let counterclockwise = args.get(5).and_then(Value::as_bool).unwrap_or(false);
+                    if counterclockwise && start_angle - end_angle >= 2.0 * PI {
+                        path.arc(center, numbers[2], 0.0, 2.0 * PI, ArcDirection::CW);
+                    } else if counterclockwise {
+                        path.arc(center, numbers[2], start_angle, end_angle, ArcDirection::CCW);
+                    } else {
+                        path.arc(center, numbers[2], start_angle, end_angle, ArcDirection::CW);
+                    }
+                }
+            }
+            "arcTo" => {
+                if let Some(numbers) = self.numbers(args, 5)? {
+                    if numbers[4] < 0.0 {
+                        return Err(RecordingError::InvalidArguments(self.index));
+                    }
+                    path.arc_to(vec2f(numbers[0], numbers[1]),
+                                vec2f(numbers[2], numbers[3]),
+                                numbers[4]);
+                }
+            }
+            "ellipse" => {
+                if let Some(numbers) = self.numbers(args, 7)? {
+                    if numbers[2] < 0.0 || numbers[3] < 0.0 {
+                        return Err(RecordingError::InvalidArguments(self.index));
+                    }
+                    let center = vec2f(numbers[0], numbers[1]);
+                    let axes = vec2f(numbers[2], numbers[3]);
+                    let counterclockwise = args.get(7).and_then(Value::as_bool).unwrap_or(false);
+                    if counterclockwise {
+                        // Mirroring the ellipse across its own X axis negates the angles and
+                        // reverses the direction of travel.
+                        path.ellipse(center,
+                                     axes * vec2f(1.0, -1.0),
+                                     numbers[4],
+                                     -numbers[5],
+                                     -numbers[6]);
+                    } else {
+                        path.ellipse(center, axes, numbers[4], numbers[5], numbers[6]);
+                    }
+                }
+            }
+            "rect" => {
+                if let Some(numbers) = self.numbers(args, 4)? {
+                    path.rect(RectF::new(vec2f(numbers[0], numbers[1]),
+                                         vec2f(numbers[2], numbers[3])));
+                }
+            }
+            "roundRect" => {
+                if let Some(numbers) = self.numbers(args, 4)? {
+                    if let Some(radii) = self.round_rect_radii(args.get(4))? {
+                        round_rect(path,
+                                   vec2f(numbers[0], numbers[1]),
+                                   vec2f(numbers[2], numbers[3]),
+                                   radii);
+                    }
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    #[cfg(feature = "pf-text")]
+    fn call_text(&mut self, name: &str, args: &[Value]) -> Result<bool, RecordingError> {
+        if name != "fillText" && name != "strokeText" {
+            return Ok(false);
+        }
+        let text = args.first()
+                       .and_then(Value::as_str)
+                       .ok_or(RecordingError::InvalidArguments(self.index))?;
+        // The maximum width argument isn't supported, and is ignored.
+        if let Some(numbers) = self.numbers(&args[1..], 2)? {
+            let position = vec2f(numbers[0], numbers[1]);
+            if name == "fillText" {
+                self.context.fill_text(text, position);
+            } else {
+                self.context.stroke_text(text, position);
+            }
+        }
+        Ok(true)
+    }
+
+    #[cfg(not(feature = "pf-text"))]
+    fn call_text(&mut self, _: &str, _: &[Value]) -> Result<bool, RecordingError> {
+        Ok(false)
+    }
+
+    #[cfg(feature = "pf-text")]
+    fn set_text_property(&mut self, name: &str, value: &Value) -> bool {
+        match (name, value.as_str()) {
+            ("font", Some(font)) => {
+                if let Some(font_size) = parse_font_size(font) {
+                    self.context.set_font_size(font_size);
+                }
+            }
+            ("textAlign", Some("left")) | ("textAlign", Some("start")) => {
+                self.context.set_text_align(TextAlign::Left)
+            }
+            ("textAlign", Some("right")) | ("textAlign", Some("end")) => {
+                self.context.set_text_align(TextAlign::Right)
+            }
+            ("textAlign", Some("center")) => self.context.set_text_align(TextAlign::Center),
+            ("textBaseline", Some(baseline)) => {
+                let baseline = match baseline {
+                    "alphabetic" => TextBaseline::Alphabetic,
+                    "top" => TextBaseline::Top,
+                    "hanging" => TextBaseline::Hanging,
+                    "middle" => TextBaseline::Middle,
+                    "ideographic" => TextBaseline::Ideographic,
+                    "bottom" => TextBaseline::Bottom,
+                    _ => return true,
+                };
+                self.context.set_text_baseline(baseline);
+            }
+            ("font", _) | ("textAlign", _) => {}
+            _ => return false,
+        }
+        true
+    }
+
+    // Without text support, the text properties are accepted and ignored, and drawing text fails.
+    #[cfg(not(feature = "pf-text"))]
+    fn set_text_property(&mut self, name: &str, _: &Value) -> bool {
+        name == "font" || name == "textAlign" || name == "textBaseline"
+    }
+
+    // Moves the current path into the current user space and returns it.
+    //
+    // If the transform isn't invertible, the path stays where it is until the transform becomes
+    // invertible again.
+    fn current_path(&mut self) -> &mut Path2D {
+        let transform = self.context.transform();
+        if transform != self.path_transform && transform.matrix.det() != 0.0 {
+            let path = mem::replace(&mut self.path, Path2D::new());
+            self.path.add_path(path, &(transform.inverse() * self.path_transform));
+            self.path_transform = transform;
+        }
+        &mut self.path
+    }
+
+    fn create_object(&mut self, result: Option<u64>, object: Object) {
+        if let Some(id) = result {
+            self.objects.insert(id, object);
+        }
+    }
+
+    fn path_object(&self, id: u64) -> Result<&Path2D, RecordingError> {
+        match self.objects.get(&id) {
+            Some(Object::Path(path)) => Ok(path),
+            _ => Err(RecordingError::UnknownObject(self.index, id)),
+        }
+    }
+
+    fn paint_object(&self, id: u64) -> Result<FillStyle, RecordingError> {
+        match self.objects.get(&id) {
+            Some(Object::Gradient(gradient)) => Ok(FillStyle::Gradient(gradient.clone())),
+            Some(Object::Pattern(pattern)) => Ok(FillStyle::Pattern(pattern.clone())),
+            _ => Err(RecordingError::UnknownObject(self.index, id)),
+        }
+    }
+
+    // Reads the first `count` arguments as numbers. Returns `None` if any of them are `null`.
+    fn numbers(&self, args: &[Value], count: usize) -> Result<Option<Vec<f32>>, RecordingError> {
+        if args.len() < count {
+            return Err(RecordingError::InvalidArguments(self.index));
+        }
+        let mut numbers = Vec::with_capacity(count);
+        for arg in &args[0..count] {
+            match *arg {
+                Value::Null => return Ok(None),
+                _ => {
+                    let number = arg.as_f64()
+                                    .ok_or(RecordingError::InvalidArguments(self.index))?;
+                    numbers.push(number as f32);
+                }
+            }
+        }
+        Ok(Some(numbers))
+    }
+
+    // Reads the radii argument of `roundRect`: a radius or a list of one to four of them, each
+    // either a number or a `DOMPointInit`. Returns the radii of the upper left, upper right, lower
+    // right, and lower left corners, or `None` if any of them are `null`.
+    fn round_rect_radii(&self, value: Option<&Value>)
+                        -> Result<Option<[Vector2F; 4]>, RecordingError> {
+        let values: Vec<&Value> = match value {
+            None => return Ok(Some([Vector2F::zero(); 4])),
+            Some(Value::Array(values)) => values.iter().collect(),
+            Some(value) => vec![value],
+        };
+        let mut radii = Vec::with_capacity(values.len());
+        for value in values {
+            let radius = match *value {
+                Value::Null => return Ok(None),
+                Value::Object(_) => {
+                    let member = |name: &str| {
+                        value.get(name).and_then(Value::as_f64).map_or(0.0, |number| number as f32)
+                    };
+                    vec2f(member("x"), member("y"))
+                }
+                _ => {
+                    let radius = value.as_f64()
+                                      .ok_or(RecordingError::InvalidArguments(self.index))?;
+                    Vector2F::splat(radius as f32)
+                }
+            };
+            if radius.x() < 0.0 || radius.y() < 0.0 {
+                return Err(RecordingError::InvalidArguments(self.index));
+            }
+            radii.push(radius);
+        }
+        match radii.len() {
+            1 => Ok(Some([radii[0]; 4])),
+            2 => Ok(Some([radii[0], radii[1], radii[0], radii[1]])),
+            3 => Ok(Some([radii[0], radii[1], radii[2], radii[1]])),
+            4 => Ok(Some([radii[0], radii[1], radii[2], radii[3]])),
+            _ => Err(RecordingError::InvalidArguments(self.index)),
+        }
+    }
+
+    // Reads a `DOMMatrix2DInit`. Missing members take their values from the identity matrix.
+    fn matrix(&self, value: &Value) -> Result<Transform2F, RecordingError> {
+        if !value.is_object() {
+            return Err(RecordingError::InvalidArguments(self.index));
+        }
+        let member = |name: &str, default: f32| {
+            value.get(name).and_then(Value::as_f64).map_or(default, |number| number as f32)
+        };
+        Ok(Transform2F::row_major(member("a", 1.0),
+                                  member("c", 0.0),
+                                  member("e", 0.0),
+                                  member("b", 0.0),
+                                  member("d", 1.0),
+                                  member("f", 0.0)))
+    }
+
+    fn image(&self, value: &Value) -> Result<Image, RecordingError> {
+        let width = value.get("width").and_then(Value::as_u64);
+        let height = value.get("height").and_then(Value::as_u64);
+        let data = value.get("data").and_then(Value::as_array);
+        let (width, height, data) = match (width, height, data) {
+            (Some(width), Some(height), Some(data)) if data.len() as u64 == width * height * 4 => {
+                (width, height, data)
+            }
+            _ => return Err(RecordingError::InvalidArguments(self.index)),
+        };
+
+        let mut pixels = Vec::with_capacity(data.len() / 4);
+        for pixel in data.chunks(4) {
+            let mut channels = [0; 4];
+            for (channel, value) in channels.iter_mut().zip(pixel) {
+                match value.as_u64() {
+                    Some(value) if value < 256 => *channel = value as u8,
+                    _ => return Err(RecordingError::InvalidArguments(self.index)),
+                }
+            }
+            pixels.push(ColorU::new(channels[0], channels[1], channels[2], channels[3]));
+        }
+        Ok(Image::new(vec2i(width as i32, height as i32), Arc::new(pixels)))
+    }
+}
+
+fn object_id(value: &Value) -> Option<u64> {
+    value.get("object").and_then(Value::as_u64)
+}
+
+// Adds a rounded rectangle to `path`, as `roundRect` does. `radii` holds the radii of the upper
+// left, upper right, lower right, and lower left corners.
+fn round_rect(path: &mut Path2D, origin: Vector2F, size: Vector2F, mut radii: [Vector2F; 4]) {
+    // Each corner is a quarter ellipse, approximated with a cubic Bézier curve.
+    const KAPPA: f32 = 0.552_284_8;
+
+    // A negative width or height mirrors the rectangle, and with it the corners and the direction
+    // the path goes around them.
+    let sign = vec2f(size.x().signum(), size.y().signum());
+    let (width, height) = (size.x().abs(), size.y().abs());
+    let point = |x: f32, y: f32| origin + vec2f(x, y) * sign;
+
+    // Scale the radii down so that the corners don't overlap.
+    let scale = (width / (radii[0].x() + radii[1].x()))
+        .min(height / (radii[1].y() + radii[2].y()))
+        .min(width / (radii[2].x() + radii[3].x()))
+        .min(height / (radii[3].y() + radii[0].y()));
+    if scale < 1.0 {
+        for radius in &mut radii {
+            *radius *= scale;
+        }
+    }
+
+    let corner = |path: &mut Path2D, from: Vector2F, corner: Vector2F, to: Vector2F| {
+        if from == corner || to == corner {
+            path.line_to(to);
+        } else {
+            path.bezier_curve_to(from.lerp(corner, KAPPA), to.lerp(corner, KAPPA), to);
+        }
+    };
+
+    let [upper_left, upper_right, lower_right, lower_left] = radii;
+    path.move_to(point(upper_left.x(), 0.0));
+    path.line_to(point(width - upper_right.x(), 0.0));
+    corner(path,
+           point(width - upper_right.x(), 0.0),
+           point(width, 0.0),
+           point(width, upper_right.y()));
+    path.line_to(point(width, height - lower_right.y()));
+    corner(path,
+           point(width, height - lower_right.y()),
+           point(width, height),
+           point(width - lower_right.x(), height));
+    path.line_to(point(lower_left.x(), height));
+    corner(path,
+           point(lower_left.x(), height),
+           point(0.0, height),
+           point(0.0, height - lower_left.y()));
+    path.line_to(point(0.0, upper_left.y()));
+    corner(path, point(0.0, upper_left.y()), point(0.0, 0.0), point(upper_left.x(), 0.0));
+    path.close_path();
+    path.move_to(origin);
+}
+
+// Builds a path from SVG path data, as the `Path2D` constructor does. Like the browser, this
+// keeps the segments before the first error in the data and drops the rest.
+fn parse_path_data(data: &str) -> Path2D {
+    let mut path = Path2D::new();
+    let mut parser = PathDataParser { data: data.as_bytes(), position: 0 };
+    let (mut current, mut start) = (Vector2F::zero(), Vector2F::zero());
+    // The control point of the previous segment, if it was a cubic or quadratic curve
+    // respectively, for the smooth curves that reflect it.
+    let (mut last_cubic_ctrl, mut last_quadratic_ctrl) = (None, None);
+    let mut command: Option<u8> = None;
+    let mut closed = false;
+
+    parser.skip_whitespace();
+    while !parser.at_end() {
+        // A command letter can be left out when it repeats, except that a repeated move is a
+        // line.
+        let letter = match (parser.command(), command) {
+            (Some(letter), _) => letter,
+            (None, Some(b'M')) if parser.at_number() => b'L',
+            (None, Some(b'm')) if parser.at_number() => b'l',
+            (None, Some(letter)) if parser.at_number() && !letter.eq_ignore_ascii_case(&b'z') => {
+                letter
+            }
+            (None, _) => break,
+        };
+        if command.is_none() && !letter.eq_ignore_ascii_case(&b'm') {
+            break;
+        }
+        let origin = if letter.is_ascii_lowercase() { current } else { Vector2F::zero() };
+
+        // After a close, the next segment starts a new subpath at the start of the old one.
+        if closed && !letter.eq_ignore_ascii_case(&b'm') && !letter.eq_ignore_ascii_case(&b'z') {
+            path.move_to(start);
+            closed = false;
+        }
+
+        let (mut cubic_ctrl, mut quadratic_ctrl) = (None, None);
+        match letter.to_ascii_uppercase() {
+            b'M' => {
+                let to = match parser.point() {
+                    None => break,
+                    Some(to) => origin + to,
+                };
+                path.move_to(to);
+                current = to;
+                start = to;
+                closed = false;
+            }
+            b'L' => {
+                let to = match parser.point() {
+                    None => break,
+                    Some(to) => origin + to,
+                };
+                path.line_to(to);
+                current = to;
+            }
+            b'H' => {
+                let to = match parser.number() {
+                    None => break,
+                    Some(x) => vec2f(origin.x() + x, current.y()),
+                };
+                path.line_to(to);
+                current = to;
+            }
+            b'V' => {
+                let to = match parser.number() {
+                    None => break,
+                    Some(y) => vec2f(current.x(), origin.y() + y),
+                };
+                path.line_to(to);
+                current = to;
+            }
+            b'C' | b'S' => {
+                let ctrl0 = if letter.eq_ignore_ascii_case(&b'c') {
+                    match parser.point() {
+                        None => break,
+                        Some(ctrl0) => origin + ctrl0,
+                    }
+                } else {
+                    last_cubic_ctrl.map_or(current, |ctrl: Vector2F| current * 2.0 - ctrl)
+                };
+                let (ctrl1, to) = match (parser.point(), parser.point()) {
+                    (Some(ctrl1), Some(to)) => (origin + ctrl1, origin + to),
+                    _ => break,
+                };
+                path.bezier_curve_to(ctrl0, ctrl1, to);
+                cubic_ctrl = Some(ctrl1);
+                current = to;
+            }
+            b'Q' | b'T' => {
+                let ctrl = if letter.eq_ignore_ascii_case(&b'q') {
+                    match parser.point() {
+                        None => break,
+                        Some(ctrl) => origin + ctrl,
+                    }
+                } else {
+                    last_quadratic_ctrl.map_or(current, |ctrl: Vector2F| current * 2.0 - ctrl)
+                };
+                let to = match parser.point() {
+                    None => break,
+                    Some(to) => origin + to,
+                };
+                path.quadratic_curve_to(ctrl, to);
+                quadratic_ctrl = Some(ctrl);
+                current = to;
+            }
+            b'A' => {
+                let arc =
+                    (parser.point(), parser.number(), parser.flag(), parser.flag(), parser.point());
+                let (radius, x_axis_rotation, large_arc, sweep, to) = match arc {
+                    (Some(radius), Some(rotation), Some(large_arc), Some(sweep), Some(to)) => {
+                        (radius.abs(), rotation, large_arc, sweep, origin + to)
+                    }
+                    _ => break,
+                };
+                if to == current {
+                    // An arc to the current point is left out entirely.
+                } else if radius.x() == 0.0 || radius.y() == 0.0 {
+                    path.line_to(to);
+                } else {
+                    // `Path2D` has no method for SVG arcs, so this goes to the contour directly.
+                    let direction = if sweep { ArcDirection::CW } else { ArcDirection::CCW };
+                    path.current_contour.push_svg_arc(radius,
+                                                      x_axis_rotation.to_radians(),
+                                                      large_arc,
+                                                      direction,
+                                                      to);
+                }
+                current = to;
+            }
+            b'Z' => {
+                path.close_path();
+                current = start;
+                closed = true;
+            }
+            _ => break,
+        }
+
+        last_cubic_ctrl = cubic_ctrl;
+        last_quadratic_ctrl = quadratic_ctrl;
+        command = Some(letter);
+    }
+    path
+}
+
+// Reads the tokens of SVG path data.
+struct PathDataParser<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PathDataParser<'a> {
+    fn at_end(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.position).cloned()
+    }
+
+    fn at_number(&self) -> bool {
+        match self.peek() {
+            Some(byte) => byte.is_ascii_digit() || byte == b'+' || byte == b'-' || byte == b'.',
+            None => false,
+        }
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        let letter = self.peek().filter(|letter| b"MmLlHhVvCcSsQqTtAaZz".contains(letter))?;
+        self.position += 1;
+        self.skip_whitespace();
+        Some(letter)
+    }
+
+    fn point(&mut self) -> Option<Vector2F> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Some(vec2f(x, y))
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        let start = self.position;
+        if let Some(b'+') | Some(b'-') = self.peek() {
+            self.position += 1;
+        }
+        let mut digit_count = self.skip_digits();
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            digit_count += self.skip_digits();
+        }
+        if digit_count == 0 {
+            self.position = start;
+            return None;
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            let mantissa_end = self.position;
+            self.position += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.position += 1;
+            }
+            if self.skip_digits() == 0 {
+                self.position = mantissa_end;
+            }
+        }
+        let number = std::str::from_utf8(&self.data[start..self.position]).ok()?.parse().ok()?;
+        self.skip_separator();
+        Some(number)
+    }
+
+    // Arc flags are single digits, which needn't be separated from what follows them.
+    fn flag(&mut self) -> Option<bool> {
+        let flag = match self.peek()? {
+            b'0' => false,
+            b'1' => true,
+            _ => return None,
+        };
+        self.position += 1;
+        self.skip_separator();
+        Some(flag)
+    }
+
+    fn skip_digits(&mut self) -> usize {
+        let start = self.position;
+        while matches!(self.peek(), Some(byte) if byte.is_ascii_digit()) {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    fn skip_separator(&mut self) {
+        self.skip_whitespace();
+        if self.peek() == Some(b',') {
+            self.position += 1;
+            self.skip_whitespace();
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') | Some(b'\x0c') =
+                self.peek() {
+            self.position += 1;
+        }
+    }
+}
+
+fn parse_composite_operation(name: &str) -> Option<CompositeOperation> {
+    match name {
+        "source-over" => Some(CompositeOperation::SourceOver),
+        "source-in" => Some(CompositeOperation::SourceIn),
+        "source-out" => Some(CompositeOperation::SourceOut),
+        "source-atop" => Some(CompositeOperation::SourceAtop),
+        "destination-over" => Some(CompositeOperation::DestinationOver),
+        "destination-in" => Some(CompositeOperation::DestinationIn),
+        "destination-out" => Some(CompositeOperation::DestinationOut),
+        "destination-atop" => Some(CompositeOperation::DestinationAtop),
+        "lighter" => Some(CompositeOperation::Lighter),
+        "copy" => Some(CompositeOperation::Copy),
+        "xor" => Some(CompositeOperation::Xor),
+        "multiply" => Some(CompositeOperation::Multiply),
+        "screen" => Some(CompositeOperation::Screen),
+        "overlay" => Some(CompositeOperation::Overlay),
+        "darken" => Some(CompositeOperation::Darken),
+        "lighten" => Some(CompositeOperation::Lighten),
+        "color-dodge" => Some(CompositeOperation::ColorDodge),
+        "color-burn" => Some(CompositeOperation::ColorBurn),
+        "hard-light" => Some(CompositeOperation::HardLight),
+        "soft-light" => Some(CompositeOperation::SoftLight),
+        "difference" => Some(CompositeOperation::Difference),
+        "exclusion" => Some(CompositeOperation::Exclusion),
+        "hue" => Some(CompositeOperation::Hue),
+        "saturation" => Some(CompositeOperation::Saturation),
+        "color" => Some(CompositeOperation::Color),
+        "luminosity" => Some(CompositeOperation::Luminosity),
+        _ => None,
+    }
+}
+
+// Finds the size in a CSS `font` shorthand, like the `12px` in `bold 12px/1.5 sans-serif`.
+#[cfg(feature = "pf-text")]
+fn parse_font_size(font: &str) -> Option<f32> {
+    font.split_whitespace().filter_map(|token| {
+        let size = token.split('/').next().unwrap_or(token);
+        if size.ends_with("px") {
+            size[..size.len() - 2].parse::<f32>().ok()
+        } else if size.ends_with("pt") {
+            size[..size.len() - 2].parse::<f32>().ok().map(|size| size * 4.0 / 3.0)
+        } else {
+            None
+        }
+    }).next()
+}
+
+// Parses a CSS color: hex notation, `rgb()`, `rgba()`, `hsl()`, `hsla()`, or a named color.
+fn parse_color(string: &str) -> Option<ColorU> {
+    let string = string.trim().to_ascii_lowercase();
+    if string.starts_with('#') {
+        return parse_hex_color(&string[1..]);
+    }
+    if string == "transparent" {
+        return Some(ColorU::transparent_black());
+    }
+
+    if let Some(open) = string.find('(') {
+        if !string.ends_with(')') {
+            return None;
+        }
+        let args: Vec<&str> = string[(open + 1)..(string.len() - 1)]
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .collect();
+        if args.len() != 3 && args.len() != 4 {
+            return None;
+        }
+        let alpha = match args.get(3) {
+            None => 1.0,
+            Some(arg) => parse_css_number(arg, 1.0)?,
+        };
+        let color = match string[..open].trim() {
+            "rgb" | "rgba" => {
+                ColorF::new(parse_css_number(args[0], 255.0)? / 255.0,
+                            parse_css_number(args[1], 255.0)? / 255.0,
+                            parse_css_number(args[2], 255.0)? / 255.0,
+                            alpha)
+            }
+            "hsl" | "hsla" => {
+                let hue = args[0].trim_end_matches("deg").parse::<f32>().ok()?;
+                ColorF::from_hsla(hue * PI / 180.0,
+                                  parse_css_number(args[1], 100.0)? / 100.0,
+                                  parse_css_number(args[2], 100.0)? / 100.0,
+                                  alpha)
+            }
+            _ => return None,
+        };
+        let channel = |value: f32| (value.max(0.0).min(1.0) * 255.0).round() as u8;
+        return Some(ColorU::new(channel(color.r()),
+                                channel(color.g()),
+                                channel(color.b()),
+                                channel(color.a())));
+    }
+
+    NAMED_COLORS.binary_search_by_key(&string.as_str(), |&(name, _)| name).ok().map(|index| {
+        let rgb = NAMED_COLORS[index].1;
+        ColorU::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 255)
+    })
+}
+
+// Parses a number or a percentage of `full`, clamped to between zero and `full`.
+fn parse_css_number(string: &str, full: f32) -> Option<f32> {
+    let number = if string.ends_with('%') {
+        string[..(string.len() - 1)].parse::<f32>().ok()? * full / 100.0
+    } else {
+        string.parse::<f32>().ok()?
+    };
+    Some(number.max(0.0).min(full))
+}
+
+fn parse_hex_color(hex: &str) -> Option<ColorU> {
+    let digits: Option<Vec<u8>> = hex.chars()
+                                     .map(|digit| digit.to_digit(16).map(|digit| digit as u8))
+                                     .collect();
+    let digits = digits?;
+    match digits.len() {
+        3 | 4 => {
+            let channel = |index: usize| digits.get(index).map_or(255, |digit| digit * 17);
+            Some(ColorU::new(channel(0), channel(1), channel(2), channel(3)))
+        }
+        6 | 8 => {
+            let channel = |index: usize| {
+                digits.get(index * 2).map_or(255, |digit| digit * 16 + digits[index * 2 + 1])
+            };
+            Some(ColorU::new(channel(0), channel(1), channel(2), channel(3)))
+        }
+        _ => None,
+    }
+}
+
+impl Display for RecordingError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            RecordingError::Json(ref error) => write!(formatter, "invalid JSON: {}", error),
+            RecordingError::InvalidRecording => write!(formatter, "missing command list"),
+            RecordingError::InvalidCommand(index) => {
+                write!(formatter, "command {}: not a property set or a call", index)
+            }
+            RecordingError::InvalidArguments(index) => {
+                write!(formatter, "command {}: invalid arguments", index)
+            }
+            RecordingError::UnsupportedCall(index, ref name) => {
+                write!(formatter, "command {}: unsupported call to `{}`", index, name)
+            }
+            RecordingError::UnsupportedProperty(index, ref name) => {
+                write!(formatter, "command {}: unsupported property `{}`", index, name)
+            }
+            RecordingError::UnknownObject(index, id) => {
+                write!(formatter, "command {}: no suitable object {}", index, id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+// The CSS named colors, sorted by name.
+static NAMED_COLORS: [(&'static str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
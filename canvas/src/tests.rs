@@ -3,8 +3,17 @@
 // For this file only, any copyright is dedicated to the Public Domain.
 // https://creativecommons.org/publicdomain/zero/1.0/
 
+use pathfinder_color::ColorU;
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::gradient::{GradientGeometry, GradientWrap};
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::rect::RectF;
 use pathfinder_geometry::vector::{Vector2F, vec2f};
-use super::Path2D;
+use pathfinder_renderer::scene::DrawPathId;
+#[cfg(feature = "pf-recording")]
+use super::recording::{Recording, RecordingError};
+use super::{AccessibilityRole, Canvas, CanvasFontContext, DashDirection, LineCap, LineJoin};
+use super::{LineTrim, Path2D, Transform2F};
 
 #[test]
 pub fn test_path2d_formatting() {
@@ -17,3 +26,204 @@ pub fn test_path2d_formatting() {
     path.close_path();
     assert_eq!(format!("{:?}", path), "M 0 1 L 2 3 L 4 5 z");
 }
+
+#[cfg(feature = "pf-recording")]
+#[test]
+pub fn test_recording_replay() {
+    let recording = Recording::from_slice(br##"{
+        "width": 100,
+        "height": 50,
+        "commands": [
+            { "set": "fillStyle", "value": "#0f08" },
+            { "call": "fillRect", "args": [0, 0, 10, 10] },
+            { "set": "fillStyle", "value": "not a color" },
+            { "call": "Path2D", "result": 1 },
+            { "call": "rect", "target": 1, "args": [20, 20, 5, 5] },
+            { "set": "fillStyle", "value": "RebeccaPurple" },
+            { "call": "fill", "args": [{ "object": 1 }, "evenodd"] },
+            { "call": "measureText", "args": ["ignored"] },
+            { "call": "drawFocusIfNeeded", "args": [] }
+        ]
+    }"##).unwrap();
+    assert_eq!(recording.size, vec2f(100.0, 50.0));
+
+    let canvas = Canvas::new(recording.size);
+    let mut context = canvas.get_context_2d(CanvasFontContext::from_system_source());
+    match recording.replay(&mut context) {
+        Err(RecordingError::UnsupportedCall(8, ref name)) if name == "drawFocusIfNeeded" => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    let scene = context.canvas().scene();
+    assert_eq!(scene.draw_path_count(), 2);
+    let square = scene.get_draw_path(DrawPathId(0));
+    assert_eq!(scene.get_paint(square.paint).base_color(), ColorU::new(0, 255, 0, 136));
+    let rect = scene.get_draw_path(DrawPathId(1));
+    assert_eq!(rect.outline.bounds(), RectF::new(vec2f(20.0, 20.0), vec2f(5.0, 5.0)));
+    assert_eq!(rect.fill_rule, FillRule::EvenOdd);
+    assert_eq!(scene.get_paint(rect.paint).base_color(), ColorU::new(102, 51, 153, 255));
+}
+
+#[cfg(feature = "pf-recording")]
+#[test]
+pub fn test_recording_round_rects_and_path_data() {
+    // The path data ends in an incomplete segment, which is dropped along with everything after
+    // it, as in the browser.
+    let recording = Recording::from_slice(br#"{
+        "commands": [
+            { "call": "roundRect", "args": [30, 20, -20, -10, [4, { "x": 2, "y": 8 }]] },
+            { "call": "fill" },
+            { "call": "Path2D", "args": ["M40,10h20a10 10 0 01-20 0z L0 0 q1"], "result": 1 },
+            { "call": "fill", "args": [{ "object": 1 }] },
+            { "call": "roundRect", "args": [0, 0, 10, 10, -1] }
+        ]
+    }"#).unwrap();
+
+    let canvas = Canvas::new(recording.size);
+    let mut context = canvas.get_context_2d(CanvasFontContext::from_system_source());
+    match recording.replay(&mut context) {
+        Err(RecordingError::InvalidArguments(4)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    let scene = context.canvas().scene();
+    assert_eq!(scene.draw_path_count(), 2);
+    let round_rect = scene.get_draw_path(DrawPathId(0));
+    assert_eq!(round_rect.outline.bounds(), RectF::new(vec2f(10.0, 10.0), vec2f(20.0, 10.0)));
+    let path = scene.get_draw_path(DrawPathId(1)).outline.bounds();
+    assert!((path.origin() - Vector2F::zero()).length() < 0.01);
+    assert!((path.lower_right() - vec2f(60.0, 20.0)).length() < 0.01);
+}
+
+#[cfg(feature = "pf-recording")]
+#[test]
+pub fn test_recording_current_path_follows_transform() {
+    // The browser transforms points as they're added to the current path, so changing the
+    // transform only affects the points that come after it.
+    let recording = Recording::from_slice(br#"{
+        "commands": [
+            { "call": "beginPath" },
+            { "call": "moveTo", "args": [0, 0] },
+            { "call": "lineTo", "args": [10, 0] },
+            { "call": "save" },
+            { "call": "translate", "args": [5, 5] },
+            { "call": "lineTo", "args": [10, 10] },
+            { "call": "lineTo", "args": [null, 100] },
+            { "call": "restore" },
+            { "call": "fill" }
+        ]
+    }"#).unwrap();
+    assert_eq!(recording.size, vec2f(300.0, 150.0));
+
+    let canvas = recording.to_canvas(CanvasFontContext::from_system_source()).unwrap();
+    let triangle = canvas.scene().get_draw_path(DrawPathId(0));
+    assert_eq!(triangle.outline.bounds(), RectF::new(Vector2F::zero(), vec2f(15.0, 15.0)));
+}
+
+#[test]
+pub fn test_simple_dashes_are_drawn_with_gradients() {
+    let canvas = Canvas::new(vec2f(100.0, 100.0));
+    let mut context = canvas.get_context_2d(CanvasFontContext::from_system_source());
+    context.set_line_dash(vec![6.0, 4.0]);
+    let mut path = Path2D::new();
+    path.move_to(vec2f(10.0, 10.0));
+    path.line_to(vec2f(98.0, 10.0));
+
+    // A straight, butt-capped line is one piece, painted with a repeating dash gradient.
+    context.stroke_path(path.clone());
+    let scene = context.canvas().scene();
+    assert_eq!(scene.draw_path_count(), 1);
+    let line = scene.get_draw_path(DrawPathId(0));
+    let gradient = scene.get_paint(line.paint).gradient().expect("Dashes weren't a gradient!");
+    assert_eq!(gradient.wrap, GradientWrap::Repeat);
+
+    // Square caps extend each dash, so the dashes have to be split out of the path.
+    context.set_line_cap(LineCap::Square);
+    context.stroke_path(path);
+    let scene = context.canvas().scene();
+    assert_eq!(scene.draw_path_count(), 2);
+    let dashes = scene.get_draw_path(DrawPathId(1));
+    assert!(scene.get_paint(dashes.paint).is_color());
+    assert_eq!(dashes.outline.contours().len(), 9);
+}
+
+#[test]
+pub fn test_line_dash_direction_reverses_closed_paths() {
+    let canvas = Canvas::new(vec2f(100.0, 100.0));
+    let mut context = canvas.get_context_2d(CanvasFontContext::from_system_source());
+    context.set_line_dash(vec![30.0, 10.0]);
+    context.set_line_dash_offset(10.0);
+    let mut square = Path2D::new();
+    square.rect(RectF::new(vec2f(10.0, 10.0), vec2f(40.0, 40.0)));
+
+    // The square is drawn clockwise, so dashing it clockwise changes nothing, but dashing it
+    // counterclockwise puts the dashes elsewhere.
+    context.stroke_path(square.clone());
+    context.set_line_dash_direction(DashDirection::Clockwise);
+    context.stroke_path(square.clone());
+    context.set_line_dash_direction(DashDirection::Counterclockwise);
+    context.stroke_path(square);
+    let scene = context.canvas().scene();
+    assert_eq!(scene.draw_path_count(), 3);
+    let outlines: Vec<_> = (0..3).map(|index| &scene.get_draw_path(DrawPathId(index)).outline)
+                                 .collect();
+    assert_eq!(outlines[0], outlines[1]);
+    assert_ne!(outlines[0], outlines[2]);
+}
+
+#[test]
+pub fn test_line_trims_are_drawn_with_gradients() {
+    let canvas = Canvas::new(vec2f(100.0, 100.0));
+    let mut context = canvas.get_context_2d(CanvasFontContext::from_system_source());
+    let mut line = Path2D::new();
+    line.move_to(vec2f(10.0, 10.0));
+    line.line_to(vec2f(90.0, 10.0));
+
+    // Keeping a quarter of a straight line, from 20 to 40 units along, is one piece painted with
+    // a single repeating dash of that length.
+    context.set_line_trim(Some(LineTrim { start: 0.25, end: 0.5, offset: 0.0 }));
+    context.stroke_path(line.clone());
+    let scene = context.canvas().scene();
+    assert_eq!(scene.draw_path_count(), 1);
+    let path = scene.get_draw_path(DrawPathId(0));
+    let gradient = scene.get_paint(path.paint).gradient().expect("Trim wasn't a gradient!");
+    assert_eq!(gradient.wrap, GradientWrap::Repeat);
+    assert_eq!(gradient.geometry,
+               GradientGeometry::Linear(LineSegment2F::new(vec2f(-50.0, 10.0),
+                                                           vec2f(30.0, 10.0))));
+
+    // An empty trim draws nothing.
+    context.set_line_trim(Some(LineTrim { start: 0.5, end: 0.5, offset: 0.0 }));
+    context.stroke_path(line);
+    assert_eq!(context.canvas().scene().draw_path_count(), 1);
+
+    // Keeping the first half of an L keeps its first leg, which is drawn in the plain color, and
+    // drops the second.
+    let mut corner = Path2D::new();
+    corner.move_to(vec2f(0.0, 10.0));
+    corner.line_to(vec2f(40.0, 10.0));
+    corner.line_to(vec2f(40.0, 50.0));
+    context.set_line_join(LineJoin::Round);
+    context.set_line_trim(Some(LineTrim { start: 0.0, end: 0.5, offset: 0.0 }));
+    context.stroke_path(corner);
+    let scene = context.canvas().scene();
+    assert_eq!(scene.draw_path_count(), 2);
+    assert!(scene.get_paint(scene.get_draw_path(DrawPathId(1)).paint).is_color());
+}
+
+#[test]
+pub fn test_accessibility_nodes_tag_shapes() {
+    let canvas = Canvas::new(vec2f(100.0, 100.0));
+    let mut context = canvas.get_context_2d(CanvasFontContext::from_system_source());
+    context.fill_rect(RectF::new(vec2f(0.0, 0.0), vec2f(100.0, 100.0)));
+    let button = context.push_accessibility_node(AccessibilityRole::Button, "Close".to_owned());
+    context.set_transform(&Transform2F::from_translation(vec2f(80.0, 0.0)));
+    context.fill_rect(RectF::new(vec2f(0.0, 0.0), vec2f(20.0, 20.0)));
+    context.pop_accessibility_node();
+
+    let tree = context.canvas().scene().accessibility_tree(&Transform2F::default());
+    assert_eq!(tree.roots(), &[button]);
+    let node = tree.get(button);
+    assert_eq!((node.role, &*node.label), (AccessibilityRole::Button, "Close"));
+    assert_eq!(node.bounds, Some(RectF::new(vec2f(80.0, 0.0), vec2f(20.0, 20.0))));
+}
@@ -21,8 +21,11 @@ use font_kit::sources::mem::MemSource;
 use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::util;
 use pathfinder_geometry::vector::{Vector2F, vec2f};
+use pathfinder_renderer::accessibility::AccessibilityRole;
 use pathfinder_renderer::paint::PaintId;
-use pathfinder_text::{FontContext, FontRenderOptions, TextRenderMode};
+use pathfinder_text::decoration::TextDecorations;
+use pathfinder_text::{FontContext, FontRenderOptions, GlyphRenderStrategy, SubpixelPositions};
+use pathfinder_text::TextRenderMode;
 use skribo::{FontCollection, FontFamily, FontRef, Layout as SkriboLayout, TextStyle};
 use std::borrow::Cow;
 use std::cell::{Cell, RefCell};
@@ -78,6 +81,11 @@ impl CanvasRenderingContext2D {
         position += layout.text_origin();
         let transform = self.current_state.transform * Transform2F::from_translation(position);
 
+        // Text that we have the characters of is tagged as a text run for assistive technology.
+        if let Some(ref text) = layout.text {
+            self.canvas.scene.push_accessibility_node(AccessibilityRole::Text, text.clone());
+        }
+
         // TODO(pcwalton): Report errors.
         drop(self.canvas_font_context
                  .0
@@ -93,7 +101,15 @@ impl CanvasRenderingContext2D {
                                   clip_path,
                                   blend_mode,
                                   paint_id,
+                                  decorations: TextDecorations::default(),
+                                  glyph_strategy: GlyphRenderStrategy::Outlines,
+                                  glyph_effect: None,
+                                  subpixel_positions: SubpixelPositions::default(),
                               }));
+
+        if layout.text.is_some() {
+            self.canvas.scene.pop_accessibility_node();
+        }
     }
 
     // Text styles
@@ -157,10 +173,12 @@ impl ToTextLayout for str {
         let skribo_layout = Rc::new(skribo::layout(&TextStyle { size: state.0.font_size },
                                                    &state.0.font_collection,
                                                    self));
-        Cow::Owned(TextMetrics::new(skribo_layout,
-                                    state.0.font_size,
-                                    state.0.text_align,
-                                    state.0.text_baseline))
+        let mut metrics = TextMetrics::new(skribo_layout,
+                                           state.0.font_size,
+                                           state.0.text_align,
+                                           state.0.text_baseline);
+        metrics.text = Some(self.to_owned());
+        Cow::Owned(metrics)
     }
 }
 
@@ -269,6 +287,9 @@ pub struct TextMetrics {
     // The distance from the typographic right side of the text to the right side of the bounding
     // rectangle of the given text, in pixels. The distance is measured parallel to the baseline.
     actual_right_extent: Cell<Option<f32>>,
+    // The text that was laid out, if this was made from a string, so that it can be exposed to
+    // assistive technology when it's drawn.
+    text: Option<String>,
 }
 
 #[derive(Clone, Copy)]
@@ -319,6 +340,7 @@ impl TextMetrics {
             width: Cell::new(None),
             actual_left_extent: Cell::new(None),
             actual_right_extent: Cell::new(None),
+            text: None,
         }
     }
 
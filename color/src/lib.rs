@@ -11,6 +11,7 @@
 use pathfinder_simd::default::F32x4;
 use std::f32::consts::PI;
 use std::fmt::{self, Debug, Formatter};
+use std::hash::{Hash, Hasher};
 use std::slice;
 
 pub mod matrix;
@@ -224,6 +225,78 @@ impl Debug for ColorF {
     }
 }
 
+/// A color in the CMYK process color model, for print.
+///
+/// Each component is the amount of cyan, magenta, yellow, or black ink, from 0.0 to 1.0. CMYK
+/// colors are device-dependent, so there's no exact conversion to RGB; they're carried as-is to
+/// print output, alongside an RGB color that's used on screen.
+#[derive(Clone, Copy, Default)]
+pub struct ColorCmyk {
+    pub c: f32,
+    pub m: f32,
+    pub y: f32,
+    pub k: f32,
+}
+
+impl ColorCmyk {
+    #[inline]
+    pub fn new(c: f32, m: f32, y: f32, k: f32) -> ColorCmyk {
+        ColorCmyk { c, m, y, k }
+    }
+
+    #[inline]
+    fn to_bits(&self) -> [u32; 4] {
+        [self.c.to_bits(), self.m.to_bits(), self.y.to_bits(), self.k.to_bits()]
+    }
+
+    /// Creates a CMYK color from ink percentages, as they're usually written in brand guides.
+    #[inline]
+    pub fn from_percentages(c: f32, m: f32, y: f32, k: f32) -> ColorCmyk {
+        ColorCmyk::new(c / 100.0, m / 100.0, y / 100.0, k / 100.0)
+    }
+
+    /// Returns a copy of this color with all components clamped to the 0.0 to 1.0 range.
+    #[inline]
+    pub fn clamp(&self) -> ColorCmyk {
+        let clamp = |value: f32| value.max(0.0).min(1.0);
+        ColorCmyk::new(clamp(self.c), clamp(self.m), clamp(self.y), clamp(self.k))
+    }
+
+    /// A naïve, profile-less conversion to opaque RGB.
+    ///
+    /// This is only an approximation, suitable for previews. Use a proper color-managed RGB color
+    /// for display where accuracy matters.
+    #[inline]
+    pub fn to_rgb_approximation(&self) -> ColorU {
+        let color = self.clamp();
+        let white = 1.0 - color.k;
+        ColorF::new((1.0 - color.c) * white, (1.0 - color.m) * white, (1.0 - color.y) * white, 1.0)
+            .to_u8()
+    }
+}
+
+// Compares bitwise, so that CMYK colors can be used in hashed paints.
+impl PartialEq for ColorCmyk {
+    #[inline]
+    fn eq(&self, other: &ColorCmyk) -> bool {
+        self.to_bits() == other.to_bits()
+    }
+}
+
+impl Eq for ColorCmyk {}
+
+impl Hash for ColorCmyk {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        self.to_bits().hash(state);
+    }
+}
+
+impl Debug for ColorCmyk {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "cmyk({}, {}, {}, {})", self.c, self.m, self.y, self.k)
+    }
+}
+
 #[inline]
 pub fn color_slice_to_u8_slice(slice: &[ColorU]) -> &[u8] {
     unsafe {
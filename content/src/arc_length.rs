@@ -0,0 +1,324 @@
+// pathfinder/content/src/arc_length.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Strokes whose paint follows the distance along the path.
+//!
+//! Paints parameterized by arc length, such as a track colored by speed or a progress ring, aren't
+//! a function of position, so they can't be expressed as an ordinary gradient. Instead, the path
+//! is flattened and each line is stroked as a separate piece that records the distance along the
+//! path at both of its ends. Along a straight line, distance varies linearly with position, so a
+//! linear gradient between the ends of each piece reproduces the paint exactly, and the shaders
+//! evaluate it per pixel like any other gradient.
+//!
+//! Pieces always meet with round joins, and they overlap slightly where they meet, which shows as
+//! seams if the paint is translucent.
+
+use crate::gradient::{Gradient, GradientWrap};
+use crate::outline::{Contour, ContourIterFlags, Outline};
+use crate::segment::Segment;
+use crate::stroke::{LineCap, StrokeStyle};
+use pathfinder_color::ColorU;
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+
+const EPSILON: f32 = 0.0001;
+
+// The maximum number of times a curve is halved while flattening it.
+const MAX_FLATTENING_DEPTH: u32 = 16;
+
+/// A stroke split into pieces labeled with their distance along the path.
+#[derive(Clone, Debug)]
+pub struct ArcLengthStroke {
+    pieces: Vec<StrokePiece>,
+    length: f32,
+}
+
+/// A piece of a stroke covering one straight line of the flattened path.
+#[derive(Clone, Debug)]
+pub struct StrokePiece {
+    /// The outline to fill, with the nonzero winding rule.
+    pub outline: Outline,
+    /// The center line of the piece, in the direction of the path.
+    pub line: LineSegment2F,
+    /// The distance along the path to the start of `line`.
+    pub start_distance: f32,
+    /// The distance along the path to the end of `line`.
+    pub end_distance: f32,
+}
+
+impl ArcLengthStroke {
+    /// Strokes `input` with the width and caps of `style`, flattening curves until they deviate
+    /// from straight lines by no more than `tolerance`.
+    ///
+    /// Contours are measured one after another, so distances keep increasing across them.
+    pub fn new(input: &Outline, style: &StrokeStyle, tolerance: f32) -> ArcLengthStroke {
+        let radius = style.line_width * 0.5;
+        let mut pieces = vec![];
+        let mut length = 0.0;
+
+        for contour in input.contours() {
+            let mut lines = vec![];
+            for segment in contour.iter(ContourIterFlags::empty()) {
+                flatten_segment(&segment, tolerance, &mut lines);
+            }
+            lines.retain(|line| line.vector().length() > EPSILON);
+
+            let closed = contour.is_closed();
+            let line_count = lines.len();
+            for (line_index, line) in lines.into_iter().enumerate() {
+                // A round cap at the start of each piece after the first joins it to the previous
+                // one.
+                let start_cap = if line_index == 0 && !closed {
+                    style.line_cap
+                } else {
+                    LineCap::Round
+                };
+                let end_cap = if line_index + 1 == line_count && !closed {
+                    style.line_cap
+                } else {
+                    LineCap::Butt
+                };
+
+                let mut outline = Outline::new();
+                outline.push_contour(line_contour(line, radius, start_cap, end_cap));
+                if start_cap == LineCap::Round {
+                    outline.push_contour(circle_contour(line.from(), radius));
+                }
+                if end_cap == LineCap::Round {
+                    outline.push_contour(circle_contour(line.to(), radius));
+                }
+
+                let start_distance = length;
+                length += line.vector().length();
+                pieces.push(StrokePiece { outline, line, start_distance, end_distance: length });
+            }
+        }
+
+        ArcLengthStroke { pieces, length }
+    }
+
+    /// Returns the pieces of the stroke, in path order.
+    #[inline]
+    pub fn pieces(&self) -> &[StrokePiece] {
+        &self.pieces
+    }
+
+    /// Returns the total length of the path.
+    #[inline]
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+}
+
+impl StrokePiece {
+    /// Returns the linear gradient that paints this piece with `ramp`, whose stop offsets are
+    /// fractions of `length`, the total length of the path. The geometry of `ramp` is ignored.
+    pub fn gradient(&self, ramp: &Gradient, length: f32) -> Gradient {
+        let length = length.max(EPSILON);
+        let (start, end) = (self.start_distance / length, self.end_distance / length);
+
+        let mut gradient = Gradient::linear(self.line);
+        gradient.wrap = ramp.wrap;
+        gradient.add_color_stop(ramp.sample(start), 0.0);
+        for stop in ramp.stops() {
+            if stop.offset > start && stop.offset < end {
+                gradient.add_color_stop(stop.color, (stop.offset - start) / (end - start));
+            }
+        }
+        gradient.add_color_stop(ramp.sample(end), 1.0);
+        gradient
+    }
+
+    /// Returns the linear gradient that paints this piece with `color` where a dash pattern is on
+    /// and leaves it transparent where the pattern is off, so that a stroke can be dashed without
+    /// splitting the path.
+    ///
+    /// `dashes` alternates the lengths of dashes and gaps, and must have an even length and a
+    /// positive sum. `offset` is the distance into the pattern at the start of the path, as for
+    /// `OutlineDash`. The ends of the dashes are cut square, like butt caps.
+    pub fn dash_gradient(&self, color: ColorU, dashes: &[f32], offset: f32) -> Gradient {
+        let period: f32 = dashes.iter().sum();
+        let length = self.end_distance - self.start_distance;
+        let phase = (self.start_distance + offset).rem_euclid(period);
+        let dash_color = |dash_index: usize| {
+            if dash_index % 2 == 0 { color } else { ColorU::transparent_black() }
+        };
+
+        // A piece shorter than the pattern gets just the part of the pattern along it, which
+        // resolves the ends of its dashes more finely.
+        if length < period {
+            let mut gradient = Gradient::linear(self.line);
+            let mut distance = -phase;
+            for (dash_index, &dash) in dashes.iter().enumerate().cycle() {
+                if distance >= length {
+                    break;
+                }
+                let dash_end = distance + dash;
+                if dash_end > 0.0 {
+                    gradient.add_color_stop(dash_color(dash_index), distance.max(0.0) / length);
+                    gradient.add_color_stop(dash_color(dash_index), dash_end.min(length) / length);
+                }
+                distance = dash_end;
+            }
+            return gradient;
+        }
+
+        // Otherwise, the whole pattern repeats along the piece.
+        let direction = self.line.vector().normalize();
+        let from = self.line.from() - direction * phase;
+        let mut gradient = Gradient::linear(LineSegment2F::new(from, from + direction * period));
+        gradient.wrap = GradientWrap::Repeat;
+        let mut distance = 0.0;
+        for (dash_index, &dash) in dashes.iter().enumerate() {
+            gradient.add_color_stop(dash_color(dash_index), distance / period);
+            distance += dash;
+            gradient.add_color_stop(dash_color(dash_index), distance / period);
+        }
+        gradient
+    }
+}
+
+/// Appends the lines that approximate `segment` to within `tolerance` to `lines`.
+pub fn flatten_segment(segment: &Segment, tolerance: f32, lines: &mut Vec<LineSegment2F>) {
+    flatten(segment, tolerance, 0, lines)
+}
+
+fn flatten(segment: &Segment, tolerance: f32, depth: u32, lines: &mut Vec<LineSegment2F>) {
+    if segment.is_none() {
+        return;
+    }
+    if segment.is_line() {
+        lines.push(segment.baseline);
+        return;
+    }
+
+    let cubic = segment.to_cubic();
+    if depth == MAX_FLATTENING_DEPTH || cubic.as_cubic_segment().is_flat(tolerance) {
+        lines.push(cubic.baseline);
+        return;
+    }
+    let (before, after) = cubic.split(0.5);
+    flatten(&before, tolerance, depth + 1, lines);
+    flatten(&after, tolerance, depth + 1, lines);
+}
+
+// Returns the rectangle covering a line stroked with the given radius. Square caps extend it past
+// the ends. It winds the same way as `circle_contour()`, so the two add up where they overlap.
+fn line_contour(line: LineSegment2F, radius: f32, start_cap: LineCap, end_cap: LineCap)
+                -> Contour {
+    let direction = line.vector().normalize() * radius;
+    let normal = vec2f(-direction.y(), direction.x());
+    let extension = |cap: LineCap| {
+        if cap == LineCap::Square { direction } else { Vector2F::zero() }
+    };
+    let (from, to) = (line.from() - extension(start_cap), line.to() + extension(end_cap));
+
+    let mut contour = Contour::with_capacity(4);
+    contour.push_endpoint(from - normal);
+    contour.push_endpoint(to - normal);
+    contour.push_endpoint(to + normal);
+    contour.push_endpoint(from + normal);
+    contour.close();
+    contour
+}
+
+fn circle_contour(center: Vector2F, radius: f32) -> Contour {
+    let mut contour = Contour::new();
+    contour.push_ellipse(&Transform2F::from_scale(radius).translate(center));
+    contour.close();
+    contour
+}
+
+#[cfg(test)]
+mod test {
+    use super::ArcLengthStroke;
+    use crate::gradient::{Gradient, GradientGeometry, GradientWrap};
+    use crate::outline::{Contour, Outline};
+    use crate::stroke::{LineCap, LineJoin, StrokeStyle};
+    use pathfinder_color::ColorU;
+    use pathfinder_geometry::line_segment::LineSegment2F;
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_pieces_follow_arc_length() {
+        // An L-shaped path, 30 units along and then 10 units down.
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_endpoint(vec2f(30.0, 0.0));
+        contour.push_endpoint(vec2f(30.0, 10.0));
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let style = StrokeStyle {
+            line_width: 2.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Round,
+        };
+        let stroke = ArcLengthStroke::new(&outline, &style, 0.25);
+        assert_eq!(stroke.length(), 40.0);
+        assert_eq!(stroke.pieces().len(), 2);
+        assert_eq!(stroke.pieces()[1].start_distance, 30.0);
+
+        // The second piece runs from 75% to 100% of the way along, which crosses the stop at 90%.
+        let mut ramp = Gradient::linear(LineSegment2F::default());
+        ramp.add_color_stop(ColorU::new(0, 0, 0, 255), 0.0);
+        ramp.add_color_stop(ColorU::new(255, 0, 0, 255), 0.9);
+        ramp.add_color_stop(ColorU::new(255, 255, 255, 255), 1.0);
+        let gradient = stroke.pieces()[1].gradient(&ramp, stroke.length());
+        let offsets: Vec<f32> = gradient.stops().iter().map(|stop| stop.offset).collect();
+        assert_eq!(offsets.len(), 3);
+        assert!((offsets[1] - 0.6).abs() < 0.0001);
+        assert_eq!(gradient.stops()[2].color, ColorU::new(255, 255, 255, 255));
+    }
+
+    #[test]
+    fn test_dash_gradients_continue_across_pieces() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_endpoint(vec2f(25.0, 0.0));
+        contour.push_endpoint(vec2f(25.0, 10.0));
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let style = StrokeStyle {
+            line_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Round,
+        };
+        let stroke = ArcLengthStroke::new(&outline, &style, 0.25);
+        let color = ColorU::new(255, 0, 0, 255);
+        let dashes = [6.0, 4.0];
+
+        // The pattern is 10 units long and starts 2 units in, so the second piece, which starts
+        // 25 units along, starts 7 units into the pattern, in a gap.
+        let gradient = stroke.pieces()[1].dash_gradient(color, &dashes, 2.0);
+        assert_eq!(gradient.wrap, GradientWrap::Repeat);
+        match gradient.geometry {
+            GradientGeometry::Linear(line) => {
+                assert!((line.from() - vec2f(25.0, -7.0)).length() < 0.0001);
+                assert!((line.to() - vec2f(25.0, 3.0)).length() < 0.0001);
+            }
+            GradientGeometry::Radial { .. } => panic!("Dash gradients should be linear!"),
+        }
+        let stops: Vec<(f32, u8)> =
+            gradient.stops().iter().map(|stop| (stop.offset, stop.color.a)).collect();
+        assert_eq!(stops, vec![(0.0, 255), (0.6, 255), (0.6, 0), (1.0, 0)]);
+
+        // With a pattern longer than the piece, the gradient spans just the piece, which is in the
+        // last 5 units of the first dash and then in the gap.
+        let gradient = stroke.pieces()[1].dash_gradient(color, &[30.0, 10.0], 0.0);
+        assert_eq!(gradient.wrap, GradientWrap::Clamp);
+        assert_eq!(gradient.geometry, GradientGeometry::Linear(stroke.pieces()[1].line));
+        let stops: Vec<(f32, u8)> =
+            gradient.stops().iter().map(|stop| (stop.offset, stop.color.a)).collect();
+        assert_eq!(stops, vec![(0.0, 255), (0.5, 255), (0.5, 0), (1.0, 0)]);
+    }
+}
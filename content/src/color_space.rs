@@ -0,0 +1,522 @@
+// pathfinder/content/src/color_space.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The color spaces of raster images, and conversion from them to sRGB.
+//!
+//! Pathfinder renders in sRGB. Images in other color spaces, such as photos from phones that
+//! shoot in Display P3, are converted to sRGB when they're uploaded, so that they look the same as
+//! they do in the operating system's image viewer. Colors outside the sRGB gamut are clipped.
+//!
+//! Color spaces are usually found from the ICC profile embedded in an image file. Only RGB
+//! profiles built from primaries and tone curves are understood, which covers the profiles that
+//! cameras, phones, and image editors embed.
+
+use pathfinder_color::ColorU;
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+// The primaries of sRGB, as XYZ colorants adapted to the D50 white point of ICC profiles.
+const SRGB_COLORANTS: [[f32; 3]; 3] = [
+    [0.436066, 0.222488, 0.013916],
+    [0.385147, 0.716873, 0.097076],
+    [0.143066, 0.060608, 0.714096],
+];
+
+// The primaries of Display P3, likewise.
+const DISPLAY_P3_COLORANTS: [[f32; 3]; 3] = [
+    [0.515102, 0.241182, -0.001050],
+    [0.291965, 0.692236, 0.041881],
+    [0.157153, 0.066582, 0.784378],
+];
+
+// How far colorants may be from the standard ones and still be taken for them. Profiles round
+// their colorants differently.
+const COLORANT_TOLERANCE: f32 = 0.002;
+// How far a tone curve may be from sRGB's and still be taken for it.
+const CURVE_TOLERANCE: f32 = 0.005;
+
+// The number of entries in the table that encodes linear light to sRGB.
+const ENCODE_TABLE_LENGTH: usize = 4096;
+
+/// The color space that an image's pixels are in.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub enum ImageColorSpace {
+    /// sRGB, the color space Pathfinder renders in. Images in it aren't converted.
+    #[default]
+    Srgb,
+    /// Display P3, with the primaries of DCI-P3, a D65 white point, and sRGB's tone curve.
+    DisplayP3,
+    /// The color space described by an ICC profile.
+    Icc(Arc<IccProfile>),
+}
+
+/// An RGB ICC profile made of primaries and tone curves.
+#[derive(Clone)]
+pub struct IccProfile {
+    // The XYZ colorants of red, green, and blue.
+    colorants: [[f32; 3]; 3],
+    curves: [ToneCurve; 3],
+    data_hash: u64,
+}
+
+/// The reasons an ICC profile can fail to parse.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IccProfileError {
+    /// The profile ended before all of its data had been read.
+    Truncated,
+    /// The profile isn't for RGB images.
+    NotRgb,
+    /// The profile lacks a tag that's needed. Profiles built from lookup tables lack the colorant
+    /// and tone curve tags.
+    MissingTag([u8; 4]),
+    /// A tag's data is of a type that isn't understood.
+    UnsupportedTagType([u8; 4]),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum ToneCurve {
+    Gamma(f32),
+    // Evenly spaced samples of the curve, from 0.0 to 1.0.
+    Table(Vec<f32>),
+    // One of the ICC parametric curve functions, with its parameters g, a, b, c, d, e, and f.
+    Parametric { function: u16, params: [f32; 7] },
+}
+
+impl ImageColorSpace {
+    /// Returns the color space that an embedded ICC profile describes.
+    ///
+    /// Profiles of sRGB and Display P3 are recognized as such. Profiles that can't be parsed are
+    /// assumed to be sRGB, with a warning, since that's how they'd have been treated anyway.
+    pub fn from_icc_profile(data: &[u8]) -> ImageColorSpace {
+        match IccProfile::parse(data) {
+            Ok(profile) => profile.into_color_space(),
+            Err(err) => {
+                warn!("Treating an image with an unsupported ICC profile as sRGB: {}", err);
+                ImageColorSpace::Srgb
+            }
+        }
+    }
+
+    /// Returns true if images in this color space are used as they are.
+    #[inline]
+    pub fn is_srgb(&self) -> bool {
+        *self == ImageColorSpace::Srgb
+    }
+
+    /// Converts nonpremultiplied pixels in this color space to sRGB. Alpha is left alone.
+    pub fn convert_to_srgb(&self, pixels: &[ColorU]) -> Vec<ColorU> {
+        let (colorants, curves) = match *self {
+            ImageColorSpace::Srgb => return pixels.to_vec(),
+            ImageColorSpace::DisplayP3 => (DISPLAY_P3_COLORANTS, srgb_curves()),
+            ImageColorSpace::Icc(ref profile) => (profile.colorants, profile.curves.clone()),
+        };
+
+        // Decode each channel to linear light with a table, then move it to sRGB's primaries.
+        let mut decode_tables = [[0.0; 256]; 3];
+        for (table, curve) in decode_tables.iter_mut().zip(curves.iter()) {
+            for (value, entry) in table.iter_mut().enumerate() {
+                *entry = curve.eval(value as f32 / 255.0);
+            }
+        }
+        let matrix = mul_matrices(&invert_matrix(&SRGB_COLORANTS), &colorants);
+        let encode_table: Vec<u8> = (0..ENCODE_TABLE_LENGTH).map(|index| {
+            let linear = index as f32 / (ENCODE_TABLE_LENGTH - 1) as f32;
+            (srgb_encode(linear) * 255.0).round() as u8
+        }).collect();
+
+        pixels.iter().map(|pixel| {
+            let linear = [
+                decode_tables[0][pixel.r as usize],
+                decode_tables[1][pixel.g as usize],
+                decode_tables[2][pixel.b as usize],
+            ];
+            let mut channels = [0; 3];
+            for (channel, row) in channels.iter_mut().zip(matrix.iter()) {
+                let value = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+                let index = value.max(0.0).min(1.0) * (ENCODE_TABLE_LENGTH - 1) as f32;
+                *channel = encode_table[index.round() as usize];
+            }
+            ColorU::new(channels[0], channels[1], channels[2], pixel.a)
+        }).collect()
+    }
+}
+
+impl IccProfile {
+    /// Parses an ICC profile, as embedded in a PNG, JPEG, or WebP image.
+    pub fn parse(data: &[u8]) -> Result<IccProfile, IccProfileError> {
+        if data.len() < 132 {
+            return Err(IccProfileError::Truncated);
+        }
+        if &data[16..20] != b"RGB " {
+            return Err(IccProfileError::NotRgb);
+        }
+
+        let mut colorants = [[0.0; 3]; 3];
+        for (colorant, signature) in colorants.iter_mut().zip([b"rXYZ", b"gXYZ", b"bXYZ"]) {
+            let tag = find_tag(data, signature)?;
+            expect_tag_type(tag, b"XYZ ")?;
+            for (component_index, component) in colorant.iter_mut().enumerate() {
+                *component = read_s15_fixed16(tag, 8 + component_index * 4)?;
+            }
+        }
+
+        let mut curves = [ToneCurve::Gamma(1.0), ToneCurve::Gamma(1.0), ToneCurve::Gamma(1.0)];
+        for (curve, signature) in curves.iter_mut().zip([b"rTRC", b"gTRC", b"bTRC"]) {
+            *curve = ToneCurve::parse(find_tag(data, signature)?)?;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        Ok(IccProfile { colorants, curves, data_hash: hasher.finish() })
+    }
+
+    // Returns the standard color space this profile describes, if it's one of them.
+    fn into_color_space(self) -> ImageColorSpace {
+        let has_srgb_curves = self.curves.iter().all(ToneCurve::approximates_srgb);
+        if has_srgb_curves && colorants_match(&self.colorants, &SRGB_COLORANTS) {
+            ImageColorSpace::Srgb
+        } else if has_srgb_curves && colorants_match(&self.colorants, &DISPLAY_P3_COLORANTS) {
+            ImageColorSpace::DisplayP3
+        } else {
+            ImageColorSpace::Icc(Arc::new(self))
+        }
+    }
+}
+
+impl PartialEq for IccProfile {
+    #[inline]
+    fn eq(&self, other: &IccProfile) -> bool {
+        self.data_hash == other.data_hash
+    }
+}
+
+impl Eq for IccProfile {}
+
+impl Hash for IccProfile {
+    #[inline]
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        self.data_hash.hash(state)
+    }
+}
+
+impl Debug for IccProfile {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "(ICC profile {:016x})", self.data_hash)
+    }
+}
+
+impl ToneCurve {
+    fn parse(tag: &[u8]) -> Result<ToneCurve, IccProfileError> {
+        match tag.get(0..4) {
+            Some(b"curv") => {
+                let count = read_u32(tag, 8)? as usize;
+                match count {
+                    0 => Ok(ToneCurve::Gamma(1.0)),
+                    1 => Ok(ToneCurve::Gamma(read_u16(tag, 12)? as f32 / 256.0)),
+                    _ => {
+                        let table = (0..count).map(|index| {
+                            read_u16(tag, 12 + index * 2).map(|value| value as f32 / 65535.0)
+                        }).collect::<Result<Vec<_>, _>>()?;
+                        Ok(ToneCurve::Table(table))
+                    }
+                }
+            }
+            Some(b"para") => {
+                let function = read_u16(tag, 8)?;
+                let param_count = match function {
+                    0 => 1,
+                    1 => 3,
+                    2 => 4,
+                    3 => 5,
+                    4 => 7,
+                    _ => return Err(IccProfileError::UnsupportedTagType(*b"para")),
+                };
+                let mut params = [0.0; 7];
+                for (param_index, param) in params.iter_mut().take(param_count).enumerate() {
+                    *param = read_s15_fixed16(tag, 12 + param_index * 4)?;
+                }
+                Ok(ToneCurve::Parametric { function, params })
+            }
+            Some(tag_type) => {
+                Err(IccProfileError::UnsupportedTagType([tag_type[0],
+                                                         tag_type[1],
+                                                         tag_type[2],
+                                                         tag_type[3]]))
+            }
+            None => Err(IccProfileError::Truncated),
+        }
+    }
+
+    // Maps an encoded value to linear light.
+    fn eval(&self, x: f32) -> f32 {
+        match *self {
+            ToneCurve::Gamma(gamma) => x.powf(gamma),
+            ToneCurve::Table(ref table) => {
+                let position = x * (table.len() - 1) as f32;
+                let index = (position.floor() as usize).min(table.len() - 2);
+                let t = position - index as f32;
+                table[index] + (table[index + 1] - table[index]) * t
+            }
+            ToneCurve::Parametric { function, params } => {
+                let [g, a, b, c, d, e, f] = params;
+                match function {
+                    0 => x.powf(g),
+                    1 if x >= -b / a => (a * x + b).powf(g),
+                    1 => 0.0,
+                    2 if x >= -b / a => (a * x + b).powf(g) + c,
+                    2 => c,
+                    3 if x >= d => (a * x + b).powf(g),
+                    3 => c * x,
+                    _ if x >= d => (a * x + b).powf(g) + e,
+                    _ => c * x + f,
+                }
+            }
+        }
+    }
+
+    fn approximates_srgb(&self) -> bool {
+        (0..=16).all(|step| {
+            let x = step as f32 / 16.0;
+            (self.eval(x) - srgb_decode(x)).abs() <= CURVE_TOLERANCE
+        })
+    }
+}
+
+impl Display for IccProfileError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            IccProfileError::Truncated => write!(formatter, "the ICC profile is truncated"),
+            IccProfileError::NotRgb => write!(formatter, "the ICC profile isn't an RGB profile"),
+            IccProfileError::MissingTag(signature) => {
+                write!(formatter,
+                       "the ICC profile has no `{}` tag",
+                       String::from_utf8_lossy(&signature))
+            }
+            IccProfileError::UnsupportedTagType(tag_type) => {
+                write!(formatter,
+                       "the ICC profile has an unsupported `{}` tag",
+                       String::from_utf8_lossy(&tag_type))
+            }
+        }
+    }
+}
+
+impl Error for IccProfileError {}
+
+fn srgb_curves() -> [ToneCurve; 3] {
+    let curve = ToneCurve::Parametric {
+        function: 3,
+        params: [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045, 0.0, 0.0],
+    };
+    [curve.clone(), curve.clone(), curve]
+}
+
+fn srgb_decode(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn srgb_encode(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn colorants_match(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> bool {
+    a.iter().flatten().zip(b.iter().flatten()).all(|(a, b)| (a - b).abs() <= COLORANT_TOLERANCE)
+}
+
+// Colorants are stored as columns: each one is the XYZ of a primary.
+fn mul_matrices(a: &[[f32; 3]; 3], colorants: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut result = [[0.0; 3]; 3];
+    for (row_index, row) in result.iter_mut().enumerate() {
+        for (column_index, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[row_index][k] * colorants[column_index][k]).sum();
+        }
+    }
+    result
+}
+
+// Inverts the matrix whose columns are the colorants, returning the inverse in rows.
+fn invert_matrix(colorants: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let m = |row: usize, column: usize| colorants[column][row];
+    let cofactor = |row: usize, column: usize| {
+        let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+        let (c0, c1) = ((column + 1) % 3, (column + 2) % 3);
+        m(r0, c0) * m(r1, c1) - m(r0, c1) * m(r1, c0)
+    };
+    let determinant = m(0, 0) * cofactor(0, 0) + m(0, 1) * cofactor(0, 1) +
+        m(0, 2) * cofactor(0, 2);
+    let mut inverse = [[0.0; 3]; 3];
+    for (row_index, row) in inverse.iter_mut().enumerate() {
+        for (column_index, value) in row.iter_mut().enumerate() {
+            *value = cofactor(column_index, row_index) / determinant;
+        }
+    }
+    inverse
+}
+
+// Returns the data of the tag with the given signature.
+fn find_tag<'a>(data: &'a [u8], signature: &[u8; 4]) -> Result<&'a [u8], IccProfileError> {
+    let tag_count = read_u32(data, 128)? as usize;
+    for tag_index in 0..tag_count {
+        let entry = 132 + tag_index * 12;
+        if data.get(entry..(entry + 4)) != Some(&signature[..]) {
+            continue;
+        }
+        let offset = read_u32(data, entry + 4)? as usize;
+        let size = read_u32(data, entry + 8)? as usize;
+        return data.get(offset..(offset + size)).ok_or(IccProfileError::Truncated);
+    }
+    Err(IccProfileError::MissingTag(*signature))
+}
+
+fn expect_tag_type(tag: &[u8], tag_type: &[u8; 4]) -> Result<(), IccProfileError> {
+    match tag.get(0..4) {
+        Some(found) if found == tag_type => Ok(()),
+        Some(found) => {
+            Err(IccProfileError::UnsupportedTagType([found[0], found[1], found[2], found[3]]))
+        }
+        None => Err(IccProfileError::Truncated),
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, IccProfileError> {
+    match data.get(offset..(offset + 2)) {
+        Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]])),
+        None => Err(IccProfileError::Truncated),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, IccProfileError> {
+    match data.get(offset..(offset + 4)) {
+        Some(bytes) => Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        None => Err(IccProfileError::Truncated),
+    }
+}
+
+fn read_s15_fixed16(data: &[u8], offset: usize) -> Result<f32, IccProfileError> {
+    read_u32(data, offset).map(|value| value as i32 as f32 / 65536.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DISPLAY_P3_COLORANTS, IccProfile, IccProfileError, ImageColorSpace};
+    use super::SRGB_COLORANTS;
+    use pathfinder_color::ColorU;
+    use std::sync::Arc;
+
+    // Builds an RGB profile with the given colorants and a parametric tone curve.
+    fn build_profile(colorants: &[[f32; 3]; 3], curve_function: u16, curve_params: &[f32])
+                     -> Vec<u8> {
+        let mut xyz_tags = vec![];
+        for colorant in colorants {
+            let mut tag = b"XYZ \0\0\0\0".to_vec();
+            for component in colorant {
+                tag.extend_from_slice(&((component * 65536.0).round() as i32).to_be_bytes());
+            }
+            xyz_tags.push(tag);
+        }
+        let mut curve_tag = b"para\0\0\0\0".to_vec();
+        curve_tag.extend_from_slice(&curve_function.to_be_bytes());
+        curve_tag.extend_from_slice(&[0, 0]);
+        for param in curve_params {
+            curve_tag.extend_from_slice(&((param * 65536.0).round() as i32).to_be_bytes());
+        }
+
+        let tags: Vec<(&[u8; 4], &[u8])> = vec![
+            (b"rXYZ", &xyz_tags[0][..]),
+            (b"gXYZ", &xyz_tags[1][..]),
+            (b"bXYZ", &xyz_tags[2][..]),
+            (b"rTRC", &curve_tag[..]),
+            (b"gTRC", &curve_tag[..]),
+            (b"bTRC", &curve_tag[..]),
+        ];
+        let mut data = vec![0; 128];
+        data[16..20].copy_from_slice(b"RGB ");
+        data[20..24].copy_from_slice(b"XYZ ");
+        data.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+        let mut offset = 132 + tags.len() * 12;
+        for &(signature, tag) in &tags {
+            data.extend_from_slice(signature);
+            data.extend_from_slice(&(offset as u32).to_be_bytes());
+            data.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+            offset += tag.len();
+        }
+        for &(_, tag) in &tags {
+            data.extend_from_slice(tag);
+        }
+        data
+    }
+
+    const SRGB_CURVE: [f32; 5] = [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045];
+
+    #[test]
+    fn test_recognize_standard_profiles() {
+        let srgb = build_profile(&SRGB_COLORANTS, 3, &SRGB_CURVE);
+        assert_eq!(ImageColorSpace::from_icc_profile(&srgb), ImageColorSpace::Srgb);
+        let display_p3 = build_profile(&DISPLAY_P3_COLORANTS, 3, &SRGB_CURVE);
+        assert_eq!(ImageColorSpace::from_icc_profile(&display_p3), ImageColorSpace::DisplayP3);
+
+        // sRGB's primaries with a plain gamma curve aren't sRGB.
+        let gamma_2_2 = build_profile(&SRGB_COLORANTS, 0, &[2.2]);
+        match ImageColorSpace::from_icc_profile(&gamma_2_2) {
+            ImageColorSpace::Icc(_) => {}
+            color_space => panic!("Expected an ICC color space, got {:?}", color_space),
+        }
+    }
+
+    #[test]
+    fn test_reject_unsupported_profiles() {
+        assert_eq!(IccProfile::parse(&[0; 64]).unwrap_err(), IccProfileError::Truncated);
+        let mut cmyk = build_profile(&SRGB_COLORANTS, 3, &SRGB_CURVE);
+        cmyk[16..20].copy_from_slice(b"CMYK");
+        assert_eq!(IccProfile::parse(&cmyk).unwrap_err(), IccProfileError::NotRgb);
+        assert_eq!(ImageColorSpace::from_icc_profile(&cmyk), ImageColorSpace::Srgb);
+    }
+
+    #[test]
+    fn test_convert_display_p3_to_srgb() {
+        let pixels = [
+            ColorU::new(255, 255, 255, 255),
+            ColorU::new(0, 0, 0, 128),
+            ColorU::new(255, 0, 0, 255),
+            ColorU::new(128, 128, 128, 255),
+        ];
+        let converted = ImageColorSpace::DisplayP3.convert_to_srgb(&pixels);
+
+        // White, black, and grays stay put; alpha is untouched.
+        assert_eq!(converted[0], ColorU::new(255, 255, 255, 255));
+        assert_eq!(converted[1], ColorU::new(0, 0, 0, 128));
+        for &channel in &[converted[3].r, converted[3].g, converted[3].b] {
+            assert!((channel as i32 - 128).abs() <= 1);
+        }
+        // P3's red lies outside sRGB, so it clips to sRGB's red.
+        assert_eq!(converted[2], ColorU::new(255, 0, 0, 255));
+
+        // An ICC profile describing P3 converts the same way.
+        let profile = build_profile(&DISPLAY_P3_COLORANTS, 3, &SRGB_CURVE);
+        let icc = ImageColorSpace::Icc(Arc::new(IccProfile::parse(&profile).unwrap()));
+        let icc_converted = icc.convert_to_srgb(&pixels);
+        for (a, b) in icc_converted.iter().zip(converted.iter()) {
+            assert!((a.r as i32 - b.r as i32).abs() <= 1);
+            assert!((a.g as i32 - b.g as i32).abs() <= 1);
+            assert!((a.b as i32 - b.b as i32).abs() <= 1);
+        }
+    }
+}
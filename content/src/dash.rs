@@ -10,16 +10,45 @@
 
 //! Transforms a stroke into a dashed stroke.
 
+use crate::orientation::Orientation;
 use crate::outline::{Contour, ContourIterFlags, Outline, PushSegmentFlags};
+use crate::segment::Segment;
 use std::mem;
 
 const EPSILON: f32 = 0.0001;
 
 /// Transforms a stroke into a dashed stroke.
+///
+/// The dash pattern continues from one contour to the next. On a closed contour, a dash that runs
+/// across the starting point is a single dash, so changing the offset makes dashes flow around
+/// the contour without a seam.
 pub struct OutlineDash<'a> {
     input: &'a Outline,
     output: Outline,
     state: DashState<'a>,
+    direction: DashDirection,
+}
+
+/// Which way dashes run around closed contours.
+///
+/// Increasing the dash offset moves dashes backward along the path, so this determines which way
+/// animated dashes flow, whichever way the contours were drawn. Open contours are always dashed
+/// from their start.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DashDirection {
+    /// Dashes run the way each contour is drawn. This is the default.
+    Path,
+    /// Dashes run clockwise, with Y down.
+    Clockwise,
+    /// Dashes run counterclockwise, with Y down.
+    Counterclockwise,
+}
+
+impl Default for DashDirection {
+    #[inline]
+    fn default() -> DashDirection {
+        DashDirection::Path
+    }
 }
 
 impl<'a> OutlineDash<'a> {
@@ -33,50 +62,93 @@ impl<'a> OutlineDash<'a> {
     /// * `dashes`: The list of dashes, specified as alternating pixel lengths of lines and gaps
     ///   that describe the pattern. See
     ///   <https://developer.mozilla.org/en-US/docs/Web/API/CanvasRenderingContext2D/setLineDash>.
+    ///   A pattern without a positive, finite length leaves the stroke solid.
     ///
     /// * `offset`: The line dash offset, or "phase". See
     ///   <https://developer.mozilla.org/en-US/docs/Web/API/CanvasRenderingContext2D/lineDashOffset>.
     #[inline]
     pub fn new(input: &'a Outline, dashes: &'a [f32], offset: f32) -> OutlineDash<'a> {
-        OutlineDash { input, output: Outline::new(), state: DashState::new(dashes, offset) }
+        OutlineDash {
+            input,
+            output: Outline::new(),
+            state: DashState::new(dashes, offset),
+            direction: DashDirection::Path,
+        }
+    }
+
+    /// Sets which way dashes run around closed contours.
+    #[inline]
+    pub fn set_direction(&mut self, direction: DashDirection) {
+        self.direction = direction;
     }
 
     /// Performs the dashing operation.
     ///
     /// The results can be retrieved with the `into_outline()` method.
     pub fn dash(&mut self) {
+        if !self.state.is_valid() {
+            self.output = self.input.clone();
+            return;
+        }
+
         for contour in &self.input.contours {
-            ContourDash::new(contour, &mut self.output, &mut self.state).dash()
+            let reversed = contour.is_closed() && match self.direction {
+                DashDirection::Path => false,
+                DashDirection::Clockwise => Orientation::from_contour(contour) != Orientation::Cw,
+                DashDirection::Counterclockwise => {
+                    Orientation::from_contour(contour) != Orientation::Ccw
+                }
+            };
+            ContourDash::new(contour, reversed, &mut self.output, &mut self.state).dash()
         }
     }
 
     /// Returns the resulting dashed outline.
-    pub fn into_outline(mut self) -> Outline {
-        if self.state.is_on() {
-            self.output.push_contour(self.state.output);
-        }
+    #[inline]
+    pub fn into_outline(self) -> Outline {
         self.output
     }
 }
 
 struct ContourDash<'a, 'b, 'c> {
     input: &'a Contour,
+    reversed: bool,
     output: &'b mut Outline,
     state: &'c mut DashState<'a>,
 }
 
 impl<'a, 'b, 'c> ContourDash<'a, 'b, 'c> {
-    fn new(input: &'a Contour, output: &'b mut Outline, state: &'c mut DashState<'a>)
+    fn new(input: &'a Contour,
+           reversed: bool,
+           output: &'b mut Outline,
+           state: &'c mut DashState<'a>)
            -> ContourDash<'a, 'b, 'c> {
-        ContourDash { input, output, state }
+        ContourDash { input, reversed, output, state }
     }
 
     fn dash(&mut self) {
-        let mut iterator = self.input.iter(ContourIterFlags::empty());
+        // A reversed closed contour is traced backward from the same starting point, so the
+        // pattern keeps its place.
+        let mut segments: Vec<Segment> = self.input.iter(ContourIterFlags::empty()).collect();
+        if self.reversed {
+            segments.reverse();
+            for segment in &mut segments {
+                *segment = segment.reversed();
+            }
+        }
+
+        // On a closed contour, the dash that the contour starts in is held back, so that it can be
+        // joined to the dash that the contour ends in.
+        let closed = self.input.is_closed();
+        let starts_on = self.state.is_on();
+        let mut leading_dash = None;
+        let mut dash_ended = false;
+
+        let mut segments = segments.into_iter();
         let mut queued_segment = None;
         loop {
             if queued_segment.is_none() {
-                match iterator.next() {
+                match segments.next() {
                     None => break,
                     Some(segment) => queued_segment = Some(segment),
                 }
@@ -101,17 +173,33 @@ impl<'a, 'b, 'c> ContourDash<'a, 'b, 'c> {
             self.state.distance_left -= distance;
             if self.state.distance_left < EPSILON {
                 if self.state.is_on() {
-                    self.output.push_contour(mem::replace(&mut self.state.output, Contour::new()));
+                    let dash = mem::replace(&mut self.state.output, Contour::new());
+                    if closed && starts_on && !dash_ended {
+                        leading_dash = Some(dash);
+                    } else {
+                        self.output.push_contour(dash);
+                    }
                 }
+                dash_ended = true;
+                self.state.next_dash();
+            }
+        }
 
-                self.state.current_dash_index += 1;
-                if self.state.current_dash_index == self.state.dashes.len() {
-                    self.state.current_dash_index = 0;
+        let mut trailing_dash = mem::replace(&mut self.state.output, Contour::new());
+        match leading_dash {
+            Some(leading_dash) if self.state.is_on() => {
+                for segment in leading_dash.iter(ContourIterFlags::IGNORE_CLOSE_SEGMENT) {
+                    trailing_dash.push_segment(&segment, PushSegmentFlags::empty());
                 }
-
-                self.state.distance_left = self.state.dashes[self.state.current_dash_index];
             }
+            Some(leading_dash) => self.output.push_contour(leading_dash),
+            None if closed && starts_on && !dash_ended => {
+                // One dash runs all the way around.
+                trailing_dash.close();
+            }
+            None => {}
         }
+        self.output.push_contour(trailing_dash);
     }
 }
 
@@ -123,26 +211,50 @@ struct DashState<'a> {
 }
 
 impl<'a> DashState<'a> {
-    fn new(dashes: &'a [f32], mut offset: f32) -> DashState<'a> {
-        let total: f32 = dashes.iter().cloned().sum();
-        offset %= total;
+    fn new(dashes: &'a [f32], offset: f32) -> DashState<'a> {
+        let mut state = DashState {
+            output: Contour::new(),
+            dashes,
+            current_dash_index: 0,
+            distance_left: 0.0,
+        };
+        if !state.is_valid() {
+            return state;
+        }
 
-        let mut current_dash_index = 0;
-        while current_dash_index < dashes.len() {
-            let dash = dashes[current_dash_index];
+        // Negative offsets start partway through the last period.
+        let total: f32 = dashes.iter().sum();
+        let mut offset = if offset.is_finite() { offset.rem_euclid(total) } else { 0.0 };
+        while state.current_dash_index < dashes.len() {
+            let dash = dashes[state.current_dash_index];
             if offset < dash {
                 break;
             }
             offset -= dash;
-            current_dash_index += 1;
+            state.current_dash_index += 1;
         }
 
-        DashState {
-            output: Contour::new(),
-            dashes,
-            current_dash_index,
-            distance_left: offset,
+        // Rounding can leave the offset at the very end of the pattern.
+        if state.current_dash_index == dashes.len() {
+            state.current_dash_index = 0;
+            offset = 0.0;
+        }
+        state.distance_left = dashes[state.current_dash_index] - offset;
+        state
+    }
+
+    // Whether the pattern has a positive, finite length, without which dashing wouldn't advance.
+    fn is_valid(&self) -> bool {
+        self.dashes.iter().all(|&dash| dash.is_finite() && dash >= 0.0) &&
+            self.dashes.iter().sum::<f32>() > 0.0
+    }
+
+    fn next_dash(&mut self) {
+        self.current_dash_index += 1;
+        if self.current_dash_index == self.dashes.len() {
+            self.current_dash_index = 0;
         }
+        self.distance_left = self.dashes[self.current_dash_index];
     }
 
     #[inline]
@@ -150,3 +262,85 @@ impl<'a> DashState<'a> {
         self.current_dash_index % 2 == 0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{DashDirection, OutlineDash};
+    use crate::outline::{Contour, Outline};
+    use pathfinder_geometry::vector::{Vector2F, vec2f};
+
+    fn assert_near(actual: Option<Vector2F>, expected: Vector2F) {
+        let actual = actual.unwrap();
+        assert!((actual - expected).length() < 0.001, "{:?} != {:?}", actual, expected);
+    }
+
+    // A 10-unit square, drawn clockwise from the origin.
+    fn square() -> Outline {
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_endpoint(vec2f(10.0, 0.0));
+        contour.push_endpoint(vec2f(10.0, 10.0));
+        contour.push_endpoint(vec2f(0.0, 10.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    fn dash(outline: &Outline, dashes: &[f32], offset: f32, direction: DashDirection)
+            -> Outline {
+        let mut dash = OutlineDash::new(outline, dashes, offset);
+        dash.set_direction(direction);
+        dash.dash();
+        dash.into_outline()
+    }
+
+    #[test]
+    fn test_dashes_start_at_the_offset() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_endpoint(vec2f(10.0, 0.0));
+        let mut line = Outline::new();
+        line.push_contour(contour);
+
+        let dashed = dash(&line, &[2.0, 2.0], 0.0, DashDirection::Path);
+        assert_eq!(dashed.contours().len(), 3);
+        assert_near(dashed.contours()[0].first_position(), vec2f(0.0, 0.0));
+        assert_near(dashed.contours()[0].last_position(), vec2f(2.0, 0.0));
+
+        // A negative offset moves the pattern forward.
+        let dashed = dash(&line, &[2.0, 2.0], -1.0, DashDirection::Path);
+        assert_near(dashed.contours()[0].first_position(), vec2f(1.0, 0.0));
+        assert_near(dashed.contours()[0].last_position(), vec2f(3.0, 0.0));
+    }
+
+    #[test]
+    fn test_dashes_join_across_the_start_of_closed_contours() {
+        // The pattern ends halfway through a dash, which continues into the first one.
+        let dashed = dash(&square(), &[4.0, 4.0], 2.0, DashDirection::Path);
+        assert_eq!(dashed.contours().len(), 5);
+        let joined = dashed.contours().last().unwrap();
+        assert_near(joined.first_position(), vec2f(0.0, 2.0));
+        assert_near(joined.last_position(), vec2f(2.0, 0.0));
+
+        // A dash longer than the contour goes all the way around.
+        let dashed = dash(&square(), &[50.0, 4.0], 0.0, DashDirection::Path);
+        assert_eq!(dashed.contours().len(), 1);
+        assert!(dashed.contours()[0].is_closed());
+    }
+
+    #[test]
+    fn test_dash_direction_reverses_closed_contours() {
+        let clockwise = dash(&square(), &[4.0, 4.0], 2.0, DashDirection::Clockwise);
+        assert_eq!(clockwise, dash(&square(), &[4.0, 4.0], 2.0, DashDirection::Path));
+
+        // The square is traced the other way from the same start, so the dashes are mirrored.
+        let counterclockwise = dash(&square(), &[4.0, 4.0], 2.0, DashDirection::Counterclockwise);
+        assert_eq!(counterclockwise.contours().len(), 5);
+        assert_near(counterclockwise.contours()[0].first_position(), vec2f(0.0, 6.0));
+        assert_near(counterclockwise.contours()[0].last_position(), vec2f(0.0, 10.0));
+        let joined = counterclockwise.contours().last().unwrap();
+        assert_near(joined.first_position(), vec2f(2.0, 0.0));
+        assert_near(joined.last_position(), vec2f(0.0, 2.0));
+    }
+}
@@ -0,0 +1,323 @@
+// pathfinder/content/src/gradient_mesh.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Gradient meshes made of Coons patches, like SVG 2's `<meshgradient>` and the mesh gradients
+//! of Illustrator and Inkscape.
+//!
+//! The renderer has no mesh gradient shader, so a mesh is drawn as a pattern:
+//! `GradientMesh::to_pattern()` subdivides each patch into small quads on the CPU and rasterizes
+//! them into an image, interpolating the corner colors across each patch.
+
+use crate::pattern::{Image, Pattern};
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f, vec2i};
+use std::sync::Arc;
+
+// The largest size of the quads that patches are subdivided into, in pixels.
+const MAX_SUBDIVISION_SIZE: f32 = 4.0;
+// The most quads a patch is subdivided into along each side.
+const MAX_SUBDIVISIONS: usize = 64;
+
+/// A mesh of Coons patches, each of which blends the colors at its four corners.
+///
+/// Patches are painted in order, so later patches cover earlier ones where they overlap.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct GradientMesh {
+    patches: Vec<CoonsPatch>,
+}
+
+/// A patch bounded by four cubic Bézier curves, with a color at each corner.
+///
+/// The layout matches a `<meshpatch>` in SVG 2: the edges go around the patch from corner 0, and
+/// each edge starts at the corner with the same index.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CoonsPatch {
+    /// The control points of the top (from corner 0 to 1), right (1 to 2), bottom (2 to 3), and
+    /// left (3 to 0) edges.
+    pub edges: [[Vector2F; 4]; 4],
+    /// The colors at the corners. Colors are interpolated without premultiplying them.
+    pub colors: [ColorU; 4],
+}
+
+impl GradientMesh {
+    /// Creates an empty gradient mesh.
+    #[inline]
+    pub fn new() -> GradientMesh {
+        GradientMesh::default()
+    }
+
+    /// Adds a patch on top of the existing ones.
+    #[inline]
+    pub fn push_patch(&mut self, patch: CoonsPatch) {
+        self.patches.push(patch);
+    }
+
+    /// Returns the patches in this mesh, in painting order.
+    #[inline]
+    pub fn patches(&self) -> &[CoonsPatch] {
+        &self.patches
+    }
+
+    /// Returns true if this mesh has no patches.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    /// Applies an affine transform to every patch of this mesh.
+    pub fn apply_transform(&mut self, transform: Transform2F) {
+        for patch in &mut self.patches {
+            for edge in &mut patch.edges {
+                for point in edge {
+                    *point = transform * *point;
+                }
+            }
+        }
+    }
+
+    /// Rasterizes this mesh into a pattern with `scale` pixels per unit, positioned so that it
+    /// covers the mesh in scene coordinates.
+    ///
+    /// Returns `None` if the mesh is empty.
+    pub fn to_pattern(&self, scale: f32) -> Option<Pattern> {
+        let scale = scale.max(f32::EPSILON);
+        let (image, origin) = self.rasterize(scale)?;
+        let mut pattern = Pattern::from_image(image);
+        pattern.apply_transform(Transform2F::from_translation(origin) *
+                                Transform2F::from_scale(1.0 / scale));
+        Some(pattern)
+    }
+
+    /// Rasterizes this mesh into an image with `scale` pixels per unit.
+    ///
+    /// Returns the image, and the position of its upper left corner in scene coordinates, or
+    /// `None` if the mesh is empty. Pixels more than half a pixel away from every patch are
+    /// transparent.
+    pub fn rasterize(&self, scale: f32) -> Option<(Image, Vector2F)> {
+        let scale = scale.max(f32::EPSILON);
+        let grids: Vec<_> =
+            self.patches.iter().map(|patch| PatchGrid::new(patch, scale)).collect();
+        let mut grid_points = grids.iter().flat_map(|grid| grid.points.iter());
+        let first_point = *grid_points.next()?;
+        let bounds = grid_points.fold(RectF::from_points(first_point, first_point),
+                                      |bounds, &point| bounds.union_point(point));
+
+        // Pad by a pixel so that bilinear filtering doesn't cut into the edges of the mesh.
+        let origin = (bounds.origin() * scale).floor() - vec2f(1.0, 1.0);
+        let size = ((bounds.lower_right() * scale).ceil() - origin + vec2f(1.0, 1.0)).to_i32();
+        let size = vec2i(size.x().max(1), size.y().max(1));
+        let mut pixels = vec![ColorU::transparent_black(); size.x() as usize * size.y() as usize];
+
+        for (patch, grid) in self.patches.iter().zip(grids.iter()) {
+            let colors = patch.colors.map(|color| color.to_f32());
+            for row in 0..grid.subdivisions {
+                for column in 0..grid.subdivisions {
+                    let corners = [(column, row), (column + 1, row), (column + 1, row + 1),
+                                   (column, row + 1)].map(|(column, row)| {
+                        let params = vec2f(column as f32, row as f32) / grid.subdivisions as f32;
+                        (grid.point(column, row) * scale - origin, params)
+                    });
+                    for triangle in [[corners[0], corners[1], corners[2]],
+                                     [corners[0], corners[2], corners[3]]] {
+                        fill_triangle(&mut pixels, size.x(), &triangle, &colors);
+                    }
+                }
+            }
+        }
+
+        let image = Image::new(size, Arc::new(pixels));
+        Some((image, origin / scale))
+    }
+}
+
+impl CoonsPatch {
+    /// Creates a patch with straight edges between the given corners.
+    pub fn from_corners(corners: [Vector2F; 4], colors: [ColorU; 4]) -> CoonsPatch {
+        let edges = [0, 1, 2, 3].map(|index| {
+            let (from, to) = (corners[index], corners[(index + 1) % 4]);
+            [from, from.lerp(to, 1.0 / 3.0), from.lerp(to, 2.0 / 3.0), to]
+        });
+        CoonsPatch { edges, colors }
+    }
+
+    /// Returns the point of the patch at the parameters `(u, v)`, each from 0.0 to 1.0.
+    ///
+    /// `u` runs along the top edge and `v` along the left edge, so `(0, 0)` is corner 0 and
+    /// `(1, 1)` is corner 2.
+    pub fn point(&self, u: f32, v: f32) -> Vector2F {
+        let top = cubic_point(&self.edges[0], u);
+        let right = cubic_point(&self.edges[1], v);
+        let bottom = cubic_point(&self.edges[2], 1.0 - u);
+        let left = cubic_point(&self.edges[3], 1.0 - v);
+        let corners = [self.edges[0][0], self.edges[1][0], self.edges[2][0], self.edges[3][0]];
+        let ruled_uv = top * (1.0 - v) + bottom * v;
+        let ruled_vu = left * (1.0 - u) + right * u;
+        let bilinear = corners[0].lerp(corners[1], u).lerp(corners[3].lerp(corners[2], u), v);
+        ruled_uv + ruled_vu - bilinear
+    }
+
+    /// Returns the color of the patch at the parameters `(u, v)`.
+    pub fn color(&self, u: f32, v: f32) -> ColorU {
+        let colors = self.colors.map(|color| color.to_f32());
+        bilinear_color(&colors, vec2f(u, v)).to_u8()
+    }
+}
+
+// The points of a patch evaluated on a regular grid of parameters.
+struct PatchGrid {
+    subdivisions: usize,
+    points: Vec<Vector2F>,
+}
+
+impl PatchGrid {
+    fn new(patch: &CoonsPatch, scale: f32) -> PatchGrid {
+        // Subdivide finely enough that the longest edge is split into small quads.
+        let longest_edge = patch.edges.iter().map(|edge| {
+            (edge[1] - edge[0]).length() + (edge[2] - edge[1]).length() +
+                (edge[3] - edge[2]).length()
+        }).fold(0.0, f32::max);
+        let subdivisions = (longest_edge * scale / MAX_SUBDIVISION_SIZE).ceil() as usize;
+        let subdivisions = subdivisions.max(1).min(MAX_SUBDIVISIONS);
+
+        let mut points = Vec::with_capacity((subdivisions + 1) * (subdivisions + 1));
+        for row in 0..=subdivisions {
+            for column in 0..=subdivisions {
+                points.push(patch.point(column as f32 / subdivisions as f32,
+                                        row as f32 / subdivisions as f32));
+            }
+        }
+        PatchGrid { subdivisions, points }
+    }
+
+    #[inline]
+    fn point(&self, column: usize, row: usize) -> Vector2F {
+        self.points[row * (self.subdivisions + 1) + column]
+    }
+}
+
+// Fills the pixels whose centers lie within a triangle, or within half a pixel of its edges so
+// that neighboring triangles meet without cracks. Each vertex carries its patch parameters, which
+// are interpolated to find the color.
+fn fill_triangle(pixels: &mut [ColorU],
+                 width: i32,
+                 vertices: &[(Vector2F, Vector2F); 3],
+                 colors: &[ColorF; 4]) {
+    let [(p0, uv0), (p1, uv1), (p2, uv2)] = *vertices;
+    let area = (p1 - p0).det(p2 - p0);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+
+    let height = pixels.len() as i32 / width;
+    let min = (p0.min(p1).min(p2) - vec2f(0.5, 0.5)).floor().to_i32();
+    let max = (p0.max(p1).max(p2) + vec2f(0.5, 0.5)).ceil().to_i32();
+    let edges = [(p1, p2), (p2, p0), (p0, p1)];
+    let edge_lengths = edges.map(|(from, to)| (to - from).length().max(f32::EPSILON));
+
+    for y in min.y().max(0)..(max.y() + 1).min(height) {
+        for x in min.x().max(0)..(max.x() + 1).min(width) {
+            let center = vec2f(x as f32 + 0.5, y as f32 + 0.5);
+            let mut weights = [0.0; 3];
+            let mut inside = true;
+            for (index, &(from, to)) in edges.iter().enumerate() {
+                let edge_function = (to - from).det(center - from) / area;
+                let distance = edge_function * area.abs() / edge_lengths[index];
+                inside &= distance >= -0.5;
+                weights[index] = edge_function.max(0.0);
+            }
+            if !inside {
+                continue;
+            }
+
+            let total = weights[0] + weights[1] + weights[2];
+            if total <= 0.0 {
+                continue;
+            }
+            let params = (uv0 * weights[0] + uv1 * weights[1] + uv2 * weights[2]) / total;
+            pixels[(y * width + x) as usize] = bilinear_color(colors, params).to_u8();
+        }
+    }
+}
+
+fn bilinear_color(colors: &[ColorF; 4], params: Vector2F) -> ColorF {
+    let (u, v) = (params.x().max(0.0).min(1.0), params.y().max(0.0).min(1.0));
+    colors[0].lerp(colors[1], u).lerp(colors[3].lerp(colors[2], u), v)
+}
+
+fn cubic_point(points: &[Vector2F; 4], t: f32) -> Vector2F {
+    let p01 = points[0].lerp(points[1], t);
+    let p12 = points[1].lerp(points[2], t);
+    let p23 = points[2].lerp(points[3], t);
+    p01.lerp(p12, t).lerp(p12.lerp(p23, t), t)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CoonsPatch, GradientMesh};
+    use pathfinder_color::ColorU;
+    use pathfinder_geometry::vector::{Vector2F, vec2f};
+
+    fn assert_near(actual: Vector2F, expected: Vector2F) {
+        assert!((actual - expected).length() < 1.0e-4, "{:?} != {:?}", actual, expected);
+    }
+
+    fn square_patch() -> CoonsPatch {
+        CoonsPatch::from_corners([vec2f(0.0, 0.0), vec2f(16.0, 0.0), vec2f(16.0, 16.0),
+                                  vec2f(0.0, 16.0)],
+                                 [ColorU::new(255, 0, 0, 255), ColorU::new(0, 255, 0, 255),
+                                  ColorU::new(0, 0, 255, 255), ColorU::new(255, 255, 255, 255)])
+    }
+
+    #[test]
+    fn test_patch_interpolates_corners() {
+        let patch = square_patch();
+        assert_near(patch.point(0.0, 0.0), vec2f(0.0, 0.0));
+        assert_near(patch.point(1.0, 1.0), vec2f(16.0, 16.0));
+        assert_near(patch.point(0.5, 0.25), vec2f(8.0, 4.0));
+        assert_eq!(patch.color(1.0, 0.0), ColorU::new(0, 255, 0, 255));
+        assert_eq!(patch.color(0.0, 1.0), ColorU::new(255, 255, 255, 255));
+    }
+
+    #[test]
+    fn test_curved_edges_bend_the_patch() {
+        let mut patch = square_patch();
+        // Bow the top edge upward.
+        patch.edges[0][1] = vec2f(16.0 / 3.0, -8.0);
+        patch.edges[0][2] = vec2f(32.0 / 3.0, -8.0);
+        assert_near(patch.point(0.5, 0.0), vec2f(8.0, -6.0));
+        assert!(patch.point(0.5, 0.5).y() < 8.0);
+        assert_near(patch.point(0.5, 1.0), vec2f(8.0, 16.0));
+    }
+
+    #[test]
+    fn test_rasterize_covers_the_mesh() {
+        let mut mesh = GradientMesh::new();
+        assert!(mesh.rasterize(1.0).is_none());
+        mesh.push_patch(square_patch());
+
+        let (image, origin) = mesh.rasterize(1.0).unwrap();
+        assert_eq!(origin, vec2f(-1.0, -1.0));
+        assert!(image.size().x() >= 18 && image.size().y() >= 18);
+        let pixel = |position: Vector2F| {
+            let position = (position - origin).to_i32();
+            image.pixels()[(position.y() * image.size().x() + position.x()) as usize]
+        };
+
+        let top_left = pixel(vec2f(0.0, 0.0));
+        assert!(top_left.r > 230 && top_left.g < 25 && top_left.b < 25);
+        let bottom_right = pixel(vec2f(15.0, 15.0));
+        assert!(bottom_right.b > 230 && bottom_right.r < 25);
+        assert!(pixel(vec2f(8.0, 8.0)).is_opaque());
+        // The padding takes the colors of the edges, so that filtering doesn't fade them out.
+        assert!(image.pixels()[0].is_opaque());
+    }
+}
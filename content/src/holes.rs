@@ -0,0 +1,97 @@
+// pathfinder/content/src/holes.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Explicit control over which contours of an outline are filled and which are holes.
+//!
+//! With the nonzero fill rule, a contour cuts a hole only if it winds the opposite way from the
+//! contour around it. Procedurally generated geometry often gets winding wrong or leaves it
+//! arbitrary, and fixing it at the source can be impractical. `Outline::apply_contour_roles()`
+//! instead tags each contour as filled or a hole and rewinds it to match, whatever its original
+//! direction.
+//!
+//! After rewinding, the winding number at a point is the number of filled contours around it
+//! minus the number of holes. Fill the outline with `FillRule::Winding`: holes must lie within
+//! filled contours, and holes mustn't overlap one another, or the overlap is filled again.
+
+use crate::orientation::Orientation;
+use crate::outline::Outline;
+
+/// Whether a contour adds to the filled area or cuts a hole in it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ContourRole {
+    /// The area inside the contour is filled.
+    Fill,
+    /// The area inside the contour is cut out of the filled contours around it.
+    Hole,
+}
+
+impl Outline {
+    /// Rewinds each contour so that it fills or cuts a hole according to its entry in `roles`,
+    /// for use with `FillRule::Winding`.
+    ///
+    /// Filled contours wind clockwise and holes counterclockwise, with Y down. Panics if `roles`
+    /// doesn't have one entry per contour.
+    pub fn apply_contour_roles(&mut self, roles: &[ContourRole]) {
+        assert_eq!(roles.len(), self.contours.len(), "Need one role per contour!");
+        for (contour, &role) in self.contours.iter_mut().zip(roles.iter()) {
+            let orientation = match role {
+                ContourRole::Fill => Orientation::Cw,
+                ContourRole::Hole => Orientation::Ccw,
+            };
+            if !contour.is_empty() && Orientation::from_contour(contour) != orientation {
+                contour.reverse();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ContourRole;
+    use crate::orientation::Orientation;
+    use crate::outline::{Contour, ContourIterFlags, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_contours_are_rewound() {
+        // A square with a curved hole, both wound the same way.
+        let mut hole = Contour::new();
+        hole.push_endpoint(vec2f(4.0, 4.0));
+        hole.push_cubic(vec2f(5.0, 3.0), vec2f(6.0, 3.0), vec2f(6.0, 4.0));
+        hole.push_endpoint(vec2f(6.0, 6.0));
+        hole.push_endpoint(vec2f(4.0, 6.0));
+        hole.close();
+        let mut outline = Outline::from_rect(RectF::new(vec2f(0.0, 0.0), vec2f(10.0, 10.0)));
+        let original_hole = hole.clone();
+        outline.push_contour(hole);
+        let square_orientation = Orientation::from_contour(&outline.contours()[0]);
+        assert_eq!(square_orientation, Orientation::from_contour(&original_hole));
+
+        let roles = match square_orientation {
+            Orientation::Cw => [ContourRole::Fill, ContourRole::Hole],
+            Orientation::Ccw => [ContourRole::Hole, ContourRole::Fill],
+        };
+        outline.apply_contour_roles(&roles);
+        assert_eq!(Orientation::from_contour(&outline.contours()[0]), square_orientation);
+        assert_ne!(Orientation::from_contour(&outline.contours()[1]), square_orientation);
+
+        // The reversed hole traces the same curve backward.
+        let segments: Vec<_> = outline.contours()[1].iter(ContourIterFlags::empty()).collect();
+        let original_segments: Vec<_> = original_hole.iter(ContourIterFlags::empty()).collect();
+        assert_eq!(segments.len(), original_segments.len());
+        let last_segment = segments[segments.len() - 2];
+        assert!(last_segment.is_cubic());
+        assert_eq!(last_segment.baseline.from(), vec2f(6.0, 4.0));
+        assert_eq!(last_segment.ctrl.from(), vec2f(6.0, 3.0));
+        assert_eq!(last_segment.ctrl.to(), vec2f(5.0, 3.0));
+        assert_eq!(last_segment.baseline.to(), vec2f(4.0, 4.0));
+    }
+}
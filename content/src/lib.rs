@@ -17,15 +17,22 @@ extern crate bitflags;
 #[macro_use]
 extern crate log;
 
+pub mod arc_length;
 pub mod clip;
+pub mod color_space;
 pub mod dash;
 pub mod effects;
 pub mod fill;
 pub mod gradient;
+pub mod gradient_mesh;
+pub mod holes;
+#[cfg(feature = "pf-lyon")]
+pub mod lyon;
 pub mod orientation;
 pub mod outline;
 pub mod pattern;
 pub mod render_target;
+pub mod sanitize;
 pub mod segment;
 pub mod stroke;
 pub mod transform;
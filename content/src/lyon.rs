@@ -0,0 +1,298 @@
+// pathfinder/content/src/lyon.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversions between Pathfinder outlines and segments and `lyon_path` paths and events.
+//!
+//! These allow lyon's tooling (tessellation, path operations, flattening) to be used alongside
+//! Pathfinder rendering. Lyon paths convert to outlines with `Outline::from()`, and outlines to
+//! lyon paths with `lyon_path::Path::from()`. For streaming, `PathEventsToSegments` and
+//! `SegmentsToPathEvents` adapt iterators in either direction, and `Outline::path_events()`
+//! iterates over an outline as lyon events without allocating.
+
+pub use lyon_path;
+
+use crate::outline::{Contour, ContourIterFlags, Outline};
+use crate::segment::{Segment, SegmentFlags, SegmentKind};
+use lyon_path::math::{Point, point};
+use lyon_path::{Path, PathEvent};
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+
+/// Adapts an iterator over lyon path events into an iterator over segments, flagged so that
+/// `Outline::from_segments()` reconstructs the contours.
+///
+/// Each subpath's first segment has `SegmentFlags::FIRST_IN_SUBPATH` set, and a closed subpath is
+/// followed by an empty segment with `SegmentFlags::CLOSES_SUBPATH` set. Subpaths without any
+/// segments produce nothing.
+pub struct PathEventsToSegments<I> where I: Iterator<Item = PathEvent> {
+    events: I,
+    first_in_subpath: bool,
+}
+
+/// Adapts an iterator over segments into an iterator over lyon path events.
+///
+/// A new subpath is begun at the first segment, at any segment with
+/// `SegmentFlags::FIRST_IN_SUBPATH` set, and wherever a segment doesn't start where the previous
+/// one ended. A segment with `SegmentFlags::CLOSES_SUBPATH` set ends its subpath as closed; other
+/// subpaths end open.
+pub struct SegmentsToPathEvents<I> where I: Iterator<Item = Segment> {
+    segments: I,
+    // The start and current end of the open subpath, if any.
+    subpath: Option<(Vector2F, Vector2F)>,
+    // A segment that had to wait while the previous subpath was ended.
+    pending: Option<Segment>,
+}
+
+impl<I> PathEventsToSegments<I> where I: Iterator<Item = PathEvent> {
+    /// Wraps an iterator over lyon path events.
+    #[inline]
+    pub fn new(events: I) -> PathEventsToSegments<I> {
+        PathEventsToSegments { events, first_in_subpath: false }
+    }
+}
+
+impl<I> Iterator for PathEventsToSegments<I> where I: Iterator<Item = PathEvent> {
+    type Item = Segment;
+
+    fn next(&mut self) -> Option<Segment> {
+        loop {
+            let mut segment = match self.events.next()? {
+                PathEvent::Begin { .. } => {
+                    self.first_in_subpath = true;
+                    continue;
+                }
+                PathEvent::End { close, .. } => {
+                    let started = !self.first_in_subpath;
+                    self.first_in_subpath = false;
+                    if !close || !started {
+                        continue;
+                    }
+                    let mut segment = Segment::none();
+                    segment.flags = SegmentFlags::CLOSES_SUBPATH;
+                    segment
+                }
+                PathEvent::Line { from, to } => {
+                    Segment::line(LineSegment2F::new(to_vector(from), to_vector(to)))
+                }
+                PathEvent::Quadratic { from, ctrl, to } => {
+                    Segment::quadratic(LineSegment2F::new(to_vector(from), to_vector(to)),
+                                       to_vector(ctrl))
+                }
+                PathEvent::Cubic { from, ctrl1, ctrl2, to } => {
+                    Segment::cubic(LineSegment2F::new(to_vector(from), to_vector(to)),
+                                   LineSegment2F::new(to_vector(ctrl1), to_vector(ctrl2)))
+                }
+            };
+            if self.first_in_subpath {
+                segment.flags.insert(SegmentFlags::FIRST_IN_SUBPATH);
+                self.first_in_subpath = false;
+            }
+            return Some(segment);
+        }
+    }
+}
+
+impl<I> SegmentsToPathEvents<I> where I: Iterator<Item = Segment> {
+    /// Wraps an iterator over segments.
+    #[inline]
+    pub fn new(segments: I) -> SegmentsToPathEvents<I> {
+        SegmentsToPathEvents { segments, subpath: None, pending: None }
+    }
+}
+
+impl<I> Iterator for SegmentsToPathEvents<I> where I: Iterator<Item = Segment> {
+    type Item = PathEvent;
+
+    fn next(&mut self) -> Option<PathEvent> {
+        loop {
+            let mut segment = match self.pending.take().or_else(|| self.segments.next()) {
+                Some(segment) => segment,
+                None => {
+                    let (first, last) = self.subpath.take()?;
+                    return Some(end_event(first, last, false));
+                }
+            };
+
+            if segment.flags.contains(SegmentFlags::CLOSES_SUBPATH) {
+                match self.subpath.take() {
+                    Some((first, last)) => return Some(end_event(first, last, true)),
+                    None => continue,
+                }
+            }
+            if segment.is_none() {
+                continue;
+            }
+
+            let from = segment.baseline.from();
+            match self.subpath {
+                Some((first, last)) => {
+                    if segment.flags.contains(SegmentFlags::FIRST_IN_SUBPATH) || last != from {
+                        self.subpath = None;
+                        self.pending = Some(segment);
+                        return Some(end_event(first, last, false));
+                    }
+                }
+                None => {
+                    self.subpath = Some((from, from));
+                    segment.flags.remove(SegmentFlags::FIRST_IN_SUBPATH);
+                    self.pending = Some(segment);
+                    return Some(PathEvent::Begin { at: to_point(from) });
+                }
+            }
+
+            let to = segment.baseline.to();
+            if let Some((_, ref mut last)) = self.subpath {
+                *last = to;
+            }
+            return Some(segment_event(&segment));
+        }
+    }
+}
+
+impl Outline {
+    /// Creates an outline from lyon path events.
+    #[inline]
+    pub fn from_path_events<I>(events: I) -> Outline where I: IntoIterator<Item = PathEvent> {
+        Outline::from_segments(PathEventsToSegments::new(events.into_iter()))
+    }
+
+    /// Returns an iterator over this outline as lyon path events.
+    ///
+    /// Each contour becomes one subpath, ended as closed if the contour is closed. The closing
+    /// line is left implicit, as lyon expects.
+    pub fn path_events(&self) -> impl Iterator<Item = PathEvent> + '_ {
+        self.contours().iter().flat_map(Contour::path_events)
+    }
+}
+
+impl Contour {
+    /// Returns an iterator over this contour as lyon path events, forming a single subpath.
+    pub fn path_events(&self) -> impl Iterator<Item = PathEvent> + '_ {
+        let endpoints = self.first_position().zip(self.last_position());
+        let begin = endpoints.map(|(first, _)| PathEvent::Begin { at: to_point(first) });
+        let end = endpoints.map(|(first, last)| end_event(first, last, self.is_closed()));
+        begin.into_iter()
+             .chain(self.iter(ContourIterFlags::IGNORE_CLOSE_SEGMENT)
+                        .map(|segment| segment_event(&segment)))
+             .chain(end)
+    }
+}
+
+impl<'a> From<&'a Path> for Outline {
+    #[inline]
+    fn from(path: &'a Path) -> Outline {
+        Outline::from_path_events(path.iter())
+    }
+}
+
+impl<'a> From<&'a Outline> for Path {
+    fn from(outline: &'a Outline) -> Path {
+        let mut builder = Path::builder();
+        for event in outline.path_events() {
+            match event {
+                PathEvent::Begin { at } => {
+                    builder.begin(at);
+                }
+                PathEvent::Line { to, .. } => {
+                    builder.line_to(to);
+                }
+                PathEvent::Quadratic { ctrl, to, .. } => {
+                    builder.quadratic_bezier_to(ctrl, to);
+                }
+                PathEvent::Cubic { ctrl1, ctrl2, to, .. } => {
+                    builder.cubic_bezier_to(ctrl1, ctrl2, to);
+                }
+                PathEvent::End { close, .. } => builder.end(close),
+            }
+        }
+        builder.build()
+    }
+}
+
+fn segment_event(segment: &Segment) -> PathEvent {
+    let (from, to) = (to_point(segment.baseline.from()), to_point(segment.baseline.to()));
+    match segment.kind {
+        SegmentKind::Quadratic => {
+            PathEvent::Quadratic { from, ctrl: to_point(segment.ctrl.from()), to }
+        }
+        SegmentKind::Cubic => {
+            PathEvent::Cubic {
+                from,
+                ctrl1: to_point(segment.ctrl.from()),
+                ctrl2: to_point(segment.ctrl.to()),
+                to,
+            }
+        }
+        SegmentKind::None | SegmentKind::Line => PathEvent::Line { from, to },
+    }
+}
+
+#[inline]
+fn end_event(first: Vector2F, last: Vector2F, close: bool) -> PathEvent {
+    PathEvent::End { last: to_point(last), first: to_point(first), close }
+}
+
+#[inline]
+fn to_vector(point: Point) -> Vector2F {
+    vec2f(point.x, point.y)
+}
+
+#[inline]
+fn to_point(vector: Vector2F) -> Point {
+    point(vector.x(), vector.y())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PathEventsToSegments, SegmentsToPathEvents};
+    use crate::outline::Outline;
+    use lyon_path::math::point;
+    use lyon_path::{Path, PathEvent};
+    use pathfinder_geometry::vector::vec2f;
+
+    fn lyon_path() -> Path {
+        let mut builder = Path::builder();
+        builder.begin(point(0.0, 0.0));
+        builder.line_to(point(10.0, 0.0));
+        builder.quadratic_bezier_to(point(15.0, 5.0), point(10.0, 10.0));
+        builder.end(true);
+        builder.begin(point(20.0, 20.0));
+        builder.cubic_bezier_to(point(25.0, 20.0), point(30.0, 25.0), point(30.0, 30.0));
+        builder.end(false);
+        builder.build()
+    }
+
+    #[test]
+    fn test_lyon_to_outline() {
+        let outline = Outline::from(&lyon_path());
+        let contours = outline.contours();
+        assert_eq!(contours.len(), 2);
+        assert!(contours[0].is_closed());
+        assert!(!contours[1].is_closed());
+        assert_eq!(contours[0].len(), 4);
+        assert_eq!(contours[1].len(), 4);
+        assert_eq!(outline.bounds().lower_right(), vec2f(30.0, 30.0));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = lyon_path();
+        let round_tripped = Path::from(&Outline::from(&path));
+        assert_eq!(path.iter().collect::<Vec<_>>(), round_tripped.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_segment_adapters() {
+        let path = lyon_path();
+        let events: Vec<PathEvent> =
+            SegmentsToPathEvents::new(PathEventsToSegments::new(path.iter())).collect();
+        assert_eq!(events, path.iter().collect::<Vec<_>>());
+    }
+}
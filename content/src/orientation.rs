@@ -10,7 +10,7 @@
 
 //! Determining whether an outline is wound clockwise or counterclockwise.
 
-use crate::outline::Outline;
+use crate::outline::{Contour, Outline};
 
 /// Winding order: counterclockwise or clockwise, with Y down.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -26,18 +26,15 @@ impl Orientation {
     ///
     /// This follows the FreeType algorithm.
     pub fn from_outline(outline: &Outline) -> Orientation {
-        let mut area = 0.0;
-        for contour in &outline.contours {
-            let mut prev_position = match contour.last_position() {
-                None => continue,
-                Some(position) => position,
-            };
-            for &next_position in &contour.points {
-                area += prev_position.det(next_position);
-                prev_position = next_position;
-            }
-        }
-        Orientation::from_area(area)
+        Orientation::from_area(outline.contours.iter().map(contour_area).sum())
+    }
+
+    /// Determines whether a single contour is wound counterclockwise or clockwise.
+    ///
+    /// Control points are treated as vertices, which gives the right answer for any contour that
+    /// doesn't intersect itself.
+    pub fn from_contour(contour: &Contour) -> Orientation {
+        Orientation::from_area(contour_area(contour))
     }
 
     fn from_area(area: f32) -> Orientation {
@@ -48,3 +45,17 @@ impl Orientation {
         }
     }
 }
+
+// Twice the signed area of the polygon through the points of a contour.
+fn contour_area(contour: &Contour) -> f32 {
+    let mut area = 0.0;
+    let mut prev_position = match contour.last_position() {
+        None => return area,
+        Some(position) => position,
+    };
+    for &next_position in &contour.points {
+        area += prev_position.det(next_position);
+        prev_position = next_position;
+    }
+    area
+}
@@ -23,13 +23,25 @@ use pathfinder_geometry::unit_vector::UnitVector;
 use pathfinder_geometry::vector::{Vector2F, vec2f};
 use std::f32::consts::PI;
 use std::fmt::{self, Debug, Formatter};
+use std::hash::{Hash, Hasher};
 use std::mem;
 
+/// The grid, in path units, that point coordinates are snapped to by the `Hash` implementations of
+/// `Outline` and `Contour`.
+pub const HASH_QUANTUM: f32 = 1.0 / 256.0;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
 /// A vector path to be filled. Outlines (a.k.a. paths) consist of *contours* (a.k.a. subpaths),
 /// which can be filled according to a fill rule.
 ///
 /// The names "outline" and "contour" come from the TrueType specification. They were chosen to
 /// avoid conflicting with the Rust use of "path" for filesystem paths.
+///
+/// Two outlines are equal if their contours have exactly the same points, point flags, and
+/// closedness. Hashing snaps points to a grid of `HASH_QUANTUM`, which keeps hashes consistent with
+/// equality while making them insensitive to the sign of zero.
 #[derive(Clone)]
 pub struct Outline {
     pub(crate) contours: Vec<Contour>,
@@ -91,6 +103,23 @@ impl Outline {
     /// Creates a new outline from a list of segments.
     #[inline]
     pub fn from_segments<I>(segments: I) -> Outline where I: Iterator<Item = Segment> {
+        Outline::from_segments_with(segments, Contour::push_point)
+    }
+
+    /// Creates a new outline from a list of segments without checking that their points are
+    /// numbers.
+    ///
+    /// This is for untrusted input such as SVG path data. The outline must be sanitized with
+    /// `OutlineSanitizer` before it's rendered.
+    #[inline]
+    pub fn from_segments_unchecked<I>(segments: I) -> Outline where I: Iterator<Item = Segment> {
+        Outline::from_segments_with(segments, Contour::push_point_unchecked)
+    }
+
+    fn from_segments_with<I>(segments: I,
+                             push_point: fn(&mut Contour, Vector2F, PointFlags, bool))
+                             -> Outline
+                             where I: Iterator<Item = Segment> {
         let mut outline = Outline::new();
         let mut current_contour = Contour::new();
 
@@ -101,7 +130,10 @@ impl Outline {
                         .contours
                         .push(mem::replace(&mut current_contour, Contour::new()));
                 }
-                current_contour.push_point(segment.baseline.from(), PointFlags::empty(), true);
+                push_point(&mut current_contour,
+                           segment.baseline.from(),
+                           PointFlags::empty(),
+                           true);
             }
 
             if segment.flags.contains(SegmentFlags::CLOSES_SUBPATH) {
@@ -118,17 +150,19 @@ impl Outline {
             }
 
             if !segment.is_line() {
-                current_contour.push_point(segment.ctrl.from(), PointFlags::CONTROL_POINT_0, true);
+                push_point(&mut current_contour,
+                           segment.ctrl.from(),
+                           PointFlags::CONTROL_POINT_0,
+                           true);
                 if !segment.is_quadratic() {
-                    current_contour.push_point(
-                        segment.ctrl.to(),
-                        PointFlags::CONTROL_POINT_1,
-                        true,
-                    );
+                    push_point(&mut current_contour,
+                               segment.ctrl.to(),
+                               PointFlags::CONTROL_POINT_1,
+                               true);
                 }
             }
 
-            current_contour.push_point(segment.baseline.to(), PointFlags::empty(), true);
+            push_point(&mut current_contour, segment.baseline.to(), PointFlags::empty(), true);
         }
 
         outline.push_contour(current_contour);
@@ -157,6 +191,14 @@ impl Outline {
         self.bounds
     }
 
+    /// Returns the total length of the outline's contours, including the segments that close
+    /// them.
+    pub fn arc_length(&self) -> f32 {
+        self.contours.iter().map(|contour| {
+            contour.iter(ContourIterFlags::empty()).map(|segment| segment.arc_length()).sum::<f32>()
+        }).sum()
+    }
+
     /// Returns a list of the subpaths in this path.
     #[inline]
     pub fn contours(&self) -> &[Contour] {
@@ -306,6 +348,51 @@ impl Outline {
 
         self.contours.extend(other.contours);
     }
+
+    /// Returns a hash of this outline's geometry with points snapped to a grid of the given size.
+    ///
+    /// Unlike the `Hash` implementation, which feeds whatever hasher it's given, this always uses
+    /// the same hash function and byte order, so the result is the same on every platform and
+    /// every run. It's suitable for persistent cache keys.
+    pub fn quantized_hash(&self, quantum: f32) -> u64 {
+        let mut hasher = StableHasher(FNV_OFFSET_BASIS);
+        self.hash_quantized(quantum, &mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns true if the two outlines are equal once their points are snapped to a grid of the
+    /// given size.
+    ///
+    /// Outlines that are equal by this test have the same `quantized_hash()` with that quantum.
+    pub fn quantized_eq(&self, other: &Outline, quantum: f32) -> bool {
+        self.contours.len() == other.contours.len() &&
+            self.contours.iter().zip(other.contours.iter()).all(|(this, other)| {
+                this.quantized_eq(other, quantum)
+            })
+    }
+
+    fn hash_quantized<H>(&self, quantum: f32, state: &mut H) where H: Hasher {
+        state.write(&(self.contours.len() as u64).to_le_bytes());
+        for contour in &self.contours {
+            contour.hash_quantized(quantum, state);
+        }
+    }
+}
+
+impl PartialEq for Outline {
+    #[inline]
+    fn eq(&self, other: &Outline) -> bool {
+        self.contours == other.contours
+    }
+}
+
+impl Eq for Outline {}
+
+impl Hash for Outline {
+    #[inline]
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        self.hash_quantized(HASH_QUANTUM, state)
+    }
 }
 
 impl Debug for Outline {
@@ -562,7 +649,19 @@ impl Contour {
                              flags: PointFlags,
                              update_bounds: bool) {
         debug_assert!(!point.x().is_nan() && !point.y().is_nan());
+        self.push_point_unchecked(point, flags, update_bounds);
+    }
 
+    /// Pushes a point without checking that its coordinates are numbers.
+    ///
+    /// This is for building malformed outlines on purpose, as the fuzzers and the tests of
+    /// `OutlineSanitizer` do. Such outlines must be sanitized before they're rendered.
+    #[doc(hidden)]
+    #[inline]
+    pub fn push_point_unchecked(&mut self,
+                                point: Vector2F,
+                                flags: PointFlags,
+                                update_bounds: bool) {
         if update_bounds {
             let first = self.is_empty();
             union_rect(&mut self.bounds, point, first);
@@ -900,6 +999,34 @@ impl Contour {
         self
     }
 
+    /// Reverses the direction of this contour, which flips the sign of the winding numbers it
+    /// contributes.
+    ///
+    /// The contour traces the same shape from its last endpoint back to its first.
+    pub fn reverse(&mut self) {
+        self.points.reverse();
+        self.flags.reverse();
+
+        // The control points of each cubic curve now appear in the opposite order.
+        let mut point_index = 0;
+        while point_index < self.flags.len() {
+            if self.flags[point_index].contains(PointFlags::CONTROL_POINT_1) {
+                self.flags[point_index] = PointFlags::CONTROL_POINT_0;
+                self.flags[point_index + 1] = PointFlags::CONTROL_POINT_1;
+                point_index += 1;
+            }
+            point_index += 1;
+        }
+    }
+
+    /// Reverses the direction of this contour, returning a new contour instead of mutating this
+    /// one.
+    #[inline]
+    pub fn reversed(mut self) -> Contour {
+        self.reverse();
+        self
+    }
+
     /// Applies a perspective transform to this subpath.
     #[deprecated]
     pub fn apply_perspective(&mut self, perspective: &Perspective) {
@@ -924,6 +1051,81 @@ impl Contour {
             Some(bounds) => bounds.union_rect(self.bounds),
         })
     }
+
+    /// Returns true if the two contours are equal once their points are snapped to a grid of the
+    /// given size.
+    pub fn quantized_eq(&self, other: &Contour, quantum: f32) -> bool {
+        self.closed == other.closed && self.flags == other.flags &&
+            self.points.len() == other.points.len() &&
+            self.points.iter().zip(other.points.iter()).all(|(&this, &other)| {
+                quantize(this, quantum) == quantize(other, quantum)
+            })
+    }
+
+    fn hash_quantized<H>(&self, quantum: f32, state: &mut H) where H: Hasher {
+        state.write(&[self.closed as u8]);
+        state.write(&(self.points.len() as u64).to_le_bytes());
+        for (&point, flags) in self.points.iter().zip(self.flags.iter()) {
+            let [x, y] = quantize(point, quantum);
+            state.write(&[flags.bits()]);
+            state.write(&x.to_le_bytes());
+            state.write(&y.to_le_bytes());
+        }
+    }
+}
+
+impl PartialEq for Contour {
+    #[inline]
+    fn eq(&self, other: &Contour) -> bool {
+        self.closed == other.closed && self.flags == other.flags &&
+            self.points.len() == other.points.len() &&
+            self.points.iter().zip(other.points.iter()).all(|(&this, &other)| {
+                point_bits(this) == point_bits(other)
+            })
+    }
+}
+
+impl Eq for Contour {}
+
+impl Hash for Contour {
+    #[inline]
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        self.hash_quantized(HASH_QUANTUM, state)
+    }
+}
+
+// Snaps a point to the grid. Equal points always snap to the same grid point, including zeroes
+// of either sign; NaNs snap to zero.
+#[inline]
+fn quantize(point: Vector2F, quantum: f32) -> [i64; 2] {
+    let point = point * (1.0 / quantum);
+    [point.x().round() as i64, point.y().round() as i64]
+}
+
+// Returns the bits of a point's coordinates, for comparing points exactly. Unlike comparing the
+// coordinates as floats, this makes a NaN equal to itself, as `Eq` requires. Zeroes of either
+// sign have the same bits, since they also have the same hash.
+#[inline]
+fn point_bits(point: Vector2F) -> [u32; 2] {
+    let bits = |value: f32| if value == 0.0 { 0 } else { value.to_bits() };
+    [bits(point.x()), bits(point.y())]
+}
+
+// 64-bit FNV-1a, which unlike `DefaultHasher` is specified to never change.
+struct StableHasher(u64);
+
+impl Hasher for StableHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
 }
 
 impl Debug for Contour {
@@ -1090,3 +1292,44 @@ pub(crate) fn union_rect(bounds: &mut RectF, new_point: Vector2F, first: bool) {
         *bounds = bounds.union_point(new_point)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{HASH_QUANTUM, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash(outline: &Outline) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        outline.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_outline_equality_and_hashing() {
+        let square = Outline::from_rect(RectF::new(vec2f(0.0, 0.0), vec2f(10.0, 10.0)));
+        let negative_zero = Outline::from_rect(RectF::new(vec2f(-0.0, 0.0), vec2f(10.0, 10.0)));
+        assert_eq!(square, negative_zero);
+        assert_eq!(hash(&square), hash(&negative_zero));
+        assert_eq!(square.quantized_hash(HASH_QUANTUM), negative_zero.quantized_hash(HASH_QUANTUM));
+
+        let nudged = Outline::from_rect(RectF::new(vec2f(0.001, 0.0), vec2f(10.0, 10.0)));
+        assert_ne!(square, nudged);
+        assert!(square.quantized_eq(&nudged, 0.01));
+        assert_eq!(square.quantized_hash(0.01), nudged.quantized_hash(0.01));
+        assert_ne!(square.quantized_hash(0.0001), nudged.quantized_hash(0.0001));
+
+        let mut open = square.clone();
+        open.contours[0].closed = false;
+        assert_ne!(square, open);
+        assert_ne!(square.quantized_hash(1.0), open.quantized_hash(1.0));
+
+        let mut malformed = square.clone();
+        malformed.contours[0].points[1] = vec2f(f32::NAN, 0.0);
+        assert_eq!(malformed, malformed.clone());
+        assert_ne!(malformed, square);
+        assert_eq!(hash(&malformed), hash(&malformed.clone()));
+    }
+}
@@ -10,6 +10,7 @@
 
 //! Raster image patterns.
 
+use crate::color_space::ImageColorSpace;
 use crate::effects::PatternFilter;
 use crate::render_target::RenderTargetId;
 use crate::util;
@@ -22,7 +23,9 @@ use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 #[cfg(feature = "pf-image")]
-use image::RgbaImage;
+use image::{DynamicImage, ImageDecoder, ImageError, ImageReader, RgbaImage};
+#[cfg(feature = "pf-image")]
+use std::io::Cursor;
 
 /// A raster image pattern.
 #[derive(Clone, PartialEq, Debug)]
@@ -50,15 +53,18 @@ pub enum PatternSource {
 }
 
 /// A raster image, in 32-bit RGBA (8 bits per channel), non-premultiplied form.
+///
+/// The pixels are in sRGB unless the image says otherwise with `set_color_space()`, in which case
+/// they're converted to sRGB when they're uploaded.
 // FIXME(pcwalton): Hash the pixel contents so that we don't have to compare every pixel!
 // TODO(pcwalton): Should the pixels be premultiplied?
-// TODO(pcwalton): Color spaces.
 #[derive(Clone, PartialEq, Eq)]
 pub struct Image {
     size: Vector2I,
     pixels: Arc<Vec<ColorU>>,
     pixels_hash: u64,
     is_opaque: bool,
+    color_space: ImageColorSpace,
 }
 
 /// Unique identifier for an image.
@@ -76,6 +82,10 @@ bitflags! {
         /// image will be pixelated). If unset, bilinear interpolation is used when compositing
         /// this pattern (i.e. the image will be smooth).
         const NO_SMOOTHING  = 0x04;
+        /// If set, a repeating pattern is wrapped and filtered by the shader rather than by the
+        /// texture sampler, so that no seams show where copies of it meet, even at fractional
+        /// scales. This costs four texture reads per pixel instead of one.
+        const SEAMLESS      = 0x08;
     }
 }
 
@@ -185,6 +195,20 @@ impl Pattern {
         self.flags.set(PatternFlags::NO_SMOOTHING, !enable);
     }
 
+    /// Returns true if the shader wraps and filters this pattern itself, which avoids seams
+    /// between repeated copies at some cost in speed. See `PatternFlags::SEAMLESS`.
+    #[inline]
+    pub fn seamless(&self) -> bool {
+        self.flags.contains(PatternFlags::SEAMLESS)
+    }
+
+    /// Set to true if the shader should wrap and filter this pattern itself, avoiding seams
+    /// between repeated copies at some cost in speed. See `PatternFlags::SEAMLESS`.
+    #[inline]
+    pub fn set_seamless(&mut self, seamless: bool) {
+        self.flags.set(PatternFlags::SEAMLESS, seamless);
+    }
+
     /// Returns true if this pattern is obviously fully opaque.
     ///
     /// This is a best-effort quick check, so it might return false even if the image is actually
@@ -213,7 +237,7 @@ impl Image {
         pixels.hash(&mut pixels_hasher);
         let pixels_hash = pixels_hasher.finish();
 
-        Image { size, pixels, pixels_hash, is_opaque }
+        Image { size, pixels, pixels_hash, is_opaque, color_space: ImageColorSpace::Srgb }
     }
 
     /// A convenience function to create a new image with the given image from the `image` crate.
@@ -224,6 +248,24 @@ impl Image {
         Image::new(vec2i(width as i32, height as i32), Arc::new(pixels))
     }
 
+    /// Decodes a PNG, JPEG, WebP, or QOI image. If the image has an embedded ICC profile, the
+    /// image is given the color space the profile describes, so that its colors match what image
+    /// viewers show.
+    #[cfg(feature = "pf-image")]
+    pub fn from_encoded_bytes(data: &[u8]) -> Result<Image, ImageError> {
+        let reader = ImageReader::new(Cursor::new(data)).with_guessed_format()
+                                                        .map_err(ImageError::IoError)?;
+        let mut decoder = reader.into_decoder()?;
+        let icc_profile = decoder.icc_profile()?;
+        let image_buffer = DynamicImage::from_decoder(decoder)?.to_rgba8();
+
+        let mut image = Image::from_image_buffer(image_buffer);
+        if let Some(icc_profile) = icc_profile {
+            image.set_color_space(ImageColorSpace::from_icc_profile(&icc_profile));
+        }
+        Ok(image)
+    }
+
     /// Returns the device pixel size of the image.
     #[inline]
     pub fn size(&self) -> Vector2I {
@@ -237,6 +279,28 @@ impl Image {
         &self.pixels
     }
 
+    /// Returns the color space of the image's pixels.
+    #[inline]
+    pub fn color_space(&self) -> &ImageColorSpace {
+        &self.color_space
+    }
+
+    /// Sets the color space of the image's pixels. Pixels not in sRGB are converted to it when
+    /// they're uploaded.
+    #[inline]
+    pub fn set_color_space(&mut self, color_space: ImageColorSpace) {
+        self.color_space = color_space;
+    }
+
+    /// Returns the image's pixels converted to sRGB. Pixels already in sRGB are returned without
+    /// being copied.
+    pub fn srgb_pixels(&self) -> Arc<Vec<ColorU>> {
+        if self.color_space.is_srgb() {
+            return self.pixels.clone();
+        }
+        Arc::new(self.color_space.convert_to_srgb(&self.pixels))
+    }
+
     /// Returns true if this image is obviously opaque.
     ///
     /// This is a best-guess quick check, and as such it might return false even if the image is
@@ -284,6 +348,7 @@ impl Hash for Image {
         self.size.hash(hasher);
         self.pixels_hash.hash(hasher);
         self.is_opaque.hash(hasher);
+        self.color_space.hash(hasher);
     }
 }
 
@@ -0,0 +1,253 @@
+// pathfinder/content/src/sanitize.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Detects and repairs malformed path data.
+//!
+//! Outlines built from untrusted input (e.g. user-supplied SVG) can contain NaN or infinite
+//! coordinates, degenerate segments, and coordinates far outside any reasonable viewport, all of
+//! which the tiler assumes never occur. Running such outlines through the sanitizer first makes
+//! them safe to render.
+
+use crate::outline::{Contour, Outline, PointFlags};
+use pathfinder_geometry::vector::Vector2F;
+
+/// The default maximum absolute value of any coordinate.
+///
+/// This is well within the range of the 16-bit tile coordinates that the tiler uses.
+pub const DEFAULT_MAX_COORDINATE: f32 = 262144.0;
+
+/// Options that control which repairs the sanitizer performs.
+#[derive(Clone, Copy, Debug)]
+pub struct SanitizeOptions {
+    /// Coordinates with an absolute value larger than this are clamped to it.
+    pub max_coordinate: f32,
+    /// Whether segments that don't move the current point are removed.
+    pub remove_zero_length_segments: bool,
+    /// Whether open contours should be closed.
+    ///
+    /// Set this if the outline is to be filled, as opposed to stroked.
+    pub close_open_contours: bool,
+}
+
+/// A summary of the repairs that the sanitizer made.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct SanitizeReport {
+    /// The number of NaN or infinite points found. Segments containing them are dropped.
+    pub non_finite_points: u32,
+    /// The number of points that had one or more coordinates clamped to the maximum magnitude.
+    pub clamped_points: u32,
+    /// The number of zero-length segments removed.
+    pub zero_length_segments: u32,
+    /// The number of open contours that were closed.
+    pub closed_contours: u32,
+    /// The number of contours removed entirely because nothing of them remained.
+    pub removed_contours: u32,
+}
+
+/// Detects and repairs malformed path data in an outline.
+pub struct OutlineSanitizer<'a> {
+    input: &'a Outline,
+    output: Outline,
+    options: SanitizeOptions,
+    report: SanitizeReport,
+}
+
+impl Default for SanitizeOptions {
+    #[inline]
+    fn default() -> SanitizeOptions {
+        SanitizeOptions {
+            max_coordinate: DEFAULT_MAX_COORDINATE,
+            remove_zero_length_segments: true,
+            close_open_contours: false,
+        }
+    }
+}
+
+impl SanitizeReport {
+    /// Returns true if the sanitizer didn't need to change anything.
+    #[inline]
+    pub fn is_clean(&self) -> bool {
+        *self == SanitizeReport::default()
+    }
+}
+
+impl<'a> OutlineSanitizer<'a> {
+    /// Creates a new sanitizer for the given outline.
+    #[inline]
+    pub fn new(input: &'a Outline, options: SanitizeOptions) -> OutlineSanitizer<'a> {
+        OutlineSanitizer {
+            input,
+            output: Outline::new(),
+            options,
+            report: SanitizeReport::default(),
+        }
+    }
+
+    /// Performs the sanitization.
+    ///
+    /// The results can be retrieved with the `report()` and `into_outline()` methods.
+    pub fn sanitize(&mut self) {
+        for contour in &self.input.contours {
+            let contour = self.sanitize_contour(contour);
+            if contour.len() < 2 {
+                self.report.removed_contours += 1;
+                continue;
+            }
+            self.output.push_contour(contour);
+        }
+    }
+
+    /// Returns a summary of the repairs that were made.
+    #[inline]
+    pub fn report(&self) -> SanitizeReport {
+        self.report
+    }
+
+    /// Returns the sanitized outline.
+    #[inline]
+    pub fn into_outline(self) -> Outline {
+        self.output
+    }
+
+    fn sanitize_contour(&mut self, input: &Contour) -> Contour {
+        let mut output = Contour::with_capacity(input.points.len());
+
+        // Each segment is a run of zero, one, or two control points followed by an endpoint. The
+        // first point of a contour is always an endpoint.
+        let mut segment_points = [Vector2F::zero(); 3];
+        let mut segment_point_count = 0;
+        let mut segment_is_finite = true;
+        for (&point, &flags) in input.points.iter().zip(input.flags.iter()) {
+            if segment_point_count == segment_points.len() {
+                // Malformed flags: more than two control points in a row. Drop the run.
+                segment_point_count = 0;
+                segment_is_finite = true;
+            }
+
+            if !point.x().is_finite() || !point.y().is_finite() {
+                self.report.non_finite_points += 1;
+                segment_is_finite = false;
+            } else {
+                segment_points[segment_point_count] = self.clamp_point(point);
+            }
+            segment_point_count += 1;
+
+            if flags.intersects(PointFlags::CONTROL_POINT_0 | PointFlags::CONTROL_POINT_1) {
+                continue;
+            }
+
+            if segment_is_finite {
+                self.push_segment(&mut output, &segment_points[0..segment_point_count]);
+            }
+            segment_point_count = 0;
+            segment_is_finite = true;
+        }
+
+        if input.closed {
+            output.close();
+        } else if self.options.close_open_contours && output.len() >= 2 {
+            output.close();
+            self.report.closed_contours += 1;
+        }
+
+        output
+    }
+
+    fn push_segment(&mut self, output: &mut Contour, points: &[Vector2F]) {
+        let last_position = match output.last_position() {
+            None => {
+                // This is the first endpoint, so any control points before it are meaningless.
+                output.push_endpoint(points[points.len() - 1]);
+                return;
+            }
+            Some(last_position) => last_position,
+        };
+
+        if self.options.remove_zero_length_segments &&
+                points.iter().all(|&point| point == last_position) {
+            self.report.zero_length_segments += 1;
+            return;
+        }
+
+        match *points {
+            [to] => output.push_endpoint(to),
+            [ctrl, to] => output.push_quadratic(ctrl, to),
+            [ctrl0, ctrl1, to] => output.push_cubic(ctrl0, ctrl1, to),
+            _ => unreachable!(),
+        }
+    }
+
+    fn clamp_point(&mut self, point: Vector2F) -> Vector2F {
+        let max = Vector2F::splat(self.options.max_coordinate);
+        let clamped_point = point.clamp(-max, max);
+        if clamped_point != point {
+            self.report.clamped_points += 1;
+        }
+        clamped_point
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::outline::{Contour, Outline, PointFlags};
+    use crate::segment::{Segment, SegmentFlags};
+    use pathfinder_geometry::line_segment::LineSegment2F;
+    use pathfinder_geometry::vector::vec2f;
+    use std::f32;
+
+    use super::{OutlineSanitizer, SanitizeOptions};
+
+    #[test]
+    fn test_sanitize_malformed_contour() {
+        let mut contour = Contour::new();
+        for &point in &[
+            vec2f(0.0, 0.0),
+            vec2f(0.0, 0.0),
+            vec2f(f32::NAN, 1.0),
+            vec2f(10.0, 0.0),
+            vec2f(1.0e9, 10.0),
+        ] {
+            contour.push_point_unchecked(point, PointFlags::empty(), true);
+        }
+        let mut outline = Outline::new();
+        outline.contours.push(contour);
+
+        let options = SanitizeOptions { close_open_contours: true, ..SanitizeOptions::default() };
+        let mut sanitizer = OutlineSanitizer::new(&outline, options);
+        sanitizer.sanitize();
+        let report = sanitizer.report();
+        assert_eq!(report.non_finite_points, 1);
+        assert_eq!(report.zero_length_segments, 1);
+        assert_eq!(report.clamped_points, 1);
+        assert_eq!(report.closed_contours, 1);
+        assert!(!report.is_clean());
+
+        let outline = sanitizer.into_outline();
+        assert_eq!(outline.len(), 1);
+        let contour = &outline.contours()[0];
+        assert_eq!(contour.len(), 3);
+        assert!(contour.is_closed());
+        assert!(contour.points().iter().all(|point| point.x().is_finite()));
+    }
+
+    #[test]
+    fn test_sanitize_outline_from_malformed_segments() {
+        let mut first = Segment::line(LineSegment2F::new(vec2f(0.0, 0.0), vec2f(f32::NAN, 5.0)));
+        first.flags.insert(SegmentFlags::FIRST_IN_SUBPATH);
+        let second = Segment::line(LineSegment2F::new(vec2f(f32::NAN, 5.0), vec2f(10.0, 10.0)));
+        let outline = Outline::from_segments_unchecked(vec![first, second].into_iter());
+
+        let mut sanitizer = OutlineSanitizer::new(&outline, SanitizeOptions::default());
+        sanitizer.sanitize();
+        assert_eq!(sanitizer.report().non_finite_points, 1);
+        let outline = sanitizer.into_outline();
+        assert!(outline.contours()[0].points().iter().all(|point| point.x().is_finite()));
+    }
+}
@@ -10,13 +10,15 @@
 
 //! Utilities for converting path strokes to fills.
 
-use crate::outline::{ArcDirection, Contour, ContourIterFlags, Outline, PushSegmentFlags};
+use crate::outline::{ArcDirection, Contour, ContourIterFlags, Outline, PointFlags};
+use crate::outline::PushSegmentFlags;
 use crate::segment::Segment;
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::rect::RectF;
 use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::util::EPSILON;
 use pathfinder_geometry::vector::{Vector2F, vec2f};
+use std::cmp::Ordering;
 use std::f32;
 
 const TOLERANCE: f32 = 0.01;
@@ -79,6 +81,15 @@ pub enum LineJoin {
     /// common endpoint of connected segments. The radius for these rounded corners is equal to the
     /// line width.
     Round,
+    /// Like `Miter`, but where the miter would exceed the limit, it's cut off at the limit by a
+    /// line perpendicular to the join's bisector instead of falling back to a bevel. This is SVG
+    /// 2's `miter-clip`. The `f32` value specifies the miter limit ratio.
+    MiterClip(f32),
+    /// Extends the outside edges of the connected segments with circular arcs that continue their
+    /// curvature until they meet, which is a miter where both segments are straight. This is SVG
+    /// 2's `arcs`. Where the arcs don't meet or meet beyond the miter limit, the join falls back
+    /// to `MiterClip`. The `f32` value specifies the miter limit ratio.
+    Arcs(f32),
 }
 
 impl<'a> OutlineStrokeToFill<'a> {
@@ -137,12 +148,11 @@ impl<'a> OutlineStrokeToFill<'a> {
                             closed: bool) {
         // Add join if necessary.
         if closed && stroker.output.might_need_join(self.style.line_join) {
-            let (p1, p0) = (stroker.output.position_of(1), stroker.output.position_of(0));
-            let final_segment = LineSegment2F::new(p1, p0);
+            let first_segment = stroker.output.segment_after(0);
             stroker.output.add_join(self.style.line_width * 0.5,
                                     self.style.line_join,
                                     stroker.input.position_of(0),
-                                    final_segment);
+                                    &first_segment);
         }
 
         stroker.output.closed = true;
@@ -277,16 +287,7 @@ impl Offset for Segment {
                       contour: &mut Contour) {
         // Add join if necessary.
         if contour.might_need_join(join) {
-            let p3 = self.baseline.from();
-            let p4 = if self.is_line() {
-                self.baseline.to()
-            } else {
-                // NB: If you change the representation of quadratic curves, you will need to
-                // change this.
-                self.ctrl.from()
-            };
-
-            contour.add_join(distance, join, join_point, LineSegment2F::new(p4, p3));
+            contour.add_join(distance, join, join_point, self);
         }
 
         // Push segment.
@@ -398,7 +399,8 @@ impl Contour {
             false
         } else {
             match join {
-                LineJoin::Miter(_) | LineJoin::Round => true,
+                LineJoin::Miter(_) | LineJoin::MiterClip(_) | LineJoin::Arcs(_) |
+                LineJoin::Round => true,
                 LineJoin::Bevel => false,
             }
         }
@@ -408,27 +410,71 @@ impl Contour {
                 distance: f32,
                 join: LineJoin,
                 join_point: Vector2F,
-                next_tangent: LineSegment2F) {
+                next_segment: &Segment) {
         let (p0, p1) = (self.position_of_last(2), self.position_of_last(1));
         let prev_tangent = LineSegment2F::new(p0, p1);
 
+        let p3 = next_segment.baseline.from();
+        let p4 = if next_segment.is_line() {
+            next_segment.baseline.to()
+        } else {
+            // NB: If you change the representation of quadratic curves, you will need to
+            // change this.
+            next_segment.ctrl.from()
+        };
+        let next_tangent = LineSegment2F::new(p4, p3);
+
         if prev_tangent.square_length() < EPSILON || next_tangent.square_length() < EPSILON {
             return;
         }
 
         match join {
             LineJoin::Bevel => {}
-            LineJoin::Miter(miter_limit) => {
+            LineJoin::Miter(miter_limit) | LineJoin::MiterClip(miter_limit) => {
                 if let Some(prev_tangent_t) = prev_tangent.intersection_t(next_tangent) {
                     if prev_tangent_t < -EPSILON {
                         return;
                     }
                     let miter_endpoint = prev_tangent.sample(prev_tangent_t);
                     let threshold = miter_limit * distance;
-                    if (miter_endpoint - join_point).square_length() > threshold * threshold {
-                        return;
+                    if (miter_endpoint - join_point).square_length() <= threshold * threshold {
+                        self.push_endpoint(miter_endpoint);
+                    } else if let LineJoin::MiterClip(_) = join {
+                        self.add_clipped_miter(join_point,
+                                               (miter_endpoint - join_point).normalize(),
+                                               threshold.abs(),
+                                               prev_tangent,
+                                               next_tangent);
+                    }
+                }
+            }
+            LineJoin::Arcs(miter_limit) => {
+                let prev_segment = self.last_segment();
+                let prev_curve = JoinCurve::at_start(&prev_segment.reversed());
+                let next_curve = JoinCurve::at_start(next_segment);
+                let (prev_direction, next_direction) =
+                    (prev_tangent.vector().normalize(), -next_tangent.vector().normalize());
+
+                // The arcs meet where the two curves cross ahead of both ends of the join.
+                let threshold = miter_limit * distance.abs();
+                let tip = prev_curve.intersections(&next_curve).into_iter().filter(|&tip| {
+                    (tip - p1).dot(prev_direction) >= -EPSILON &&
+                        (p3 - tip).dot(next_direction) >= -EPSILON &&
+                        (tip - join_point).length() <= threshold
+                }).min_by(|a, b| {
+                    let (a, b) = ((*a - join_point).length(), (*b - join_point).length());
+                    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+                });
+
+                match tip {
+                    Some(tip) => {
+                        prev_curve.push_arc(self, p1, tip, p1, prev_direction);
+                        next_curve.push_arc(self, tip, p3, p3, next_direction);
+                    }
+                    None => {
+                        self.add_join(distance, LineJoin::MiterClip(miter_limit), join_point,
+                                      next_segment)
                     }
-                    self.push_endpoint(miter_endpoint);
                 }
             }
             LineJoin::Round => {
@@ -441,6 +487,144 @@ impl Contour {
             }
         }
     }
+
+    // Adds a miter cut off `clip_distance` from the join point along the unit `bisector`.
+    fn add_clipped_miter(&mut self,
+                         join_point: Vector2F,
+                         bisector: Vector2F,
+                         clip_distance: f32,
+                         prev_tangent: LineSegment2F,
+                         next_tangent: LineSegment2F) {
+        // Finds where each outside edge, extended past its end, crosses the clipping line.
+        let clip = |tangent: LineSegment2F| {
+            let (point, vector) = (tangent.to(), tangent.vector());
+            let speed = vector.dot(bisector);
+            if speed <= EPSILON {
+                return None;
+            }
+            let t = (clip_distance - (point - join_point).dot(bisector)) / speed;
+            if t < 0.0 { None } else { Some(point + vector * t) }
+        };
+
+        // If the clipping line falls inside the bevel, the bevel is all that's left.
+        if let (Some(prev_point), Some(next_point)) = (clip(prev_tangent), clip(next_tangent)) {
+            self.push_endpoint(prev_point);
+            self.push_endpoint(next_point);
+        }
+    }
+
+    // Returns the segment ending at the last point.
+    fn last_segment(&self) -> Segment {
+        let point_count = self.len();
+        let to = self.position_of_last(1);
+        if self.flags_of(point_count - 2).contains(PointFlags::CONTROL_POINT_1) {
+            let ctrl = LineSegment2F::new(self.position_of_last(3), self.position_of_last(2));
+            Segment::cubic(LineSegment2F::new(self.position_of_last(4), to), ctrl)
+        } else if self.flags_of(point_count - 2).contains(PointFlags::CONTROL_POINT_0) {
+            let from = self.position_of_last(3);
+            Segment::quadratic(LineSegment2F::new(from, to), self.position_of_last(2))
+        } else {
+            Segment::line(LineSegment2F::new(self.position_of_last(2), to))
+        }
+    }
+}
+
+// The circle, or line if it's straight, that continues an offset segment with the curvature it
+// has at one end, for `LineJoin::Arcs`.
+#[derive(Clone, Copy, Debug)]
+enum JoinCurve {
+    Line { point: Vector2F, direction: Vector2F },
+    Circle { center: Vector2F, radius: f32 },
+}
+
+impl JoinCurve {
+    // The curve osculating `segment` at its start.
+    fn at_start(segment: &Segment) -> JoinCurve {
+        let point = segment.baseline.from();
+        if segment.is_line() {
+            return JoinCurve::Line { point, direction: segment.baseline.vector().normalize() };
+        }
+
+        let cubic = segment.to_cubic();
+        let (p0, p1, p2) = (cubic.baseline.from(), cubic.ctrl.from(), cubic.ctrl.to());
+        let velocity = (p1 - p0) * 3.0;
+        if velocity.square_length() < EPSILON {
+            return JoinCurve::Line { point, direction: (p2 - p0).normalize() };
+        }
+        let acceleration = (p0 - p1 * 2.0 + p2) * 6.0;
+        let speed = velocity.length();
+        let curvature = velocity.det(acceleration) / (speed * speed * speed);
+        let direction = velocity / speed;
+        if curvature.abs() < EPSILON {
+            return JoinCurve::Line { point, direction };
+        }
+
+        let normal = vec2f(-direction.y(), direction.x());
+        JoinCurve::Circle { center: point + normal / curvature, radius: 1.0 / curvature.abs() }
+    }
+
+    fn intersections(&self, other: &JoinCurve) -> Vec<Vector2F> {
+        match (*self, *other) {
+            (JoinCurve::Line { point: point_a, direction: direction_a },
+             JoinCurve::Line { point: point_b, direction: direction_b }) => {
+                let denominator = direction_a.det(direction_b);
+                if denominator.abs() < EPSILON {
+                    return vec![];
+                }
+                let t = (point_b - point_a).det(direction_b) / denominator;
+                vec![point_a + direction_a * t]
+            }
+            (JoinCurve::Line { point, direction }, JoinCurve::Circle { center, radius }) |
+            (JoinCurve::Circle { center, radius }, JoinCurve::Line { point, direction }) => {
+                let offset = point - center;
+                let half_b = direction.dot(offset);
+                let discriminant = half_b * half_b - (offset.square_length() - radius * radius);
+                if discriminant < 0.0 {
+                    return vec![];
+                }
+                let root = discriminant.sqrt();
+                vec![point + direction * (-half_b - root), point + direction * (-half_b + root)]
+            }
+            (JoinCurve::Circle { center: center_a, radius: radius_a },
+             JoinCurve::Circle { center: center_b, radius: radius_b }) => {
+                let between = center_b - center_a;
+                let distance = between.length();
+                if distance < EPSILON || distance > radius_a + radius_b ||
+                        distance < (radius_a - radius_b).abs() {
+                    return vec![];
+                }
+                let along = (radius_a * radius_a - radius_b * radius_b + distance * distance) /
+                    (2.0 * distance);
+                let across = f32::max(radius_a * radius_a - along * along, 0.0).sqrt();
+                let base = center_a + between * (along / distance);
+                let normal = vec2f(-between.y(), between.x()) * (across / distance);
+                vec![base + normal, base - normal]
+            }
+        }
+    }
+
+    // Adds the part of this curve from `from` to `to`, traveling the way that it runs in
+    // `direction` at `anchor`.
+    fn push_arc(&self,
+                contour: &mut Contour,
+                from: Vector2F,
+                to: Vector2F,
+                anchor: Vector2F,
+                direction: Vector2F) {
+        match *self {
+            JoinCurve::Line { .. } => contour.push_endpoint(to),
+            JoinCurve::Circle { center, radius } => {
+                let (from_vector, to_vector) = ((from - center) / radius, (to - center) / radius);
+                // Positive rotation turns the radius toward the perpendicular on its left.
+                let anchor_vector = anchor - center;
+                let clockwise = vec2f(-anchor_vector.y(), anchor_vector.x()).dot(direction) > 0.0;
+                let arc_direction = if clockwise { ArcDirection::CW } else { ArcDirection::CCW };
+                let transform = Transform2F::from_scale(radius).translate(center);
+                let chord = LineSegment2F::new(from_vector.normalize(), to_vector.normalize());
+                contour.push_arc_from_unit_chord(&transform, chord, arc_direction);
+            }
+        }
+    }
 }
 
 impl Default for StrokeStyle {
@@ -463,3 +647,37 @@ impl Default for LineJoin {
     #[inline]
     fn default() -> LineJoin { LineJoin::Miter(10.0) }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+    use crate::outline::{Contour, Outline};
+    use pathfinder_geometry::vector::vec2f;
+
+    fn stroke_right_edge(line_join: LineJoin) -> f32 {
+        // A sharp V pointing right, joined at (20, 0).
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, -5.0));
+        contour.push_endpoint(vec2f(20.0, 0.0));
+        contour.push_endpoint(vec2f(0.0, 5.0));
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let style = StrokeStyle { line_width: 2.0, line_cap: LineCap::Butt, line_join };
+        let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, style);
+        stroke_to_fill.offset();
+        stroke_to_fill.into_outline().bounds().max_x()
+    }
+
+    #[test]
+    fn test_clipped_and_arc_joins() {
+        // The full miter reaches 1 / sin(θ) ≈ 4.123 past the join, where tan(θ) = 5 / 20.
+        let miter_length = f32::sqrt(425.0) / 5.0;
+        assert!(stroke_right_edge(LineJoin::Miter(4.0)) < 21.0);
+        assert!((stroke_right_edge(LineJoin::MiterClip(4.0)) - 24.0).abs() < 0.01);
+
+        // Straight segments make arcs joins into miters, clipped when they're too long.
+        assert!((stroke_right_edge(LineJoin::Arcs(5.0)) - (20.0 + miter_length)).abs() < 0.01);
+        assert!((stroke_right_edge(LineJoin::Arcs(4.0)) - 24.0).abs() < 0.01);
+    }
+}
@@ -36,9 +36,11 @@ use pathfinder_geometry::vector::{Vector2F, Vector2I, Vector4F, vec2f, vec2i};
 use pathfinder_gpu::{Device, Texture};
 use pathfinder_renderer::concurrent::scene_proxy::SceneProxy;
 use pathfinder_renderer::gpu::options::{DestFramebuffer, RendererLevel};
-use pathfinder_renderer::gpu::options::{RendererMode, RendererOptions};
+use pathfinder_renderer::gpu::options::{AlphaMode, LoadAction, RendererMode};
+use pathfinder_renderer::gpu::options::RendererOptions;
 use pathfinder_renderer::gpu::renderer::{DebugUiPresenterInfo, Renderer};
-use pathfinder_renderer::options::{BuildOptions, RenderTransform};
+use pathfinder_renderer::options::{BuildOptions, MemoryBudget, RenderTransform, TilingMode};
+use pathfinder_renderer::options::DEFAULT_FLATTENING_TOLERANCE;
 use pathfinder_renderer::paint::Paint;
 use pathfinder_renderer::scene::{DrawPath, RenderTarget, Scene};
 use pathfinder_resources::ResourceLoader;
@@ -140,6 +142,8 @@ impl<W> DemoApp<W> where W: Window {
         let render_options = RendererOptions {
             dest: dest_framebuffer,
             background_color: None,
+            load_action: LoadAction::Clear,
+            alpha_mode: AlphaMode::Premultiplied,
             show_debug_ui: true,
         };
 
@@ -257,6 +261,13 @@ impl<W> DemoApp<W> where W: Window {
                 Vector2F::zero()
             },
             subpixel_aa_enabled: self.ui_model.subpixel_aa_effect_enabled,
+            deterministic: false,
+            opaque_regions: vec![],
+            flattening_tolerance: DEFAULT_FLATTENING_TOLERANCE,
+            memory_budget: MemoryBudget::default(),
+            tiling_mode: TilingMode::ByLevel,
+            pattern_filters_enabled: true,
+            cancellation_token: None,
         };
 
         self.scene_proxy.build(build_options);
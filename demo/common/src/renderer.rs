@@ -19,7 +19,8 @@ use pathfinder_gpu::{Device, RenderTarget, TextureData, UniformData};
 use pathfinder_geometry::rect::RectI;
 use pathfinder_geometry::transform3d::Transform4F;
 use pathfinder_geometry::vector::{Vector2I, Vector4F};
-use pathfinder_renderer::gpu::options::{DestFramebuffer, RendererOptions};
+use pathfinder_renderer::gpu::options::{AlphaMode, DestFramebuffer, LoadAction};
+use pathfinder_renderer::gpu::options::RendererOptions;
 use pathfinder_renderer::options::RenderTransform;
 use std::mem;
 use std::path::PathBuf;
@@ -71,6 +72,8 @@ impl<W> DemoApp<W> where W: Window {
                 *self.renderer.options_mut() = RendererOptions {
                     dest: DestFramebuffer::Other(self.scene_framebuffer.take().unwrap()),
                     background_color: clear_color,
+                    load_action: LoadAction::Clear,
+                    alpha_mode: AlphaMode::Premultiplied,
                     show_debug_ui: self.options.ui != UIVisibility::None,
                 };
                 2
@@ -82,6 +85,8 @@ impl<W> DemoApp<W> where W: Window {
                         window_size,
                     },
                     background_color: clear_color,
+                    load_action: LoadAction::Clear,
+                    alpha_mode: AlphaMode::Premultiplied,
                     show_debug_ui: self.options.ui != UIVisibility::None,
                 };
                 1
@@ -15,7 +15,7 @@ use pathfinder_color::ColorU;
 use pathfinder_geometry::rect::RectI;
 use pathfinder_geometry::vector::{vec2i, Vector2I};
 use pathfinder_gpu::allocator::GpuMemoryAllocator;
-use pathfinder_gpu::{Device, Texture, TextureDataRef};
+use pathfinder_gpu::{Device, Texture};
 use pathfinder_renderer::gpu::debug::DebugUiPresenter;
 use pathfinder_resources::ResourceLoader;
 use pathfinder_ui::{BUTTON_HEIGHT, BUTTON_TEXT_OFFSET, BUTTON_WIDTH, FONT_ASCENT, PADDING};
@@ -811,11 +811,9 @@ impl ScreenshotType {
 
 fn load_png_texture(device: &Device, resources: &dyn ResourceLoader, name: &str) -> Texture {
     let data = resources.slurp(&format!("textures/{}.png", name)).unwrap();
-    let image = image::load_from_memory_with_format(&data, image::ImageFormat::Png).unwrap();
-    let image = image.to_luma8();
-    let size = vec2i(image.width() as i32, image.height() as i32);
-    let texture = device.create_texture(wgpu::TextureFormat::R8Unorm, size, wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC);
-    let rect = RectI::new(Vector2I::default(), size);
-    device.upload_to_texture(&texture, rect, TextureDataRef::U8(&image));
-    texture
+    let usage = wgpu::TextureUsages::TEXTURE_BINDING |
+        wgpu::TextureUsages::RENDER_ATTACHMENT |
+        wgpu::TextureUsages::COPY_SRC;
+    device.create_texture_from_image_bytes(&data, Some(wgpu::TextureFormat::R8Unorm), usage)
+          .unwrap()
 }
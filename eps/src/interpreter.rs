@@ -0,0 +1,976 @@
+// pathfinder/eps/src/interpreter.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Executes PostScript, drawing into a scene.
+
+use crate::lexer::Lexer;
+use crate::object::{self, Dict, Object};
+use crate::{BuildResultFlags, EPSError};
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::dash::OutlineDash;
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::outline::{ArcDirection, Contour, Outline};
+use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{ClipPath, ClipPathId, DrawPath, Scene};
+use std::f32::consts::PI;
+use std::mem;
+use std::rc::Rc;
+
+// Bounds the work done by programs that loop for a long time, or forever.
+const MAX_OPERATIONS: u64 = 10_000_000;
+const MAX_CALL_DEPTH: u32 = 128;
+
+pub(crate) struct Interpreter {
+    pub(crate) scene: Scene,
+    pub(crate) result_flags: BuildResultFlags,
+    operands: Vec<Object>,
+    dicts: Vec<Dict>,
+    state: State,
+    state_stack: Vec<State>,
+    initial_transform: Transform2F,
+    operation_count: u64,
+}
+
+#[derive(Clone)]
+struct State {
+    // The current transformation matrix, from user space to scene space.
+    transform: Transform2F,
+    // The current path, in scene space.
+    path: Outline,
+    contour: Contour,
+    current_point: Option<Vector2F>,
+    color: ColorU,
+    line_width: f32,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    miter_limit: f32,
+    dash: Vec<f32>,
+    dash_offset: f32,
+    clip_path: Option<ClipPathId>,
+}
+
+impl Interpreter {
+    pub(crate) fn new(scene: Scene, initial_transform: Transform2F) -> Interpreter {
+        Interpreter {
+            scene,
+            result_flags: BuildResultFlags::empty(),
+            operands: vec![],
+            dicts: vec![object::new_dict()],
+            state: State::new(initial_transform),
+            state_stack: vec![],
+            initial_transform,
+            operation_count: 0,
+        }
+    }
+
+    pub(crate) fn run(&mut self, lexer: &mut Lexer) -> Result<(), EPSError> {
+        while let Some(object) = lexer.next_object()? {
+            self.execute(object, 0)?;
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, object: Object, depth: u32) -> Result<(), EPSError> {
+        self.operation_count += 1;
+        if self.operation_count > MAX_OPERATIONS {
+            return Err(EPSError::LimitCheck);
+        }
+
+        match object {
+            Object::Name(name) => {
+                match self.lookup(&name) {
+                    Some(Object::Procedure(procedure)) => self.call_procedure(&procedure, depth),
+                    Some(Object::Operator(operator)) => self.operator(&operator, depth),
+                    Some(value) => {
+                        self.operands.push(value);
+                        Ok(())
+                    }
+                    None => self.operator(&name, depth),
+                }
+            }
+            Object::Operator(operator) => self.operator(&operator, depth),
+            object => {
+                self.operands.push(object);
+                Ok(())
+            }
+        }
+    }
+
+    // Executes an operand of `exec`, `if`, and friends, which runs procedures rather than pushing
+    // them.
+    fn call(&mut self, object: Object, depth: u32) -> Result<(), EPSError> {
+        match object {
+            Object::Procedure(procedure) => self.call_procedure(&procedure, depth),
+            object => self.execute(object, depth),
+        }
+    }
+
+    fn call_procedure(&mut self, procedure: &[Object], depth: u32) -> Result<(), EPSError> {
+        if depth >= MAX_CALL_DEPTH {
+            return Err(EPSError::ExecStackOverflow);
+        }
+        for object in procedure {
+            self.execute(object.clone(), depth + 1)?;
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, key: &str) -> Option<Object> {
+        self.dicts.iter().rev().filter_map(|dict| dict.borrow().get(key).cloned()).next()
+    }
+
+    fn operator(&mut self, name: &str, depth: u32) -> Result<(), EPSError> {
+        match name {
+            // Operand stack
+            "pop" => {
+                self.pop(name)?;
+            }
+            "exch" => {
+                let (a, b) = (self.pop(name)?, self.pop(name)?);
+                self.operands.push(a);
+                self.operands.push(b);
+            }
+            "dup" => {
+                let object = self.pop(name)?;
+                self.operands.push(object.clone());
+                self.operands.push(object);
+            }
+            "copy" => {
+                let count = self.pop_count(name)?;
+                let copied = self.operands[(self.operands.len() - count)..].to_vec();
+                self.operands.extend(copied);
+            }
+            "index" => {
+                let index = self.pop_index(name)?;
+                if index >= self.operands.len() {
+                    return Err(EPSError::StackUnderflow(name.to_owned()));
+                }
+                let object = self.operands[self.operands.len() - 1 - index].clone();
+                self.operands.push(object);
+            }
+            "roll" => {
+                let amount = self.pop_number(name)? as i64;
+                let count = self.pop_count(name)?;
+                if count > 0 {
+                    let start = self.operands.len() - count;
+                    let amount = amount.rem_euclid(count as i64) as usize;
+                    self.operands[start..].rotate_right(amount);
+                }
+            }
+            "clear" => self.operands.clear(),
+            "count" => self.operands.push(Object::Number(self.operands.len() as f64)),
+            "mark" | "[" | "<<" => self.operands.push(Object::Mark),
+            "cleartomark" => {
+                let mark = self.find_mark(name)?;
+                self.operands.truncate(mark);
+            }
+            "counttomark" => {
+                let mark = self.find_mark(name)?;
+                let count = self.operands.len() - mark - 1;
+                self.operands.push(Object::Number(count as f64));
+            }
+            "]" => {
+                let mark = self.find_mark(name)?;
+                let objects = self.operands.split_off(mark + 1);
+                self.operands.pop();
+                self.operands.push(Object::Array(Rc::new(objects)));
+            }
+            ">>" => {
+                let mark = self.find_mark(name)?;
+                let objects = self.operands.split_off(mark + 1);
+                self.operands.pop();
+                if objects.len() % 2 != 0 {
+                    return Err(EPSError::RangeCheck(name.to_owned()));
+                }
+                let dict = object::new_dict();
+                for pair in objects.chunks(2) {
+                    let key = pair[0].to_key().ok_or_else(|| type_check(name))?;
+                    dict.borrow_mut().insert(key, pair[1].clone());
+                }
+                self.operands.push(Object::Dict(dict));
+            }
+
+            // Arithmetic
+            "add" | "sub" | "mul" | "div" | "idiv" | "mod" | "exp" | "atan" => {
+                let b = self.pop_number(name)?;
+                let a = self.pop_number(name)?;
+                let result = match name {
+                    "add" => a + b,
+                    "sub" => a - b,
+                    "mul" => a * b,
+                    "div" | "idiv" | "mod" if b == 0.0 => {
+                        return Err(EPSError::UndefinedResult(name.to_owned()));
+                    }
+                    "div" => a / b,
+                    "idiv" => (a.trunc() / b.trunc()).trunc(),
+                    "mod" => a.trunc() % b.trunc(),
+                    "exp" => a.powf(b),
+                    // `atan` takes a numerator and denominator and returns degrees in [0, 360).
+                    _ => a.atan2(b).to_degrees().rem_euclid(360.0),
+                };
+                self.operands.push(Object::Number(result));
+            }
+            "neg" | "abs" | "sqrt" | "floor" | "ceiling" | "round" | "truncate" | "cvi" | "cvr" |
+            "sin" | "cos" | "ln" | "log" => {
+                let a = self.pop_number(name)?;
+                let result = match name {
+                    "neg" => -a,
+                    "abs" => a.abs(),
+                    "sqrt" => a.sqrt(),
+                    "floor" => a.floor(),
+                    "ceiling" => a.ceil(),
+                    // PostScript rounds halves up, even when negative.
+                    "round" => (a + 0.5).floor(),
+                    "truncate" | "cvi" => a.trunc(),
+                    "sin" => a.to_radians().sin(),
+                    "cos" => a.to_radians().cos(),
+                    "ln" => a.ln(),
+                    "log" => a.log10(),
+                    _ => a,
+                };
+                self.operands.push(Object::Number(result));
+            }
+
+            // Relational and logical operators
+            "eq" | "ne" => {
+                let (b, a) = (self.pop(name)?, self.pop(name)?);
+                self.operands.push(Object::Bool(a.ps_eq(&b) == (name == "eq")));
+            }
+            "gt" | "ge" | "lt" | "le" => {
+                let b = self.pop_number(name)?;
+                let a = self.pop_number(name)?;
+                let result = match name {
+                    "gt" => a > b,
+                    "ge" => a >= b,
+                    "lt" => a < b,
+                    _ => a <= b,
+                };
+                self.operands.push(Object::Bool(result));
+            }
+            "and" | "or" | "xor" => {
+                let result = match (self.pop(name)?, self.pop(name)?) {
+                    (Object::Bool(b), Object::Bool(a)) => {
+                        Object::Bool(match name { "and" => a & b, "or" => a | b, _ => a ^ b })
+                    }
+                    (Object::Number(b), Object::Number(a)) => {
+                        let (a, b) = (a as i64, b as i64);
+                        let result = match name { "and" => a & b, "or" => a | b, _ => a ^ b };
+                        Object::Number(result as f64)
+                    }
+                    _ => return Err(type_check(name)),
+                };
+                self.operands.push(result);
+            }
+            "not" => {
+                let result = match self.pop(name)? {
+                    Object::Bool(a) => Object::Bool(!a),
+                    Object::Number(a) => Object::Number(!(a as i64) as f64),
+                    _ => return Err(type_check(name)),
+                };
+                self.operands.push(result);
+            }
+            "true" => self.operands.push(Object::Bool(true)),
+            "false" => self.operands.push(Object::Bool(false)),
+            "null" => self.operands.push(Object::Null),
+
+            // Control
+            "exec" => {
+                let object = self.pop(name)?;
+                self.call(object, depth)?;
+            }
+            "if" => {
+                let procedure = self.pop_procedure(name)?;
+                if self.pop_bool(name)? {
+                    self.call(procedure, depth)?;
+                }
+            }
+            "ifelse" => {
+                let if_false = self.pop_procedure(name)?;
+                let if_true = self.pop_procedure(name)?;
+                let procedure = if self.pop_bool(name)? { if_true } else { if_false };
+                self.call(procedure, depth)?;
+            }
+            "repeat" => {
+                let procedure = self.pop_procedure(name)?;
+                for _ in 0..self.pop_index(name)? {
+                    self.call(procedure.clone(), depth)?;
+                }
+            }
+            "for" => {
+                let procedure = self.pop_procedure(name)?;
+                let limit = self.pop_number(name)?;
+                let increment = self.pop_number(name)?;
+                let mut control = self.pop_number(name)?;
+                while (increment >= 0.0 && control <= limit) ||
+                        (increment < 0.0 && control >= limit) {
+                    self.operands.push(Object::Number(control));
+                    self.call(procedure.clone(), depth)?;
+                    control += increment;
+                }
+            }
+
+            // Dictionaries and conversions
+            "def" => {
+                let value = self.pop(name)?;
+                let key = self.pop_key(name)?;
+                self.dicts.last().unwrap().borrow_mut().insert(key, value);
+            }
+            "load" => {
+                let key = self.pop_key(name)?;
+                let value = self.lookup(&key).unwrap_or(Object::Operator(key));
+                self.operands.push(value);
+            }
+            "dict" => {
+                self.pop_number(name)?;
+                self.operands.push(Object::Dict(object::new_dict()));
+            }
+            "begin" => {
+                let dict = self.pop_dict(name)?;
+                self.dicts.push(dict);
+            }
+            "end" => {
+                if self.dicts.len() > 1 {
+                    self.dicts.pop();
+                }
+            }
+            "currentdict" => {
+                self.operands.push(Object::Dict(self.dicts.last().unwrap().clone()));
+            }
+            // There's no separate system dictionary; built-in operators aren't stored in one.
+            "userdict" | "systemdict" | "globaldict" | "statusdict" => {
+                self.operands.push(Object::Dict(self.dicts[0].clone()));
+            }
+            "known" => {
+                let key = self.pop_key(name)?;
+                let dict = self.pop_dict(name)?;
+                let known = dict.borrow().contains_key(&key);
+                self.operands.push(Object::Bool(known));
+            }
+            "where" => {
+                let key = self.pop_key(name)?;
+                match self.dicts.iter().rev().find(|dict| dict.borrow().contains_key(&key)) {
+                    Some(dict) => {
+                        self.operands.push(Object::Dict(dict.clone()));
+                        self.operands.push(Object::Bool(true));
+                    }
+                    None => self.operands.push(Object::Bool(false)),
+                }
+            }
+            "get" => {
+                let key = self.pop(name)?;
+                let value = match (self.pop(name)?, key) {
+                    (Object::Dict(dict), key) => {
+                        let key = key.to_key().ok_or_else(|| type_check(name))?;
+                        let value = dict.borrow().get(&key).cloned();
+                        value.ok_or_else(|| EPSError::Undefined(key.to_string()))?
+                    }
+                    (Object::Array(array), Object::Number(index)) |
+                    (Object::Procedure(array), Object::Number(index)) => {
+                        let index = index as usize;
+                        array.get(index).cloned().ok_or_else(|| range_check(name))?
+                    }
+                    (Object::String(string), Object::Number(index)) => {
+                        let byte = string.get(index as usize).ok_or_else(|| range_check(name))?;
+                        Object::Number(*byte as f64)
+                    }
+                    _ => return Err(type_check(name)),
+                };
+                self.operands.push(value);
+            }
+            "put" => {
+                let value = self.pop(name)?;
+                let key = self.pop_key(name)?;
+                self.pop_dict(name)?.borrow_mut().insert(key, value);
+            }
+            "length" => {
+                let length = match self.pop(name)? {
+                    Object::Array(array) | Object::Procedure(array) => array.len(),
+                    Object::String(string) => string.len(),
+                    Object::Dict(dict) => dict.borrow().len(),
+                    Object::Name(name) | Object::LiteralName(name) => name.len(),
+                    _ => return Err(type_check(name)),
+                };
+                self.operands.push(Object::Number(length as f64));
+            }
+            "cvx" => {
+                let object = match self.pop(name)? {
+                    Object::LiteralName(name) => Object::Name(name),
+                    Object::Array(array) => Object::Procedure(array),
+                    object => object,
+                };
+                self.operands.push(object);
+            }
+            "cvlit" => {
+                let object = match self.pop(name)? {
+                    Object::Name(name) => Object::LiteralName(name),
+                    Object::Procedure(array) => Object::Array(array),
+                    object => object,
+                };
+                self.operands.push(object);
+            }
+            // Access attributes aren't tracked, and names are looked up at run time either way.
+            "bind" | "readonly" | "executeonly" | "noaccess" => {
+                let object = self.pop(name)?;
+                self.operands.push(object);
+            }
+
+            // Graphics state
+            "gsave" => self.state_stack.push(self.state.clone()),
+            "grestore" => {
+                if let Some(state) = self.state_stack.pop() {
+                    self.state = state;
+                }
+            }
+            "grestoreall" => self.restore_state_to(0),
+            "save" => {
+                self.operands.push(Object::Save(self.state_stack.len()));
+                self.state_stack.push(self.state.clone());
+            }
+            "restore" => {
+                match self.pop(name)? {
+                    Object::Save(level) => self.restore_state_to(level),
+                    _ => return Err(type_check(name)),
+                }
+            }
+            "setlinewidth" => self.state.line_width = self.pop_number(name)? as f32,
+            "currentlinewidth" => {
+                self.operands.push(Object::Number(self.state.line_width as f64));
+            }
+            "setlinecap" => {
+                self.state.line_cap = match self.pop_number(name)? as i32 {
+                    0 => LineCap::Butt,
+                    1 => LineCap::Round,
+                    2 => LineCap::Square,
+                    _ => return Err(range_check(name)),
+                };
+            }
+            "setlinejoin" => {
+                self.state.line_join = match self.pop_number(name)? as i32 {
+                    0 => LineJoin::Miter(self.state.miter_limit),
+                    1 => LineJoin::Round,
+                    2 => LineJoin::Bevel,
+                    _ => return Err(range_check(name)),
+                };
+            }
+            "setmiterlimit" => {
+                self.state.miter_limit = self.pop_number(name)? as f32;
+                if let LineJoin::Miter(ref mut limit) = self.state.line_join {
+                    *limit = self.state.miter_limit;
+                }
+            }
+            "setdash" => {
+                let offset = self.pop_number(name)? as f32;
+                let dash = match self.pop(name)? {
+                    Object::Array(array) | Object::Procedure(array) => {
+                        array.iter().map(|object| match *object {
+                            Object::Number(number) => Ok(number as f32),
+                            _ => Err(type_check(name)),
+                        }).collect::<Result<Vec<f32>, EPSError>>()?
+                    }
+                    _ => return Err(type_check(name)),
+                };
+                self.state.dash = dash;
+                self.state.dash_offset = offset;
+            }
+            "setgray" => {
+                let gray = self.pop_number(name)? as f32;
+                self.set_color(gray, gray, gray);
+            }
+            "setrgbcolor" => {
+                let b = self.pop_number(name)? as f32;
+                let g = self.pop_number(name)? as f32;
+                let r = self.pop_number(name)? as f32;
+                self.set_color(r, g, b);
+            }
+            "setcmykcolor" => {
+                let k = self.pop_number(name)? as f32;
+                let y = self.pop_number(name)? as f32;
+                let m = self.pop_number(name)? as f32;
+                let c = self.pop_number(name)? as f32;
+                self.set_color(1.0 - (c + k).min(1.0),
+                               1.0 - (m + k).min(1.0),
+                               1.0 - (y + k).min(1.0));
+            }
+            "sethsbcolor" => {
+                let brightness = self.pop_number(name)? as f32;
+                let saturation = self.pop_number(name)? as f32;
+                let hue = self.pop_number(name)? as f32;
+                let (r, g, b) = hsb_to_rgb(hue, saturation, brightness);
+                self.set_color(r, g, b);
+            }
+            // Device parameters that have no effect on the scene.
+            "setflat" | "setstrokeadjust" | "setoverprint" | "setsmoothness" |
+            "setpagedevice" => {
+                self.pop(name)?;
+            }
+            "showpage" | "copypage" => {}
+
+            // Coordinate systems
+            "translate" => {
+                let offset = self.pop_vector(name)?;
+                self.state.transform = self.state.transform * Transform2F::from_translation(offset);
+            }
+            "scale" => {
+                let scale = self.pop_vector(name)?;
+                self.state.transform = self.state.transform * Transform2F::from_scale(scale);
+            }
+            "rotate" => {
+                let angle = (self.pop_number(name)? as f32).to_radians();
+                self.state.transform = self.state.transform * Transform2F::from_rotation(angle);
+            }
+            "concat" => {
+                let matrix = self.pop_matrix(name)?;
+                self.state.transform = self.state.transform * matrix;
+            }
+            "matrix" => self.operands.push(matrix_object(&Transform2F::default())),
+            "currentmatrix" => {
+                self.pop_matrix(name)?;
+                self.operands.push(matrix_object(&self.state.transform));
+            }
+            "setmatrix" => self.state.transform = self.pop_matrix(name)?,
+            "initmatrix" => self.state.transform = self.initial_transform,
+
+            // Path construction
+            "newpath" => {
+                self.state.take_path();
+            }
+            "moveto" => {
+                let point = self.pop_point(name)?;
+                self.state.move_to(point);
+            }
+            "rmoveto" => {
+                let point = self.pop_relative_point(name)?;
+                self.state.move_to(point);
+            }
+            "lineto" => {
+                let point = self.pop_point(name)?;
+                self.state.start_segment(name)?;
+                self.state.line_to(point);
+            }
+            "rlineto" => {
+                let point = self.pop_relative_point(name)?;
+                self.state.start_segment(name)?;
+                self.state.line_to(point);
+            }
+            "curveto" => {
+                let to = self.pop_point(name)?;
+                let ctrl1 = self.pop_point(name)?;
+                let ctrl0 = self.pop_point(name)?;
+                self.state.start_segment(name)?;
+                self.state.curve_to(ctrl0, ctrl1, to);
+            }
+            "rcurveto" => {
+                let to = self.pop_relative_point(name)?;
+                let ctrl1 = self.pop_relative_point(name)?;
+                let ctrl0 = self.pop_relative_point(name)?;
+                self.state.start_segment(name)?;
+                self.state.curve_to(ctrl0, ctrl1, to);
+            }
+            "arc" | "arcn" => {
+                let end_angle = self.pop_number(name)? as f32;
+                let start_angle = self.pop_number(name)? as f32;
+                let radius = self.pop_number(name)? as f32;
+                let center = self.pop_vector(name)?;
+                let direction = if name == "arc" { ArcDirection::CW } else { ArcDirection::CCW };
+                self.state.arc(center, radius, start_angle, end_angle, direction);
+            }
+            "closepath" => self.state.close_path(),
+            "currentpoint" => {
+                let point = self.state.current_point.ok_or_else(|| no_current_point(name))?;
+                let point = self.state.transform.inverse() * point;
+                self.operands.push(Object::Number(point.x() as f64));
+                self.operands.push(Object::Number(point.y() as f64));
+            }
+
+            // Painting and clipping
+            "fill" | "eofill" => {
+                let mut outline = self.state.take_path();
+                outline.close_all_contours();
+                self.push_draw_path(outline, fill_rule_for(name == "fill"));
+            }
+            "stroke" => {
+                let outline = self.state.take_path();
+                let outline = self.stroke_outline(outline);
+                self.push_draw_path(outline, FillRule::Winding);
+            }
+            "clip" | "eoclip" => {
+                self.state.flush_contour();
+                let outline = self.state.path.clone();
+                self.clip(outline, fill_rule_for(name == "clip"));
+            }
+            "initclip" => self.state.clip_path = None,
+            "rectfill" | "rectstroke" | "rectclip" => {
+                let size = self.pop_vector(name)?;
+                let origin = self.pop_vector(name)?;
+                let outline = self.rect_outline(origin, size);
+                match name {
+                    "rectfill" => self.push_draw_path(outline, FillRule::Winding),
+                    "rectstroke" => {
+                        let outline = self.stroke_outline(outline);
+                        self.push_draw_path(outline, FillRule::Winding);
+                    }
+                    _ => {
+                        self.clip(outline, FillRule::Winding);
+                        self.state.take_path();
+                    }
+                }
+            }
+
+            // Text isn't supported, but the operators are recognized so that the stack stays
+            // balanced and the rest of the page can still be drawn.
+            "findfont" | "currentfont" | "scalefont" | "makefont" | "setfont" | "selectfont" |
+            "definefont" | "show" | "ashow" | "widthshow" | "awidthshow" | "stringwidth" |
+            "charpath" => {
+                self.result_flags.insert(BuildResultFlags::UNSUPPORTED_TEXT);
+                let (pops, pushes_font) = match name {
+                    "findfont" => (1, true),
+                    "currentfont" => (0, true),
+                    "scalefont" | "makefont" | "definefont" => (2, true),
+                    "setfont" | "show" | "stringwidth" => (1, false),
+                    "selectfont" | "charpath" => (2, false),
+                    "ashow" => (3, false),
+                    "widthshow" => (4, false),
+                    _ => (6, false),
+                };
+                for _ in 0..pops {
+                    self.pop(name)?;
+                }
+                if pushes_font {
+                    self.operands.push(Object::Dict(object::new_dict()));
+                }
+                if name == "stringwidth" {
+                    self.operands.push(Object::Number(0.0));
+                    self.operands.push(Object::Number(0.0));
+                }
+            }
+
+            _ => self.result_flags.insert(BuildResultFlags::UNSUPPORTED_OPERATOR),
+        }
+        Ok(())
+    }
+
+    fn restore_state_to(&mut self, level: usize) {
+        if let Some(state) = self.state_stack.drain(level..).next() {
+            self.state = state;
+        }
+    }
+
+    fn set_color(&mut self, r: f32, g: f32, b: f32) {
+        let clamp = |value: f32| value.max(0.0).min(1.0);
+        self.state.color = ColorF::new(clamp(r), clamp(g), clamp(b), 1.0).to_u8();
+    }
+
+    // Returns a closed contour around a rectangle in user space, in scene space.
+    fn rect_outline(&self, origin: Vector2F, size: Vector2F) -> Outline {
+        let transform = &self.state.transform;
+        let mut contour = Contour::new();
+        contour.push_endpoint(*transform * origin);
+        contour.push_endpoint(*transform * (origin + vec2f(size.x(), 0.0)));
+        contour.push_endpoint(*transform * (origin + size));
+        contour.push_endpoint(*transform * (origin + vec2f(0.0, size.y())));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    // Converts a path to the outline of its stroke, using the current line style.
+    fn stroke_outline(&self, outline: Outline) -> Outline {
+        let state = &self.state;
+
+        // Line widths and dashes are measured in user space, so stroke there. A zero width asks
+        // for the thinnest line the device can draw, which we take to be one scene unit.
+        let (mut outline, transform, line_width) =
+            if state.line_width > 0.0 && state.transform.matrix.det() != 0.0 {
+                (outline.transformed(&state.transform.inverse()),
+                 state.transform,
+                 state.line_width)
+            } else {
+                (outline, Transform2F::default(), 1.0)
+            };
+
+        let dash_length: f32 = state.dash.iter().sum();
+        if dash_length > 0.0 && state.dash.iter().all(|&length| length >= 0.0) {
+            let mut dash = OutlineDash::new(&outline, &state.dash, state.dash_offset);
+            dash.dash();
+            outline = dash.into_outline();
+        }
+
+        let line_join = state.line_join;
+        let style = StrokeStyle { line_width, line_cap: state.line_cap, line_join };
+        let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, style);
+        stroke_to_fill.offset();
+        stroke_to_fill.into_outline().transformed(&transform)
+    }
+
+    fn push_draw_path(&mut self, outline: Outline, fill_rule: FillRule) {
+        if outline.is_empty() {
+            return;
+        }
+        let paint = self.scene.push_paint(&Paint::from_color(self.state.color));
+        let mut path = DrawPath::new(outline, paint);
+        path.set_clip_path(self.state.clip_path);
+        path.set_fill_rule(fill_rule);
+        self.scene.push_draw_path(path);
+    }
+
+    fn clip(&mut self, mut outline: Outline, fill_rule: FillRule) {
+        outline.close_all_contours();
+        let mut clip_path = ClipPath::new(outline);
+        clip_path.set_clip_path(self.state.clip_path);
+        clip_path.set_fill_rule(fill_rule);
+        self.state.clip_path = Some(self.scene.push_clip_path(clip_path));
+    }
+
+    fn find_mark(&self, operator: &str) -> Result<usize, EPSError> {
+        self.operands
+            .iter()
+            .rposition(|object| matches!(*object, Object::Mark))
+            .ok_or_else(|| EPSError::UnmatchedMark(operator.to_owned()))
+    }
+
+    fn pop(&mut self, operator: &str) -> Result<Object, EPSError> {
+        self.operands.pop().ok_or_else(|| EPSError::StackUnderflow(operator.to_owned()))
+    }
+
+    fn pop_number(&mut self, operator: &str) -> Result<f64, EPSError> {
+        match self.pop(operator)? {
+            Object::Number(number) => Ok(number),
+            _ => Err(type_check(operator)),
+        }
+    }
+
+    fn pop_bool(&mut self, operator: &str) -> Result<bool, EPSError> {
+        match self.pop(operator)? {
+            Object::Bool(value) => Ok(value),
+            _ => Err(type_check(operator)),
+        }
+    }
+
+    // Pops a non-negative integer.
+    fn pop_index(&mut self, operator: &str) -> Result<usize, EPSError> {
+        let number = self.pop_number(operator)?;
+        if number < 0.0 {
+            return Err(range_check(operator));
+        }
+        Ok(number as usize)
+    }
+
+    // Pops a count of operands, which must be no more than the operands remaining.
+    fn pop_count(&mut self, operator: &str) -> Result<usize, EPSError> {
+        let count = self.pop_index(operator)?;
+        if count > self.operands.len() {
+            return Err(EPSError::StackUnderflow(operator.to_owned()));
+        }
+        Ok(count)
+    }
+
+    fn pop_procedure(&mut self, operator: &str) -> Result<Object, EPSError> {
+        match self.pop(operator)? {
+            object @ Object::Procedure(_) => Ok(object),
+            _ => Err(type_check(operator)),
+        }
+    }
+
+    fn pop_key(&mut self, operator: &str) -> Result<Rc<str>, EPSError> {
+        self.pop(operator)?.to_key().ok_or_else(|| type_check(operator))
+    }
+
+    fn pop_dict(&mut self, operator: &str) -> Result<Dict, EPSError> {
+        match self.pop(operator)? {
+            Object::Dict(dict) => Ok(dict),
+            _ => Err(type_check(operator)),
+        }
+    }
+
+    // Pops an x and y coordinate pair.
+    fn pop_vector(&mut self, operator: &str) -> Result<Vector2F, EPSError> {
+        let y = self.pop_number(operator)? as f32;
+        let x = self.pop_number(operator)? as f32;
+        Ok(vec2f(x, y))
+    }
+
+    // Pops a point in user space and returns it in scene space.
+    fn pop_point(&mut self, operator: &str) -> Result<Vector2F, EPSError> {
+        Ok(self.state.transform * self.pop_vector(operator)?)
+    }
+
+    // Pops an offset in user space from the current point and returns the point it refers to in
+    // scene space.
+    fn pop_relative_point(&mut self, operator: &str) -> Result<Vector2F, EPSError> {
+        let offset = self.pop_vector(operator)?;
+        let current_point = self.state.current_point.ok_or_else(|| no_current_point(operator))?;
+        Ok(current_point + self.state.transform.matrix * offset)
+    }
+
+    fn pop_matrix(&mut self, operator: &str) -> Result<Transform2F, EPSError> {
+        let array = match self.pop(operator)? {
+            Object::Array(array) | Object::Procedure(array) => array,
+            _ => return Err(type_check(operator)),
+        };
+        let mut elements = [0.0; 6];
+        if array.len() != elements.len() {
+            return Err(range_check(operator));
+        }
+        for (element, object) in elements.iter_mut().zip(array.iter()) {
+            match *object {
+                Object::Number(number) => *element = number as f32,
+                _ => return Err(type_check(operator)),
+            }
+        }
+        let [a, b, c, d, tx, ty] = elements;
+        Ok(Transform2F::row_major(a, c, tx, b, d, ty))
+    }
+}
+
+impl State {
+    fn new(transform: Transform2F) -> State {
+        State {
+            transform,
+            path: Outline::new(),
+            contour: Contour::new(),
+            current_point: None,
+            color: ColorU::black(),
+            line_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter(10.0),
+            miter_limit: 10.0,
+            dash: vec![],
+            dash_offset: 0.0,
+            clip_path: None,
+        }
+    }
+
+    fn flush_contour(&mut self) {
+        if !self.contour.is_empty() {
+            self.path.push_contour(mem::replace(&mut self.contour, Contour::new()));
+        }
+    }
+
+    // Removes and returns the current path.
+    fn take_path(&mut self) -> Outline {
+        self.flush_contour();
+        self.current_point = None;
+        mem::replace(&mut self.path, Outline::new())
+    }
+
+    fn move_to(&mut self, point: Vector2F) {
+        self.flush_contour();
+        self.contour.push_endpoint(point);
+        self.current_point = Some(point);
+    }
+
+    // Begins a new contour at the current point if the last one was closed, before a segment is
+    // added.
+    fn start_segment(&mut self, operator: &str) -> Result<(), EPSError> {
+        if self.contour.is_empty() {
+            let point = self.current_point.ok_or_else(|| no_current_point(operator))?;
+            self.contour.push_endpoint(point);
+        }
+        Ok(())
+    }
+
+    fn line_to(&mut self, point: Vector2F) {
+        self.contour.push_endpoint(point);
+        self.current_point = Some(point);
+    }
+
+    fn curve_to(&mut self, ctrl0: Vector2F, ctrl1: Vector2F, to: Vector2F) {
+        self.contour.push_cubic(ctrl0, ctrl1, to);
+        self.current_point = Some(to);
+    }
+
+    // Adds an arc, with angles in degrees, preceded by a line from the current point if there is
+    // one.
+    fn arc(&mut self,
+           center: Vector2F,
+           radius: f32,
+           start_angle: f32,
+           mut end_angle: f32,
+           direction: ArcDirection) {
+        // PostScript sweeps `arc` counterclockwise in user space and `arcn` clockwise, adding
+        // whole turns to the end angle as needed.
+        let sweep = end_angle - start_angle;
+        if direction == ArcDirection::CW && sweep < 0.0 {
+            end_angle += (-sweep / 360.0).ceil() * 360.0;
+        } else if direction == ArcDirection::CCW && sweep > 0.0 {
+            end_angle -= (sweep / 360.0).ceil() * 360.0;
+        }
+        let (start_angle, end_angle) = (start_angle.to_radians(), end_angle.to_radians());
+
+        if self.contour.is_empty() {
+            if let Some(point) = self.current_point {
+                self.contour.push_endpoint(point);
+            }
+        }
+
+        let transform = self.transform * Transform2F::from_scale(radius).translate(center);
+        if (end_angle - start_angle).abs() >= PI * 2.0 {
+            let mut transform = transform * Transform2F::from_rotation(start_angle);
+            if direction == ArcDirection::CCW {
+                transform = transform * Transform2F::from_scale(vec2f(1.0, -1.0));
+            }
+            self.contour.push_ellipse(&transform);
+        } else {
+            self.contour.push_arc(&transform, start_angle, end_angle, direction);
+        }
+        self.current_point = self.contour.last_position();
+    }
+
+    fn close_path(&mut self) {
+        if let Some(first) = self.contour.first_position() {
+            self.contour.close();
+            self.flush_contour();
+            self.current_point = Some(first);
+        }
+    }
+}
+
+fn matrix_object(transform: &Transform2F) -> Object {
+    let elements = [transform.m11(),
+                    transform.m21(),
+                    transform.m12(),
+                    transform.m22(),
+                    transform.m13(),
+                    transform.m23()];
+    Object::Array(Rc::new(elements.iter().map(|&element| Object::Number(element as f64)).collect()))
+}
+
+fn hsb_to_rgb(hue: f32, saturation: f32, brightness: f32) -> (f32, f32, f32) {
+    let hue = hue.max(0.0).min(1.0) * 6.0;
+    let channel = |offset: f32| {
+        let k = (offset + hue) % 6.0;
+        brightness - brightness * saturation * k.min(4.0 - k).max(0.0).min(1.0)
+    };
+    (channel(5.0), channel(3.0), channel(1.0))
+}
+
+#[inline]
+fn fill_rule_for(nonzero: bool) -> FillRule {
+    if nonzero { FillRule::Winding } else { FillRule::EvenOdd }
+}
+
+#[inline]
+fn type_check(operator: &str) -> EPSError {
+    EPSError::TypeCheck(operator.to_owned())
+}
+
+#[inline]
+fn range_check(operator: &str) -> EPSError {
+    EPSError::RangeCheck(operator.to_owned())
+}
+
+#[inline]
+fn no_current_point(operator: &str) -> EPSError {
+    EPSError::NoCurrentPoint(operator.to_owned())
+}
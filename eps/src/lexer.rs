@@ -0,0 +1,272 @@
+// pathfinder/eps/src/lexer.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Splits PostScript source into objects.
+
+use crate::EPSError;
+use crate::object::Object;
+use std::rc::Rc;
+
+pub(crate) struct Lexer<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+enum Token {
+    Object(Object),
+    ProcedureStart,
+    ProcedureEnd,
+}
+
+impl<'a> Lexer<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Lexer<'a> {
+        Lexer { data, position: 0 }
+    }
+
+    /// Returns the next object, reading procedures in their entirety, or `None` at the end of the
+    /// data.
+    pub(crate) fn next_object(&mut self) -> Result<Option<Object>, EPSError> {
+        match self.next_token()? {
+            None => Ok(None),
+            Some(Token::Object(object)) => Ok(Some(object)),
+            Some(Token::ProcedureStart) => self.read_procedure().map(Some),
+            Some(Token::ProcedureEnd) => Err(EPSError::Syntax(self.position - 1)),
+        }
+    }
+
+    fn read_procedure(&mut self) -> Result<Object, EPSError> {
+        let start = self.position - 1;
+        let mut objects = vec![];
+        loop {
+            match self.next_token()? {
+                None => return Err(EPSError::Syntax(start)),
+                Some(Token::Object(object)) => objects.push(object),
+                Some(Token::ProcedureStart) => objects.push(self.read_procedure()?),
+                Some(Token::ProcedureEnd) => return Ok(Object::Procedure(Rc::new(objects))),
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, EPSError> {
+        self.skip_whitespace_and_comments();
+        let start = self.position;
+        let byte = match self.data.get(start) {
+            None => return Ok(None),
+            Some(&byte) => byte,
+        };
+        self.position += 1;
+
+        match byte {
+            b'{' => Ok(Some(Token::ProcedureStart)),
+            b'}' => Ok(Some(Token::ProcedureEnd)),
+            b'[' => Ok(Some(Token::Object(Object::name("[")))),
+            b']' => Ok(Some(Token::Object(Object::name("]")))),
+            b'(' => self.read_string().map(|string| Some(Token::Object(string))),
+            b'<' if self.data.get(self.position) == Some(&b'<') => {
+                self.position += 1;
+                Ok(Some(Token::Object(Object::name("<<"))))
+            }
+            b'<' => self.read_hex_string().map(|string| Some(Token::Object(string))),
+            b'>' if self.data.get(self.position) == Some(&b'>') => {
+                self.position += 1;
+                Ok(Some(Token::Object(Object::name(">>"))))
+            }
+            b'/' => {
+                // `//name` is an immediately evaluated name, which we treat as an ordinary one.
+                if self.data.get(self.position) == Some(&b'/') {
+                    self.position += 1;
+                    return Ok(Some(Token::Object(Object::Name(self.read_regular()))));
+                }
+                Ok(Some(Token::Object(Object::LiteralName(self.read_regular()))))
+            }
+            _ if is_delimiter(byte) => Err(EPSError::Syntax(start)),
+            _ => {
+                self.position = start;
+                let token = self.read_regular();
+                Ok(Some(Token::Object(match parse_number(&token) {
+                    Some(number) => Object::Number(number),
+                    None => Object::Name(token),
+                })))
+            }
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&byte) = self.data.get(self.position) {
+            if byte == b'%' {
+                while let Some(&byte) = self.data.get(self.position) {
+                    if byte == b'\n' || byte == b'\r' {
+                        break;
+                    }
+                    self.position += 1;
+                }
+            } else if is_whitespace(byte) {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn read_regular(&mut self) -> Rc<str> {
+        let start = self.position;
+        while let Some(&byte) = self.data.get(self.position) {
+            if is_whitespace(byte) || is_delimiter(byte) {
+                break;
+            }
+            self.position += 1;
+        }
+        String::from_utf8_lossy(&self.data[start..self.position]).into()
+    }
+
+    // Reads a literal string, after its opening parenthesis.
+    fn read_string(&mut self) -> Result<Object, EPSError> {
+        let start = self.position - 1;
+        let (mut string, mut depth) = (vec![], 0);
+        loop {
+            let byte = *self.data.get(self.position).ok_or(EPSError::Syntax(start))?;
+            self.position += 1;
+            match byte {
+                b'(' => depth += 1,
+                b')' if depth == 0 => return Ok(Object::String(Rc::new(string))),
+                b')' => depth -= 1,
+                b'\\' => {
+                    let escaped = *self.data.get(self.position).ok_or(EPSError::Syntax(start))?;
+                    self.position += 1;
+                    match escaped {
+                        b'n' => string.push(b'\n'),
+                        b'r' => string.push(b'\r'),
+                        b't' => string.push(b'\t'),
+                        b'b' => string.push(0x08),
+                        b'f' => string.push(0x0c),
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.data.get(self.position) {
+                                    Some(&digit @ b'0'..=b'7') => {
+                                        value = value * 8 + (digit - b'0') as u32;
+                                        self.position += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            string.push(value as u8);
+                        }
+                        // A backslash before a line break continues the string on the next line.
+                        b'\r' => {
+                            if self.data.get(self.position) == Some(&b'\n') {
+                                self.position += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        _ => string.push(escaped),
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            string.push(byte);
+        }
+    }
+
+    // Reads a hexadecimal string, after its opening angle bracket.
+    fn read_hex_string(&mut self) -> Result<Object, EPSError> {
+        let start = self.position - 1;
+        let mut digits = vec![];
+        loop {
+            let byte = *self.data.get(self.position).ok_or(EPSError::Syntax(start))?;
+            self.position += 1;
+            match byte {
+                b'>' => break,
+                _ if is_whitespace(byte) => {}
+                _ => digits.push((byte as char).to_digit(16).ok_or(EPSError::Syntax(start))? as u8),
+            }
+        }
+        // An odd final digit is padded with zero.
+        let string = digits.chunks(2)
+                           .map(|pair| pair[0] << 4 | pair.get(1).cloned().unwrap_or(0))
+                           .collect();
+        Ok(Object::String(Rc::new(string)))
+    }
+}
+
+fn parse_number(token: &str) -> Option<f64> {
+    if let Some(hash) = token.find('#') {
+        let radix = token[..hash].parse::<u32>().ok()?;
+        if radix < 2 || radix > 36 {
+            return None;
+        }
+        return u32::from_str_radix(&token[(hash + 1)..], radix).ok().map(|value| value as f64);
+    }
+    // Rust accepts spellings such as `inf` and `NaN` that PostScript doesn't.
+    let first = token.bytes().find(|&byte| byte != b'+' && byte != b'-')?;
+    if !first.is_ascii_digit() && first != b'.' {
+        return None;
+    }
+    token.parse().ok()
+}
+
+#[inline]
+fn is_whitespace(byte: u8) -> bool {
+    match byte {
+        b' ' | b'\t' | b'\r' | b'\n' | 0x0c | 0 => true,
+        _ => false,
+    }
+}
+
+#[inline]
+fn is_delimiter(byte: u8) -> bool {
+    match byte {
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%' => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Lexer;
+    use crate::object::Object;
+
+    #[test]
+    fn test_tokens() {
+        let source = b"% comment\n/m {moveto} def 1 -2.5 16#ff (a\\(b\\)\\101) <48 69> [1]";
+        let mut lexer = Lexer::new(source);
+        let mut objects = vec![];
+        while let Some(object) = lexer.next_object().unwrap() {
+            objects.push(object);
+        }
+        assert_eq!(objects.len(), 11);
+        match (&objects[0], &objects[1]) {
+            (&Object::LiteralName(ref name), &Object::Procedure(ref procedure)) => {
+                assert_eq!(&**name, "m");
+                assert_eq!(procedure.len(), 1);
+            }
+            _ => panic!("expected a literal name and a procedure"),
+        }
+        let numbers: Vec<f64> = objects[3..6].iter().map(|object| match *object {
+            Object::Number(number) => number,
+            _ => panic!("expected a number"),
+        }).collect();
+        assert_eq!(numbers, vec![1.0, -2.5, 255.0]);
+        match (&objects[6], &objects[7]) {
+            (&Object::String(ref string), &Object::String(ref hex)) => {
+                assert_eq!(&string[..], b"a(b)A");
+                assert_eq!(&hex[..], b"Hi");
+            }
+            _ => panic!("expected strings"),
+        }
+    }
+
+    #[test]
+    fn test_unbalanced_procedure() {
+        assert!(Lexer::new(b"{ 1 2 add").next_object().is_err());
+        assert!(Lexer::new(b"}").next_object().is_err());
+    }
+}
@@ -0,0 +1,276 @@
+// pathfinder/eps/src/lib.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Converts a subset of Encapsulated PostScript to a Pathfinder scene.
+//!
+//! The importer runs the PostScript program with a small interpreter that understands path
+//! construction (`moveto`, `lineto`, `curveto`, `arc`, `closepath`, and their relative forms),
+//! painting (`fill`, `eofill`, `stroke`, `clip`, and the `rect` operators), the graphics state
+//! (`gsave`, line styles, dashes, gray, RGB, CMYK, and HSB colors, and the coordinate system
+//! operators), and enough of the stack, arithmetic, control, and dictionary operators to run the
+//! prologs that plotting programs emit. Text and images aren't drawn, and other operators are
+//! skipped; `EPSScene::result_flags` records when that happens.
+//!
+//! The `%%BoundingBox` comment, or `%%HiResBoundingBox` if present, becomes the scene's view box,
+//! with PostScript's upward Y axis flipped to point down.
+
+#[macro_use]
+extern crate bitflags;
+
+use crate::interpreter::Interpreter;
+use crate::lexer::Lexer;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::vec2f;
+use pathfinder_renderer::scene::Scene;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::str;
+
+mod interpreter;
+mod lexer;
+mod object;
+
+// The first four bytes of a DOS EPS binary file, which wraps the PostScript with a preview.
+const DOS_EPS_MAGIC: [u8; 4] = [0xc5, 0xd0, 0xd3, 0xc6];
+
+// The bounding box to use when the file doesn't have one: a US Letter page, in points.
+const DEFAULT_BOUNDING_BOX: [f32; 4] = [0.0, 0.0, 612.0, 792.0];
+
+pub struct EPSScene {
+    pub scene: Scene,
+    pub result_flags: BuildResultFlags,
+    /// The bounding box from the file's header comments, in PostScript points with the Y axis
+    /// pointing up, if there was one.
+    pub bounding_box: Option<RectF>,
+}
+
+bitflags! {
+    // NB: If you change this, make sure to update the `Display`
+    // implementation as well.
+    pub struct BuildResultFlags: u16 {
+        const UNSUPPORTED_OPERATOR               = 0x0001;
+        const UNSUPPORTED_TEXT                   = 0x0002;
+    }
+}
+
+/// The ways running a PostScript program can fail.
+///
+/// Errors that name an operator correspond to the PostScript errors of the same names.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EPSError {
+    /// A DOS EPS binary header pointed outside the file.
+    InvalidBinaryHeader,
+    /// The source couldn't be split into tokens. Holds the byte offset of the problem.
+    Syntax(usize),
+    StackUnderflow(String),
+    TypeCheck(String),
+    RangeCheck(String),
+    UndefinedResult(String),
+    /// A dictionary key was looked up that isn't defined.
+    Undefined(String),
+    UnmatchedMark(String),
+    NoCurrentPoint(String),
+    /// Procedures called each other too deeply.
+    ExecStackOverflow,
+    /// The program ran for too long, probably in an infinite loop.
+    LimitCheck,
+}
+
+impl EPSScene {
+    #[inline]
+    pub fn from_bytes(data: &[u8]) -> Result<EPSScene, EPSError> {
+        EPSScene::from_bytes_and_scene(data, Scene::new())
+    }
+
+    pub fn from_bytes_and_scene(data: &[u8], scene: Scene) -> Result<EPSScene, EPSError> {
+        let source = postscript_section(data)?;
+        let bounding_box = find_bounding_box(source);
+
+        let [left, bottom, right, top] = match bounding_box {
+            None => DEFAULT_BOUNDING_BOX,
+            Some(rect) => [rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y()],
+        };
+        let mut scene = scene;
+        scene.set_view_box(RectF::from_points(vec2f(0.0, 0.0), vec2f(right - left, top - bottom)));
+
+        // Map the bounding box's lower left corner to the view box's upper left.
+        let transform = Transform2F::row_major(1.0, 0.0, -left, 0.0, -1.0, top);
+        let mut interpreter = Interpreter::new(scene, transform);
+        interpreter.run(&mut Lexer::new(source))?;
+
+        Ok(EPSScene {
+            scene: interpreter.scene,
+            result_flags: interpreter.result_flags,
+            bounding_box,
+        })
+    }
+}
+
+// Returns the PostScript part of the file, skipping the previews of DOS EPS binary files.
+fn postscript_section(data: &[u8]) -> Result<&[u8], EPSError> {
+    if !data.starts_with(&DOS_EPS_MAGIC) {
+        return Ok(data);
+    }
+    let read_u32 = |offset: usize| {
+        data.get(offset..(offset + 4))
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+            .ok_or(EPSError::InvalidBinaryHeader)
+    };
+    let (start, length) = (read_u32(4)?, read_u32(8)?);
+    start.checked_add(length)
+         .and_then(|end| data.get(start..end))
+         .ok_or(EPSError::InvalidBinaryHeader)
+}
+
+// Finds the bounding box in the document structuring comments, preferring the high resolution
+// one. Boxes deferred to the trailer with `(atend)` are found there.
+fn find_bounding_box(source: &[u8]) -> Option<RectF> {
+    let mut bounding_box = None;
+    for line in source.split(|&byte| byte == b'\n' || byte == b'\r') {
+        let line = match str::from_utf8(line) {
+            Ok(line) => line,
+            Err(_) => continue,
+        };
+        let (rest, high_resolution) = if line.starts_with("%%HiResBoundingBox:") {
+            (&line["%%HiResBoundingBox:".len()..], true)
+        } else if line.starts_with("%%BoundingBox:") {
+            (&line["%%BoundingBox:".len()..], false)
+        } else {
+            continue;
+        };
+
+        let values: Vec<f32> = rest.split_whitespace().filter_map(|value| value.parse().ok())
+                                                       .collect();
+        if values.len() != 4 {
+            continue;
+        }
+        let rect = RectF::from_points(vec2f(values[0], values[1]), vec2f(values[2], values[3]));
+        if high_resolution {
+            return Some(rect);
+        }
+        if bounding_box.is_none() {
+            bounding_box = Some(rect);
+        }
+    }
+    bounding_box
+}
+
+impl Display for BuildResultFlags {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut first = true;
+        for (bit, name) in NAMES.iter().enumerate() {
+            if (self.bits() >> bit) & 1 == 0 {
+                continue;
+            }
+            if !first {
+                formatter.write_str(", ")?;
+            } else {
+                first = false;
+            }
+            formatter.write_str(name)?;
+        }
+
+        return Ok(());
+
+        // Must match the order in `BuildResultFlags`.
+        static NAMES: &'static [&'static str] = &[
+            "unsupported operator",
+            "text",
+        ];
+    }
+}
+
+impl Display for EPSError {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        match *self {
+            EPSError::InvalidBinaryHeader => write!(formatter, "invalid DOS EPS binary header"),
+            EPSError::Syntax(offset) => write!(formatter, "syntax error at byte {}", offset),
+            EPSError::StackUnderflow(ref operator) => {
+                write!(formatter, "stackunderflow in {}", operator)
+            }
+            EPSError::TypeCheck(ref operator) => write!(formatter, "typecheck in {}", operator),
+            EPSError::RangeCheck(ref operator) => write!(formatter, "rangecheck in {}", operator),
+            EPSError::UndefinedResult(ref operator) => {
+                write!(formatter, "undefinedresult in {}", operator)
+            }
+            EPSError::Undefined(ref key) => write!(formatter, "undefined key {}", key),
+            EPSError::UnmatchedMark(ref operator) => {
+                write!(formatter, "unmatchedmark in {}", operator)
+            }
+            EPSError::NoCurrentPoint(ref operator) => {
+                write!(formatter, "nocurrentpoint in {}", operator)
+            }
+            EPSError::ExecStackOverflow => write!(formatter, "execstackoverflow"),
+            EPSError::LimitCheck => write!(formatter, "operation limit exceeded"),
+        }
+    }
+}
+
+impl Error for EPSError {}
+
+#[cfg(test)]
+mod test {
+    use super::{BuildResultFlags, EPSError, EPSScene};
+    use pathfinder_content::fill::FillRule;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+    use pathfinder_renderer::scene::DrawPathId;
+
+    const PLOT: &'static [u8] = b"%!PS-Adobe-3.0 EPSF-3.0
+%%BoundingBox: 10 20 110 120
+%%EndComments
+/m { moveto } bind def
+/l { lineto } bind def
+/box { 4 dict begin /h exch def /w exch def /y exch def /x exch def
+       x y m w 0 rlineto 0 h rlineto w neg 0 rlineto closepath end } def
+gsave
+1 0 0 setrgbcolor
+10 20 50 50 box fill
+0.5 setgray 2 setlinewidth [4 2] 0 setdash
+10 20 m 110 120 l stroke
+grestore
+30 30 m 60 60 l 60 30 l closepath eofill
+/Helvetica findfont 12 scalefont setfont 20 20 moveto (label) show
+showpage
+%%EOF
+";
+
+    #[test]
+    fn test_plot() {
+        let eps = EPSScene::from_bytes(PLOT).unwrap();
+        assert_eq!(eps.bounding_box,
+                   Some(RectF::from_points(vec2f(10.0, 20.0), vec2f(110.0, 120.0))));
+        assert_eq!(eps.scene.view_box(),
+                   RectF::from_points(vec2f(0.0, 0.0), vec2f(100.0, 100.0)));
+        assert_eq!(eps.result_flags, BuildResultFlags::UNSUPPORTED_TEXT);
+        assert_eq!(eps.scene.draw_path_count(), 3);
+
+        // The box's lower left corner is the bounding box's, at the bottom of the view box.
+        let fill = &eps.scene.get_draw_path(DrawPathId(0)).outline;
+        assert_eq!(fill.bounds(), RectF::from_points(vec2f(0.0, 50.0), vec2f(50.0, 100.0)));
+        assert_eq!(eps.scene.get_draw_path(DrawPathId(2)).fill_rule, FillRule::EvenOdd);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(EPSScene::from_bytes(b"1 add").err(),
+                   Some(EPSError::StackUnderflow("add".to_owned())));
+        assert_eq!(EPSScene::from_bytes(b"10 10 lineto").err(),
+                   Some(EPSError::NoCurrentPoint("lineto".to_owned())));
+        assert_eq!(EPSScene::from_bytes(b"/f { f } def f").err(),
+                   Some(EPSError::ExecStackOverflow));
+        assert!(EPSScene::from_bytes(b"{ 0 0 moveto").is_err());
+    }
+}
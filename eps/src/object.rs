@@ -0,0 +1,77 @@
+// pathfinder/eps/src/object.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! PostScript objects.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub(crate) type Dict = Rc<RefCell<HashMap<Rc<str>, Object>>>;
+
+#[derive(Clone, Debug)]
+pub(crate) enum Object {
+    Null,
+    Bool(bool),
+    // Integers and reals are both stored as doubles, which represent every 32-bit integer.
+    Number(f64),
+    // An executable name, which is looked up and executed.
+    Name(Rc<str>),
+    LiteralName(Rc<str>),
+    String(Rc<Vec<u8>>),
+    Array(Rc<Vec<Object>>),
+    Procedure(Rc<Vec<Object>>),
+    Dict(Dict),
+    // A built-in operator, as produced by `load`.
+    Operator(Rc<str>),
+    Mark,
+    // The result of `save`, holding the depth of the graphics state stack to restore.
+    Save(usize),
+}
+
+impl Object {
+    #[inline]
+    pub(crate) fn name(name: &str) -> Object {
+        Object::Name(name.into())
+    }
+
+    /// Returns the dictionary key for this object, if it can be one.
+    pub(crate) fn to_key(&self) -> Option<Rc<str>> {
+        match *self {
+            Object::Name(ref name) | Object::LiteralName(ref name) => Some(name.clone()),
+            Object::String(ref string) => Some(String::from_utf8_lossy(string).into()),
+            _ => None,
+        }
+    }
+
+    /// Implements the `eq` operator: names and strings with the same text are equal, and
+    /// composite objects are equal only if they're the same object.
+    pub(crate) fn ps_eq(&self, other: &Object) -> bool {
+        match (self, other) {
+            (&Object::Null, &Object::Null) | (&Object::Mark, &Object::Mark) => true,
+            (&Object::Bool(a), &Object::Bool(b)) => a == b,
+            (&Object::Number(a), &Object::Number(b)) => a == b,
+            (&Object::Array(ref a), &Object::Array(ref b)) |
+            (&Object::Procedure(ref a), &Object::Procedure(ref b)) => Rc::ptr_eq(a, b),
+            (&Object::Dict(ref a), &Object::Dict(ref b)) => Rc::ptr_eq(a, b),
+            (&Object::Operator(ref a), &Object::Operator(ref b)) => a == b,
+            (&Object::Save(a), &Object::Save(b)) => a == b,
+            _ => match (self.to_key(), other.to_key()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            },
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn new_dict() -> Dict {
+    Rc::new(RefCell::new(HashMap::new()))
+}
@@ -87,7 +87,10 @@ fn export_pdf<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
         // TODO(pcwalton): Gradients and patterns.
         let paint = scene.get_paint(draw_path.paint);
         if paint.is_color() {
-            pdf.set_fill_color(paint.base_color());
+            match paint.print_color() {
+                Some(print_color) => pdf.set_fill_color_cmyk(print_color),
+                None => pdf.set_fill_color(paint.base_color()),
+            }
         }
 
         for contour in draw_path.outline.contours() {
@@ -193,8 +196,17 @@ fn export_ps<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
         // TODO(pcwalton): Gradients and patterns.
         let paint = scene.get_paint(draw_path.paint);
         if paint.is_color() {
-            let color = paint.base_color();
-            writeln!(writer, "{} {} {} setrgbcolor", color.r, color.g, color.b)?;
+            match paint.print_color() {
+                Some(print_color) => {
+                    let color = print_color.clamp();
+                    writeln!(writer, "{} {} {} {} setcmykcolor",
+                             color.c, color.m, color.y, color.k)?;
+                }
+                None => {
+                    let color = paint.base_color();
+                    writeln!(writer, "{} {} {} setrgbcolor", color.r, color.g, color.b)?;
+                }
+            }
         }
 
         writeln!(writer, "fill")?;
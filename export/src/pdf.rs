@@ -12,7 +12,7 @@
 //! (aka. saethlin)
 
 use deflate::Compression;
-use pathfinder_color::ColorU;
+use pathfinder_color::{ColorCmyk, ColorU};
 use pathfinder_geometry::vector::Vector2F;
 use std::io::{self, Write};
 
@@ -120,6 +120,12 @@ impl Pdf {
         ).unwrap();
     }
 
+    /// Set the fill color to a process color, in the DeviceCMYK color space
+    pub fn set_fill_color_cmyk(&mut self, color: ColorCmyk) {
+        let color = color.clamp();
+        writeln!(self.page_buffer, "{} {} {} {} k", color.c, color.m, color.y, color.k).unwrap();
+    }
+
     /// Move to a new page in the PDF document
     #[inline]
     pub fn add_page(&mut self, size: Vector2F) {
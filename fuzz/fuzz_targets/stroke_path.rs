@@ -0,0 +1,18 @@
+// pathfinder/fuzz/fuzz_targets/stroke_path.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pathfinder_renderer::fuzzing;
+
+fuzz_target!(|data: &[u8]| {
+    fuzzing::stroke_path_for_fuzzing(data);
+});
@@ -0,0 +1,294 @@
+// pathfinder/geo/src/geojson.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Loading GeoJSON (RFC 7946) and drawing it into scenes through a map projection.
+//!
+//! Any GeoJSON object is accepted at the top level: a feature collection, a single feature, or a
+//! bare geometry. Coordinates are longitude and latitude in degrees; altitudes are ignored.
+//!
+//! Polygons are drawn with the nonzero fill rule. Since not all producers follow the RFC's ring
+//! orientation rules, rings are reoriented so that holes always wind against their exterior, and
+//! the parts of multi-geometries are drawn as one path so that overlapping parts merge.
+
+use crate::geometry::{Feature, Geometry, PropertyValue};
+use crate::projection::Projection;
+use crate::tiles::{self, FeatureStyle};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f};
+use pathfinder_renderer::scene::Scene;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+/// The features of a GeoJSON document.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GeoJson {
+    pub features: Vec<Feature>,
+}
+
+/// The ways loading GeoJSON can fail.
+#[derive(Debug)]
+pub enum GeoJsonError {
+    /// The document isn't valid JSON.
+    Json(serde_json::Error),
+    /// An object had a missing or unknown `type`.
+    InvalidType,
+    /// A geometry's `coordinates` didn't have the shape its type requires.
+    InvalidCoordinates,
+}
+
+impl GeoJson {
+    /// Parses a GeoJSON document.
+    pub fn from_slice(data: &[u8]) -> Result<GeoJson, GeoJsonError> {
+        let value: Value = serde_json::from_slice(data).map_err(GeoJsonError::Json)?;
+        GeoJson::from_value(&value)
+    }
+
+    /// Converts an already-parsed JSON value.
+    pub fn from_value(value: &Value) -> Result<GeoJson, GeoJsonError> {
+        let mut geojson = GeoJson::default();
+        match object_type(value)? {
+            "FeatureCollection" => {
+                let features = value.get("features")
+                                    .and_then(Value::as_array)
+                                    .ok_or(GeoJsonError::InvalidType)?;
+                for feature in features {
+                    geojson.features.extend(parse_feature(feature)?);
+                }
+            }
+            "Feature" => geojson.features.extend(parse_feature(value)?),
+            _ => geojson.features.push(Feature::new(parse_geometry(value)?)),
+        }
+        Ok(geojson)
+    }
+
+    /// Returns the bounds of every feature after projection, or `None` if there are no
+    /// coordinates.
+    pub fn projected_bounds<P>(&self, projection: &P) -> Option<RectF> where P: Projection {
+        self.features.iter().filter_map(|feature| {
+            feature.geometry.map_points(&|point| projection.project(point)).bounds()
+        }).fold(None, |bounds, rect| {
+            Some(match bounds {
+                None => rect,
+                Some(bounds) => bounds.union_rect(rect),
+            })
+        })
+    }
+
+    /// Projects every feature and draws it into `scene`, transformed by `transform` after
+    /// projection.
+    ///
+    /// `style` is called with each feature and returns how to draw it, or `None` to skip it.
+    pub fn push_to_scene<P, S>(&self,
+                               scene: &mut Scene,
+                               projection: &P,
+                               transform: &Transform2F,
+                               mut style: S)
+                               where P: Projection,
+                                     S: FnMut(&Feature) -> Option<FeatureStyle> {
+        for feature in &self.features {
+            if let Some(feature_style) = style(feature) {
+                let geometry = feature.geometry.map_points(&|point| {
+                    *transform * projection.project(point)
+                });
+                tiles::push_styled_geometry(scene, &geometry, &feature_style);
+            }
+        }
+    }
+
+    /// Creates a scene of the given size with every feature projected and scaled to fit,
+    /// preserving the aspect ratio.
+    pub fn to_scene<P, S>(&self, projection: &P, size: Vector2I, style: S) -> Scene
+                          where P: Projection, S: FnMut(&Feature) -> Option<FeatureStyle> {
+        let view_box = RectF::new(Vector2F::zero(), size.to_f32());
+        let mut scene = Scene::new();
+        scene.set_view_box(view_box);
+
+        let transform = match self.projected_bounds(projection) {
+            Some(bounds) if bounds.width() > 0.0 || bounds.height() > 0.0 => {
+                let scale = (view_box.width() / bounds.width())
+                    .min(view_box.height() / bounds.height());
+                let offset = (view_box.size() - bounds.size() * scale) * 0.5;
+                Transform2F::from_translation(offset) *
+                    Transform2F::from_scale(scale) *
+                    Transform2F::from_translation(-bounds.origin())
+            }
+            _ => Transform2F::default(),
+        };
+        self.push_to_scene(&mut scene, projection, &transform, style);
+        scene
+    }
+}
+
+fn object_type(value: &Value) -> Result<&str, GeoJsonError> {
+    value.get("type").and_then(Value::as_str).ok_or(GeoJsonError::InvalidType)
+}
+
+// Features with a null geometry are allowed, and are skipped.
+fn parse_feature(value: &Value) -> Result<Option<Feature>, GeoJsonError> {
+    if object_type(value)? != "Feature" {
+        return Err(GeoJsonError::InvalidType);
+    }
+    let geometry = match value.get("geometry") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(geometry) => parse_geometry(geometry)?,
+    };
+
+    let mut feature = Feature::new(geometry);
+    if let Some(properties) = value.get("properties").and_then(Value::as_object) {
+        feature.properties = parse_properties(properties);
+    }
+    Ok(Some(feature))
+}
+
+fn parse_properties(properties: &Map<String, Value>) -> BTreeMap<String, PropertyValue> {
+    properties.iter().map(|(key, value)| {
+        let value = match *value {
+            Value::Null => PropertyValue::Null,
+            Value::Bool(boolean) => PropertyValue::Bool(boolean),
+            Value::Number(ref number) => PropertyValue::Number(number.as_f64().unwrap_or(0.0)),
+            Value::String(ref string) => PropertyValue::String(string.clone()),
+            // Nested values are kept as their JSON text.
+            Value::Array(_) | Value::Object(_) => PropertyValue::String(value.to_string()),
+        };
+        (key.clone(), value)
+    }).collect()
+}
+
+fn parse_geometry(value: &Value) -> Result<Geometry, GeoJsonError> {
+    let geometry_type = object_type(value)?;
+    if geometry_type == "GeometryCollection" {
+        let geometries = value.get("geometries")
+                              .and_then(Value::as_array)
+                              .ok_or(GeoJsonError::InvalidType)?;
+        return Ok(Geometry::GeometryCollection(
+            geometries.iter().map(parse_geometry).collect::<Result<_, _>>()?));
+    }
+
+    let coordinates = value.get("coordinates").ok_or(GeoJsonError::InvalidCoordinates)?;
+    Ok(match geometry_type {
+        "Point" => Geometry::Point(parse_position(coordinates)?),
+        "MultiPoint" => Geometry::MultiPoint(parse_positions(coordinates)?),
+        "LineString" => Geometry::LineString(parse_positions(coordinates)?),
+        "MultiLineString" => Geometry::MultiLineString(parse_rings(coordinates)?),
+        "Polygon" => Geometry::Polygon(parse_rings(coordinates)?),
+        "MultiPolygon" => {
+            Geometry::MultiPolygon(as_array(coordinates)?.iter()
+                                                         .map(parse_rings)
+                                                         .collect::<Result<_, _>>()?)
+        }
+        _ => return Err(GeoJsonError::InvalidType),
+    })
+}
+
+fn parse_rings(value: &Value) -> Result<Vec<Vec<Vector2F>>, GeoJsonError> {
+    as_array(value)?.iter().map(parse_positions).collect()
+}
+
+fn parse_positions(value: &Value) -> Result<Vec<Vector2F>, GeoJsonError> {
+    as_array(value)?.iter().map(parse_position).collect()
+}
+
+fn parse_position(value: &Value) -> Result<Vector2F, GeoJsonError> {
+    match as_array(value)?.as_slice() {
+        [x, y, ..] => {
+            match (x.as_f64(), y.as_f64()) {
+                (Some(x), Some(y)) => Ok(vec2f(x as f32, y as f32)),
+                _ => Err(GeoJsonError::InvalidCoordinates),
+            }
+        }
+        _ => Err(GeoJsonError::InvalidCoordinates),
+    }
+}
+
+fn as_array(value: &Value) -> Result<&Vec<Value>, GeoJsonError> {
+    value.as_array().ok_or(GeoJsonError::InvalidCoordinates)
+}
+
+impl Display for GeoJsonError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            GeoJsonError::Json(ref error) => write!(formatter, "invalid JSON: {}", error),
+            GeoJsonError::InvalidType => write!(formatter, "missing or unknown GeoJSON type"),
+            GeoJsonError::InvalidCoordinates => write!(formatter, "malformed coordinates"),
+        }
+    }
+}
+
+impl std::error::Error for GeoJsonError {}
+
+#[cfg(test)]
+mod test {
+    use super::GeoJson;
+    use crate::geometry::{self, Geometry, PropertyValue};
+    use crate::projection::{Projection, WebMercator};
+
+    const DOCUMENT: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {
+                "type": "Feature",
+                "properties": { "name": "Lake", "depth": 12.5 },
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [
+                        [[0, 0], [10, 0], [10, 10], [0, 10], [0, 0]],
+                        [[2, 2], [4, 2], [4, 4], [2, 4], [2, 2]]
+                    ]
+                }
+            },
+            { "type": "Feature", "properties": null, "geometry": null },
+            {
+                "type": "Feature",
+                "properties": {},
+                "geometry": {
+                    "type": "MultiLineString",
+                    "coordinates": [[[0, 0, 100], [1, 1, 100]], [[2, 2], [3, 3]]]
+                }
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_feature_collection() {
+        let geojson = GeoJson::from_slice(DOCUMENT.as_bytes()).unwrap();
+        assert_eq!(geojson.features.len(), 2);
+        assert_eq!(geojson.features[0].property("name"),
+                   Some(&PropertyValue::String("Lake".to_owned())));
+        assert_eq!(geojson.features[0].property("depth"), Some(&PropertyValue::Number(12.5)));
+        match geojson.features[1].geometry {
+            Geometry::MultiLineString(ref lines) => assert_eq!(lines.len(), 2),
+            ref geometry => panic!("expected a multi line string, got {:?}", geometry),
+        }
+    }
+
+    #[test]
+    fn test_projected_hole_winds_against_exterior() {
+        // Both rings are counterclockwise in the source, which the RFC forbids but producers
+        // emit anyway.
+        let geojson = GeoJson::from_slice(DOCUMENT.as_bytes()).unwrap();
+        let projected = geojson.features[0].geometry.map_points(&|point| {
+            WebMercator.project(point)
+        });
+        let outline = projected.area_outline();
+        let contours = outline.contours();
+        assert_eq!(contours.len(), 2);
+        let exterior_area = geometry::signed_area(contours[0].points());
+        let hole_area = geometry::signed_area(contours[1].points());
+        assert!(exterior_area * hole_area < 0.0);
+    }
+
+    #[test]
+    fn test_invalid_coordinates() {
+        let document = r#"{ "type": "Point", "coordinates": [1] }"#;
+        assert!(GeoJson::from_slice(document.as_bytes()).is_err());
+    }
+}
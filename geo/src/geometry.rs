@@ -0,0 +1,303 @@
+// pathfinder/geo/src/geometry.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Simple features: points, line strings, and polygons, with their properties.
+//!
+//! The geometry model follows GeoJSON. Coordinates are in whatever space the source uses
+//! (longitude/latitude in degrees for GeoJSON, tile units for vector tiles) until they're
+//! projected.
+
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::Vector2F;
+use std::collections::BTreeMap;
+
+/// A geometry, in the GeoJSON sense.
+///
+/// A polygon is a list of rings, the first of which is the exterior and the rest of which are
+/// holes. Rings may be given in either orientation and may or may not repeat their first point at
+/// the end.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Geometry {
+    Point(Vector2F),
+    MultiPoint(Vec<Vector2F>),
+    LineString(Vec<Vector2F>),
+    MultiLineString(Vec<Vec<Vector2F>>),
+    Polygon(Vec<Vec<Vector2F>>),
+    MultiPolygon(Vec<Vec<Vec<Vector2F>>>),
+    GeometryCollection(Vec<Geometry>),
+}
+
+/// The value of a feature property.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+/// A geometry with properties, e.g. a road with its name and class.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Feature {
+    pub geometry: Geometry,
+    pub properties: BTreeMap<String, PropertyValue>,
+}
+
+impl Feature {
+    /// Creates a feature with no properties.
+    #[inline]
+    pub fn new(geometry: Geometry) -> Feature {
+        Feature { geometry, properties: BTreeMap::new() }
+    }
+
+    /// Returns the value of the named property, if present.
+    #[inline]
+    pub fn property(&self, name: &str) -> Option<&PropertyValue> {
+        self.properties.get(name)
+    }
+}
+
+impl PropertyValue {
+    /// Returns the value as a string, if it is one.
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            PropertyValue::String(ref string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a number, if it is one.
+    #[inline]
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            PropertyValue::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+}
+
+impl Geometry {
+    /// Returns a copy of this geometry with `f` applied to every coordinate.
+    pub fn map_points<F>(&self, f: &F) -> Geometry where F: Fn(Vector2F) -> Vector2F {
+        let map_line = |line: &Vec<Vector2F>| line.iter().map(|&point| f(point)).collect();
+        let map_polygon = |polygon: &Vec<Vec<Vector2F>>| polygon.iter().map(map_line).collect();
+        match *self {
+            Geometry::Point(point) => Geometry::Point(f(point)),
+            Geometry::MultiPoint(ref points) => Geometry::MultiPoint(map_line(points)),
+            Geometry::LineString(ref line) => Geometry::LineString(map_line(line)),
+            Geometry::MultiLineString(ref lines) => {
+                Geometry::MultiLineString(lines.iter().map(map_line).collect())
+            }
+            Geometry::Polygon(ref polygon) => Geometry::Polygon(map_polygon(polygon)),
+            Geometry::MultiPolygon(ref polygons) => {
+                Geometry::MultiPolygon(polygons.iter().map(map_polygon).collect())
+            }
+            Geometry::GeometryCollection(ref geometries) => {
+                Geometry::GeometryCollection(geometries.iter()
+                                                       .map(|geometry| geometry.map_points(f))
+                                                       .collect())
+            }
+        }
+    }
+
+    /// Returns the bounding box of every coordinate, or `None` if there are none.
+    pub fn bounds(&self) -> Option<RectF> {
+        let mut bounds: Option<RectF> = None;
+        self.for_each_point(&mut |point| {
+            bounds = Some(match bounds {
+                None => RectF::new(point, Vector2F::zero()),
+                Some(bounds) => bounds.union_point(point),
+            });
+        });
+        bounds
+    }
+
+    /// Returns the filled area of the polygons in this geometry, for filling with the nonzero
+    /// winding rule.
+    ///
+    /// Exterior rings are oriented one way and holes the other, whatever their orientation in
+    /// the source, so holes cut out of their polygon and overlapping polygons merge.
+    pub fn area_outline(&self) -> Outline {
+        let mut outline = Outline::new();
+        self.push_areas(&mut outline);
+        outline
+    }
+
+    /// Returns the lines in this geometry, for stroking: line strings as open contours and
+    /// polygon rings as closed ones.
+    pub fn line_outline(&self) -> Outline {
+        let mut outline = Outline::new();
+        self.push_lines(&mut outline);
+        outline
+    }
+
+    /// Returns a circle of the given radius around each point in this geometry.
+    pub fn point_outline(&self, radius: f32) -> Outline {
+        let mut outline = Outline::new();
+        self.push_points(&mut outline, radius);
+        outline
+    }
+
+    fn for_each_point(&self, f: &mut dyn FnMut(Vector2F)) {
+        match *self {
+            Geometry::Point(point) => f(point),
+            Geometry::MultiPoint(ref line) | Geometry::LineString(ref line) => {
+                line.iter().for_each(|&point| f(point))
+            }
+            Geometry::MultiLineString(ref lines) | Geometry::Polygon(ref lines) => {
+                lines.iter().flatten().for_each(|&point| f(point))
+            }
+            Geometry::MultiPolygon(ref polygons) => {
+                polygons.iter().flatten().flatten().for_each(|&point| f(point))
+            }
+            Geometry::GeometryCollection(ref geometries) => {
+                geometries.iter().for_each(|geometry| geometry.for_each_point(f))
+            }
+        }
+    }
+
+    fn push_areas(&self, outline: &mut Outline) {
+        match *self {
+            Geometry::Polygon(ref polygon) => push_polygon(outline, polygon),
+            Geometry::MultiPolygon(ref polygons) => {
+                polygons.iter().for_each(|polygon| push_polygon(outline, polygon))
+            }
+            Geometry::GeometryCollection(ref geometries) => {
+                geometries.iter().for_each(|geometry| geometry.push_areas(outline))
+            }
+            Geometry::Point(_) |
+            Geometry::MultiPoint(_) |
+            Geometry::LineString(_) |
+            Geometry::MultiLineString(_) => {}
+        }
+    }
+
+    fn push_lines(&self, outline: &mut Outline) {
+        match *self {
+            Geometry::LineString(ref line) => push_ring(outline, line, false, false),
+            Geometry::MultiLineString(ref lines) => {
+                lines.iter().for_each(|line| push_ring(outline, line, false, false))
+            }
+            Geometry::Polygon(ref rings) => {
+                rings.iter().for_each(|ring| push_ring(outline, ring, true, false))
+            }
+            Geometry::MultiPolygon(ref polygons) => {
+                polygons.iter().flatten().for_each(|ring| push_ring(outline, ring, true, false))
+            }
+            Geometry::GeometryCollection(ref geometries) => {
+                geometries.iter().for_each(|geometry| geometry.push_lines(outline))
+            }
+            Geometry::Point(_) | Geometry::MultiPoint(_) => {}
+        }
+    }
+
+    fn push_points(&self, outline: &mut Outline, radius: f32) {
+        let mut push_point = |point: Vector2F| {
+            let mut contour = Contour::new();
+            contour.push_ellipse(&Transform2F::from_scale(radius).translate(point));
+            outline.push_contour(contour);
+        };
+        match *self {
+            Geometry::Point(point) => push_point(point),
+            Geometry::MultiPoint(ref points) => points.iter().for_each(|&point| push_point(point)),
+            Geometry::GeometryCollection(ref geometries) => {
+                geometries.iter().for_each(|geometry| geometry.push_points(outline, radius))
+            }
+            _ => {}
+        }
+    }
+}
+
+fn push_polygon(outline: &mut Outline, rings: &[Vec<Vector2F>]) {
+    for (ring_index, ring) in rings.iter().enumerate() {
+        let is_exterior = ring_index == 0;
+        let reverse = (signed_area(ring) < 0.0) == is_exterior;
+        push_ring(outline, ring, true, reverse);
+    }
+}
+
+fn push_ring(outline: &mut Outline, points: &[Vector2F], close: bool, reverse: bool) {
+    let mut points = points.to_vec();
+    if close && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    if points.len() < 2 {
+        return;
+    }
+    if reverse {
+        points.reverse();
+    }
+
+    let mut contour = Contour::with_capacity(points.len());
+    points.into_iter().for_each(|point| contour.push_endpoint(point));
+    if close {
+        contour.close();
+    }
+    outline.push_contour(contour);
+}
+
+// Twice the signed area, by the shoelace formula. Only the sign matters here.
+pub(crate) fn signed_area(ring: &[Vector2F]) -> f32 {
+    let mut area = 0.0;
+    for (index, &from) in ring.iter().enumerate() {
+        let to = ring[(index + 1) % ring.len()];
+        area += from.x() * to.y() - to.x() * from.y();
+    }
+    area
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Geometry, signed_area};
+    use pathfinder_geometry::vector::{Vector2F, vec2f};
+
+    fn square(origin: Vector2F, size: f32, clockwise: bool) -> Vec<Vector2F> {
+        let mut ring = vec![
+            origin,
+            origin + vec2f(size, 0.0),
+            origin + vec2f(size, size),
+            origin + vec2f(0.0, size),
+            origin,
+        ];
+        if !clockwise {
+            ring.reverse();
+        }
+        ring
+    }
+
+    #[test]
+    fn test_holes_are_oriented_against_exteriors() {
+        // Both rings are given in the same orientation, as some sources do.
+        let exterior = square(vec2f(0.0, 0.0), 10.0, true);
+        let hole = square(vec2f(2.0, 2.0), 4.0, true);
+        let outline = Geometry::Polygon(vec![exterior, hole]).area_outline();
+
+        let contours = outline.contours();
+        assert_eq!(contours.len(), 2);
+        assert!(contours.iter().all(|contour| contour.is_closed() && contour.len() == 4));
+        let exterior_area = signed_area(contours[0].points());
+        let hole_area = signed_area(contours[1].points());
+        assert!(exterior_area * hole_area < 0.0);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let geometry = Geometry::GeometryCollection(vec![
+            Geometry::Point(vec2f(-5.0, 3.0)),
+            Geometry::LineString(vec![vec2f(1.0, 1.0), vec2f(4.0, 8.0)]),
+        ]);
+        let bounds = geometry.bounds().unwrap();
+        assert_eq!(bounds.origin(), vec2f(-5.0, 1.0));
+        assert_eq!(bounds.lower_right(), vec2f(4.0, 8.0));
+    }
+}
@@ -0,0 +1,18 @@
+// pathfinder/geo/src/lib.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Geographic vector data for Pathfinder: features, map projections, GeoJSON loading, Mapbox
+//! Vector Tile decoding, and rendering of standard z/x/y web map tiles.
+
+pub mod geojson;
+pub mod geometry;
+pub mod mvt;
+pub mod projection;
+pub mod tiles;
@@ -0,0 +1,526 @@
+// pathfinder/geo/src/mvt.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Decoding of Mapbox Vector Tiles (MVT), versions 1 and 2.
+//!
+//! `VectorTile::decode()` parses the protobuf encoding into layers of features, with coordinates
+//! in tile units (0 to the layer's extent, Y down). `VectorTile::push_to_scene()` draws them
+//! directly, with a callback choosing the style of each feature from its layer and properties.
+//!
+//! See <https://github.com/mapbox/vector-tile-spec/tree/master/2.1>.
+
+use crate::geometry::{self, Feature, Geometry, PropertyValue};
+use crate::tiles::{self, FeatureStyle};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f, vec2i};
+use pathfinder_renderer::scene::Scene;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+use std::str;
+
+const DEFAULT_EXTENT: u32 = 4096;
+
+const GEOM_TYPE_POINT: u32 = 1;
+const GEOM_TYPE_LINESTRING: u32 = 2;
+const GEOM_TYPE_POLYGON: u32 = 3;
+
+const COMMAND_MOVE_TO: u32 = 1;
+const COMMAND_LINE_TO: u32 = 2;
+const COMMAND_CLOSE_PATH: u32 = 7;
+
+const WIRE_TYPE_VARINT: u8 = 0;
+const WIRE_TYPE_FIXED64: u8 = 1;
+const WIRE_TYPE_LENGTH_DELIMITED: u8 = 2;
+const WIRE_TYPE_FIXED32: u8 = 5;
+
+/// A decoded vector tile.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VectorTile {
+    pub layers: Vec<VectorTileLayer>,
+}
+
+/// A named layer of a vector tile, e.g. "water" or "roads".
+#[derive(Clone, Debug, PartialEq)]
+pub struct VectorTileLayer {
+    pub name: String,
+    /// The size of the tile in this layer's coordinate space. Coordinates outside `0..extent`
+    /// are in the tile's buffer zone.
+    pub extent: u32,
+    pub features: Vec<Feature>,
+}
+
+/// The ways decoding a vector tile can fail.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MvtError {
+    /// The data ended in the middle of a field.
+    UnexpectedEof,
+    /// A varint was longer than 64 bits.
+    InvalidVarint,
+    /// A field had a protobuf wire type that isn't supported.
+    InvalidWireType(u8),
+    /// A string wasn't valid UTF-8.
+    InvalidUtf8,
+    /// A feature's tags referred to a key or value that the layer doesn't have.
+    InvalidTag,
+    /// A feature's geometry commands were malformed.
+    InvalidGeometry,
+    /// The layer's version isn't 1 or 2.
+    UnsupportedVersion(u32),
+}
+
+impl VectorTile {
+    /// Decodes a tile from its protobuf encoding. The data must already be decompressed.
+    pub fn decode(data: &[u8]) -> Result<VectorTile, MvtError> {
+        let mut tile = VectorTile::default();
+        let mut reader = ProtobufReader::new(data);
+        while let Some((field, wire_type)) = reader.read_key()? {
+            match field {
+                3 => tile.layers.push(decode_layer(reader.read_bytes(wire_type)?)?),
+                _ => reader.skip(wire_type)?,
+            }
+        }
+        Ok(tile)
+    }
+
+    /// Returns the layer with the given name, if present.
+    pub fn layer(&self, name: &str) -> Option<&VectorTileLayer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    /// Draws every feature in the tile into `scene`, scaled so that the tile covers `size`
+    /// pixels square and then transformed by `transform`.
+    ///
+    /// `style` is called with each feature and the layer it belongs to, and returns how to draw
+    /// it, or `None` to skip it. Layers are drawn in order.
+    pub fn push_to_scene<S>(&self,
+                            scene: &mut Scene,
+                            size: f32,
+                            transform: &Transform2F,
+                            mut style: S)
+                            where S: FnMut(&VectorTileLayer, &Feature) -> Option<FeatureStyle> {
+        for layer in &self.layers {
+            let layer_transform = *transform * Transform2F::from_scale(size / layer.extent as f32);
+            for feature in &layer.features {
+                if let Some(feature_style) = style(layer, feature) {
+                    let geometry = feature.geometry.map_points(&|point| layer_transform * point);
+                    tiles::push_styled_geometry(scene, &geometry, &feature_style);
+                }
+            }
+        }
+    }
+
+    /// Creates a scene of `size` pixels square containing every feature in the tile.
+    ///
+    /// See `push_to_scene()` for the meaning of `style`.
+    pub fn to_scene<S>(&self, size: i32, style: S) -> Scene
+                       where S: FnMut(&VectorTileLayer, &Feature) -> Option<FeatureStyle> {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::zero(), vec2i(size, size).to_f32()));
+        self.push_to_scene(&mut scene, size as f32, &Transform2F::default(), style);
+        scene
+    }
+}
+
+fn decode_layer(data: &[u8]) -> Result<VectorTileLayer, MvtError> {
+    let mut name = String::new();
+    let mut extent = DEFAULT_EXTENT;
+    let mut keys = vec![];
+    let mut values = vec![];
+    let mut raw_features = vec![];
+
+    let mut reader = ProtobufReader::new(data);
+    while let Some((field, wire_type)) = reader.read_key()? {
+        match field {
+            1 => name = reader.read_string(wire_type)?.to_owned(),
+            2 => raw_features.push(reader.read_bytes(wire_type)?),
+            3 => keys.push(reader.read_string(wire_type)?.to_owned()),
+            4 => values.push(decode_value(reader.read_bytes(wire_type)?)?),
+            5 => extent = reader.read_varint_field(wire_type)? as u32,
+            15 => {
+                let version = reader.read_varint_field(wire_type)? as u32;
+                if version != 1 && version != 2 {
+                    return Err(MvtError::UnsupportedVersion(version));
+                }
+            }
+            _ => reader.skip(wire_type)?,
+        }
+    }
+
+    let mut features = Vec::with_capacity(raw_features.len());
+    for raw_feature in raw_features {
+        if let Some(feature) = decode_feature(raw_feature, &keys, &values)? {
+            features.push(feature);
+        }
+    }
+    Ok(VectorTileLayer { name, extent, features })
+}
+
+fn decode_value(data: &[u8]) -> Result<PropertyValue, MvtError> {
+    let mut value = PropertyValue::Null;
+    let mut reader = ProtobufReader::new(data);
+    while let Some((field, wire_type)) = reader.read_key()? {
+        value = match field {
+            1 => PropertyValue::String(reader.read_string(wire_type)?.to_owned()),
+            2 => PropertyValue::Number(f32::from_bits(reader.read_fixed32(wire_type)?) as f64),
+            3 => PropertyValue::Number(f64::from_bits(reader.read_fixed64(wire_type)?)),
+            4 => PropertyValue::Number(reader.read_varint_field(wire_type)? as i64 as f64),
+            5 => PropertyValue::Number(reader.read_varint_field(wire_type)? as f64),
+            6 => PropertyValue::Number(zigzag_decode(reader.read_varint_field(wire_type)?) as f64),
+            7 => PropertyValue::Bool(reader.read_varint_field(wire_type)? != 0),
+            _ => {
+                reader.skip(wire_type)?;
+                continue;
+            }
+        };
+    }
+    Ok(value)
+}
+
+// Returns `None` for features of unknown type, which the spec says to ignore.
+fn decode_feature(data: &[u8], keys: &[String], values: &[PropertyValue])
+                  -> Result<Option<Feature>, MvtError> {
+    let mut tags = vec![];
+    let mut geom_type = 0;
+    let mut commands = vec![];
+
+    let mut reader = ProtobufReader::new(data);
+    while let Some((field, wire_type)) = reader.read_key()? {
+        match field {
+            2 => reader.read_packed_u32(wire_type, &mut tags)?,
+            3 => geom_type = reader.read_varint_field(wire_type)? as u32,
+            4 => reader.read_packed_u32(wire_type, &mut commands)?,
+            _ => reader.skip(wire_type)?,
+        }
+    }
+
+    let geometry = match decode_geometry(geom_type, &commands)? {
+        None => return Ok(None),
+        Some(geometry) => geometry,
+    };
+
+    let mut feature = Feature::new(geometry);
+    for tag in tags.chunks(2) {
+        if tag.len() < 2 {
+            return Err(MvtError::InvalidTag);
+        }
+        let key = keys.get(tag[0] as usize).ok_or(MvtError::InvalidTag)?;
+        let value = values.get(tag[1] as usize).ok_or(MvtError::InvalidTag)?;
+        feature.properties.insert(key.clone(), value.clone());
+    }
+    Ok(Some(feature))
+}
+
+fn decode_geometry(geom_type: u32, commands: &[u32]) -> Result<Option<Geometry>, MvtError> {
+    // Decode the command stream into a list of lines, each begun by a MoveTo.
+    let mut lines: Vec<Vec<Vector2F>> = vec![];
+    let mut cursor = (0i64, 0i64);
+    let mut index = 0;
+    while index < commands.len() {
+        let command = commands[index] & 0x7;
+        let count = commands[index] >> 3;
+        index += 1;
+        match command {
+            COMMAND_MOVE_TO | COMMAND_LINE_TO => {
+                for _ in 0..count {
+                    if index + 2 > commands.len() {
+                        return Err(MvtError::InvalidGeometry);
+                    }
+                    cursor.0 += zigzag_decode(commands[index] as u64);
+                    cursor.1 += zigzag_decode(commands[index + 1] as u64);
+                    index += 2;
+
+                    let point = vec2f(cursor.0 as f32, cursor.1 as f32);
+                    if command == COMMAND_MOVE_TO {
+                        lines.push(vec![point]);
+                    } else {
+                        lines.last_mut().ok_or(MvtError::InvalidGeometry)?.push(point);
+                    }
+                }
+            }
+            // Rings are closed implicitly.
+            COMMAND_CLOSE_PATH => {}
+            _ => return Err(MvtError::InvalidGeometry),
+        }
+    }
+
+    let geometry = match geom_type {
+        GEOM_TYPE_POINT => {
+            let mut points: Vec<Vector2F> = lines.into_iter().flatten().collect();
+            match points.len() {
+                0 => return Err(MvtError::InvalidGeometry),
+                1 => Geometry::Point(points.pop().unwrap()),
+                _ => Geometry::MultiPoint(points),
+            }
+        }
+        GEOM_TYPE_LINESTRING => {
+            match lines.len() {
+                0 => return Err(MvtError::InvalidGeometry),
+                1 => Geometry::LineString(lines.pop().unwrap()),
+                _ => Geometry::MultiLineString(lines),
+            }
+        }
+        GEOM_TYPE_POLYGON => {
+            // Each exterior ring (positive area in Y-down coordinates) starts a new polygon; the
+            // negative rings that follow it are its holes.
+            let mut polygons: Vec<Vec<Vec<Vector2F>>> = vec![];
+            let mut current_polygon = vec![];
+            for ring in lines {
+                let area = geometry::signed_area(&ring);
+                if area == 0.0 {
+                    continue;
+                }
+                if area > 0.0 && !current_polygon.is_empty() {
+                    polygons.push(mem::replace(&mut current_polygon, vec![]));
+                }
+                current_polygon.push(ring);
+            }
+            if !current_polygon.is_empty() {
+                polygons.push(current_polygon);
+            }
+            match polygons.len() {
+                0 => return Err(MvtError::InvalidGeometry),
+                1 => Geometry::Polygon(polygons.pop().unwrap()),
+                _ => Geometry::MultiPolygon(polygons),
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(geometry))
+}
+
+#[inline]
+fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+// Just enough of a protobuf decoder for the vector tile schema.
+struct ProtobufReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ProtobufReader<'a> {
+    fn new(data: &'a [u8]) -> ProtobufReader<'a> {
+        ProtobufReader { data, position: 0 }
+    }
+
+    fn read_key(&mut self) -> Result<Option<(u32, u8)>, MvtError> {
+        if self.position >= self.data.len() {
+            return Ok(None);
+        }
+        let key = self.read_varint()?;
+        Ok(Some(((key >> 3) as u32, (key & 0x7) as u8)))
+    }
+
+    fn read_varint(&mut self) -> Result<u64, MvtError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.data.get(self.position).ok_or(MvtError::UnexpectedEof)?;
+            self.position += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(MvtError::InvalidVarint)
+    }
+
+    fn read_varint_field(&mut self, wire_type: u8) -> Result<u64, MvtError> {
+        expect_wire_type(wire_type, WIRE_TYPE_VARINT)?;
+        self.read_varint()
+    }
+
+    fn read_fixed32(&mut self, wire_type: u8) -> Result<u32, MvtError> {
+        expect_wire_type(wire_type, WIRE_TYPE_FIXED32)?;
+        let bytes = self.read_slice(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_fixed64(&mut self, wire_type: u8) -> Result<u64, MvtError> {
+        expect_wire_type(wire_type, WIRE_TYPE_FIXED64)?;
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.read_slice(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn read_bytes(&mut self, wire_type: u8) -> Result<&'a [u8], MvtError> {
+        expect_wire_type(wire_type, WIRE_TYPE_LENGTH_DELIMITED)?;
+        let length = self.read_varint()? as usize;
+        self.read_slice(length)
+    }
+
+    fn read_string(&mut self, wire_type: u8) -> Result<&'a str, MvtError> {
+        str::from_utf8(self.read_bytes(wire_type)?).map_err(|_| MvtError::InvalidUtf8)
+    }
+
+    // Accepts both packed and unpacked encodings, as protobuf parsers must.
+    fn read_packed_u32(&mut self, wire_type: u8, values: &mut Vec<u32>) -> Result<(), MvtError> {
+        if wire_type == WIRE_TYPE_VARINT {
+            values.push(self.read_varint()? as u32);
+            return Ok(());
+        }
+        let mut packed_reader = ProtobufReader::new(self.read_bytes(wire_type)?);
+        while packed_reader.position < packed_reader.data.len() {
+            values.push(packed_reader.read_varint()? as u32);
+        }
+        Ok(())
+    }
+
+    fn read_slice(&mut self, length: usize) -> Result<&'a [u8], MvtError> {
+        let end = self.position.checked_add(length).ok_or(MvtError::UnexpectedEof)?;
+        let slice = self.data.get(self.position..end).ok_or(MvtError::UnexpectedEof)?;
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn skip(&mut self, wire_type: u8) -> Result<(), MvtError> {
+        match wire_type {
+            WIRE_TYPE_VARINT => {
+                self.read_varint()?;
+            }
+            WIRE_TYPE_FIXED64 => {
+                self.read_slice(8)?;
+            }
+            WIRE_TYPE_LENGTH_DELIMITED => {
+                self.read_bytes(wire_type)?;
+            }
+            WIRE_TYPE_FIXED32 => {
+                self.read_slice(4)?;
+            }
+            _ => return Err(MvtError::InvalidWireType(wire_type)),
+        }
+        Ok(())
+    }
+}
+
+fn expect_wire_type(wire_type: u8, expected: u8) -> Result<(), MvtError> {
+    if wire_type == expected {
+        Ok(())
+    } else {
+        Err(MvtError::InvalidWireType(wire_type))
+    }
+}
+
+impl Display for MvtError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            MvtError::UnexpectedEof => write!(formatter, "unexpected end of data"),
+            MvtError::InvalidVarint => write!(formatter, "invalid varint"),
+            MvtError::InvalidWireType(wire_type) => {
+                write!(formatter, "invalid wire type {}", wire_type)
+            }
+            MvtError::InvalidUtf8 => write!(formatter, "invalid UTF-8 in string"),
+            MvtError::InvalidTag => write!(formatter, "feature tag out of range"),
+            MvtError::InvalidGeometry => write!(formatter, "malformed geometry commands"),
+            MvtError::UnsupportedVersion(version) => {
+                write!(formatter, "unsupported vector tile version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MvtError {}
+
+#[cfg(test)]
+mod test {
+    use super::{MvtError, VectorTile};
+    use crate::geometry::{Geometry, PropertyValue};
+    use pathfinder_geometry::vector::vec2f;
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn bytes_field(field: u64, data: &[u8], out: &mut Vec<u8>) {
+        varint(field << 3 | 2, out);
+        varint(data.len() as u64, out);
+        out.extend_from_slice(data);
+    }
+
+    fn varint_field(field: u64, value: u64, out: &mut Vec<u8>) {
+        varint(field << 3, out);
+        varint(value, out);
+    }
+
+    fn packed_field(field: u64, values: &[u32], out: &mut Vec<u8>) {
+        let mut packed = vec![];
+        values.iter().for_each(|&value| varint(value as u64, &mut packed));
+        bytes_field(field, &packed, out);
+    }
+
+    fn zigzag(value: i32) -> u32 {
+        ((value << 1) ^ (value >> 31)) as u32
+    }
+
+    // A 10×10 square from (2, 2), with a 2×2 hole at (4, 4) and a "class" property.
+    fn square_tile() -> Vec<u8> {
+        let move_to = |count: u32| 1 | count << 3;
+        let line_to = |count: u32| 2 | count << 3;
+        let close_path = 7 | 1 << 3;
+        let geometry = [
+            move_to(1), zigzag(2), zigzag(2),
+            line_to(3), zigzag(10), zigzag(0), zigzag(0), zigzag(10), zigzag(-10), zigzag(0),
+            close_path,
+            move_to(1), zigzag(2), zigzag(-8),
+            line_to(3), zigzag(0), zigzag(2), zigzag(2), zigzag(0), zigzag(0), zigzag(-2),
+            close_path,
+        ];
+
+        let mut feature = vec![];
+        packed_field(2, &[0, 0], &mut feature);
+        varint_field(3, 3, &mut feature);
+        packed_field(4, &geometry, &mut feature);
+
+        let mut value = vec![];
+        bytes_field(1, b"park", &mut value);
+
+        let mut layer = vec![];
+        varint_field(15, 2, &mut layer);
+        bytes_field(1, b"landuse", &mut layer);
+        bytes_field(2, &feature, &mut layer);
+        bytes_field(3, b"class", &mut layer);
+        bytes_field(4, &value, &mut layer);
+        varint_field(5, 256, &mut layer);
+
+        let mut tile = vec![];
+        bytes_field(3, &layer, &mut tile);
+        tile
+    }
+
+    #[test]
+    fn test_decode_polygon_with_hole() {
+        let tile = VectorTile::decode(&square_tile()).unwrap();
+        let layer = tile.layer("landuse").unwrap();
+        assert_eq!(layer.extent, 256);
+        assert_eq!(layer.features.len(), 1);
+
+        let feature = &layer.features[0];
+        assert_eq!(feature.property("class"), Some(&PropertyValue::String("park".to_owned())));
+        match feature.geometry {
+            Geometry::Polygon(ref rings) => {
+                assert_eq!(rings.len(), 2);
+                assert_eq!(rings[0][2], vec2f(12.0, 12.0));
+                assert_eq!(rings[1][0], vec2f(4.0, 4.0));
+            }
+            ref geometry => panic!("expected a polygon, got {:?}", geometry),
+        }
+    }
+
+    #[test]
+    fn test_truncated_tile() {
+        let data = square_tile();
+        assert_eq!(VectorTile::decode(&data[..data.len() - 3]), Err(MvtError::UnexpectedEof));
+    }
+}
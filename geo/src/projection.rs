@@ -0,0 +1,70 @@
+// pathfinder/geo/src/projection.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Map projections from longitude and latitude to the plane.
+
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use std::f32::consts::PI;
+
+/// The latitude beyond which Web Mercator is cut off, making the world square.
+pub const WEB_MERCATOR_MAX_LATITUDE: f32 = 85.051_13;
+
+/// Projects longitude and latitude, in degrees, to the plane.
+pub trait Projection {
+    /// Projects a point whose X coordinate is the longitude and whose Y coordinate is the
+    /// latitude, both in degrees.
+    fn project(&self, lon_lat: Vector2F) -> Vector2F;
+}
+
+/// The spherical Web Mercator projection (EPSG:3857) used by web maps.
+///
+/// Points are projected into "world" coordinates, where the whole world is the unit square, with
+/// (0, 0) at the top left (180°W, 85.05°N) and Y increasing southward. This is the coordinate
+/// space that z/x/y tiles divide up.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WebMercator;
+
+impl Projection for WebMercator {
+    fn project(&self, lon_lat: Vector2F) -> Vector2F {
+        let latitude = lon_lat.y()
+                              .max(-WEB_MERCATOR_MAX_LATITUDE)
+                              .min(WEB_MERCATOR_MAX_LATITUDE)
+                              .to_radians();
+        let x = lon_lat.x() / 360.0 + 0.5;
+        let y = 0.5 - (PI * 0.25 + latitude * 0.5).tan().ln() / (2.0 * PI);
+        vec2f(x, y)
+    }
+}
+
+impl<F> Projection for F where F: Fn(Vector2F) -> Vector2F {
+    #[inline]
+    fn project(&self, lon_lat: Vector2F) -> Vector2F {
+        (*self)(lon_lat)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Projection, WEB_MERCATOR_MAX_LATITUDE, WebMercator};
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_web_mercator() {
+        let center = WebMercator.project(vec2f(0.0, 0.0));
+        assert!((center - vec2f(0.5, 0.5)).length() < 1e-6);
+
+        let top_left = WebMercator.project(vec2f(-180.0, WEB_MERCATOR_MAX_LATITUDE));
+        assert!((top_left - vec2f(0.0, 0.0)).length() < 1e-4);
+
+        // Latitudes past the cutoff are clamped.
+        let bottom_right = WebMercator.project(vec2f(180.0, -90.0));
+        assert!((bottom_right - vec2f(1.0, 1.0)).length() < 1e-4);
+    }
+}
@@ -0,0 +1,313 @@
+// pathfinder/geo/src/tiles.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rendering features into standard z/x/y web map tiles, e.g. for a tile server.
+//!
+//! Features are projected into world coordinates once, with `ProjectedFeatures::new()`. A
+//! `TileRenderer` then draws any number of tiles from them on the same GPU device, skipping
+//! features whose bounds miss each tile, and encodes the results as PNG.
+//!
+//! Tiles use the XYZ ("slippy map") scheme: zoom level `z` divides the Web Mercator world into
+//! 2<sup>z</sup> × 2<sup>z</sup> tiles, with (0, 0) at the top left.
+
+use crate::geometry::{Feature, Geometry};
+use crate::projection::Projection;
+use image::{ImageFormat, RgbaImage};
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::outline::Outline;
+use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2I, vec2f};
+use pathfinder_headless::{HeadlessError, HeadlessRenderer};
+use pathfinder_renderer::paint::{Paint, PaintId};
+use pathfinder_renderer::scene::{DrawPath, Scene};
+use std::io::Cursor;
+
+/// The address of a map tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TileId {
+    /// The zoom level.
+    pub z: u8,
+    /// The column, from 0 at 180°W.
+    pub x: u32,
+    /// The row, from 0 at the north edge.
+    pub y: u32,
+}
+
+/// How to draw a feature. Polygons are filled and outlined, lines are stroked, and points are
+/// drawn as filled circles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FeatureStyle {
+    /// The color to fill polygons and points with, if any.
+    pub fill: Option<ColorU>,
+    /// The color to stroke lines and polygon outlines with, if any.
+    pub stroke: Option<ColorU>,
+    /// The width of strokes, in tile pixels.
+    pub line_width: f32,
+    /// The radius of points, in tile pixels.
+    pub point_radius: f32,
+}
+
+/// Features projected into world coordinates, ready to be drawn into tiles.
+pub struct ProjectedFeatures {
+    features: Vec<ProjectedFeature>,
+}
+
+struct ProjectedFeature {
+    feature: Feature,
+    bounds: Option<RectF>,
+}
+
+/// Renders tiles of a fixed pixel size, reusing one GPU device for all of them.
+pub struct TileRenderer {
+    renderer: HeadlessRenderer,
+    tile_size: i32,
+}
+
+impl TileId {
+    #[inline]
+    pub fn new(z: u8, x: u32, y: u32) -> TileId {
+        TileId { z, x, y }
+    }
+
+    /// Parses a tile path of the form `z/x/y`, optionally followed by an extension such as
+    /// `.png`, as used in tile URLs.
+    pub fn parse(path: &str) -> Option<TileId> {
+        let path = path.trim_matches('/');
+        let path = match path.rfind('.') {
+            Some(dot_index) if path[dot_index..].find('/').is_none() => &path[..dot_index],
+            _ => path,
+        };
+        let mut components = path.split('/');
+        let z = components.next()?.parse().ok()?;
+        let x = components.next()?.parse().ok()?;
+        let y = components.next()?.parse().ok()?;
+        if components.next().is_some() {
+            return None;
+        }
+        let tile = TileId::new(z, x, y);
+        if tile.z >= 32 || tile.x >= tile.tiles_per_side() || tile.y >= tile.tiles_per_side() {
+            return None;
+        }
+        Some(tile)
+    }
+
+    /// Returns the tiles at zoom level `z` that intersect the given rectangle in world
+    /// coordinates, in row-major order.
+    pub fn covering(world_rect: RectF, z: u8) -> Vec<TileId> {
+        let tiles_per_side = 1u64 << z;
+        let to_tile = |coord: f32| {
+            ((coord as f64 * tiles_per_side as f64).floor().max(0.0) as u64)
+                .min(tiles_per_side - 1) as u32
+        };
+        let (min_x, min_y) = (to_tile(world_rect.min_x()), to_tile(world_rect.min_y()));
+        let (max_x, max_y) = (to_tile(world_rect.max_x()), to_tile(world_rect.max_y()));
+        let mut tiles = vec![];
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                tiles.push(TileId::new(z, x, y));
+            }
+        }
+        tiles
+    }
+
+    /// Returns the area this tile covers, in world coordinates.
+    pub fn world_bounds(self) -> RectF {
+        let size = 1.0 / self.tiles_per_side() as f32;
+        RectF::new(vec2f(self.x as f32, self.y as f32) * size, vec2f(size, size))
+    }
+
+    #[inline]
+    fn tiles_per_side(self) -> u32 {
+        1 << self.z
+    }
+}
+
+impl Default for FeatureStyle {
+    /// A black, one-pixel stroke and no fill.
+    #[inline]
+    fn default() -> FeatureStyle {
+        FeatureStyle {
+            fill: None,
+            stroke: Some(ColorU::black()),
+            line_width: 1.0,
+            point_radius: 2.0,
+        }
+    }
+}
+
+impl ProjectedFeatures {
+    /// Projects features whose coordinates are longitude and latitude in degrees.
+    ///
+    /// The projection must map to world coordinates, as `WebMercator` does.
+    pub fn new<P>(features: &[Feature], projection: &P) -> ProjectedFeatures where P: Projection {
+        ProjectedFeatures::from_world(features.iter().map(|feature| {
+            Feature {
+                geometry: feature.geometry.map_points(&|point| projection.project(point)),
+                properties: feature.properties.clone(),
+            }
+        }).collect())
+    }
+
+    /// Wraps features whose coordinates are already in world coordinates.
+    pub fn from_world(features: Vec<Feature>) -> ProjectedFeatures {
+        ProjectedFeatures {
+            features: features.into_iter().map(|feature| {
+                let bounds = feature.geometry.bounds();
+                ProjectedFeature { feature, bounds }
+            }).collect(),
+        }
+    }
+
+    /// Returns the bounds of all features, in world coordinates.
+    pub fn bounds(&self) -> Option<RectF> {
+        self.features.iter().filter_map(|feature| feature.bounds).fold(None, |bounds, rect| {
+            Some(match bounds {
+                None => rect,
+                Some(bounds) => bounds.union_rect(rect),
+            })
+        })
+    }
+}
+
+impl TileRenderer {
+    /// Creates a renderer for tiles of `tile_size` pixels square, usually 256 or 512.
+    pub fn new(tile_size: i32, background_color: Option<ColorF>)
+               -> Result<TileRenderer, HeadlessError> {
+        let renderer = HeadlessRenderer::new(Vector2I::splat(tile_size), background_color)?;
+        Ok(TileRenderer { renderer, tile_size })
+    }
+
+    /// Returns the size of the tiles, in pixels.
+    #[inline]
+    pub fn tile_size(&self) -> i32 {
+        self.tile_size
+    }
+
+    /// Builds the scene for a single tile without rendering it.
+    ///
+    /// `style` is called for every feature that might be visible in the tile; features for which
+    /// it returns `None` aren't drawn.
+    pub fn build_tile_scene<S>(&self, tile: TileId, features: &ProjectedFeatures, style: S)
+                               -> Scene
+                               where S: Fn(&Feature) -> Option<FeatureStyle> {
+        let tile_size = self.tile_size as f32;
+        let world_bounds = tile.world_bounds();
+        let scale = tile_size / world_bounds.width();
+        let transform = Transform2F::from_scale(scale) *
+            Transform2F::from_translation(-world_bounds.origin());
+
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(tile_size, tile_size)));
+
+        for projected_feature in &features.features {
+            let bounds = match projected_feature.bounds {
+                None => continue,
+                Some(bounds) => bounds,
+            };
+            let feature_style = match style(&projected_feature.feature) {
+                None => continue,
+                Some(feature_style) => feature_style,
+            };
+
+            // Leave room for strokes and points that overhang the tile edge.
+            let overhang = feature_style.line_width.max(feature_style.point_radius * 2.0) / scale;
+            if !bounds.dilate(overhang).intersects(world_bounds) {
+                continue;
+            }
+
+            let geometry = &projected_feature.feature.geometry;
+            let geometry = geometry.map_points(&|point| transform * point);
+            push_styled_geometry(&mut scene, &geometry, &feature_style);
+        }
+
+        scene
+    }
+
+    /// Renders a single tile.
+    pub fn render_tile<S>(&mut self, tile: TileId, features: &ProjectedFeatures, style: S)
+                          -> RgbaImage
+                          where S: Fn(&Feature) -> Option<FeatureStyle> {
+        let mut scene = self.build_tile_scene(tile, features, style);
+        self.renderer.render(&mut scene)
+    }
+
+    /// Renders a single tile and encodes it as PNG.
+    pub fn render_tile_png<S>(&mut self, tile: TileId, features: &ProjectedFeatures, style: S)
+                              -> Vec<u8>
+                              where S: Fn(&Feature) -> Option<FeatureStyle> {
+        encode_png(&self.render_tile(tile, features, style))
+    }
+
+    /// Renders a batch of tiles and encodes each as PNG, returning them in the order given.
+    pub fn render_tiles_png<S>(&mut self, tiles: &[TileId], features: &ProjectedFeatures, style: S)
+                               -> Vec<(TileId, Vec<u8>)>
+                               where S: Fn(&Feature) -> Option<FeatureStyle> {
+        tiles.iter()
+             .map(|&tile| (tile, self.render_tile_png(tile, features, &style)))
+             .collect()
+    }
+}
+
+/// Draws a geometry, already transformed into scene coordinates, with the given style.
+pub(crate) fn push_styled_geometry(scene: &mut Scene, geometry: &Geometry, style: &FeatureStyle) {
+    if let Some(fill) = style.fill {
+        let paint = scene.push_paint(&Paint::from_color(fill));
+        push_outline(scene, geometry.area_outline(), paint);
+        push_outline(scene, geometry.point_outline(style.point_radius), paint);
+    }
+    if let Some(stroke) = style.stroke {
+        let paint = scene.push_paint(&Paint::from_color(stroke));
+        let stroke_style = StrokeStyle {
+            line_width: style.line_width,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+        };
+        let mut stroke_to_fill = OutlineStrokeToFill::new(&geometry.line_outline(), stroke_style);
+        stroke_to_fill.offset();
+        push_outline(scene, stroke_to_fill.into_outline(), paint);
+    }
+}
+
+fn push_outline(scene: &mut Scene, outline: Outline, paint: PaintId) {
+    if !outline.is_empty() {
+        scene.push_draw_path(DrawPath::new(outline, paint));
+    }
+}
+
+fn encode_png(image: &RgbaImage) -> Vec<u8> {
+    let mut data = vec![];
+    image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png).unwrap();
+    data
+}
+
+#[cfg(test)]
+mod test {
+    use super::TileId;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_parse_tile_id() {
+        assert_eq!(TileId::parse("3/2/5.png"), Some(TileId::new(3, 2, 5)));
+        assert_eq!(TileId::parse("/0/0/0"), Some(TileId::new(0, 0, 0)));
+        assert_eq!(TileId::parse("1/2/0"), None);
+        assert_eq!(TileId::parse("1/0"), None);
+    }
+
+    #[test]
+    fn test_covering() {
+        let rect = RectF::from_points(vec2f(0.3, 0.3), vec2f(0.6, 0.4));
+        assert_eq!(TileId::covering(rect, 2),
+                   vec![TileId::new(2, 1, 1), TileId::new(2, 2, 1)]);
+        assert_eq!(TileId::new(2, 1, 1).world_bounds().origin(), vec2f(0.25, 0.25));
+    }
+}
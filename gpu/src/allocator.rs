@@ -28,7 +28,7 @@ const DECAY_TIME: f32 = 0.250;
 // Number of frames to wait before we can reuse an object.
 //
 // This helps avoid stalls and ensure that the GPU is no longer using the resource.
-const MAX_FRAMES_IN_FLIGHT: u64 = 3;
+pub const MAX_FRAMES_IN_FLIGHT: u64 = 3;
 
 pub struct GpuMemoryAllocator {
     general_buffers_in_use: FxHashMap<GeneralBufferID, BufferAllocation>,
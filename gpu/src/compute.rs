@@ -0,0 +1,252 @@
+// pathfinder/gpu/src/compute.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Compute programs: pipelines whose bind group layouts are described by the caller and whose
+//! workgroup size is read from the shader.
+//!
+//! Dispatches are recorded into the device's command encoder, like draws. wgpu synchronizes
+//! storage buffer and texture accesses between dispatches itself, so one dispatch always sees what
+//! earlier ones wrote. What it can't order is queue writes: `Device::upload_to_buffer()` and
+//! friends take effect before everything in the next submission. `Device::compute_barrier()`
+//! submits what's been recorded so far, so that uploads after the barrier only reach dispatches
+//! recorded after it.
+
+use crate::Device;
+use pathfinder_resources::ResourceLoader;
+
+// The entry point of every compute shader.
+const ENTRY_POINT: &str = "cs_main";
+
+/// What a compute shader binds at one binding index of a bind group.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ComputeBinding {
+    /// A uniform buffer.
+    UniformBuffer,
+    /// A storage buffer, which the shader may write unless it's read-only.
+    StorageBuffer { read_only: bool },
+    /// A 2D texture that the shader samples or loads from.
+    Texture { sample_type: wgpu::TextureSampleType },
+    /// A 2D storage texture, which the shader loads from and stores to directly.
+    StorageTexture { format: wgpu::TextureFormat, access: wgpu::StorageTextureAccess },
+    /// A sampler.
+    Sampler(wgpu::SamplerBindingType),
+}
+
+/// A compute pipeline, along with its bind group layouts and workgroup size.
+pub struct ComputeProgram {
+    pub pipeline: wgpu::ComputePipeline,
+    pub bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    workgroup_size: [u32; 3],
+}
+
+/// How many workgroups a dispatch runs.
+#[derive(Clone, Copy, Debug)]
+pub enum DispatchSize<'a> {
+    /// A workgroup count known on the CPU.
+    Workgroups([u32; 3]),
+    /// A workgroup count read from a buffer at dispatch time, laid out as
+    /// `wgpu::util::DispatchIndirectArgs`. The buffer needs `INDIRECT` usage.
+    Indirect { buffer: &'a wgpu::Buffer, offset: u64 },
+}
+
+impl ComputeProgram {
+    /// The number of invocations in each workgroup along each axis, as declared by the shader's
+    /// `@workgroup_size` attribute.
+    #[inline]
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    /// Returns the number of workgroups needed to run at least `invocations` invocations along
+    /// each axis.
+    pub fn workgroup_count(&self, invocations: [u32; 3]) -> [u32; 3] {
+        let mut count = [0; 3];
+        for axis in 0..3 {
+            count[axis] = (invocations[axis] + self.workgroup_size[axis] - 1) /
+                self.workgroup_size[axis];
+        }
+        count
+    }
+}
+
+impl ComputeBinding {
+    fn layout_entry(&self, binding: u32) -> wgpu::BindGroupLayoutEntry {
+        let ty = match *self {
+            ComputeBinding::UniformBuffer => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            ComputeBinding::StorageBuffer { read_only } => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            ComputeBinding::Texture { sample_type } => wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            ComputeBinding::StorageTexture { format, access } => {
+                wgpu::BindingType::StorageTexture {
+                    access,
+                    format,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                }
+            }
+            ComputeBinding::Sampler(sampler_type) => wgpu::BindingType::Sampler(sampler_type),
+        };
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        }
+    }
+}
+
+impl Device {
+    /// Creates a compute program from the shader `shaders/<name>.wgsl`, whose entry point must be
+    /// `cs_main`.
+    ///
+    /// `bind_groups` describes each bind group in order, each binding being numbered by its
+    /// position. Panics if the shader is missing or doesn't declare a workgroup size that can be
+    /// read.
+    pub fn create_compute_program(&self,
+                                  resources: &dyn ResourceLoader,
+                                  name: &str,
+                                  bind_groups: &[&[ComputeBinding]])
+                                  -> ComputeProgram {
+        let path = format!("shaders/{}.wgsl", name);
+        let source = resources.slurp(&path).expect("Failed to load shader");
+        let source = String::from_utf8_lossy(&source);
+        let workgroup_size = match parse_workgroup_size(&source) {
+            Some(workgroup_size) => workgroup_size,
+            None => panic!("Couldn't read the workgroup size of shader {}!", name),
+        };
+
+        let module = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(name),
+            source: wgpu::ShaderSource::Wgsl(source.into_owned().into()),
+        });
+
+        let bind_group_layouts: Vec<_> = bind_groups.iter().map(|bindings| {
+            let entries: Vec<_> = bindings.iter().enumerate().map(|(index, binding)| {
+                binding.layout_entry(index as u32)
+            }).collect();
+            self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(name),
+                entries: &entries,
+            })
+        }).collect();
+        let layout_refs: Vec<_> = bind_group_layouts.iter().map(Some).collect();
+        let pipeline_layout = self.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(name),
+            bind_group_layouts: &layout_refs,
+            immediate_size: 0,
+        });
+
+        let pipeline = self.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(name),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: Some(ENTRY_POINT),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        ComputeProgram { pipeline, bind_group_layouts, workgroup_size }
+    }
+
+    /// Creates a bind group for one of a program's layouts, binding `resources` in order.
+    pub fn create_compute_bind_group(&self,
+                                     program: &ComputeProgram,
+                                     group: usize,
+                                     resources: &[wgpu::BindingResource])
+                                     -> wgpu::BindGroup {
+        let entries: Vec<_> = resources.iter().enumerate().map(|(index, resource)| {
+            wgpu::BindGroupEntry { binding: index as u32, resource: resource.clone() }
+        }).collect();
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &program.bind_group_layouts[group],
+            entries: &entries,
+        })
+    }
+
+    /// Records a dispatch of a compute program, with `bind_groups` bound in order.
+    pub fn dispatch_compute(&self,
+                            program: &ComputeProgram,
+                            bind_groups: &[&wgpu::BindGroup],
+                            size: DispatchSize) {
+        self.begin_commands();
+        let mut encoder_borrow = self.encoder.borrow_mut();
+        let encoder = encoder_borrow.as_mut().unwrap();
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: None,
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&program.pipeline);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            compute_pass.set_bind_group(index as u32, *bind_group, &[]);
+        }
+        match size {
+            DispatchSize::Workgroups([x, y, z]) => compute_pass.dispatch_workgroups(x, y, z),
+            DispatchSize::Indirect { buffer, offset } => {
+                compute_pass.dispatch_workgroups_indirect(buffer, offset)
+            }
+        }
+    }
+
+    /// Submits the commands recorded so far, so that queue writes made after this call don't
+    /// reach dispatches recorded before it. See the module documentation.
+    pub fn compute_barrier(&self) {
+        self.end_commands();
+        self.begin_commands();
+    }
+}
+
+// Reads the `@workgroup_size` attribute of the entry point. Sizes may be literals or the names of
+// constants declared with literal values, and omitted sizes are 1.
+fn parse_workgroup_size(source: &str) -> Option<[u32; 3]> {
+    let entry_point = source.find(&format!("fn {}", ENTRY_POINT))?;
+    let attribute_start = source[..entry_point].rfind("@workgroup_size(")? + 16;
+    let attribute_end = attribute_start + source[attribute_start..].find(')')?;
+
+    let mut workgroup_size = [1; 3];
+    let sizes = source[attribute_start..attribute_end].split(',').map(str::trim);
+    for (axis, size) in sizes.filter(|size| !size.is_empty()).enumerate() {
+        if axis >= 3 {
+            return None;
+        }
+        workgroup_size[axis] = match parse_integer(size) {
+            Some(size) => size,
+            None => parse_integer(constant_value(source, size)?)?,
+        };
+    }
+    Some(workgroup_size)
+}
+
+fn parse_integer(literal: &str) -> Option<u32> {
+    literal.trim_end_matches(|c| c == 'u' || c == 'i').parse().ok()
+}
+
+// Finds the value of `const <name>: <type> = <value>;` or `const <name> = <value>;`.
+fn constant_value<'a>(source: &'a str, name: &str) -> Option<&'a str> {
+    source.lines().find_map(|line| {
+        let declaration = line.trim().strip_prefix("const ")?.strip_prefix(name)?;
+        if !declaration.starts_with(|c: char| c == ':' || c == '=' || c.is_whitespace()) {
+            return None;
+        }
+        let value = &declaration[(declaration.find('=')? + 1)..];
+        Some(value.trim().trim_end_matches(';').trim())
+    })
+}
@@ -16,13 +16,22 @@ extern crate bitflags;
 extern crate log;
 
 pub mod allocator;
-
+pub mod compute;
+pub mod readback;
+pub mod sampler;
+pub mod staging;
+#[cfg(feature = "pf-image")]
+pub mod texture_loading;
+pub mod uniform_block;
+
+use crate::readback::ReadbackBuffer;
+use crate::sampler::SamplerOptions;
+use fxhash::FxHashMap;
 use half::f16;
-use image::ImageFormat;
 use pathfinder_color::ColorF;
 use pathfinder_geometry::rect::RectI;
 use pathfinder_geometry::transform3d::Transform4F;
-use pathfinder_geometry::vector::{vec2i, Vector2I};
+use pathfinder_geometry::vector::Vector2I;
 use pathfinder_resources::ResourceLoader;
 use pathfinder_simd::default::{F32x2, F32x4, I32x2};
 use std::cell::RefCell;
@@ -37,6 +46,7 @@ pub struct Device {
     pub device_name: String,
     pub backend_name: String,
     pub(crate) encoder: Arc<RefCell<Option<wgpu::CommandEncoder>>>,
+    pub(crate) samplers: Arc<RefCell<FxHashMap<SamplerOptions, wgpu::Sampler>>>,
 }
 
 pub struct TimerQuery {
@@ -105,6 +115,7 @@ impl Device {
             device_name,
             backend_name,
             encoder: Arc::new(RefCell::new(None)),
+            samplers: Arc::new(RefCell::new(FxHashMap::default())),
         }
     }
 
@@ -141,6 +152,27 @@ impl Device {
         }
     }
 
+    /// Decodes a PNG and creates an `R8Unorm` or RGBA8 texture holding it.
+    ///
+    /// Unlike `create_texture_from_image_bytes()`, this works without the `pf-image` feature. The
+    /// texture also gets `COPY_DST` usage, which the upload needs.
+    pub fn create_texture_from_png(
+        &self,
+        data: &[u8],
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> Texture {
+        let (size, texels) = decode_png(data, format);
+        let texture = self.create_texture(format, size, usage | wgpu::TextureUsages::COPY_DST);
+        let rect = RectI::new(Vector2I::default(), size);
+        self.upload_to_texture(&texture, rect, TextureDataRef::U8(&texels));
+        texture
+    }
+
+    /// Uploads the PNG resource `textures/<name>.png` to the upper left corner of an `R8Unorm` or
+    /// RGBA8 texture.
+    ///
+    /// See `upload_image_bytes_to_texture()`, with the `pf-image` feature, for other formats.
     pub fn upload_png_to_texture(
         &self,
         resources: &dyn ResourceLoader,
@@ -148,23 +180,9 @@ impl Device {
         texture: &Texture,
     ) {
         let data = resources.slurp(&format!("textures/{}.png", name)).unwrap();
-        let image = image::load_from_memory_with_format(&data, ImageFormat::Png).unwrap();
-        let format = texture.format;
-        match format {
-            wgpu::TextureFormat::R8Unorm => {
-                let image = image.to_luma8();
-                let size = vec2i(image.width() as i32, image.height() as i32);
-                let rect = RectI::new(Vector2I::default(), size);
-                self.upload_to_texture(&texture, rect, TextureDataRef::U8(&image))
-            }
-            wgpu::TextureFormat::Rgba8Unorm => {
-                let image = image.to_rgba8();
-                let size = vec2i(image.width() as i32, image.height() as i32);
-                let rect = RectI::new(Vector2I::default(), size);
-                self.upload_to_texture(&texture, rect, TextureDataRef::U8(&image))
-            }
-            _ => unimplemented!(),
-        }
+        let (size, texels) = decode_png(&data, texture.format);
+        let rect = RectI::new(Vector2I::default(), size);
+        self.upload_to_texture(texture, rect, TextureDataRef::U8(&texels));
     }
 
     pub fn upload_to_buffer<T>(&self, buffer: &wgpu::Buffer, position: usize, data: &[T]) {
@@ -203,39 +221,66 @@ impl Device {
         texture.size
     }
 
+    /// Uploads texels to a region of a texture. `data` holds exactly the region's texels, row by
+    /// row.
     pub fn upload_to_texture(&self, texture: &Texture, rect: RectI, data: TextureDataRef) {
-        let bytes = match data {
-            TextureDataRef::U8(d) => d,
-            TextureDataRef::F16(d) => unsafe {
-                std::slice::from_raw_parts(d.as_ptr() as *const u8, d.len() * 2)
-            },
-            TextureDataRef::F32(d) => unsafe {
-                std::slice::from_raw_parts(d.as_ptr() as *const u8, d.len() * 4)
-            },
+        let source = TextureSubImage {
+            data,
+            stride: rect.width() as u32,
+            origin: Vector2I::default(),
         };
+        self.upload_sub_image_to_texture(texture, rect, source)
+    }
+
+    /// Uploads part of a larger image to a region of a texture, for example to update one glyph
+    /// or gradient in an atlas from a CPU-side copy of the whole atlas.
+    ///
+    /// The region of `source` starting at its origin, the size of `dest_rect`, is copied to
+    /// `dest_rect`. Panics if either region is out of bounds.
+    pub fn upload_sub_image_to_texture(
+        &self,
+        texture: &Texture,
+        dest_rect: RectI,
+        source: TextureSubImage,
+    ) {
+        assert!(dest_rect.min_x() >= 0 && dest_rect.min_y() >= 0 &&
+                dest_rect.max_x() <= texture.size.x() && dest_rect.max_y() <= texture.size.y(),
+                "Destination region out of bounds!");
+        if dest_rect.width() <= 0 || dest_rect.height() <= 0 {
+            return;
+        }
 
         let block_size = texture.format.block_copy_size(None).unwrap_or(4);
+        let bytes = source.data.as_bytes();
+        let stride = source.stride as usize * block_size as usize;
+        let offset = source.origin.y() as usize * stride +
+            source.origin.x() as usize * block_size as usize;
+        let row_size = dest_rect.width() as usize * block_size as usize;
+        assert!(source.origin.x() >= 0 && source.origin.y() >= 0 &&
+                source.origin.x() + dest_rect.width() <= source.stride as i32 &&
+                offset + (dest_rect.height() as usize - 1) * stride + row_size <= bytes.len(),
+                "Source region out of bounds!");
 
         self.queue.write_texture(
             wgpu::TexelCopyTextureInfo {
                 texture: &texture.texture,
                 mip_level: 0,
                 origin: wgpu::Origin3d {
-                    x: rect.origin().x() as u32,
-                    y: rect.origin().y() as u32,
+                    x: dest_rect.origin().x() as u32,
+                    y: dest_rect.origin().y() as u32,
                     z: 0,
                 },
                 aspect: wgpu::TextureAspect::All,
             },
             bytes,
             wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(rect.size().x() as u32 * block_size),
-                rows_per_image: Some(rect.size().y() as u32),
+                offset: offset as u64,
+                bytes_per_row: Some(stride as u32),
+                rows_per_image: Some(dest_rect.size().y() as u32),
             },
             wgpu::Extent3d {
-                width: rect.size().x() as u32,
-                height: rect.size().y() as u32,
+                width: dest_rect.size().x() as u32,
+                height: dest_rect.size().y() as u32,
                 depth_or_array_layers: 1,
             },
         );
@@ -420,11 +465,55 @@ impl Device {
         TextureData::U8(vec![])
     }
 
+    /// Copies the given rectangle of an 8-bit-per-channel texture back to the CPU, blocking
+    /// until the GPU has finished. Rows are returned tightly packed, top to bottom.
+    ///
+    /// The texture must have been created with `COPY_SRC` usage. To read back several textures
+    /// without stalling, use a `ReadbackBatch` instead.
+    pub fn read_texture(&self, texture: &Texture, rect: RectI) -> TextureData {
+        let mut encoder =
+            self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let readback_buffer =
+            ReadbackBuffer::copy_from_texture(self, &mut encoder, texture, rect);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback_buffer.buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        self.device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        readback_buffer.take_pixels()
+    }
+
+    /// Runs the callbacks of any `ReadbackBatch` copies that have completed.
+    ///
+    /// If `wait` is true, blocks until all submitted GPU work, including every pending readback,
+    /// has finished first.
+    pub fn poll_readbacks(&self, wait: bool) {
+        let poll_type = if wait {
+            wgpu::PollType::wait_indefinitely()
+        } else {
+            wgpu::PollType::Poll
+        };
+        self.device.poll(poll_type).unwrap();
+    }
+
     pub fn create_render_pipeline(
         &self,
         resources: &dyn ResourceLoader,
         name: &str,
         extra: Option<&str>,
+    ) -> wgpu::RenderPipeline {
+        self.create_render_pipeline_with_depth_stencil(resources, name, extra, None)
+    }
+
+    /// Like `create_render_pipeline()`, but with the given depth/stencil state.
+    ///
+    /// Only the `d3d9/tile` pipeline supports a depth/stencil state; it's ignored otherwise.
+    pub fn create_render_pipeline_with_depth_stencil(
+        &self,
+        resources: &dyn ResourceLoader,
+        name: &str,
+        extra: Option<&str>,
+        depth_stencil: Option<wgpu::DepthStencilState>,
     ) -> wgpu::RenderPipeline {
         let path = format!("shaders/{}.wgsl", name);
         let source = resources.slurp(&path).expect("Failed to load shader");
@@ -500,7 +589,8 @@ impl Device {
                         entry_point: Some("fs_main"),
                         targets: &[Some(wgpu::ColorTargetState {
                             format: wgpu::TextureFormat::Rgba8Unorm,
-                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            // The rendered frame has premultiplied alpha.
+                            blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
                             write_mask: wgpu::ColorWrites::ALL,
                         })],
                         compilation_options: Default::default(),
@@ -866,12 +956,13 @@ impl Device {
                 .device
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                     label: Some("D3D9 Tile Globals"),
+                    // Bound at a dynamic offset into the renderer's per-frame uniform buffer.
                     entries: &[wgpu::BindGroupLayoutEntry {
                         binding: 0,
                         visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
+                            has_dynamic_offset: true,
                             min_binding_size: None,
                         },
                         count: None,
@@ -1032,7 +1123,7 @@ impl Device {
                         compilation_options: Default::default(),
                     }),
                     primitive: wgpu::PrimitiveState::default(),
-                    depth_stencil: None,
+                    depth_stencil,
                     multisample: wgpu::MultisampleState::default(),
                     multiview_mask: None,
                     cache: None,
@@ -2160,6 +2251,32 @@ pub enum TextureDataRef<'a> {
     F32(&'a [f32]),
 }
 
+/// Texels to upload from part of a larger image, for `Device::upload_sub_image_to_texture()`.
+#[derive(Clone, Copy, Debug)]
+pub struct TextureSubImage<'a> {
+    /// The texels of the whole source image, row by row.
+    pub data: TextureDataRef<'a>,
+    /// The number of texels in each row of the source image.
+    pub stride: u32,
+    /// The upper left corner of the region to upload, in texels within the source image.
+    pub origin: Vector2I,
+}
+
+impl<'a> TextureDataRef<'a> {
+    /// Returns the texels as raw bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match *self {
+            TextureDataRef::U8(data) => data,
+            TextureDataRef::F16(data) => unsafe {
+                std::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * 2)
+            },
+            TextureDataRef::F32(data) => unsafe {
+                std::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * 4)
+            },
+        }
+    }
+}
+
 bitflags! {
     pub struct TextureSamplingFlags: u8 {
         const REPEAT_U    = 0x01;
@@ -2169,6 +2286,35 @@ bitflags! {
     }
 }
 
+// Decodes a PNG to the texels of an `R8Unorm` or RGBA8 texture. Grayscale textures take the
+// image's first channel. This only needs the `png` crate, so the renderer's own resources load
+// without `image`.
+fn decode_png(data: &[u8], format: wgpu::TextureFormat) -> (Vector2I, Vec<u8>) {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().expect("Failed to read PNG header!");
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).expect("Failed to decode PNG!");
+    pixels.truncate(info.buffer_size());
+
+    let channels = info.color_type.samples();
+    let texels = match format {
+        wgpu::TextureFormat::R8Unorm => pixels.chunks_exact(channels).map(|p| p[0]).collect(),
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {
+            pixels.chunks_exact(channels).flat_map(|p| {
+                match channels {
+                    1 => [p[0], p[0], p[0], 255],
+                    2 => [p[0], p[0], p[0], p[1]],
+                    3 => [p[0], p[1], p[2], 255],
+                    _ => [p[0], p[1], p[2], p[3]],
+                }
+            }).collect()
+        }
+        _ => panic!("Can't load PNGs into {:?} textures!", format),
+    };
+    (Vector2I::new(info.width as i32, info.height as i32), texels)
+}
+
 fn rect_y(rect: RectI, texture_size: Vector2I) -> u32 {
     (texture_size.y() - rect.origin().y() - rect.size().y()) as u32
 }
@@ -0,0 +1,148 @@
+// pathfinder/gpu/src/readback.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Asynchronous texture readback, batched into a single submission.
+//!
+//! `Device::read_texture()` submits one copy and then blocks until the GPU is idle, which
+//! serializes the CPU and GPU and costs a full round trip per texture. Pipelines that read back
+//! many small textures per frame, such as thumbnail generators, should instead record all of the
+//! copies into a `ReadbackBatch`, submit it once, and receive each texture's pixels in a callback
+//! when it arrives.
+//!
+//! Callbacks run from inside `Device::poll_readbacks()`, or from any other poll of the device.
+
+use crate::{Device, Texture, TextureData};
+use pathfinder_geometry::rect::RectI;
+
+/// A set of texture copies to the CPU, recorded together and submitted at once.
+pub struct ReadbackBatch {
+    encoder: wgpu::CommandEncoder,
+    pending: Vec<PendingReadback>,
+}
+
+struct PendingReadback {
+    buffer: ReadbackBuffer,
+    callback: Box<dyn FnOnce(Result<TextureData, wgpu::BufferAsyncError>) + Send>,
+}
+
+// A mappable buffer holding a copy of a texture region, with rows padded as wgpu requires.
+pub(crate) struct ReadbackBuffer {
+    pub(crate) buffer: wgpu::Buffer,
+    row_size: u32,
+    padded_row_size: u32,
+    height: u32,
+}
+
+impl ReadbackBatch {
+    /// Creates an empty batch.
+    pub fn new(device: &Device) -> ReadbackBatch {
+        let encoder = device.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        ReadbackBatch { encoder, pending: vec![] }
+    }
+
+    /// Returns the number of readbacks recorded so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns true if no readbacks have been recorded.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Records a copy of the given rectangle of an 8-bit-per-channel texture back to the CPU.
+    ///
+    /// `callback` receives the pixels, with rows tightly packed top to bottom, once the copy has
+    /// completed on the GPU. The texture must have been created with `COPY_SRC` usage. The copy
+    /// reads the texture as it is when the batch is submitted, not when this is called.
+    pub fn read_texture<F>(&mut self, device: &Device, texture: &Texture, rect: RectI, callback: F)
+                           where F: FnOnce(Result<TextureData, wgpu::BufferAsyncError>) +
+                                    Send + 'static {
+        let buffer = ReadbackBuffer::copy_from_texture(device, &mut self.encoder, texture, rect);
+        self.pending.push(PendingReadback { buffer, callback: Box::new(callback) });
+    }
+
+    /// Submits every recorded copy in one command buffer and starts mapping the results.
+    ///
+    /// This doesn't block. Each callback runs when the device is next polled after its copy has
+    /// completed.
+    pub fn submit(self, device: &Device) {
+        device.queue.submit(Some(self.encoder.finish()));
+        for PendingReadback { buffer, callback } in self.pending {
+            let mapped_buffer = buffer.buffer.clone();
+            mapped_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                callback(result.map(|()| buffer.take_pixels()))
+            });
+        }
+    }
+}
+
+impl ReadbackBuffer {
+    // Records a copy of `rect` of `texture` into a new buffer.
+    pub(crate) fn copy_from_texture(device: &Device,
+                                    encoder: &mut wgpu::CommandEncoder,
+                                    texture: &Texture,
+                                    rect: RectI)
+                                    -> ReadbackBuffer {
+        let block_size = texture.format.block_copy_size(None).unwrap_or(4);
+        let row_size = rect.width() as u32 * block_size;
+        let padded_row_size = (row_size + wgpu::COPY_BYTES_PER_ROW_ALIGNMENT - 1) /
+            wgpu::COPY_BYTES_PER_ROW_ALIGNMENT * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let height = rect.height() as u32;
+        let buffer = device.create_buffer(padded_row_size as u64 * height as u64,
+                                          wgpu::BufferUsages::COPY_DST |
+                                          wgpu::BufferUsages::MAP_READ);
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: rect.origin().x() as u32,
+                    y: rect.origin().y() as u32,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_size),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width: rect.width() as u32,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        ReadbackBuffer { buffer, row_size, padded_row_size, height }
+    }
+
+    // Copies the pixels out of the mapped buffer without row padding, and unmaps it.
+    pub(crate) fn take_pixels(&self) -> TextureData {
+        let mut pixels = Vec::with_capacity(self.row_size as usize * self.height as usize);
+        {
+            let data = self.buffer.slice(..).get_mapped_range();
+            for row in data.chunks(self.padded_row_size as usize) {
+                pixels.extend_from_slice(&row[..self.row_size as usize]);
+            }
+        }
+        self.buffer.unmap();
+        TextureData::U8(pixels)
+    }
+}
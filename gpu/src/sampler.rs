@@ -0,0 +1,143 @@
+// pathfinder/gpu/src/sampler.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Samplers described by how they filter, wrap, and compare texels.
+//!
+//! Each texture binding picks its sampler explicitly, so that, for example, a pattern paint can
+//! repeat while a tile atlas clamps, and pixel art can be drawn with nearest filtering. A renderer
+//! only needs a handful of distinct samplers, so `Device::sampler()` creates each configuration
+//! once and shares it.
+
+use crate::{Device, TextureSamplingFlags};
+
+/// How a texture binding filters, wraps, and compares the texels it reads.
+///
+/// The default is wgpu's default sampler: nearest filtering, clamped to the edge.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SamplerOptions {
+    /// How texels are filtered when the texture is drawn smaller than its size.
+    pub min_filter: wgpu::FilterMode,
+    /// How texels are filtered when the texture is drawn larger than its size.
+    pub mag_filter: wgpu::FilterMode,
+    /// What's read outside the texture horizontally.
+    pub wrap_u: wgpu::AddressMode,
+    /// What's read outside the texture vertically.
+    pub wrap_v: wgpu::AddressMode,
+    /// Makes this a comparison sampler, which compares depth texels against a reference value.
+    pub compare: Option<wgpu::CompareFunction>,
+    /// The maximum anisotropy, from 1 (no anisotropic filtering) to 16. Anisotropic filtering
+    /// needs linear filtering.
+    pub anisotropy: u16,
+}
+
+impl Default for SamplerOptions {
+    #[inline]
+    fn default() -> SamplerOptions {
+        SamplerOptions {
+            min_filter: wgpu::FilterMode::Nearest,
+            mag_filter: wgpu::FilterMode::Nearest,
+            wrap_u: wgpu::AddressMode::ClampToEdge,
+            wrap_v: wgpu::AddressMode::ClampToEdge,
+            compare: None,
+            anisotropy: 1,
+        }
+    }
+}
+
+impl SamplerOptions {
+    /// Linear filtering, clamped to the edge.
+    #[inline]
+    pub fn linear() -> SamplerOptions {
+        SamplerOptions {
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            ..SamplerOptions::default()
+        }
+    }
+
+    /// Returns these options with both filters set to `filter`.
+    #[inline]
+    pub fn filter(mut self, filter: wgpu::FilterMode) -> SamplerOptions {
+        self.min_filter = filter;
+        self.mag_filter = filter;
+        self
+    }
+
+    /// Returns these options with both directions wrapped by `wrap`.
+    #[inline]
+    pub fn wrap(mut self, wrap: wgpu::AddressMode) -> SamplerOptions {
+        self.wrap_u = wrap;
+        self.wrap_v = wrap;
+        self
+    }
+}
+
+impl From<TextureSamplingFlags> for SamplerOptions {
+    /// Converts the sampling flags of a paint. Without flags, textures are filtered linearly and
+    /// clamped.
+    fn from(flags: TextureSamplingFlags) -> SamplerOptions {
+        let filter = |nearest| {
+            if nearest { wgpu::FilterMode::Nearest } else { wgpu::FilterMode::Linear }
+        };
+        let wrap = |repeat| {
+            if repeat { wgpu::AddressMode::Repeat } else { wgpu::AddressMode::ClampToEdge }
+        };
+        SamplerOptions {
+            min_filter: filter(flags.contains(TextureSamplingFlags::NEAREST_MIN)),
+            mag_filter: filter(flags.contains(TextureSamplingFlags::NEAREST_MAG)),
+            wrap_u: wrap(flags.contains(TextureSamplingFlags::REPEAT_U)),
+            wrap_v: wrap(flags.contains(TextureSamplingFlags::REPEAT_V)),
+            ..SamplerOptions::default()
+        }
+    }
+}
+
+impl Device {
+    /// Creates a new sampler. Prefer `sampler()`, which shares samplers with the same options.
+    ///
+    /// Panics if the anisotropy is out of range, or above 1 without linear filtering.
+    pub fn create_sampler(&self, options: &SamplerOptions) -> wgpu::Sampler {
+        assert!((1..=16).contains(&options.anisotropy), "Anisotropy must be from 1 to 16!");
+        assert!(options.anisotropy == 1 ||
+                (options.min_filter == wgpu::FilterMode::Linear &&
+                 options.mag_filter == wgpu::FilterMode::Linear),
+                "Anisotropic filtering needs linear filtering!");
+
+        // Textures have no mipmaps, so the mipmap filter is moot, except that anisotropic
+        // filtering requires it to be linear.
+        let mipmap_filter = if options.anisotropy > 1 {
+            wgpu::MipmapFilterMode::Linear
+        } else {
+            wgpu::MipmapFilterMode::Nearest
+        };
+        self.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: options.wrap_u,
+            address_mode_v: options.wrap_v,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: options.mag_filter,
+            min_filter: options.min_filter,
+            mipmap_filter,
+            compare: options.compare,
+            anisotropy_clamp: options.anisotropy,
+            ..wgpu::SamplerDescriptor::default()
+        })
+    }
+
+    /// Returns the sampler with the given options, creating it the first time it's asked for.
+    pub fn sampler(&self, options: SamplerOptions) -> wgpu::Sampler {
+        if let Some(sampler) = self.samplers.borrow().get(&options) {
+            return sampler.clone();
+        }
+        let sampler = self.create_sampler(&options);
+        self.samplers.borrow_mut().insert(options, sampler.clone());
+        sampler
+    }
+}
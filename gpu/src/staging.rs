@@ -0,0 +1,108 @@
+// pathfinder/gpu/src/staging.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Buffer uploads through a staging belt, with frame fencing.
+//!
+//! `Device::upload_to_buffer()` goes through `Queue::write_buffer()`, which allocates fresh
+//! staging memory for every call. The uploader instead writes into a belt of persistently
+//! allocated, mapped staging chunks and records copies out of them into an upload encoder, which
+//! is submitted ahead of the draw that reads the data. Chunks are recycled once the GPU is done
+//! with them.
+//!
+//! The uploader also counts frames in flight. `begin_frame()` blocks until at most
+//! `MAX_FRAMES_IN_FLIGHT - 1` earlier frames are still executing, which bounds staging memory and
+//! makes the allocator's assumption that objects freed that many frames ago are idle hold.
+
+use crate::allocator::MAX_FRAMES_IN_FLIGHT;
+use crate::Device;
+use std::mem;
+use std::slice;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use wgpu::BufferSize;
+
+// The size of each staging chunk. Larger uploads get a chunk of their own.
+const STAGING_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Uploads buffer data through a staging belt.
+pub struct StagingUploader {
+    belt: wgpu::util::StagingBelt,
+    encoder: Option<wgpu::CommandEncoder>,
+    submitted_frames: u64,
+    completed_frames: Arc<AtomicU64>,
+}
+
+impl StagingUploader {
+    /// Creates a new uploader with no staging chunks allocated yet.
+    pub fn new(device: &Device) -> StagingUploader {
+        StagingUploader {
+            belt: wgpu::util::StagingBelt::new((*device.device).clone(), STAGING_CHUNK_SIZE),
+            encoder: None,
+            submitted_frames: 0,
+            completed_frames: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Copies `data` into `buffer`, starting at element `position`.
+    ///
+    /// The copy is recorded, not executed: call `flush()` before submitting work that reads the
+    /// buffer. The buffer must have `COPY_DST` usage, and the byte offset and size must be
+    /// multiples of `wgpu::COPY_BUFFER_ALIGNMENT`.
+    pub fn upload_to_buffer<T>(&mut self,
+                               device: &Device,
+                               buffer: &wgpu::Buffer,
+                               position: usize,
+                               data: &[T]) {
+        let bytes: &[u8] = unsafe {
+            slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * mem::size_of::<T>())
+        };
+        let size = match BufferSize::new(bytes.len() as u64) {
+            None => return,
+            Some(size) => size,
+        };
+
+        let encoder = self.encoder.get_or_insert_with(|| {
+            device.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Staging Upload Encoder"),
+            })
+        });
+        let offset = (position * mem::size_of::<T>()) as u64;
+        self.belt.write_buffer(encoder, buffer, offset, size).copy_from_slice(bytes);
+    }
+
+    /// Submits all recorded copies, so that work submitted afterward sees the uploaded data.
+    pub fn flush(&mut self, device: &Device) {
+        let encoder = match self.encoder.take() {
+            None => return,
+            Some(encoder) => encoder,
+        };
+        self.belt.finish();
+        device.queue.submit(Some(encoder.finish()));
+        self.belt.recall();
+    }
+
+    /// Waits, if necessary, until few enough frames are in flight to start another one.
+    pub fn begin_frame(&mut self, device: &Device) {
+        while self.submitted_frames - self.completed_frames.load(Ordering::Acquire) >=
+                MAX_FRAMES_IN_FLIGHT {
+            device.device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        }
+    }
+
+    /// Flushes pending copies and marks the end of the frame's GPU work.
+    pub fn end_frame(&mut self, device: &Device) {
+        self.flush(device);
+        self.submitted_frames += 1;
+        let completed_frames = self.completed_frames.clone();
+        device.queue.on_submitted_work_done(move || {
+            completed_frames.fetch_add(1, Ordering::Release);
+        });
+    }
+}
@@ -0,0 +1,169 @@
+// pathfinder/gpu/src/texture_loading.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Textures created from encoded images: PNG, JPEG, WebP, or QOI.
+//!
+//! The image format is sniffed from the data, so callers needn't know how a resource was encoded.
+//! Images are converted to the texture's format as they're uploaded, so grayscale, grayscale with
+//! alpha, RGB, RGBA, and 16-bit images can all be uploaded to `R8Unorm`, `Rg8Unorm`, RGBA8, BGRA8,
+//! `Rgba16Float`, `R32Float`, and `Rgba32Float` textures.
+
+use crate::{Device, Texture, TextureDataRef};
+use half::f16;
+use image::{DynamicImage, ImageError};
+use pathfinder_geometry::rect::RectI;
+use pathfinder_geometry::vector::{Vector2I, vec2i};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The reasons an image can fail to load into a texture.
+#[derive(Debug)]
+pub enum TextureLoadError {
+    /// The image's format wasn't recognized, or the image couldn't be decoded.
+    Image(ImageError),
+    /// Images can't be converted to the texture's format.
+    UnsupportedFormat(wgpu::TextureFormat),
+    /// The image doesn't fit in the texture where it was to be placed.
+    TooLarge { image_size: Vector2I, texture_size: Vector2I },
+}
+
+// Texels converted to a texture format, ready to upload.
+enum ConvertedTexels {
+    U8(Vec<u8>),
+    F16(Vec<f16>),
+    F32(Vec<f32>),
+}
+
+impl Device {
+    /// Decodes an image and creates a texture holding it.
+    ///
+    /// If `format` is `None`, the texture's format is chosen to fit the image, as
+    /// `texture_format_for_image()` does. The texture also gets `COPY_DST` usage, which the
+    /// upload needs.
+    pub fn create_texture_from_image_bytes(&self,
+                                           data: &[u8],
+                                           format: Option<wgpu::TextureFormat>,
+                                           usage: wgpu::TextureUsages)
+                                           -> Result<Texture, TextureLoadError> {
+        let image = decode_image(data)?;
+        let format = format.unwrap_or_else(|| texture_format_for_image(&image));
+        let texels = convert_image(&image, format)?;
+        let size = vec2i(image.width() as i32, image.height() as i32);
+        let texture = self.create_texture(format, size, usage | wgpu::TextureUsages::COPY_DST);
+        self.upload_converted_texels(&texture, RectI::new(Vector2I::default(), size), &texels);
+        Ok(texture)
+    }
+
+    /// Decodes an image and uploads it to the upper left corner of a texture, converting it to
+    /// the texture's format.
+    pub fn upload_image_bytes_to_texture(&self, data: &[u8], texture: &Texture)
+                                         -> Result<(), TextureLoadError> {
+        self.upload_image_bytes_to_texture_at(data, texture, Vector2I::default())
+    }
+
+    /// Decodes an image and uploads it to a texture with its upper left corner at `origin`,
+    /// leaving the rest of the texture alone. This suits adding images to an atlas one by one.
+    pub fn upload_image_bytes_to_texture_at(&self,
+                                            data: &[u8],
+                                            texture: &Texture,
+                                            origin: Vector2I)
+                                            -> Result<(), TextureLoadError> {
+        let image = decode_image(data)?;
+        let size = vec2i(image.width() as i32, image.height() as i32);
+        let rect = RectI::new(origin, size);
+        if origin.x() < 0 || origin.y() < 0 || rect.max_x() > texture.size.x() ||
+                rect.max_y() > texture.size.y() {
+            return Err(TextureLoadError::TooLarge { image_size: size, texture_size: texture.size });
+        }
+        let texels = convert_image(&image, texture.format)?;
+        self.upload_converted_texels(texture, rect, &texels);
+        Ok(())
+    }
+
+    fn upload_converted_texels(&self, texture: &Texture, rect: RectI, texels: &ConvertedTexels) {
+        let data = match *texels {
+            ConvertedTexels::U8(ref texels) => TextureDataRef::U8(texels),
+            ConvertedTexels::F16(ref texels) => TextureDataRef::F16(texels),
+            ConvertedTexels::F32(ref texels) => TextureDataRef::F32(texels),
+        };
+        self.upload_to_texture(texture, rect, data)
+    }
+}
+
+/// Returns the texture format that holds an image without losing information: `R8Unorm` for
+/// grayscale, `Rg8Unorm` for grayscale with alpha, `Rgba8Unorm` for other 8-bit images,
+/// `Rgba16Float` for 16-bit images, and `Rgba32Float` for floating-point images.
+pub fn texture_format_for_image(image: &DynamicImage) -> wgpu::TextureFormat {
+    match *image {
+        DynamicImage::ImageLuma8(_) => wgpu::TextureFormat::R8Unorm,
+        DynamicImage::ImageLumaA8(_) => wgpu::TextureFormat::Rg8Unorm,
+        DynamicImage::ImageLuma16(_) |
+        DynamicImage::ImageLumaA16(_) |
+        DynamicImage::ImageRgb16(_) |
+        DynamicImage::ImageRgba16(_) => wgpu::TextureFormat::Rgba16Float,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+            wgpu::TextureFormat::Rgba32Float
+        }
+        _ => wgpu::TextureFormat::Rgba8Unorm,
+    }
+}
+
+fn decode_image(data: &[u8]) -> Result<DynamicImage, TextureLoadError> {
+    let format = image::guess_format(data).map_err(TextureLoadError::Image)?;
+    image::load_from_memory_with_format(data, format).map_err(TextureLoadError::Image)
+}
+
+// Converts an image to the texels of a texture format. Grayscale formats take the image's
+// luminance, and `Rg8Unorm` takes luminance and alpha.
+fn convert_image(image: &DynamicImage, format: wgpu::TextureFormat)
+                 -> Result<ConvertedTexels, TextureLoadError> {
+    let texels = match format {
+        wgpu::TextureFormat::R8Unorm => ConvertedTexels::U8(image.to_luma8().into_raw()),
+        wgpu::TextureFormat::Rg8Unorm => ConvertedTexels::U8(image.to_luma_alpha8().into_raw()),
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {
+            ConvertedTexels::U8(image.to_rgba8().into_raw())
+        }
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+            let mut texels = image.to_rgba8().into_raw();
+            texels.chunks_exact_mut(4).for_each(|texel| texel.swap(0, 2));
+            ConvertedTexels::U8(texels)
+        }
+        wgpu::TextureFormat::Rgba16Float => {
+            let texels = image.to_rgba32f().into_raw();
+            ConvertedTexels::F16(texels.into_iter().map(f16::from_f32).collect())
+        }
+        wgpu::TextureFormat::R32Float => ConvertedTexels::F32(image.to_luma32f().into_raw()),
+        wgpu::TextureFormat::Rgba32Float => ConvertedTexels::F32(image.to_rgba32f().into_raw()),
+        _ => return Err(TextureLoadError::UnsupportedFormat(format)),
+    };
+    Ok(texels)
+}
+
+impl Display for TextureLoadError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            TextureLoadError::Image(ref error) => write!(formatter, "{}", error),
+            TextureLoadError::UnsupportedFormat(format) => {
+                write!(formatter, "can't load images into {:?} textures", format)
+            }
+            TextureLoadError::TooLarge { image_size, texture_size } => {
+                write!(formatter,
+                       "{}x{} image doesn't fit in {}x{} texture",
+                       image_size.x(),
+                       image_size.y(),
+                       texture_size.x(),
+                       texture_size.y())
+            }
+        }
+    }
+}
+
+impl Error for TextureLoadError {}
+
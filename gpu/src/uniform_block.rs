@@ -0,0 +1,346 @@
+// pathfinder/gpu/src/uniform_block.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Uniform structs whose layout is checked against WGSL.
+//!
+//! A uniform buffer is only correct if the Rust struct written to it and the WGSL struct read from
+//! it agree field for field. `uniform_block!` declares a `#[repr(C)]` struct along with a
+//! description of its fields, and `check_uniform_block()` checks that description two ways:
+//! against WGSL's layout rules for uniform buffers, which catches missing padding, and against the
+//! struct of the same name in a shader's source, which catches fields that were added, removed,
+//! reordered, or retyped on one side only.
+//!
+//! Padding that WGSL inserts implicitly must be spelled out in Rust, with fields of type `Padding`.
+//! These fields are left out when comparing with the shader.
+
+use bytemuck::{Pod, Zeroable};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The types that uniform blocks may contain.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UniformType {
+    F32,
+    I32,
+    U32,
+    Vec2F,
+    Vec2I,
+    Vec4F,
+    Vec4I,
+    Mat4F,
+    /// Explicit padding of the given number of 32-bit words, which isn't declared in WGSL.
+    Padding(usize),
+}
+
+/// One field of a uniform block.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct UniformField {
+    pub name: &'static str,
+    pub ty: UniformType,
+    /// The field's offset in the Rust struct, in bytes.
+    pub offset: usize,
+}
+
+/// A Rust struct that mirrors a WGSL uniform struct. Implement it with `uniform_block!`.
+pub trait UniformBlock: Pod {
+    /// The name of the WGSL struct.
+    const WGSL_NAME: &'static str;
+    /// The struct's fields, in order.
+    const FIELDS: &'static [UniformField];
+}
+
+/// Rust types that can be fields of a uniform block.
+pub trait UniformValue: Pod {
+    const TYPE: UniformType;
+}
+
+/// Explicit padding of `N` 32-bit words in a uniform block.
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Padding<const N: usize>(pub [u32; N]);
+
+/// The ways a uniform block can disagree with WGSL.
+#[derive(Clone, PartialEq, Debug)]
+pub enum UniformLayoutError {
+    /// WGSL's layout rules put a field at a different offset than the Rust struct does.
+    Offset { field: &'static str, rust: usize, wgsl: usize },
+    /// WGSL's layout rules give the struct a different size than Rust does.
+    Size { rust: usize, wgsl: usize },
+    /// The shader doesn't declare a struct with the block's name.
+    MissingStruct(&'static str),
+    /// A field differs between Rust and the shader. Fields are written as `name: type`.
+    Field { index: usize, rust: String, wgsl: String },
+    /// Rust and the shader declare different numbers of fields.
+    FieldCount { rust: usize, wgsl: usize },
+}
+
+/// Declares a `#[repr(C)]` uniform struct and implements `UniformBlock` for it.
+///
+/// Field names must match the WGSL struct's, and field types must implement `UniformValue`. The
+/// caller needs `bytemuck` as a dependency, for the derived `Pod` implementation.
+///
+/// ```ignore
+/// uniform_block! {
+///     pub(crate) struct FillGlobals: "Globals" {
+///         tile_size: [f32; 2],
+///         framebuffer_size: [f32; 2],
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! uniform_block {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident : $wgsl_name:literal {
+            $($field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[repr(C)]
+        #[allow(non_snake_case)]
+        #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+        $vis struct $name {
+            $(pub $field: $ty,)*
+        }
+
+        impl $crate::uniform_block::UniformBlock for $name {
+            const WGSL_NAME: &'static str = $wgsl_name;
+            const FIELDS: &'static [$crate::uniform_block::UniformField] = &[$(
+                $crate::uniform_block::UniformField {
+                    name: stringify!($field),
+                    ty: <$ty as $crate::uniform_block::UniformValue>::TYPE,
+                    offset: ::std::mem::offset_of!($name, $field),
+                },
+            )*];
+        }
+    };
+}
+
+impl UniformType {
+    /// The size of the type, in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            UniformType::F32 | UniformType::I32 | UniformType::U32 => 4,
+            UniformType::Vec2F | UniformType::Vec2I => 8,
+            UniformType::Vec4F | UniformType::Vec4I => 16,
+            UniformType::Mat4F => 64,
+            UniformType::Padding(words) => words * 4,
+        }
+    }
+
+    /// The alignment WGSL requires of the type in a uniform buffer, in bytes.
+    pub fn alignment(self) -> usize {
+        match self {
+            UniformType::F32 | UniformType::I32 | UniformType::U32 | UniformType::Padding(_) => 4,
+            UniformType::Vec2F | UniformType::Vec2I => 8,
+            UniformType::Vec4F | UniformType::Vec4I | UniformType::Mat4F => 16,
+        }
+    }
+
+    /// The ways WGSL spells the type, or `None` for padding.
+    pub fn wgsl_names(self) -> Option<&'static [&'static str]> {
+        match self {
+            UniformType::F32 => Some(&["f32"]),
+            UniformType::I32 => Some(&["i32"]),
+            UniformType::U32 => Some(&["u32"]),
+            UniformType::Vec2F => Some(&["vec2<f32>", "vec2f"]),
+            UniformType::Vec2I => Some(&["vec2<i32>", "vec2i"]),
+            UniformType::Vec4F => Some(&["vec4<f32>", "vec4f"]),
+            UniformType::Vec4I => Some(&["vec4<i32>", "vec4i"]),
+            UniformType::Mat4F => Some(&["mat4x4<f32>", "mat4x4f"]),
+            UniformType::Padding(_) => None,
+        }
+    }
+}
+
+macro_rules! impl_uniform_value {
+    ($($rust:ty => $ty:expr,)*) => {
+        $(impl UniformValue for $rust { const TYPE: UniformType = $ty; })*
+    };
+}
+
+impl_uniform_value! {
+    f32 => UniformType::F32,
+    i32 => UniformType::I32,
+    u32 => UniformType::U32,
+    [f32; 2] => UniformType::Vec2F,
+    [i32; 2] => UniformType::Vec2I,
+    [f32; 4] => UniformType::Vec4F,
+    [i32; 4] => UniformType::Vec4I,
+    [[f32; 4]; 4] => UniformType::Mat4F,
+    [f32; 16] => UniformType::Mat4F,
+}
+
+unsafe impl<const N: usize> Zeroable for Padding<N> {}
+unsafe impl<const N: usize> Pod for Padding<N> {}
+
+impl<const N: usize> Default for Padding<N> {
+    #[inline]
+    fn default() -> Padding<N> {
+        Padding([0; N])
+    }
+}
+
+impl<const N: usize> UniformValue for Padding<N> {
+    const TYPE: UniformType = UniformType::Padding(N);
+}
+
+/// Checks a uniform block against WGSL's layout rules and against its declaration in
+/// `shader_source`, returning every disagreement found.
+pub fn check_uniform_block<T>(shader_source: &str) -> Vec<UniformLayoutError>
+                              where T: UniformBlock {
+    check_uniform_block_as::<T>(shader_source, T::WGSL_NAME)
+}
+
+/// Like `check_uniform_block()`, but checks against the WGSL struct named `wgsl_name` instead of
+/// `T::WGSL_NAME`, for shaders that declare the same block under another name.
+pub fn check_uniform_block_as<T>(shader_source: &str, wgsl_name: &'static str)
+                                 -> Vec<UniformLayoutError> where T: UniformBlock {
+    let mut errors = vec![];
+
+    // WGSL places each field at the next offset that suits its alignment, and rounds the size of
+    // the struct up to its largest alignment.
+    let (mut wgsl_offset, mut max_alignment) = (0, 4);
+    for field in T::FIELDS {
+        let alignment = field.ty.alignment();
+        wgsl_offset = round_up(wgsl_offset, alignment);
+        if field.offset != wgsl_offset {
+            errors.push(UniformLayoutError::Offset {
+                field: field.name,
+                rust: field.offset,
+                wgsl: wgsl_offset,
+            });
+        }
+        wgsl_offset += field.ty.size();
+        max_alignment = max_alignment.max(alignment);
+    }
+    let wgsl_size = round_up(wgsl_offset, max_alignment);
+    if std::mem::size_of::<T>() != wgsl_size {
+        errors.push(UniformLayoutError::Size { rust: std::mem::size_of::<T>(), wgsl: wgsl_size });
+    }
+
+    let wgsl_fields = match reflect_struct(shader_source, wgsl_name) {
+        Some(wgsl_fields) => wgsl_fields,
+        None => {
+            errors.push(UniformLayoutError::MissingStruct(wgsl_name));
+            return errors;
+        }
+    };
+    let rust_fields: Vec<_> = T::FIELDS.iter().filter_map(|field| {
+        field.ty.wgsl_names().map(|wgsl_names| (field.name, wgsl_names))
+    }).collect();
+    for (index, (&(name, wgsl_names), (wgsl_name, wgsl_type))) in
+            rust_fields.iter().zip(wgsl_fields.iter()).enumerate() {
+        if name != wgsl_name || !wgsl_names.contains(&wgsl_type.as_str()) {
+            errors.push(UniformLayoutError::Field {
+                index,
+                rust: format!("{}: {}", name, wgsl_names[0]),
+                wgsl: format!("{}: {}", wgsl_name, wgsl_type),
+            });
+        }
+    }
+    if rust_fields.len() != wgsl_fields.len() {
+        errors.push(UniformLayoutError::FieldCount {
+            rust: rust_fields.len(),
+            wgsl: wgsl_fields.len(),
+        });
+    }
+    errors
+}
+
+// Finds `struct <name> { ... }` in WGSL source and returns its fields as names and types, with
+// whitespace removed from the types. Comments and attributes are skipped.
+fn reflect_struct(source: &str, name: &str) -> Option<Vec<(String, String)>> {
+    let mut body = String::new();
+    let mut in_struct = false;
+    for line in source.lines() {
+        let line = match line.find("//") {
+            Some(comment_start) => &line[..comment_start],
+            None => line,
+        };
+        if !in_struct {
+            let mut words = line.split_whitespace();
+            if words.next() == Some("struct") &&
+                    words.next().map(|word| word.trim_end_matches('{')) == Some(name) {
+                in_struct = true;
+                body.push_str(&line[(line.find('{').map_or(line.len(), |brace| brace + 1))..]);
+            }
+            continue;
+        }
+        body.push_str(line);
+        body.push(' ');
+        if line.contains('}') {
+            break;
+        }
+    }
+    if !in_struct {
+        return None;
+    }
+
+    // Fields are separated by commas outside angle brackets.
+    let body = &body[..body.find('}')?];
+    let (mut depth, mut field_start) = (0, 0);
+    let mut fields = vec![];
+    for (index, c) in body.char_indices().chain(Some((body.len(), ','))) {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                let field = body[field_start..index].trim();
+                field_start = index + 1;
+                if field.is_empty() {
+                    continue;
+                }
+                let colon = field.find(':')?;
+                let name = field[..colon].split_whitespace().last()?.to_owned();
+                let ty = field[(colon + 1)..].chars().filter(|c| !c.is_whitespace()).collect();
+                fields.push((name, ty));
+            }
+            _ => {}
+        }
+    }
+    Some(fields)
+}
+
+fn round_up(value: usize, alignment: usize) -> usize {
+    (value + alignment - 1) / alignment * alignment
+}
+
+impl Display for UniformLayoutError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            UniformLayoutError::Offset { field, rust, wgsl } => {
+                write!(formatter,
+                       "field `{}` is at offset {} in Rust but {} in WGSL",
+                       field,
+                       rust,
+                       wgsl)
+            }
+            UniformLayoutError::Size { rust, wgsl } => {
+                write!(formatter, "struct is {} bytes in Rust but {} bytes in WGSL", rust, wgsl)
+            }
+            UniformLayoutError::MissingStruct(name) => {
+                write!(formatter, "shader doesn't declare struct `{}`", name)
+            }
+            UniformLayoutError::Field { index, ref rust, ref wgsl } => {
+                write!(formatter,
+                       "field {} is `{}` in Rust but `{}` in WGSL",
+                       index,
+                       rust,
+                       wgsl)
+            }
+            UniformLayoutError::FieldCount { rust, wgsl } => {
+                write!(formatter, "struct has {} fields in Rust but {} in WGSL", rust, wgsl)
+            }
+        }
+    }
+}
+
+impl Error for UniformLayoutError {}
@@ -0,0 +1,166 @@
+// pathfinder/headless/src/animation.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rendering animations frame by frame and exporting them as GIF, APNG, or a PNG sequence.
+//!
+//! ```ignore
+//! let mut renderer = HeadlessRenderer::new(vec2i(320, 240), Some(ColorF::white()))?;
+//! let animation = Animation::render(&mut renderer, 60, 30, |_, time| build_scene(time));
+//! animation.save("spinner.gif")?;
+//! ```
+
+use crate::HeadlessRenderer;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use pathfinder_renderer::scene::Scene;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// A sequence of rendered frames, played back at a fixed rate.
+pub struct Animation {
+    frames: Vec<RgbaImage>,
+    frames_per_second: u32,
+}
+
+impl Animation {
+    /// Renders `frame_count` frames.
+    ///
+    /// For each frame, `build_frame` is called with the frame index and the time of the frame in
+    /// seconds, and returns the scene to draw.
+    pub fn render<F>(renderer: &mut HeadlessRenderer,
+                     frame_count: u32,
+                     frames_per_second: u32,
+                     mut build_frame: F)
+                     -> Animation
+                     where F: FnMut(u32, f32) -> Scene {
+        assert!(frames_per_second > 0, "The frame rate must be positive!");
+        let frames = (0..frame_count).map(|frame_index| {
+            let time = frame_index as f32 / frames_per_second as f32;
+            let mut scene = build_frame(frame_index, time);
+            renderer.render(&mut scene)
+        }).collect();
+        Animation { frames, frames_per_second }
+    }
+
+    /// Creates an animation from already-rendered frames, which must all be the same size.
+    pub fn from_frames(frames: Vec<RgbaImage>, frames_per_second: u32) -> Animation {
+        assert!(frames_per_second > 0, "The frame rate must be positive!");
+        Animation { frames, frames_per_second }
+    }
+
+    /// Returns the rendered frames.
+    #[inline]
+    pub fn frames(&self) -> &[RgbaImage] {
+        &self.frames
+    }
+
+    /// Returns the playback rate.
+    #[inline]
+    pub fn frames_per_second(&self) -> u32 {
+        self.frames_per_second
+    }
+
+    /// Writes the animation to a file, choosing the format from the extension: `.gif` for GIF,
+    /// and `.png` or `.apng` for APNG.
+    pub fn save<P>(&self, path: P) -> io::Result<()> where P: AsRef<Path> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let writer = match extension {
+            Some("gif") | Some("png") | Some("apng") => BufWriter::new(File::create(path)?),
+            _ => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "animation filename must have .gif, .png, or .apng \
+                                           extension"))
+            }
+        };
+        match extension {
+            Some("gif") => self.write_gif(writer),
+            _ => self.write_apng(writer),
+        }
+    }
+
+    /// Writes the animation as a GIF that loops forever.
+    ///
+    /// GIF only supports 1-bit transparency and 256 colors per frame, so prefer APNG where it's
+    /// supported.
+    pub fn write_gif<W>(&self, writer: W) -> io::Result<()> where W: Write {
+        let mut encoder = GifEncoder::new(writer);
+        encoder.set_repeat(Repeat::Infinite).map_err(to_io_error)?;
+        let delay = Delay::from_numer_denom_ms(1000, self.frames_per_second);
+        let frames = self.frames.iter().map(|frame| Frame::from_parts(frame.clone(), 0, 0, delay));
+        encoder.encode_frames(frames).map_err(to_io_error)
+    }
+
+    /// Writes the animation as an animated PNG that loops forever.
+    pub fn write_apng<W>(&self, writer: W) -> io::Result<()> where W: Write {
+        let (width, height) = match self.frames.first() {
+            None => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "an animation must have at least one frame"))
+            }
+            Some(frame) => frame.dimensions(),
+        };
+
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frames.len() as u32, 0).map_err(to_io_error)?;
+        encoder.set_frame_delay(1, self.frames_per_second as u16).map_err(to_io_error)?;
+
+        let mut writer = encoder.write_header().map_err(to_io_error)?;
+        for frame in &self.frames {
+            writer.write_image_data(frame.as_raw()).map_err(to_io_error)?;
+        }
+        writer.finish().map_err(to_io_error)
+    }
+
+    /// Writes each frame to its own PNG file in `directory`, named `frame_0000.png`,
+    /// `frame_0001.png`, and so on. The directory is created if needed.
+    pub fn save_png_sequence<P>(&self, directory: P) -> io::Result<()> where P: AsRef<Path> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory)?;
+        for (frame_index, frame) in self.frames.iter().enumerate() {
+            let path = directory.join(format!("frame_{:04}.png", frame_index));
+            frame.save(path).map_err(to_io_error)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn to_io_error<E>(error: E) -> io::Error
+                            where E: Into<Box<dyn std::error::Error + Send + Sync>> {
+    io::Error::new(io::ErrorKind::Other, error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Animation;
+    use image::{Rgba, RgbaImage};
+
+    fn frames() -> Vec<RgbaImage> {
+        (0..3).map(|index| RgbaImage::from_pixel(4, 4, Rgba([index * 100, 0, 0, 255]))).collect()
+    }
+
+    #[test]
+    fn test_write_apng() {
+        let mut data = vec![];
+        Animation::from_frames(frames(), 10).write_apng(&mut data).unwrap();
+        assert_eq!(&data[1..4], b"PNG");
+        assert!(data.windows(4).any(|chunk| chunk == b"acTL"));
+    }
+
+    #[test]
+    fn test_write_gif() {
+        let mut data = vec![];
+        Animation::from_frames(frames(), 10).write_gif(&mut data).unwrap();
+        assert_eq!(&data[0..6], b"GIF89a");
+    }
+}
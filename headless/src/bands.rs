@@ -0,0 +1,488 @@
+// pathfinder/headless/src/bands.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rendering images larger than a GPU texture, such as posters and map exports, band by band.
+//!
+//! `render_to_image()` stitches the bands into one image in memory, and `render_to_png()` streams
+//! them to a PNG encoder a row of bands at a time, so that even a 20,000×20,000 pixel image only
+//! needs a few bands' worth of memory. `render_in_bands()` hands each band to a callback for other
+//! destinations, such as tile pyramids.
+//!
+//! For exports that take long enough to be worth resuming, a `BandedExport` instead saves each
+//! band as a PNG in a working directory as soon as it has been read back. Bands already in the
+//! directory are skipped, so an export that was stopped from the progress callback, or whose
+//! process died, picks up where it left off when it's run again. Once every band is there,
+//! `assemble()` stitches them into the final PNG, again one row of bands at a time.
+//!
+//! ```ignore
+//! let export = BandedExport::new(&device, vec2i(20000, 30000), "poster.bands")?;
+//! export.render(&device, &mut scene, Some(ColorF::white()), |progress| {
+//!     println!("{:.0}%", progress.fraction() * 100.0);
+//!     ControlFlow::Continue(())
+//! })?;
+//! export.assemble("poster.png")?;
+//! export.remove_directory()?;
+//! ```
+//!
+//! All of these draw the scene in pixels of the whole image, moving its view box and transform to
+//! each band in turn, and restore them afterward.
+
+use crate::animation::to_io_error;
+use crate::{HeadlessRenderer, unpremultiply};
+use image::{ImageFormat, RgbaImage};
+use pathfinder_color::ColorF;
+use pathfinder_geometry::rect::{RectF, RectI};
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2i};
+use pathfinder_gpu::{Device, TextureData};
+use pathfinder_renderer::concurrent::executor::SequentialExecutor;
+use pathfinder_renderer::options::{BuildOptions, RenderTransform};
+use pathfinder_renderer::scene::Scene;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+/// The largest band that `BandedExport::new()` and the `render_*()` functions render along either
+/// axis, even on devices that allow larger textures, to bound the memory each band needs.
+pub const DEFAULT_MAX_BAND_DIMENSION: i32 = 4096;
+
+// Records what the bands in a directory belong to, so that they aren't resumed into another
+// export by mistake.
+const CHECKPOINT_FILENAME: &str = "export.txt";
+const CHECKPOINT_MAGIC: &str = "pathfinder-banded-export";
+
+/// An image rendered in bands, each of which is saved to a working directory.
+pub struct BandedExport {
+    size: Vector2I,
+    bands: Vec<RectI>,
+    directory: PathBuf,
+}
+
+/// How far along an export is, reported after each band is saved.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ExportProgress {
+    /// The band that was just saved, in pixels of the whole image.
+    pub band_rect: RectI,
+    /// The number of bands saved so far, including those saved by earlier runs.
+    pub completed_band_count: usize,
+    /// The total number of bands.
+    pub band_count: usize,
+}
+
+/// Whether `BandedExport::render()` saved every band.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportStatus {
+    /// Every band has been saved, and the image can be assembled.
+    Complete,
+    /// The progress callback stopped the export. Rendering again resumes it.
+    Stopped,
+}
+
+impl BandedExport {
+    /// Prepares to export an image of the given size, with bands as large as the device allows,
+    /// up to `DEFAULT_MAX_BAND_DIMENSION`.
+    ///
+    /// See `with_max_band_size()`.
+    pub fn new<P>(device: &Device, size: Vector2I, directory: P) -> io::Result<BandedExport>
+                  where P: Into<PathBuf> {
+        BandedExport::with_max_band_size(size, max_band_size(device), directory)
+    }
+
+    /// Prepares to export an image of the given size, split into bands no larger than
+    /// `max_band_size`, whose PNGs are kept in `directory`.
+    ///
+    /// The directory is created if needed. If it already holds bands of an export with the same
+    /// size and band size, they're kept, and rendering resumes after them. If it holds bands of a
+    /// different export, this fails with `io::ErrorKind::InvalidData`.
+    pub fn with_max_band_size<P>(size: Vector2I, max_band_size: Vector2I, directory: P)
+                                 -> io::Result<BandedExport>
+                                 where P: Into<PathBuf> {
+        assert!(size.x() > 0 && size.y() > 0, "The image must not be empty!");
+        assert!(max_band_size.x() > 0 && max_band_size.y() > 0, "Bands must not be empty!");
+
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        let checkpoint = format!("{} {} {} {} {}\n",
+                                 CHECKPOINT_MAGIC,
+                                 size.x(),
+                                 size.y(),
+                                 max_band_size.x(),
+                                 max_band_size.y());
+        let checkpoint_path = directory.join(CHECKPOINT_FILENAME);
+        match fs::read_to_string(&checkpoint_path) {
+            Ok(ref existing) if *existing == checkpoint => {}
+            Ok(_) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          "the directory holds the bands of a different export"))
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+                fs::write(&checkpoint_path, checkpoint)?
+            }
+            Err(error) => return Err(error),
+        }
+
+        Ok(BandedExport { size, bands: split_into_bands(size, max_band_size), directory })
+    }
+
+    /// Returns the size of the whole image.
+    #[inline]
+    pub fn size(&self) -> Vector2I {
+        self.size
+    }
+
+    /// Returns the bands, row by row, in pixels of the whole image.
+    #[inline]
+    pub fn bands(&self) -> &[RectI] {
+        &self.bands
+    }
+
+    /// Returns the directory the bands are saved in.
+    #[inline]
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns true if the given band has been saved.
+    pub fn is_band_complete(&self, band_index: usize) -> bool {
+        self.band_path(band_index).exists()
+    }
+
+    /// Returns the number of bands that have been saved.
+    pub fn completed_band_count(&self) -> usize {
+        (0..self.bands.len()).filter(|&band_index| self.is_band_complete(band_index)).count()
+    }
+
+    /// Renders and saves each band that hasn't been saved yet.
+    ///
+    /// The scene is drawn in pixels of the whole image. Each band is built and rendered in its
+    /// own submission, with the view box and transform moved to that band, and both are restored
+    /// before this returns. `progress` is called after each band is saved; returning
+    /// `ControlFlow::Break` stops the export, which a later call resumes.
+    pub fn render<F>(&self,
+                     device: &Device,
+                     scene: &mut Scene,
+                     background_color: Option<ColorF>,
+                     mut progress: F)
+                     -> io::Result<ExportStatus>
+                     where F: FnMut(&ExportProgress) -> ControlFlow<()> {
+        let remaining: Vec<usize> = (0..self.bands.len()).filter(|&band_index| {
+            !self.is_band_complete(band_index)
+        }).collect();
+        if remaining.is_empty() {
+            return Ok(ExportStatus::Complete);
+        }
+
+        let mut renderer = band_renderer(device, &self.bands, background_color);
+
+        let view_box = scene.view_box();
+        let mut completed_band_count = self.bands.len() - remaining.len();
+        let mut result = Ok(ExportStatus::Complete);
+        for band_index in remaining {
+            let band_rect = self.bands[band_index];
+            let pixels = render_band(&mut renderer, scene, band_rect);
+            if let Err(error) = self.save_band(band_index, pixels) {
+                result = Err(error);
+                break;
+            }
+
+            completed_band_count += 1;
+            let band_progress = ExportProgress {
+                band_rect,
+                completed_band_count,
+                band_count: self.bands.len(),
+            };
+            if progress(&band_progress).is_break() && completed_band_count < self.bands.len() {
+                result = Ok(ExportStatus::Stopped);
+                break;
+            }
+        }
+        scene.set_view_box(view_box);
+        result
+    }
+
+    /// Stitches the saved bands into a PNG of the whole image at `path`.
+    ///
+    /// Fails with `io::ErrorKind::InvalidInput` if any band hasn't been saved yet.
+    pub fn assemble<P>(&self, path: P) -> io::Result<()> where P: AsRef<Path> {
+        if let Some(band_index) = (0..self.bands.len()).find(|&band_index| {
+            !self.is_band_complete(band_index)
+        }) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("band {} hasn't been rendered", band_index)));
+        }
+
+        let mut writer = PngBandWriter::new(BufWriter::new(File::create(path)?), self.size)?;
+        for (band_index, &band_rect) in self.bands.iter().enumerate() {
+            writer.push_band(band_rect, self.load_band(band_index)?.into_raw())?;
+        }
+        writer.finish()
+    }
+
+    /// Deletes the working directory and the bands in it, e.g. once the image is assembled.
+    pub fn remove_directory(self) -> io::Result<()> {
+        fs::remove_dir_all(&self.directory)
+    }
+
+    fn band_path(&self, band_index: usize) -> PathBuf {
+        self.directory.join(format!("band_{:05}.png", band_index))
+    }
+
+    // Writes the band under a temporary name first, so that a band cut off partway through
+    // saving isn't mistaken for a finished one.
+    fn save_band(&self, band_index: usize, pixels: Vec<u8>) -> io::Result<()> {
+        let size = self.bands[band_index].size();
+        let image = RgbaImage::from_raw(size.x() as u32, size.y() as u32, pixels).unwrap();
+        let path = self.band_path(band_index);
+        let partial_path = path.with_extension("png.partial");
+        image.save_with_format(&partial_path, ImageFormat::Png).map_err(to_io_error)?;
+        fs::rename(partial_path, path)
+    }
+
+    fn load_band(&self, band_index: usize) -> io::Result<RgbaImage> {
+        let image = image::open(self.band_path(band_index)).map_err(to_io_error)?.to_rgba8();
+        let size = self.bands[band_index].size();
+        if image.dimensions() != (size.x() as u32, size.y() as u32) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("band {} has the wrong size", band_index)));
+        }
+        Ok(image)
+    }
+}
+
+impl ExportProgress {
+    /// Returns the fraction of bands saved so far, from 0 to 1.
+    #[inline]
+    pub fn fraction(&self) -> f32 {
+        self.completed_band_count as f32 / self.band_count as f32
+    }
+}
+
+/// Renders an image of the given size in bands as large as the device allows, up to
+/// `DEFAULT_MAX_BAND_DIMENSION`, and calls `f` with each band's rectangle and its pixels.
+///
+/// Bands arrive row by row, each in tightly packed rows with straight alpha, and each is rendered
+/// in its own submission. If `f` returns an error, no more bands are rendered and the error is
+/// returned.
+pub fn render_in_bands<F, E>(device: &Device,
+                             scene: &mut Scene,
+                             size: Vector2I,
+                             background_color: Option<ColorF>,
+                             mut f: F)
+                             -> Result<(), E>
+                             where F: FnMut(RectI, Vec<u8>) -> Result<(), E> {
+    let bands = split_into_bands(size, max_band_size(device));
+    let mut renderer = band_renderer(device, &bands, background_color);
+    let view_box = scene.view_box();
+    let mut result = Ok(());
+    for band_rect in bands {
+        let pixels = render_band(&mut renderer, scene, band_rect);
+        result = f(band_rect, pixels);
+        if result.is_err() {
+            break;
+        }
+    }
+    scene.set_view_box(view_box);
+    result
+}
+
+/// Renders an image of the given size, however large, and returns it with straight alpha.
+///
+/// The whole image is held in memory, 4 bytes per pixel; prefer `render_to_png()` for images
+/// that are only going to be written out.
+pub fn render_to_image(device: &Device,
+                       scene: &mut Scene,
+                       size: Vector2I,
+                       background_color: Option<ColorF>)
+                       -> RgbaImage {
+    let mut image = RgbaImage::new(size.x() as u32, size.y() as u32);
+    let image_stride = size.x() as usize * 4;
+    let copy_band = |rect: RectI, pixels: Vec<u8>| -> Result<(), ()> {
+        let band_stride = rect.width() as usize * 4;
+        for (y, band_row) in pixels.chunks(band_stride).enumerate() {
+            let start = (rect.origin_y() as usize + y) * image_stride +
+                rect.origin_x() as usize * 4;
+            (*image)[start..(start + band_stride)].copy_from_slice(band_row);
+        }
+        Ok(())
+    };
+    render_in_bands(device, scene, size, background_color, copy_band).unwrap();
+    image
+}
+
+/// Renders an image of the given size, however large, and writes it to `writer` as a PNG.
+///
+/// Bands are encoded as soon as a whole row of them has been rendered, so only one row of bands
+/// is held in memory at a time.
+pub fn render_to_png<W>(device: &Device,
+                        scene: &mut Scene,
+                        size: Vector2I,
+                        background_color: Option<ColorF>,
+                        writer: W)
+                        -> io::Result<()>
+                        where W: Write {
+    let mut writer = PngBandWriter::new(writer, size)?;
+    render_in_bands(device, scene, size, background_color, |rect, pixels| {
+        writer.push_band(rect, pixels)
+    })?;
+    writer.finish()
+}
+
+/// Splits an image into bands no larger than `max_band_size`, row by row.
+///
+/// Images narrow enough are split into full-width horizontal bands; wider ones into a grid. The
+/// bands in the last row and column are smaller if the image doesn't divide evenly.
+pub fn split_into_bands(size: Vector2I, max_band_size: Vector2I) -> Vec<RectI> {
+    let mut bands = vec![];
+    for y in (0..size.y()).step_by(max_band_size.y() as usize) {
+        for x in (0..size.x()).step_by(max_band_size.x() as usize) {
+            let origin = vec2i(x, y);
+            bands.push(RectI::new(origin, (size - origin).min(max_band_size)));
+        }
+    }
+    bands
+}
+
+// Encodes bands as a PNG. Bands must be pushed row by row, and a row is written out when the
+// first band of the next one arrives.
+struct PngBandWriter<W> where W: Write {
+    stream: png::StreamWriter<'static, W>,
+    row_bands: Vec<(RectI, Vec<u8>)>,
+    row: Vec<u8>,
+}
+
+impl<W> PngBandWriter<W> where W: Write {
+    fn new(writer: W, size: Vector2I) -> io::Result<PngBandWriter<W>> {
+        let mut encoder = png::Encoder::new(writer, size.x() as u32, size.y() as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let writer = encoder.write_header().map_err(to_io_error)?;
+        let stream = writer.into_stream_writer().map_err(to_io_error)?;
+        Ok(PngBandWriter { stream, row_bands: vec![], row: vec![] })
+    }
+
+    fn push_band(&mut self, rect: RectI, pixels: Vec<u8>) -> io::Result<()> {
+        if let Some(&(row_rect, _)) = self.row_bands.first() {
+            if row_rect.origin_y() != rect.origin_y() {
+                self.flush_row()?;
+            }
+        }
+        self.row_bands.push((rect, pixels));
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.flush_row()?;
+        self.stream.finish().map_err(to_io_error)
+    }
+
+    fn flush_row(&mut self) -> io::Result<()> {
+        let height = match self.row_bands.first() {
+            None => return Ok(()),
+            Some(&(row_rect, _)) => row_rect.height() as usize,
+        };
+        for y in 0..height {
+            self.row.clear();
+            for (rect, pixels) in &self.row_bands {
+                let stride = rect.width() as usize * 4;
+                self.row.extend_from_slice(&pixels[(y * stride)..((y + 1) * stride)]);
+            }
+            self.stream.write_all(&self.row)?;
+        }
+        self.row_bands.clear();
+        Ok(())
+    }
+}
+
+fn max_band_size(device: &Device) -> Vector2I {
+    let max_texture_dimension = device.device.limits().max_texture_dimension_2d as i32;
+    Vector2I::splat(max_texture_dimension.min(DEFAULT_MAX_BAND_DIMENSION))
+}
+
+// Creates a renderer large enough for the biggest of the bands.
+fn band_renderer(device: &Device, bands: &[RectI], background_color: Option<ColorF>)
+                 -> HeadlessRenderer {
+    let band_size = bands.iter().fold(Vector2I::zero(), |size, band| size.max(band.size()));
+    HeadlessRenderer::from_device(device.clone(), band_size, background_color)
+}
+
+// Renders the part of the scene under `band_rect` and returns its pixels with straight alpha.
+fn render_band(renderer: &mut HeadlessRenderer, scene: &mut Scene, band_rect: RectI)
+               -> Vec<u8> {
+    scene.set_view_box(RectF::new(Vector2F::zero(), band_rect.size().to_f32()));
+    let transform = Transform2F::from_translation(-band_rect.origin().to_f32());
+    let options = BuildOptions {
+        transform: RenderTransform::Transform2D(transform),
+        ..BuildOptions::default()
+    };
+    scene.build_and_render(renderer.renderer_mut(), options, SequentialExecutor);
+
+    let renderer = renderer.renderer();
+    let rect = RectI::new(Vector2I::zero(), band_rect.size());
+    let mut pixels = match renderer.device().read_texture(renderer.intermediate_dest_texture(),
+                                                          rect) {
+        TextureData::U8(pixels) => pixels,
+        _ => panic!("Unexpected pixel format for the destination texture!"),
+    };
+    unpremultiply(&mut pixels);
+    pixels
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PngBandWriter, split_into_bands};
+    use pathfinder_geometry::rect::RectI;
+    use pathfinder_geometry::vector::vec2i;
+
+    #[test]
+    fn test_split_into_horizontal_bands() {
+        let bands = split_into_bands(vec2i(100, 250), vec2i(128, 100));
+        assert_eq!(bands, vec![
+            RectI::new(vec2i(0, 0), vec2i(100, 100)),
+            RectI::new(vec2i(0, 100), vec2i(100, 100)),
+            RectI::new(vec2i(0, 200), vec2i(100, 50)),
+        ]);
+    }
+
+    #[test]
+    fn test_split_into_grid() {
+        let bands = split_into_bands(vec2i(300, 150), vec2i(128, 128));
+        assert_eq!(bands.len(), 6);
+        assert_eq!(bands[2], RectI::new(vec2i(256, 0), vec2i(44, 128)));
+        assert_eq!(bands[3], RectI::new(vec2i(0, 128), vec2i(128, 22)));
+        let area: i32 = bands.iter().map(|band| band.width() * band.height()).sum();
+        assert_eq!(area, 300 * 150);
+    }
+
+    #[test]
+    fn test_stitch_bands_into_png() {
+        let size = vec2i(5, 3);
+        let mut data = vec![];
+        let mut writer = PngBandWriter::new(&mut data, size).unwrap();
+        for band_rect in split_into_bands(size, vec2i(2, 2)) {
+            // Each pixel's red channel is its X coordinate in the whole image and its green
+            // channel its Y coordinate.
+            let mut pixels = vec![];
+            for y in band_rect.min_y()..band_rect.max_y() {
+                for x in band_rect.min_x()..band_rect.max_x() {
+                    pixels.extend_from_slice(&[x as u8, y as u8, 0, 255]);
+                }
+            }
+            writer.push_band(band_rect, pixels).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let image = image::load_from_memory(&data).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (5, 3));
+        for (x, y, pixel) in image.enumerate_pixels() {
+            assert_eq!(pixel.0, [x as u8, y as u8, 0, 255]);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+// pathfinder/headless/src/lib.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Renders Pathfinder scenes offscreen and reads the results back as images.
+//!
+//! This is for tools that never open a window: atlas bakers, exporters, tile servers, and so on.
+//! A `HeadlessRenderer` owns a GPU device on the default adapter and a renderer drawing into an
+//! offscreen texture, and returns each rendered frame as an `image::RgbaImage`, either at once or,
+//! with `render_async()`, from a callback once the GPU is done with it. The `animation` module
+//! builds on this to export animated GIFs, APNGs, and PNG sequences, the `bands` module exports
+//! images too large for one texture, and the `masks` module rasterizes batches of glyphs and paths
+//! to alpha masks for engines with their own compositor. The `screenshot` module captures frames
+//! with a JSON description of the scene, for bug reports and visual test triage.
+//! With the `tiny-skia` feature, the `pixmap` module exchanges images with tiny-skia for hybrid
+//! CPU/GPU pipelines.
+
+use image::RgbaImage;
+use pathfinder_color::ColorF;
+use pathfinder_geometry::rect::RectI;
+use pathfinder_geometry::vector::{Vector2I, vec2i};
+use pathfinder_gpu::readback::ReadbackBatch;
+use pathfinder_gpu::{Device, TextureData};
+use pathfinder_renderer::concurrent::executor::SequentialExecutor;
+use pathfinder_renderer::gpu::options::{DestFramebuffer, RendererLevel, RendererMode};
+use pathfinder_renderer::gpu::options::{AlphaMode, LoadAction, RendererOptions};
+use pathfinder_renderer::gpu::renderer::Renderer;
+use pathfinder_renderer::options::{BuildOptions, THUMBNAIL_SCALE};
+use pathfinder_renderer::scene::Scene;
+use pathfinder_resources::embedded::EmbeddedResourceLoader;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+
+pub mod animation;
+pub mod bands;
+pub mod masks;
+pub mod screenshot;
+#[cfg(feature = "tiny-skia")]
+pub mod pixmap;
+
+/// A renderer that draws into an offscreen texture of a fixed size.
+pub struct HeadlessRenderer {
+    renderer: Renderer,
+    size: Vector2I,
+    background_color: Option<ColorF>,
+}
+
+/// The reasons a headless renderer can fail to be created.
+#[derive(Debug)]
+pub enum HeadlessError {
+    /// No GPU adapter is available, e.g. on a CI machine without a software rasterizer.
+    NoAdapter,
+    /// The adapter refused to create a device.
+    RequestDevice(wgpu::RequestDeviceError),
+}
+
+impl HeadlessRenderer {
+    /// Creates a renderer on the default adapter that renders frames of the given size.
+    ///
+    /// If `background_color` is `None`, frames are cleared to transparent.
+    pub fn new(size: Vector2I, background_color: Option<ColorF>)
+               -> Result<HeadlessRenderer, HeadlessError> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        })).map_err(|_| HeadlessError::NoAdapter)?;
+
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("Headless Device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::default(),
+            memory_hints: Default::default(),
+            experimental_features: wgpu::ExperimentalFeatures::disabled(),
+            trace: wgpu::Trace::default(),
+        })).map_err(HeadlessError::RequestDevice)?;
+
+        let device = Device::new(Arc::new(device),
+                                 Arc::new(queue),
+                                 adapter.get_info().name,
+                                 adapter.get_info().backend.to_str().to_string());
+        Ok(HeadlessRenderer::from_device(device, size, background_color))
+    }
+
+    /// Creates a renderer on an existing device, e.g. one shared with another engine so that
+    /// the rendered textures can be used there directly.
+    pub fn from_device(device: Device, size: Vector2I, background_color: Option<ColorF>)
+                       -> HeadlessRenderer {
+        let renderer = create_renderer(device, size, background_color);
+        HeadlessRenderer { renderer, size, background_color }
+    }
+
+    /// Returns the size of the frames this renderer produces, in device pixels.
+    #[inline]
+    pub fn size(&self) -> Vector2I {
+        self.size
+    }
+
+    /// Changes the size of subsequent frames.
+    ///
+    /// The GPU device is kept, but the renderer and its resources are recreated.
+    pub fn set_size(&mut self, new_size: Vector2I) {
+        if new_size == self.size {
+            return;
+        }
+        let device = self.renderer.device().clone();
+        self.renderer = create_renderer(device, new_size, self.background_color);
+        self.size = new_size;
+    }
+
+    /// Creates a renderer for thumbnails of this renderer's frames, on the same device.
+    ///
+    /// Its frames are `THUMBNAIL_SCALE` times the size, to be rendered with
+    /// `BuildOptions::thumbnail()`. Since it has its own destination, thumbnails can be rendered
+    /// between full-quality frames without disturbing them.
+    pub fn thumbnail_renderer(&self) -> HeadlessRenderer {
+        let size = (self.size.to_f32() * THUMBNAIL_SCALE).ceil().to_i32().max(vec2i(1, 1));
+        HeadlessRenderer::from_device(self.renderer.device().clone(), size, self.background_color)
+    }
+
+    /// Returns the underlying renderer.
+    #[inline]
+    pub fn renderer(&self) -> &Renderer {
+        &self.renderer
+    }
+
+    /// Returns the underlying renderer, e.g. to set custom shaders.
+    #[inline]
+    pub fn renderer_mut(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+
+    /// Builds and renders the scene with default build options, and returns the resulting frame.
+    #[inline]
+    pub fn render(&mut self, scene: &mut Scene) -> RgbaImage {
+        self.render_with_options(scene, BuildOptions::default())
+    }
+
+    /// Builds and renders the scene, and returns the resulting frame.
+    ///
+    /// The returned image has straight (not premultiplied) alpha, as image formats expect.
+    pub fn render_with_options(&mut self, scene: &mut Scene, options: BuildOptions)
+                               -> RgbaImage {
+        let mut pixels = self.render_premultiplied(scene, options);
+        unpremultiply(&mut pixels);
+        RgbaImage::from_raw(self.size.x() as u32, self.size.y() as u32, pixels).unwrap()
+    }
+
+    /// Builds and renders the scene, and calls `callback` with the resulting frame once it has
+    /// been read back, without waiting for the GPU.
+    ///
+    /// The frame is copied out of the offscreen texture before the next one is rendered into it,
+    /// so many small scenes, such as thumbnails, can be rendered back to back without stalling on
+    /// each readback. Callbacks run from `poll_readbacks()`, in the order the frames were
+    /// rendered. The image has straight alpha, like that of `render_with_options()`.
+    pub fn render_async<F>(&mut self, scene: &mut Scene, options: BuildOptions, callback: F)
+                           where F: FnOnce(RgbaImage) + Send + 'static {
+        scene.build_and_render(&mut self.renderer, options, SequentialExecutor);
+        let device = self.renderer.device();
+        let size = self.size;
+        let mut batch = ReadbackBatch::new(device);
+        batch.read_texture(device,
+                           self.renderer.intermediate_dest_texture(),
+                           RectI::new(Vector2I::zero(), size),
+                           move |result| {
+            let mut pixels = match result.expect("Failed to read back a frame!") {
+                TextureData::U8(pixels) => pixels,
+                _ => panic!("Unexpected pixel format for the destination texture!"),
+            };
+            unpremultiply(&mut pixels);
+            callback(RgbaImage::from_raw(size.x() as u32, size.y() as u32, pixels).unwrap())
+        });
+        batch.submit(device);
+    }
+
+    /// Runs the callbacks of frames from `render_async()` that have been read back.
+    ///
+    /// If `wait` is true, first blocks until every frame rendered so far has been read back.
+    #[inline]
+    pub fn poll_readbacks(&self, wait: bool) {
+        self.renderer.device().poll_readbacks(wait)
+    }
+
+    // Returns tightly packed RGBA rows with premultiplied alpha, as the renderer produces them.
+    fn render_premultiplied(&mut self, scene: &mut Scene, options: BuildOptions) -> Vec<u8> {
+        scene.build_and_render(&mut self.renderer, options, SequentialExecutor);
+        let device = self.renderer.device();
+        let rect = RectI::new(Vector2I::zero(), self.size);
+        match device.read_texture(self.renderer.intermediate_dest_texture(), rect) {
+            TextureData::U8(pixels) => pixels,
+            _ => panic!("Unexpected pixel format for the destination texture!"),
+        }
+    }
+}
+
+fn create_renderer(device: Device, size: Vector2I, background_color: Option<ColorF>)
+                   -> Renderer {
+    let dest_texture = device.create_texture(wgpu::TextureFormat::Rgba8Unorm,
+                                             size,
+                                             wgpu::TextureUsages::RENDER_ATTACHMENT |
+                                             wgpu::TextureUsages::TEXTURE_BINDING |
+                                             wgpu::TextureUsages::COPY_SRC);
+    let options = RendererOptions {
+        dest: DestFramebuffer::Other(dest_texture),
+        background_color,
+        load_action: LoadAction::Clear,
+        alpha_mode: AlphaMode::Premultiplied,
+        show_debug_ui: false,
+    };
+    let mode = RendererMode { level: RendererLevel::D3D9 };
+    Renderer::new(device, &EmbeddedResourceLoader::new(), mode, options)
+}
+
+fn unpremultiply(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_mut(4) {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 || alpha == 255 {
+            continue;
+        }
+        for channel in &mut pixel[0..3] {
+            *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+impl Display for HeadlessError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            HeadlessError::NoAdapter => write!(formatter, "no GPU adapter is available"),
+            HeadlessError::RequestDevice(ref error) => {
+                write!(formatter, "failed to create a GPU device: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for HeadlessError {}
+
+#[cfg(test)]
+mod test {
+    use super::unpremultiply;
+
+    #[test]
+    fn test_unpremultiply() {
+        let mut pixels = [128, 64, 0, 128, 10, 20, 30, 255, 0, 0, 0, 0];
+        unpremultiply(&mut pixels);
+        assert_eq!(pixels, [255, 128, 0, 128, 10, 20, 30, 255, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,345 @@
+// pathfinder/headless/src/masks.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rasterizing glyphs and paths to alpha masks on the GPU, for text engines and other renderers
+//! that have their own compositor and only want Pathfinder's rasterizer.
+//!
+//! Masks are queued with `push_glyph()` and `push_outline()`, then rasterized together by
+//! `rasterize()`. The queued masks are packed into as few atlas pages as possible, and each page
+//! is rendered in a single pass in coverage output mode, so the cost of a batch grows with the
+//! number of pages rather than the number of masks. Results come back either as CPU bytes with
+//! one byte of coverage per pixel, or, with `rasterize_with()`, as regions of the GPU coverage
+//! texture for the caller to copy into its own atlas.
+//!
+//! Each mask keeps the subpixel position of its outline, so glyphs pushed with different
+//! fractional offsets rasterize differently.
+
+use crate::{HeadlessError, HeadlessRenderer};
+use font_kit::error::GlyphLoadingError;
+use font_kit::hinting::HintingOptions;
+use font_kit::loader::Loader;
+use pathfinder_color::ColorU;
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::outline::Outline;
+use pathfinder_geometry::rect::{RectF, RectI};
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f, vec2i};
+use pathfinder_gpu::{Device, Texture, TextureData};
+use pathfinder_renderer::concurrent::executor::SequentialExecutor;
+use pathfinder_renderer::gpu::options::CoverageFormat;
+use pathfinder_renderer::options::BuildOptions;
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{DrawPath, Scene};
+use pathfinder_text::OutlinePathBuilder;
+use std::mem;
+
+/// Queues glyphs and paths and rasterizes them to alpha masks in batches.
+pub struct MaskRasterizer {
+    renderer: HeadlessRenderer,
+    padding: i32,
+    pending: Vec<(Outline, FillRule)>,
+}
+
+/// Identifies a mask within the batch it was pushed to.
+///
+/// IDs are assigned in the order masks are pushed, starting from zero after each call to
+/// `rasterize()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaskId(pub u32);
+
+/// Where a rasterized mask is in its atlas page.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaskLocation {
+    /// The index of the page that holds the mask.
+    pub page: u32,
+    /// The mask's rectangle in the page, including padding. Empty if the mask has no coverage.
+    pub rect: RectI,
+    /// The position of the outline's origin (the pen position, for glyphs), relative to the top
+    /// left corner of `rect`.
+    pub origin: Vector2I,
+}
+
+/// A rasterized batch of masks, read back to the CPU.
+pub struct MaskBatch {
+    /// The atlas pages, trimmed to the area that holds masks.
+    pub pages: Vec<MaskPage>,
+    /// The location of each mask, indexed by `MaskId`.
+    pub locations: Vec<MaskLocation>,
+}
+
+/// One atlas page of coverage values.
+pub struct MaskPage {
+    /// The size of the page, in pixels.
+    pub size: Vector2I,
+    /// One byte of coverage per pixel, in tightly packed rows from the top.
+    pub coverage: Vec<u8>,
+}
+
+/// An atlas page that has just been rendered on the GPU.
+///
+/// See `MaskRasterizer::rasterize_with()`.
+pub struct RenderedMaskPage<'a> {
+    /// The index of the page.
+    pub index: u32,
+    /// The device the texture belongs to.
+    pub device: &'a Device,
+    /// A single-channel `R8Unorm` texture holding the coverage of the page.
+    pub texture: &'a Texture,
+    /// The area of the texture that holds masks. Everything outside it is undefined.
+    pub used_rect: RectI,
+}
+
+impl MaskRasterizer {
+    /// Creates a rasterizer on the default adapter, with atlas pages of the given size.
+    ///
+    /// Larger pages mean fewer passes per batch. Masks larger than a page are clipped.
+    pub fn new(page_size: Vector2I) -> Result<MaskRasterizer, HeadlessError> {
+        Ok(MaskRasterizer::from_renderer(HeadlessRenderer::new(page_size, None)?))
+    }
+
+    /// Creates a rasterizer on an existing device, so that `rasterize_with()` can hand out
+    /// textures that another engine can copy from directly.
+    pub fn from_device(device: Device, page_size: Vector2I) -> MaskRasterizer {
+        MaskRasterizer::from_renderer(HeadlessRenderer::from_device(device, page_size, None))
+    }
+
+    fn from_renderer(mut renderer: HeadlessRenderer) -> MaskRasterizer {
+        renderer.renderer_mut().set_coverage_output(Some(CoverageFormat::R8));
+        MaskRasterizer { renderer, padding: 1, pending: vec![] }
+    }
+
+    /// Returns the size of each atlas page, in pixels.
+    #[inline]
+    pub fn page_size(&self) -> Vector2I {
+        self.renderer.size()
+    }
+
+    /// Returns the number of empty pixels around each mask. The default is 1.
+    #[inline]
+    pub fn padding(&self) -> i32 {
+        self.padding
+    }
+
+    /// Sets the number of empty pixels around each mask, which avoids bleeding when the masks
+    /// are sampled with filtering.
+    #[inline]
+    pub fn set_padding(&mut self, new_padding: i32) {
+        self.padding = new_padding.max(0);
+    }
+
+    /// Returns the number of masks waiting to be rasterized.
+    #[inline]
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Queues the outline, transformed to pixels by `transform`, to be rasterized.
+    pub fn push_outline(&mut self, outline: &Outline, transform: &Transform2F, fill_rule: FillRule)
+                        -> MaskId {
+        self.pending.push((outline.clone().transformed(transform), fill_rule));
+        MaskId(self.pending.len() as u32 - 1)
+    }
+
+    /// Queues a glyph at the given size in pixels per em to be rasterized.
+    ///
+    /// `subpixel_offset` moves the glyph by a fraction of a pixel. The Y axis points down, with
+    /// the origin of the mask at the pen position on the baseline.
+    pub fn push_glyph<F>(&mut self,
+                         font: &F,
+                         glyph_id: u32,
+                         size: f32,
+                         subpixel_offset: Vector2F)
+                         -> Result<MaskId, GlyphLoadingError>
+                         where F: Loader {
+        let scale = size / font.metrics().units_per_em as f32;
+        let transform = Transform2F::from_translation(subpixel_offset) *
+            Transform2F::from_scale(vec2f(scale, -scale));
+        let mut outline_builder = OutlinePathBuilder::new(&transform);
+        font.outline(glyph_id, HintingOptions::None, &mut outline_builder)?;
+        self.pending.push((outline_builder.build(), FillRule::Winding));
+        Ok(MaskId(self.pending.len() as u32 - 1))
+    }
+
+    /// Rasterizes every queued mask and reads the results back to the CPU.
+    pub fn rasterize(&mut self) -> MaskBatch {
+        let mut pages = vec![];
+        let locations = self.rasterize_with(|page| {
+            let coverage = match page.device.read_texture(page.texture, page.used_rect) {
+                TextureData::U8(coverage) => coverage,
+                _ => panic!("Unexpected pixel format for the coverage texture!"),
+            };
+            pages.push(MaskPage { size: page.used_rect.size(), coverage });
+        });
+        MaskBatch { pages, locations }
+    }
+
+    /// Rasterizes every queued mask, calling `f` with each atlas page as soon as it has been
+    /// rendered, and returns where each mask is.
+    ///
+    /// The texture is reused for the next page, so `f` must copy what it needs before returning.
+    pub fn rasterize_with<F>(&mut self, mut f: F) -> Vec<MaskLocation>
+                             where F: FnMut(&RenderedMaskPage) {
+        let page_size = self.page_size();
+        let padding = Vector2I::splat(self.padding);
+
+        let mut packer = PagePacker::new(page_size);
+        let mut pages: Vec<Vec<(Outline, FillRule)>> = vec![];
+        let mut locations = Vec::with_capacity(self.pending.len());
+        for (mut outline, fill_rule) in mem::replace(&mut self.pending, vec![]) {
+            let bounds = outline.bounds().round_out().to_i32();
+            if outline.is_empty() || bounds.width() <= 0 || bounds.height() <= 0 {
+                locations.push(MaskLocation {
+                    page: 0,
+                    rect: RectI::default(),
+                    origin: Vector2I::zero(),
+                });
+                continue;
+            }
+
+            let (page, slot_origin) = packer.allocate(bounds.size() + padding * 2);
+            let outline_origin = slot_origin + padding - bounds.origin();
+            outline.transform(&Transform2F::from_translation(outline_origin.to_f32()));
+            while pages.len() <= page as usize {
+                pages.push(vec![]);
+            }
+            pages[page as usize].push((outline, fill_rule));
+
+            let slot_size = (bounds.size() + padding * 2).min(page_size);
+            locations.push(MaskLocation {
+                page,
+                rect: RectI::new(slot_origin, slot_size),
+                origin: outline_origin - slot_origin,
+            });
+        }
+
+        for (index, outlines) in pages.into_iter().enumerate() {
+            let mut scene = Scene::new();
+            scene.set_view_box(RectF::new(Vector2F::zero(), page_size.to_f32()));
+            let paint = scene.push_paint(&Paint::from_color(ColorU::white()));
+            for (outline, fill_rule) in outlines {
+                let mut path = DrawPath::new(outline, paint);
+                path.set_fill_rule(fill_rule);
+                scene.push_draw_path(path);
+            }
+
+            let renderer = self.renderer.renderer_mut();
+            scene.build_and_render(renderer, BuildOptions::default(), SequentialExecutor);
+            f(&RenderedMaskPage {
+                index: index as u32,
+                device: renderer.device(),
+                texture: renderer.coverage_texture().unwrap(),
+                used_rect: RectI::new(Vector2I::zero(), packer.page_extent(index as u32)),
+            });
+        }
+
+        locations
+    }
+
+    /// Returns the device the masks are rendered with.
+    #[inline]
+    pub fn device(&self) -> &Device {
+        self.renderer.renderer().device()
+    }
+}
+
+impl MaskBatch {
+    /// Returns where the given mask is.
+    #[inline]
+    pub fn location(&self, id: MaskId) -> MaskLocation {
+        self.locations[id.0 as usize]
+    }
+
+    /// Copies the coverage of a single mask out of its page, in tightly packed rows.
+    pub fn coverage(&self, id: MaskId) -> Vec<u8> {
+        let location = self.location(id);
+        if location.rect.area() == 0 {
+            return vec![];
+        }
+        let page = &self.pages[location.page as usize];
+        let mut coverage = Vec::with_capacity(location.rect.area() as usize);
+        for y in location.rect.min_y()..location.rect.max_y() {
+            let row_start = (y * page.size.x() + location.rect.min_x()) as usize;
+            let row_end = row_start + location.rect.width() as usize;
+            coverage.extend_from_slice(&page.coverage[row_start..row_end]);
+        }
+        coverage
+    }
+}
+
+// Packs rectangles into rows ("shelves") on fixed-size pages, starting a new row when one is
+// full and a new page when the rows reach the bottom.
+struct PagePacker {
+    page_size: Vector2I,
+    page: u32,
+    cursor: Vector2I,
+    shelf_height: i32,
+    extents: Vec<Vector2I>,
+}
+
+impl PagePacker {
+    fn new(page_size: Vector2I) -> PagePacker {
+        PagePacker {
+            page_size,
+            page: 0,
+            cursor: Vector2I::zero(),
+            shelf_height: 0,
+            extents: vec![Vector2I::zero()],
+        }
+    }
+
+    // Rectangles larger than a page get a page of their own.
+    fn allocate(&mut self, size: Vector2I) -> (u32, Vector2I) {
+        if self.cursor.x() > 0 && self.cursor.x() + size.x() > self.page_size.x() {
+            self.cursor = vec2i(0, self.cursor.y() + self.shelf_height);
+            self.shelf_height = 0;
+        }
+        if self.cursor.y() > 0 && self.cursor.y() + size.y() > self.page_size.y() {
+            self.page += 1;
+            self.cursor = Vector2I::zero();
+            self.shelf_height = 0;
+            self.extents.push(Vector2I::zero());
+        }
+
+        let origin = self.cursor;
+        self.cursor = self.cursor + vec2i(size.x(), 0);
+        self.shelf_height = self.shelf_height.max(size.y());
+        let extent = &mut self.extents[self.page as usize];
+        *extent = extent.max(vec2i(self.cursor.x(), origin.y() + self.shelf_height))
+                        .min(self.page_size);
+        (self.page, origin)
+    }
+
+    fn page_extent(&self, page: u32) -> Vector2I {
+        self.extents[page as usize]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PagePacker;
+    use pathfinder_geometry::vector::vec2i;
+
+    #[test]
+    fn test_page_packer() {
+        let mut packer = PagePacker::new(vec2i(10, 8));
+        assert_eq!(packer.allocate(vec2i(4, 3)), (0, vec2i(0, 0)));
+        assert_eq!(packer.allocate(vec2i(4, 5)), (0, vec2i(4, 0)));
+        assert_eq!(packer.allocate(vec2i(4, 2)), (0, vec2i(0, 5)));
+        assert_eq!(packer.page_extent(0), vec2i(8, 7));
+
+        // Doesn't fit below the second shelf, so it starts a new page.
+        assert_eq!(packer.allocate(vec2i(3, 4)), (1, vec2i(0, 0)));
+        assert_eq!(packer.page_extent(1), vec2i(3, 4));
+
+        // Too large for any page, so it's clipped.
+        assert_eq!(packer.allocate(vec2i(20, 20)), (2, vec2i(0, 0)));
+        assert_eq!(packer.page_extent(2), vec2i(10, 8));
+    }
+}
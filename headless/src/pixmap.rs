@@ -0,0 +1,301 @@
+// pathfinder/headless/src/pixmap.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Exchanging images with tiny-skia, for hybrid CPU/GPU pipelines and parity testing.
+//!
+//! A tiny-skia `Pixmap` drawn on the CPU can be placed into a scene as a pattern-filled layer
+//! with `push_pixmap()`. In the other direction, `HeadlessRenderer::render_to_pixmap()` renders a
+//! scene on the GPU into a pixmap, and `rasterize_scene()` rasterizes it on the CPU with
+//! tiny-skia as a reference. `max_difference()` compares the two.
+//!
+//! The CPU rasterizer covers solid-color fills with clip paths, fill rules, blend modes, and tints.
+//! Paths with gradient or pattern paints and paths drawn into render targets are skipped, and
+//! filters are ignored.
+
+use crate::HeadlessRenderer;
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::effects::BlendMode;
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::outline::{ContourIterFlags, Outline};
+use pathfinder_content::pattern::{Image, Pattern};
+use pathfinder_content::segment::SegmentKind;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2i};
+use pathfinder_renderer::options::BuildOptions;
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{ClipPathId, DisplayItem, DrawPath, DrawPathId, Scene};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tiny_skia::{IntSize, Mask, Path, PathBuilder, Pixmap, PixmapRef, Transform};
+
+impl HeadlessRenderer {
+    /// Builds and renders the scene on the GPU, and returns the frame as a pixmap.
+    ///
+    /// Like all pixmaps, the result has premultiplied alpha.
+    pub fn render_to_pixmap(&mut self, scene: &mut Scene) -> Pixmap {
+        let pixels = self.render_premultiplied(scene, BuildOptions::default());
+        let size = IntSize::from_wh(self.size.x() as u32, self.size.y() as u32).unwrap();
+        Pixmap::from_vec(pixels, size).unwrap()
+    }
+}
+
+/// Converts a pixmap to a Pathfinder image, undoing the premultiplication.
+pub fn image_from_pixmap(pixmap: PixmapRef) -> Image {
+    let pixels = pixmap.pixels().iter().map(|pixel| {
+        let pixel = pixel.demultiply();
+        ColorU::new(pixel.red(), pixel.green(), pixel.blue(), pixel.alpha())
+    }).collect();
+    Image::new(vec2i(pixmap.width() as i32, pixmap.height() as i32), Arc::new(pixels))
+}
+
+/// Converts a pixmap to a pattern whose top left corner is at the origin.
+#[inline]
+pub fn pattern_from_pixmap(pixmap: PixmapRef) -> Pattern {
+    Pattern::from_image(image_from_pixmap(pixmap))
+}
+
+/// Draws a pixmap into the scene as a layer, with its top left corner at the origin before
+/// `transform` is applied.
+pub fn push_pixmap(scene: &mut Scene, pixmap: PixmapRef, transform: &Transform2F)
+                   -> DrawPathId {
+    let mut pattern = pattern_from_pixmap(pixmap);
+    let outline = Outline::from_rect(RectF::new(Vector2F::zero(), pattern.size().to_f32()));
+    pattern.apply_transform(*transform);
+    let paint = scene.push_paint(&Paint::from_pattern(pattern));
+    scene.push_draw_path(DrawPath::new(outline.transformed(transform), paint))
+}
+
+/// Rasterizes a scene on the CPU with tiny-skia, covering the scene's view box.
+///
+/// See the module documentation for what's supported. Returns `None` if the view box is empty.
+pub fn rasterize_scene(scene: &Scene, background_color: Option<ColorU>) -> Option<Pixmap> {
+    let view_box = scene.view_box();
+    let size = view_box.size().ceil().to_i32();
+    let mut pixmap = Pixmap::new(size.x() as u32, size.y() as u32)?;
+    if let Some(color) = background_color {
+        pixmap.fill(tiny_skia::Color::from_rgba8(color.r, color.g, color.b, color.a));
+    }
+
+    let origin = view_box.origin();
+    let transform = Transform::from_translate(-origin.x(), -origin.y());
+    let mut clip_masks = HashMap::new();
+
+    // Only paths at the top level of the display list reach the output.
+    let mut render_target_depth = 0;
+    for display_item in scene.display_list() {
+        match *display_item {
+            DisplayItem::PushRenderTarget(_) => render_target_depth += 1,
+            DisplayItem::PopRenderTarget => render_target_depth -= 1,
+            DisplayItem::DrawPaths(ref range) if render_target_depth == 0 => {
+                for index in range.start.0..range.end.0 {
+                    let draw_path = scene.get_draw_path(DrawPathId(index));
+                    let paint = scene.get_paint(draw_path.paint);
+                    if !paint.is_color() {
+                        continue;
+                    }
+                    let path = match outline_to_path(&draw_path.outline) {
+                        None => continue,
+                        Some(path) => path,
+                    };
+                    let mask = draw_path.clip_path.map(|clip_path_id| {
+                        clip_mask(scene, clip_path_id, size, transform, &mut clip_masks)
+                    });
+
+                    let color = paint.base_color().to_f32().0 * draw_path.tint.to_f32().0;
+                    let color = ColorF(color).to_u8();
+                    let mut tiny_skia_paint = tiny_skia::Paint::default();
+                    tiny_skia_paint.set_color_rgba8(color.r, color.g, color.b, color.a);
+                    tiny_skia_paint.blend_mode = to_tiny_skia_blend_mode(draw_path.blend_mode);
+                    tiny_skia_paint.anti_alias = draw_path.is_antialiased();
+                    pixmap.fill_path(&path,
+                                     &tiny_skia_paint,
+                                     to_tiny_skia_fill_rule(draw_path.fill_rule),
+                                     transform,
+                                     mask.as_ref());
+                }
+            }
+            DisplayItem::DrawPaths(_) => {}
+        }
+    }
+
+    Some(pixmap)
+}
+
+/// Returns the largest difference between corresponding channels of two pixmaps, or `None` if
+/// their sizes differ.
+pub fn max_difference(a: PixmapRef, b: PixmapRef) -> Option<u8> {
+    if a.width() != b.width() || a.height() != b.height() {
+        return None;
+    }
+    let differences = a.data().iter().zip(b.data()).map(|(&a, &b)| (a as i16 - b as i16).abs());
+    Some(differences.max().unwrap_or(0) as u8)
+}
+
+/// Converts an outline to a tiny-skia path, or returns `None` if it's empty.
+pub fn outline_to_path(outline: &Outline) -> Option<Path> {
+    let mut builder = PathBuilder::new();
+    for contour in outline.contours() {
+        let first = match contour.first_position() {
+            None => continue,
+            Some(first) => first,
+        };
+        builder.move_to(first.x(), first.y());
+        for segment in contour.iter(ContourIterFlags::IGNORE_CLOSE_SEGMENT) {
+            let (ctrl, to) = (segment.ctrl, segment.baseline.to());
+            match segment.kind {
+                SegmentKind::None => {}
+                SegmentKind::Line => builder.line_to(to.x(), to.y()),
+                SegmentKind::Quadratic => {
+                    builder.quad_to(ctrl.from_x(), ctrl.from_y(), to.x(), to.y())
+                }
+                SegmentKind::Cubic => {
+                    builder.cubic_to(ctrl.from_x(),
+                                     ctrl.from_y(),
+                                     ctrl.to_x(),
+                                     ctrl.to_y(),
+                                     to.x(),
+                                     to.y())
+                }
+            }
+        }
+        if contour.is_closed() {
+            builder.close();
+        }
+    }
+    builder.finish()
+}
+
+// Nested clip paths intersect with their parents. An empty clip path clips everything away.
+fn clip_mask(scene: &Scene,
+             clip_path_id: ClipPathId,
+             size: Vector2I,
+             transform: Transform,
+             cache: &mut HashMap<ClipPathId, Mask>)
+             -> Mask {
+    if let Some(mask) = cache.get(&clip_path_id) {
+        return mask.clone();
+    }
+
+    let clip_path = scene.get_clip_path(clip_path_id);
+    let mut mask = match clip_path.clip_path {
+        Some(parent_id) => clip_mask(scene, parent_id, size, transform, cache),
+        None => {
+            let mut mask = Mask::new(size.x() as u32, size.y() as u32).unwrap();
+            mask.data_mut().iter_mut().for_each(|coverage| *coverage = 255);
+            mask
+        }
+    };
+    let fill_rule = to_tiny_skia_fill_rule(clip_path.fill_rule);
+    match (outline_to_path(&clip_path.outline), clip_path.inverted) {
+        (Some(path), false) => mask.intersect_path(&path, fill_rule, true, transform),
+        (None, false) => mask.data_mut().iter_mut().for_each(|coverage| *coverage = 0),
+        (Some(path), true) => {
+            // Keep what's outside the path by scaling by its uncovered fraction.
+            let mut inside = Mask::new(size.x() as u32, size.y() as u32).unwrap();
+            inside.fill_path(&path, fill_rule, true, transform);
+            for (coverage, &inside) in mask.data_mut().iter_mut().zip(inside.data()) {
+                *coverage = ((*coverage as u32 * (255 - inside as u32) + 127) / 255) as u8;
+            }
+        }
+        (None, true) => {}
+    }
+
+    cache.insert(clip_path_id, mask.clone());
+    mask
+}
+
+fn to_tiny_skia_fill_rule(fill_rule: FillRule) -> tiny_skia::FillRule {
+    match fill_rule {
+        FillRule::Winding => tiny_skia::FillRule::Winding,
+        FillRule::EvenOdd => tiny_skia::FillRule::EvenOdd,
+    }
+}
+
+fn to_tiny_skia_blend_mode(blend_mode: BlendMode) -> tiny_skia::BlendMode {
+    match blend_mode {
+        BlendMode::Clear => tiny_skia::BlendMode::Clear,
+        BlendMode::Copy => tiny_skia::BlendMode::Source,
+        BlendMode::SrcIn => tiny_skia::BlendMode::SourceIn,
+        BlendMode::SrcOut => tiny_skia::BlendMode::SourceOut,
+        BlendMode::SrcOver => tiny_skia::BlendMode::SourceOver,
+        BlendMode::SrcAtop => tiny_skia::BlendMode::SourceAtop,
+        BlendMode::DestIn => tiny_skia::BlendMode::DestinationIn,
+        BlendMode::DestOut => tiny_skia::BlendMode::DestinationOut,
+        BlendMode::DestOver => tiny_skia::BlendMode::DestinationOver,
+        BlendMode::DestAtop => tiny_skia::BlendMode::DestinationAtop,
+        BlendMode::Xor => tiny_skia::BlendMode::Xor,
+        BlendMode::Lighter => tiny_skia::BlendMode::Plus,
+        BlendMode::Darken => tiny_skia::BlendMode::Darken,
+        BlendMode::Lighten => tiny_skia::BlendMode::Lighten,
+        BlendMode::Multiply => tiny_skia::BlendMode::Multiply,
+        BlendMode::Screen => tiny_skia::BlendMode::Screen,
+        BlendMode::HardLight => tiny_skia::BlendMode::HardLight,
+        BlendMode::Overlay => tiny_skia::BlendMode::Overlay,
+        BlendMode::ColorDodge => tiny_skia::BlendMode::ColorDodge,
+        BlendMode::ColorBurn => tiny_skia::BlendMode::ColorBurn,
+        BlendMode::SoftLight => tiny_skia::BlendMode::SoftLight,
+        BlendMode::Difference => tiny_skia::BlendMode::Difference,
+        BlendMode::Exclusion => tiny_skia::BlendMode::Exclusion,
+        BlendMode::Hue => tiny_skia::BlendMode::Hue,
+        BlendMode::Saturation => tiny_skia::BlendMode::Saturation,
+        BlendMode::Color => tiny_skia::BlendMode::Color,
+        BlendMode::Luminosity => tiny_skia::BlendMode::Luminosity,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{image_from_pixmap, max_difference, push_pixmap, rasterize_scene};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::vec2f;
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{ClipPath, DrawPath, Scene};
+    use tiny_skia::Pixmap;
+
+    #[test]
+    fn test_rasterize_clipped_rect() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(8.0, 8.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let clip_outline = Outline::from_rect(RectF::new(vec2f(0.0, 0.0), vec2f(4.0, 8.0)));
+        let clip_path = scene.push_clip_path(ClipPath::new(clip_outline));
+        let outline = Outline::from_rect(RectF::new(vec2f(0.0, 0.0), vec2f(8.0, 4.0)));
+        let mut draw_path = DrawPath::new(outline, paint);
+        draw_path.set_clip_path(Some(clip_path));
+        scene.push_draw_path(draw_path);
+
+        let pixmap = rasterize_scene(&scene, None).unwrap();
+        assert_eq!(pixmap.pixel(1, 1).unwrap().red(), 255);
+        assert_eq!(pixmap.pixel(6, 1).unwrap().alpha(), 0);
+        assert_eq!(pixmap.pixel(1, 6).unwrap().alpha(), 0);
+    }
+
+    #[test]
+    fn test_pixmap_round_trip() {
+        let mut pixmap = Pixmap::new(4, 4).unwrap();
+        pixmap.fill(tiny_skia::Color::from_rgba8(0, 0, 255, 128));
+        let image = image_from_pixmap(pixmap.as_ref());
+        assert_eq!(image.pixels()[0], ColorU::new(0, 0, 255, 128));
+
+        // The CPU rasterizer skips pattern paints, so the layer doesn't show up there.
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(4.0, 4.0)));
+        push_pixmap(&mut scene, pixmap.as_ref(), &Transform2F::default());
+        assert_eq!(scene.draw_path_count(), 1);
+        let blank = rasterize_scene(&scene, None).unwrap();
+        assert_eq!(max_difference(blank.as_ref(), Pixmap::new(4, 4).unwrap().as_ref()), Some(0));
+        assert_eq!(max_difference(blank.as_ref(), pixmap.as_ref()), Some(128));
+        assert_eq!(max_difference(blank.as_ref(), Pixmap::new(2, 2).unwrap().as_ref()), None);
+    }
+}
@@ -0,0 +1,265 @@
+// pathfinder/headless/src/screenshot.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Capturing rendered frames together with a description of the scene that produced them.
+//!
+//! A `Screenshot` holds the frame and `ScreenshotMetadata`: the size of the scene, the view
+//! transform, the time each stage of the frame took, and the bounds of each layer. Saving one
+//! writes the image and a JSON sidecar next to it, for attaching to bug reports or triaging
+//! failures of visual tests.
+//!
+//! ```ignore
+//! let screenshot = renderer.capture(&mut scene, BuildOptions::default());
+//! screenshot.save("failure.png")?; // Also writes `failure.json`.
+//! ```
+
+use crate::animation::to_io_error;
+use crate::HeadlessRenderer;
+use image::RgbaImage;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::Vector2I;
+use pathfinder_renderer::gpu::perf::FrameStats;
+use pathfinder_renderer::options::{BuildOptions, RenderTransform};
+use pathfinder_renderer::scene::{DrawPathId, Scene};
+use serde_json::{Value, json};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// A rendered frame and a description of the scene it shows.
+pub struct Screenshot {
+    /// The rendered frame, with straight alpha.
+    pub image: RgbaImage,
+    /// A description of the scene and of how it was rendered.
+    pub metadata: ScreenshotMetadata,
+}
+
+/// A description of a rendered scene, for the sidecar of a `Screenshot`.
+#[derive(Clone, Debug)]
+pub struct ScreenshotMetadata {
+    /// The size of the frame, in device pixels.
+    pub size: Vector2I,
+    /// The transform from scene units to device pixels.
+    pub view_transform: RenderTransform,
+    /// The number of draw paths in the scene, including hidden ones.
+    pub draw_path_count: u32,
+    /// The number of points in the outlines of all draw paths.
+    pub point_count: usize,
+    /// The rectangle that encloses everything in the scene, in scene units.
+    pub scene_bounds: RectF,
+    /// The view box of the scene, in scene units.
+    pub view_box: RectF,
+    /// The time each stage of rendering took.
+    pub frame_stats: FrameStats,
+    /// The layers of the scene, from bottom to top.
+    pub layers: Vec<LayerMetadata>,
+}
+
+/// A description of a layer, for `ScreenshotMetadata`.
+#[derive(Clone, Debug)]
+pub struct LayerMetadata {
+    /// The name the layer was created with.
+    pub name: String,
+    /// True if the layer is shown.
+    pub visible: bool,
+    /// The opacity of the layer, from 0.0 to 1.0.
+    pub opacity: f32,
+    /// The bounds of the layer's paths in scene units, or `None` if it has none.
+    pub bounds: Option<RectF>,
+    /// The bounds of the layer's paths in device pixels, or `None` if it has none or the view
+    /// transform is a perspective one.
+    pub device_bounds: Option<RectF>,
+}
+
+impl HeadlessRenderer {
+    /// Builds and renders the scene, and returns the frame with a description of the scene.
+    pub fn capture(&mut self, scene: &mut Scene, options: BuildOptions) -> Screenshot {
+        let view_transform = options.transform.clone();
+        let image = self.render_with_options(scene, options);
+        let frame_stats = self.renderer().frame_stats();
+        let metadata = ScreenshotMetadata::new(scene, view_transform, self.size(), frame_stats);
+        Screenshot { image, metadata }
+    }
+}
+
+impl Screenshot {
+    /// Writes the image to `path`, in the format given by its extension, and the metadata as
+    /// JSON to the same path with the extension `.json`.
+    pub fn save<P>(&self, path: P) -> io::Result<()> where P: AsRef<Path> {
+        let path = path.as_ref();
+        if path.extension().and_then(|extension| extension.to_str()) == Some("json") {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "screenshot filename can't have .json extension"));
+        }
+        self.image.save(path).map_err(to_io_error)?;
+        fs::write(path.with_extension("json"), self.metadata.to_json())
+    }
+}
+
+impl ScreenshotMetadata {
+    /// Describes a scene that was rendered into a frame of `size` device pixels.
+    pub fn new(scene: &Scene,
+               view_transform: RenderTransform,
+               size: Vector2I,
+               frame_stats: FrameStats)
+               -> ScreenshotMetadata {
+        let point_count = (0..scene.draw_path_count()).map(|index| {
+            let outline = &scene.get_draw_path(DrawPathId(index)).outline;
+            outline.contours().iter().map(|contour| contour.len() as usize).sum::<usize>()
+        }).sum();
+
+        let layers = scene.layer_order().iter().map(|&layer_id| {
+            let bounds = scene.layer_bounds(layer_id);
+            let device_bounds = match view_transform {
+                RenderTransform::Transform2D(ref transform) => {
+                    bounds.map(|bounds| *transform * bounds)
+                }
+                RenderTransform::Perspective(_) => None,
+            };
+            LayerMetadata {
+                name: scene.layer_name(layer_id).to_owned(),
+                visible: scene.layer_visible(layer_id),
+                opacity: scene.layer_opacity(layer_id),
+                bounds,
+                device_bounds,
+            }
+        }).collect();
+
+        ScreenshotMetadata {
+            size,
+            view_transform,
+            draw_path_count: scene.draw_path_count(),
+            point_count,
+            scene_bounds: scene.bounds(),
+            view_box: scene.view_box(),
+            frame_stats,
+            layers,
+        }
+    }
+
+    /// Returns the metadata as pretty-printed JSON.
+    ///
+    /// Rectangles are `[x, y, width, height]`, and times are in milliseconds. A 2D view
+    /// transform is a row-major `[m11, m12, m13, m21, m22, m23]` matrix; a perspective one is a
+    /// column-major 4x4 matrix with the window size.
+    pub fn to_json(&self) -> String {
+        let view_transform = match self.view_transform {
+            RenderTransform::Transform2D(ref transform) => {
+                json!({ "2d": transform_to_json(transform) })
+            }
+            RenderTransform::Perspective(ref perspective) => {
+                let columns = perspective.transform.to_columns();
+                let matrix: Vec<f32> = columns.iter().flat_map(|column| {
+                    vec![column[0], column[1], column[2], column[3]]
+                }).collect();
+                json!({
+                    "perspective": {
+                        "matrix": matrix,
+                        "window_size": [perspective.window_size.x(), perspective.window_size.y()],
+                    }
+                })
+            }
+        };
+
+        let layers: Vec<Value> = self.layers.iter().map(|layer| {
+            json!({
+                "name": layer.name,
+                "visible": layer.visible,
+                "opacity": layer.opacity,
+                "bounds": layer.bounds.map(rect_to_json),
+                "device_bounds": layer.device_bounds.map(rect_to_json),
+            })
+        }).collect();
+
+        let stats = &self.frame_stats;
+        let value = json!({
+            "size": [self.size.x(), self.size.y()],
+            "view_transform": view_transform,
+            "scene": {
+                "draw_path_count": self.draw_path_count,
+                "point_count": self.point_count,
+                "bounds": rect_to_json(self.scene_bounds),
+                "view_box": rect_to_json(self.view_box),
+            },
+            "frame_stats": {
+                "scene_build_ms": duration_to_ms(stats.scene_build_time),
+                "stroke_ms": duration_to_ms(stats.stroke_time),
+                "tile_ms": duration_to_ms(stats.tile_time),
+                "batch_ms": duration_to_ms(stats.batch_time),
+                "encode_ms": duration_to_ms(stats.encode_time),
+                "total_ms": duration_to_ms(stats.total_time()),
+            },
+            "layers": layers,
+        });
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+}
+
+fn rect_to_json(rect: RectF) -> Value {
+    json!([rect.origin_x(), rect.origin_y(), rect.width(), rect.height()])
+}
+
+fn transform_to_json(transform: &Transform2F) -> Value {
+    json!([transform.m11(), transform.m12(), transform.m13(),
+           transform.m21(), transform.m22(), transform.m23()])
+}
+
+fn duration_to_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::ScreenshotMetadata;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::{vec2f, vec2i};
+    use pathfinder_renderer::gpu::perf::FrameStats;
+    use pathfinder_renderer::options::RenderTransform;
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{DrawPath, Scene};
+    use serde_json::{Value, json};
+
+    #[test]
+    fn test_metadata_describes_layers() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(100.0, 50.0)));
+        let paint = scene.push_paint(&Paint::black());
+        let square = Outline::from_rect(RectF::new(vec2f(10.0, 10.0), vec2f(20.0, 20.0)));
+        scene.push_draw_path(DrawPath::new(square.clone(), paint));
+        let overlay = scene.push_layer("overlay".to_owned());
+        scene.push_draw_path(DrawPath::new(square.transformed(&Transform2F::from_scale(2.0)),
+                                           paint));
+        scene.pop_layer();
+        scene.set_layer_opacity(overlay, 0.5);
+
+        let transform = RenderTransform::Transform2D(Transform2F::from_scale(2.0));
+        let metadata = ScreenshotMetadata::new(&scene,
+                                               transform,
+                                               vec2i(200, 100),
+                                               FrameStats::default());
+        assert_eq!(metadata.draw_path_count, 2);
+        assert_eq!(metadata.point_count, 8);
+
+        let json: Value = serde_json::from_str(&metadata.to_json()).unwrap();
+        assert_eq!(json["size"], json!([200, 100]));
+        assert_eq!(json["view_transform"]["2d"], json!([2.0, 0.0, 0.0, 0.0, 2.0, 0.0]));
+        assert_eq!(json["scene"]["view_box"], json!([0.0, 0.0, 100.0, 50.0]));
+        assert_eq!(json["layers"].as_array().unwrap().len(), 1);
+        let layer = &json["layers"][0];
+        assert_eq!(layer["name"], "overlay");
+        assert_eq!(layer["opacity"], 0.5);
+        assert_eq!(layer["bounds"], json!([20.0, 20.0, 40.0, 40.0]));
+        assert_eq!(layer["device_bounds"], json!([40.0, 40.0, 80.0, 80.0]));
+    }
+}
@@ -0,0 +1,1006 @@
+// pathfinder/pdf/src/content.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Runs content streams, adding what they paint to a scene.
+
+use crate::BuildResultFlags;
+use crate::document::PDFDocument;
+use crate::font::Font;
+use crate::object::{Dict, Object, ObjectId};
+use crate::parser::{ContentItem, Parser};
+use pathfinder_color::ColorF;
+use pathfinder_content::dash::OutlineDash;
+use pathfinder_content::effects::BlendMode;
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{ClipPath, ClipPathId, DrawPath, Scene};
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+
+// Form XObjects that nest deeper than this are skipped, which also stops forms that draw
+// themselves.
+const MAX_FORM_DEPTH: u32 = 12;
+const MAX_COLOR_SPACE_DEPTH: u32 = 8;
+
+pub(crate) struct ContentInterpreter<'a> {
+    document: &'a PDFDocument,
+    pub(crate) scene: Scene,
+    pub(crate) result_flags: BuildResultFlags,
+    operands: Vec<Object>,
+    state: State,
+    state_stack: Vec<State>,
+    // The current path, in scene space. The path isn't part of the graphics state.
+    path: Outline,
+    contour: Contour,
+    // The current point and the start of the current subpath, in user space.
+    current_point: Option<Vector2F>,
+    subpath_start: Vector2F,
+    pending_clip: Option<FillRule>,
+    // The text matrix and text line matrix, valid between `BT` and `ET`.
+    text_matrix: Transform2F,
+    line_matrix: Transform2F,
+    // Glyphs shown with a clipping text rendering mode, in scene space.
+    text_clip: Option<Outline>,
+    fonts: HashMap<ObjectId, Rc<Font>>,
+    form_depth: u32,
+}
+
+#[derive(Clone)]
+struct State {
+    // The current transformation matrix, from user space to scene space.
+    transform: Transform2F,
+    fill_space: ColorSpace,
+    stroke_space: ColorSpace,
+    // `None` when painting with a pattern, which isn't supported.
+    fill_color: Option<ColorF>,
+    stroke_color: Option<ColorF>,
+    fill_alpha: f32,
+    stroke_alpha: f32,
+    blend_mode: BlendMode,
+    line_width: f32,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    miter_limit: f32,
+    dash: Vec<f32>,
+    dash_offset: f32,
+    clip_path: Option<ClipPathId>,
+    font: Option<Rc<Font>>,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scale: f32,
+    leading: f32,
+    rise: f32,
+    render_mode: i64,
+}
+
+#[derive(Clone, Debug)]
+enum ColorSpace {
+    Gray,
+    RGB,
+    CMYK,
+    Indexed { base: Box<ColorSpace>, high_value: usize, lookup: Rc<Vec<u8>> },
+    // Separation and DeviceN colors, approximated as gray by the amount of ink.
+    Tint(usize),
+    Pattern,
+}
+
+impl<'a> ContentInterpreter<'a> {
+    pub(crate) fn new(document: &'a PDFDocument, scene: Scene, transform: Transform2F)
+                      -> ContentInterpreter<'a> {
+        ContentInterpreter {
+            document,
+            scene,
+            result_flags: BuildResultFlags::empty(),
+            operands: vec![],
+            state: State::new(transform),
+            state_stack: vec![],
+            path: Outline::new(),
+            contour: Contour::new(),
+            current_point: None,
+            subpath_start: Vector2F::zero(),
+            pending_clip: None,
+            text_matrix: Transform2F::default(),
+            line_matrix: Transform2F::default(),
+            text_clip: None,
+            fonts: HashMap::new(),
+            form_depth: 0,
+        }
+    }
+
+    /// Runs a content stream. Like other readers, we skip malformed operators and keep going,
+    /// noting `INVALID_CONTENT` in the result flags.
+    pub(crate) fn run(&mut self, content: &[u8], resources: &Dict) {
+        let state_depth = self.state_stack.len();
+        let mut parser = Parser::new(content, 0);
+        loop {
+            let item = match parser.next_content_item() {
+                Ok(Some(item)) => item,
+                Ok(None) => break,
+                Err(_) => {
+                    self.result_flags.insert(BuildResultFlags::INVALID_CONTENT);
+                    break;
+                }
+            };
+            match item {
+                ContentItem::Operand(operand) => self.operands.push(operand),
+                ContentItem::Operator(b"BI") => {
+                    self.result_flags.insert(BuildResultFlags::UNSUPPORTED_IMAGE);
+                    if parser.skip_inline_image().is_err() {
+                        self.result_flags.insert(BuildResultFlags::INVALID_CONTENT);
+                        break;
+                    }
+                }
+                ContentItem::Operator(operator) => {
+                    if self.operator(operator, resources).is_none() {
+                        self.result_flags.insert(BuildResultFlags::INVALID_CONTENT);
+                    }
+                    self.operands.clear();
+                }
+            }
+        }
+
+        // Unbalanced `q` operators are implicitly closed.
+        self.restore_state_to(state_depth);
+    }
+
+    fn operator(&mut self, operator: &[u8], resources: &Dict) -> Option<()> {
+        match operator {
+            // Graphics state
+            b"q" => self.state_stack.push(self.state.clone()),
+            b"Q" => {
+                if let Some(state) = self.state_stack.pop() {
+                    self.state = state;
+                }
+            }
+            b"cm" => {
+                let transform = self.transform_operand()?;
+                self.state.transform = self.state.transform * transform;
+            }
+            b"w" => self.state.line_width = self.number(0)?,
+            b"J" => self.state.line_cap = line_cap(self.number(0)?),
+            b"j" => self.state.line_join = line_join(self.number(0)?, self.state.miter_limit),
+            b"M" => {
+                self.state.miter_limit = self.number(0)?;
+                if let LineJoin::Miter(_) = self.state.line_join {
+                    self.state.line_join = LineJoin::Miter(self.state.miter_limit);
+                }
+            }
+            b"d" => {
+                let count = self.operands.len();
+                let dash = self.operands.get(count.checked_sub(2)?)?.as_numbers()?;
+                self.state.dash = dash;
+                self.state.dash_offset = self.number(0)?;
+            }
+            b"gs" => {
+                let name = self.operands.last()?.as_name()?.to_owned();
+                let ext_g_state = self.resource(resources, "ExtGState", &name);
+                self.set_ext_g_state(ext_g_state.as_dict()?);
+            }
+            b"ri" | b"i" => {}
+
+            // Path construction
+            b"m" => {
+                let point = self.point_operand(0)?;
+                self.move_to(point);
+            }
+            b"l" => {
+                let point = self.point_operand(0)?;
+                self.line_to(point);
+            }
+            b"c" => {
+                let (ctrl0, ctrl1) = (self.point_operand(2)?, self.point_operand(1)?);
+                let to = self.point_operand(0)?;
+                self.curve_to(ctrl0, ctrl1, to);
+            }
+            b"v" => {
+                let (ctrl1, to) = (self.point_operand(1)?, self.point_operand(0)?);
+                let ctrl0 = self.current_point.unwrap_or(ctrl1);
+                self.curve_to(ctrl0, ctrl1, to);
+            }
+            b"y" => {
+                let (ctrl0, to) = (self.point_operand(1)?, self.point_operand(0)?);
+                self.curve_to(ctrl0, to, to);
+            }
+            b"h" => self.close_path(),
+            b"re" => {
+                let (origin, size) = (self.point_operand(1)?, self.point_operand(0)?);
+                self.move_to(origin);
+                self.line_to(origin + vec2f(size.x(), 0.0));
+                self.line_to(origin + size);
+                self.line_to(origin + vec2f(0.0, size.y()));
+                self.close_path();
+            }
+
+            // Path painting
+            b"S" => self.paint_path(None, true, false),
+            b"s" => {
+                self.close_path();
+                self.paint_path(None, true, false);
+            }
+            b"f" | b"F" => self.paint_path(Some(FillRule::Winding), false, false),
+            b"f*" => self.paint_path(Some(FillRule::EvenOdd), false, false),
+            b"B" => self.paint_path(Some(FillRule::Winding), true, false),
+            b"B*" => self.paint_path(Some(FillRule::EvenOdd), true, false),
+            b"b" => self.paint_path(Some(FillRule::Winding), true, true),
+            b"b*" => self.paint_path(Some(FillRule::EvenOdd), true, true),
+            b"n" => self.paint_path(None, false, false),
+            b"W" => self.pending_clip = Some(FillRule::Winding),
+            b"W*" => self.pending_clip = Some(FillRule::EvenOdd),
+
+            // Color
+            b"g" | b"G" | b"rg" | b"RG" | b"k" | b"K" => {
+                let (space, count) = match operator[0] {
+                    b'g' | b'G' => (ColorSpace::Gray, 1),
+                    b'r' | b'R' => (ColorSpace::RGB, 3),
+                    _ => (ColorSpace::CMYK, 4),
+                };
+                let components = self.numbers(count)?;
+                let color = space.color(&components);
+                if operator[0].is_ascii_lowercase() {
+                    self.state.fill_space = space;
+                    self.state.fill_color = color;
+                } else {
+                    self.state.stroke_space = space;
+                    self.state.stroke_color = color;
+                }
+            }
+            b"cs" | b"CS" => {
+                let operand = self.operands.last()?.clone();
+                let space = self.color_space(&operand, resources, 0);
+                let color = space.initial_color();
+                if operator == b"cs" {
+                    self.state.fill_space = space;
+                    self.state.fill_color = color;
+                } else {
+                    self.state.stroke_space = space;
+                    self.state.stroke_color = color;
+                }
+            }
+            b"sc" | b"scn" | b"SC" | b"SCN" => {
+                let fill = operator[0] == b's';
+                let space = if fill { &self.state.fill_space } else { &self.state.stroke_space };
+                let components: Vec<f32> = self.operands
+                                                .iter()
+                                                .filter_map(Object::as_f32)
+                                                .collect();
+                let color = match *space {
+                    ColorSpace::Pattern => None,
+                    ref space => space.color(&components),
+                };
+                if color.is_none() {
+                    self.result_flags.insert(BuildResultFlags::UNSUPPORTED_PATTERN);
+                }
+                if fill {
+                    self.state.fill_color = color;
+                } else {
+                    self.state.stroke_color = color;
+                }
+            }
+
+            // Text objects and state
+            b"BT" => {
+                self.text_matrix = Transform2F::default();
+                self.line_matrix = Transform2F::default();
+            }
+            b"ET" => {
+                if let Some(outline) = self.text_clip.take() {
+                    self.clip(outline, FillRule::Winding);
+                }
+            }
+            b"Tc" => self.state.char_spacing = self.number(0)?,
+            b"Tw" => self.state.word_spacing = self.number(0)?,
+            b"Tz" => self.state.horizontal_scale = self.number(0)? / 100.0,
+            b"TL" => self.state.leading = self.number(0)?,
+            b"Ts" => self.state.rise = self.number(0)?,
+            b"Tr" => self.state.render_mode = self.number(0)? as i64,
+            b"Tf" => {
+                let count = self.operands.len();
+                let name = self.operands.get(count.checked_sub(2)?)?.as_name()?.to_owned();
+                self.state.font_size = self.number(0)?;
+                self.state.font = self.font(resources, &name);
+            }
+
+            // Text positioning
+            b"Td" => {
+                let offset = self.point_operand(0)?;
+                self.next_line(offset);
+            }
+            b"TD" => {
+                let offset = self.point_operand(0)?;
+                self.state.leading = -offset.y();
+                self.next_line(offset);
+            }
+            b"Tm" => {
+                self.line_matrix = self.transform_operand()?;
+                self.text_matrix = self.line_matrix;
+            }
+            b"T*" => self.next_line(vec2f(0.0, -self.state.leading)),
+
+            // Text showing
+            b"Tj" => {
+                let string = self.operands.last()?.as_string()?.to_vec();
+                self.show_text(&[Object::String(Rc::new(string))]);
+            }
+            b"TJ" => {
+                let array = self.operands.last()?.as_array()?.to_vec();
+                self.show_text(&array);
+            }
+            b"'" => {
+                let string = self.operands.last()?.as_string()?.to_vec();
+                self.next_line(vec2f(0.0, -self.state.leading));
+                self.show_text(&[Object::String(Rc::new(string))]);
+            }
+            b"\"" => {
+                let string = self.operands.last()?.as_string()?.to_vec();
+                self.state.word_spacing = self.number(2)?;
+                self.state.char_spacing = self.number(1)?;
+                self.next_line(vec2f(0.0, -self.state.leading));
+                self.show_text(&[Object::String(Rc::new(string))]);
+            }
+
+            // XObjects, shadings, and Type 3 glyph metrics
+            b"Do" => {
+                let name = self.operands.last()?.as_name()?.to_owned();
+                let xobject = self.resource(resources, "XObject", &name);
+                self.draw_xobject(&xobject, resources);
+            }
+            b"sh" => self.result_flags.insert(BuildResultFlags::UNSUPPORTED_SHADING),
+            b"d0" | b"d1" => {}
+
+            // Marked content and compatibility sections
+            b"MP" | b"DP" | b"BMC" | b"BDC" | b"EMC" | b"BX" | b"EX" => {}
+
+            _ => self.result_flags.insert(BuildResultFlags::UNSUPPORTED_OPERATOR),
+        }
+        Some(())
+    }
+
+    // Operands
+
+    // Returns the number `index` places from the top of the operand stack.
+    fn number(&self, index: usize) -> Option<f32> {
+        let count = self.operands.len();
+        self.operands.get(count.checked_sub(index + 1)?)?.as_f32()
+    }
+
+    // Returns the topmost `count` operands, which must all be numbers, bottom first.
+    fn numbers(&self, count: usize) -> Option<Vec<f32>> {
+        let start = self.operands.len().checked_sub(count)?;
+        self.operands[start..].iter().map(Object::as_f32).collect()
+    }
+
+    // Returns a point made from the pair of numbers `index` pairs from the top of the stack.
+    fn point_operand(&self, index: usize) -> Option<Vector2F> {
+        Some(vec2f(self.number(index * 2 + 1)?, self.number(index * 2)?))
+    }
+
+    fn transform_operand(&self) -> Option<Transform2F> {
+        transform_from_numbers(&self.numbers(6)?)
+    }
+
+    // Path construction
+
+    fn move_to(&mut self, point: Vector2F) {
+        self.flush_contour();
+        self.contour.push_endpoint(self.state.transform * point);
+        self.current_point = Some(point);
+        self.subpath_start = point;
+    }
+
+    fn line_to(&mut self, point: Vector2F) {
+        self.start_segment();
+        self.contour.push_endpoint(self.state.transform * point);
+        self.current_point = Some(point);
+    }
+
+    fn curve_to(&mut self, ctrl0: Vector2F, ctrl1: Vector2F, to: Vector2F) {
+        self.start_segment();
+        let transform = self.state.transform;
+        self.contour.push_cubic(transform * ctrl0, transform * ctrl1, transform * to);
+        self.current_point = Some(to);
+    }
+
+    // Begins a new subpath at the current point if the last one was closed.
+    fn start_segment(&mut self) {
+        if self.contour.is_empty() {
+            let point = self.current_point.unwrap_or(self.subpath_start);
+            self.contour.push_endpoint(self.state.transform * point);
+            self.subpath_start = point;
+        }
+    }
+
+    fn close_path(&mut self) {
+        if !self.contour.is_empty() {
+            self.contour.close();
+            self.flush_contour();
+            self.current_point = Some(self.subpath_start);
+        }
+    }
+
+    fn flush_contour(&mut self) {
+        if !self.contour.is_empty() {
+            self.path.push_contour(mem::replace(&mut self.contour, Contour::new()));
+        }
+    }
+
+    // Paints the current path, applies any pending clip, and ends the path.
+    fn paint_path(&mut self, fill_rule: Option<FillRule>, stroke: bool, close: bool) {
+        if close {
+            self.close_path();
+        }
+        self.flush_contour();
+        let path = mem::replace(&mut self.path, Outline::new());
+        self.current_point = None;
+
+        if let Some(fill_rule) = fill_rule {
+            let mut outline = path.clone();
+            outline.close_all_contours();
+            self.fill(outline, fill_rule);
+        }
+        if stroke {
+            let outline = self.stroke_outline(path.clone());
+            self.push_draw_path(outline, FillRule::Winding, true);
+        }
+        if let Some(clip_rule) = self.pending_clip.take() {
+            self.clip(path, clip_rule);
+        }
+    }
+
+    // Painting
+
+    fn fill(&mut self, outline: Outline, fill_rule: FillRule) {
+        self.push_draw_path(outline, fill_rule, false);
+    }
+
+    // Converts a path to the outline of its stroke, using the current line style.
+    fn stroke_outline(&self, outline: Outline) -> Outline {
+        let state = &self.state;
+
+        // Line widths and dashes are measured in user space, so stroke there. A zero width asks
+        // for the thinnest line the device can draw, which we take to be one scene unit.
+        let (mut outline, transform, line_width) =
+            if state.line_width > 0.0 && state.transform.matrix.det() != 0.0 {
+                (outline.transformed(&state.transform.inverse()),
+                 state.transform,
+                 state.line_width)
+            } else {
+                (outline, Transform2F::default(), 1.0)
+            };
+
+        let dash_length: f32 = state.dash.iter().sum();
+        if dash_length > 0.0 && state.dash.iter().all(|&length| length >= 0.0) {
+            let mut dash = OutlineDash::new(&outline, &state.dash, state.dash_offset);
+            dash.dash();
+            outline = dash.into_outline();
+        }
+
+        let style = StrokeStyle {
+            line_width,
+            line_cap: state.line_cap,
+            line_join: state.line_join,
+        };
+        let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, style);
+        stroke_to_fill.offset();
+        stroke_to_fill.into_outline().transformed(&transform)
+    }
+
+    fn push_draw_path(&mut self, outline: Outline, fill_rule: FillRule, stroke: bool) {
+        if outline.is_empty() {
+            return;
+        }
+        let (color, alpha) = if stroke {
+            (self.state.stroke_color, self.state.stroke_alpha)
+        } else {
+            (self.state.fill_color, self.state.fill_alpha)
+        };
+        // Patterns aren't supported, so there's nothing to paint with.
+        let color = match color {
+            Some(color) => color,
+            None => return,
+        };
+        let color = ColorF::new(color.r(), color.g(), color.b(), alpha.max(0.0).min(1.0));
+        let paint = self.scene.push_paint(&Paint::from_color(color.to_u8()));
+        let mut path = DrawPath::new(outline, paint);
+        path.set_clip_path(self.state.clip_path);
+        path.set_fill_rule(fill_rule);
+        path.set_blend_mode(self.state.blend_mode);
+        self.scene.push_draw_path(path);
+    }
+
+    fn clip(&mut self, mut outline: Outline, fill_rule: FillRule) {
+        outline.close_all_contours();
+        let mut clip_path = ClipPath::new(outline);
+        clip_path.set_clip_path(self.state.clip_path);
+        clip_path.set_fill_rule(fill_rule);
+        self.state.clip_path = Some(self.scene.push_clip_path(clip_path));
+    }
+
+    fn restore_state_to(&mut self, level: usize) {
+        if let Some(state) = self.state_stack.drain(level..).next() {
+            self.state = state;
+        }
+    }
+
+    // Graphics state parameter dictionaries
+
+    fn set_ext_g_state(&mut self, dict: &Dict) {
+        let document = self.document;
+        for (key, value) in dict {
+            let value = document.resolve(value);
+            match &**key {
+                "LW" => self.state.line_width = value.as_f32().unwrap_or(self.state.line_width),
+                "LC" => self.state.line_cap = line_cap(value.as_f32().unwrap_or(0.0)),
+                "LJ" => {
+                    let miter_limit = self.state.miter_limit;
+                    self.state.line_join = line_join(value.as_f32().unwrap_or(0.0), miter_limit);
+                }
+                "ML" => {
+                    self.state.miter_limit = value.as_f32().unwrap_or(self.state.miter_limit);
+                    if let LineJoin::Miter(_) = self.state.line_join {
+                        self.state.line_join = LineJoin::Miter(self.state.miter_limit);
+                    }
+                }
+                "D" => {
+                    let dash = value.as_array().unwrap_or(&[]);
+                    if let (Some(pattern), Some(phase)) = (dash.get(0), dash.get(1)) {
+                        self.state.dash = document.resolve(pattern).as_numbers().unwrap_or(vec![]);
+                        self.state.dash_offset = document.resolve(phase).as_f32().unwrap_or(0.0);
+                    }
+                }
+                "CA" => self.state.stroke_alpha = value.as_f32().unwrap_or(1.0),
+                "ca" => self.state.fill_alpha = value.as_f32().unwrap_or(1.0),
+                "BM" => {
+                    // An array lists blend modes in order of preference.
+                    let name = match value {
+                        Object::Array(ref names) => names.first().cloned().unwrap_or(Object::Null),
+                        value => value,
+                    };
+                    match name.as_name().and_then(blend_mode) {
+                        Some(mode) => self.state.blend_mode = mode,
+                        None => self.result_flags.insert(BuildResultFlags::UNSUPPORTED_OPERATOR),
+                    }
+                }
+                "SMask" => {
+                    if value.as_name() != Some("None") {
+                        self.result_flags.insert(BuildResultFlags::UNSUPPORTED_SOFT_MASK);
+                    }
+                }
+                "Font" => {
+                    let font = value.as_array().unwrap_or(&[]);
+                    if let (Some(font), Some(size)) = (font.get(0), font.get(1)) {
+                        self.state.font = self.load_font(font);
+                        self.state.font_size = document.resolve(size).as_f32().unwrap_or(0.0);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Color spaces
+
+    // Named color spaces may refer to others; `depth` guards against cycles.
+    fn color_space(&mut self, object: &Object, resources: &Dict, depth: u32) -> ColorSpace {
+        if depth > MAX_COLOR_SPACE_DEPTH {
+            self.result_flags.insert(BuildResultFlags::INVALID_CONTENT);
+            return ColorSpace::Gray;
+        }
+        let object = self.document.resolve(object);
+        let (family, array) = match object {
+            Object::Name(ref name) => {
+                match &**name {
+                    "DeviceGray" | "G" | "CalGray" => return ColorSpace::Gray,
+                    "DeviceRGB" | "RGB" | "CalRGB" => return ColorSpace::RGB,
+                    "DeviceCMYK" | "CMYK" => return ColorSpace::CMYK,
+                    "Pattern" => return ColorSpace::Pattern,
+                    _ => {}
+                }
+                match self.resource(resources, "ColorSpace", name) {
+                    Object::Null => {
+                        self.result_flags.insert(BuildResultFlags::INVALID_CONTENT);
+                        return ColorSpace::Gray;
+                    }
+                    resource => return self.color_space(&resource, resources, depth + 1),
+                }
+            }
+            Object::Array(ref array) => {
+                let family = array.first()
+                                  .map(|family| self.document.resolve(family))
+                                  .and_then(|family| family.as_name().map(str::to_owned));
+                (family, array.clone())
+            }
+            _ => (None, Rc::new(vec![])),
+        };
+
+        let operand = |index: usize| array.get(index).map(|operand| self.document.resolve(operand));
+        match family.as_ref().map(String::as_str) {
+            Some("CalGray") => ColorSpace::Gray,
+            Some("CalRGB") => ColorSpace::RGB,
+            Some("ICCBased") => {
+                let components = operand(1).and_then(|stream| {
+                    self.document.get(stream.as_dict()?, "N").as_integer()
+                });
+                match components {
+                    Some(1) => ColorSpace::Gray,
+                    Some(4) => ColorSpace::CMYK,
+                    _ => ColorSpace::RGB,
+                }
+            }
+            Some("Indexed") | Some("I") => {
+                let base = operand(1).unwrap_or(Object::Null);
+                let high_value = operand(2).and_then(|value| value.as_integer()).unwrap_or(0);
+                let lookup = match operand(3) {
+                    Some(Object::String(string)) => string,
+                    Some(Object::Stream(stream)) => {
+                        Rc::new(self.document.decode_stream(&stream).unwrap_or(vec![]))
+                    }
+                    _ => Rc::new(vec![]),
+                };
+                let base = self.color_space(&base, resources, depth + 1);
+                ColorSpace::Indexed {
+                    base: Box::new(base),
+                    high_value: high_value.max(0) as usize,
+                    lookup,
+                }
+            }
+            Some("Separation") => ColorSpace::Tint(1),
+            Some("DeviceN") => {
+                let names = operand(1);
+                ColorSpace::Tint(names.as_ref().and_then(Object::as_array).map_or(1, |names| {
+                    names.len()
+                }))
+            }
+            Some("Pattern") => ColorSpace::Pattern,
+            Some("Lab") => {
+                self.result_flags.insert(BuildResultFlags::UNSUPPORTED_COLOR_SPACE);
+                ColorSpace::Tint(3)
+            }
+            _ => {
+                self.result_flags.insert(BuildResultFlags::UNSUPPORTED_COLOR_SPACE);
+                ColorSpace::Gray
+            }
+        }
+    }
+
+    // Resources
+
+    fn resource(&self, resources: &Dict, category: &str, name: &str) -> Object {
+        match self.document.get(resources, category).as_dict() {
+            Some(category) => self.document.get(category, name),
+            None => Object::Null,
+        }
+    }
+
+    fn font(&mut self, resources: &Dict, name: &str) -> Option<Rc<Font>> {
+        let font = self.document.get(resources, "Font");
+        let font = font.as_dict()?.get(name)?.clone();
+        self.load_font(&font)
+    }
+
+    // Loads a font, sharing fonts that are indirect objects.
+    fn load_font(&mut self, font: &Object) -> Option<Rc<Font>> {
+        let id = match *font {
+            Object::Reference(id) => Some(id),
+            _ => None,
+        };
+        if let Some(font) = id.and_then(|id| self.fonts.get(&id)) {
+            return Some(font.clone());
+        }
+
+        let dict = self.document.resolve(font);
+        let font = Rc::new(Font::load(self.document, dict.as_dict()?));
+        if let Some(id) = id {
+            self.fonts.insert(id, font.clone());
+        }
+        Some(font)
+    }
+
+    // Text
+
+    fn next_line(&mut self, offset: Vector2F) {
+        self.line_matrix = self.line_matrix * Transform2F::from_translation(offset);
+        self.text_matrix = self.line_matrix;
+    }
+
+    // Shows strings, which may be interspersed with position adjustments as in `TJ`.
+    fn show_text(&mut self, items: &[Object]) {
+        let font = match self.state.font {
+            Some(ref font) => font.clone(),
+            None => {
+                self.result_flags.insert(BuildResultFlags::INVALID_CONTENT);
+                return;
+            }
+        };
+        if !font.is_supported() {
+            self.result_flags.insert(BuildResultFlags::UNSUPPORTED_FONT);
+        }
+
+        let state = &self.state;
+        let (font_size, horizontal_scale) = (state.font_size, state.horizontal_scale);
+        let text_space = Transform2F::row_major(font_size * horizontal_scale, 0.0, 0.0,
+                                                0.0, font_size, state.rise);
+        let mut outline = Outline::new();
+        for item in items {
+            let string = match *item {
+                Object::String(ref string) => string,
+                ref adjustment => {
+                    // Adjustments are in thousandths of text space units, against the writing
+                    // direction.
+                    let adjustment = -adjustment.as_f32().unwrap_or(0.0) / 1000.0 * font_size;
+                    let offset = if font.is_vertical() {
+                        vec2f(0.0, adjustment)
+                    } else {
+                        vec2f(adjustment * horizontal_scale, 0.0)
+                    };
+                    self.text_matrix = self.text_matrix * Transform2F::from_translation(offset);
+                    continue;
+                }
+            };
+
+            for code in font.char_codes(string) {
+                if let Some(glyph) = font.outline(code) {
+                    let transform = self.text_matrix * text_space *
+                        Transform2F::from_translation(font.origin(code));
+                    for contour in glyph.transformed(&transform).contours() {
+                        outline.push_contour(contour.clone());
+                    }
+                }
+
+                let mut spacing = self.state.char_spacing;
+                if font.is_word_space(code) {
+                    spacing += self.state.word_spacing;
+                }
+                let advance = font.advance(code) * font_size;
+                let offset = if font.is_vertical() {
+                    vec2f(0.0, advance.y() + spacing)
+                } else {
+                    vec2f((advance.x() + spacing) * horizontal_scale, 0.0)
+                };
+                self.text_matrix = self.text_matrix * Transform2F::from_translation(offset);
+            }
+        }
+
+        // The glyphs are in user space; move them to scene space.
+        let outline = outline.transformed(&self.state.transform);
+        let render_mode = self.state.render_mode;
+        if render_mode == 0 || render_mode == 2 || render_mode == 4 || render_mode == 6 {
+            self.fill(outline.clone(), FillRule::Winding);
+        }
+        if render_mode == 1 || render_mode == 2 || render_mode == 5 || render_mode == 6 {
+            let stroke = self.stroke_outline(outline.clone());
+            self.push_draw_path(stroke, FillRule::Winding, true);
+        }
+        if render_mode >= 4 && render_mode <= 7 {
+            let text_clip = self.text_clip.get_or_insert_with(Outline::new);
+            for contour in outline.contours() {
+                text_clip.push_contour(contour.clone());
+            }
+        }
+    }
+
+    // XObjects
+
+    fn draw_xobject(&mut self, xobject: &Object, resources: &Dict) {
+        let stream = match *xobject {
+            Object::Stream(ref stream) => stream.clone(),
+            _ => {
+                self.result_flags.insert(BuildResultFlags::INVALID_CONTENT);
+                return;
+            }
+        };
+        match self.document.get(&stream.dict, "Subtype").as_name() {
+            Some("Form") => {}
+            Some("Image") => {
+                self.result_flags.insert(BuildResultFlags::UNSUPPORTED_IMAGE);
+                return;
+            }
+            _ => return,
+        }
+        if self.form_depth >= MAX_FORM_DEPTH {
+            self.result_flags.insert(BuildResultFlags::INVALID_CONTENT);
+            return;
+        }
+        let content = match self.document.decode_stream(&stream) {
+            Ok(content) => content,
+            Err(_) => {
+                self.result_flags.insert(BuildResultFlags::INVALID_CONTENT);
+                return;
+            }
+        };
+
+        // Forms without resources use those of the page or form that draws them.
+        let form_resources = self.document.get(&stream.dict, "Resources");
+        let form_resources = form_resources.as_dict().unwrap_or(resources).clone();
+
+        self.state_stack.push(self.state.clone());
+        let matrix = self.document.get(&stream.dict, "Matrix");
+        if let Some(matrix) = matrix.as_numbers().and_then(|numbers| {
+            transform_from_numbers(&numbers)
+        }) {
+            self.state.transform = self.state.transform * matrix;
+        }
+        if let Some(bounding_box) = self.document.get_rect(&stream.dict, "BBox") {
+            let outline = Outline::from_rect(bounding_box).transformed(&self.state.transform);
+            self.clip(outline, FillRule::Winding);
+        }
+
+        // The form's path and text state don't leak out.
+        let saved_path = mem::replace(&mut self.path, Outline::new());
+        let saved_contour = mem::replace(&mut self.contour, Contour::new());
+        let saved_text = (self.text_matrix, self.line_matrix, self.text_clip.take());
+        let saved_operands = mem::replace(&mut self.operands, vec![]);
+
+        self.form_depth += 1;
+        self.run(&content, &form_resources);
+        self.form_depth -= 1;
+
+        self.operands = saved_operands;
+        let (text_matrix, line_matrix, text_clip) = saved_text;
+        self.text_matrix = text_matrix;
+        self.line_matrix = line_matrix;
+        self.text_clip = text_clip;
+        self.path = saved_path;
+        self.contour = saved_contour;
+        if let Some(state) = self.state_stack.pop() {
+            self.state = state;
+        }
+    }
+}
+
+impl State {
+    fn new(transform: Transform2F) -> State {
+        State {
+            transform,
+            fill_space: ColorSpace::Gray,
+            stroke_space: ColorSpace::Gray,
+            fill_color: Some(ColorF::black()),
+            stroke_color: Some(ColorF::black()),
+            fill_alpha: 1.0,
+            stroke_alpha: 1.0,
+            blend_mode: BlendMode::SrcOver,
+            line_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter(10.0),
+            miter_limit: 10.0,
+            dash: vec![],
+            dash_offset: 0.0,
+            clip_path: None,
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            render_mode: 0,
+        }
+    }
+}
+
+impl ColorSpace {
+    fn initial_color(&self) -> Option<ColorF> {
+        match *self {
+            ColorSpace::Gray | ColorSpace::RGB | ColorSpace::Indexed { .. } => {
+                Some(ColorF::black())
+            }
+            ColorSpace::CMYK => self.color(&[0.0, 0.0, 0.0, 1.0]),
+            ColorSpace::Tint(count) => self.color(&vec![1.0; count]),
+            ColorSpace::Pattern => None,
+        }
+    }
+
+    // Converts color components to RGB.
+    fn color(&self, components: &[f32]) -> Option<ColorF> {
+        let component = |index: usize| components.get(index).map(|value| value.max(0.0).min(1.0));
+        match *self {
+            ColorSpace::Gray => {
+                let gray = component(0)?;
+                Some(ColorF::new(gray, gray, gray, 1.0))
+            }
+            ColorSpace::RGB => Some(ColorF::new(component(0)?, component(1)?, component(2)?, 1.0)),
+            ColorSpace::CMYK => {
+                let (cyan, magenta) = (component(0)?, component(1)?);
+                let (yellow, black) = (component(2)?, component(3)?);
+                Some(ColorF::new((1.0 - cyan) * (1.0 - black),
+                                 (1.0 - magenta) * (1.0 - black),
+                                 (1.0 - yellow) * (1.0 - black),
+                                 1.0))
+            }
+            ColorSpace::Indexed { ref base, high_value, ref lookup } => {
+                let index = (*components.get(0)?).max(0.0).min(high_value as f32) as usize;
+                let count = base.component_count();
+                let entry = lookup.get((index * count)..((index + 1) * count))?;
+                let entry: Vec<f32> = entry.iter().map(|&value| value as f32 / 255.0).collect();
+                base.color(&entry)
+            }
+            ColorSpace::Tint(count) => {
+                let ink = (0..count).filter_map(component).sum::<f32>() / count.max(1) as f32;
+                let gray = 1.0 - ink.min(1.0);
+                Some(ColorF::new(gray, gray, gray, 1.0))
+            }
+            ColorSpace::Pattern => None,
+        }
+    }
+
+    fn component_count(&self) -> usize {
+        match *self {
+            ColorSpace::Gray | ColorSpace::Indexed { .. } | ColorSpace::Pattern => 1,
+            ColorSpace::RGB => 3,
+            ColorSpace::CMYK => 4,
+            ColorSpace::Tint(count) => count,
+        }
+    }
+}
+
+fn transform_from_numbers(numbers: &[f32]) -> Option<Transform2F> {
+    match *numbers {
+        [a, b, c, d, e, f] => Some(Transform2F::row_major(a, c, e, b, d, f)),
+        _ => None,
+    }
+}
+
+fn line_cap(value: f32) -> LineCap {
+    match value as i32 {
+        1 => LineCap::Round,
+        2 => LineCap::Square,
+        _ => LineCap::Butt,
+    }
+}
+
+fn line_join(value: f32, miter_limit: f32) -> LineJoin {
+    match value as i32 {
+        1 => LineJoin::Round,
+        2 => LineJoin::Bevel,
+        _ => LineJoin::Miter(miter_limit),
+    }
+}
+
+fn blend_mode(name: &str) -> Option<BlendMode> {
+    match name {
+        "Normal" | "Compatible" => Some(BlendMode::SrcOver),
+        "Multiply" => Some(BlendMode::Multiply),
+        "Screen" => Some(BlendMode::Screen),
+        "Overlay" => Some(BlendMode::Overlay),
+        "Darken" => Some(BlendMode::Darken),
+        "Lighten" => Some(BlendMode::Lighten),
+        "ColorDodge" => Some(BlendMode::ColorDodge),
+        "ColorBurn" => Some(BlendMode::ColorBurn),
+        "HardLight" => Some(BlendMode::HardLight),
+        "SoftLight" => Some(BlendMode::SoftLight),
+        "Difference" => Some(BlendMode::Difference),
+        "Exclusion" => Some(BlendMode::Exclusion),
+        "Hue" => Some(BlendMode::Hue),
+        "Saturation" => Some(BlendMode::Saturation),
+        "Color" => Some(BlendMode::Color),
+        "Luminosity" => Some(BlendMode::Luminosity),
+        _ => None,
+    }
+}
+
+/// Returns the transform from a page's default user space to scene space, which puts the upper
+/// left corner of the visible area at the origin with the Y axis pointing down, after rotating
+/// the page clockwise by `rotation` degrees.
+pub(crate) fn page_transform(visible_area: RectF, rotation: i32) -> Transform2F {
+    let (left, right) = (visible_area.min_x(), visible_area.max_x());
+    let (bottom, top) = (visible_area.min_y(), visible_area.max_y());
+    match rotation {
+        90 => Transform2F::row_major(0.0, 1.0, -bottom, 1.0, 0.0, -left),
+        180 => Transform2F::row_major(-1.0, 0.0, right, 0.0, 1.0, -bottom),
+        270 => Transform2F::row_major(0.0, -1.0, top, -1.0, 0.0, right),
+        _ => Transform2F::row_major(1.0, 0.0, -left, 0.0, -1.0, top),
+    }
+}
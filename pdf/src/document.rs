@@ -0,0 +1,604 @@
+// pathfinder/pdf/src/document.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The file structure of PDF documents: cross-reference tables, indirect objects, and the page
+//! tree.
+
+use crate::PDFError;
+use crate::filter;
+use crate::object::{Dict, Object, ObjectId, Stream};
+use crate::parser::{Parser, is_whitespace};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+// Guards against reference cycles and absurdly deep page trees.
+const MAX_RESOLVE_DEPTH: u32 = 32;
+const MAX_PAGE_TREE_DEPTH: u32 = 64;
+
+/// A parsed PDF file.
+///
+/// Objects are loaded lazily as pages are drawn. If the cross-reference table is missing or
+/// damaged, the file is scanned for objects instead, as most readers do.
+pub struct PDFDocument {
+    data: Vec<u8>,
+    xref: HashMap<u32, XrefEntry>,
+    pages: Vec<Page>,
+    objects: RefCell<HashMap<u32, Object>>,
+    object_streams: RefCell<HashMap<u32, Rc<Vec<Object>>>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum XrefEntry {
+    Offset(usize),
+    Compressed { stream: u32, index: usize },
+}
+
+/// A page, with the attributes it inherits from the page tree filled in.
+pub(crate) struct Page {
+    pub(crate) dict: Rc<Dict>,
+    pub(crate) resources: Object,
+    pub(crate) media_box: RectF,
+    pub(crate) crop_box: Option<RectF>,
+    pub(crate) rotation: i32,
+}
+
+impl Page {
+    /// Returns the area of the page that's displayed: the crop box, clipped to the media box.
+    pub(crate) fn visible_area(&self) -> RectF {
+        self.crop_box
+            .and_then(|crop_box| crop_box.intersection(self.media_box))
+            .unwrap_or(self.media_box)
+    }
+}
+
+#[derive(Clone)]
+struct InheritedAttributes {
+    resources: Object,
+    media_box: Option<RectF>,
+    crop_box: Option<RectF>,
+    rotation: i32,
+}
+
+impl PDFDocument {
+    /// Parses a PDF file.
+    ///
+    /// Encrypted files aren't supported.
+    pub fn from_bytes(data: Vec<u8>) -> Result<PDFDocument, PDFError> {
+        let mut document = PDFDocument {
+            data,
+            xref: HashMap::new(),
+            pages: vec![],
+            objects: RefCell::new(HashMap::new()),
+            object_streams: RefCell::new(HashMap::new()),
+        };
+
+        // Fall back to scanning if the cross-reference data doesn't lead to any pages.
+        let mut pages = document.read_xref_chain()
+                                .and_then(|trailer| document.load_pages(&trailer));
+        match pages {
+            Err(PDFError::Encrypted) => return Err(PDFError::Encrypted),
+            Ok(ref pages) if !pages.is_empty() => {}
+            _ => {
+                document.xref = document.scan_for_objects();
+                document.objects.borrow_mut().clear();
+                document.object_streams.borrow_mut().clear();
+                let trailer = document.recover_trailer().ok_or(PDFError::InvalidXref)?;
+                pages = document.load_pages(&trailer);
+            }
+        }
+        document.pages = pages?;
+        Ok(document)
+    }
+
+    /// Returns the number of pages.
+    #[inline]
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns the size of a page in points, after cropping and rotation.
+    pub fn page_size(&self, page_index: usize) -> Option<Vector2F> {
+        let page = self.pages.get(page_index)?;
+        let size = page.visible_area().size();
+        if page.rotation % 180 == 0 { Some(size) } else { Some(size.yx()) }
+    }
+
+    #[inline]
+    pub(crate) fn page(&self, page_index: usize) -> Option<&Page> {
+        self.pages.get(page_index)
+    }
+
+    /// Follows indirect references. Missing objects are null, as the specification requires.
+    pub(crate) fn resolve(&self, object: &Object) -> Object {
+        let mut object = object.clone();
+        for _ in 0..MAX_RESOLVE_DEPTH {
+            match object {
+                Object::Reference(id) => object = self.load_object(id).unwrap_or(Object::Null),
+                object => return object,
+            }
+        }
+        Object::Null
+    }
+
+    /// Looks up a dictionary entry, following indirect references.
+    #[inline]
+    pub(crate) fn get(&self, dict: &Dict, key: &str) -> Object {
+        dict.get(key).map(|object| self.resolve(object)).unwrap_or(Object::Null)
+    }
+
+    /// Applies a stream's filters to its data.
+    pub(crate) fn decode_stream(&self, stream: &Stream) -> Result<Vec<u8>, PDFError> {
+        let filters = match self.get(&stream.dict, "Filter") {
+            Object::Name(name) => vec![Object::Name(name)],
+            Object::Array(array) => array.iter().map(|filter| self.resolve(filter)).collect(),
+            _ => return Ok(stream.data.clone()),
+        };
+        let params = match self.get(&stream.dict, "DecodeParms") {
+            Object::Array(array) => array.iter().map(|params| self.resolve(params)).collect(),
+            params => vec![params],
+        };
+
+        let mut data = stream.data.clone();
+        for (index, filter) in filters.iter().enumerate() {
+            let filter = filter.as_name().ok_or_else(|| {
+                PDFError::UnsupportedFilter("filter that isn't a name".to_owned())
+            })?;
+            let params = params.get(index).and_then(Object::as_dict);
+            data = filter::decode(&data, filter, params)?;
+        }
+        Ok(data)
+    }
+
+    fn load_object(&self, id: ObjectId) -> Option<Object> {
+        if let Some(object) = self.objects.borrow().get(&id.number) {
+            return Some(object.clone());
+        }
+        let object = match *self.xref.get(&id.number)? {
+            XrefEntry::Offset(offset) => self.parse_indirect_object(offset, Some(id.number)).ok()?,
+            XrefEntry::Compressed { stream, index } => {
+                self.load_object_stream(stream)?.get(index)?.clone()
+            }
+        };
+        self.objects.borrow_mut().insert(id.number, object.clone());
+        Some(object)
+    }
+
+    fn load_object_stream(&self, number: u32) -> Option<Rc<Vec<Object>>> {
+        if let Some(objects) = self.object_streams.borrow().get(&number) {
+            return Some(objects.clone());
+        }
+        let stream = match *self.xref.get(&number)? {
+            XrefEntry::Offset(offset) => self.parse_indirect_object(offset, Some(number)).ok()?,
+            XrefEntry::Compressed { .. } => return None,
+        };
+        let objects = Rc::new(self.parse_object_stream(stream.as_stream()?)?);
+        self.object_streams.borrow_mut().insert(number, objects.clone());
+        Some(objects)
+    }
+
+    // Returns the objects in an object stream, in order.
+    fn parse_object_stream(&self, stream: &Stream) -> Option<Vec<Object>> {
+        let data = self.decode_stream(stream).ok()?;
+        let count = self.get(&stream.dict, "N").as_integer()? as usize;
+        let first = self.get(&stream.dict, "First").as_integer()? as usize;
+
+        let mut parser = Parser::new(&data, 0);
+        let mut offsets = vec![];
+        for _ in 0..count {
+            parser.parse_integer().ok()?;
+            offsets.push(parser.parse_integer().ok()? as usize);
+        }
+        Some(offsets.into_iter().map(|offset| {
+            Parser::new(&data, first + offset).parse_object().unwrap_or(Object::Null)
+        }).collect())
+    }
+
+    // Parses `number generation obj`, the object, and any stream data that follows.
+    fn parse_indirect_object(&self, offset: usize, expected_number: Option<u32>)
+                             -> Result<Object, PDFError> {
+        let mut parser = Parser::new(&self.data, offset);
+        let number = parser.parse_integer()?;
+        parser.parse_integer()?;
+        parser.expect_keyword(b"obj")?;
+        if expected_number.map_or(false, |expected| expected as i64 != number) {
+            return Err(PDFError::Syntax(offset));
+        }
+
+        let object = parser.parse_object()?;
+        let dict = match object {
+            Object::Dict(ref dict) if parser.try_keyword(b"stream") => dict,
+            object => return Ok(object),
+        };
+
+        // The data starts after the end of the line.
+        let mut start = parser.position;
+        if self.data.get(start) == Some(&b'\r') {
+            start += 1;
+        }
+        if self.data.get(start) == Some(&b'\n') {
+            start += 1;
+        }
+
+        // Trust the length if `endstream` follows it; otherwise search for `endstream`.
+        let length = match dict.get("Length") {
+            Some(&Object::Reference(id)) if expected_number != Some(id.number) => {
+                self.load_object(id).and_then(|length| length.as_integer())
+            }
+            Some(length) => length.as_integer(),
+            None => None,
+        };
+        let end = length.and_then(|length| {
+            let end = start.checked_add(length as usize)?;
+            let mut position = end;
+            while self.data.get(position).map_or(false, |&byte| is_whitespace(byte)) {
+                position += 1;
+            }
+            if self.data.get(position..)?.starts_with(b"endstream") { Some(end) } else { None }
+        });
+        let end = match end {
+            Some(end) => end,
+            None => {
+                let mut end = find(&self.data[start..], b"endstream")
+                    .map(|position| start + position)
+                    .ok_or(PDFError::Syntax(start))?;
+                while end > start && (self.data[end - 1] == b'\n' || self.data[end - 1] == b'\r') {
+                    end -= 1;
+                }
+                end
+            }
+        };
+
+        let dict = (**dict).clone();
+        Ok(Object::Stream(Rc::new(Stream { dict, data: self.data[start..end].to_vec() })))
+    }
+
+    // Reads the cross-reference sections, starting at the last, and returns the newest trailer.
+    fn read_xref_chain(&mut self) -> Result<Dict, PDFError> {
+        let startxref = rfind(&self.data, b"startxref").ok_or(PDFError::InvalidXref)?;
+        let mut parser = Parser::new(&self.data, startxref + b"startxref".len());
+        let mut offset = Some(parser.parse_integer()? as usize);
+
+        let mut trailer: Option<Dict> = None;
+        let mut visited = HashSet::new();
+        while let Some(section_offset) = offset {
+            if !visited.insert(section_offset) {
+                break;
+            }
+            let section_trailer = self.read_xref_section(section_offset)?;
+
+            // Hybrid files keep some entries in a cross-reference stream.
+            if let Some(stream_offset) = section_trailer.get("XRefStm")
+                                                        .and_then(Object::as_integer) {
+                if visited.insert(stream_offset as usize) {
+                    self.read_xref_section(stream_offset as usize)?;
+                }
+            }
+
+            offset = section_trailer.get("Prev")
+                                    .and_then(Object::as_integer)
+                                    .map(|offset| offset as usize);
+            if trailer.is_none() {
+                trailer = Some(section_trailer);
+            }
+        }
+        trailer.ok_or(PDFError::InvalidXref)
+    }
+
+    // Reads a cross-reference table or stream, adding entries that newer sections haven't
+    // already provided, and returns its trailer.
+    fn read_xref_section(&mut self, offset: usize) -> Result<Dict, PDFError> {
+        let mut parser = Parser::new(&self.data, offset);
+        if !parser.try_keyword(b"xref") {
+            return self.read_xref_stream(offset);
+        }
+
+        loop {
+            if parser.try_keyword(b"trailer") {
+                return match parser.parse_object()? {
+                    Object::Dict(dict) => Ok((*dict).clone()),
+                    _ => Err(PDFError::InvalidXref),
+                };
+            }
+            let first = parser.parse_integer()?;
+            let count = parser.parse_integer()?;
+            for number in first..(first + count) {
+                let entry_offset = parser.parse_integer()?;
+                parser.parse_integer()?;
+                let in_use = if parser.try_keyword(b"n") {
+                    true
+                } else if parser.try_keyword(b"f") {
+                    false
+                } else {
+                    return Err(PDFError::InvalidXref);
+                };
+                if in_use && number >= 0 && entry_offset > 0 {
+                    self.xref.entry(number as u32)
+                             .or_insert(XrefEntry::Offset(entry_offset as usize));
+                }
+            }
+        }
+    }
+
+    fn read_xref_stream(&mut self, offset: usize) -> Result<Dict, PDFError> {
+        let object = self.parse_indirect_object(offset, None)?;
+        let stream = object.as_stream().ok_or(PDFError::InvalidXref)?;
+        let data = self.decode_stream(stream)?;
+
+        let widths: Vec<usize> = stream.dict.get("W")
+                                            .and_then(Object::as_array)
+                                            .ok_or(PDFError::InvalidXref)?
+                                            .iter()
+                                            .map(|width| width.as_integer().unwrap_or(0) as usize)
+                                            .collect();
+        if widths.len() != 3 || widths.iter().any(|&width| width > 8) {
+            return Err(PDFError::InvalidXref);
+        }
+        let size = stream.dict.get("Size").and_then(Object::as_integer).unwrap_or(0);
+        let index = match stream.dict.get("Index").and_then(Object::as_array) {
+            Some(index) => index.iter().filter_map(Object::as_integer).collect(),
+            None => vec![0, size],
+        };
+
+        let entry_length: usize = widths.iter().sum();
+        let mut entries = data.chunks(entry_length.max(1));
+        for range in index.chunks(2) {
+            let (first, count) = (range[0], *range.get(1).unwrap_or(&0));
+            for number in first..(first + count) {
+                let entry = match entries.next() {
+                    Some(entry) if entry.len() == entry_length => entry,
+                    _ => break,
+                };
+                let mut fields = [0u64; 3];
+                let mut position = 0;
+                for (field, &width) in fields.iter_mut().zip(widths.iter()) {
+                    *field = entry[position..(position + width)]
+                        .iter()
+                        .fold(0, |value, &byte| value << 8 | byte as u64);
+                    position += width;
+                }
+                // The type defaults to 1 when its field is absent.
+                let kind = if widths[0] == 0 { 1 } else { fields[0] };
+                let entry = match kind {
+                    1 => XrefEntry::Offset(fields[1] as usize),
+                    2 => XrefEntry::Compressed {
+                        stream: fields[1] as u32,
+                        index: fields[2] as usize,
+                    },
+                    _ => continue,
+                };
+                if number >= 0 {
+                    self.xref.entry(number as u32).or_insert(entry);
+                }
+            }
+        }
+        Ok(stream.dict.clone())
+    }
+
+    // Finds every `number generation obj` header, for files whose cross-reference data is
+    // unusable. Later definitions win, as they do with incremental updates.
+    fn scan_for_objects(&self) -> HashMap<u32, XrefEntry> {
+        let mut xref = HashMap::new();
+        let mut object_streams = vec![];
+        let mut position = 0;
+        while let Some(found) = find(&self.data[position..], b"obj") {
+            let keyword = position + found;
+            position = keyword + 3;
+            if self.data.get(position).map_or(false, |&byte| byte.is_ascii_alphanumeric()) {
+                continue;
+            }
+            if let Some((number, start)) = object_header_before(&self.data, keyword) {
+                xref.insert(number, XrefEntry::Offset(start));
+                let header = &self.data[position..(position + 256).min(self.data.len())];
+                if find(header, b"/ObjStm").is_some() {
+                    object_streams.push(start);
+                }
+            }
+        }
+
+        for offset in object_streams {
+            let stream = match self.parse_indirect_object(offset, None) {
+                Ok(Object::Stream(stream)) => stream,
+                _ => continue,
+            };
+            let data = match self.decode_stream(&stream) {
+                Ok(data) => data,
+                Err(_) => continue,
+            };
+            let stream_number = match Parser::new(&self.data, offset).parse_integer() {
+                Ok(number) => number as u32,
+                Err(_) => continue,
+            };
+            let count = stream.dict.get("N").and_then(Object::as_integer).unwrap_or(0);
+            let mut parser = Parser::new(&data, 0);
+            for index in 0..(count as usize) {
+                match parser.parse_integer() {
+                    Ok(number) if parser.parse_integer().is_ok() => {
+                        xref.entry(number as u32)
+                            .or_insert(XrefEntry::Compressed { stream: stream_number, index });
+                    }
+                    _ => break,
+                }
+            }
+        }
+        xref
+    }
+
+    // Finds a trailer for a file whose cross-reference data is unusable: the last `trailer`
+    // dictionary or cross-reference stream naming a catalog, or failing that the catalog itself.
+    fn recover_trailer(&self) -> Option<Dict> {
+        let mut position = self.data.len();
+        while let Some(found) = rfind(&self.data[..position], b"trailer") {
+            let trailer = Parser::new(&self.data, found + b"trailer".len()).parse_object();
+            if let Ok(Object::Dict(dict)) = trailer {
+                if dict.contains_key("Root") {
+                    return Some((*dict).clone());
+                }
+            }
+            position = found;
+        }
+
+        let mut numbers: Vec<u32> = self.xref.keys().cloned().collect();
+        numbers.sort();
+        let mut catalog = None;
+        for number in numbers {
+            let object = self.load_object(ObjectId { number, generation: 0 });
+            let dict = match object.as_ref().and_then(Object::as_dict) {
+                Some(dict) => dict,
+                None => continue,
+            };
+            match dict.get("Type").and_then(Object::as_name) {
+                Some("XRef") if dict.contains_key("Root") => return Some(dict.clone()),
+                Some("Catalog") => catalog = Some(number),
+                _ => {}
+            }
+        }
+        let mut trailer = Dict::new();
+        let root = Object::Reference(ObjectId { number: catalog?, generation: 0 });
+        trailer.insert("Root".into(), root);
+        Some(trailer)
+    }
+
+    fn load_pages(&self, trailer: &Dict) -> Result<Vec<Page>, PDFError> {
+        if trailer.contains_key("Encrypt") {
+            return Err(PDFError::Encrypted);
+        }
+        let root = self.get(trailer, "Root");
+        let pages = root.as_dict()
+                        .and_then(|root| root.get("Pages"))
+                        .ok_or(PDFError::InvalidPageTree)?;
+
+        let mut visited = HashSet::new();
+        if let Object::Reference(id) = *pages {
+            visited.insert(id);
+        }
+        let inherited = InheritedAttributes {
+            resources: Object::Null,
+            media_box: None,
+            crop_box: None,
+            rotation: 0,
+        };
+        let mut page_list = vec![];
+        self.collect_pages(&self.resolve(pages), inherited, 0, &mut visited, &mut page_list)?;
+        Ok(page_list)
+    }
+
+    fn collect_pages(&self,
+                     node: &Object,
+                     mut inherited: InheritedAttributes,
+                     depth: u32,
+                     visited: &mut HashSet<ObjectId>,
+                     pages: &mut Vec<Page>)
+                     -> Result<(), PDFError> {
+        if depth > MAX_PAGE_TREE_DEPTH {
+            return Err(PDFError::InvalidPageTree);
+        }
+        let dict = match *node {
+            Object::Dict(ref dict) => dict.clone(),
+            _ => return Ok(()),
+        };
+
+        if dict.contains_key("Resources") {
+            inherited.resources = self.get(&dict, "Resources");
+        }
+        if let Some(media_box) = self.get_rect(&dict, "MediaBox") {
+            inherited.media_box = Some(media_box);
+        }
+        if let Some(crop_box) = self.get_rect(&dict, "CropBox") {
+            inherited.crop_box = Some(crop_box);
+        }
+        if let Some(rotation) = self.get(&dict, "Rotate").as_integer() {
+            inherited.rotation = (rotation.rem_euclid(360) / 90 * 90) as i32;
+        }
+
+        let kids = self.get(&dict, "Kids");
+        let is_page = match dict.get("Type").and_then(Object::as_name) {
+            Some("Page") => true,
+            Some("Pages") => false,
+            _ => kids.as_array().is_none(),
+        };
+        if is_page {
+            pages.push(Page {
+                dict,
+                resources: inherited.resources,
+                // US Letter is the conventional default for a missing media box.
+                media_box: inherited.media_box.unwrap_or_else(|| {
+                    RectF::new(Vector2F::zero(), vec2f(612.0, 792.0))
+                }),
+                crop_box: inherited.crop_box,
+                rotation: inherited.rotation,
+            });
+            return Ok(());
+        }
+
+        for kid in kids.as_array().unwrap_or(&[]) {
+            if let Object::Reference(id) = *kid {
+                if !visited.insert(id) {
+                    return Err(PDFError::InvalidPageTree);
+                }
+            }
+            let kid = self.resolve(kid);
+            self.collect_pages(&kid, inherited.clone(), depth + 1, visited, pages)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get_rect(&self, dict: &Dict, key: &str) -> Option<RectF> {
+        let values: Vec<f32> = self.get(dict, key)
+                                   .as_array()?
+                                   .iter()
+                                   .map(|value| self.resolve(value).as_f32())
+                                   .collect::<Option<_>>()?;
+        if values.len() != 4 {
+            return None;
+        }
+        let (a, b) = (vec2f(values[0], values[1]), vec2f(values[2], values[3]));
+        Some(RectF::from_points(a.min(b), a.max(b)))
+    }
+}
+
+// Given the position of an `obj` keyword, returns the object number and the offset of the
+// header if it's preceded by `number generation`.
+fn object_header_before(data: &[u8], keyword: usize) -> Option<(u32, usize)> {
+    let mut position = keyword;
+    let skip = |predicate: fn(u8) -> bool, position: &mut usize| {
+        let end = *position;
+        while *position > 0 && predicate(data[*position - 1]) {
+            *position -= 1;
+        }
+        end - *position
+    };
+    if skip(is_whitespace, &mut position) == 0 || skip(|byte| byte.is_ascii_digit(),
+                                                       &mut position) == 0 {
+        return None;
+    }
+    if skip(is_whitespace, &mut position) == 0 {
+        return None;
+    }
+    let number_end = position;
+    if skip(|byte| byte.is_ascii_digit(), &mut position) == 0 {
+        return None;
+    }
+    if position > 0 && !is_whitespace(data[position - 1]) {
+        return None;
+    }
+    let number = std::str::from_utf8(&data[position..number_end]).ok()?.parse().ok()?;
+    Some((number, position))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|window| window == needle)
+}
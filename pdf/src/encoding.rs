@@ -0,0 +1,231 @@
+// pathfinder/pdf/src/encoding.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The standard Latin text encodings, and the mapping from glyph names to Unicode.
+
+/// An encoding that a simple font's `Encoding` entry can name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum BaseEncoding {
+    Standard,
+    WinAnsi,
+    MacRoman,
+}
+
+impl BaseEncoding {
+    pub(crate) fn from_name(name: &str) -> Option<BaseEncoding> {
+        match name {
+            "StandardEncoding" => Some(BaseEncoding::Standard),
+            "WinAnsiEncoding" => Some(BaseEncoding::WinAnsi),
+            "MacRomanEncoding" => Some(BaseEncoding::MacRoman),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the glyph that a code selects.
+    pub(crate) fn glyph_name(self, code: u8) -> Option<&'static str> {
+        match (self, code) {
+            (BaseEncoding::WinAnsi, b'\'') | (BaseEncoding::MacRoman, b'\'') => Some("quotesingle"),
+            (BaseEncoding::WinAnsi, b'`') | (BaseEncoding::MacRoman, b'`') => Some("grave"),
+            (_, 0x20..=0x7e) => Some(ASCII_NAMES[(code - 0x20) as usize]),
+            (BaseEncoding::Standard, _) => lookup(&STANDARD_HIGH, code),
+            (BaseEncoding::WinAnsi, _) => {
+                WIN_ANSI_HIGH.iter().find(|entry| entry.0 == code).map(|entry| entry.1)
+            }
+            (BaseEncoding::MacRoman, _) => {
+                MAC_ROMAN_HIGH.iter().find(|entry| entry.0 == code).map(|entry| entry.1)
+            }
+        }
+    }
+}
+
+/// Returns the character a glyph name stands for. Besides the names in the standard encodings,
+/// this understands the `uniXXXX` and `uXXXX` forms.
+pub(crate) fn unicode_for_glyph_name(name: &str) -> Option<char> {
+    match name {
+        "quoteright" => return Some('\u{2019}'),
+        "quoteleft" => return Some('\u{2018}'),
+        "quotesingle" => return Some('\''),
+        "grave" => return Some('`'),
+        "Lslash" => return Some('\u{141}'),
+        "lslash" => return Some('\u{142}'),
+        "minus" => return Some('\u{2212}'),
+        "nbspace" => return Some('\u{a0}'),
+        "sfthyphen" => return Some('\u{ad}'),
+        _ => {}
+    }
+    if let Some(index) = ASCII_NAMES.iter().position(|&ascii_name| ascii_name == name) {
+        return Some((0x20 + index as u8) as char);
+    }
+    if let Some(entry) = WIN_ANSI_HIGH.iter()
+                                      .chain(MAC_ROMAN_HIGH.iter())
+                                      .find(|entry| entry.1 == name) {
+        return Some(entry.2);
+    }
+
+    let hex = if name.starts_with("uni") && name.len() == 7 {
+        &name[3..]
+    } else if name.starts_with('u') && (name.len() == 5 || name.len() == 7) {
+        &name[1..]
+    } else {
+        return None;
+    };
+    u32::from_str_radix(hex, 16).ok().and_then(std::char::from_u32)
+}
+
+fn lookup(table: &[(u8, &'static str)], code: u8) -> Option<&'static str> {
+    table.iter().find(|entry| entry.0 == code).map(|entry| entry.1)
+}
+
+// The names of codes 0x20 to 0x7e in StandardEncoding.
+static ASCII_NAMES: [&str; 95] = [
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "quoteright",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash", "zero",
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "colon", "semicolon",
+    "less", "equal", "greater", "question", "at", "A", "B", "C", "D", "E", "F", "G", "H", "I", "J",
+    "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z", "bracketleft",
+    "backslash", "bracketright", "asciicircum", "underscore", "quoteleft", "a", "b", "c", "d", "e",
+    "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t", "u", "v", "w", "x",
+    "y", "z", "braceleft", "bar", "braceright", "asciitilde",
+];
+
+// The names of the codes above 0x7e in StandardEncoding.
+static STANDARD_HIGH: [(u8, &str); 54] = [
+    (0o241, "exclamdown"), (0o242, "cent"), (0o243, "sterling"), (0o244, "fraction"),
+    (0o245, "yen"), (0o246, "florin"), (0o247, "section"), (0o250, "currency"),
+    (0o251, "quotesingle"), (0o252, "quotedblleft"), (0o253, "guillemotleft"),
+    (0o254, "guilsinglleft"), (0o255, "guilsinglright"), (0o256, "fi"), (0o257, "fl"),
+    (0o261, "endash"), (0o262, "dagger"), (0o263, "daggerdbl"), (0o264, "periodcentered"),
+    (0o266, "paragraph"), (0o267, "bullet"), (0o270, "quotesinglbase"), (0o271, "quotedblbase"),
+    (0o272, "quotedblright"), (0o273, "guillemotright"), (0o274, "ellipsis"),
+    (0o275, "perthousand"), (0o277, "questiondown"), (0o301, "grave"), (0o302, "acute"),
+    (0o303, "circumflex"), (0o304, "tilde"), (0o305, "macron"), (0o306, "breve"),
+    (0o307, "dotaccent"), (0o310, "dieresis"), (0o312, "ring"), (0o313, "cedilla"),
+    (0o315, "hungarumlaut"), (0o316, "ogonek"), (0o317, "caron"), (0o320, "emdash"), (0o341, "AE"),
+    (0o343, "ordfeminine"), (0o350, "Lslash"), (0o351, "Oslash"), (0o352, "OE"),
+    (0o353, "ordmasculine"), (0o361, "ae"), (0o365, "dotlessi"), (0o370, "lslash"),
+    (0o371, "oslash"), (0o372, "oe"), (0o373, "germandbls"),
+];
+
+// The names of the codes above 0x7f in WinAnsiEncoding, and their characters.
+static WIN_ANSI_HIGH: [(u8, &str, char); 123] = [
+    (0x80, "Euro", '\u{20ac}'), (0x82, "quotesinglbase", '\u{201a}'), (0x83, "florin", '\u{192}'),
+    (0x84, "quotedblbase", '\u{201e}'), (0x85, "ellipsis", '\u{2026}'),
+    (0x86, "dagger", '\u{2020}'), (0x87, "daggerdbl", '\u{2021}'), (0x88, "circumflex", '\u{2c6}'),
+    (0x89, "perthousand", '\u{2030}'), (0x8a, "Scaron", '\u{160}'),
+    (0x8b, "guilsinglleft", '\u{2039}'), (0x8c, "OE", '\u{152}'), (0x8e, "Zcaron", '\u{17d}'),
+    (0x91, "quoteleft", '\u{2018}'), (0x92, "quoteright", '\u{2019}'),
+    (0x93, "quotedblleft", '\u{201c}'), (0x94, "quotedblright", '\u{201d}'),
+    (0x95, "bullet", '\u{2022}'), (0x96, "endash", '\u{2013}'), (0x97, "emdash", '\u{2014}'),
+    (0x98, "tilde", '\u{2dc}'), (0x99, "trademark", '\u{2122}'), (0x9a, "scaron", '\u{161}'),
+    (0x9b, "guilsinglright", '\u{203a}'), (0x9c, "oe", '\u{153}'), (0x9e, "zcaron", '\u{17e}'),
+    (0x9f, "Ydieresis", '\u{178}'), (0xa0, "space", '\u{a0}'), (0xa1, "exclamdown", '\u{a1}'),
+    (0xa2, "cent", '\u{a2}'), (0xa3, "sterling", '\u{a3}'), (0xa4, "currency", '\u{a4}'),
+    (0xa5, "yen", '\u{a5}'), (0xa6, "brokenbar", '\u{a6}'), (0xa7, "section", '\u{a7}'),
+    (0xa8, "dieresis", '\u{a8}'), (0xa9, "copyright", '\u{a9}'), (0xaa, "ordfeminine", '\u{aa}'),
+    (0xab, "guillemotleft", '\u{ab}'), (0xac, "logicalnot", '\u{ac}'), (0xad, "hyphen", '\u{ad}'),
+    (0xae, "registered", '\u{ae}'), (0xaf, "macron", '\u{af}'), (0xb0, "degree", '\u{b0}'),
+    (0xb1, "plusminus", '\u{b1}'), (0xb2, "twosuperior", '\u{b2}'),
+    (0xb3, "threesuperior", '\u{b3}'), (0xb4, "acute", '\u{b4}'), (0xb5, "mu", '\u{b5}'),
+    (0xb6, "paragraph", '\u{b6}'), (0xb7, "periodcentered", '\u{b7}'), (0xb8, "cedilla", '\u{b8}'),
+    (0xb9, "onesuperior", '\u{b9}'), (0xba, "ordmasculine", '\u{ba}'),
+    (0xbb, "guillemotright", '\u{bb}'), (0xbc, "onequarter", '\u{bc}'), (0xbd, "onehalf", '\u{bd}'),
+    (0xbe, "threequarters", '\u{be}'), (0xbf, "questiondown", '\u{bf}'), (0xc0, "Agrave", '\u{c0}'),
+    (0xc1, "Aacute", '\u{c1}'), (0xc2, "Acircumflex", '\u{c2}'), (0xc3, "Atilde", '\u{c3}'),
+    (0xc4, "Adieresis", '\u{c4}'), (0xc5, "Aring", '\u{c5}'), (0xc6, "AE", '\u{c6}'),
+    (0xc7, "Ccedilla", '\u{c7}'), (0xc8, "Egrave", '\u{c8}'), (0xc9, "Eacute", '\u{c9}'),
+    (0xca, "Ecircumflex", '\u{ca}'), (0xcb, "Edieresis", '\u{cb}'), (0xcc, "Igrave", '\u{cc}'),
+    (0xcd, "Iacute", '\u{cd}'), (0xce, "Icircumflex", '\u{ce}'), (0xcf, "Idieresis", '\u{cf}'),
+    (0xd0, "Eth", '\u{d0}'), (0xd1, "Ntilde", '\u{d1}'), (0xd2, "Ograve", '\u{d2}'),
+    (0xd3, "Oacute", '\u{d3}'), (0xd4, "Ocircumflex", '\u{d4}'), (0xd5, "Otilde", '\u{d5}'),
+    (0xd6, "Odieresis", '\u{d6}'), (0xd7, "multiply", '\u{d7}'), (0xd8, "Oslash", '\u{d8}'),
+    (0xd9, "Ugrave", '\u{d9}'), (0xda, "Uacute", '\u{da}'), (0xdb, "Ucircumflex", '\u{db}'),
+    (0xdc, "Udieresis", '\u{dc}'), (0xdd, "Yacute", '\u{dd}'), (0xde, "Thorn", '\u{de}'),
+    (0xdf, "germandbls", '\u{df}'), (0xe0, "agrave", '\u{e0}'), (0xe1, "aacute", '\u{e1}'),
+    (0xe2, "acircumflex", '\u{e2}'), (0xe3, "atilde", '\u{e3}'), (0xe4, "adieresis", '\u{e4}'),
+    (0xe5, "aring", '\u{e5}'), (0xe6, "ae", '\u{e6}'), (0xe7, "ccedilla", '\u{e7}'),
+    (0xe8, "egrave", '\u{e8}'), (0xe9, "eacute", '\u{e9}'), (0xea, "ecircumflex", '\u{ea}'),
+    (0xeb, "edieresis", '\u{eb}'), (0xec, "igrave", '\u{ec}'), (0xed, "iacute", '\u{ed}'),
+    (0xee, "icircumflex", '\u{ee}'), (0xef, "idieresis", '\u{ef}'), (0xf0, "eth", '\u{f0}'),
+    (0xf1, "ntilde", '\u{f1}'), (0xf2, "ograve", '\u{f2}'), (0xf3, "oacute", '\u{f3}'),
+    (0xf4, "ocircumflex", '\u{f4}'), (0xf5, "otilde", '\u{f5}'), (0xf6, "odieresis", '\u{f6}'),
+    (0xf7, "divide", '\u{f7}'), (0xf8, "oslash", '\u{f8}'), (0xf9, "ugrave", '\u{f9}'),
+    (0xfa, "uacute", '\u{fa}'), (0xfb, "ucircumflex", '\u{fb}'), (0xfc, "udieresis", '\u{fc}'),
+    (0xfd, "yacute", '\u{fd}'), (0xfe, "thorn", '\u{fe}'), (0xff, "ydieresis", '\u{ff}'),
+];
+
+// The names of the codes above 0x7f in MacRomanEncoding, and their characters.
+static MAC_ROMAN_HIGH: [(u8, &str, char); 128] = [
+    (0x80, "Adieresis", '\u{c4}'), (0x81, "Aring", '\u{c5}'), (0x82, "Ccedilla", '\u{c7}'),
+    (0x83, "Eacute", '\u{c9}'), (0x84, "Ntilde", '\u{d1}'), (0x85, "Odieresis", '\u{d6}'),
+    (0x86, "Udieresis", '\u{dc}'), (0x87, "aacute", '\u{e1}'), (0x88, "agrave", '\u{e0}'),
+    (0x89, "acircumflex", '\u{e2}'), (0x8a, "adieresis", '\u{e4}'), (0x8b, "atilde", '\u{e3}'),
+    (0x8c, "aring", '\u{e5}'), (0x8d, "ccedilla", '\u{e7}'), (0x8e, "eacute", '\u{e9}'),
+    (0x8f, "egrave", '\u{e8}'), (0x90, "ecircumflex", '\u{ea}'), (0x91, "edieresis", '\u{eb}'),
+    (0x92, "iacute", '\u{ed}'), (0x93, "igrave", '\u{ec}'), (0x94, "icircumflex", '\u{ee}'),
+    (0x95, "idieresis", '\u{ef}'), (0x96, "ntilde", '\u{f1}'), (0x97, "oacute", '\u{f3}'),
+    (0x98, "ograve", '\u{f2}'), (0x99, "ocircumflex", '\u{f4}'), (0x9a, "odieresis", '\u{f6}'),
+    (0x9b, "otilde", '\u{f5}'), (0x9c, "uacute", '\u{fa}'), (0x9d, "ugrave", '\u{f9}'),
+    (0x9e, "ucircumflex", '\u{fb}'), (0x9f, "udieresis", '\u{fc}'), (0xa0, "dagger", '\u{2020}'),
+    (0xa1, "degree", '\u{b0}'), (0xa2, "cent", '\u{a2}'), (0xa3, "sterling", '\u{a3}'),
+    (0xa4, "section", '\u{a7}'), (0xa5, "bullet", '\u{2022}'), (0xa6, "paragraph", '\u{b6}'),
+    (0xa7, "germandbls", '\u{df}'), (0xa8, "registered", '\u{ae}'), (0xa9, "copyright", '\u{a9}'),
+    (0xaa, "trademark", '\u{2122}'), (0xab, "acute", '\u{b4}'), (0xac, "dieresis", '\u{a8}'),
+    (0xad, "notequal", '\u{2260}'), (0xae, "AE", '\u{c6}'), (0xaf, "Oslash", '\u{d8}'),
+    (0xb0, "infinity", '\u{221e}'), (0xb1, "plusminus", '\u{b1}'), (0xb2, "lessequal", '\u{2264}'),
+    (0xb3, "greaterequal", '\u{2265}'), (0xb4, "yen", '\u{a5}'), (0xb5, "mu", '\u{b5}'),
+    (0xb6, "partialdiff", '\u{2202}'), (0xb7, "summation", '\u{2211}'),
+    (0xb8, "product", '\u{220f}'), (0xb9, "pi", '\u{3c0}'), (0xba, "integral", '\u{222b}'),
+    (0xbb, "ordfeminine", '\u{aa}'), (0xbc, "ordmasculine", '\u{ba}'), (0xbd, "Omega", '\u{3a9}'),
+    (0xbe, "ae", '\u{e6}'), (0xbf, "oslash", '\u{f8}'), (0xc0, "questiondown", '\u{bf}'),
+    (0xc1, "exclamdown", '\u{a1}'), (0xc2, "logicalnot", '\u{ac}'), (0xc3, "radical", '\u{221a}'),
+    (0xc4, "florin", '\u{192}'), (0xc5, "approxequal", '\u{2248}'), (0xc6, "Delta", '\u{2206}'),
+    (0xc7, "guillemotleft", '\u{ab}'), (0xc8, "guillemotright", '\u{bb}'),
+    (0xc9, "ellipsis", '\u{2026}'), (0xca, "space", '\u{a0}'), (0xcb, "Agrave", '\u{c0}'),
+    (0xcc, "Atilde", '\u{c3}'), (0xcd, "Otilde", '\u{d5}'), (0xce, "OE", '\u{152}'),
+    (0xcf, "oe", '\u{153}'), (0xd0, "endash", '\u{2013}'), (0xd1, "emdash", '\u{2014}'),
+    (0xd2, "quotedblleft", '\u{201c}'), (0xd3, "quotedblright", '\u{201d}'),
+    (0xd4, "quoteleft", '\u{2018}'), (0xd5, "quoteright", '\u{2019}'), (0xd6, "divide", '\u{f7}'),
+    (0xd7, "lozenge", '\u{25ca}'), (0xd8, "ydieresis", '\u{ff}'), (0xd9, "Ydieresis", '\u{178}'),
+    (0xda, "fraction", '\u{2044}'), (0xdb, "currency", '\u{a4}'),
+    (0xdc, "guilsinglleft", '\u{2039}'), (0xdd, "guilsinglright", '\u{203a}'),
+    (0xde, "fi", '\u{fb01}'), (0xdf, "fl", '\u{fb02}'), (0xe0, "daggerdbl", '\u{2021}'),
+    (0xe1, "periodcentered", '\u{b7}'), (0xe2, "quotesinglbase", '\u{201a}'),
+    (0xe3, "quotedblbase", '\u{201e}'), (0xe4, "perthousand", '\u{2030}'),
+    (0xe5, "Acircumflex", '\u{c2}'), (0xe6, "Ecircumflex", '\u{ca}'), (0xe7, "Aacute", '\u{c1}'),
+    (0xe8, "Edieresis", '\u{cb}'), (0xe9, "Egrave", '\u{c8}'), (0xea, "Iacute", '\u{cd}'),
+    (0xeb, "Icircumflex", '\u{ce}'), (0xec, "Idieresis", '\u{cf}'), (0xed, "Igrave", '\u{cc}'),
+    (0xee, "Oacute", '\u{d3}'), (0xef, "Ocircumflex", '\u{d4}'), (0xf0, "apple", '\u{f8ff}'),
+    (0xf1, "Ograve", '\u{d2}'), (0xf2, "Uacute", '\u{da}'), (0xf3, "Ucircumflex", '\u{db}'),
+    (0xf4, "Ugrave", '\u{d9}'), (0xf5, "dotlessi", '\u{131}'), (0xf6, "circumflex", '\u{2c6}'),
+    (0xf7, "tilde", '\u{2dc}'), (0xf8, "macron", '\u{af}'), (0xf9, "breve", '\u{2d8}'),
+    (0xfa, "dotaccent", '\u{2d9}'), (0xfb, "ring", '\u{2da}'), (0xfc, "cedilla", '\u{b8}'),
+    (0xfd, "hungarumlaut", '\u{2dd}'), (0xfe, "ogonek", '\u{2db}'), (0xff, "caron", '\u{2c7}'),
+];
+
+#[cfg(test)]
+mod test {
+    use super::{BaseEncoding, unicode_for_glyph_name};
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(BaseEncoding::Standard.glyph_name(b'\''), Some("quoteright"));
+        assert_eq!(BaseEncoding::WinAnsi.glyph_name(b'\''), Some("quotesingle"));
+        assert_eq!(BaseEncoding::Standard.glyph_name(0o256), Some("fi"));
+        assert_eq!(BaseEncoding::WinAnsi.glyph_name(0x80), Some("Euro"));
+        assert_eq!(BaseEncoding::MacRoman.glyph_name(0x8e), Some("eacute"));
+        assert_eq!(BaseEncoding::WinAnsi.glyph_name(0x81), None);
+
+        assert_eq!(unicode_for_glyph_name("A"), Some('A'));
+        assert_eq!(unicode_for_glyph_name("eacute"), Some('é'));
+        assert_eq!(unicode_for_glyph_name("fi"), Some('\u{fb01}'));
+        assert_eq!(unicode_for_glyph_name("uni2022"), Some('•'));
+        assert_eq!(unicode_for_glyph_name("g42"), None);
+    }
+}
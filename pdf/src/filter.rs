@@ -0,0 +1,281 @@
+// pathfinder/pdf/src/filter.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Stream filters.
+//!
+//! The general-purpose filters are supported. Image compression filters aren't, since images
+//! aren't drawn.
+
+use crate::PDFError;
+use crate::object::Dict;
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+use std::io::Read;
+
+/// Decodes data with one filter.
+pub(crate) fn decode(data: &[u8], filter: &str, params: Option<&Dict>)
+                     -> Result<Vec<u8>, PDFError> {
+    match filter {
+        "FlateDecode" | "Fl" => predict(inflate(data), params),
+        "LZWDecode" | "LZW" => {
+            let early_change = params.and_then(|params| params.get("EarlyChange"))
+                                     .and_then(|value| value.as_integer())
+                                     .unwrap_or(1);
+            predict(decode_lzw(data, early_change != 0), params)
+        }
+        "ASCIIHexDecode" | "AHx" => Ok(decode_ascii_hex(data)),
+        "ASCII85Decode" | "A85" => decode_ascii_85(data),
+        "RunLengthDecode" | "RL" => Ok(decode_run_length(data)),
+        _ => Err(PDFError::UnsupportedFilter(filter.to_owned())),
+    }
+}
+
+// Inflates zlib data, keeping whatever was decoded before any error, since truncated streams are
+// common. Some writers omit the zlib header, so raw deflate data is accepted too.
+fn inflate(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![];
+    if ZlibDecoder::new(data).read_to_end(&mut output).is_err() && output.is_empty() {
+        let _ = DeflateDecoder::new(data).read_to_end(&mut output);
+    }
+    output
+}
+
+// Undoes the PNG and TIFF predictors used with the Flate and LZW filters.
+fn predict(data: Vec<u8>, params: Option<&Dict>) -> Result<Vec<u8>, PDFError> {
+    let param = |key: &str, default: i64| {
+        params.and_then(|params| params.get(key))
+              .and_then(|value| value.as_integer())
+              .unwrap_or(default)
+    };
+    let predictor = param("Predictor", 1);
+    if predictor == 1 {
+        return Ok(data);
+    }
+
+    let (colors, bits) = (param("Colors", 1).max(1) as usize, param("BitsPerComponent", 8));
+    let columns = param("Columns", 1).max(1) as usize;
+    let bits = bits.max(1) as usize;
+    let bytes_per_pixel = ((colors * bits + 7) / 8).max(1);
+    let row_length = (colors * bits * columns + 7) / 8;
+
+    if predictor == 2 {
+        // TIFF prediction, which we only support for whole bytes per component.
+        if bits != 8 {
+            return Err(PDFError::UnsupportedFilter("TIFF predictor".to_owned()));
+        }
+        let mut data = data;
+        for row in data.chunks_mut(row_length) {
+            for index in bytes_per_pixel..row.len() {
+                row[index] = row[index].wrapping_add(row[index - bytes_per_pixel]);
+            }
+        }
+        return Ok(data);
+    }
+
+    // PNG prediction: each row starts with a byte saying which filter it uses.
+    let mut output = Vec::with_capacity(data.len());
+    let mut previous_row = vec![0; row_length];
+    for encoded_row in data.chunks(row_length + 1) {
+        if encoded_row.len() < 2 {
+            break;
+        }
+        let (filter, encoded_row) = (encoded_row[0], &encoded_row[1..]);
+        let mut row = encoded_row.to_vec();
+        for index in 0..row.len() {
+            let left = if index >= bytes_per_pixel { row[index - bytes_per_pixel] } else { 0 };
+            let up = previous_row[index];
+            let up_left = if index >= bytes_per_pixel {
+                previous_row[index - bytes_per_pixel]
+            } else {
+                0
+            };
+            let prediction = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(PDFError::UnsupportedFilter(format!("PNG filter {}", filter))),
+            };
+            row[index] = row[index].wrapping_add(prediction);
+        }
+        output.extend_from_slice(&row);
+        previous_row[..row.len()].copy_from_slice(&row);
+    }
+    Ok(output)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (distance_left, distance_up, distance_up_left) = ((estimate - left as i16).abs(),
+                                                          (estimate - up as i16).abs(),
+                                                          (estimate - up_left as i16).abs());
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+fn decode_lzw(data: &[u8], early_change: bool) -> Vec<u8> {
+    const CLEAR_TABLE: usize = 256;
+    const END_OF_DATA: usize = 257;
+
+    let mut output = vec![];
+    let mut table: Vec<Vec<u8>> = (0..=255u8).map(|byte| vec![byte]).collect();
+    table.push(vec![]);
+    table.push(vec![]);
+    let (mut code_length, mut previous): (u32, Option<usize>) = (9, None);
+    let (mut buffer, mut buffered_bits) = (0u32, 0u32);
+
+    for &byte in data {
+        buffer = buffer << 8 | byte as u32;
+        buffered_bits += 8;
+        while buffered_bits >= code_length {
+            buffered_bits -= code_length;
+            let code = ((buffer >> buffered_bits) & ((1 << code_length) - 1)) as usize;
+            match code {
+                CLEAR_TABLE => {
+                    table.truncate(258);
+                    code_length = 9;
+                    previous = None;
+                    continue;
+                }
+                END_OF_DATA => return output,
+                _ => {}
+            }
+
+            let entry = match (table.get(code), previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) if code == table.len() => {
+                    let mut entry = table[previous].clone();
+                    entry.push(table[previous][0]);
+                    entry
+                }
+                _ => return output,
+            };
+            output.extend_from_slice(&entry);
+            if let (Some(previous), true) = (previous, table.len() < 4096) {
+                let mut new_entry = table[previous].clone();
+                new_entry.push(entry[0]);
+                table.push(new_entry);
+            }
+            previous = Some(code);
+
+            let next_size = table.len() + if early_change { 1 } else { 0 };
+            code_length = match next_size {
+                0..=511 => 9,
+                512..=1023 => 10,
+                1024..=2047 => 11,
+                _ => 12,
+            };
+        }
+    }
+    output
+}
+
+fn decode_ascii_hex(data: &[u8]) -> Vec<u8> {
+    let mut digits = vec![];
+    for &byte in data {
+        if byte == b'>' {
+            break;
+        }
+        if let Some(digit) = (byte as char).to_digit(16) {
+            digits.push(digit as u8);
+        }
+    }
+    digits.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).cloned().unwrap_or(0)).collect()
+}
+
+fn decode_ascii_85(data: &[u8]) -> Result<Vec<u8>, PDFError> {
+    let mut output = vec![];
+    let mut group = [0u32; 5];
+    let mut group_length = 0;
+    for &byte in data {
+        match byte {
+            b'~' => break,
+            b'z' if group_length == 0 => output.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group[group_length] = (byte - b'!') as u32;
+                group_length += 1;
+                if group_length == 5 {
+                    output.extend_from_slice(&ascii_85_group(&group)?);
+                    group_length = 0;
+                }
+            }
+            _ if byte.is_ascii_whitespace() || byte == 0 => {}
+            _ => return Err(PDFError::UnsupportedFilter("invalid ASCII85 data".to_owned())),
+        }
+    }
+
+    // A final partial group is padded with the highest digit.
+    if group_length > 1 {
+        for digit in &mut group[group_length..] {
+            *digit = 84;
+        }
+        output.extend_from_slice(&ascii_85_group(&group)?[..(group_length - 1)]);
+    }
+    Ok(output)
+}
+
+fn ascii_85_group(group: &[u32; 5]) -> Result<[u8; 4], PDFError> {
+    let value = group.iter().fold(0u64, |value, &digit| value * 85 + digit as u64);
+    if value > u32::MAX as u64 {
+        return Err(PDFError::UnsupportedFilter("invalid ASCII85 data".to_owned()));
+    }
+    Ok((value as u32).to_be_bytes())
+}
+
+fn decode_run_length(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![];
+    let mut index = 0;
+    while let Some(&length) = data.get(index) {
+        match length {
+            128 => break,
+            0..=127 => {
+                let end = (index + 2 + length as usize).min(data.len());
+                output.extend_from_slice(&data[(index + 1)..end]);
+                index = end;
+            }
+            _ => {
+                if let Some(&byte) = data.get(index + 1) {
+                    output.extend(std::iter::repeat(byte).take(257 - length as usize));
+                }
+                index += 2;
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::decode;
+    use crate::object::{Dict, Object};
+
+    #[test]
+    fn test_ascii_filters() {
+        assert_eq!(decode(b"48 65 6c6c 6>", "ASCIIHexDecode", None).unwrap(), b"Hell`");
+        assert_eq!(decode(b"87cURD]j7BEbo7~>", "ASCII85Decode", None).unwrap(),
+                   b"Hello world");
+        assert_eq!(decode(b"\x02abc\xfdx\x80", "RunLengthDecode", None).unwrap(), b"abcxxxx");
+    }
+
+    #[test]
+    fn test_png_predictor() {
+        let mut params = Dict::new();
+        params.insert("Predictor".into(), Object::Integer(12));
+        params.insert("Columns".into(), Object::Integer(3));
+        // An "up" row following a "sub" row.
+        let encoded = super::predict(vec![1, 1, 1, 1, 2, 1, 1, 1], Some(&params)).unwrap();
+        assert_eq!(encoded, vec![1, 2, 3, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,453 @@
+// pathfinder/pdf/src/font.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Fonts: character codes, glyph widths, and glyph outlines.
+//!
+//! Embedded Type 1 fonts are read with our own parser, and embedded TrueType and OpenType fonts
+//! are loaded with `font-kit`. Fonts that aren't embedded fall back to a similar system font.
+//! Composite fonts are supported with the `Identity-H` and `Identity-V` encodings.
+
+use crate::document::PDFDocument;
+use crate::encoding::{self, BaseEncoding};
+use crate::object::{Dict, Object};
+use crate::type1::Type1Font;
+use font_kit::family_name::FamilyName;
+use font_kit::hinting::HintingOptions;
+use font_kit::loader::Loader;
+use font_kit::loaders::default::Font as DefaultLoader;
+use font_kit::outline::OutlineSink;
+use font_kit::properties::{Properties, Style, Weight};
+use font_kit::source::{Source, SystemSource};
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+
+// The `Symbolic` bit of a font descriptor's flags.
+const SYMBOLIC_FLAG: i64 = 1 << 2;
+
+// The default vertical metrics of composite fonts, in text space: the glyph origin's height
+// above the baseline and the vertical advance.
+const DEFAULT_VERTICAL_ORIGIN: f32 = 0.88;
+const DEFAULT_VERTICAL_ADVANCE: f32 = -1.0;
+
+pub(crate) struct Font {
+    glyphs: GlyphSource,
+    // For simple fonts, the glyph names that codes select, where the font dictionary says.
+    glyph_names: HashMap<u32, String>,
+    // Whether the font dictionary named an encoding, rather than relying on the font's own.
+    has_encoding: bool,
+    symbolic: bool,
+    composite: bool,
+    vertical: bool,
+    cid_to_gid: Option<Vec<u16>>,
+    // Glyph widths, in text space.
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+    outline_cache: RefCell<HashMap<u32, Option<Outline>>>,
+}
+
+enum GlyphSource {
+    FontKit { font: DefaultLoader, scale: f32 },
+    Type1(Type1Font),
+    Missing,
+}
+
+impl Font {
+    pub(crate) fn load(document: &PDFDocument, dict: &Dict) -> Font {
+        let subtype = document.get(dict, "Subtype");
+        let composite = subtype.as_name() == Some("Type0");
+        let (font_dict, vertical) = if composite {
+            let descendant = document.get(dict, "DescendantFonts")
+                                     .as_array()
+                                     .and_then(|fonts| fonts.first())
+                                     .map(|font| document.resolve(font))
+                                     .unwrap_or(Object::Null);
+            let vertical = document.get(dict, "Encoding").as_name() == Some("Identity-V");
+            (descendant, vertical)
+        } else {
+            (Object::Dict(dict.clone().into()), false)
+        };
+        let font_dict = match font_dict.as_dict() {
+            Some(font_dict) => font_dict.clone(),
+            None => Dict::new(),
+        };
+
+        let descriptor = document.get(&font_dict, "FontDescriptor");
+        let descriptor = descriptor.as_dict();
+        let flags = descriptor.and_then(|descriptor| document.get(descriptor, "Flags").as_integer())
+                              .unwrap_or(0);
+
+        let mut font = Font {
+            glyphs: GlyphSource::Missing,
+            glyph_names: HashMap::new(),
+            has_encoding: false,
+            symbolic: flags & SYMBOLIC_FLAG != 0,
+            composite,
+            vertical,
+            cid_to_gid: None,
+            widths: HashMap::new(),
+            default_width: 0.0,
+            outline_cache: RefCell::new(HashMap::new()),
+        };
+
+        if composite {
+            font.load_composite_widths(document, &font_dict);
+            font.cid_to_gid = match document.get(&font_dict, "CIDToGIDMap") {
+                Object::Stream(stream) => document.decode_stream(&stream).ok().map(|map| {
+                    map.chunks(2)
+                       .map(|pair| (pair[0] as u16) << 8 | *pair.get(1).unwrap_or(&0) as u16)
+                       .collect()
+                }),
+                _ => None,
+            };
+        } else {
+            font.load_simple_widths(document, dict, descriptor);
+            font.load_encoding(document, dict);
+        }
+
+        font.glyphs = match descriptor {
+            Some(descriptor) => load_embedded_font(document, descriptor),
+            None => GlyphSource::Missing,
+        };
+        if let GlyphSource::Missing = font.glyphs {
+            let base_font = document.get(dict, "BaseFont");
+            font.glyphs = load_system_font(base_font.as_name().unwrap_or(""));
+        }
+        font
+    }
+
+    /// Returns whether glyph outlines are available.
+    #[inline]
+    pub(crate) fn is_supported(&self) -> bool {
+        match self.glyphs {
+            GlyphSource::Missing => false,
+            _ => true,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_vertical(&self) -> bool {
+        self.vertical
+    }
+
+    /// Splits a string into character codes.
+    pub(crate) fn char_codes(&self, string: &[u8]) -> Vec<u32> {
+        if self.composite {
+            string.chunks(2)
+                  .map(|pair| (pair[0] as u32) << 8 | *pair.get(1).unwrap_or(&0) as u32)
+                  .collect()
+        } else {
+            string.iter().map(|&byte| byte as u32).collect()
+        }
+    }
+
+    /// Returns whether word spacing applies to a character code, which is only true of the
+    /// single-byte code 32.
+    #[inline]
+    pub(crate) fn is_word_space(&self, code: u32) -> bool {
+        !self.composite && code == 32
+    }
+
+    /// Returns how far a glyph moves the text position, in text space, before spacing and
+    /// scaling.
+    pub(crate) fn advance(&self, code: u32) -> Vector2F {
+        if self.vertical {
+            vec2f(0.0, DEFAULT_VERTICAL_ADVANCE)
+        } else {
+            vec2f(self.width(code), 0.0)
+        }
+    }
+
+    /// Returns the position of a glyph's origin relative to the text position, in text space.
+    pub(crate) fn origin(&self, code: u32) -> Vector2F {
+        if self.vertical {
+            vec2f(-0.5 * self.width(code), -DEFAULT_VERTICAL_ORIGIN)
+        } else {
+            Vector2F::zero()
+        }
+    }
+
+    /// Returns the outline of the glyph a character code selects, in text space.
+    pub(crate) fn outline(&self, code: u32) -> Option<Outline> {
+        if let Some(outline) = self.outline_cache.borrow().get(&code) {
+            return outline.clone();
+        }
+        let outline = self.load_outline(code);
+        self.outline_cache.borrow_mut().insert(code, outline.clone());
+        outline
+    }
+
+    fn width(&self, code: u32) -> f32 {
+        self.widths.get(&code).cloned().unwrap_or(self.default_width)
+    }
+
+    fn load_outline(&self, code: u32) -> Option<Outline> {
+        match self.glyphs {
+            GlyphSource::Missing => None,
+            GlyphSource::Type1(ref font) => {
+                let glyph_name = match self.glyph_names.get(&code) {
+                    Some(glyph_name) => glyph_name.as_str(),
+                    None => match font.encoding {
+                        Some(ref encoding) => encoding.get(&(code as u8))?.as_str(),
+                        None => BaseEncoding::Standard.glyph_name(code as u8)?,
+                    },
+                };
+                font.outline(glyph_name)
+            }
+            GlyphSource::FontKit { ref font, scale } => {
+                let glyph_id = self.font_kit_glyph_id(font, code)?;
+                let mut builder = OutlineBuilder::new(&Transform2F::from_scale(scale));
+                font.outline(glyph_id, HintingOptions::None, &mut builder).ok()?;
+                Some(builder.build())
+            }
+        }
+    }
+
+    fn font_kit_glyph_id(&self, font: &DefaultLoader, code: u32) -> Option<u32> {
+        if self.composite {
+            return match self.cid_to_gid {
+                Some(ref cid_to_gid) => cid_to_gid.get(code as usize).map(|&gid| gid as u32),
+                None => Some(code),
+            };
+        }
+
+        // Nonsymbolic fonts are looked up by the Unicode value of their glyph names. Symbolic
+        // TrueType fonts conventionally map codes to the private use area.
+        if self.has_encoding || !self.symbolic {
+            let character = self.glyph_names
+                                .get(&code)
+                                .map(|name| name.as_str())
+                                .or_else(|| BaseEncoding::Standard.glyph_name(code as u8))
+                                .and_then(encoding::unicode_for_glyph_name);
+            if let Some(glyph_id) = character.and_then(|character| font.glyph_for_char(character)) {
+                return Some(glyph_id);
+            }
+        }
+        std::char::from_u32(0xf000 + code).and_then(|character| font.glyph_for_char(character))
+                                          .or_else(|| font.glyph_for_char(code as u8 as char))
+    }
+
+    fn load_simple_widths(&mut self,
+                          document: &PDFDocument,
+                          dict: &Dict,
+                          descriptor: Option<&Dict>) {
+        self.default_width = descriptor.and_then(|descriptor| {
+            document.get(descriptor, "MissingWidth").as_f32()
+        }).unwrap_or(0.0) / 1000.0;
+
+        let first_char = document.get(dict, "FirstChar").as_integer().unwrap_or(0);
+        if let Some(widths) = document.get(dict, "Widths").as_array() {
+            for (index, width) in widths.iter().enumerate() {
+                if let Some(width) = document.resolve(width).as_f32() {
+                    self.widths.insert((first_char + index as i64) as u32, width / 1000.0);
+                }
+            }
+        }
+    }
+
+    // Reads the `DW` and `W` entries of a CID font. `W` mixes two forms: `c [w1 w2 ...]` for
+    // consecutive CIDs and `c_first c_last w` for a range with the same width.
+    fn load_composite_widths(&mut self, document: &PDFDocument, dict: &Dict) {
+        self.default_width = document.get(dict, "DW").as_f32().unwrap_or(1000.0) / 1000.0;
+
+        let widths = document.get(dict, "W");
+        let widths: Vec<Object> = match widths.as_array() {
+            Some(widths) => widths.iter().map(|width| document.resolve(width)).collect(),
+            None => return,
+        };
+        let mut index = 0;
+        while index + 1 < widths.len() {
+            let first = match widths[index].as_integer() {
+                Some(first) => first as u32,
+                None => break,
+            };
+            if let Some(array) = widths[index + 1].as_array() {
+                for (offset, width) in array.iter().enumerate() {
+                    if let Some(width) = document.resolve(width).as_f32() {
+                        self.widths.insert(first + offset as u32, width / 1000.0);
+                    }
+                }
+                index += 2;
+                continue;
+            }
+
+            let (last, width) = match (widths[index + 1].as_integer(),
+                                       widths.get(index + 2).and_then(Object::as_f32)) {
+                (Some(last), Some(width)) => (last as u32, width / 1000.0),
+                _ => break,
+            };
+            // Guard against absurd ranges.
+            for cid in first..=last.min(first.saturating_add(65535)) {
+                self.widths.insert(cid, width);
+            }
+            index += 3;
+        }
+    }
+
+    // Reads a simple font's encoding: a base encoding name, optionally with differences.
+    fn load_encoding(&mut self, document: &PDFDocument, dict: &Dict) {
+        let encoding = document.get(dict, "Encoding");
+        let (base, differences) = match encoding {
+            Object::Name(ref name) => (BaseEncoding::from_name(name), Object::Null),
+            Object::Dict(ref encoding) => {
+                let base = document.get(encoding, "BaseEncoding");
+                (base.as_name().and_then(BaseEncoding::from_name),
+                 document.get(encoding, "Differences"))
+            }
+            _ => return,
+        };
+
+        if let Some(base) = base {
+            for code in 0..=255 {
+                if let Some(glyph_name) = base.glyph_name(code) {
+                    self.glyph_names.insert(code as u32, glyph_name.to_owned());
+                }
+            }
+        }
+        self.has_encoding = true;
+
+        let mut code = 0;
+        for difference in differences.as_array().unwrap_or(&[]) {
+            match document.resolve(difference) {
+                Object::Integer(new_code) => code = new_code as u32,
+                Object::Name(glyph_name) => {
+                    self.glyph_names.insert(code, glyph_name.to_string());
+                    code += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn load_embedded_font(document: &PDFDocument, descriptor: &Dict) -> GlyphSource {
+    if let Some(stream) = document.get(descriptor, "FontFile").as_stream() {
+        let cleartext_length = document.get(&stream.dict, "Length1")
+                                       .as_integer()
+                                       .map(|length| length as usize);
+        return match document.decode_stream(stream) {
+            Ok(data) => match Type1Font::parse(&data, cleartext_length) {
+                Some(font) => GlyphSource::Type1(font),
+                None => GlyphSource::Missing,
+            },
+            Err(_) => GlyphSource::Missing,
+        };
+    }
+
+    let stream = match document.get(descriptor, "FontFile2") {
+        Object::Stream(stream) => stream,
+        // Of the compressed formats, only OpenType can be loaded without a CFF parser.
+        _ => match document.get(descriptor, "FontFile3") {
+            Object::Stream(ref stream) if document.get(&stream.dict, "Subtype")
+                                                  .as_name() == Some("OpenType") => {
+                stream.clone()
+            }
+            _ => return GlyphSource::Missing,
+        },
+    };
+    match document.decode_stream(&stream) {
+        Ok(data) => font_kit_source(DefaultLoader::from_bytes(Arc::new(data), 0).ok()),
+        Err(_) => GlyphSource::Missing,
+    }
+}
+
+// Substitutes a system font for a font that isn't embedded, going by its name.
+fn load_system_font(base_font: &str) -> GlyphSource {
+    // Subset fonts have names like `ABCDEF+Times-Roman`.
+    let name = base_font.splitn(2, '+').last().unwrap_or("");
+    let family = if name.starts_with("Times") || name.contains("Serif") && !name.contains("Sans") {
+        FamilyName::Serif
+    } else if name.starts_with("Courier") || name.contains("Mono") {
+        FamilyName::Monospace
+    } else if name.starts_with("Symbol") || name.starts_with("ZapfDingbats") {
+        return GlyphSource::Missing;
+    } else {
+        FamilyName::SansSerif
+    };
+
+    let mut properties = Properties::new();
+    if name.contains("Bold") {
+        properties.weight = Weight::BOLD;
+    }
+    if name.contains("Italic") || name.contains("Oblique") {
+        properties.style = Style::Italic;
+    }
+    let font = SystemSource::new().select_best_match(&[family], &properties)
+                                  .ok()
+                                  .and_then(|handle| handle.load().ok());
+    font_kit_source(font)
+}
+
+fn font_kit_source(font: Option<DefaultLoader>) -> GlyphSource {
+    match font {
+        Some(font) => {
+            let scale = 1.0 / font.metrics().units_per_em as f32;
+            GlyphSource::FontKit { font, scale }
+        }
+        None => GlyphSource::Missing,
+    }
+}
+
+struct OutlineBuilder {
+    outline: Outline,
+    current_contour: Contour,
+    transform: Transform2F,
+}
+
+impl OutlineBuilder {
+    fn new(transform: &Transform2F) -> OutlineBuilder {
+        OutlineBuilder {
+            outline: Outline::new(),
+            current_contour: Contour::new(),
+            transform: *transform,
+        }
+    }
+
+    fn flush_current_contour(&mut self) {
+        if !self.current_contour.is_empty() {
+            self.outline.push_contour(mem::replace(&mut self.current_contour, Contour::new()));
+        }
+    }
+
+    fn build(mut self) -> Outline {
+        self.flush_current_contour();
+        self.outline
+    }
+}
+
+impl OutlineSink for OutlineBuilder {
+    fn move_to(&mut self, to: Vector2F) {
+        self.flush_current_contour();
+        self.current_contour.push_endpoint(self.transform * to);
+    }
+
+    fn line_to(&mut self, to: Vector2F) {
+        self.current_contour.push_endpoint(self.transform * to);
+    }
+
+    fn quadratic_curve_to(&mut self, ctrl: Vector2F, to: Vector2F) {
+        self.current_contour.push_quadratic(self.transform * ctrl, self.transform * to);
+    }
+
+    fn cubic_curve_to(&mut self, ctrl: LineSegment2F, to: Vector2F) {
+        self.current_contour.push_cubic(self.transform * ctrl.from(),
+                                        self.transform * ctrl.to(),
+                                        self.transform * to);
+    }
+
+    fn close(&mut self) {
+        self.current_contour.close();
+    }
+}
@@ -0,0 +1,271 @@
+// pathfinder/pdf/src/lib.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Renders the vector content of PDF pages to Pathfinder scenes.
+//!
+//! This is meant for displaying vector-heavy documents, not for full PDF compliance. The content
+//! stream interpreter handles path construction and painting, clipping, the graphics state
+//! (including constant alpha and blend modes from graphics state parameter dictionaries), the
+//! device, calibrated, ICC-based, and indexed color spaces, form XObjects, and text in embedded
+//! Type 1 and TrueType fonts. Images, shadings, patterns, soft masks, and CFF and Type 3 fonts
+//! aren't drawn; `PDFScene::result_flags` records when content was skipped.
+//!
+//! Files with damaged cross-reference tables are repaired by scanning for objects. Encrypted
+//! files aren't supported.
+//!
+//! Each page becomes its own scene, with a view box the size of the page's crop box in points,
+//! the Y axis pointing down, and the page's rotation applied.
+
+#[macro_use]
+extern crate bitflags;
+
+use crate::content::ContentInterpreter;
+use crate::object::Object;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_renderer::scene::Scene;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+
+pub use crate::document::PDFDocument;
+
+mod content;
+mod document;
+mod encoding;
+mod filter;
+mod font;
+mod object;
+mod parser;
+mod type1;
+
+pub struct PDFScene {
+    pub scene: Scene,
+    pub result_flags: BuildResultFlags,
+}
+
+bitflags! {
+    // NB: If you change this, make sure to update the `Display`
+    // implementation as well.
+    pub struct BuildResultFlags: u16 {
+        const UNSUPPORTED_OPERATOR               = 0x0001;
+        const UNSUPPORTED_FONT                   = 0x0002;
+        const UNSUPPORTED_IMAGE                  = 0x0004;
+        const UNSUPPORTED_SHADING                = 0x0008;
+        const UNSUPPORTED_PATTERN                = 0x0010;
+        const UNSUPPORTED_SOFT_MASK              = 0x0020;
+        const UNSUPPORTED_COLOR_SPACE            = 0x0040;
+        const INVALID_CONTENT                    = 0x0080;
+    }
+}
+
+/// The ways reading a PDF file can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PDFError {
+    /// The file couldn't be parsed. Holds the byte offset of the problem.
+    Syntax(usize),
+    /// Neither the cross-reference data nor a scan of the file led to a document catalog.
+    InvalidXref,
+    /// The page tree is missing or has a cycle.
+    InvalidPageTree,
+    /// The file is encrypted.
+    Encrypted,
+    /// A stream uses a filter that isn't supported.
+    UnsupportedFilter(String),
+    /// A page was requested that the document doesn't have.
+    PageOutOfRange(usize),
+}
+
+impl PDFScene {
+    #[inline]
+    pub fn from_page(document: &PDFDocument, page_index: usize) -> Result<PDFScene, PDFError> {
+        PDFScene::from_page_and_scene(document, page_index, Scene::new())
+    }
+
+    pub fn from_page_and_scene(document: &PDFDocument, page_index: usize, scene: Scene)
+                               -> Result<PDFScene, PDFError> {
+        let page = document.page(page_index).ok_or(PDFError::PageOutOfRange(page_index))?;
+        let visible_area = page.visible_area();
+        let page_size = document.page_size(page_index).unwrap_or(visible_area.size());
+
+        let mut scene = scene;
+        scene.set_view_box(RectF::new(Vector2F::zero(), page_size));
+
+        let transform = content::page_transform(visible_area, page.rotation);
+        let mut interpreter = ContentInterpreter::new(document, scene, transform);
+
+        // The contents may be split across several streams, which are concatenated.
+        let contents = match document.get(&page.dict, "Contents") {
+            Object::Array(streams) => {
+                streams.iter().map(|stream| document.resolve(stream)).collect()
+            }
+            object => vec![object],
+        };
+        let mut content = vec![];
+        for stream in contents {
+            let data = match stream.as_stream().map(|stream| document.decode_stream(stream)) {
+                Some(Ok(data)) => data,
+                None => continue,
+                Some(Err(_)) => {
+                    interpreter.result_flags.insert(BuildResultFlags::INVALID_CONTENT);
+                    continue;
+                }
+            };
+            content.extend_from_slice(&data);
+            content.push(b'\n');
+        }
+
+        let resources = page.resources.as_dict().cloned().unwrap_or_default();
+        interpreter.run(&content, &resources);
+
+        Ok(PDFScene { scene: interpreter.scene, result_flags: interpreter.result_flags })
+    }
+}
+
+impl Display for BuildResultFlags {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut first = true;
+        for (bit, name) in NAMES.iter().enumerate() {
+            if (self.bits() >> bit) & 1 == 0 {
+                continue;
+            }
+            if !first {
+                formatter.write_str(", ")?;
+            } else {
+                first = false;
+            }
+            formatter.write_str(name)?;
+        }
+
+        return Ok(());
+
+        // Must match the order in `BuildResultFlags`.
+        static NAMES: &'static [&'static str] = &[
+            "unsupported operator",
+            "font",
+            "image",
+            "shading",
+            "pattern",
+            "soft mask",
+            "color space",
+            "invalid content",
+        ];
+    }
+}
+
+impl Display for PDFError {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        match *self {
+            PDFError::Syntax(offset) => write!(formatter, "syntax error at byte {}", offset),
+            PDFError::InvalidXref => write!(formatter, "no usable cross-reference data"),
+            PDFError::InvalidPageTree => write!(formatter, "invalid page tree"),
+            PDFError::Encrypted => write!(formatter, "encrypted files aren't supported"),
+            PDFError::UnsupportedFilter(ref filter) => {
+                write!(formatter, "unsupported filter: {}", filter)
+            }
+            PDFError::PageOutOfRange(index) => write!(formatter, "no page at index {}", index),
+        }
+    }
+}
+
+impl Error for PDFError {}
+
+#[cfg(test)]
+mod test {
+    use super::{BuildResultFlags, PDFDocument, PDFError, PDFScene};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::effects::BlendMode;
+    use pathfinder_content::fill::FillRule;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+    use pathfinder_renderer::scene::DrawPathId;
+
+    // Writes a file with the given objects, numbered from 1, and a correct cross-reference
+    // table.
+    fn build_pdf(objects: &[String]) -> Vec<u8> {
+        let mut data = b"%PDF-1.4\n".to_vec();
+        let mut offsets = vec![];
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(data.len());
+            data.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).as_bytes());
+        }
+        let xref = data.len();
+        data.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n",
+                                       objects.len() + 1).as_bytes());
+        for offset in offsets {
+            data.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        data.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF",
+                                       objects.len() + 1,
+                                       xref).as_bytes());
+        data
+    }
+
+    fn page_objects(content: &str, page_attributes: &str) -> Vec<String> {
+        vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 /MediaBox [0 0 200 100] >>".to_owned(),
+            format!("<< /Type /Page /Parent 2 0 R /Contents 4 0 R {} \
+                     /Resources << /ExtGState << /Half << /ca 0.2 /BM /Multiply >> >> >> >>",
+                    page_attributes),
+            format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content),
+        ]
+    }
+
+    const CONTENT: &'static str = "1 0 0 rg 10 10 50 30 re f
+q 0 0 1 RG 4 w 100 50 m 190 50 l S Q
+/Half gs 0 g 0 0 m 20 0 l 20 20 l h f*
+BI /W 1 /H 1 /CS /G /BPC 8 ID \x7f EI";
+
+    #[test]
+    fn test_page() {
+        let document = PDFDocument::from_bytes(build_pdf(&page_objects(CONTENT, ""))).unwrap();
+        assert_eq!(document.page_count(), 1);
+        assert_eq!(document.page_size(0), Some(vec2f(200.0, 100.0)));
+
+        let pdf = PDFScene::from_page(&document, 0).unwrap();
+        assert_eq!(pdf.result_flags, BuildResultFlags::UNSUPPORTED_IMAGE);
+        assert_eq!(pdf.scene.view_box(), RectF::new(vec2f(0.0, 0.0), vec2f(200.0, 100.0)));
+        assert_eq!(pdf.scene.draw_path_count(), 3);
+
+        // The Y axis is flipped.
+        let rect = pdf.scene.get_draw_path(DrawPathId(0));
+        assert_eq!(rect.outline.bounds(), RectF::from_points(vec2f(10.0, 60.0), vec2f(60.0, 90.0)));
+        assert_eq!(pdf.scene.get_paint(rect.paint).base_color(), ColorU::new(255, 0, 0, 255));
+
+        let triangle = pdf.scene.get_draw_path(DrawPathId(2));
+        assert_eq!(triangle.fill_rule, FillRule::EvenOdd);
+        assert_eq!(triangle.blend_mode, BlendMode::Multiply);
+        assert_eq!(pdf.scene.get_paint(triangle.paint).base_color().a, 51);
+
+        assert_eq!(PDFScene::from_page(&document, 1).err(), Some(PDFError::PageOutOfRange(1)));
+    }
+
+    #[test]
+    fn test_rotation_and_repair() {
+        let mut data = build_pdf(&page_objects("10 10 50 30 re f", "/Rotate 90"));
+
+        // Point `startxref` at garbage, so the file has to be scanned.
+        let startxref = data.windows(9).rposition(|window| window == b"startxref").unwrap();
+        data.truncate(startxref);
+        data.extend_from_slice(b"startxref\n9999\n%%EOF");
+
+        let document = PDFDocument::from_bytes(data).unwrap();
+        assert_eq!(document.page_size(0), Some(vec2f(100.0, 200.0)));
+        let pdf = PDFScene::from_page(&document, 0).unwrap();
+        let rect = &pdf.scene.get_draw_path(DrawPathId(0)).outline;
+        assert_eq!(rect.bounds(), RectF::from_points(vec2f(10.0, 10.0), vec2f(40.0, 60.0)));
+
+        assert!(PDFDocument::from_bytes(b"not a PDF".to_vec()).is_err());
+    }
+}
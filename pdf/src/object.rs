@@ -0,0 +1,116 @@
+// pathfinder/pdf/src/object.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! PDF objects.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub(crate) type Dict = HashMap<Rc<str>, Object>;
+
+/// The number and generation of an indirect object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct ObjectId {
+    pub(crate) number: u32,
+    pub(crate) generation: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Object {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    String(Rc<Vec<u8>>),
+    Name(Rc<str>),
+    Array(Rc<Vec<Object>>),
+    Dict(Rc<Dict>),
+    Stream(Rc<Stream>),
+    Reference(ObjectId),
+}
+
+/// A stream, with its data still encoded.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Stream {
+    pub(crate) dict: Dict,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Object {
+    #[inline]
+    pub(crate) fn as_number(&self) -> Option<f64> {
+        match *self {
+            Object::Integer(value) => Some(value as f64),
+            Object::Real(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn as_f32(&self) -> Option<f32> {
+        self.as_number().map(|value| value as f32)
+    }
+
+    #[inline]
+    pub(crate) fn as_integer(&self) -> Option<i64> {
+        match *self {
+            Object::Integer(value) => Some(value),
+            Object::Real(value) if value.fract() == 0.0 => Some(value as i64),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn as_name(&self) -> Option<&str> {
+        match *self {
+            Object::Name(ref name) => Some(name),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn as_string(&self) -> Option<&[u8]> {
+        match *self {
+            Object::String(ref string) => Some(string),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn as_array(&self) -> Option<&[Object]> {
+        match *self {
+            Object::Array(ref array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// Returns the dictionary of a dictionary or stream.
+    #[inline]
+    pub(crate) fn as_dict(&self) -> Option<&Dict> {
+        match *self {
+            Object::Dict(ref dict) => Some(dict),
+            Object::Stream(ref stream) => Some(&stream.dict),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn as_stream(&self) -> Option<&Rc<Stream>> {
+        match *self {
+            Object::Stream(ref stream) => Some(stream),
+            _ => None,
+        }
+    }
+
+    /// Returns the numbers in an array, if every element is a number.
+    pub(crate) fn as_numbers(&self) -> Option<Vec<f32>> {
+        self.as_array()?.iter().map(Object::as_f32).collect()
+    }
+}
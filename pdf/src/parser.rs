@@ -0,0 +1,404 @@
+// pathfinder/pdf/src/parser.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parses PDF object syntax, in files and in content streams.
+
+use crate::PDFError;
+use crate::object::{Dict, Object, ObjectId};
+use std::rc::Rc;
+
+pub(crate) struct Parser<'a> {
+    data: &'a [u8],
+    pub(crate) position: usize,
+}
+
+enum Token<'a> {
+    Object(Object),
+    ArrayStart,
+    ArrayEnd,
+    DictStart,
+    DictEnd,
+    Keyword(&'a [u8]),
+}
+
+/// An element of a content stream.
+pub(crate) enum ContentItem<'a> {
+    Operand(Object),
+    Operator(&'a [u8]),
+}
+
+impl<'a> Parser<'a> {
+    #[inline]
+    pub(crate) fn new(data: &'a [u8], position: usize) -> Parser<'a> {
+        Parser { data, position }
+    }
+
+    /// Parses a complete object, including indirect references.
+    pub(crate) fn parse_object(&mut self) -> Result<Object, PDFError> {
+        let token = self.next_token()?.ok_or(PDFError::Syntax(self.position))?;
+        self.object_from_token(token, true)
+    }
+
+    /// Parses a keyword, failing if it isn't the expected one.
+    pub(crate) fn expect_keyword(&mut self, expected: &[u8]) -> Result<(), PDFError> {
+        let start = self.position;
+        match self.next_token()? {
+            Some(Token::Keyword(keyword)) if keyword == expected => Ok(()),
+            _ => Err(PDFError::Syntax(start)),
+        }
+    }
+
+    /// Parses a keyword if it's next, returning whether it was.
+    pub(crate) fn try_keyword(&mut self, expected: &[u8]) -> bool {
+        let start = self.position;
+        match self.next_token() {
+            Ok(Some(Token::Keyword(keyword))) if keyword == expected => true,
+            _ => {
+                self.position = start;
+                false
+            }
+        }
+    }
+
+    pub(crate) fn parse_integer(&mut self) -> Result<i64, PDFError> {
+        let start = self.position;
+        match self.next_token()? {
+            Some(Token::Object(Object::Integer(value))) => Ok(value),
+            _ => Err(PDFError::Syntax(start)),
+        }
+    }
+
+    /// Returns the next operand or operator of a content stream.
+    pub(crate) fn next_content_item(&mut self) -> Result<Option<ContentItem<'a>>, PDFError> {
+        let token = match self.next_token()? {
+            None => return Ok(None),
+            Some(token) => token,
+        };
+        match token {
+            Token::Keyword(b"true") => Ok(Some(ContentItem::Operand(Object::Bool(true)))),
+            Token::Keyword(b"false") => Ok(Some(ContentItem::Operand(Object::Bool(false)))),
+            Token::Keyword(b"null") => Ok(Some(ContentItem::Operand(Object::Null))),
+            Token::Keyword(keyword) => Ok(Some(ContentItem::Operator(keyword))),
+            token => self.object_from_token(token, false).map(|object| {
+                Some(ContentItem::Operand(object))
+            }),
+        }
+    }
+
+    /// Skips an inline image, after its `BI` operator, returning its dictionary.
+    pub(crate) fn skip_inline_image(&mut self) -> Result<Dict, PDFError> {
+        let mut dict = Dict::new();
+        loop {
+            let start = self.position;
+            match self.next_token()?.ok_or(PDFError::Syntax(start))? {
+                Token::Keyword(b"ID") => break,
+                Token::Object(Object::Name(key)) => {
+                    let value = self.parse_object()?;
+                    dict.insert(key, value);
+                }
+                _ => return Err(PDFError::Syntax(start)),
+            }
+        }
+
+        // The data starts after a single whitespace byte and ends at an `EI` surrounded by
+        // whitespace.
+        let start = self.position + 1;
+        let mut position = start;
+        while position + 2 <= self.data.len() {
+            if &self.data[position..(position + 2)] == b"EI" &&
+                    is_whitespace(self.data[position - 1]) &&
+                    self.data.get(position + 2).map_or(true, |&byte| is_whitespace(byte)) {
+                self.position = position + 2;
+                return Ok(dict);
+            }
+            position += 1;
+        }
+        Err(PDFError::Syntax(start))
+    }
+
+    fn object_from_token(&mut self, token: Token<'a>, allow_references: bool)
+                         -> Result<Object, PDFError> {
+        match token {
+            Token::Object(Object::Integer(number)) if allow_references => {
+                // Look ahead for `generation R`.
+                let start = self.position;
+                if let Ok(Some(Token::Object(Object::Integer(generation)))) = self.next_token() {
+                    if let Ok(Some(Token::Keyword(b"R"))) = self.next_token() {
+                        if number >= 0 && number <= u32::MAX as i64 && generation >= 0 {
+                            let (number, generation) = (number as u32, generation as u16);
+                            return Ok(Object::Reference(ObjectId { number, generation }));
+                        }
+                    }
+                }
+                self.position = start;
+                Ok(Object::Integer(number))
+            }
+            Token::Object(object) => Ok(object),
+            Token::Keyword(b"true") => Ok(Object::Bool(true)),
+            Token::Keyword(b"false") => Ok(Object::Bool(false)),
+            Token::Keyword(b"null") => Ok(Object::Null),
+            Token::ArrayStart => {
+                let mut array = vec![];
+                loop {
+                    let start = self.position;
+                    match self.next_token()?.ok_or(PDFError::Syntax(start))? {
+                        Token::ArrayEnd => return Ok(Object::Array(Rc::new(array))),
+                        token => array.push(self.object_from_token(token, allow_references)?),
+                    }
+                }
+            }
+            Token::DictStart => {
+                let mut dict = Dict::new();
+                loop {
+                    let start = self.position;
+                    match self.next_token()?.ok_or(PDFError::Syntax(start))? {
+                        Token::DictEnd => return Ok(Object::Dict(Rc::new(dict))),
+                        Token::Object(Object::Name(key)) => {
+                            let token = self.next_token()?.ok_or(PDFError::Syntax(start))?;
+                            let value = self.object_from_token(token, allow_references)?;
+                            dict.insert(key, value);
+                        }
+                        _ => return Err(PDFError::Syntax(start)),
+                    }
+                }
+            }
+            Token::ArrayEnd | Token::DictEnd | Token::Keyword(_) => {
+                Err(PDFError::Syntax(self.position))
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token<'a>>, PDFError> {
+        self.skip_whitespace_and_comments();
+        let start = self.position;
+        let byte = match self.data.get(start) {
+            None => return Ok(None),
+            Some(&byte) => byte,
+        };
+        self.position += 1;
+
+        let next = self.data.get(self.position).cloned();
+        match byte {
+            b'[' => Ok(Some(Token::ArrayStart)),
+            b']' => Ok(Some(Token::ArrayEnd)),
+            b'<' if next == Some(b'<') => {
+                self.position += 1;
+                Ok(Some(Token::DictStart))
+            }
+            b'>' if next == Some(b'>') => {
+                self.position += 1;
+                Ok(Some(Token::DictEnd))
+            }
+            b'<' => self.read_hex_string().map(|string| Some(Token::Object(string))),
+            b'(' => self.read_string().map(|string| Some(Token::Object(string))),
+            b'/' => {
+                let name = decode_name(self.read_regular());
+                Ok(Some(Token::Object(Object::Name(name))))
+            }
+            _ if is_delimiter(byte) => Err(PDFError::Syntax(start)),
+            _ => {
+                self.position = start;
+                let token = self.read_regular();
+                match byte {
+                    b'0'..=b'9' | b'+' | b'-' | b'.' => {
+                        Ok(Some(Token::Object(parse_number(token))))
+                    }
+                    _ => Ok(Some(Token::Keyword(token))),
+                }
+            }
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&byte) = self.data.get(self.position) {
+            if byte == b'%' {
+                while let Some(&byte) = self.data.get(self.position) {
+                    if byte == b'\n' || byte == b'\r' {
+                        break;
+                    }
+                    self.position += 1;
+                }
+            } else if is_whitespace(byte) {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn read_regular(&mut self) -> &'a [u8] {
+        let start = self.position;
+        while let Some(&byte) = self.data.get(self.position) {
+            if is_whitespace(byte) || is_delimiter(byte) {
+                break;
+            }
+            self.position += 1;
+        }
+        &self.data[start..self.position]
+    }
+
+    // Reads a literal string, after its opening parenthesis.
+    fn read_string(&mut self) -> Result<Object, PDFError> {
+        let start = self.position - 1;
+        let (mut string, mut depth) = (vec![], 0);
+        loop {
+            let byte = *self.data.get(self.position).ok_or(PDFError::Syntax(start))?;
+            self.position += 1;
+            match byte {
+                b'(' => depth += 1,
+                b')' if depth == 0 => return Ok(Object::String(Rc::new(string))),
+                b')' => depth -= 1,
+                b'\\' => {
+                    let escaped = *self.data.get(self.position).ok_or(PDFError::Syntax(start))?;
+                    self.position += 1;
+                    match escaped {
+                        b'n' => string.push(b'\n'),
+                        b'r' => string.push(b'\r'),
+                        b't' => string.push(b'\t'),
+                        b'b' => string.push(0x08),
+                        b'f' => string.push(0x0c),
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.data.get(self.position) {
+                                    Some(&digit @ b'0'..=b'7') => {
+                                        value = value * 8 + (digit - b'0') as u32;
+                                        self.position += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            string.push(value as u8);
+                        }
+                        // A backslash before a line break continues the string on the next line.
+                        b'\r' => {
+                            if self.data.get(self.position) == Some(&b'\n') {
+                                self.position += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        _ => string.push(escaped),
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            string.push(byte);
+        }
+    }
+
+    // Reads a hexadecimal string, after its opening angle bracket.
+    fn read_hex_string(&mut self) -> Result<Object, PDFError> {
+        let start = self.position - 1;
+        let mut digits = vec![];
+        loop {
+            let byte = *self.data.get(self.position).ok_or(PDFError::Syntax(start))?;
+            self.position += 1;
+            match byte {
+                b'>' => break,
+                _ if is_whitespace(byte) => {}
+                _ => {
+                    let digit = (byte as char).to_digit(16).ok_or(PDFError::Syntax(start))?;
+                    digits.push(digit as u8);
+                }
+            }
+        }
+        // An odd final digit is padded with zero.
+        let string = digits.chunks(2)
+                           .map(|pair| pair[0] << 4 | pair.get(1).cloned().unwrap_or(0))
+                           .collect();
+        Ok(Object::String(Rc::new(string)))
+    }
+}
+
+// Readers are expected to tolerate malformed numbers, which we read as zero.
+fn parse_number(token: &[u8]) -> Object {
+    let text = String::from_utf8_lossy(token);
+    if !token.contains(&b'.') {
+        if let Ok(value) = text.parse() {
+            return Object::Integer(value);
+        }
+    }
+    Object::Real(text.parse().unwrap_or(0.0))
+}
+
+// Expands the `#xx` escapes in a name.
+fn decode_name(token: &[u8]) -> Rc<str> {
+    let mut name = Vec::with_capacity(token.len());
+    let mut index = 0;
+    while index < token.len() {
+        if token[index] == b'#' && index + 2 < token.len() {
+            let digits = &token[(index + 1)..(index + 3)];
+            if let Some(byte) = std::str::from_utf8(digits)
+                                     .ok()
+                                     .and_then(|digits| u8::from_str_radix(digits, 16).ok()) {
+                name.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        name.push(token[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&name).into()
+}
+
+#[inline]
+pub(crate) fn is_whitespace(byte: u8) -> bool {
+    match byte {
+        b' ' | b'\t' | b'\r' | b'\n' | 0x0c | 0 => true,
+        _ => false,
+    }
+}
+
+#[inline]
+fn is_delimiter(byte: u8) -> bool {
+    match byte {
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%' => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ContentItem, Parser};
+    use crate::object::{Object, ObjectId};
+
+    #[test]
+    fn test_objects() {
+        let source = b"<< /Type /Page /Kids [1 0 R 2 0 R] /Scale -.5 /Name /A#20B >>";
+        let object = Parser::new(source, 0).parse_object().unwrap();
+        let dict = object.as_dict().unwrap();
+        assert_eq!(dict["Type"].as_name(), Some("Page"));
+        assert_eq!(dict["Kids"].as_array().unwrap()[1],
+                   Object::Reference(ObjectId { number: 2, generation: 0 }));
+        assert_eq!(dict["Scale"].as_number(), Some(-0.5));
+        assert_eq!(dict["Name"].as_name(), Some("A B"));
+        assert_eq!(dict["Kids"].as_array().unwrap().len(), 2);
+        assert_eq!(Parser::new(b"(x\\(y\\))", 0).parse_object().unwrap().as_string(),
+                   Some(&b"x(y)"[..]));
+    }
+
+    #[test]
+    fn test_content_items() {
+        let source = b"1 0 0 RG [(A) -120 (B)] TJ BI /W 1 /H 1 ID \x00\xff EI Q";
+        let mut parser = Parser::new(source, 0);
+        let mut operators = vec![];
+        while let Some(item) = parser.next_content_item().unwrap() {
+            if let ContentItem::Operator(operator) = item {
+                if operator == b"BI" {
+                    parser.skip_inline_image().unwrap();
+                }
+                operators.push(operator.to_vec());
+            }
+        }
+        assert_eq!(operators, vec![b"RG".to_vec(), b"TJ".to_vec(), b"BI".to_vec(), b"Q".to_vec()]);
+    }
+}
@@ -0,0 +1,563 @@
+// pathfinder/pdf/src/type1.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Embedded Type 1 fonts.
+//!
+//! The encrypted private dictionary is decrypted and scanned for the subroutines and charstrings,
+//! and the charstrings are run to produce outlines. Hints are ignored.
+
+use crate::encoding::BaseEncoding;
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use std::collections::HashMap;
+use std::mem;
+use std::str;
+
+const EEXEC_KEY: u16 = 55665;
+const CHARSTRING_KEY: u16 = 4330;
+
+// Charstrings that nest subroutine calls deeper than this are treated as broken.
+const MAX_SUBROUTINE_DEPTH: u32 = 10;
+
+pub(crate) struct Type1Font {
+    /// Maps glyph space to text space.
+    pub(crate) font_matrix: Transform2F,
+    /// The font's built-in encoding, or `None` if it uses `StandardEncoding`.
+    pub(crate) encoding: Option<HashMap<u8, String>>,
+    subrs: Vec<Vec<u8>>,
+    char_strings: HashMap<String, Vec<u8>>,
+}
+
+impl Type1Font {
+    /// Parses a font program. `cleartext_length` is the `Length1` entry of the font file stream,
+    /// if it has one.
+    pub(crate) fn parse(data: &[u8], cleartext_length: Option<usize>) -> Option<Type1Font> {
+        let data = strip_pfb_headers(data);
+        let eexec = find(&data, b"eexec")?;
+        let (cleartext, encrypted) = match cleartext_length {
+            Some(length) if length > eexec && length <= data.len() => data.split_at(length),
+            _ => {
+                let mut start = eexec + b"eexec".len();
+                while data.get(start).map_or(false, |byte| byte.is_ascii_whitespace()) {
+                    start += 1;
+                }
+                data.split_at(start)
+            }
+        };
+
+        let encrypted = if encrypted.len() >= 4 &&
+                encrypted[..4].iter().all(|byte| byte.is_ascii_hexdigit()) {
+            decode_hex(encrypted)
+        } else {
+            encrypted.to_vec()
+        };
+        let private = decrypt(&encrypted, EEXEC_KEY, 4);
+
+        let mut font = Type1Font {
+            font_matrix: parse_font_matrix(cleartext),
+            encoding: parse_encoding(cleartext),
+            subrs: vec![],
+            char_strings: HashMap::new(),
+        };
+        font.parse_private(&private);
+        if font.char_strings.is_empty() {
+            None
+        } else {
+            Some(font)
+        }
+    }
+
+    /// Returns the outline of a glyph, in text space.
+    pub(crate) fn outline(&self, glyph_name: &str) -> Option<Outline> {
+        let mut builder = GlyphBuilder::new();
+        builder.run_glyph(self, glyph_name, Vector2F::zero(), 0)?;
+        Some(builder.build().transformed(&self.font_matrix))
+    }
+
+    // Finds the subroutines and charstrings. Each is introduced by a length and the `RD` or `-|`
+    // operator, which reads that many bytes of binary data.
+    fn parse_private(&mut self, private: &[u8]) {
+        let len_iv = find(private, b"/lenIV").and_then(|position| {
+            next_token(private, position + b"/lenIV".len())
+        }).and_then(|(token, _)| str::from_utf8(token).ok()?.parse::<i32>().ok()).unwrap_or(4);
+
+        let (mut in_char_strings, mut tokens): (bool, Vec<&[u8]>) = (false, vec![]);
+        let mut position = 0;
+        while let Some((token, end)) = next_token(private, position) {
+            position = end;
+            if token == b"/CharStrings" {
+                in_char_strings = true;
+            }
+            if token != b"RD" && token != b"-|" {
+                tokens.push(token);
+                continue;
+            }
+
+            // The binary data starts after a single space.
+            let length = tokens.last()
+                               .and_then(|length| str::from_utf8(length).ok())
+                               .and_then(|length| length.parse::<usize>().ok());
+            let start = position + 1;
+            let data = match length.and_then(|length| private.get(start..(start + length))) {
+                Some(data) => data,
+                None => break,
+            };
+            position = start + data.len();
+
+            let data = if len_iv < 0 {
+                data.to_vec()
+            } else {
+                decrypt(data, CHARSTRING_KEY, len_iv as usize)
+            };
+            let key = tokens.len().checked_sub(2).map(|index| tokens[index]);
+            match key {
+                Some(name) if in_char_strings && name.starts_with(b"/") => {
+                    let name = String::from_utf8_lossy(&name[1..]).into_owned();
+                    self.char_strings.insert(name, data);
+                }
+                Some(index) if !in_char_strings => {
+                    let index = str::from_utf8(index).ok()
+                                                     .and_then(|index| index.parse::<usize>().ok());
+                    if let Some(index) = index.filter(|&index| index < 65536) {
+                        if self.subrs.len() <= index {
+                            self.subrs.resize(index + 1, vec![]);
+                        }
+                        self.subrs[index] = data;
+                    }
+                }
+                _ => {}
+            }
+            tokens.clear();
+        }
+    }
+}
+
+struct GlyphBuilder {
+    outline: Outline,
+    contour: Contour,
+    point: Vector2F,
+    stack: Vec<f32>,
+    // Values that `callothersubr` leaves for `pop`.
+    other_subr_results: Vec<f32>,
+    flex_points: Option<Vec<Vector2F>>,
+}
+
+impl GlyphBuilder {
+    fn new() -> GlyphBuilder {
+        GlyphBuilder {
+            outline: Outline::new(),
+            contour: Contour::new(),
+            point: Vector2F::zero(),
+            stack: vec![],
+            other_subr_results: vec![],
+            flex_points: None,
+        }
+    }
+
+    fn build(mut self) -> Outline {
+        self.flush_contour();
+        self.outline
+    }
+
+    fn flush_contour(&mut self) {
+        if !self.contour.is_empty() {
+            self.outline.push_contour(mem::replace(&mut self.contour, Contour::new()));
+        }
+    }
+
+    // Runs a glyph's charstring with its origin at `offset`. Returns `None` if the glyph
+    // doesn't exist.
+    fn run_glyph(&mut self, font: &Type1Font, glyph_name: &str, offset: Vector2F, seac_depth: u32)
+                 -> Option<()> {
+        let char_string = font.char_strings.get(glyph_name)?;
+        self.stack.clear();
+        let mut origin = offset;
+        let _ = self.run(font, char_string, &mut origin, 0, seac_depth);
+        Some(())
+    }
+
+    // Returns `Err` to stop at `endchar` or a malformed charstring, `Ok` at `return`.
+    fn run(&mut self,
+           font: &Type1Font,
+           char_string: &[u8],
+           origin: &mut Vector2F,
+           depth: u32,
+           seac_depth: u32)
+           -> Result<(), ()> {
+        if depth > MAX_SUBROUTINE_DEPTH {
+            return Err(());
+        }
+
+        let mut index = 0;
+        while let Some(&byte) = char_string.get(index) {
+            index += 1;
+            let operand = |offset: usize| char_string.get(index + offset).cloned().ok_or(());
+            match byte {
+                32..=246 => {
+                    self.stack.push(byte as f32 - 139.0);
+                    continue;
+                }
+                247..=250 => {
+                    let value = (byte as i32 - 247) * 256 + operand(0)? as i32 + 108;
+                    self.stack.push(value as f32);
+                    index += 1;
+                    continue;
+                }
+                251..=254 => {
+                    let value = -(byte as i32 - 251) * 256 - operand(0)? as i32 - 108;
+                    self.stack.push(value as f32);
+                    index += 1;
+                    continue;
+                }
+                255 => {
+                    let bytes = [operand(0)?, operand(1)?, operand(2)?, operand(3)?];
+                    self.stack.push(i32::from_be_bytes(bytes) as f32);
+                    index += 4;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let stack = mem::replace(&mut self.stack, vec![]);
+            let arg = |index: usize| stack.get(index).cloned().ok_or(());
+            match byte {
+                // hstem, vstem
+                1 | 3 => {}
+                // vmoveto, rmoveto, hmoveto
+                4 => self.move_by(vec2f(0.0, arg(0)?)),
+                21 => self.move_by(vec2f(arg(0)?, arg(1)?)),
+                22 => self.move_by(vec2f(arg(0)?, 0.0)),
+                // rlineto, hlineto, vlineto
+                5 => self.line_by(vec2f(arg(0)?, arg(1)?)),
+                6 => self.line_by(vec2f(arg(0)?, 0.0)),
+                7 => self.line_by(vec2f(0.0, arg(0)?)),
+                // rrcurveto, vhcurveto, hvcurveto
+                8 => {
+                    self.curve_by(vec2f(arg(0)?, arg(1)?),
+                                  vec2f(arg(2)?, arg(3)?),
+                                  vec2f(arg(4)?, arg(5)?))
+                }
+                30 => {
+                    self.curve_by(vec2f(0.0, arg(0)?),
+                                  vec2f(arg(1)?, arg(2)?),
+                                  vec2f(arg(3)?, 0.0))
+                }
+                31 => {
+                    self.curve_by(vec2f(arg(0)?, 0.0),
+                                  vec2f(arg(1)?, arg(2)?),
+                                  vec2f(0.0, arg(3)?))
+                }
+                // closepath
+                9 => {
+                    self.contour.close();
+                    self.flush_contour();
+                }
+                // callsubr
+                10 => {
+                    let subr = *stack.last().ok_or(())? as usize;
+                    self.stack = stack[..(stack.len() - 1)].to_vec();
+                    let subr = font.subrs.get(subr).ok_or(())?;
+                    self.run(font, subr, origin, depth + 1, seac_depth)?;
+                    continue;
+                }
+                // return
+                11 => {
+                    self.stack = stack;
+                    return Ok(());
+                }
+                // hsbw
+                13 => {
+                    *origin = *origin + vec2f(arg(0)?, 0.0);
+                    self.point = *origin;
+                }
+                // endchar
+                14 => {
+                    self.flush_contour();
+                    return Err(());
+                }
+                12 => {
+                    let escape = operand(0)?;
+                    index += 1;
+                    self.stack = stack;
+                    self.run_escape(font, escape, origin, seac_depth)?;
+                    continue;
+                }
+                _ => return Err(()),
+            }
+        }
+        Ok(())
+    }
+
+    fn run_escape(&mut self,
+                  font: &Type1Font,
+                  escape: u8,
+                  origin: &mut Vector2F,
+                  seac_depth: u32)
+                  -> Result<(), ()> {
+        let stack = mem::replace(&mut self.stack, vec![]);
+        let arg = |index: usize| stack.get(index).cloned().ok_or(());
+        match escape {
+            // dotsection, vstem3, hstem3
+            0 | 1 | 2 => {}
+            // seac: an accented character made of two standard glyphs
+            6 => {
+                if seac_depth > 0 {
+                    return Err(());
+                }
+                let (accent_bearing, accent_offset) = (arg(0)?, vec2f(arg(1)?, arg(2)?));
+                let glyph_name = |code: f32| BaseEncoding::Standard.glyph_name(code as u8);
+                let (base, accent) = (glyph_name(arg(3)?).ok_or(())?,
+                                      glyph_name(arg(4)?).ok_or(())?);
+                let composite_bearing = origin.x();
+                self.flush_contour();
+                self.run_glyph(font, base, Vector2F::zero(), 1);
+                self.flush_contour();
+                let accent_origin = accent_offset + vec2f(composite_bearing - accent_bearing, 0.0);
+                self.run_glyph(font, accent, accent_origin, 1);
+                self.flush_contour();
+                return Err(());
+            }
+            // sbw
+            7 => {
+                *origin = *origin + vec2f(arg(0)?, arg(1)?);
+                self.point = *origin;
+            }
+            // div
+            12 => {
+                let (dividend, divisor) = (arg(stack.len().wrapping_sub(2))?,
+                                           arg(stack.len() - 1)?);
+                self.stack = stack[..(stack.len() - 2)].to_vec();
+                self.stack.push(if divisor == 0.0 { 0.0 } else { dividend / divisor });
+                return Ok(());
+            }
+            // callothersubr
+            16 => {
+                let count = stack.len();
+                let (subr, arg_count) = (arg(count.wrapping_sub(1))? as i32,
+                                         arg(count.wrapping_sub(2))? as usize);
+                let args_start = count.checked_sub(2 + arg_count).ok_or(())?;
+                let args = stack[args_start..(count - 2)].to_vec();
+                self.stack = stack[..args_start].to_vec();
+                self.call_other_subr(subr, args);
+                return Ok(());
+            }
+            // pop
+            17 => {
+                self.stack = stack;
+                let value = self.other_subr_results.pop().unwrap_or(0.0);
+                self.stack.push(value);
+                return Ok(());
+            }
+            // setcurrentpoint
+            33 => self.point = vec2f(arg(0)?, arg(1)?),
+            _ => return Err(()),
+        }
+        Ok(())
+    }
+
+    // Implements the standard `OtherSubrs`: flex, and hint replacement, which leaves its
+    // argument for `pop`.
+    fn call_other_subr(&mut self, subr: i32, args: Vec<f32>) {
+        match subr {
+            1 => self.flex_points = Some(vec![]),
+            2 => {}
+            0 => {
+                let points = self.flex_points.take().unwrap_or(vec![]);
+                if points.len() >= 7 {
+                    self.contour.push_cubic(points[1], points[2], points[3]);
+                    self.contour.push_cubic(points[4], points[5], points[6]);
+                    self.point = points[6];
+                }
+                // `pop pop setcurrentpoint` follows.
+                self.other_subr_results = vec![self.point.y(), self.point.x()];
+            }
+            _ => {
+                self.other_subr_results = args;
+                self.other_subr_results.reverse();
+            }
+        }
+    }
+
+    fn move_by(&mut self, delta: Vector2F) {
+        self.point = self.point + delta;
+        if let Some(ref mut flex_points) = self.flex_points {
+            flex_points.push(self.point);
+            return;
+        }
+        self.flush_contour();
+        self.contour.push_endpoint(self.point);
+    }
+
+    fn line_by(&mut self, delta: Vector2F) {
+        self.point = self.point + delta;
+        self.contour.push_endpoint(self.point);
+    }
+
+    fn curve_by(&mut self, delta_0: Vector2F, delta_1: Vector2F, delta_2: Vector2F) {
+        let ctrl_0 = self.point + delta_0;
+        let ctrl_1 = ctrl_0 + delta_1;
+        self.point = ctrl_1 + delta_2;
+        self.contour.push_cubic(ctrl_0, ctrl_1, self.point);
+    }
+}
+
+// Removes the segment headers of PFB files, which some writers embed as is.
+fn strip_pfb_headers(data: &[u8]) -> Vec<u8> {
+    if !data.starts_with(&[0x80]) {
+        return data.to_vec();
+    }
+    let mut output = vec![];
+    let mut position = 0;
+    while data.get(position) == Some(&0x80) {
+        let kind = data.get(position + 1).cloned().unwrap_or(3);
+        if kind == 3 || position + 6 > data.len() {
+            break;
+        }
+        let header = &data[(position + 2)..(position + 6)];
+        let length = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let end = (position + 6).saturating_add(length).min(data.len());
+        output.extend_from_slice(&data[(position + 6)..end]);
+        position = end;
+    }
+    output
+}
+
+fn decrypt(data: &[u8], key: u16, skip: usize) -> Vec<u8> {
+    let mut r = key;
+    let mut output = Vec::with_capacity(data.len());
+    for &cipher in data {
+        output.push(cipher ^ (r >> 8) as u8);
+        r = (cipher as u16).wrapping_add(r).wrapping_mul(52845).wrapping_add(22719);
+    }
+    output.split_off(skip.min(output.len()))
+}
+
+fn decode_hex(data: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = data.iter()
+                              .filter_map(|&byte| (byte as char).to_digit(16))
+                              .map(|digit| digit as u8)
+                              .collect();
+    digits.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).cloned().unwrap_or(0)).collect()
+}
+
+fn parse_font_matrix(cleartext: &[u8]) -> Transform2F {
+    let default = Transform2F::from_scale(0.001);
+    let start = match find(cleartext, b"/FontMatrix") {
+        Some(start) => start + b"/FontMatrix".len(),
+        None => return default,
+    };
+    let end = match cleartext[start..].iter().position(|&byte| byte == b']' || byte == b'}') {
+        Some(end) => start + end,
+        None => return default,
+    };
+    let values: Vec<f32> = String::from_utf8_lossy(&cleartext[start..end])
+        .split(|c: char| c.is_whitespace() || c == '[' || c == '{')
+        .filter_map(|value| value.parse().ok())
+        .collect();
+    if values.len() != 6 {
+        return default;
+    }
+    Transform2F::row_major(values[0], values[2], values[4], values[1], values[3], values[5])
+}
+
+// Reads `dup <code> /<name> put` entries, or returns `None` for `StandardEncoding`.
+fn parse_encoding(cleartext: &[u8]) -> Option<HashMap<u8, String>> {
+    let start = find(cleartext, b"/Encoding")? + b"/Encoding".len();
+    let (first, _) = next_token(cleartext, start)?;
+    if first == b"StandardEncoding" {
+        return None;
+    }
+
+    let mut encoding = HashMap::new();
+    let end = find(&cleartext[start..], b"readonly def")
+        .or_else(|| find(&cleartext[start..], b"def"))
+        .map_or(cleartext.len(), |end| start + end);
+    let mut tokens = vec![];
+    let mut position = start;
+    while let Some((token, next)) = next_token(&cleartext[..end], position) {
+        position = next;
+        tokens.push(token);
+        if token != b"put" || tokens.len() < 4 {
+            continue;
+        }
+        let entry = &tokens[(tokens.len() - 4)..];
+        let code = str::from_utf8(entry[1]).ok().and_then(|code| code.parse::<u8>().ok());
+        if let (b"dup", Some(code), true) = (entry[0], code, entry[2].starts_with(b"/")) {
+            encoding.insert(code, String::from_utf8_lossy(&entry[2][1..]).into_owned());
+        }
+    }
+    Some(encoding)
+}
+
+// Splits on whitespace, keeping a name's slash with it. Returns the token and the position
+// after it.
+fn next_token(data: &[u8], mut position: usize) -> Option<(&[u8], usize)> {
+    while data.get(position)?.is_ascii_whitespace() {
+        position += 1;
+    }
+    let start = position;
+    position += 1;
+    while let Some(&byte) = data.get(position) {
+        if byte.is_ascii_whitespace() || b"/[]{}()<>".contains(&byte) {
+            break;
+        }
+        position += 1;
+    }
+    Some((&data[start..position], position))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CHARSTRING_KEY, EEXEC_KEY, Type1Font};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+
+    fn encrypt(data: &[u8], key: u16) -> Vec<u8> {
+        let mut r = key;
+        let mut output = vec![];
+        for &plain in [0u8; 4].iter().chain(data) {
+            let cipher = plain ^ (r >> 8) as u8;
+            r = (cipher as u16).wrapping_add(r).wrapping_mul(52845).wrapping_add(22719);
+            output.push(cipher);
+        }
+        output
+    }
+
+    #[test]
+    fn test_charstrings() {
+        // hsbw 50 500; rmoveto 0 0; rlineto 100 0; rlineto 0 100; closepath; endchar
+        let char_string = encrypt(&[139, 189, 248, 136, 13,
+                                    139, 139, 21,
+                                    239, 139, 5,
+                                    139, 239, 5,
+                                    9, 14], CHARSTRING_KEY);
+        let mut private = b"/lenIV 4 def /Subrs 0 array\n/CharStrings 1 dict dup begin\n/square "
+            .to_vec();
+        private.extend_from_slice(format!("{} RD ", char_string.len()).as_bytes());
+        private.extend_from_slice(&char_string);
+        private.extend_from_slice(b" ND\nend");
+
+        let mut data = b"%!FontType1\n/FontMatrix [0.5 0 0 0.5 0 0] readonly def\n\
+                         /Encoding 256 array\ndup 65 /square put\nreadonly def\ncurrentfile eexec\n"
+            .to_vec();
+        data.extend_from_slice(&encrypt(&private, EEXEC_KEY));
+
+        let font = Type1Font::parse(&data, None).unwrap();
+        assert_eq!(font.encoding.as_ref().unwrap()[&65], "square");
+        let outline = font.outline("square").unwrap();
+        assert_eq!(outline.bounds(), RectF::new(vec2f(25.0, 0.0), vec2f(50.0, 50.0)));
+        assert!(font.outline("missing").is_none());
+    }
+}
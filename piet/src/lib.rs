@@ -0,0 +1,439 @@
+// pathfinder/piet/src/lib.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A `piet` render context that records into a Pathfinder canvas.
+//!
+//! `PathfinderRenderContext` wraps a `CanvasRenderingContext2D`, so toolkits written against
+//! `piet::RenderContext` can draw into a Pathfinder scene and render it with the GPU renderer.
+//! Shapes are flattened to Bézier paths with kurbo, brushes become canvas fill styles, images
+//! become patterns, and text layouts are drawn with the canvas text API.
+//!
+//! `capture_image_area()` returns `Error::NotSupported`, since the scene hasn't been rendered while
+//! it's being recorded.
+
+use pathfinder_canvas::{CanvasRenderingContext2D, ColorU, CompositeOperation, FillRule};
+use pathfinder_canvas::{FillStyle, LineCap, LineJoin, Path2D, RectF, Transform2F, vec2f};
+use pathfinder_content::gradient::Gradient;
+use pathfinder_content::pattern::{Image, Pattern};
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::vector::{Vector2F, vec2i};
+use pathfinder_simd::default::F32x2;
+use piet::kurbo::{Affine, PathEl, Point, Rect, Shape, Size};
+use piet::{Color, Error, FixedGradient, GradientStop, ImageFormat, InterpolationMode, IntoBrush};
+use piet::{RenderContext, StrokeStyle};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+pub use crate::text::{PathfinderText, PathfinderTextLayout, PathfinderTextLayoutBuilder};
+
+mod text;
+
+// How closely curved kurbo shapes such as circles are approximated, in user space units.
+const SHAPE_TOLERANCE: f64 = 0.1;
+
+// How far `blurred_rect()` moves the rectangle itself away from its shadow, in device pixels.
+const BLUR_SHADOW_OFFSET: f32 = 1.0e5;
+
+/// A piet render context that records drawing into a Pathfinder canvas.
+pub struct PathfinderRenderContext<'a> {
+    context: &'a mut CanvasRenderingContext2D,
+    text: PathfinderText,
+    save_count: usize,
+}
+
+/// A brush: a solid color or a gradient.
+#[derive(Clone)]
+pub struct PathfinderBrush(FillStyle);
+
+/// An image that can be drawn with `RenderContext::draw_image()`.
+#[derive(Clone)]
+pub struct PathfinderImage(Image);
+
+impl<'a> PathfinderRenderContext<'a> {
+    /// Creates a render context that draws into the given canvas context.
+    ///
+    /// Drawing starts from the canvas context's current transform and clip.
+    pub fn new(context: &'a mut CanvasRenderingContext2D) -> PathfinderRenderContext<'a> {
+        PathfinderRenderContext { context, text: PathfinderText::new(), save_count: 0 }
+    }
+
+    fn fill_shape(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>, rule: FillRule) {
+        let brush = brush.make_brush(self, || shape.bounding_box());
+        self.context.set_fill_style(brush.0.clone());
+        self.context.fill_path(shape_to_path(&shape), rule);
+    }
+}
+
+impl<'a> RenderContext for PathfinderRenderContext<'a> {
+    type Brush = PathfinderBrush;
+    type Text = PathfinderText;
+    type TextLayout = PathfinderTextLayout;
+    type Image = PathfinderImage;
+
+    fn status(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn solid_brush(&mut self, color: Color) -> PathfinderBrush {
+        PathfinderBrush(FillStyle::Color(to_color_u(color)))
+    }
+
+    fn gradient(&mut self, gradient: impl Into<FixedGradient>)
+                -> Result<PathfinderBrush, Error> {
+        let (mut gradient, stops) = match gradient.into() {
+            FixedGradient::Linear(linear) => {
+                (Gradient::linear_from_points(to_vector(linear.start), to_vector(linear.end)),
+                 linear.stops)
+            }
+            FixedGradient::Radial(radial) => {
+                // piet's radial gradients grow from a point at the origin to a circle around the
+                // center.
+                let center = to_vector(radial.center);
+                let origin = to_vector(radial.center + radial.origin_offset);
+                let line = LineSegment2F::new(origin, center);
+                (Gradient::radial(line, F32x2::new(0.0, radial.radius as f32)), radial.stops)
+            }
+        };
+        for GradientStop { pos, color } in stops {
+            gradient.add_color_stop(to_color_u(color), pos);
+        }
+        Ok(PathfinderBrush(FillStyle::Gradient(gradient)))
+    }
+
+    /// Fills the region, or the whole canvas if `None`, with the color, ignoring the transform.
+    ///
+    /// Unlike other backends, the clip still applies.
+    fn clear(&mut self, region: impl Into<Option<Rect>>, color: Color) {
+        let rect = match region.into() {
+            Some(rect) => to_rect(rect),
+            None => self.context.canvas().scene().view_box(),
+        };
+        self.context.save();
+        self.context.reset_transform();
+        self.context.set_global_composite_operation(CompositeOperation::Copy);
+        self.context.set_fill_style(to_color_u(color));
+        self.context.fill_rect(rect);
+        self.context.restore();
+    }
+
+    fn stroke(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>, width: f64) {
+        self.stroke_styled(shape, brush, width, &StrokeStyle::new());
+    }
+
+    fn stroke_styled(&mut self,
+                     shape: impl Shape,
+                     brush: &impl IntoBrush<Self>,
+                     width: f64,
+                     style: &StrokeStyle) {
+        let brush = brush.make_brush(self, || shape.bounding_box());
+        self.context.save();
+        self.context.set_stroke_style(brush.0.clone());
+        self.context.set_line_width(width as f32);
+        self.context.set_line_cap(match style.line_cap {
+            piet::LineCap::Butt => LineCap::Butt,
+            piet::LineCap::Round => LineCap::Round,
+            piet::LineCap::Square => LineCap::Square,
+        });
+        match style.line_join {
+            piet::LineJoin::Miter { limit } => {
+                self.context.set_line_join(LineJoin::Miter);
+                self.context.set_miter_limit(limit as f32);
+            }
+            piet::LineJoin::Round => self.context.set_line_join(LineJoin::Round),
+            piet::LineJoin::Bevel => self.context.set_line_join(LineJoin::Bevel),
+        }
+        self.context.set_line_dash(style.dash_pattern.iter().map(|&dash| dash as f32).collect());
+        self.context.set_line_dash_offset(style.dash_offset as f32);
+        self.context.stroke_path(shape_to_path(&shape));
+        self.context.restore();
+    }
+
+    fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        self.fill_shape(shape, brush, FillRule::Winding);
+    }
+
+    fn fill_even_odd(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        self.fill_shape(shape, brush, FillRule::EvenOdd);
+    }
+
+    fn clip(&mut self, shape: impl Shape) {
+        self.context.clip_path(shape_to_path(&shape), FillRule::Winding);
+    }
+
+    fn text(&mut self) -> &mut PathfinderText {
+        &mut self.text
+    }
+
+    fn draw_text(&mut self, layout: &PathfinderTextLayout, position: impl Into<Point>) {
+        layout.draw(self.context, position.into());
+    }
+
+    fn save(&mut self) -> Result<(), Error> {
+        self.context.save();
+        self.save_count += 1;
+        Ok(())
+    }
+
+    fn restore(&mut self) -> Result<(), Error> {
+        if self.save_count == 0 {
+            return Err(Error::StackUnbalance);
+        }
+        self.context.restore();
+        self.save_count -= 1;
+        Ok(())
+    }
+
+    /// Restores any states left saved, so the canvas context is as it was before.
+    fn finish(&mut self) -> Result<(), Error> {
+        while self.save_count > 0 {
+            self.restore()?;
+        }
+        Ok(())
+    }
+
+    fn transform(&mut self, transform: Affine) {
+        let new_transform = self.context.transform() * to_transform(transform);
+        self.context.set_transform(&new_transform);
+    }
+
+    fn make_image_with_stride(&mut self,
+                              width: usize,
+                              height: usize,
+                              stride: usize,
+                              buf: &[u8],
+                              format: ImageFormat)
+                              -> Result<PathfinderImage, Error> {
+        let bytes_per_pixel = format.bytes_per_pixel();
+        if stride < width * bytes_per_pixel || buf.len() < stride * height.saturating_sub(1) +
+                width * bytes_per_pixel {
+            return Err(Error::InvalidInput);
+        }
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in buf.chunks(stride).take(height) {
+            for pixel in row[..width * bytes_per_pixel].chunks(bytes_per_pixel) {
+                pixels.push(match format {
+                    ImageFormat::Grayscale => ColorU::new(pixel[0], pixel[0], pixel[0], 255),
+                    ImageFormat::Rgb => ColorU::new(pixel[0], pixel[1], pixel[2], 255),
+                    ImageFormat::RgbaSeparate => {
+                        ColorU::new(pixel[0], pixel[1], pixel[2], pixel[3])
+                    }
+                    ImageFormat::RgbaPremul => unpremultiply(pixel),
+                    _ => return Err(Error::NotSupported),
+                });
+            }
+        }
+
+        let size = vec2i(width as i32, height as i32);
+        Ok(PathfinderImage(Image::new(size, Arc::new(pixels))))
+    }
+
+    fn draw_image(&mut self,
+                  image: &PathfinderImage,
+                  dst_rect: impl Into<Rect>,
+                  interp: InterpolationMode) {
+        let size = image.0.size().to_f32();
+        self.draw_image_area(image,
+                             Rect::new(0.0, 0.0, size.x() as f64, size.y() as f64),
+                             dst_rect,
+                             interp);
+    }
+
+    fn draw_image_area(&mut self,
+                       image: &PathfinderImage,
+                       src_rect: impl Into<Rect>,
+                       dst_rect: impl Into<Rect>,
+                       interp: InterpolationMode) {
+        let pattern = Pattern::from_image(image.0.clone());
+        self.context.save();
+        self.context.set_image_smoothing_enabled(interp == InterpolationMode::Bilinear);
+        self.context.draw_subimage(pattern, to_rect(src_rect.into()), to_rect(dst_rect.into()));
+        self.context.restore();
+    }
+
+    fn capture_image_area(&mut self, _: impl Into<Rect>) -> Result<PathfinderImage, Error> {
+        Err(Error::NotSupported)
+    }
+
+    /// Draws a blurred rectangle using the canvas shadow machinery.
+    ///
+    /// The blur radius is the standard deviation of the blur. Gradient brushes are drawn
+    /// unblurred.
+    fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &impl IntoBrush<Self>) {
+        let brush = brush.make_brush(self, || rect);
+        let color = match brush.0 {
+            FillStyle::Color(color) => color,
+            ref fill_style => {
+                self.context.set_fill_style(fill_style.clone());
+                self.context.fill_rect(to_rect(rect));
+                return;
+            }
+        };
+
+        // Draw the rectangle far outside the canvas, with its shadow offset back into place, so
+        // only the blurred shadow is visible.
+        let offset = vec2f(0.0, BLUR_SHADOW_OFFSET);
+        self.context.save();
+        let transform = Transform2F::from_translation(-offset) * self.context.transform();
+        self.context.set_transform(&transform);
+        self.context.set_shadow_color(color);
+        self.context.set_shadow_blur(blur_radius as f32 * 2.0);
+        self.context.set_shadow_offset(offset);
+        self.context.set_fill_style(ColorU::black());
+        self.context.fill_rect(to_rect(rect));
+        self.context.restore();
+    }
+
+    fn current_transform(&self) -> Affine {
+        let transform = self.context.transform();
+        Affine::new([transform.m11() as f64,
+                     transform.m21() as f64,
+                     transform.m12() as f64,
+                     transform.m22() as f64,
+                     transform.m13() as f64,
+                     transform.m23() as f64])
+    }
+}
+
+impl<'a> IntoBrush<PathfinderRenderContext<'a>> for PathfinderBrush {
+    #[inline]
+    fn make_brush<'b>(&'b self, _: &mut PathfinderRenderContext<'a>, _: impl FnOnce() -> Rect)
+                      -> Cow<'b, PathfinderBrush> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl piet::Image for PathfinderImage {
+    fn size(&self) -> Size {
+        let size = self.0.size();
+        Size::new(size.x() as f64, size.y() as f64)
+    }
+}
+
+fn shape_to_path(shape: &impl Shape) -> Path2D {
+    let mut path = Path2D::new();
+    for element in shape.path_elements(SHAPE_TOLERANCE) {
+        match element {
+            PathEl::MoveTo(to) => path.move_to(to_vector(to)),
+            PathEl::LineTo(to) => path.line_to(to_vector(to)),
+            PathEl::QuadTo(ctrl, to) => path.quadratic_curve_to(to_vector(ctrl), to_vector(to)),
+            PathEl::CurveTo(ctrl0, ctrl1, to) => {
+                path.bezier_curve_to(to_vector(ctrl0), to_vector(ctrl1), to_vector(to))
+            }
+            PathEl::ClosePath => path.close_path(),
+        }
+    }
+    path
+}
+
+// piet's affine coefficients are in column-major order.
+fn to_transform(affine: Affine) -> Transform2F {
+    let [a, b, c, d, e, f] = affine.as_coeffs();
+    Transform2F::row_major(a as f32, c as f32, e as f32, b as f32, d as f32, f as f32)
+}
+
+#[inline]
+fn to_vector(point: Point) -> Vector2F {
+    vec2f(point.x as f32, point.y as f32)
+}
+
+#[inline]
+fn to_rect(rect: Rect) -> RectF {
+    let rect = rect.abs();
+    RectF::from_points(vec2f(rect.x0 as f32, rect.y0 as f32), vec2f(rect.x1 as f32, rect.y1 as f32))
+}
+
+#[inline]
+fn to_color_u(color: Color) -> ColorU {
+    let (r, g, b, a) = color.as_rgba8();
+    ColorU::new(r, g, b, a)
+}
+
+fn unpremultiply(pixel: &[u8]) -> ColorU {
+    let alpha = pixel[3] as u32;
+    if alpha == 0 {
+        return ColorU::transparent_black();
+    }
+    let channel = |value: u8| ((value as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+    ColorU::new(channel(pixel[0]), channel(pixel[1]), channel(pixel[2]), pixel[3])
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PathfinderRenderContext, to_transform};
+    use pathfinder_canvas::{Canvas, CanvasFontContext, vec2f};
+    use piet::kurbo::{Affine, Circle, Rect, Vec2};
+    use piet::{Color, ImageFormat, InterpolationMode, RenderContext, Text, TextLayout};
+    use piet::TextLayoutBuilder;
+
+    #[test]
+    fn test_affine_round_trip() {
+        let affine = Affine::new([2.0, 0.5, -0.5, 3.0, 10.0, 20.0]);
+        let transform = to_transform(affine);
+        assert_eq!(transform * vec2f(1.0, 1.0), vec2f(11.5, 23.5));
+
+        let mut canvas = Canvas::new(vec2f(100.0, 100.0))
+            .get_context_2d(CanvasFontContext::from_system_source());
+        let mut piet = PathfinderRenderContext::new(&mut canvas);
+        piet.transform(affine);
+        assert_eq!(piet.current_transform(), affine);
+    }
+
+    #[test]
+    fn test_draw_into_scene() {
+        let mut canvas = Canvas::new(vec2f(100.0, 100.0))
+            .get_context_2d(CanvasFontContext::from_system_source());
+        {
+            let mut piet = PathfinderRenderContext::new(&mut canvas);
+            let brush = piet.solid_brush(Color::rgb8(255, 0, 0));
+            piet.save().unwrap();
+            piet.transform(Affine::translate(Vec2::new(10.0, 10.0)));
+            piet.fill(Circle::new((20.0, 20.0), 5.0), &brush);
+            piet.stroke(Rect::new(0.0, 0.0, 30.0, 30.0), &brush, 2.0);
+            let image = piet.make_image(2, 2, &[255; 16], ImageFormat::RgbaSeparate).unwrap();
+            piet.draw_image(&image, Rect::new(50.0, 50.0, 60.0, 60.0), InterpolationMode::Bilinear);
+            piet.finish().unwrap();
+            assert!(piet.restore().is_err());
+        }
+        assert_eq!(canvas.canvas().scene().draw_path_count(), 3);
+    }
+
+    #[test]
+    fn test_text_layout() {
+        let mut canvas = Canvas::new(vec2f(200.0, 100.0))
+            .get_context_2d(CanvasFontContext::from_system_source());
+        {
+            let mut piet = PathfinderRenderContext::new(&mut canvas);
+            let font_data = include_bytes!("../../resources/fonts/Roboto-Regular.ttf");
+            let family = piet.text().load_font(font_data).unwrap();
+            let layout = piet.text()
+                             .new_text_layout("Hello, world!\nBye")
+                             .font(family, 20.0)
+                             .max_width(80.0)
+                             .build()
+                             .unwrap();
+            assert_eq!(layout.line_count(), 3);
+            assert_eq!(layout.line_text(0), Some("Hello, "));
+            assert_eq!(layout.line_text(1), Some("world!\n"));
+            assert_eq!(layout.line_text(2), Some("Bye"));
+            assert_eq!(layout.line_metric(1).unwrap().trailing_whitespace, 1);
+            assert!(layout.size().width <= 80.0);
+
+            let position = layout.hit_test_text_position(3);
+            assert_eq!(position.line, 0);
+            assert_eq!(layout.hit_test_point(position.point).idx, 3);
+            assert_eq!(layout.hit_test_text_position(14).line, 2);
+
+            piet.draw_text(&layout, (10.0, 10.0));
+            piet.finish().unwrap();
+        }
+        assert!(canvas.canvas().scene().draw_path_count() > 0);
+    }
+}
@@ -0,0 +1,555 @@
+// pathfinder/piet/src/text.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Text layout for the piet backend.
+//!
+//! Fonts come from the system or from `Text::load_font()` through font-kit, and runs are shaped
+//! with skribo. Lines are broken at whitespace to fit the maximum width.
+//!
+//! Attributes that change the layout (family, size, weight, and style) are only honored as
+//! default attributes. Range attributes can set the color, underline, and strikethrough.
+
+use crate::to_color_u;
+use font_kit::family_name::FamilyName;
+use font_kit::loaders::default::Font;
+use font_kit::properties::{Properties, Style, Weight};
+use font_kit::source::SystemSource;
+use pathfinder_canvas::{CanvasRenderingContext2D, RectF, TextAlign, TextBaseline, vec2f};
+use piet::kurbo::{Point, Rect, Size};
+use piet::{Color, Error, FontFamily, FontStyle, FontWeight, HitTestPoint, HitTestPosition};
+use piet::{LineMetric, Text, TextAlignment, TextAttribute, TextLayout, TextLayoutBuilder};
+use piet::TextStorage;
+use skribo::{FontCollection, FontFamily as SkriboFontFamily, Layout as SkriboLayout, TextStyle};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::{Bound, Range, RangeBounds};
+use std::rc::Rc;
+use std::sync::Arc;
+
+const DEFAULT_FONT_SIZE: f64 = 12.0;
+
+/// The text factory for `PathfinderRenderContext`.
+///
+/// Clones share the fonts loaded with `load_font()`.
+#[derive(Clone)]
+pub struct PathfinderText(Rc<RefCell<TextData>>);
+
+struct TextData {
+    system_source: SystemSource,
+    loaded_fonts: Vec<Font>,
+    // Fonts already selected for a family, weight, and italic flag.
+    font_cache: HashMap<(String, u16, bool), Font>,
+}
+
+/// Builds text layouts.
+pub struct PathfinderTextLayoutBuilder {
+    text: PathfinderText,
+    storage: Rc<dyn TextStorage>,
+    max_width: f64,
+    alignment: TextAlignment,
+    font_family: FontFamily,
+    font_size: f64,
+    weight: FontWeight,
+    style: FontStyle,
+    default_style: RunStyle,
+    range_styles: Vec<(Range<usize>, TextAttribute)>,
+}
+
+/// A laid out piece of text.
+#[derive(Clone)]
+pub struct PathfinderTextLayout {
+    storage: Rc<dyn TextStorage>,
+    font_collection: Arc<FontCollection>,
+    font_size: f32,
+    lines: Vec<Line>,
+    size: Size,
+    trailing_whitespace_width: f64,
+    ascent: f64,
+    line_height: f64,
+    // The offset below the baseline and thickness of underlines and strikethroughs.
+    underline: (f64, f64),
+    strikethrough: (f64, f64),
+    default_style: RunStyle,
+    range_styles: Vec<(Range<usize>, TextAttribute)>,
+}
+
+#[derive(Clone)]
+struct Line {
+    range: Range<usize>,
+    trailing_whitespace: usize,
+    x_offset: f64,
+    y_offset: f64,
+    // The width of the line without its trailing whitespace.
+    width: f64,
+}
+
+#[derive(Clone, Copy)]
+struct RunStyle {
+    color: Color,
+    underline: bool,
+    strikethrough: bool,
+}
+
+impl PathfinderText {
+    /// Creates a text factory that finds fonts on the system.
+    #[inline]
+    pub fn new() -> PathfinderText {
+        PathfinderText(Rc::new(RefCell::new(TextData {
+            system_source: SystemSource::new(),
+            loaded_fonts: vec![],
+            font_cache: HashMap::new(),
+        })))
+    }
+
+    fn select_font(&self, family: &FontFamily, weight: FontWeight, style: FontStyle)
+                   -> Result<Font, Error> {
+        let italic = style == FontStyle::Italic;
+        let key = (family.name().to_owned(), weight.to_raw(), italic);
+        let mut data = self.0.borrow_mut();
+        if let Some(font) = data.font_cache.get(&key) {
+            return Ok(font.clone());
+        }
+
+        let mut properties = Properties::new();
+        properties.weight = Weight(weight.to_raw() as f32);
+        properties.style = if italic { Style::Italic } else { Style::Normal };
+
+        // Fonts loaded by the client take precedence over system fonts of the same family.
+        let loaded_font = data.loaded_fonts
+                              .iter()
+                              .filter(|font| font.family_name() == family.name())
+                              .min_by_key(|font| {
+                                  let font_properties = font.properties();
+                                  let style_mismatch = (font_properties.style != properties.style)
+                                      as u32;
+                                  let weight_distance = (font_properties.weight.0 -
+                                                         properties.weight.0).abs() as u32;
+                                  style_mismatch * 1000 + weight_distance
+                              })
+                              .cloned();
+        let font = match loaded_font {
+            Some(font) => font,
+            None => {
+                data.system_source
+                    .select_best_match(&[to_family_name(family)], &properties)
+                    .map_err(|_| Error::MissingFont)?
+                    .load()
+                    .map_err(|_| Error::FontLoadingFailed)?
+            }
+        };
+        data.font_cache.insert(key, font.clone());
+        Ok(font)
+    }
+}
+
+impl Default for PathfinderText {
+    #[inline]
+    fn default() -> PathfinderText {
+        PathfinderText::new()
+    }
+}
+
+impl Text for PathfinderText {
+    type TextLayoutBuilder = PathfinderTextLayoutBuilder;
+    type TextLayout = PathfinderTextLayout;
+
+    fn font_family(&mut self, family_name: &str) -> Option<FontFamily> {
+        let data = self.0.borrow();
+        if data.loaded_fonts.iter().any(|font| font.family_name() == family_name) ||
+                data.system_source.select_family_by_name(family_name).is_ok() {
+            Some(FontFamily::new_unchecked(family_name))
+        } else {
+            None
+        }
+    }
+
+    fn load_font(&mut self, font_data: &[u8]) -> Result<FontFamily, Error> {
+        let font = Font::from_bytes(Arc::new(font_data.to_vec()), 0)
+                        .map_err(|_| Error::FontLoadingFailed)?;
+        let family = FontFamily::new_unchecked(font.family_name());
+        let mut data = self.0.borrow_mut();
+        data.font_cache.retain(|(family_name, _, _), _| family_name != family.name());
+        data.loaded_fonts.push(font);
+        Ok(family)
+    }
+
+    fn new_text_layout(&mut self, text: impl TextStorage) -> PathfinderTextLayoutBuilder {
+        PathfinderTextLayoutBuilder {
+            text: self.clone(),
+            storage: Rc::new(text),
+            max_width: f64::INFINITY,
+            alignment: TextAlignment::Start,
+            font_family: FontFamily::SANS_SERIF,
+            font_size: DEFAULT_FONT_SIZE,
+            weight: FontWeight::NORMAL,
+            style: FontStyle::Regular,
+            default_style: RunStyle { color: Color::BLACK, underline: false, strikethrough: false },
+            range_styles: vec![],
+        }
+    }
+}
+
+impl TextLayoutBuilder for PathfinderTextLayoutBuilder {
+    type Out = PathfinderTextLayout;
+
+    fn max_width(mut self, width: f64) -> Self {
+        self.max_width = width;
+        self
+    }
+
+    fn alignment(mut self, alignment: TextAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    fn default_attribute(mut self, attribute: impl Into<TextAttribute>) -> Self {
+        match attribute.into() {
+            TextAttribute::FontFamily(family) => self.font_family = family,
+            TextAttribute::FontSize(size) => self.font_size = size,
+            TextAttribute::Weight(weight) => self.weight = weight,
+            TextAttribute::Style(style) => self.style = style,
+            attribute => self.default_style.apply(&attribute),
+        }
+        self
+    }
+
+    fn range_attribute(mut self, range: impl RangeBounds<usize>, attribute: impl Into<TextAttribute>)
+                       -> Self {
+        let range = resolve_range(range, self.storage.as_str().len());
+        self.range_styles.push((range, attribute.into()));
+        self
+    }
+
+    fn build(self) -> Result<PathfinderTextLayout, Error> {
+        let font = self.text.select_font(&self.font_family, self.weight, self.style)?;
+        let metrics = font.metrics();
+        let scale = self.font_size / metrics.units_per_em as f64;
+        let ascent = metrics.ascent as f64 * scale;
+        let line_height = (metrics.ascent - metrics.descent + metrics.line_gap) as f64 * scale;
+
+        let mut font_collection = FontCollection::new();
+        font_collection.add_family(SkriboFontFamily::new_from_font(font));
+        let font_collection = Arc::new(font_collection);
+        let font_size = self.font_size as f32;
+
+        let text = self.storage.as_str();
+        let measure = |string: &str| measure_text(&font_collection, font_size, string);
+        let mut lines = break_lines(text, self.max_width, &measure);
+        let mut width: f64 = lines.iter().map(|line| line.width).fold(0.0, f64::max);
+        if self.max_width.is_finite() && self.alignment != TextAlignment::Start {
+            width = width.max(self.max_width);
+        }
+        for (line_index, line) in lines.iter_mut().enumerate() {
+            line.x_offset = match self.alignment {
+                TextAlignment::Start | TextAlignment::Justified => 0.0,
+                TextAlignment::End => width - line.width,
+                TextAlignment::Center => 0.5 * (width - line.width),
+            };
+            line.y_offset = line_index as f64 * line_height;
+        }
+
+        let trailing_whitespace_width = match lines.last() {
+            None => 0.0,
+            Some(line) => {
+                let line_text = text[line.range.clone()].trim_end_matches('\n');
+                measure(line_text) - line.width
+            }
+        };
+
+        Ok(PathfinderTextLayout {
+            storage: self.storage.clone(),
+            font_collection,
+            font_size,
+            size: Size::new(width, lines.len() as f64 * line_height),
+            lines,
+            trailing_whitespace_width,
+            ascent,
+            line_height,
+            underline: (-metrics.underline_position as f64 * scale,
+                        metrics.underline_thickness as f64 * scale),
+            strikethrough: (-0.5 * metrics.x_height as f64 * scale,
+                            metrics.underline_thickness as f64 * scale),
+            default_style: self.default_style,
+            range_styles: self.range_styles,
+        })
+    }
+}
+
+impl PathfinderTextLayout {
+    // Draws the layout with its upper left corner at `origin`, in the current transform and clip
+    // of the context.
+    pub(crate) fn draw(&self, context: &mut CanvasRenderingContext2D, origin: Point) {
+        let text = self.storage.as_str();
+
+        context.save();
+        drop(context.set_font(self.font_collection.clone()));
+        context.set_font_size(self.font_size);
+        context.set_text_align(TextAlign::Left);
+        context.set_text_baseline(TextBaseline::Alphabetic);
+
+        for line in &self.lines {
+            let visible_end = line.range.end - line.trailing_whitespace;
+            let baseline = origin.y + line.y_offset + self.ascent;
+            for run in self.style_runs(line.range.start..visible_end) {
+                let x = origin.x + line.x_offset + self.measure(line.range.start..run.start);
+                let width = self.measure(run.clone());
+                let style = self.style_at(run.start);
+                let string = &text[run];
+                context.set_fill_style(to_color_u(style.color));
+                context.fill_text(string, vec2f(x as f32, baseline as f32));
+
+                let mut decorations = vec![];
+                if style.underline {
+                    decorations.push(self.underline);
+                }
+                if style.strikethrough {
+                    decorations.push(self.strikethrough);
+                }
+                for (offset, thickness) in decorations {
+                    let y = baseline + offset - 0.5 * thickness;
+                    context.fill_rect(RectF::new(vec2f(x as f32, y as f32),
+                                                 vec2f(width as f32, thickness as f32)));
+                }
+            }
+        }
+
+        context.restore();
+    }
+
+    fn measure(&self, range: Range<usize>) -> f64 {
+        measure_text(&self.font_collection, self.font_size, &self.storage.as_str()[range])
+    }
+
+    // Splits a range of the text into runs of the same style.
+    fn style_runs(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        let mut boundaries = vec![range.start, range.end];
+        for (style_range, _) in &self.range_styles {
+            for &boundary in &[style_range.start, style_range.end] {
+                if boundary > range.start && boundary < range.end {
+                    boundaries.push(boundary);
+                }
+            }
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        boundaries.windows(2).map(|pair| pair[0]..pair[1]).collect()
+    }
+
+    fn style_at(&self, index: usize) -> RunStyle {
+        let mut style = self.default_style;
+        for (range, attribute) in &self.range_styles {
+            if range.contains(&index) {
+                style.apply(attribute);
+            }
+        }
+        style
+    }
+
+    // Returns the line that contains the given text position. Positions at a line break belong to
+    // the line that the break ends.
+    fn line_for_text_position(&self, index: usize) -> usize {
+        self.lines
+            .iter()
+            .rposition(|line| line.range.start <= index)
+            .unwrap_or(0)
+    }
+
+    fn caret_x(&self, line: &Line, index: usize) -> f64 {
+        line.x_offset + self.measure(line.range.start..index)
+    }
+}
+
+impl TextLayout for PathfinderTextLayout {
+    fn size(&self) -> Size {
+        self.size
+    }
+
+    fn trailing_whitespace_width(&self) -> f64 {
+        self.trailing_whitespace_width
+    }
+
+    fn image_bounds(&self) -> Rect {
+        self.size.to_rect()
+    }
+
+    fn text(&self) -> &str {
+        self.storage.as_str()
+    }
+
+    fn line_text(&self, line_number: usize) -> Option<&str> {
+        let line = self.lines.get(line_number)?;
+        Some(&self.storage.as_str()[line.range.clone()])
+    }
+
+    fn line_metric(&self, line_number: usize) -> Option<LineMetric> {
+        let line = self.lines.get(line_number)?;
+        Some(LineMetric {
+            start_offset: line.range.start,
+            end_offset: line.range.end,
+            trailing_whitespace: line.trailing_whitespace,
+            baseline: self.ascent,
+            height: self.line_height,
+            y_offset: line.y_offset,
+        })
+    }
+
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn hit_test_point(&self, point: Point) -> HitTestPoint {
+        let line_index = self.lines
+                             .iter()
+                             .position(|line| point.y < line.y_offset + self.line_height)
+                             .unwrap_or(self.lines.len() - 1);
+        let line = &self.lines[line_index];
+
+        // Find the closest character boundary on the line, not counting a trailing line break.
+        let text = self.storage.as_str();
+        let line_text = text[line.range.clone()].trim_end_matches('\n');
+        let mut closest = (line.range.start, f64::INFINITY);
+        for index in line_text.char_indices()
+                              .map(|(index, _)| line.range.start + index)
+                              .chain(Some(line.range.start + line_text.len())) {
+            let distance = (self.caret_x(line, index) - point.x).abs();
+            if distance < closest.1 {
+                closest = (index, distance);
+            }
+        }
+
+        let mut hit_test_point = HitTestPoint::default();
+        hit_test_point.idx = closest.0;
+        hit_test_point.is_inside = point.y >= 0.0 && point.y < self.size.height &&
+            point.x >= line.x_offset && point.x < line.x_offset + line.width;
+        hit_test_point
+    }
+
+    fn hit_test_text_position(&self, index: usize) -> HitTestPosition {
+        let text = self.storage.as_str();
+        let mut index = index.min(text.len());
+        while !text.is_char_boundary(index) {
+            index -= 1;
+        }
+
+        let line_index = self.line_for_text_position(index);
+        let line = &self.lines[line_index];
+        let mut hit_test_position = HitTestPosition::default();
+        hit_test_position.point = Point::new(self.caret_x(line, index),
+                                             line.y_offset + self.ascent);
+        hit_test_position.line = line_index;
+        hit_test_position
+    }
+}
+
+impl RunStyle {
+    fn apply(&mut self, attribute: &TextAttribute) {
+        match *attribute {
+            TextAttribute::TextColor(color) => self.color = color,
+            TextAttribute::Underline(underline) => self.underline = underline,
+            TextAttribute::Strikethrough(strikethrough) => self.strikethrough = strikethrough,
+            _ => {}
+        }
+    }
+}
+
+// Breaks the text into lines at line breaks, and at whitespace where a line would be wider than
+// `max_width`. Each line keeps its trailing whitespace and line break. Lines are left at the
+// origin; the caller positions them.
+fn break_lines(text: &str, max_width: f64, measure: &dyn Fn(&str) -> f64) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut push_line = |range: Range<usize>| {
+        let visible_text = text[range.clone()].trim_end();
+        lines.push(Line {
+            trailing_whitespace: range.len() - visible_text.len(),
+            width: measure(visible_text),
+            range,
+            x_offset: 0.0,
+            y_offset: 0.0,
+        });
+    };
+
+    let mut paragraph_start = 0;
+    for paragraph in text.split_inclusive('\n') {
+        let paragraph_end = paragraph_start + paragraph.len();
+        let mut line_start = paragraph_start;
+
+        // Words start where whitespace is followed by anything else.
+        let mut word_starts = vec![];
+        let mut last_char_was_whitespace = false;
+        for (index, character) in paragraph.char_indices() {
+            if last_char_was_whitespace && !character.is_whitespace() {
+                word_starts.push(paragraph_start + index);
+            }
+            last_char_was_whitespace = character.is_whitespace();
+        }
+        word_starts.push(paragraph_end);
+
+        let mut word_start = paragraph_start;
+        for &word_end in &word_starts {
+            if word_start > line_start &&
+                    measure(text[line_start..word_end].trim_end()) > max_width {
+                push_line(line_start..word_start);
+                line_start = word_start;
+            }
+            word_start = word_end;
+        }
+        push_line(line_start..paragraph_end);
+        paragraph_start = paragraph_end;
+    }
+
+    // Text that's empty or ends in a line break has an empty last line for the caret.
+    if text.is_empty() || text.ends_with('\n') {
+        push_line(text.len()..text.len());
+    }
+    lines
+}
+
+// Returns the advance width of a string, including trailing whitespace.
+fn measure_text(font_collection: &FontCollection, font_size: f32, string: &str) -> f64 {
+    let layout = skribo::layout(&TextStyle { size: font_size }, font_collection, string);
+    layout_advance(&layout) as f64
+}
+
+fn layout_advance(layout: &SkriboLayout) -> f32 {
+    let last_glyph = match layout.glyphs.last() {
+        None => return 0.0,
+        Some(last_glyph) => last_glyph,
+    };
+    let font = &last_glyph.font.font;
+    let scale_factor = layout.size / font.metrics().units_per_em as f32;
+    let advance = font.advance(last_glyph.glyph_id).map(|advance| advance.x()).unwrap_or(0.0);
+    last_glyph.offset.x() + advance * scale_factor
+}
+
+fn to_family_name(family: &FontFamily) -> FamilyName {
+    if *family == FontFamily::SERIF {
+        FamilyName::Serif
+    } else if *family == FontFamily::MONOSPACE {
+        FamilyName::Monospace
+    } else if *family == FontFamily::SANS_SERIF || *family == FontFamily::SYSTEM_UI {
+        FamilyName::SansSerif
+    } else {
+        FamilyName::Title(family.name().to_owned())
+    }
+}
+
+fn resolve_range(range: impl RangeBounds<usize>, len: usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    start.min(len)..end.min(len)
+}
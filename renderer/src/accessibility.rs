@@ -0,0 +1,219 @@
+// pathfinder/renderer/src/accessibility.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Exposing the content of a scene to assistive technology.
+//!
+//! Paths can be tagged with what they mean, such as a run of text or a button, by drawing them
+//! between `Scene::push_accessibility_node()` and `Scene::pop_accessibility_node()`. Nodes nest,
+//! and `Scene::accessibility_tree()` exports them as a tree with the screen-space bounds of what
+//! they drew, in a form that maps directly onto the nodes of accessibility toolkits such as
+//! AccessKit.
+
+use crate::scene::DrawPathId;
+use pathfinder_geometry::rect::RectF;
+use std::ops::Range;
+
+/// What a tagged part of a scene is, for assistive technology.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AccessibilityRole {
+    /// A container that groups other nodes.
+    Group,
+    /// A run of text. The label is the text itself.
+    Text,
+    /// A heading of the given level, starting at 1.
+    Heading(u8),
+    /// A control that performs an action when activated.
+    Button,
+    /// A link to other content.
+    Link,
+    /// An image or illustration. The label describes it.
+    Image,
+}
+
+/// Identifies an accessibility node within a scene.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AccessibilityNodeId(pub u32);
+
+/// The nodes tagged in a scene, with the screen-space bounds of the paths they drew.
+#[derive(Clone, Debug, Default)]
+pub struct AccessibilityTree {
+    nodes: Vec<AccessibilityNode>,
+    roots: Vec<AccessibilityNodeId>,
+}
+
+/// A node in an `AccessibilityTree`.
+#[derive(Clone, Debug)]
+pub struct AccessibilityNode {
+    /// What this node is.
+    pub role: AccessibilityRole,
+    /// The text read out for this node.
+    pub label: String,
+    /// The screen-space bounds of the paths drawn inside this node, including those of its
+    /// children, or `None` if it drew nothing that can be seen.
+    pub bounds: Option<RectF>,
+    /// The node that this one was drawn inside, if any.
+    pub parent: Option<AccessibilityNodeId>,
+    /// The nodes drawn inside this one, in the order they were drawn.
+    pub children: Vec<AccessibilityNodeId>,
+}
+
+// A node as it's stored in the scene.
+#[derive(Clone, Debug)]
+pub(crate) struct AccessibilityTag {
+    pub(crate) role: AccessibilityRole,
+    pub(crate) label: String,
+    pub(crate) parent: Option<AccessibilityNodeId>,
+    pub(crate) extent: TagExtent,
+}
+
+// What an accessibility node covers.
+#[derive(Clone, Debug)]
+pub(crate) enum TagExtent {
+    // The draw paths pushed while the node was open. The end is only valid once it's popped.
+    DrawPaths(Range<u32>),
+    // The scene-space bounds of the node's draw paths, kept when `Scene::optimize()` renumbers
+    // them.
+    Fixed(Option<RectF>),
+}
+
+impl AccessibilityTree {
+    // Builds a tree from the tags of a scene. `bounds_of` returns the screen-space bounds of the
+    // visible draw paths in a range, and `fixed_bounds_of` transforms fixed scene-space bounds.
+    // Nodes in `open_nodes` cover every draw path up to `draw_path_count`.
+    pub(crate) fn new<F, G>(tags: &[AccessibilityTag],
+                            open_nodes: &[AccessibilityNodeId],
+                            draw_path_count: u32,
+                            bounds_of: F,
+                            fixed_bounds_of: G)
+                            -> AccessibilityTree
+                            where F: Fn(Range<DrawPathId>) -> Option<RectF>,
+                                  G: Fn(RectF) -> RectF {
+        let mut tree = AccessibilityTree::default();
+        for (index, tag) in tags.iter().enumerate() {
+            let node_id = AccessibilityNodeId(index as u32);
+            let bounds = match tag.extent {
+                TagExtent::Fixed(fixed_bounds) => fixed_bounds.map(&fixed_bounds_of),
+                TagExtent::DrawPaths(ref draw_paths) => {
+                    let end = if open_nodes.contains(&node_id) {
+                        draw_path_count
+                    } else {
+                        draw_paths.end
+                    };
+                    bounds_of(DrawPathId(draw_paths.start)..DrawPathId(end))
+                }
+            };
+            match tag.parent {
+                Some(parent) => tree.nodes[parent.0 as usize].children.push(node_id),
+                None => tree.roots.push(node_id),
+            }
+            tree.nodes.push(AccessibilityNode {
+                role: tag.role,
+                label: tag.label.clone(),
+                bounds,
+                parent: tag.parent,
+                children: vec![],
+            });
+        }
+        tree
+    }
+
+    /// Returns the nodes that weren't drawn inside any other node, in the order they were drawn.
+    #[inline]
+    pub fn roots(&self) -> &[AccessibilityNodeId] {
+        &self.roots
+    }
+
+    /// Returns the node with the given ID.
+    #[inline]
+    pub fn get(&self, node_id: AccessibilityNodeId) -> &AccessibilityNode {
+        &self.nodes[node_id.0 as usize]
+    }
+
+    /// Returns every node with its ID, parents before their children.
+    pub fn nodes(&self) -> impl Iterator<Item = (AccessibilityNodeId, &AccessibilityNode)> {
+        self.nodes.iter().enumerate().map(|(index, node)| {
+            (AccessibilityNodeId(index as u32), node)
+        })
+    }
+
+    /// Returns the number of nodes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if nothing in the scene was tagged.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AccessibilityRole;
+    use crate::paint::Paint;
+    use crate::scene::{DrawPath, Scene};
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::vec2f;
+
+    fn push_rect(scene: &mut Scene, origin: f32, size: f32) {
+        let paint = scene.push_paint(&Paint::black());
+        let outline = Outline::from_rect(RectF::new(vec2f(origin, origin), vec2f(size, size)));
+        scene.push_draw_path(DrawPath::new(outline, paint));
+    }
+
+    #[test]
+    fn test_nodes_nest_and_cover_their_paths() {
+        let mut scene = Scene::new();
+        push_rect(&mut scene, 0.0, 100.0);
+        let button = scene.push_accessibility_node(AccessibilityRole::Button, "OK".to_owned());
+        push_rect(&mut scene, 10.0, 20.0);
+        let text = scene.push_accessibility_node(AccessibilityRole::Text, "OK".to_owned());
+        push_rect(&mut scene, 15.0, 5.0);
+        scene.pop_accessibility_node();
+        scene.pop_accessibility_node();
+        let image = scene.push_accessibility_node(AccessibilityRole::Image, "Nothing".to_owned());
+        scene.pop_accessibility_node();
+
+        let tree = scene.accessibility_tree(&Transform2F::from_scale(2.0));
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.roots(), &[button, image]);
+        assert_eq!(tree.get(button).children, vec![text]);
+        assert_eq!(tree.get(text).parent, Some(button));
+        assert_eq!(tree.get(button).bounds,
+                   Some(RectF::new(vec2f(20.0, 20.0), vec2f(40.0, 40.0))));
+        assert_eq!(tree.get(text).bounds, Some(RectF::new(vec2f(30.0, 30.0), vec2f(10.0, 10.0))));
+        assert_eq!(tree.get(image).bounds, None);
+    }
+
+    #[test]
+    fn test_hidden_layers_and_optimizing_keep_bounds_correct() {
+        let mut scene = Scene::new();
+        let layer = scene.push_layer("popup".to_owned());
+        let heading = scene.push_accessibility_node(AccessibilityRole::Heading(1),
+                                                    "Title".to_owned());
+        push_rect(&mut scene, 10.0, 10.0);
+        scene.pop_accessibility_node();
+        scene.pop_layer();
+
+        scene.set_layer_visible(layer, false);
+        assert_eq!(scene.accessibility_tree(&Transform2F::default()).get(heading).bounds, None);
+        scene.set_layer_visible(layer, true);
+
+        push_rect(&mut scene, 50.0, 10.0);
+        scene.optimize();
+        let tree = scene.accessibility_tree(&Transform2F::from_translation(vec2f(1.0, 0.0)));
+        assert_eq!(tree.get(heading).bounds,
+                   Some(RectF::new(vec2f(11.0, 10.0), vec2f(10.0, 10.0))));
+    }
+}
@@ -0,0 +1,717 @@
+// pathfinder/renderer/src/animation.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Keyframe animation of a retained scene.
+//!
+//! A `Timeline` holds tracks, each of which animates one property of a scene that has already been
+//! built: a layer's opacity, a paint's color, or the transform or trim of a draw path. Sampling the
+//! timeline with `Timeline::patch()` produces a `ScenePatch` holding only the properties whose
+//! values changed since the last patch, and applying the patch updates the scene in place through
+//! the same setters that an embedder would call, so nothing else has to be rebuilt.
+//!
+//! A `TimelinePlayer` drives a timeline from elapsed real time, so that an embedder only has to
+//! pass the time since the last frame and apply the patch it gets back.
+//!
+//! Draw paths are animated from an outline given when the track is created, so that transforms
+//! and trims don't accumulate from frame to frame.
+
+use crate::layer::LayerId;
+use crate::paint::{Paint, PaintId};
+use crate::scene::{DrawPathId, Scene};
+use pathfinder_color::ColorF;
+use pathfinder_content::dash::OutlineDash;
+use pathfinder_content::outline::Outline;
+use pathfinder_content::stroke::{OutlineStrokeToFill, StrokeStyle};
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+
+/// A property of a scene that a track animates.
+#[derive(Clone, Debug)]
+pub enum AnimationTarget {
+    /// The opacity of a layer, animated with `AnimationValue::Scalar` values.
+    LayerOpacity(LayerId),
+    /// The color of a paint, animated with `AnimationValue::Color` values. The paint is replaced
+    /// with a solid color.
+    PaintColor(PaintId),
+    /// The transform of a draw path, animated with `AnimationValue::Transform` values.
+    Transform {
+        /// The draw path to animate.
+        draw_path: DrawPathId,
+        /// The outline that the transform applies to.
+        outline: Outline,
+    },
+    /// The part of a stroke that's drawn, animated with `AnimationValue::Trim` values.
+    Trim {
+        /// The draw path to animate, which holds the stroke.
+        draw_path: DrawPathId,
+        /// The outline that was stroked.
+        outline: Outline,
+        /// The style to stroke the trimmed outline with.
+        stroke_style: StrokeStyle,
+    },
+}
+
+/// The value of an animated property at a keyframe.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AnimationValue {
+    /// A number, such as an opacity.
+    Scalar(f32),
+    /// A color.
+    Color(ColorF),
+    /// A transform. Transforms are interpolated component-wise, so rotations need keyframes a
+    /// few degrees apart to keep their shape.
+    Transform(Transform2F),
+    /// The part of a stroke that's drawn, as fractions of its length from 0.0 to 1.0.
+    Trim {
+        /// Where the drawn part starts.
+        start: f32,
+        /// Where the drawn part ends.
+        end: f32,
+    },
+}
+
+/// How a track moves from one keyframe's value to the next.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interpolator {
+    /// Holds the previous value until the keyframe, and then jumps to it.
+    Step,
+    /// Moves from the previous value at a constant rate.
+    Linear,
+    /// Moves from the previous value along an easing curve.
+    Ease(Easing),
+    /// Moves from the previous value like a mass on a spring released at rest.
+    ///
+    /// Springs may overshoot, and they settle in their own time rather than at the keyframe, so
+    /// the gap before the keyframe should be long enough for the spring to come to rest; the
+    /// property jumps to the keyframe's value when its time is reached.
+    Spring(Spring),
+}
+
+/// A curve that maps progress through the time between two keyframes to progress between their
+/// values.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Easing {
+    /// Starts slowly and ends at full speed, like CSS `ease-in`.
+    EaseIn,
+    /// Starts at full speed and ends slowly, like CSS `ease-out`.
+    EaseOut,
+    /// Starts and ends slowly, like CSS `ease-in-out`.
+    EaseInOut,
+    /// A cubic Bézier curve from (0, 0) to (1, 1) through the two given control points, like CSS
+    /// `cubic-bezier()`. The X coordinates of the control points are clamped to 0.0 to 1.0.
+    CubicBezier(Vector2F, Vector2F),
+}
+
+/// The physical parameters of a damped spring.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Spring {
+    /// How strongly the spring pulls toward its target.
+    pub stiffness: f32,
+    /// How strongly motion is resisted. A damping ratio below 1 oscillates around the target.
+    pub damping: f32,
+    /// The mass on the end of the spring.
+    pub mass: f32,
+}
+
+/// A value that a track reaches at a point in time.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Keyframe {
+    /// The time of this keyframe, in seconds from the start of the timeline.
+    pub time: f32,
+    /// The value of the property at this keyframe.
+    pub value: AnimationValue,
+    /// How the property moves to this keyframe from the previous one.
+    pub interpolator: Interpolator,
+}
+
+/// Plays a timeline against a clock, producing a patch for each frame.
+///
+/// Frames advance the playhead by the time that has passed, in seconds, and properties are sampled
+/// at the playhead, so animations take the same time and reach the same values at any frame rate.
+#[derive(Clone, Debug)]
+pub struct TimelinePlayer {
+    timeline: Timeline,
+    time: f32,
+    speed: f32,
+    looping: bool,
+}
+
+/// Identifies a track within a timeline.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TrackId(pub u32);
+
+/// A set of tracks animating properties of a scene.
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    tracks: Vec<Track>,
+}
+
+#[derive(Clone, Debug)]
+struct Track {
+    target: AnimationTarget,
+    keyframes: Vec<Keyframe>,
+    // The value included in the last patch, if any.
+    last_value: Option<AnimationValue>,
+}
+
+/// Changes to apply to a scene for one frame of an animation.
+#[derive(Clone, Debug, Default)]
+pub struct ScenePatch {
+    ops: Vec<PatchOp>,
+}
+
+/// A single change in a `ScenePatch`.
+#[derive(Clone, Debug)]
+pub enum PatchOp {
+    /// Sets the opacity of a layer.
+    SetLayerOpacity(LayerId, f32),
+    /// Replaces a paint.
+    SetPaint(PaintId, Paint),
+    /// Replaces the outline of a draw path.
+    SetDrawPathOutline(DrawPathId, Outline),
+}
+
+impl Keyframe {
+    /// Creates a keyframe that the property moves to at a constant rate.
+    #[inline]
+    pub fn linear(time: f32, value: AnimationValue) -> Keyframe {
+        Keyframe { time, value, interpolator: Interpolator::Linear }
+    }
+
+    /// Creates a keyframe that the property jumps to.
+    #[inline]
+    pub fn step(time: f32, value: AnimationValue) -> Keyframe {
+        Keyframe { time, value, interpolator: Interpolator::Step }
+    }
+}
+
+impl Timeline {
+    /// Creates an empty timeline.
+    #[inline]
+    pub fn new() -> Timeline {
+        Timeline::default()
+    }
+
+    /// Adds a track that animates `target` through `keyframes`.
+    ///
+    /// Keyframes are sorted by time. Before the first keyframe and after the last, the property
+    /// holds that keyframe's value. Panics if there are no keyframes, or if their values are of
+    /// the wrong kind for the target.
+    pub fn add_track(&mut self, target: AnimationTarget, mut keyframes: Vec<Keyframe>) -> TrackId {
+        assert!(!keyframes.is_empty(), "A track needs at least one keyframe!");
+        assert!(keyframes.iter().all(|keyframe| target.accepts(&keyframe.value)),
+                "Keyframe values don't match the animation target!");
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+        let track_id = TrackId(self.tracks.len() as u32);
+        self.tracks.push(Track { target, keyframes, last_value: None });
+        track_id
+    }
+
+    /// Returns the time of the last keyframe of any track, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.tracks.iter().flat_map(|track| track.keyframes.last()).fold(0.0, |duration, keyframe| {
+            f32::max(duration, keyframe.time)
+        })
+    }
+
+    /// Returns the value of a track at `time`, in seconds.
+    pub fn sample(&self, track_id: TrackId, time: f32) -> AnimationValue {
+        self.tracks[track_id.0 as usize].sample(time)
+    }
+
+    /// Returns the changes to bring a scene to its state at `time`, in seconds.
+    ///
+    /// Only properties whose values differ from those in the previous patch are included, so a
+    /// paused or finished animation produces empty patches. The first patch includes every track.
+    pub fn patch(&mut self, time: f32) -> ScenePatch {
+        let mut patch = ScenePatch::new();
+        for track in &mut self.tracks {
+            let value = track.sample(time);
+            if track.last_value == Some(value) {
+                continue;
+            }
+            track.last_value = Some(value);
+            patch.ops.push(track.target.patch_op(&value));
+        }
+        patch
+    }
+
+    /// Forgets the values in the previous patch, so that the next patch includes every track, as
+    /// for a scene that has been rebuilt.
+    pub fn invalidate(&mut self) {
+        for track in &mut self.tracks {
+            track.last_value = None;
+        }
+    }
+}
+
+impl Track {
+    fn sample(&self, time: f32) -> AnimationValue {
+        let next_index = self.keyframes.iter().position(|keyframe| keyframe.time > time);
+        let next_index = match next_index {
+            None => return self.keyframes.last().unwrap().value,
+            Some(0) => return self.keyframes[0].value,
+            Some(next_index) => next_index,
+        };
+
+        let (prev, next) = (&self.keyframes[next_index - 1], &self.keyframes[next_index]);
+        let t = (time - prev.time) / (next.time - prev.time);
+        match next.interpolator {
+            Interpolator::Step => prev.value,
+            Interpolator::Linear => prev.value.lerp(&next.value, t),
+            Interpolator::Ease(easing) => prev.value.lerp(&next.value, easing.apply(t)),
+            Interpolator::Spring(spring) => {
+                prev.value.lerp(&next.value, spring.displacement(time - prev.time))
+            }
+        }
+    }
+}
+
+impl Easing {
+    /// Returns the progress between two values at progress `t`, from 0.0 to 1.0, through the
+    /// time between them.
+    pub fn apply(&self, t: f32) -> f32 {
+        let (p1, p2) = match *self {
+            Easing::EaseIn => (vec2f(0.42, 0.0), vec2f(1.0, 1.0)),
+            Easing::EaseOut => (vec2f(0.0, 0.0), vec2f(0.58, 1.0)),
+            Easing::EaseInOut => (vec2f(0.42, 0.0), vec2f(0.58, 1.0)),
+            Easing::CubicBezier(p1, p2) => (p1, p2),
+        };
+        let t = t.max(0.0).min(1.0);
+        let (x1, x2) = (p1.x().max(0.0).min(1.0), p2.x().max(0.0).min(1.0));
+
+        // Find the curve parameter whose X is `t` by bisection, which always converges since X
+        // is monotonic once the control points are clamped.
+        let (mut lo, mut hi) = (0.0, 1.0);
+        for _ in 0..EASING_BISECTION_STEPS {
+            let mid = (lo + hi) * 0.5;
+            if cubic_bezier_1d(x1, x2, mid) < t {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        cubic_bezier_1d(p1.y(), p2.y(), (lo + hi) * 0.5)
+    }
+}
+
+// Evaluates one coordinate of a cubic Bézier curve that starts at 0 and ends at 1.
+fn cubic_bezier_1d(c1: f32, c2: f32, t: f32) -> f32 {
+    let s = 1.0 - t;
+    3.0 * s * s * t * c1 + 3.0 * s * t * t * c2 + t * t * t
+}
+
+const EASING_BISECTION_STEPS: u32 = 24;
+const SPRING_CRITICAL_EPSILON: f32 = 0.001;
+
+impl Spring {
+    /// Creates a spring with the given stiffness and damping ratio, and a mass of 1.
+    ///
+    /// A damping ratio of 1 settles as fast as possible without overshooting; lower ratios
+    /// bounce.
+    pub fn new(stiffness: f32, damping_ratio: f32) -> Spring {
+        Spring { stiffness, damping: damping_ratio * 2.0 * stiffness.sqrt(), mass: 1.0 }
+    }
+
+    /// Returns how far the spring has moved toward its target, where 0.0 is the start and 1.0 is
+    /// the target, `time` seconds after release.
+    pub fn displacement(&self, time: f32) -> f32 {
+        if !(self.stiffness > 0.0 && self.mass > 0.0) {
+            return 1.0;
+        }
+        let time = time.max(0.0);
+        let omega = (self.stiffness / self.mass).sqrt();
+        let zeta = self.damping.max(0.0) / (2.0 * (self.stiffness * self.mass).sqrt());
+
+        // Treat nearly critical damping as critical, since the other solutions lose precision
+        // as their roots converge.
+        if (zeta - 1.0).abs() < SPRING_CRITICAL_EPSILON {
+            1.0 - (-omega * time).exp() * (1.0 + omega * time)
+        } else if zeta < 1.0 {
+            let omega_d = omega * (1.0 - zeta * zeta).sqrt();
+            let decay = (-zeta * omega * time).exp();
+            1.0 - decay * ((omega_d * time).cos() + zeta * omega / omega_d * (omega_d * time).sin())
+        } else {
+            let root = (zeta * zeta - 1.0).sqrt();
+            let (r1, r2) = (-omega * (zeta - root), -omega * (zeta + root));
+            1.0 - (r2 * (r1 * time).exp() - r1 * (r2 * time).exp()) / (r2 - r1)
+        }
+    }
+}
+
+impl TimelinePlayer {
+    /// Creates a player at the start of `timeline`, playing at normal speed without looping.
+    pub fn new(timeline: Timeline) -> TimelinePlayer {
+        TimelinePlayer { timeline, time: 0.0, speed: 1.0, looping: false }
+    }
+
+    /// Returns the timeline being played.
+    #[inline]
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    /// Returns the position of the playhead, in seconds.
+    #[inline]
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Sets how many seconds of the timeline play per second of real time. Negative speeds play
+    /// backward, and a speed of 0.0 pauses.
+    #[inline]
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    /// Returns how many seconds of the timeline play per second of real time.
+    #[inline]
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Sets whether playback wraps around at either end of the timeline.
+    #[inline]
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// Returns true if playback wraps around at either end of the timeline.
+    #[inline]
+    pub fn looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Returns true if playback has reached the end of the timeline in the direction it's playing.
+    /// Looping players never finish.
+    pub fn is_finished(&self) -> bool {
+        !self.looping &&
+            ((self.speed > 0.0 && self.time >= self.timeline.duration()) ||
+             (self.speed < 0.0 && self.time <= 0.0))
+    }
+
+    /// Moves the playhead to `time`, in seconds, and returns the patch for that time.
+    pub fn seek(&mut self, time: f32) -> ScenePatch {
+        self.time = self.wrap(time);
+        self.timeline.patch(self.time)
+    }
+
+    /// Advances the playhead by `elapsed` seconds of real time, scaled by the speed, and returns
+    /// the patch for the new time.
+    pub fn advance(&mut self, elapsed: f32) -> ScenePatch {
+        self.seek(self.time + elapsed.max(0.0) * self.speed)
+    }
+
+    fn wrap(&self, time: f32) -> f32 {
+        let duration = self.timeline.duration();
+        if self.looping && duration > 0.0 {
+            time.rem_euclid(duration)
+        } else {
+            time.max(0.0).min(duration)
+        }
+    }
+}
+
+impl AnimationTarget {
+    fn accepts(&self, value: &AnimationValue) -> bool {
+        matches!((self, value),
+                 (AnimationTarget::LayerOpacity(_), AnimationValue::Scalar(_)) |
+                 (AnimationTarget::PaintColor(_), AnimationValue::Color(_)) |
+                 (AnimationTarget::Transform { .. }, AnimationValue::Transform(_)) |
+                 (AnimationTarget::Trim { .. }, AnimationValue::Trim { .. }))
+    }
+
+    fn patch_op(&self, value: &AnimationValue) -> PatchOp {
+        match (self, *value) {
+            (&AnimationTarget::LayerOpacity(layer_id), AnimationValue::Scalar(opacity)) => {
+                PatchOp::SetLayerOpacity(layer_id, opacity)
+            }
+            (&AnimationTarget::PaintColor(paint_id), AnimationValue::Color(color)) => {
+                PatchOp::SetPaint(paint_id, Paint::from_color(color.to_u8()))
+            }
+            (&AnimationTarget::Transform { draw_path, ref outline },
+             AnimationValue::Transform(transform)) => {
+                PatchOp::SetDrawPathOutline(draw_path, outline.clone().transformed(&transform))
+            }
+            (&AnimationTarget::Trim { draw_path, ref outline, stroke_style },
+             AnimationValue::Trim { start, end }) => {
+                let trimmed_outline = trim_stroke(outline, stroke_style, start, end);
+                PatchOp::SetDrawPathOutline(draw_path, trimmed_outline)
+            }
+            _ => unreachable!("Keyframe values are checked against their targets"),
+        }
+    }
+}
+
+impl AnimationValue {
+    /// Interpolates between this value and `other`, which must be of the same kind.
+    ///
+    /// `t` is usually from 0.0 to 1.0, but may go past either end, as for overshooting easings.
+    pub fn lerp(&self, other: &AnimationValue, t: f32) -> AnimationValue {
+        match (*self, *other) {
+            (AnimationValue::Scalar(a), AnimationValue::Scalar(b)) => {
+                AnimationValue::Scalar(a + (b - a) * t)
+            }
+            (AnimationValue::Color(a), AnimationValue::Color(b)) => {
+                AnimationValue::Color(a.lerp(b, t))
+            }
+            (AnimationValue::Transform(a), AnimationValue::Transform(b)) => {
+                let lerp = |a: f32, b: f32| a + (b - a) * t;
+                AnimationValue::Transform(Transform2F::row_major(lerp(a.m11(), b.m11()),
+                                                                 lerp(a.m12(), b.m12()),
+                                                                 lerp(a.m13(), b.m13()),
+                                                                 lerp(a.m21(), b.m21()),
+                                                                 lerp(a.m22(), b.m22()),
+                                                                 lerp(a.m23(), b.m23())))
+            }
+            (AnimationValue::Trim { start: start_a, end: end_a },
+             AnimationValue::Trim { start: start_b, end: end_b }) => {
+                AnimationValue::Trim {
+                    start: start_a + (start_b - start_a) * t,
+                    end: end_a + (end_b - end_a) * t,
+                }
+            }
+            _ => panic!("Can't interpolate between different kinds of values!"),
+        }
+    }
+}
+
+impl ScenePatch {
+    /// Creates an empty patch.
+    #[inline]
+    pub fn new() -> ScenePatch {
+        ScenePatch::default()
+    }
+
+    /// Returns true if this patch changes nothing.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Returns the changes in this patch, in the order they're applied.
+    #[inline]
+    pub fn ops(&self) -> &[PatchOp] {
+        &self.ops
+    }
+
+    /// Adds a change to this patch.
+    #[inline]
+    pub fn push(&mut self, op: PatchOp) {
+        self.ops.push(op);
+    }
+
+    /// Applies the changes in this patch to `scene`.
+    pub fn apply(self, scene: &mut Scene) {
+        for op in self.ops {
+            match op {
+                PatchOp::SetLayerOpacity(layer_id, opacity) => {
+                    scene.set_layer_opacity(layer_id, opacity)
+                }
+                PatchOp::SetPaint(paint_id, paint) => scene.set_paint(paint_id, &paint),
+                PatchOp::SetDrawPathOutline(draw_path_id, outline) => {
+                    scene.set_draw_path_outline(draw_path_id, outline)
+                }
+            }
+        }
+    }
+}
+
+// Strokes the part of `outline` from `start` to `end`, as fractions of its length. Like a line
+// trim in the canvas API, this is a dash pattern with a single dash.
+fn trim_stroke(outline: &Outline, stroke_style: StrokeStyle, start: f32, end: f32) -> Outline {
+    let (start, end) = (start.max(0.0).min(1.0), end.max(0.0).min(1.0));
+    let (start, end) = (f32::min(start, end), f32::max(start, end));
+    let length = outline.arc_length();
+    if end <= start || length.is_nan() || length <= 0.0 {
+        return Outline::new();
+    }
+
+    let trimmed = if end - start >= 1.0 {
+        outline.clone()
+    } else {
+        let dashes = [(end - start) * length, (1.0 - (end - start)) * length];
+        let mut dash = OutlineDash::new(outline, &dashes, (-start * length).rem_euclid(length));
+        dash.dash();
+        dash.into_outline()
+    };
+
+    let mut stroke_to_fill = OutlineStrokeToFill::new(&trimmed, stroke_style);
+    stroke_to_fill.offset();
+    stroke_to_fill.into_outline()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AnimationTarget, AnimationValue, Easing, Interpolator, Keyframe, PatchOp, Spring};
+    use super::{Timeline, TimelinePlayer};
+    use crate::paint::Paint;
+    use crate::scene::{DrawPath, Scene};
+    use pathfinder_color::{ColorF, ColorU};
+    use pathfinder_content::outline::Outline;
+    use pathfinder_content::stroke::{LineCap, LineJoin, StrokeStyle};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_tracks_interpolate_between_keyframes() {
+        let mut timeline = Timeline::new();
+        let mut scene = Scene::new();
+        let layer = scene.push_layer("fade".to_owned());
+        scene.pop_layer();
+        let opacity = timeline.add_track(AnimationTarget::LayerOpacity(layer), vec![
+            Keyframe::linear(2.0, AnimationValue::Scalar(0.0)),
+            Keyframe::linear(1.0, AnimationValue::Scalar(1.0)),
+            Keyframe::step(3.0, AnimationValue::Scalar(0.5)),
+        ]);
+
+        assert_eq!(timeline.duration(), 3.0);
+        assert_eq!(timeline.sample(opacity, 0.0), AnimationValue::Scalar(1.0));
+        assert_eq!(timeline.sample(opacity, 1.5), AnimationValue::Scalar(0.5));
+        assert_eq!(timeline.sample(opacity, 2.9), AnimationValue::Scalar(0.0));
+        assert_eq!(timeline.sample(opacity, 3.0), AnimationValue::Scalar(0.5));
+    }
+
+    #[test]
+    fn test_patches_only_hold_changes() {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let square = Outline::from_rect(RectF::new(vec2f(0.0, 0.0), vec2f(10.0, 10.0)));
+        let draw_path = scene.push_draw_path(DrawPath::new(square.clone(), paint));
+
+        let mut timeline = Timeline::new();
+        timeline.add_track(AnimationTarget::PaintColor(paint), vec![
+            Keyframe::linear(0.0, AnimationValue::Color(ColorF::new(1.0, 0.0, 0.0, 1.0))),
+            Keyframe::linear(1.0, AnimationValue::Color(ColorF::new(0.0, 0.0, 1.0, 1.0))),
+        ]);
+        timeline.add_track(AnimationTarget::Transform { draw_path, outline: square }, vec![
+            Keyframe::step(0.0, AnimationValue::Transform(Transform2F::default())),
+            Keyframe::linear(2.0, AnimationValue::Transform(
+                Transform2F::from_translation(vec2f(20.0, 0.0)))),
+        ]);
+
+        // Past the end of the color track, only the transform still changes.
+        assert_eq!(timeline.patch(0.0).ops().len(), 2);
+        assert!(timeline.patch(0.0).is_empty());
+        let patch = timeline.patch(1.5);
+        assert_eq!(patch.ops().len(), 2);
+        let patch = timeline.patch(2.0);
+        assert_eq!(patch.ops().len(), 1);
+        assert!(matches!(patch.ops()[0], PatchOp::SetDrawPathOutline(..)));
+
+        patch.apply(&mut scene);
+        assert_eq!(scene.get_draw_path(draw_path).outline.bounds(),
+                   RectF::new(vec2f(20.0, 0.0), vec2f(10.0, 10.0)));
+        assert!(timeline.patch(3.0).is_empty());
+
+        timeline.invalidate();
+        let patch = timeline.patch(3.0);
+        assert_eq!(patch.ops().len(), 2);
+        patch.apply(&mut scene);
+        assert_eq!(scene.get_paint(paint).base_color(), ColorU::new(0, 0, 255, 255));
+    }
+
+    #[test]
+    fn test_trims_stroke_part_of_the_outline() {
+        let mut line = Outline::new();
+        let mut contour = pathfinder_content::outline::Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_endpoint(vec2f(100.0, 0.0));
+        line.push_contour(contour);
+
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::black());
+        let draw_path = scene.push_draw_path(DrawPath::new(Outline::new(), paint));
+        let stroke_style = StrokeStyle {
+            line_width: 2.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter(10.0),
+        };
+
+        let mut timeline = Timeline::new();
+        timeline.add_track(AnimationTarget::Trim { draw_path, outline: line, stroke_style }, vec![
+            Keyframe::linear(0.0, AnimationValue::Trim { start: 0.0, end: 0.0 }),
+            Keyframe::linear(1.0, AnimationValue::Trim { start: 0.0, end: 1.0 }),
+        ]);
+        assert_eq!(timeline.patch(0.0).ops().len(), 1);
+        timeline.patch(0.25).apply(&mut scene);
+        let bounds = scene.get_draw_path(draw_path).outline.bounds();
+        assert!((bounds.min_x() - 0.0).abs() < 0.01 && (bounds.max_x() - 25.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_easings_keep_their_endpoints() {
+        for easing in &[Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut,
+                        Easing::CubicBezier(vec2f(0.3, -0.5), vec2f(0.7, 1.5))] {
+            assert!(easing.apply(0.0).abs() < 0.001);
+            assert!((easing.apply(1.0) - 1.0).abs() < 0.001);
+        }
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        assert!((Easing::EaseInOut.apply(0.5) - 0.5).abs() < 0.001);
+        assert!(Easing::CubicBezier(vec2f(0.3, -0.5), vec2f(0.7, 1.5)).apply(0.1) < 0.0);
+    }
+
+    #[test]
+    fn test_springs_settle_on_their_target() {
+        let bouncy = Spring::new(200.0, 0.3);
+        let stiff = Spring::new(200.0, 1.0);
+        let sluggish = Spring::new(200.0, 2.0);
+        for spring in &[bouncy, stiff, sluggish] {
+            assert_eq!(spring.displacement(0.0), 0.0);
+            assert!((spring.displacement(5.0) - 1.0).abs() < 0.001);
+        }
+        let peak = (1..100).map(|step| bouncy.displacement(step as f32 * 0.01)).fold(0.0, f32::max);
+        assert!(peak > 1.1);
+        assert!((1..100).all(|step| stiff.displacement(step as f32 * 0.01) <= 1.0));
+        assert!(sluggish.displacement(0.2) < stiff.displacement(0.2));
+    }
+
+    #[test]
+    fn test_players_are_frame_rate_independent() {
+        let mut scene = Scene::new();
+        let layer = scene.push_layer("fade".to_owned());
+        scene.pop_layer();
+        let mut timeline = Timeline::new();
+        timeline.add_track(AnimationTarget::LayerOpacity(layer), vec![
+            Keyframe::linear(0.0, AnimationValue::Scalar(0.0)),
+            Keyframe {
+                time: 1.0,
+                value: AnimationValue::Scalar(1.0),
+                interpolator: Interpolator::Spring(Spring::new(100.0, 0.5)),
+            },
+        ]);
+
+        let opacity_after = |frame_rate: u32, frames: u32| {
+            let mut player = TimelinePlayer::new(timeline.clone());
+            let mut opacity = None;
+            for _ in 0..frames {
+                for op in player.advance(1.0 / frame_rate as f32).ops() {
+                    if let PatchOp::SetLayerOpacity(_, value) = *op {
+                        opacity = Some(value);
+                    }
+                }
+            }
+            (opacity.unwrap(), player.is_finished())
+        };
+        let (slow, _) = opacity_after(30, 15);
+        let (fast, _) = opacity_after(120, 60);
+        assert!((slow - fast).abs() < 0.001);
+        assert_eq!(opacity_after(60, 90), (1.0, true));
+
+        let mut player = TimelinePlayer::new(timeline);
+        player.set_looping(true);
+        player.seek(0.75);
+        player.advance(0.5);
+        assert!((player.time() - 0.25).abs() < 0.001);
+        assert!(!player.is_finished());
+    }
+}
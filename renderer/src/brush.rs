@@ -0,0 +1,267 @@
+// pathfinder/renderer/src/brush.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Small scenes repeated and bent along a path, like pattern brushes in illustration tools.
+//!
+//! A brush is a tile of artwork, such as a braid or a chain link, laid out along the positive X
+//! axis and centered vertically on it. `Scene::push_brush_path()` repeats the tile along each
+//! contour of a path, stretching it slightly so that a whole number of copies fits, and bends it
+//! to follow the path. Decorative borders and frames are the typical use.
+//!
+//! The path is flattened into lines, and the part of each copy that falls beside each line is
+//! clipped out and moved onto that line with its own rigid transform. The pieces share edges
+//! exactly along straight runs and are drawn watertight, so no seams show there. At a bend, the
+//! pieces overlap on the inside of the turn and leave a wedge-shaped gap on the outside, which is
+//! small where the path curves gently relative to the height of the brush.
+
+use crate::scene::{DisplayItem, DrawPath, DrawPathId, Scene};
+use pathfinder_content::arc_length;
+use pathfinder_content::outline::{ContourIterFlags, Outline};
+use pathfinder_content::segment::Segment;
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::unit_vector::UnitVector;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use std::fmt::{self, Debug, Formatter};
+
+// How far curves in the path may deviate from the lines that the brush is bent along.
+const BRUSH_FLATTENING_TOLERANCE: f32 = 0.25;
+
+const EPSILON: f32 = 0.0001;
+
+/// A tile of artwork to repeat along paths.
+#[derive(Clone)]
+pub struct Brush {
+    scene: Scene,
+    bounds: RectF,
+}
+
+// Scenes can't be printed, so only their size is shown.
+impl Debug for Brush {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.debug_struct("Brush")
+                 .field("draw_path_count", &self.scene.draw_path_count())
+                 .field("bounds", &self.bounds)
+                 .finish()
+    }
+}
+
+impl Brush {
+    /// Creates a brush from the draw paths in `scene`.
+    ///
+    /// One copy of the tile spans the bounds of the paths' outlines horizontally, and the
+    /// vertical center of those bounds follows the path. Paints, fill rules, blend modes, and
+    /// tints are kept.
+    ///
+    /// Panics if any path in `scene` is clipped or drawn into a render target; flatten those into
+    /// plain paths first.
+    pub fn new(scene: Scene) -> Brush {
+        let mut bounds: Option<RectF> = None;
+        for display_item in scene.display_list() {
+            match *display_item {
+                DisplayItem::DrawPaths(ref draw_path_ids) => {
+                    for draw_path_index in draw_path_ids.start.0..draw_path_ids.end.0 {
+                        let draw_path = scene.get_draw_path(DrawPathId(draw_path_index));
+                        assert!(draw_path.clip_path.is_none(), "Brush paths can't be clipped!");
+                        let path_bounds = draw_path.outline.bounds();
+                        bounds = Some(match bounds {
+                            None => path_bounds,
+                            Some(bounds) => bounds.union_rect(path_bounds),
+                        });
+                    }
+                }
+                DisplayItem::PushRenderTarget(_) | DisplayItem::PopRenderTarget => {
+                    panic!("Brushes can't contain render targets!")
+                }
+            }
+        }
+        Brush { scene, bounds: bounds.unwrap_or_default() }
+    }
+
+    /// Returns the bounds of the tile, before it's bent along a path.
+    #[inline]
+    pub fn bounds(&self) -> RectF {
+        self.bounds
+    }
+
+    // The draw paths of the brush, in drawing order.
+    fn draw_paths(&self) -> impl Iterator<Item = &DrawPath> {
+        (0..self.scene.draw_path_count()).map(move |draw_path_index| {
+            self.scene.get_draw_path(DrawPathId(draw_path_index))
+        })
+    }
+}
+
+// A line of the flattened path, with the distance along the contour to its start.
+#[derive(Clone, Copy, Debug)]
+struct PathLine {
+    line: LineSegment2F,
+    start_distance: f32,
+    end_distance: f32,
+}
+
+impl Scene {
+    /// Repeats `brush` along every contour of `path`, bent to follow it, on top of all
+    /// previously-added paths.
+    ///
+    /// `path` is in scene space. Each contour gets a whole number of copies of the tile, at least
+    /// one, stretched or squeezed along the path to fit its length exactly.
+    pub fn push_brush_path(&mut self, brush: &Brush, path: &Outline) {
+        let tile_width = brush.bounds.width();
+        if tile_width < EPSILON {
+            return;
+        }
+        // Keep clipped pieces generously taller than the tile, so the slabs never cut off its top
+        // or bottom.
+        let slab_half_height = brush.bounds.height() + 1.0;
+
+        for contour in path.contours() {
+            let lines = flatten_contour(contour.iter(ContourIterFlags::empty()));
+            let length = match lines.last() {
+                None => continue,
+                Some(last_line) => last_line.end_distance,
+            };
+            let copy_count = f32::max(1.0, (length / tile_width).round());
+            let copy_length = length / copy_count;
+
+            for copy_index in 0..(copy_count as u32) {
+                let copy_start = copy_index as f32 * copy_length;
+                let copy_end = copy_start + copy_length;
+
+                // Lay the copy out along the X axis at its distance along the contour, centered
+                // vertically on it.
+                let layout_transform =
+                    Transform2F::from_translation(vec2f(copy_start, 0.0)) *
+                    Transform2F::from_scale(vec2f(copy_length / tile_width, 1.0)) *
+                    Transform2F::from_translation(-vec2f(brush.bounds.min_x(),
+                                                         brush.bounds.center().y()));
+
+                for path_line in &lines {
+                    if path_line.end_distance <= copy_start ||
+                            path_line.start_distance >= copy_end {
+                        continue;
+                    }
+                    let slab = slab_polygon(path_line, slab_half_height);
+                    let bend_transform = path_line_transform(path_line);
+
+                    for draw_path in brush.draw_paths() {
+                        let mut outline = draw_path.outline.clone().transformed(&layout_transform);
+                        if outline.is_outside_polygon(&slab) {
+                            continue;
+                        }
+                        outline.clip_against_polygon(&slab);
+                        if outline.is_empty() {
+                            continue;
+                        }
+                        outline.transform(&bend_transform);
+
+                        let mut paint = brush.scene.get_paint(draw_path.paint).clone();
+                        paint.apply_transform(&(bend_transform * layout_transform));
+                        let paint_id = self.push_paint(&paint);
+
+                        let mut piece = DrawPath::new(outline, paint_id);
+                        piece.set_fill_rule(draw_path.fill_rule);
+                        piece.set_blend_mode(draw_path.blend_mode);
+                        piece.set_tint(draw_path.tint);
+                        piece.set_watertight(true);
+                        piece.set_name(draw_path.name.clone());
+                        self.push_draw_path(piece);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Flattens the segments of a contour into lines labeled with their distance along it. Degenerate
+// lines are dropped.
+fn flatten_contour<I>(segments: I) -> Vec<PathLine> where I: Iterator<Item = Segment> {
+    let mut lines = vec![];
+    for segment in segments {
+        arc_length::flatten_segment(&segment, BRUSH_FLATTENING_TOLERANCE, &mut lines);
+    }
+
+    let mut path_lines = Vec::with_capacity(lines.len());
+    let mut distance = 0.0;
+    for line in lines {
+        let line_length = line.vector().length();
+        if line_length <= EPSILON {
+            continue;
+        }
+        path_lines.push(PathLine {
+            line,
+            start_distance: distance,
+            end_distance: distance + line_length,
+        });
+        distance += line_length;
+    }
+    path_lines
+}
+
+// The region of the laid-out brush that's bent onto `path_line`: everything between its start and
+// end distances. The vertices wind so that the inside is on the left of each edge, as
+// `Outline::clip_against_polygon()` expects.
+fn slab_polygon(path_line: &PathLine, half_height: f32) -> [Vector2F; 4] {
+    let (start, end) = (path_line.start_distance, path_line.end_distance);
+    [
+        vec2f(start, -half_height),
+        vec2f(end, -half_height),
+        vec2f(end, half_height),
+        vec2f(start, half_height),
+    ]
+}
+
+// Maps the laid-out brush onto `path_line`, taking the X axis at the line's start distance to the
+// line's start point and turning it to point along the line.
+fn path_line_transform(path_line: &PathLine) -> Transform2F {
+    let direction = path_line.line.vector().normalize();
+    Transform2F::from_translation(path_line.line.from()) *
+        Transform2F::from_rotation_vector(UnitVector(direction)) *
+        Transform2F::from_translation(vec2f(-path_line.start_distance, 0.0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::Brush;
+    use crate::paint::Paint;
+    use crate::scene::{DrawPath, DrawPathId, Scene};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_brush_follows_path() {
+        // A 10x4 tile, bent around an L-shaped path 30 units along and then 20 units down.
+        let mut tile = Scene::new();
+        let paint = tile.push_paint(&Paint::from_color(ColorU::black()));
+        let tile_rect = RectF::new(vec2f(0.0, -2.0), vec2f(10.0, 4.0));
+        tile.push_draw_path(DrawPath::new(Outline::from_rect(tile_rect), paint));
+        let brush = Brush::new(tile);
+        assert_eq!(brush.bounds(), tile_rect);
+
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_endpoint(vec2f(30.0, 0.0));
+        contour.push_endpoint(vec2f(30.0, 20.0));
+        let mut path = Outline::new();
+        path.push_contour(contour);
+
+        let mut scene = Scene::new();
+        scene.push_brush_path(&brush, &path);
+
+        // Five copies, one per 10 units of length, each landing on a single line.
+        assert_eq!(scene.draw_path_count(), 5);
+        let last_bounds = scene.get_draw_path(DrawPathId(4)).outline.bounds();
+        assert!((last_bounds.origin() - vec2f(28.0, 10.0)).length() < 0.001);
+        assert!((last_bounds.size() - vec2f(4.0, 10.0)).length() < 0.001);
+    }
+}
@@ -10,18 +10,22 @@
 
 //! Packs data onto the GPU.
 
-use crate::concurrent::executor::Executor;
+use crate::concurrent::executor::{Executor, SequentialExecutor};
 use crate::gpu::blend::BlendModeExt;
 use crate::gpu::options::RendererLevel;
+use crate::gpu::perf::FrameStats;
 use crate::gpu_data::{AlphaTileId, BackdropInfoD3D11, Clip, ClippedPathInfo, DiceMetadataD3D11};
 use crate::gpu_data::{DrawTileBatch, DrawTileBatchD3D9, DrawTileBatchD3D11, Fill, GlobalPathId};
 use crate::gpu_data::{PathBatchIndex, PathSource, PrepareTilesInfoD3D11, PropagateMetadataD3D11};
-use crate::gpu_data::{RenderCommand, SegmentIndicesD3D11, SegmentsD3D11, TileBatchDataD3D11};
-use crate::gpu_data::{TileBatchId, TileBatchTexture, TileObjectPrimitive, TilePathInfoD3D11};
-use crate::options::{PrepareMode, PreparedBuildOptions, PreparedRenderTransform};
-use crate::paint::{PaintId, PaintInfo, PaintMetadata};
+use crate::gpu_data::{RenderCommand, RoundedRectClip, SegmentIndicesD3D11, SegmentsD3D11};
+use crate::gpu_data::{TileBatchDataD3D11, TileBatchId, TileBatchTexture, TileObjectPrimitive};
+use crate::gpu_data::TilePathInfoD3D11;
+use crate::occlusion;
+use crate::options::{Degradation, PrepareMode, PreparedBuildOptions, PreparedRenderTransform};
+use crate::options::TilingMode;
+use crate::paint::{PaintInfo, PaintMetadata};
 use crate::scene::{ClipPathId, DisplayItem, DrawPath, DrawPathId, LastSceneInfo, PathId};
-use crate::scene::{Scene, SceneSink};
+use crate::scene::{Scene, SceneSink, outline_segment_count};
 use crate::tile_map::DenseTileMap;
 use crate::tiler::Tiler;
 use crate::tiles::{self, DrawTilingPathInfo, TILE_HEIGHT, TILE_WIDTH, TilingPathInfo};
@@ -29,16 +33,19 @@ use fxhash::FxHashMap;
 use instant::Instant;
 use pathfinder_content::effects::{BlendMode, Filter};
 use pathfinder_content::fill::FillRule;
-use pathfinder_content::outline::{Outline, PointFlags};
+use pathfinder_content::outline::{Contour, ContourIterFlags, Outline, PointFlags};
 use pathfinder_geometry::line_segment::{LineSegment2F, LineSegmentU16};
 use pathfinder_geometry::rect::{RectF, RectI};
 use pathfinder_geometry::transform2d::Transform2F;
-use pathfinder_geometry::vector::{Vector2I, vec2i};
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f, vec2i};
 use pathfinder_gpu::TextureSamplingFlags;
 use pathfinder_simd::default::F32x4;
 use std::borrow::Cow;
+use std::f32::consts::PI;
+use std::mem;
 use std::ops::Range;
-use std::sync::atomic::AtomicUsize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::u32;
 
 pub(crate) const ALPHA_TILE_LEVEL_COUNT: usize = 2;
@@ -49,11 +56,37 @@ const CURVE_IS_CUBIC:     u32 = 0x40000000;
 
 const MAX_CLIP_BATCHES: u32 = 32;
 
+// How far from a pixel boundary a clip rectangle's edges may be to be applied with a scissor.
+const SCISSOR_EPSILON: f32 = 1.0 / 256.0;
+// How far a clip path's points may be from those of a rounded rectangle for it to be applied
+// analytically.
+const ROUNDED_RECT_EPSILON: f32 = 1.0 / 64.0;
+// How far, relative to its radii, a draw path may stray from an ellipse for its coverage to be
+// evaluated analytically. Cubic quarter-circle arcs are within 0.03% of the radius.
+const ELLIPSE_TOLERANCE: f32 = 1.0 / 1024.0;
+// Below this, the terms of a curve's derivative are treated as zero when finding its extrema.
+const CURVE_EXTREMA_EPSILON: f32 = 1.0e-6;
+
+// How many times, and by how much, the flattening tolerance may be coarsened to fit a memory
+// budget before paths are culled instead.
+const MAX_TOLERANCE_COARSENING_STEPS: u32 = 2;
+const TOLERANCE_COARSENING_FACTOR: f32 = 4.0;
+
+// Draw paths with at least this many segments are split into chunks of segments that are tiled
+// in parallel, since a single path otherwise occupies one thread.
+const MIN_SPLIT_PATH_SEGMENT_COUNT: usize = 32768;
+const PATH_CHUNK_SEGMENT_COUNT: usize = 8192;
+
 pub(crate) struct SceneBuilder<'a, 'b, 'c, 'd> {
     pub(crate) scene: &'a mut Scene,
-    built_options: &'b PreparedBuildOptions,
+    pub(crate) built_options: &'b PreparedBuildOptions,
     next_alpha_tile_indices: [AtomicUsize; ALPHA_TILE_LEVEL_COUNT],
     pub(crate) sink: &'c mut SceneSink<'d>,
+    // The level this scene is built at, which may be D3D9 for a D3D11-level sink.
+    pub(crate) renderer_level: RendererLevel,
+    // Multiplies every path's flattening tolerance, to fit the memory budget.
+    flattening_tolerance_scale: f32,
+    skip_pattern_filters: bool,
 }
 
 #[derive(Debug)]
@@ -61,6 +94,7 @@ pub(crate) struct ObjectBuilder {
     pub built_path: BuiltPath,
     pub fills: Vec<Fill>,
     pub bounds: RectF,
+    pub local_alpha_tile_ids: bool,
 }
 
 // Derives `Clone` just so we can use `Cow`, not because we actually want to clone it.
@@ -74,22 +108,55 @@ struct BuiltDrawPath {
     sampling_flags_1: TextureSamplingFlags,
     mask_0_fill_rule: FillRule,
     occludes: bool,
+    scissor_rect: Option<RectI>,
+    rounded_clip: Option<RoundedRectClip>,
+    ellipse_rect: Option<RectF>,
+}
+
+// A clip path that the D3D9 renderer applies without clip tiles.
+#[derive(Clone, Copy, Debug)]
+enum FastClip {
+    Scissor(RectI),
+    RoundedRect(RoundedRectClip),
 }
 
 impl BuiltDrawPath {
-    fn new(built_path: BuiltPath, path_object: &DrawPath, paint_metadata: &PaintMetadata)
+    fn new(built_path: BuiltPath,
+           path_object: &DrawPath,
+           paint_metadata: &PaintMetadata,
+           fast_clip: Option<FastClip>,
+           ellipse_rect: Option<RectF>)
            -> BuiltDrawPath {
         let blend_mode = path_object.blend_mode();
-        let occludes = paint_metadata.is_opaque && blend_mode.occludes_backdrop();
+
+        // The Z buffer is per tile, so a path with a fast clip, or an ellipse evaluated in the
+        // shader, can't occlude whole tiles.
+        let occludes = paint_metadata.is_opaque && path_object.tint.is_opaque() &&
+            blend_mode.occludes_backdrop() && fast_clip.is_none() && ellipse_rect.is_none();
+        let (scissor_rect, rounded_clip) = match fast_clip {
+            None => (None, None),
+            Some(FastClip::Scissor(scissor_rect)) => (Some(scissor_rect), None),
+            Some(FastClip::RoundedRect(rounded_clip)) => (None, Some(rounded_clip)),
+        };
         BuiltDrawPath {
             path: built_path,
-            clip_path_id: path_object.clip_path(),
+            clip_path_id: if fast_clip.is_some() { None } else { path_object.clip_path() },
             filter: paint_metadata.filter(),
             color_texture: paint_metadata.tile_batch_texture(),
             sampling_flags_1: TextureSamplingFlags::empty(),
             mask_0_fill_rule: path_object.fill_rule(),
             blend_mode,
             occludes,
+            scissor_rect,
+            rounded_clip,
+            ellipse_rect,
+        }
+    }
+
+    // Draws the path's pattern unfiltered.
+    fn skip_pattern_filter(&mut self) {
+        if let Filter::PatternFilter(_) = self.filter {
+            self.filter = Filter::None;
         }
     }
 }
@@ -102,7 +169,7 @@ pub(crate) struct BuiltPath {
     pub fill_rule: FillRule,
     pub clip_path_id: Option<ClipPathId>,
     pub ctrl_byte: u8,
-    pub paint_id: PaintId,
+    pub metadata_id: u16,
 }
 
 #[derive(Clone, Debug)]
@@ -141,10 +208,19 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
             scene,
             built_options,
             next_alpha_tile_indices: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            renderer_level: sink.renderer_level,
             sink,
+            flattening_tolerance_scale: 1.0,
+            skip_pattern_filters: !built_options.pattern_filters_enabled,
         }
     }
 
+    // Returns true if the build has been cancelled, in which case tiling stops early.
+    #[inline]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.built_options.is_cancelled()
+    }
+
     pub fn build<E>(&mut self, executor: &E) where E: Executor {
         let start_time = Instant::now();
 
@@ -156,14 +232,18 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
         let total_path_count = clip_path_count + draw_path_count;
 
         let needs_readable_framebuffer = self.needs_readable_framebuffer();
+        self.renderer_level = self.choose_renderer_level();
 
         self.sink.listener.send(RenderCommand::Start {
             bounding_quad,
             path_count: total_path_count,
             needs_readable_framebuffer,
+            opaque_regions: self.built_options.opaque_regions.clone(),
+            renderer_level: self.renderer_level,
+            coverage_curve: self.scene.coverage_curve(),
         });
 
-        let prepare_mode = self.built_options.to_prepare_mode(self.sink.renderer_level);
+        let prepare_mode = self.built_options.to_prepare_mode(self.renderer_level);
 
         let render_transform = match self.built_options.transform {
             PreparedRenderTransform::Transform2D(transform) => transform.inverse(),
@@ -179,13 +259,49 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
             self.sink.listener.send(render_command);
         }
 
+        // Find paths that later opaque rectangles cover, so we can skip them.
+        let mut hidden_draw_paths =
+            occlusion::find_hidden_draw_paths(&self.scene, self.built_options, &paint_metadata);
+
+        let mut frame_stats = FrameStats {
+            stroke_time: self.scene.stroke_time(),
+            ..FrameStats::default()
+        };
+        let tile_start_time = Instant::now();
+        frame_stats.scene_build_time = tile_start_time - start_time;
+
+        let mut degradation = Degradation::default();
         let built_paths = match prepare_mode {
             PrepareMode::CPU | PrepareMode::TransformCPUBinGPU => {
-                Some(self.build_paths_on_cpu(executor, &paint_metadata, &prepare_mode))
+                // Tiling on one thread keeps fills and alpha tile IDs in path order.
+                Some(if self.built_options.deterministic {
+                    self.build_paths_within_budget(&SequentialExecutor,
+                                                   &paint_metadata,
+                                                   &mut hidden_draw_paths,
+                                                   &prepare_mode,
+                                                   &mut degradation)
+                } else {
+                    self.build_paths_within_budget(executor,
+                                                   &paint_metadata,
+                                                   &mut hidden_draw_paths,
+                                                   &prepare_mode,
+                                                   &mut degradation)
+                })
             }
             PrepareMode::GPU { .. } => None,
         };
-
+        frame_stats.tile_time = Instant::now() - tile_start_time;
+
+        // A cancelled build still finishes, so that whoever waits for `Finish` isn't left hanging.
+        if self.built_options.is_cancelled() {
+            let cpu_build_time = Instant::now() - start_time;
+            self.sink.listener.send(RenderCommand::Finish {
+                cpu_build_time,
+                degradation,
+                frame_stats,
+            });
+            return;
+        }
 
         #[cfg(feature="d3d11")]
         {
@@ -215,34 +331,111 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
             }
         }
 
-        self.finish_building(&paint_metadata, built_paths, &prepare_mode);
+        let batch_start_time = Instant::now();
+        self.finish_building(&paint_metadata, &hidden_draw_paths, built_paths, &prepare_mode);
+        frame_stats.batch_time = Instant::now() - batch_start_time;
 
         let cpu_build_time = Instant::now() - start_time;
-        self.sink.listener.send(RenderCommand::Finish { cpu_build_time });
+        self.sink.listener.send(RenderCommand::Finish { cpu_build_time, degradation, frame_stats });
+    }
+
+    // Tiles paths on the CPU. If the result exceeds the memory budget, this retiles with coarser
+    // flattening and without pattern filters, and then culls the last draw paths that don't fit.
+    // Fills are held back until they're known to fit.
+    fn build_paths_within_budget<E>(&mut self,
+                                    executor: &E,
+                                    paint_metadata: &[PaintMetadata],
+                                    hidden_draw_paths: &mut [bool],
+                                    prepare_mode: &PrepareMode,
+                                    degradation: &mut Degradation)
+                                    -> BuiltPaths
+                                    where E: Executor {
+        let budget = self.built_options.memory_budget;
+        let defer_fills = !budget.is_unlimited();
+        let mut culled = false;
+        loop {
+            for next_alpha_tile_index in &self.next_alpha_tile_indices {
+                next_alpha_tile_index.store(0, Ordering::Relaxed);
+            }
+            let (built_paths, fills) = self.build_paths_on_cpu(executor,
+                                                               paint_metadata,
+                                                               hidden_draw_paths,
+                                                               prepare_mode,
+                                                               defer_fills);
+
+            let alpha_tile_count: usize = self.next_alpha_tile_indices.iter().map(|index| {
+                index.load(Ordering::Relaxed)
+            }).sum();
+            let fill_count: usize = fills.iter().map(Vec::len).sum();
+            let cancelled = self.built_options.is_cancelled();
+            if culled || cancelled || budget.allows(alpha_tile_count, fill_count) {
+                for fills in fills {
+                    self.send_fills(fills);
+                }
+                return built_paths;
+            }
+
+            degradation.pattern_filters_skipped = true;
+            self.skip_pattern_filters = true;
+            if degradation.tolerance_coarsening_steps < MAX_TOLERANCE_COARSENING_STEPS {
+                degradation.tolerance_coarsening_steps += 1;
+                self.flattening_tolerance_scale *= TOLERANCE_COARSENING_FACTOR;
+                continue;
+            }
+
+            // Clip paths always stay. Keep draw paths in order while they fit, and cull the rest.
+            let clip_path_count = self.scene.clip_paths().len();
+            let draw_alpha_tile_count: usize =
+                built_paths.draw.iter().map(|draw_path| draw_path.path.alpha_tile_count()).sum();
+            let mut alpha_tile_count = alpha_tile_count - draw_alpha_tile_count;
+            let mut fill_count: usize = fills[..clip_path_count].iter().map(Vec::len).sum();
+            for (draw_path_index, draw_path) in built_paths.draw.iter().enumerate() {
+                alpha_tile_count += draw_path.path.alpha_tile_count();
+                fill_count += fills[clip_path_count + draw_path_index].len();
+                if !budget.allows(alpha_tile_count, fill_count) {
+                    for hidden in &mut hidden_draw_paths[draw_path_index..] {
+                        if !*hidden {
+                            *hidden = true;
+                            degradation.culled_path_count += 1;
+                        }
+                    }
+                    break;
+                }
+            }
+            culled = true;
+        }
     }
 
+    // Returns the built paths and the fills of each clip path and then each draw path. Unless
+    // `defer_fills` is set, fills are sent as soon as each path is tiled and aren't returned.
     fn build_paths_on_cpu<E>(&mut self,
                              executor: &E,
                              paint_metadata: &[PaintMetadata],
-                             prepare_mode: &PrepareMode)
-                             -> BuiltPaths
+                             hidden_draw_paths: &[bool],
+                             prepare_mode: &PrepareMode,
+                             defer_fills: bool)
+                             -> (BuiltPaths, Vec<Vec<Fill>>)
                              where E: Executor {
         let clip_path_count = self.scene.clip_paths().len();
         let draw_path_count = self.scene.draw_paths().len();
         let effective_view_box = self.scene.effective_view_box(self.built_options);
+        let fast_clips = self.find_fast_clips(prepare_mode);
 
         let built_clip_paths = executor.build_vector(clip_path_count, |path_index| {
-            self.build_clip_path_on_cpu(PathBuildParams {
+            let (built_path, fills) = self.build_clip_path_on_cpu(PathBuildParams {
                 path_id: PathId(path_index as u32),
                 view_box: effective_view_box,
                 prepare_mode: *prepare_mode,
                 built_options: &self.built_options,
                 scene: &self.scene,
-            })
+            }, fast_clips[path_index].is_some());
+            (built_path, self.send_or_defer_fills(fills, defer_fills))
         });
+        let (built_clip_paths, mut fills): (Vec<_>, Vec<_>) =
+            built_clip_paths.into_iter().unzip();
 
-        let built_draw_paths = executor.build_vector(draw_path_count, |path_index| {
-            self.build_draw_path_on_cpu(DrawPathBuildParams {
+        let draw_path_params = |path_index: usize, transformed_outline, split| {
+            DrawPathBuildParams {
                 path_build_params: PathBuildParams {
                     path_id: PathId(path_index as u32),
                     view_box: effective_view_box,
@@ -252,16 +445,77 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
                 },
                 paint_metadata: &paint_metadata,
                 built_clip_paths: &built_clip_paths,
-            })
+                fast_clips: &fast_clips,
+                hidden: hidden_draw_paths[path_index],
+                transformed_outline,
+                split,
+            }
+        };
+
+        // Tile the chunks of very long paths in parallel, transforming each such path only once.
+        let split_path_indices: Vec<usize> = (0..draw_path_count).filter(|&path_index| {
+            !hidden_draw_paths[path_index] &&
+                outline_segment_count(self.scene.draw_paths()[path_index].outline()) >=
+                MIN_SPLIT_PATH_SEGMENT_COUNT
+        }).collect();
+        let split_outlines = executor.build_vector(split_path_indices.len(), |split_index| {
+            let draw_path = &self.scene.draw_paths()[split_path_indices[split_index]];
+            self.scene.apply_render_options(draw_path.outline(), self.built_options)
         });
+        let mut transformed_outlines = vec![None; draw_path_count];
+        let mut chunk_tasks = vec![];
+        for (&path_index, outline) in split_path_indices.iter().zip(split_outlines.iter()) {
+            transformed_outlines[path_index] = Some(outline);
+            let segment_count = outline_segment_count(outline);
+            for start in (0..segment_count).step_by(PATH_CHUNK_SEGMENT_COUNT) {
+                // The last chunk takes any segments that the count missed.
+                let end = start + PATH_CHUNK_SEGMENT_COUNT;
+                let end = if end >= segment_count { usize::MAX } else { end };
+                chunk_tasks.push((path_index, start..end));
+            }
+        }
+        let chunks = executor.build_vector(chunk_tasks.len(), |task_index| {
+            let (path_index, ref segment_range) = chunk_tasks[task_index];
+            let split = PathSplit::Chunk(segment_range.clone());
+            let (built_draw_path, fills) = self.build_draw_path_on_cpu(
+                draw_path_params(path_index, transformed_outlines[path_index], split));
+            (built_draw_path.path, fills)
+        });
+        let chunks_by_path: Vec<_> = (0..draw_path_count).map(|_| Mutex::new(vec![])).collect();
+        for (&(path_index, _), chunk) in chunk_tasks.iter().zip(chunks) {
+            chunks_by_path[path_index].lock().unwrap().push(chunk);
+        }
 
-        BuiltPaths { draw: built_draw_paths }
+        let built_draw_paths = executor.build_vector(draw_path_count, |path_index| {
+            let split = match transformed_outlines[path_index] {
+                None => PathSplit::Whole,
+                Some(_) => {
+                    let mut chunks = chunks_by_path[path_index].lock().unwrap();
+                    PathSplit::Merge(mem::take(&mut *chunks))
+                }
+            };
+            let (built_draw_path, fills) = self.build_draw_path_on_cpu(
+                draw_path_params(path_index, transformed_outlines[path_index], split));
+            (built_draw_path, self.send_or_defer_fills(fills, defer_fills))
+        });
+        let (built_draw_paths, draw_fills): (Vec<_>, Vec<_>) =
+            built_draw_paths.into_iter().unzip();
+
+        fills.extend(draw_fills);
+        (BuiltPaths { draw: built_draw_paths }, fills)
     }
 
-    fn build_clip_path_on_cpu(&self, params: PathBuildParams) -> BuiltPath {
+    fn build_clip_path_on_cpu(&self, params: PathBuildParams, has_fast_clip: bool)
+                              -> (BuiltPath, Vec<Fill>) {
         let PathBuildParams { path_id, view_box, built_options, scene, prepare_mode } = params;
         let path_object = &scene.get_clip_path(path_id.to_clip_path_id());
-        let outline = scene.apply_render_options(path_object.outline(), built_options);
+
+        // Clip paths applied without clip tiles don't need tiles of their own.
+        let outline = if has_fast_clip {
+            Outline::new()
+        } else {
+            scene.apply_render_options(&scene.clip_path_outline(path_object), built_options)
+        };
 
         let mut tiler = Tiler::new(self,
                                    path_id,
@@ -271,14 +525,15 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
                                    &prepare_mode,
                                    path_object.clip_path(),
                                    &[],
-                                   TilingPathInfo::Clip);
+                                   TilingPathInfo::Clip,
+                                   built_options.flattening_tolerance *
+                                   self.flattening_tolerance_scale);
 
         tiler.generate_tiles();
-        self.send_fills(tiler.object_builder.fills);
-        tiler.object_builder.built_path
+        (tiler.object_builder.built_path, tiler.object_builder.fills)
     }
 
-    fn build_draw_path_on_cpu(&self, params: DrawPathBuildParams) -> BuiltDrawPath {
+    fn build_draw_path_on_cpu(&self, params: DrawPathBuildParams) -> (BuiltDrawPath, Vec<Fill>) {
         let DrawPathBuildParams {
             path_build_params: PathBuildParams {
                 path_id,
@@ -289,10 +544,57 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
             },
             paint_metadata,
             built_clip_paths,
+            fast_clips,
+            mut hidden,
+            transformed_outline,
+            split,
         } = params;
 
         let path_object = scene.get_draw_path(path_id.to_draw_path_id());
-        let outline = scene.apply_render_options(path_object.outline(), built_options);
+
+        // If the clip path has a fast path, tile the path unclipped, within the clip's bounds.
+        let mut clip_path_id = path_object.clip_path();
+        let fast_clip = clip_path_id.and_then(|clip_path_id| fast_clips[clip_path_id.0 as usize]);
+        let view_box = match fast_clip {
+            None => view_box,
+            Some(fast_clip) => {
+                clip_path_id = None;
+                let clip_bounds = match fast_clip {
+                    FastClip::Scissor(scissor_rect) => scissor_rect.to_f32(),
+                    FastClip::RoundedRect(rounded_clip) => rounded_clip.rect,
+                };
+                match view_box.intersection(clip_bounds) {
+                    Some(view_box) => view_box,
+                    None => {
+                        hidden = true;
+                        view_box
+                    }
+                }
+            }
+        };
+
+        let outline = match transformed_outline {
+            _ if hidden => Cow::Owned(Outline::new()),
+            Some(transformed_outline) => Cow::Borrowed(transformed_outline),
+            None => Cow::Owned(scene.apply_render_options(path_object.outline(), built_options)),
+        };
+
+        // Ellipses are tiled as the pixels around them, and the D3D9 tile shader evaluates their
+        // edges analytically.
+        let ellipse_rect = match (&split, prepare_mode) {
+            (PathSplit::Whole, PrepareMode::CPU) if !built_options.subpixel_aa_enabled => {
+                outline_ellipse_rect(&outline)
+            }
+            _ => None,
+        };
+        let outline = match ellipse_rect {
+            None => outline,
+            Some(ellipse_rect) => {
+                let pixel_rect = RectF::from_points(ellipse_rect.origin().floor(),
+                                                    ellipse_rect.lower_right().ceil());
+                Cow::Owned(Outline::from_rect(pixel_rect))
+            }
+        };
 
         let paint_id = path_object.paint();
         let paint_metadata = &paint_metadata[paint_id.0 as usize];
@@ -303,18 +605,33 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
                                    path_object.fill_rule(),
                                    view_box,
                                    &prepare_mode,
-                                   path_object.clip_path(),
+                                   clip_path_id,
                                    &built_clip_paths,
                                    TilingPathInfo::Draw(DrawTilingPathInfo {
-            paint_id,
+            metadata_id: scene.paint_metadata_id(path_object),
             blend_mode: path_object.blend_mode(),
             fill_rule: path_object.fill_rule(),
-        }));
-
-        tiler.generate_tiles();
-        self.send_fills(tiler.object_builder.fills);
+            watertight: path_object.watertight,
+            antialiased: path_object.is_antialiased(),
+        }),
+        built_options.flattening_tolerance * self.flattening_tolerance_scale *
+            path_object.quality.flattening_tolerance_scale());
+
+        match split {
+            PathSplit::Whole => tiler.generate_tiles(),
+            PathSplit::Chunk(segment_range) => tiler.generate_chunk_fills(segment_range),
+            PathSplit::Merge(chunks) => tiler.generate_tiles_from_chunks(chunks),
+        }
 
-        BuiltDrawPath::new(tiler.object_builder.built_path, path_object, paint_metadata)
+        let mut built_draw_path = BuiltDrawPath::new(tiler.object_builder.built_path,
+                                                     path_object,
+                                                     paint_metadata,
+                                                     fast_clip,
+                                                     ellipse_rect);
+        if self.skip_pattern_filters {
+            built_draw_path.skip_pattern_filter();
+        }
+        (built_draw_path, tiler.object_builder.fills)
     }
 
     fn send_fills(&self, fills: Vec<Fill>) {
@@ -324,20 +641,47 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
         }
     }
 
+    // Sends fills right away unless they have to be counted against the memory budget first.
+    fn send_or_defer_fills(&self, fills: Vec<Fill>, defer: bool) -> Vec<Fill> {
+        if defer {
+            return fills;
+        }
+        self.send_fills(fills);
+        vec![]
+    }
+
     fn build_tile_batches(&mut self,
                           paint_metadata: &[PaintMetadata],
+                          hidden_draw_paths: &[bool],
                           prepare_mode: &PrepareMode,
                           built_paths: Option<BuiltPaths>) {
         let mut tile_batch_builder = TileBatchBuilder::new(built_paths);
 
+        // Tiles that lie entirely within the embedder's opaque regions. Only the destination
+        // framebuffer is covered, not render targets.
+        let opaque_tile_rects: Vec<RectI> =
+            self.built_options.opaque_regions.iter().filter_map(|&region| {
+                let tile_size = vec2f(TILE_WIDTH as f32, TILE_HEIGHT as f32);
+                let tile_rect = RectF::from_points((region.origin() / tile_size).ceil(),
+                                                   (region.lower_right() / tile_size).floor());
+                if tile_rect.width() > 0.0 && tile_rect.height() > 0.0 {
+                    Some(tile_rect.to_i32())
+                } else {
+                    None
+                }
+            }).collect();
+        let mut framebuffer_nesting = 0;
+
         // Prepare display items.
         for display_item in self.scene.display_list() {
             match *display_item {
                 DisplayItem::PushRenderTarget(render_target_id) => {
+                    framebuffer_nesting += 1;
                     tile_batch_builder.draw_commands
                                       .push(RenderCommand::PushRenderTarget(render_target_id))
                 }
                 DisplayItem::PopRenderTarget => {
+                    framebuffer_nesting -= 1;
                     tile_batch_builder.draw_commands.push(RenderCommand::PopRenderTarget)
                 }
                 DisplayItem::DrawPaths(ref path_id_range) => {
@@ -347,6 +691,8 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
                         self.built_options,
                         path_id_range.start..path_id_range.end,
                         paint_metadata,
+                        hidden_draw_paths,
+                        if framebuffer_nesting == 0 { &opaque_tile_rects } else { &[] },
                         prepare_mode);
                 }
             }
@@ -358,15 +704,38 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
 
     fn finish_building(&mut self,
                        paint_metadata: &[PaintMetadata],
+                       hidden_draw_paths: &[bool],
                        built_paths: Option<BuiltPaths>,
                        prepare_mode: &PrepareMode) {
-        match self.sink.renderer_level {
+        match self.renderer_level {
             #[cfg(feature="d3d9")]
             RendererLevel::D3D9 => self.sink.listener.send(RenderCommand::FlushFillsD3D9),
             _ => {}
         }
 
-        self.build_tile_batches(paint_metadata, prepare_mode, built_paths);
+        self.build_tile_batches(paint_metadata, hidden_draw_paths, prepare_mode, built_paths);
+    }
+
+    // Chooses whether to tile on the CPU, at the D3D9 level, or on the GPU, at the D3D11 level.
+    fn choose_renderer_level(&self) -> RendererLevel {
+        let renderer_level = self.sink.renderer_level;
+        if renderer_level == RendererLevel::D3D9 || !cfg!(feature="d3d9") {
+            return renderer_level;
+        }
+        let tile_on_cpu = match self.built_options.tiling_mode {
+            TilingMode::ByLevel | TilingMode::GPU => false,
+            TilingMode::CPU => true,
+            TilingMode::Auto { max_cpu_path_count, max_cpu_segment_count } => {
+                let path_count = self.scene.draw_paths().len() + self.scene.clip_paths().len();
+                path_count <= max_cpu_path_count &&
+                    self.scene.segment_count() <= max_cpu_segment_count
+            }
+        };
+        if tile_on_cpu {
+            RendererLevel::D3D9
+        } else {
+            RendererLevel::D3D11
+        }
     }
 
     fn needs_readable_framebuffer(&self) -> bool {
@@ -391,6 +760,43 @@ impl<'a, 'b, 'c, 'd> SceneBuilder<'a, 'b, 'c, 'd> {
         }
         false
     }
+
+    // Finds the clip paths that the D3D9 renderer can apply without clip tiles: pixel-aligned
+    // rectangles, applied with a scissor rectangle, and rounded rectangles, which the tile shaders
+    // evaluate analytically.
+    fn find_fast_clips(&self, prepare_mode: &PrepareMode) -> Vec<Option<FastClip>> {
+        let clip_paths = self.scene.clip_paths();
+        let mut fast_clips = vec![None; clip_paths.len()];
+
+        let transform = match (prepare_mode, &self.built_options.transform) {
+            (&PrepareMode::CPU, &PreparedRenderTransform::None) => Transform2F::default(),
+            (&PrepareMode::CPU, &PreparedRenderTransform::Transform2D(transform)) => transform,
+            _ => return fast_clips,
+        };
+        if self.built_options.subpixel_aa_enabled || !self.built_options.dilation.is_zero() {
+            return fast_clips;
+        }
+
+        for (fast_clip, clip_path) in fast_clips.iter_mut().zip(clip_paths) {
+            if clip_path.clip_path().is_some() || clip_path.inverted {
+                continue;
+            }
+            let outline = clip_path.outline();
+            *fast_clip = match occlusion::transformed_rect(outline, &transform) {
+                Some(rect) => pixel_aligned_rect(rect).map(FastClip::Scissor),
+                None => transformed_rounded_rect(outline, &transform).map(FastClip::RoundedRect),
+            };
+        }
+
+        // Clip paths that clip other clip paths still need tiles.
+        for clip_path in clip_paths {
+            if let Some(clip_path_id) = clip_path.clip_path() {
+                fast_clips[clip_path_id.0 as usize] = None;
+            }
+        }
+
+        fast_clips
+    }
 }
 
 struct BuiltPaths {
@@ -409,9 +815,34 @@ struct DrawPathBuildParams<'a> {
     path_build_params: PathBuildParams<'a>,
     paint_metadata: &'a [PaintMetadata],
     built_clip_paths: &'a [BuiltPath],
+    fast_clips: &'a [Option<FastClip>],
+    hidden: bool,
+    // The path's outline with render options applied, if that's already been done.
+    transformed_outline: Option<&'a Outline>,
+    split: PathSplit,
+}
+
+// How much of a draw path to tile, for paths that are tiled in chunks.
+enum PathSplit {
+    // Tile the whole path at once.
+    Whole,
+    // Generate fills for just this range of segments, with chunk-local alpha tile IDs.
+    Chunk(Range<usize>),
+    // Combine these tiled chunks into the whole path.
+    Merge(Vec<(BuiltPath, Vec<Fill>)>),
 }
 
 impl BuiltPath {
+    // The number of alpha tiles allocated for this path during tiling on the CPU.
+    fn alpha_tile_count(&self) -> usize {
+        match self.data {
+            BuiltPathData::CPU(ref data) => {
+                data.tiles.data.iter().filter(|tile| !tile.is_solid()).count()
+            }
+            BuiltPathData::TransformCPUBinGPU(_) | BuiltPathData::GPU => 0,
+        }
+    }
+
     fn new(path_id: PathId,
            path_bounds: RectF,
            view_box_bounds: RectF,
@@ -420,9 +851,9 @@ impl BuiltPath {
            clip_path_id: Option<ClipPathId>,
            tiling_path_info: &TilingPathInfo)
            -> BuiltPath {
-        let paint_id = match *tiling_path_info {
-            TilingPathInfo::Draw(ref draw_tiling_path_info) => draw_tiling_path_info.paint_id,
-            TilingPathInfo::Clip => PaintId(0),
+        let metadata_id = match *tiling_path_info {
+            TilingPathInfo::Draw(ref draw_tiling_path_info) => draw_tiling_path_info.metadata_id,
+            TilingPathInfo::Clip => 0,
         };
 
         let ctrl_byte = tiling_path_info.to_ctrl();
@@ -447,7 +878,7 @@ impl BuiltPath {
                                 path_id,
                                 backdrop: 0,
                                 ctrl: ctrl_byte,
-                                metadata_id: paint_id.0,
+                                metadata_id,
                             }
                         }, tile_bounds),
                     clip_tiles: match *tiling_path_info {
@@ -479,7 +910,7 @@ impl BuiltPath {
             clip_path_id,
             fill_rule,
             ctrl_byte,
-            paint_id,
+            metadata_id,
         }
     }
 }
@@ -503,7 +934,55 @@ impl ObjectBuilder {
                                         prepare_mode,
                                         clip_path_id,
                                         tiling_path_info);
-        ObjectBuilder { built_path, bounds: path_bounds, fills: vec![] }
+        ObjectBuilder {
+            built_path,
+            bounds: path_bounds,
+            fills: vec![],
+            local_alpha_tile_ids: false,
+        }
+    }
+
+    // Combines chunks of this path's segments, each tiled with local alpha tile IDs, as though
+    // the whole path had been tiled at once. The chunks must have this path's tile bounds.
+    pub(crate) fn merge_chunks(&mut self,
+                               scene_builder: &SceneBuilder,
+                               chunks: Vec<(BuiltPath, Vec<Fill>)>) {
+        let tiled_data = match self.built_path.data {
+            BuiltPathData::CPU(ref mut tiled_data) => tiled_data,
+            BuiltPathData::TransformCPUBinGPU(_) | BuiltPathData::GPU => unreachable!(),
+        };
+
+        for (chunk_path, _) in &chunks {
+            let chunk_data = match chunk_path.data {
+                BuiltPathData::CPU(ref chunk_data) => chunk_data,
+                BuiltPathData::TransformCPUBinGPU(_) | BuiltPathData::GPU => unreachable!(),
+            };
+            debug_assert_eq!(chunk_data.tiles.rect, tiled_data.tiles.rect);
+            for (backdrop, chunk_backdrop) in tiled_data.backdrops
+                                                        .iter_mut()
+                                                        .zip(chunk_data.backdrops.iter()) {
+                *backdrop += *chunk_backdrop;
+            }
+            for (tile, chunk_tile) in tiled_data.tiles.data.iter_mut().zip(&chunk_data.tiles.data) {
+                tile.backdrop += chunk_tile.backdrop;
+                if chunk_tile.alpha_tile_id.is_valid() {
+                    tile.alpha_tile_id = chunk_tile.alpha_tile_id;
+                }
+            }
+        }
+
+        // Allocate alpha tiles in tile order, and point each chunk's fills at them.
+        for tile in &mut tiled_data.tiles.data {
+            if tile.alpha_tile_id.is_valid() {
+                tile.alpha_tile_id = AlphaTileId::new(&scene_builder.next_alpha_tile_indices, 0);
+            }
+        }
+        for (_, chunk_fills) in chunks {
+            self.fills.extend(chunk_fills.into_iter().map(|mut fill| {
+                fill.link = tiled_data.tiles.data[fill.link as usize].alpha_tile_id.0;
+                fill
+            }));
+        }
     }
 
     pub(crate) fn add_fill(&mut self,
@@ -552,6 +1031,72 @@ impl ObjectBuilder {
         });
     }
 
+    // Adds a rectangle with the given corners without lattice clipping. The tiles that it covers
+    // fully get backdrops, and each tile along its edges gets fills for just the part of the tile
+    // that it covers. The winding matches what the tiler computes for the same corners.
+    pub(crate) fn add_rect(&mut self, scene_builder: &SceneBuilder, corners: [Vector2F; 4]) {
+        let rect = RectF::from_points(corners[0].min(corners[2]), corners[0].max(corners[2]));
+        let rect = match rect.intersection(self.bounds) {
+            Some(rect) if rect.width() > 0.0 && rect.height() > 0.0 => rect,
+            _ => return,
+        };
+
+        // The tiler moves backdrops down by -1 below edges running left to right, which are the
+        // top edges of clockwise rectangles.
+        let clockwise = (corners[1] - corners[0]).det(corners[2] - corners[1]) > 0.0;
+        let winding = if clockwise { -1 } else { 1 };
+
+        let tile_size = vec2f(TILE_WIDTH as f32, TILE_HEIGHT as f32);
+        let solid_tile_rect = RectF::from_points((rect.origin() / tile_size).ceil(),
+                                                 (rect.lower_right() / tile_size).floor());
+        let solid_tile_rect = solid_tile_rect.to_i32();
+        let (solid_min_y, solid_max_y) = (solid_tile_rect.min_y(), solid_tile_rect.max_y());
+        let has_solid_rows = solid_min_y < solid_max_y;
+
+        // Where the rectangle ends partway down a row of tiles, the backdrop carries on into that
+        // row, so that the tiles there below solid ones only need a fill for the bottom edge.
+        let partial_bottom_row = rect.max_y() > (solid_max_y * TILE_HEIGHT as i32) as f32;
+        let backdrop_end_y = if partial_bottom_row { solid_max_y } else { solid_max_y - 1 };
+        if has_solid_rows {
+            for tile_x in solid_tile_rect.min_x()..solid_tile_rect.max_x() {
+                self.adjust_alpha_tile_backdrop(vec2i(tile_x, solid_min_y - 1), winding);
+                self.adjust_alpha_tile_backdrop(vec2i(tile_x, backdrop_end_y), -winding);
+            }
+        }
+
+        let tile_rect = tiles::round_rect_out_to_tile_bounds(rect);
+        for tile_y in tile_rect.min_y()..tile_rect.max_y() {
+            for tile_x in tile_rect.min_x()..tile_rect.max_x() {
+                let tile_coords = vec2i(tile_x, tile_y);
+                if solid_tile_rect.contains_point(tile_coords) {
+                    continue;
+                }
+                let tile_bounds = RectF::new(tile_coords.to_f32() * tile_size, tile_size);
+                let covered = match rect.intersection(tile_bounds) {
+                    Some(covered) => covered,
+                    None => continue,
+                };
+
+                // Each fill covers the area from its line down to the bottom of the tile.
+                let backdrop_carried = has_solid_rows && partial_bottom_row &&
+                    tile_y == solid_max_y &&
+                    tile_x >= solid_tile_rect.min_x() && tile_x < solid_tile_rect.max_x();
+                if !backdrop_carried {
+                    let top = LineSegment2F::new(covered.origin(), covered.upper_right());
+                    self.add_fill(scene_builder,
+                                  if clockwise { top } else { top.reversed() },
+                                  tile_coords);
+                }
+                if covered.max_y() < tile_bounds.max_y() {
+                    let bottom = LineSegment2F::new(covered.lower_left(), covered.lower_right());
+                    self.add_fill(scene_builder,
+                                  if clockwise { bottom.reversed() } else { bottom },
+                                  tile_coords);
+                }
+            }
+        }
+    }
+
     fn get_or_allocate_alpha_tile_index(&mut self,
                                         scene_builder: &SceneBuilder,
                                         tile_coords: Vector2I)
@@ -570,7 +1115,12 @@ impl ObjectBuilder {
             return alpha_tile_id;
         }
 
-        let alpha_tile_id = AlphaTileId::new(&scene_builder.next_alpha_tile_indices, 0);
+        // Chunks of split paths name tiles by index until they're merged.
+        let alpha_tile_id = if self.local_alpha_tile_ids {
+            AlphaTileId(local_tile_index as u32)
+        } else {
+            AlphaTileId::new(&scene_builder.next_alpha_tile_indices, 0)
+        };
         tiles.data[local_tile_index].alpha_tile_id = alpha_tile_id;
         alpha_tile_id
     }
@@ -617,7 +1167,10 @@ impl ObjectBuilder {
 }
 
 impl TileBatchDataD3D11 {
-    fn new(batch_id: TileBatchId, mode: &PrepareMode, path_source: PathSource)
+    fn new(batch_id: TileBatchId,
+           mode: &PrepareMode,
+           path_source: PathSource,
+           flattening_tolerance: f32)
            -> TileBatchDataD3D11 {
         TileBatchDataD3D11 {
             batch_id,
@@ -634,6 +1187,7 @@ impl TileBatchDataD3D11 {
                         dice_metadata: vec![],
                         tile_path_info: vec![],
                         transform: Transform2F::default(),
+                        flattening_tolerance,
                     }
                 }
                 PrepareMode::GPU { ref transform } => {
@@ -643,6 +1197,7 @@ impl TileBatchDataD3D11 {
                         dice_metadata: vec![],
                         tile_path_info: vec![],
                         transform: *transform,
+                        flattening_tolerance,
                     }
                 }
             },
@@ -655,6 +1210,7 @@ impl TileBatchDataD3D11 {
             global_path_id: PathId,
             batch_clip_path_id: Option<GlobalPathId>,
             z_write: bool,
+            tolerance_scale: f32,
             sink: &SceneSink)
             -> PathBatchIndex {
         let batch_path_index = PathBatchIndex(self.path_count);
@@ -704,7 +1260,7 @@ impl TileBatchDataD3D11 {
             first_batch_segment_index: self.segment_count,
             first_global_segment_index: segment_range.start,
             global_path_id,
-            pad: 0,
+            tolerance_scale,
         });
         self.prepare_info.tile_path_info.push(TilePathInfoD3D11 {
             tile_min_x: path.tile_bounds.min_x() as i16,
@@ -712,7 +1268,7 @@ impl TileBatchDataD3D11 {
             tile_max_x: path.tile_bounds.max_x() as i16,
             tile_max_y: path.tile_bounds.max_y() as i16,
             first_tile_index: self.tile_count,
-            color: path.paint_id.0,
+            color: path.metadata_id,
             ctrl: path.ctrl_byte,
             backdrop: 0,
         });
@@ -784,7 +1340,8 @@ impl BuiltSegments {
         };
 
         for clip_path in scene.clip_paths() {
-            let range = built_segments.clip_segments.add_path(clip_path.outline());
+            let outline = scene.clip_path_outline(clip_path);
+            let range = built_segments.clip_segments.add_path(&outline);
             built_segments.clip_segment_ranges.push(range);
         }
         for draw_path in scene.draw_paths() {
@@ -889,9 +1446,14 @@ impl TileBatchBuilder {
                                                      built_options: &PreparedBuildOptions,
                                                      draw_path_id_range: Range<DrawPathId>,
                                                      paint_metadata: &[PaintMetadata],
+                                                     hidden_draw_paths: &[bool],
+                                                     opaque_tile_rects: &[RectI],
                                                      prepare_mode: &PrepareMode) {
         let mut draw_tile_batch = None;
         for draw_path_id in draw_path_id_range.start.0..draw_path_id_range.end.0 {
+            if hidden_draw_paths[draw_path_id as usize] {
+                continue;
+            }
             let draw_path_id = DrawPathId(draw_path_id);
             let draw_path = match self.level {
                 #[cfg(feature="d3d11")]
@@ -920,8 +1482,11 @@ impl TileBatchBuilder {
                 }
                 #[cfg(feature="d3d9")]
                 Some(DrawTileBatch::D3D9(ref mut existing_batch)) => {
-                    !fixup_batch_for_new_path_if_possible(&mut existing_batch.color_texture,
-                                                          &draw_path)
+                    existing_batch.scissor_rect != draw_path.scissor_rect ||
+                        existing_batch.rounded_clip != draw_path.rounded_clip ||
+                        existing_batch.ellipse_rect != draw_path.ellipse_rect ||
+                        !fixup_batch_for_new_path_if_possible(&mut existing_batch.color_texture,
+                                                              &draw_path)
                 }
                 None => false,
             };
@@ -947,21 +1512,34 @@ impl TileBatchBuilder {
                     #[cfg(feature="d3d9")]
                     TileBatchBuilderLevel::D3D9 { .. } => {
                         let tile_bounds = tiles::round_rect_out_to_tile_bounds(scene.view_box());
+                        // Tiles under the embedder's opaque regions start out occluded by
+                        // everything.
+                        let z_buffer_data = DenseTileMap::from_builder(|tile_coords| {
+                            let under_opaque_region = opaque_tile_rects.iter().any(|rect| {
+                                rect.contains_point(tile_coords)
+                            });
+                            if under_opaque_region { i32::MAX } else { 0 }
+                        }, tile_bounds);
                         Some(DrawTileBatch::D3D9(DrawTileBatchD3D9 {
                             tiles: vec![],
                             clips: vec![],
-                            z_buffer_data: DenseTileMap::from_builder(|_| 0, tile_bounds),
+                            z_buffer_data,
                             color_texture: draw_path.color_texture,
                             filter: draw_path.filter,
                             blend_mode: draw_path.blend_mode,
+                            scissor_rect: draw_path.scissor_rect,
+                            rounded_clip: draw_path.rounded_clip,
+                            ellipse_rect: draw_path.ellipse_rect,
                         }))
                     }
                     #[cfg(feature="d3d11")]
                     TileBatchBuilderLevel::D3D11 { .. } => {
                         Some(DrawTileBatch::D3D11(DrawTileBatchD3D11 {
-                            tile_batch_data: TileBatchDataD3D11::new(self.next_batch_id,
-                                                                     &prepare_mode,
-                                                                     PathSource::Draw),
+                            tile_batch_data: TileBatchDataD3D11::new(
+                                self.next_batch_id,
+                                &prepare_mode,
+                                PathSource::Draw,
+                                built_options.flattening_tolerance),
                             color_texture: draw_path.color_texture,
                         }))
                     }
@@ -988,10 +1566,12 @@ impl TileBatchBuilder {
             match *draw_tile_batch {
                 #[cfg(feature="d3d11")]
                 DrawTileBatch::D3D11(ref mut draw_tile_batch) => {
+                    let quality = scene.get_draw_path(draw_path_id).quality;
                     draw_tile_batch.tile_batch_data.push(&draw_path.path,
                                                          draw_path_id.to_path_id(),
                                                          clip_path,
                                                          draw_path.occludes,
+                                                         quality.flattening_tolerance_scale(),
                                                          sink);
                 }
                 #[cfg(feature="d3d9")]
@@ -1087,11 +1667,18 @@ impl TileBatchBuilder {
                                         &prepare_mode,
                                         draw_path.clip_path(),
                                         &TilingPathInfo::Draw(DrawTilingPathInfo {
-                                            paint_id,
+                                            metadata_id: scene.paint_metadata_id(draw_path),
                                             blend_mode: draw_path.blend_mode(),
                                             fill_rule: draw_path.fill_rule(),
+                                            watertight: draw_path.watertight,
+                                            antialiased: draw_path.is_antialiased(),
                                         }));
-        Some(BuiltDrawPath::new(built_path, draw_path, paint_metadata))
+        let mut built_draw_path =
+            BuiltDrawPath::new(built_path, draw_path, paint_metadata, None, None);
+        if !built_options.pattern_filters_enabled {
+            built_draw_path.skip_pattern_filter();
+        }
+        Some(built_draw_path)
     }
 
     fn send_to(self, sink: &SceneSink) {
@@ -1154,9 +1741,11 @@ fn add_clip_path_to_batch(scene: &Scene,
                         let clip_tile_batch_id =
                             TileBatchId(clip_batches_d3d11.prepare_batches.len() as u32);
                         clip_batches_d3d11.prepare_batches
-                                          .push(TileBatchDataD3D11::new(clip_tile_batch_id,
-                                                                        &prepare_mode,
-                                                                        PathSource::Clip));
+                                          .push(TileBatchDataD3D11::new(
+                                                clip_tile_batch_id,
+                                                &prepare_mode,
+                                                PathSource::Clip,
+                                                built_options.flattening_tolerance));
                     }
                     let clip_path_batch_index =
                         clip_batches_d3d11.prepare_batches[clip_level]
@@ -1164,6 +1753,7 @@ fn add_clip_path_to_batch(scene: &Scene,
                                                 clip_path_id.to_path_id(),
                                                 subclip_id,
                                                 true,
+                                                1.0,
                                                 sink);
                     clip_batches_d3d11.clip_id_to_path_batch_index.insert(clip_path_id,
                                                                           clip_path_batch_index);
@@ -1204,7 +1794,7 @@ fn prepare_clip_path_for_gpu_binning(scene: &Scene,
                                             clip_level + 1,
                                             clip_batches_d3d11);
 
-    let path_bounds = transform * clip_path.outline().bounds();
+    let path_bounds = transform * scene.clip_path_outline(clip_path).bounds();
 
     // TODO(pcwalton): Clip to view box!
 
@@ -1224,6 +1814,145 @@ struct PreparedClipPath {
     subclip_id: Option<GlobalPathId>,
 }
 
+fn pixel_aligned_rect(rect: RectF) -> Option<RectI> {
+    let rounded_rect = rect.round();
+    if (rect.0 - rounded_rect.0).abs().packed_lt(F32x4::splat(SCISSOR_EPSILON)).all_true() {
+        Some(rounded_rect.to_i32())
+    } else {
+        None
+    }
+}
+
+// If `outline`, transformed, is a single rectangle with circular corners, as
+// `Outline::from_rect_rounded()` makes, returns it.
+fn transformed_rounded_rect(outline: &Outline, transform: &Transform2F)
+                            -> Option<RoundedRectClip> {
+    let contour = match *outline.contours() {
+        [ref contour] => contour.clone().transformed(transform),
+        _ => return None,
+    };
+    if contour.len() != 16 {
+        return None;
+    }
+
+    // The contour starts at the bottom of the upper left corner's arc, and that arc ends at the
+    // top edge.
+    let rect = contour.bounds();
+    let radius = contour.position_of(0).y() - rect.min_y();
+    if radius <= 0.0 || (contour.position_of(3).x() - rect.min_x() - radius).abs() >
+            ROUNDED_RECT_EPSILON {
+        return None;
+    }
+
+    let expected = Contour::from_rect_rounded(rect, Vector2F::splat(radius));
+    if expected.len() != contour.len() || !expected.is_closed() || !contour.is_closed() {
+        return None;
+    }
+    for point_index in 0..contour.len() {
+        if contour.flags_of(point_index) != expected.flags_of(point_index) {
+            return None;
+        }
+        let error = contour.position_of(point_index) - expected.position_of(point_index);
+        if error.length() > ROUNDED_RECT_EPSILON {
+            return None;
+        }
+    }
+
+    Some(RoundedRectClip { rect, radius })
+}
+
+// If `outline` is a single axis-aligned ellipse, as `Contour::push_ellipse()` makes, returns the
+// rectangle that it's inscribed in. Every segment has to stay on the ellipse and sweep around it
+// in the same direction, once.
+fn outline_ellipse_rect(outline: &Outline) -> Option<RectF> {
+    let contour = match *outline.contours() {
+        [ref contour] => contour,
+        _ => return None,
+    };
+    let rect = contour_curve_bounds(contour);
+    let (center, radii) = (rect.center(), rect.size() * 0.5);
+    if radii.x() <= 0.0 || radii.y() <= 0.0 {
+        return None;
+    }
+
+    let (mut swept_angle, mut direction): (f32, f32) = (0.0, 0.0);
+    for segment in contour.iter(ContourIterFlags::empty()) {
+        let mut last_angle: Option<f32> = None;
+        for &t in &[0.0, 0.25, 0.5, 0.75, 1.0] {
+            let vector = (segment.sample(t) - center) / radii;
+            if (vector.length() - 1.0).abs() > ELLIPSE_TOLERANCE {
+                return None;
+            }
+            let angle = vector.y().atan2(vector.x());
+            if let Some(last_angle) = last_angle {
+                let mut delta = angle - last_angle;
+                if delta > PI {
+                    delta -= PI * 2.0;
+                } else if delta < -PI {
+                    delta += PI * 2.0;
+                }
+                if delta.abs() > ELLIPSE_TOLERANCE {
+                    if delta * direction < 0.0 {
+                        return None;
+                    }
+                    direction = delta.signum();
+                }
+                swept_angle += delta;
+            }
+            last_angle = Some(angle);
+        }
+    }
+
+    if (swept_angle.abs() - PI * 2.0).abs() > 0.01 {
+        return None;
+    }
+    Some(rect)
+}
+
+// Returns the bounds of the curves of `contour` themselves, which may be tighter than
+// `Contour::bounds()`, since that includes control points.
+fn contour_curve_bounds(contour: &Contour) -> RectF {
+    let mut points = vec![];
+    for segment in contour.iter(ContourIterFlags::empty()) {
+        points.push(segment.baseline.from());
+        points.push(segment.baseline.to());
+        if segment.is_line() {
+            continue;
+        }
+
+        // The other extrema of a curve on each axis are where its derivative, a quadratic, is
+        // zero.
+        let segment = segment.to_cubic();
+        let (from, to) = (segment.baseline.from(), segment.baseline.to());
+        let (ctrl0, ctrl1) = (segment.ctrl.from(), segment.ctrl.to());
+        for &(p0, p1, p2, p3) in &[(from.x(), ctrl0.x(), ctrl1.x(), to.x()),
+                                   (from.y(), ctrl0.y(), ctrl1.y(), to.y())] {
+            let (d0, d1, d2) = (p1 - p0, p2 - p1, p3 - p2);
+            let (a, b, c) = (d0 - 2.0 * d1 + d2, 2.0 * (d1 - d0), d0);
+            let mut roots = vec![];
+            if a.abs() < CURVE_EXTREMA_EPSILON {
+                if b.abs() >= CURVE_EXTREMA_EPSILON {
+                    roots.push(-c / b);
+                }
+            } else if b * b - 4.0 * a * c >= 0.0 {
+                let root = (b * b - 4.0 * a * c).sqrt();
+                roots.push((-b + root) / (2.0 * a));
+                roots.push((-b - root) / (2.0 * a));
+            }
+            for t in roots.into_iter().filter(|t| (0.0..=1.0).contains(t)) {
+                points.push(segment.sample(t));
+            }
+        }
+    }
+
+    let mut points = points.into_iter();
+    let first = match points.next() {
+        None => return RectF::default(),
+        Some(first) => RectF::from_points(first, first),
+    };
+    points.fold(first, |bounds, point| bounds.union_rect(RectF::from_points(point, point)))
+}
+
 fn fixup_batch_for_new_path_if_possible(batch_color_texture: &mut Option<TileBatchTexture>,
                                         draw_path: &BuiltDrawPath)
                                         -> bool {
@@ -1241,3 +1970,509 @@ fn fixup_batch_for_new_path_if_possible(batch_color_texture: &mut Option<TileBat
     }
     true
 }
+
+#[cfg(all(test, feature = "d3d9"))]
+mod test {
+    use crate::concurrent::build_job::CancellationToken;
+    use crate::concurrent::rayon::RayonExecutor;
+    use crate::gpu::options::RendererLevel;
+    use crate::gpu_data::{DrawTileBatchD3D9, Fill, RenderCommand, TileObjectPrimitive};
+    use crate::options::{BuildOptions, Degradation, MemoryBudget, RenderCommandListener};
+    use crate::options::TilingMode;
+    use crate::paint::Paint;
+    use crate::scene::{ClipPath, DrawPath, RenderTarget, Scene, SceneSink};
+    use crate::scene::outline_segment_count;
+    use crate::tiles::{TILE_HEIGHT, TILE_WIDTH};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::effects::{BlurDirection, Filter, PatternFilter};
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_content::pattern::Pattern;
+    use pathfinder_geometry::rect::{RectF, RectI};
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::{vec2f, vec2i};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    // Builds the scene into a sink at the given level and returns the commands it was sent.
+    fn build_commands_at_level(scene: &mut Scene, level: RendererLevel, options: BuildOptions)
+                               -> Vec<RenderCommand> {
+        let commands = Mutex::new(vec![]);
+        let listener = RenderCommandListener::new(Box::new(|command| {
+            commands.lock().unwrap().push(command);
+        }));
+        let mut sink = SceneSink::new(listener, level);
+        scene.build(options, &mut sink, &RayonExecutor);
+        drop(sink);
+        commands.into_inner().unwrap()
+    }
+
+    fn build_commands(scene: &mut Scene, options: BuildOptions) -> Vec<RenderCommand> {
+        build_commands_at_level(scene, RendererLevel::D3D9, options)
+    }
+
+    // Records the fills and tiles of a build, which are what thread scheduling could reorder.
+    fn build_with_rayon(scene: &mut Scene) -> Vec<String> {
+        let options = BuildOptions { deterministic: true, ..BuildOptions::default() };
+        build_commands(scene, options).into_iter().filter_map(|command| {
+            match command {
+                RenderCommand::AddFillsD3D9(fills) => Some(format!("{:?}", fills)),
+                RenderCommand::DrawTilesD3D9(batch) => Some(format!("{:?}", batch.tiles)),
+                _ => None,
+            }
+        }).collect()
+    }
+
+    // Returns the tile batches that a build sent.
+    fn build_tile_batches(scene: &mut Scene, options: BuildOptions) -> Vec<DrawTileBatchD3D9> {
+        build_commands(scene, options).into_iter().filter_map(|command| {
+            match command {
+                RenderCommand::DrawTilesD3D9(batch) => Some(batch),
+                _ => None,
+            }
+        }).collect()
+    }
+
+    // Returns the number of fills that a build sent.
+    fn fill_count(commands: &[RenderCommand]) -> usize {
+        commands.iter().map(|command| {
+            match *command {
+                RenderCommand::AddFillsD3D9(ref fills) => fills.len(),
+                _ => 0,
+            }
+        }).sum()
+    }
+
+    // Returns how a build degraded.
+    fn degradation(commands: &[RenderCommand]) -> Degradation {
+        commands.iter().find_map(|command| {
+            match *command {
+                RenderCommand::Finish { degradation, .. } => Some(degradation),
+                _ => None,
+            }
+        }).expect("No finish command!")
+    }
+
+    #[test]
+    fn test_deterministic_builds_match() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(512.0, 512.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 0, 128)));
+        for index in 0..256 {
+            let origin = vec2f((index * 37 % 480) as f32 + 0.3, (index * 53 % 480) as f32 + 0.7);
+            let rect = RectF::new(origin, vec2f(24.5, 17.25));
+            let outline = Outline::from_rect_rounded(rect, vec2f(6.0, 6.0));
+            scene.push_draw_path(DrawPath::new(outline, paint));
+        }
+
+        let first = build_with_rayon(&mut scene);
+        assert!(!first.is_empty());
+        for _ in 0..4 {
+            assert_eq!(build_with_rayon(&mut scene), first);
+        }
+    }
+
+    #[test]
+    fn test_rect_clips_become_scissor_rects() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(512.0, 512.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 0, 255)));
+        let clip_rect = RectF::new(vec2f(10.0, 20.0), vec2f(100.0, 50.0));
+        let clip_path = scene.push_clip_path(ClipPath::new(Outline::from_rect(clip_rect)));
+        let circle = Outline::from_rect_rounded(RectF::new(vec2f(0.0, 0.0), vec2f(128.0, 128.0)),
+                                                vec2f(64.0, 64.0));
+        let mut draw_path = DrawPath::new(circle, paint);
+        draw_path.set_clip_path(Some(clip_path));
+        scene.push_draw_path(draw_path);
+
+        let batches = build_tile_batches(&mut scene, BuildOptions::default());
+        assert!(batches.iter().all(|batch| batch.clips.is_empty()));
+        let scissor_rects: Vec<_> = batches.iter().map(|batch| batch.scissor_rect).collect();
+        let expected = RectI::new(vec2i(10, 20), vec2i(100, 50));
+        assert_eq!(scissor_rects, vec![Some(expected)]);
+    }
+
+    #[test]
+    fn test_rounded_rect_clips_are_evaluated_in_shader() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(512.0, 512.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 0, 255)));
+        let clip_rect = RectF::new(vec2f(10.5, 20.0), vec2f(100.0, 50.0));
+        let clip_outline = Outline::from_rect_rounded(clip_rect, vec2f(8.0, 8.0));
+        let clip_path = scene.push_clip_path(ClipPath::new(clip_outline));
+        let outline = Outline::from_rect(RectF::new(vec2f(0.0, 0.0), vec2f(128.0, 128.0)));
+        let mut draw_path = DrawPath::new(outline, paint);
+        draw_path.set_clip_path(Some(clip_path));
+        scene.push_draw_path(draw_path);
+
+        let batches = build_tile_batches(&mut scene, BuildOptions::default());
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0].clips.is_empty());
+        assert_eq!(batches[0].scissor_rect, None);
+        let rounded_clip = batches[0].rounded_clip.expect("Rounded clip wasn't detected!");
+        assert_eq!(rounded_clip.rect, clip_rect);
+        assert!((rounded_clip.radius - 8.0).abs() < 1.0 / 64.0);
+    }
+
+    #[test]
+    fn test_opaque_regions_hide_paths_and_tiles() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(512.0, 512.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 0, 128)));
+        let hidden = Outline::from_rect_rounded(RectF::new(vec2f(40.0, 40.0), vec2f(20.0, 20.0)),
+                                                vec2f(10.0, 10.0));
+        scene.push_draw_path(DrawPath::new(hidden, paint));
+        let visible = Outline::from_rect(RectF::new(vec2f(0.0, 0.0), vec2f(256.0, 256.0)));
+        scene.push_draw_path(DrawPath::new(visible, paint));
+
+        let opaque_region = RectF::new(vec2f(15.5, 16.0), vec2f(113.0, 64.0));
+        let options = BuildOptions {
+            opaque_regions: vec![opaque_region],
+            ..BuildOptions::default()
+        };
+        let batches = build_tile_batches(&mut scene, options);
+
+        // Only whole tiles within the region, which spans tiles (1, 1) to (7, 4), are occluded.
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0].tiles.iter().all(|tile| tile.path_id.0 == 1));
+        let z_buffer = &batches[0].z_buffer_data;
+        assert_eq!(*z_buffer.get(vec2i(1, 1)).unwrap(), i32::MAX);
+        assert_eq!(*z_buffer.get(vec2i(7, 4)).unwrap(), i32::MAX);
+        assert_eq!(*z_buffer.get(vec2i(0, 1)).unwrap(), 0);
+        assert_eq!(*z_buffer.get(vec2i(8, 1)).unwrap(), 0);
+        assert_eq!(*z_buffer.get(vec2i(1, 5)).unwrap(), 0);
+    }
+
+    // Returns the number of fills a build sent and how it degraded.
+    fn build_with_budget(scene: &mut Scene, memory_budget: MemoryBudget)
+                         -> (usize, Degradation) {
+        let options = BuildOptions { memory_budget, ..BuildOptions::default() };
+        let commands = build_commands(scene, options);
+        (fill_count(&commands), degradation(&commands))
+    }
+
+    #[test]
+    fn test_memory_budget_degrades_instead_of_overflowing() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(512.0, 512.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 0, 128)));
+        for index in 0..64 {
+            let origin = vec2f((index % 8 * 64) as f32 + 0.5, (index / 8 * 64) as f32 + 0.5);
+            let rect = RectF::new(origin, vec2f(48.0, 48.0));
+            let outline = Outline::from_rect_rounded(rect, vec2f(20.0, 20.0));
+            scene.push_draw_path(DrawPath::new(outline, paint));
+        }
+
+        let (fill_count, degradation) = build_with_budget(&mut scene, MemoryBudget::default());
+        assert!(!degradation.is_degraded());
+
+        let (fill_count, degradation) = build_with_budget(&mut scene, MemoryBudget {
+            max_fills: Some(fill_count),
+            ..MemoryBudget::default()
+        });
+        assert!(!degradation.is_degraded());
+
+        let max_fills = fill_count / 4;
+        let (fill_count, degradation) = build_with_budget(&mut scene, MemoryBudget {
+            max_fills: Some(max_fills),
+            ..MemoryBudget::default()
+        });
+        assert!(fill_count <= max_fills);
+        assert!(degradation.pattern_filters_skipped);
+        assert_eq!(degradation.tolerance_coarsening_steps, 2);
+        assert!(degradation.culled_path_count > 0 && degradation.culled_path_count < 64);
+    }
+
+    // Returns the number of fills a build sent and whether any batch applied a pattern filter.
+    fn build_for_filters(scene: &mut Scene, options: BuildOptions)
+                         -> (usize, bool, Degradation) {
+        let commands = build_commands(scene, options);
+        let filtered = commands.iter().any(|command| {
+            match *command {
+                RenderCommand::DrawTilesD3D9(ref batch) => {
+                    matches!(batch.filter, Filter::PatternFilter(_))
+                }
+                _ => false,
+            }
+        });
+        (fill_count(&commands), filtered, degradation(&commands))
+    }
+
+    #[test]
+    fn test_thumbnails_are_coarse_and_unfiltered() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(256.0, 256.0)));
+        let size = vec2i(64, 64);
+        let render_target = scene.push_render_target(RenderTarget::new(size, String::new()));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 255, 255)));
+        let square = Outline::from_rect(RectF::new(vec2f(16.0, 16.0), vec2f(32.0, 32.0)));
+        scene.push_draw_path(DrawPath::new(square, paint));
+        scene.pop_render_target();
+
+        let mut pattern = Pattern::from_render_target(render_target, size);
+        pattern.set_filter(Some(PatternFilter::Blur { direction: BlurDirection::X, sigma: 2.0 }));
+        let blurred_paint = scene.push_paint(&Paint::from_pattern(pattern));
+        let rect = RectF::new(vec2f(8.5, 8.5), vec2f(240.0, 240.0));
+        let outline = Outline::from_rect_rounded(rect, vec2f(96.0, 96.0));
+        scene.push_draw_path(DrawPath::new(outline, blurred_paint));
+
+        let (full_fill_count, filtered, _) = build_for_filters(&mut scene, BuildOptions::default());
+        assert!(filtered);
+
+        let options = BuildOptions::thumbnail(Transform2F::default());
+        let (fill_count, filtered, degradation) = build_for_filters(&mut scene, options);
+        assert!(!filtered);
+        assert!(fill_count < full_fill_count);
+        assert!(!degradation.is_degraded());
+    }
+
+    // Returns the level a build chose and whether it sent fills, which only CPU tiling produces.
+    fn build_with_tiling_mode(scene: &mut Scene, sink_level: RendererLevel, mode: TilingMode)
+                              -> (RendererLevel, bool) {
+        let options = BuildOptions { tiling_mode: mode, ..BuildOptions::default() };
+        let commands = build_commands_at_level(scene, sink_level, options);
+        let renderer_level = commands.iter().find_map(|command| {
+            match *command {
+                RenderCommand::Start { renderer_level, .. } => Some(renderer_level),
+                _ => None,
+            }
+        }).expect("No start command!");
+        let tiled_on_cpu = commands.iter().any(|command| {
+            matches!(*command, RenderCommand::AddFillsD3D9(_))
+        });
+        (renderer_level, tiled_on_cpu)
+    }
+
+    #[test]
+    fn test_small_scenes_tile_on_cpu() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(256.0, 256.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 0, 128)));
+        let rect = RectF::new(vec2f(10.5, 10.5), vec2f(100.0, 100.0));
+        scene.push_draw_path(DrawPath::new(Outline::from_rect_rounded(rect, vec2f(8.0, 8.0)),
+                                           paint));
+        assert_eq!(scene.segment_count(), 8);
+
+        let result = build_with_tiling_mode(&mut scene, RendererLevel::D3D11, TilingMode::auto());
+        assert_eq!(result, (RendererLevel::D3D9, true));
+        let result = build_with_tiling_mode(&mut scene, RendererLevel::D3D11, TilingMode::CPU);
+        assert_eq!(result, (RendererLevel::D3D9, true));
+
+        // A D3D9-level renderer can't tile on the GPU.
+        let result = build_with_tiling_mode(&mut scene, RendererLevel::D3D9, TilingMode::GPU);
+        assert_eq!(result, (RendererLevel::D3D9, true));
+    }
+
+    // Returns the fills and tiles that a build sent.
+    fn build_fills_and_tiles(scene: &mut Scene) -> (Vec<Fill>, Vec<TileObjectPrimitive>) {
+        let (mut fills, mut tiles) = (vec![], vec![]);
+        for command in build_commands(scene, BuildOptions::default()) {
+            match command {
+                RenderCommand::AddFillsD3D9(command_fills) => fills.extend(command_fills),
+                RenderCommand::DrawTilesD3D9(batch) => tiles.extend(batch.tiles),
+                _ => {}
+            }
+        }
+        (fills, tiles)
+    }
+
+    #[test]
+    fn test_long_paths_tile_in_chunks_seamlessly() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(256.0, 256.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 0, 128)));
+
+        // A rectangle whose sides are each made of many short lines, so that it's split.
+        const SEGMENTS_PER_SIDE: usize = 10000;
+        let corners = [vec2f(8.5, 8.5), vec2f(248.5, 8.5), vec2f(248.5, 248.5), vec2f(8.5, 248.5)];
+        let mut contour = Contour::new();
+        for (side, &from) in corners.iter().enumerate() {
+            let to = corners[(side + 1) % 4];
+            for step in 0..SEGMENTS_PER_SIDE {
+                contour.push_endpoint(from.lerp(to, step as f32 / SEGMENTS_PER_SIDE as f32));
+            }
+        }
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        assert_eq!(outline_segment_count(&outline), SEGMENTS_PER_SIDE * 4);
+        scene.push_draw_path(DrawPath::new(outline, paint));
+
+        let (fills, tiles) = build_fills_and_tiles(&mut scene);
+
+        // The 16x16 tiles on the border have edges in them, and the rest are solid.
+        assert_eq!(tiles.len(), 256);
+        let alpha_tile_ids: Vec<u32> = tiles.iter()
+                                            .filter(|tile| !tile.is_solid())
+                                            .map(|tile| tile.alpha_tile_id.0)
+                                            .collect();
+        assert_eq!(alpha_tile_ids.len(), 60);
+        assert!(tiles.iter().filter(|tile| tile.is_solid()).all(|tile| tile.backdrop != 0));
+        for alpha_tile_id in &alpha_tile_ids {
+            assert_eq!(alpha_tile_ids.iter().filter(|&id| id == alpha_tile_id).count(), 1);
+            assert!(fills.iter().any(|fill| fill.link == *alpha_tile_id));
+        }
+        assert!(fills.iter().all(|fill| alpha_tile_ids.contains(&fill.link)));
+    }
+
+    // The area that a scene covers in a tile, keyed by the tile's coordinates.
+    type TileCoverage = ((i16, i16), f32);
+
+    // Builds on the CPU, returning the area that the scene covers in each tile that it touches.
+    fn build_tile_coverage(scene: &mut Scene) -> (Vec<TileCoverage>, usize) {
+        let (fills, tiles) = build_fills_and_tiles(scene);
+
+        // Each fill covers the area between its line and the bottom of the tile.
+        let mut coverage = vec![];
+        for tile in &tiles {
+            let mut area = tile.backdrop as f32 * (TILE_WIDTH * TILE_HEIGHT) as f32;
+            for fill in &fills {
+                if tile.is_solid() || fill.link != tile.alpha_tile_id.0 {
+                    continue;
+                }
+                let line = fill.line_segment;
+                let (from_x, to_x) = (line.from_x as f32 / 256.0, line.to_x as f32 / 256.0);
+                let mean_y = (line.from_y as f32 + line.to_y as f32) / 512.0;
+                area += (from_x - to_x) * (TILE_HEIGHT as f32 - mean_y);
+            }
+            if area.abs() > 0.5 {
+                coverage.push(((tile.tile_x, tile.tile_y), area.abs()));
+            }
+        }
+        coverage.sort_by_key(|&(tile_coords, _)| tile_coords);
+        (coverage, fills.len())
+    }
+
+    #[test]
+    fn test_rects_skip_the_tiler_with_the_same_coverage() {
+        // A rectangular frame, which only takes the fast path if its outer contour is exactly four
+        // corners.
+        fn frame(extra_point: bool) -> Outline {
+            let mut outer = Contour::new();
+            outer.push_endpoint(vec2f(8.5, 20.25));
+            if extra_point {
+                outer.push_endpoint(vec2f(60.0, 20.25));
+            }
+            outer.push_endpoint(vec2f(108.5, 20.25));
+            outer.push_endpoint(vec2f(108.5, 80.75));
+            outer.push_endpoint(vec2f(8.5, 80.75));
+            outer.close();
+            let mut inner = Contour::new();
+            for &point in &[vec2f(24.25, 36.0), vec2f(24.25, 64.5), vec2f(92.0, 64.5),
+                            vec2f(92.0, 36.0)] {
+                inner.push_endpoint(point);
+            }
+            inner.close();
+            let mut outline = Outline::new();
+            outline.push_contour(outer);
+            outline.push_contour(inner);
+            outline
+        }
+
+        let mut coverage = vec![];
+        for &extra_point in &[false, true] {
+            let mut scene = Scene::new();
+            scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(256.0, 256.0)));
+            let paint = scene.push_paint(&Paint::from_color(ColorU::black()));
+            scene.push_draw_path(DrawPath::new(frame(extra_point), paint));
+            coverage.push(build_tile_coverage(&mut scene));
+        }
+        let (ref fast_coverage, fast_fill_count) = coverage[0];
+        let (ref tiled_coverage, tiled_fill_count) = coverage[1];
+
+        assert!(fast_fill_count <= tiled_fill_count);
+        assert_eq!(fast_coverage.len(), tiled_coverage.len());
+        for (&(fast_coords, fast_area), &(tiled_coords, tiled_area)) in
+                fast_coverage.iter().zip(tiled_coverage.iter()) {
+            assert_eq!(fast_coords, tiled_coords);
+            assert!((fast_area - tiled_area).abs() < 0.5);
+        }
+        let total_area: f32 = fast_coverage.iter().map(|&(_, area)| area).sum();
+        assert!((total_area - (100.0 * 60.5 - 67.75 * 28.5)).abs() < 4.0);
+    }
+
+    #[test]
+    fn test_ellipses_are_evaluated_in_shader() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(256.0, 256.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::black()));
+
+        let mut ellipse = Contour::new();
+        ellipse.push_ellipse(&Transform2F::from_scale(vec2f(20.0, 6.5))
+                                          .translate(vec2f(40.5, 60.25)));
+        ellipse.close();
+        let mut outline = Outline::new();
+        outline.push_contour(ellipse);
+        scene.push_draw_path(DrawPath::new(outline, paint));
+
+        // A rounded rectangle isn't an ellipse, even though it's made of arcs too.
+        let rect = RectF::new(vec2f(100.0, 100.0), vec2f(40.0, 40.0));
+        let outline = Outline::from_rect_rounded(rect, vec2f(8.0, 8.0));
+        scene.push_draw_path(DrawPath::new(outline, paint));
+
+        let batches = build_tile_batches(&mut scene, BuildOptions::default());
+        assert_eq!(batches.len(), 2);
+        let ellipse_rect = batches[0].ellipse_rect.expect("Ellipse wasn't detected!");
+        assert!((ellipse_rect.origin() - vec2f(20.5, 53.75)).length() < 1.0 / 64.0);
+        assert!((ellipse_rect.size() - vec2f(40.0, 13.0)).length() < 1.0 / 64.0);
+        assert_eq!(batches[1].ellipse_rect, None);
+
+        // The ellipse is tiled as the pixels around it, which cover six tiles.
+        assert_eq!(batches[0].tiles.len(), 6);
+    }
+
+    #[test]
+    fn test_frame_stats_break_down_build_time() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(256.0, 256.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 0, 128)));
+        let rect = RectF::new(vec2f(10.5, 10.5), vec2f(100.0, 100.0));
+        scene.push_draw_path(DrawPath::new(Outline::from_rect_rounded(rect, vec2f(8.0, 8.0)),
+                                           paint));
+        scene.add_stroke_time(Duration::from_millis(3));
+
+        let commands = build_commands(&mut scene, BuildOptions::default());
+
+        // The stroke time comes from the scene, and the building stages fit within the build.
+        let (cpu_build_time, frame_stats) = commands.into_iter().find_map(|command| {
+            match command {
+                RenderCommand::Finish { cpu_build_time, frame_stats, .. } => {
+                    Some((cpu_build_time, frame_stats))
+                }
+                _ => None,
+            }
+        }).expect("No finish command!");
+        assert_eq!(frame_stats.stroke_time, Duration::from_millis(3));
+        assert_eq!(frame_stats.encode_time, Duration::default());
+        assert!(frame_stats.scene_build_time + frame_stats.tile_time + frame_stats.batch_time <=
+                cpu_build_time);
+    }
+
+    #[test]
+    fn test_cancelled_builds_stop_early_but_finish() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(256.0, 256.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 0, 255)));
+        let rect = RectF::new(vec2f(10.5, 10.5), vec2f(200.0, 200.0));
+        scene.push_draw_path(DrawPath::new(Outline::from_rect_rounded(rect, vec2f(50.0, 50.0)),
+                                           paint));
+
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        let options = BuildOptions {
+            cancellation_token: Some(cancellation_token),
+            ..BuildOptions::default()
+        };
+        let commands = build_commands(&mut scene, options);
+
+        // No fills or tiles are sent, but the build still finishes.
+        assert_eq!(fill_count(&commands), 0);
+        let batch_count = commands.iter().filter(|command| {
+            matches!(**command, RenderCommand::DrawTilesD3D9(_))
+        }).count();
+        assert_eq!(batch_count, 0);
+        let finish_count = commands.iter().filter(|command| {
+            matches!(**command, RenderCommand::Finish { .. })
+        }).count();
+        assert_eq!(finish_count, 1);
+    }
+}
@@ -0,0 +1,200 @@
+// pathfinder/renderer/src/concurrent/build_job.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Scene builds that run on their own thread and can be cancelled while they're in flight.
+//!
+//! This is useful when builds are superseded before they finish; e.g. while the user keeps
+//! zooming, only the build for the latest zoom level matters, so the older ones should be
+//! cancelled rather than left to finish. Cancellation is cooperative: the scene builder checks for
+//! it between stages and as it tiles paths, and stops early once it's requested.
+
+use crate::concurrent::executor::Executor;
+use crate::gpu::options::RendererLevel;
+use crate::gpu::renderer::Renderer;
+use crate::gpu_data::RenderCommand;
+use crate::options::{BuildOptions, RenderCommandListener};
+use crate::scene::{Scene, SceneSink};
+use crossbeam_channel::{self, Receiver, TryRecvError};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A flag that asks the builds using it to stop early.
+///
+/// Clones share the flag, so a token can be handed to a build through `BuildOptions` and then
+/// cancelled from another thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+/// A scene build running on its own thread.
+pub struct BuildJob {
+    cancellation_token: CancellationToken,
+    receiver: Receiver<BuiltScene>,
+}
+
+/// The render commands of a finished build job, ready to be rendered.
+pub struct BuiltScene {
+    pub(crate) commands: Vec<RenderCommand>,
+}
+
+/// The state of a build job.
+pub enum BuildJobStatus {
+    /// The build is still in flight.
+    Running,
+    /// The build finished. This is only returned once.
+    Finished(BuiltScene),
+    /// The build was cancelled, or its result has already been returned.
+    Cancelled,
+}
+
+impl CancellationToken {
+    /// Creates a token that hasn't been cancelled.
+    #[inline]
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Asks the builds using this token to stop.
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed)
+    }
+
+    /// Returns true if `cancel()` has been called on this token or one of its clones.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl BuildJob {
+    /// Starts building the scene on a new thread, for a renderer at the given level.
+    ///
+    /// If `options` already has a cancellation token, cancelling it cancels this job too.
+    pub fn spawn<E>(mut scene: Scene,
+                    mut options: BuildOptions,
+                    renderer_level: RendererLevel,
+                    executor: E)
+                    -> BuildJob
+                    where E: Executor + Send + 'static {
+        let cancellation_token =
+            options.cancellation_token.get_or_insert_with(CancellationToken::new).clone();
+        let thread_cancellation_token = cancellation_token.clone();
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        thread::spawn(move || {
+            let commands = Arc::new(Mutex::new(vec![]));
+            let listener_commands = commands.clone();
+            let listener = RenderCommandListener::new(Box::new(move |command| {
+                listener_commands.lock().unwrap().push(command)
+            }));
+            let mut sink = SceneSink::new(listener, renderer_level);
+            scene.build(options, &mut sink, &executor);
+
+            // The commands of a cancelled build are incomplete, so they're dropped. Dropping the
+            // sender tells the job.
+            if !thread_cancellation_token.is_cancelled() {
+                let commands = mem::replace(&mut *commands.lock().unwrap(), vec![]);
+                drop(sender.send(BuiltScene { commands }));
+            }
+        });
+        BuildJob { cancellation_token, receiver }
+    }
+
+    /// Asks the build to stop. It returns no render commands after this.
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancellation_token.cancel()
+    }
+
+    /// Returns the token that cancels this job.
+    #[inline]
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    /// Returns the state of the build without waiting for it.
+    pub fn poll(&self) -> BuildJobStatus {
+        if self.cancellation_token.is_cancelled() {
+            return BuildJobStatus::Cancelled;
+        }
+        match self.receiver.try_recv() {
+            Ok(built_scene) => BuildJobStatus::Finished(built_scene),
+            Err(TryRecvError::Empty) => BuildJobStatus::Running,
+            Err(TryRecvError::Disconnected) => BuildJobStatus::Cancelled,
+        }
+    }
+
+    /// Blocks until the build finishes, and returns its result, or `None` if it was cancelled.
+    pub fn wait(self) -> Option<BuiltScene> {
+        let built_scene = self.receiver.recv().ok();
+        if self.cancellation_token.is_cancelled() {
+            None
+        } else {
+            built_scene
+        }
+    }
+}
+
+impl BuiltScene {
+    /// Sends the commands to the given renderer to render the scene.
+    pub fn render(&self, renderer: &mut Renderer) {
+        renderer.begin_scene();
+        for command in &self.commands {
+            renderer.render_command(command);
+        }
+        renderer.end_scene();
+    }
+}
+
+#[cfg(all(test, feature = "d3d9"))]
+mod test {
+    use super::{BuildJob, CancellationToken};
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::gpu::options::RendererLevel;
+    use crate::options::BuildOptions;
+    use crate::paint::Paint;
+    use crate::scene::{DrawPath, Scene};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+
+    fn circle_scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(128.0, 128.0)));
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 0, 255)));
+        let rect = RectF::new(vec2f(8.5, 8.5), vec2f(112.0, 112.0));
+        scene.push_draw_path(DrawPath::new(Outline::from_rect_rounded(rect, vec2f(56.0, 56.0)),
+                                           paint));
+        scene
+    }
+
+    #[test]
+    fn test_build_jobs_finish_unless_cancelled() {
+        let job = BuildJob::spawn(circle_scene(),
+                                  BuildOptions::default(),
+                                  RendererLevel::D3D9,
+                                  SequentialExecutor);
+        assert!(job.wait().map_or(false, |built_scene| !built_scene.commands.is_empty()));
+
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        let options = BuildOptions {
+            cancellation_token: Some(cancellation_token),
+            ..BuildOptions::default()
+        };
+        let job = BuildJob::spawn(circle_scene(), options, RendererLevel::D3D9, SequentialExecutor);
+        assert!(job.cancellation_token().is_cancelled());
+        assert!(job.wait().is_none());
+    }
+}
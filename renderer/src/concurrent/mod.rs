@@ -10,6 +10,7 @@
 
 //! Threading and concurrency support.
 
+pub mod build_job;
 pub mod executor;
 pub mod rayon;
 pub mod scene_proxy;
@@ -0,0 +1,223 @@
+// pathfinder/renderer/src/flattened.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The flattened line segments that the renderer draws, for reuse outside of rendering.
+//!
+//! Physics, collision detection, and hit testing often want exactly the geometry that ended up on
+//! screen: after the build transform, stroking, and curve flattening. `Scene::flatten()` returns
+//! that geometry, grouped by the layer (render target) that each path is drawn into.
+
+use crate::options::BuildOptions;
+use crate::paint::PaintId;
+use crate::scene::{DisplayItem, DrawPathId, Scene};
+use crate::tiler;
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::outline::{ContourIterFlags, Outline};
+use pathfinder_content::render_target::RenderTargetId;
+use pathfinder_geometry::line_segment::LineSegment2F;
+use std::ops::Range;
+
+/// The flattened geometry of a scene.
+#[derive(Clone, Debug, Default)]
+pub struct FlattenedScene {
+    /// One entry per layer, in the order that the layers' paths are drawn.
+    pub layers: Vec<FlattenedLayer>,
+}
+
+/// The flattened geometry of the paths drawn into a single render target.
+#[derive(Clone, Debug)]
+pub struct FlattenedLayer {
+    /// The render target that the paths are drawn into, or `None` for the destination
+    /// framebuffer.
+    pub render_target: Option<RenderTargetId>,
+    /// The paths, in drawing order.
+    pub paths: Vec<FlattenedPath>,
+}
+
+/// The flattened geometry of a single draw path.
+#[derive(Clone, Debug)]
+pub struct FlattenedPath {
+    /// The ID of the draw path in the scene.
+    pub draw_path_id: DrawPathId,
+    /// The paint that the path is filled with.
+    pub paint: PaintId,
+    /// The fill rule that determines which regions the segments enclose.
+    pub fill_rule: FillRule,
+    /// The line segments, in device pixels. Every contour is closed.
+    pub segments: Vec<LineSegment2F>,
+    /// The range of `segments` belonging to each contour.
+    pub contours: Vec<Range<usize>>,
+}
+
+impl Scene {
+    /// Returns the line segments that the tiler would produce for every draw path in the scene
+    /// when built with the given options.
+    ///
+    /// The segments are exactly those the renderer rasterizes, except that they aren't clipped
+    /// to the view box. Clip paths aren't applied.
+    pub fn flatten(&self, options: BuildOptions) -> FlattenedScene {
+        let prepared_options = options.prepare(self.bounds());
+
+        let mut flattened_scene = FlattenedScene::default();
+        let mut render_target_stack = vec![];
+        for display_item in self.display_list() {
+            match *display_item {
+                DisplayItem::PushRenderTarget(render_target_id) => {
+                    render_target_stack.push(render_target_id)
+                }
+                DisplayItem::PopRenderTarget => {
+                    render_target_stack.pop();
+                }
+                DisplayItem::DrawPaths(ref draw_path_id_range) => {
+                    let render_target = render_target_stack.last().cloned();
+                    let layer_index = match flattened_scene.layers.last() {
+                        Some(layer) if layer.render_target == render_target => {
+                            flattened_scene.layers.len() - 1
+                        }
+                        _ => {
+                            flattened_scene.layers.push(FlattenedLayer {
+                                render_target,
+                                paths: vec![],
+                            });
+                            flattened_scene.layers.len() - 1
+                        }
+                    };
+
+                    for draw_path_index in draw_path_id_range.start.0..draw_path_id_range.end.0 {
+                        let draw_path_id = DrawPathId(draw_path_index);
+                        let draw_path = self.get_draw_path(draw_path_id);
+                        let outline =
+                            self.apply_render_options(draw_path.outline(), &prepared_options);
+                        let tolerance = prepared_options.flattening_tolerance *
+                            draw_path.quality.flattening_tolerance_scale();
+                        let (segments, contours) = flatten_outline(&outline, tolerance);
+                        flattened_scene.layers[layer_index].paths.push(FlattenedPath {
+                            draw_path_id,
+                            paint: draw_path.paint(),
+                            fill_rule: draw_path.fill_rule(),
+                            segments,
+                            contours,
+                        });
+                    }
+                }
+            }
+        }
+
+        flattened_scene
+    }
+}
+
+fn flatten_outline(outline: &Outline, tolerance: f32)
+                   -> (Vec<LineSegment2F>, Vec<Range<usize>>) {
+    let mut segments = vec![];
+    let mut contours = Vec::with_capacity(outline.contours().len());
+    for contour in outline.contours() {
+        let start = segments.len();
+        for segment in contour.iter(ContourIterFlags::empty()) {
+            tiler::flatten_segment(&segment, tolerance, &mut |line_segment| {
+                segments.push(line_segment)
+            });
+        }
+        contours.push(start..segments.len());
+    }
+    (segments, contours)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::options::{BuildOptions, DEFAULT_FLATTENING_TOLERANCE, RenderTransform};
+    use crate::paint::Paint;
+    use crate::scene::{DrawPath, DrawPathId, PathQuality, Scene};
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_flatten_scene() {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::black());
+        let rect = RectF::new(vec2f(0.0, 0.0), vec2f(10.0, 10.0));
+        scene.push_draw_path(DrawPath::new(Outline::from_rect(rect), paint));
+
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_quadratic(vec2f(50.0, 100.0), vec2f(100.0, 0.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        scene.push_draw_path(DrawPath::new(outline, paint));
+
+        let flattened = scene.flatten(BuildOptions::default());
+        assert_eq!(flattened.layers.len(), 1);
+        assert_eq!(flattened.layers[0].render_target, None);
+
+        let paths = &flattened.layers[0].paths;
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].segments.len(), 4);
+        assert_eq!(paths[0].contours, vec![0..4]);
+
+        // The curve is flattened into many lines, plus the closing segment.
+        assert!(paths[1].segments.len() > 2);
+        assert_eq!(paths[1].segments.last().unwrap().to(), vec2f(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_flattening_tolerance_follows_zoom() {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::black());
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_quadratic(vec2f(50.0, 100.0), vec2f(100.0, 0.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        scene.push_draw_path(DrawPath::new(outline, paint));
+
+        let segment_count = |scale: f32, tolerance: f32| {
+            let options = BuildOptions {
+                transform: RenderTransform::Transform2D(Transform2F::from_scale(scale)),
+                flattening_tolerance: tolerance,
+                ..BuildOptions::default()
+            };
+            scene.flatten(options).layers[0].paths[0].segments.len()
+        };
+
+        let default_count = segment_count(1.0, DEFAULT_FLATTENING_TOLERANCE);
+        assert!(segment_count(1.0, 4.0) < default_count);
+        assert!(segment_count(16.0, DEFAULT_FLATTENING_TOLERANCE) > default_count);
+        assert!(segment_count(1.0 / 16.0, DEFAULT_FLATTENING_TOLERANCE) < default_count);
+        assert_eq!(segment_count(1.0, 0.0), segment_count(1.0, 1.0 / 64.0));
+    }
+
+    #[test]
+    fn test_path_quality_scales_flattening() {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::black());
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_quadratic(vec2f(50.0, 100.0), vec2f(100.0, 0.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        for &quality in &[PathQuality::Low, PathQuality::Medium, PathQuality::High] {
+            let mut draw_path = DrawPath::new(outline.clone(), paint);
+            draw_path.set_quality(quality);
+            scene.push_draw_path(draw_path);
+        }
+
+        let flattened = scene.flatten(BuildOptions::default());
+        let paths = &flattened.layers[0].paths;
+        assert!(paths[0].segments.len() < paths[1].segments.len());
+        assert!(paths[1].segments.len() < paths[2].segments.len());
+        assert!(!scene.get_draw_path(DrawPathId(0)).is_antialiased());
+        assert!(scene.get_draw_path(DrawPathId(2)).is_antialiased());
+    }
+}
@@ -0,0 +1,173 @@
+// pathfinder/renderer/src/fuzzing.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Entry points for fuzzing the tiler and stroker with arbitrary input.
+//!
+//! Each entry point decodes an outline from raw bytes, sanitizes it the same way untrusted input
+//! should be sanitized in production, and runs it through the pipeline on the CPU. None of them
+//! should ever panic; if one does, that's a bug. See the `fuzz/` directory for the cargo-fuzz
+//! targets that drive these.
+
+use crate::concurrent::executor::SequentialExecutor;
+use crate::gpu::options::RendererLevel;
+use crate::options::{BuildOptions, RenderCommandListener};
+use crate::paint::Paint;
+use crate::scene::{DrawPath, Scene, SceneSink};
+use pathfinder_content::outline::{Contour, Outline, PointFlags};
+use pathfinder_content::sanitize::{OutlineSanitizer, SanitizeOptions};
+use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const VIEW_BOX_SIZE: f32 = 4096.0;
+const MAX_LINE_WIDTH: f32 = 1024.0;
+
+/// Decodes a path from `data`, then tiles it on the CPU.
+///
+/// Returns the number of render commands produced.
+pub fn tile_path_for_fuzzing(data: &[u8]) -> usize {
+    let mut reader = ByteReader::new(data);
+    let outline = sanitize(&decode_outline(&mut reader), true);
+    tile_outline(outline)
+}
+
+/// Decodes a stroke style and a path from `data`, converts the stroke to a fill, and tiles the
+/// result on the CPU.
+///
+/// Returns the number of render commands produced.
+pub fn stroke_path_for_fuzzing(data: &[u8]) -> usize {
+    let mut reader = ByteReader::new(data);
+
+    let mut line_width = reader.read_f32().unwrap_or(1.0);
+    if !line_width.is_finite() {
+        line_width = 1.0;
+    }
+    let line_width = line_width.abs().min(MAX_LINE_WIDTH);
+    let line_cap = match reader.read_u8().unwrap_or(0) % 3 {
+        0 => LineCap::Butt,
+        1 => LineCap::Square,
+        _ => LineCap::Round,
+    };
+    let line_join = match reader.read_u8().unwrap_or(0) % 3 {
+        0 => LineJoin::Miter(reader.read_f32().unwrap_or(10.0).abs().min(100.0)),
+        1 => LineJoin::Bevel,
+        _ => LineJoin::Round,
+    };
+    let style = StrokeStyle { line_width, line_cap, line_join };
+
+    let outline = sanitize(&decode_outline(&mut reader), false);
+    let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, style);
+    stroke_to_fill.offset();
+
+    // The stroker can push points outside the coordinate range, so sanitize again.
+    let outline = sanitize(&stroke_to_fill.into_outline(), true);
+    tile_outline(outline)
+}
+
+fn sanitize(outline: &Outline, is_fill: bool) -> Outline {
+    let mut options = SanitizeOptions::default();
+    options.close_open_contours = is_fill;
+    let mut sanitizer = OutlineSanitizer::new(outline, options);
+    sanitizer.sanitize();
+    sanitizer.into_outline()
+}
+
+fn tile_outline(outline: Outline) -> usize {
+    let mut scene = Scene::new();
+    scene.set_view_box(RectF::new(Vector2F::zero(), Vector2F::splat(VIEW_BOX_SIZE)));
+    let paint = scene.push_paint(&Paint::black());
+    scene.push_draw_path(DrawPath::new(outline, paint));
+
+    let command_count = AtomicUsize::new(0);
+    let listener = RenderCommandListener::new(Box::new(|_| {
+        command_count.fetch_add(1, Ordering::Relaxed);
+    }));
+    let mut sink = SceneSink::new(listener, RendererLevel::D3D9);
+    scene.build(BuildOptions::default(), &mut sink, &SequentialExecutor);
+    command_count.load(Ordering::Relaxed)
+}
+
+// Each command is a byte, followed by as many little-endian `f32` coordinates as it needs.
+// Decoding stops as soon as the data runs out. Coordinates may be NaN, which the sanitizer
+// removes, so points are pushed unchecked.
+fn decode_outline(reader: &mut ByteReader) -> Outline {
+    let mut outline = Outline::new();
+    let mut contour = Contour::new();
+    while let Some(command) = reader.read_u8() {
+        match command % 5 {
+            0 => {
+                let to = match reader.read_point() { Some(to) => to, None => break };
+                outline.push_contour(contour);
+                contour = Contour::new();
+                contour.push_point_unchecked(to, PointFlags::empty(), true);
+            }
+            1 => {
+                let to = match reader.read_point() { Some(to) => to, None => break };
+                contour.push_point_unchecked(to, PointFlags::empty(), true);
+            }
+            2 => {
+                let ctrl = match reader.read_point() { Some(ctrl) => ctrl, None => break };
+                let to = match reader.read_point() { Some(to) => to, None => break };
+                contour.push_point_unchecked(ctrl, PointFlags::CONTROL_POINT_0, true);
+                contour.push_point_unchecked(to, PointFlags::empty(), true);
+            }
+            3 => {
+                let ctrl0 = match reader.read_point() { Some(ctrl0) => ctrl0, None => break };
+                let ctrl1 = match reader.read_point() { Some(ctrl1) => ctrl1, None => break };
+                let to = match reader.read_point() { Some(to) => to, None => break };
+                contour.push_point_unchecked(ctrl0, PointFlags::CONTROL_POINT_0, true);
+                contour.push_point_unchecked(ctrl1, PointFlags::CONTROL_POINT_1, true);
+                contour.push_point_unchecked(to, PointFlags::empty(), true);
+            }
+            _ => {
+                contour.close();
+                outline.push_contour(contour);
+                contour = Contour::new();
+            }
+        }
+    }
+    outline.push_contour(contour);
+    outline
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> ByteReader<'a> {
+        ByteReader { data }
+    }
+
+    fn read_u8(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.data.split_first()?;
+        self.data = rest;
+        Some(byte)
+    }
+
+    fn read_f32(&mut self) -> Option<f32> {
+        if self.data.len() < 4 {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(4);
+        self.data = rest;
+        Some(f32::from_bits(u32::from(bytes[0]) |
+                            u32::from(bytes[1]) << 8 |
+                            u32::from(bytes[2]) << 16 |
+                            u32::from(bytes[3]) << 24))
+    }
+
+    fn read_point(&mut self) -> Option<Vector2F> {
+        let x = self.read_f32()?;
+        let y = self.read_f32()?;
+        Some(vec2f(x, y))
+    }
+}
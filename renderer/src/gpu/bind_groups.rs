@@ -0,0 +1,80 @@
+// pathfinder/renderer/src/gpu/bind_groups.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reuses bind groups across batches and frames.
+//!
+//! Most of the textures a tile batch binds (texture metadata, mask pages, the gamma LUT, and the
+//! Z buffers, which the allocator recycles) are the same from one batch to the next, so the bind
+//! groups that reference them can be too. Bind groups are keyed by the shape of their layout and
+//! the IDs of the textures they bind. The allocator never reuses a texture ID for a different
+//! texture, so a hit is always valid. Entries that go unused for a few frames are dropped, which
+//! releases the textures of purged allocations.
+
+use fxhash::FxHashMap;
+use pathfinder_gpu::allocator::TextureID;
+use pathfinder_gpu::Device;
+use pathfinder_gpu::sampler::SamplerOptions;
+
+// How many scenes a bind group may go unused before it's dropped.
+const MAX_UNUSED_FRAMES: u64 = 8;
+
+/// Identifies a bind group by the layout it was created for and the textures it binds.
+///
+/// `layout` names the shape of the layout rather than a particular layout object: wgpu treats
+/// layouts with identical entries as interchangeable, so one bind group serves, for example, every
+/// custom tile pipeline.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct BindGroupKey {
+    pub(crate) layout: &'static str,
+    pub(crate) textures: Vec<TextureID>,
+}
+
+pub(crate) struct BindGroupCache {
+    sampler: wgpu::Sampler,
+    bind_groups: FxHashMap<BindGroupKey, CachedBindGroup>,
+    frame: u64,
+}
+
+struct CachedBindGroup {
+    bind_group: wgpu::BindGroup,
+    last_used_frame: u64,
+}
+
+impl BindGroupCache {
+    pub(crate) fn new(device: &Device) -> BindGroupCache {
+        let sampler = device.sampler(SamplerOptions::default());
+        BindGroupCache { sampler, bind_groups: FxHashMap::default(), frame: 0 }
+    }
+
+    /// The default filtering sampler, shared by every cached bind group.
+    #[inline]
+    pub(crate) fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// Starts a new frame, dropping bind groups that haven't been used recently.
+    pub(crate) fn begin_frame(&mut self) {
+        self.frame += 1;
+        let frame = self.frame;
+        self.bind_groups.retain(|_, cached| frame - cached.last_used_frame <= MAX_UNUSED_FRAMES);
+    }
+
+    /// Returns the bind group for `key`, calling `create` with the shared sampler to create it if
+    /// it isn't cached.
+    pub(crate) fn get_or_create<F>(&mut self, key: BindGroupKey, create: F) -> wgpu::BindGroup
+                                   where F: FnOnce(&wgpu::Sampler) -> wgpu::BindGroup {
+        let (sampler, frame) = (&self.sampler, self.frame);
+        let cached = self.bind_groups.entry(key).or_insert_with(|| {
+            CachedBindGroup { bind_group: create(sampler), last_used_frame: frame }
+        });
+        cached.last_used_frame = frame;
+        cached.bind_group.clone()
+    }
+}
@@ -20,6 +20,8 @@ use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::vector::Vector2F;
 use pathfinder_gpu::allocator::{BufferTag, GeneralBufferID, GpuMemoryAllocator};
 use pathfinder_gpu::Device;
+use pathfinder_gpu::sampler::SamplerOptions;
+use pathfinder_gpu::uniform_block;
 use pathfinder_resources::ResourceLoader;
 use std::mem;
 use std::ops::Range;
@@ -34,7 +36,6 @@ const FILL_INDIRECT_DRAW_PARAMS_SIZE: usize = 8;
 
 const BIN_INDIRECT_DRAW_PARAMS_MICROLINE_COUNT_INDEX: usize = 3;
 
-const LOAD_ACTION_CLEAR: i32 = 0;
 const LOAD_ACTION_LOAD: i32 = 1;
 
 const INITIAL_ALLOCATED_MICROLINE_COUNT: u32 = 1024 * 16;
@@ -46,6 +47,25 @@ pub(crate) const BIN_WORKGROUP_SIZE: u32 = 64;
 pub(crate) const PROPAGATE_WORKGROUP_SIZE: u32 = 64;
 pub(crate) const SORT_WORKGROUP_SIZE: u32 = 64;
 
+uniform_block! {
+    /// The globals of `d3d11/tile.wgsl`.
+    pub(crate) struct TileGlobals: "Globals" {
+        uClearColor: [f32; 4],
+        uLoadAction: i32,
+        uPad0: i32,
+        uPad1: i32,
+        uPad2: i32,
+        uTileSize: [f32; 2],
+        uTextureMetadataSize: [f32; 2],
+        uFramebufferSize: [f32; 2],
+        uFramebufferTileSize: [i32; 2],
+        uMaskTextureSize0: [f32; 2],
+        uColorTextureSize0: [f32; 2],
+        // The reciprocal of the coverage gamma in x, and the coverage contrast in y.
+        uCoverageCurve: [f32; 4],
+    }
+}
+
 pub(crate) struct RendererD3D11 {
     propagate_pipeline: wgpu::ComputePipeline,
     fill_pipeline: wgpu::ComputePipeline,
@@ -156,6 +176,7 @@ impl RendererD3D11 {
                 batch.segment_count,
                 batch.path_source,
                 batch.prepare_info.transform,
+                batch.prepare_info.flattening_tolerance,
             );
             if microlines_storage.is_some() {
                 break;
@@ -433,6 +454,7 @@ impl RendererD3D11 {
         segment_count: u32,
         path_source: PathSource,
         transform: Transform2F,
+        flattening_tolerance: f32,
     ) -> Option<MicrolinesBufferIDsD3D11> {
         let dice_pipeline = &self.dice_pipeline;
 
@@ -479,7 +501,7 @@ impl RendererD3D11 {
             uPathCount: i32,
             uLastBatchSegmentIndex: i32,
             uMaxMicrolineCount: i32,
-            _padding: [u8; 4],
+            uTolerance: f32,
         }
 
         let globals = DiceGlobals {
@@ -493,7 +515,7 @@ impl RendererD3D11 {
             uPathCount: dice_metadata.len() as i32,
             uLastBatchSegmentIndex: segment_count as i32,
             uMaxMicrolineCount: self.allocated_microline_count as i32,
-            _padding: [0; 4],
+            uTolerance: flattening_tolerance,
         };
 
         let globals_buffer =
@@ -1083,10 +1105,7 @@ impl RendererD3D11 {
                     usage: wgpu::BufferUsages::UNIFORM,
                 });
 
-        let sampler = core
-            .device
-            .device
-            .create_sampler(&wgpu::SamplerDescriptor::default());
+        let sampler = core.device.sampler(SamplerOptions::default());
 
         let bind_group_0 = core
             .device
@@ -1285,6 +1304,9 @@ impl RendererD3D11 {
         first_tile_map_buffer_id: GeneralBufferID,
         color_texture_0: Option<TileBatchTexture>,
     ) {
+        // Tiles only cover what's drawn, so the destination is cleared as a whole beforehand.
+        core.clear_dest_if_needed();
+
         let mut timer_query = core
             .timer_query_cache
             .start_timing_draw_call(&core.device, &core.options);
@@ -1292,51 +1314,32 @@ impl RendererD3D11 {
         let tile_pipeline = &self.tile_pipeline;
         let device = &core.device.device;
 
-        #[repr(C)]
-        #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-        struct TileGlobals {
-            uClearColor: [f32; 4],
-            uLoadAction: i32,
-            _padding1: [i32; 3],
-            uTileSize: [f32; 2],
-            uTextureMetadataSize: [i32; 2],
-            uColorTextureSize0: [f32; 2],
-            uMaskTextureSize0: [f32; 2],
-            uFramebufferSize: [f32; 2],
-            uFramebufferTileSize: [i32; 2],
-        }
-
-        let clear_color = core.clear_color_for_draw_operation();
-        let load_action = match clear_color {
-            None => LOAD_ACTION_LOAD,
-            Some(_) => LOAD_ACTION_CLEAR,
-        };
-
         let draw_viewport = core.draw_viewport();
         let mask_storage = core.mask_storage.as_ref().unwrap();
         let mask_texture = core.allocator.get_texture(mask_storage.texture_id);
 
         let framebuffer_tile_size = core.framebuffer_tile_size().0;
+        let [inverse_gamma, contrast] = core.coverage_curve.to_shader_params();
 
         let globals = TileGlobals {
-            uClearColor: match clear_color {
-                Some(c) => [c.r(), c.g(), c.b(), c.a()],
-                None => [0.0, 0.0, 0.0, 0.0],
-            },
-            uLoadAction: load_action,
-            _padding1: [0; 3],
+            uClearColor: [0.0; 4],
+            uLoadAction: LOAD_ACTION_LOAD,
+            uPad0: 0,
+            uPad1: 0,
+            uPad2: 0,
             uTileSize: [
                 crate::tiles::TILE_WIDTH as f32,
                 crate::tiles::TILE_HEIGHT as f32,
             ],
-            uTextureMetadataSize: [1024, 1024],
-            uColorTextureSize0: [1024.0, 1024.0],
-            uMaskTextureSize0: [mask_texture.size.x() as f32, mask_texture.size.y() as f32],
+            uTextureMetadataSize: [1024.0, 1024.0],
             uFramebufferSize: [
                 draw_viewport.size().x() as f32,
                 draw_viewport.size().y() as f32,
             ],
             uFramebufferTileSize: [framebuffer_tile_size.x(), framebuffer_tile_size.y()],
+            uMaskTextureSize0: [mask_texture.size.x() as f32, mask_texture.size.y() as f32],
+            uColorTextureSize0: [1024.0, 1024.0],
+            uCoverageCurve: [inverse_gamma, contrast, 0.0, 0.0],
         };
 
         let globals_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -1367,7 +1370,7 @@ impl RendererD3D11 {
             }],
         });
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let sampler = core.device.sampler(SamplerOptions::default());
         let metadata_texture = core.allocator.get_texture(core.texture_metadata_texture_id);
         let gamma_lut_texture = core.allocator.get_texture(core.gamma_lut_texture_id);
 
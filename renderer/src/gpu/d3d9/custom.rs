@@ -0,0 +1,225 @@
+// pathfinder/renderer/src/gpu/d3d9/custom.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! User-supplied shaders that replace the built-in tile shader for a paint.
+//!
+//! A custom tile shader sees the same alpha-tile coverage that the built-in shader does, so it
+//! can implement stylized effects (ink bleed, halftones, hatching, and so on) on top of
+//! Pathfinder's anti-aliasing. Register one with `Renderer::set_custom_tile_shader()`; every tile
+//! of every path filled with that paint is then drawn with it.
+//!
+//! The source supplied is a WGSL fragment shader. The renderer prepends a prelude
+//! (`shaders/d3d9/custom_tile_prelude.wgsl`) that declares the bindings below, the vertex shader,
+//! and the `CustomTileVarying` struct passed to the fragment shader. The user source must define
+//! `@fragment fn fs_main(input: CustomTileVarying) -> @location(0) vec4<f32>`, returning a
+//! premultiplied color, which is blended over the destination with premultiplied source-over.
+//!
+//! | Group | Binding | Contents                                                         |
+//! |-------|---------|------------------------------------------------------------------|
+//! | 0     | 0       | `CustomTileGlobals` uniform (tile size, framebuffer size, etc.)  |
+//! | 1     | 0       | Texture metadata (paint colors)                                  |
+//! | 1     | 1       | Z buffer, for occlusion culling                                  |
+//! | 1     | 2       | Alpha tile coverage (mask) texture                               |
+//! | 1     | 3       | Filtering sampler                                                |
+//! | 2     | 0       | User uniform buffer, present only if `uniform_data` was supplied |
+//!
+//! Custom tile shaders are only supported by the D3D9 level renderer.
+
+use crate::gpu::renderer::TILE_INSTANCE_SIZE;
+use crate::gpu::uniforms::dynamic_uniform_layout_entry;
+use pathfinder_gpu::Device;
+use wgpu::util::DeviceExt;
+
+pub(crate) const CUSTOM_TILE_PRELUDE_PATH: &str = "shaders/d3d9/custom_tile_prelude.wgsl";
+pub(crate) const TILE_COVERAGE_PATH: &str = "shaders/d3d9/tile_coverage.wgsl";
+pub(crate) const TILE_OVERDRAW_PATH: &str = "shaders/d3d9/tile_overdraw.wgsl";
+pub(crate) const TILE_ID_PATH: &str = "shaders/d3d9/tile_id.wgsl";
+
+/// A user-supplied fragment shader for drawing tiles.
+#[derive(Clone, Debug)]
+pub struct CustomTileShader {
+    /// A label for debugging tools.
+    pub label: String,
+    /// The WGSL source of the fragment shader, without the prelude.
+    pub source: String,
+    /// The initial contents of the user uniform buffer at group 2, if any.
+    ///
+    /// The contents can later be updated with `Renderer::set_custom_tile_uniforms()`, but the
+    /// size can't change.
+    pub uniform_data: Option<Vec<u8>>,
+}
+
+impl CustomTileShader {
+    /// Creates a new custom tile shader with no user uniforms.
+    #[inline]
+    pub fn new<L, S>(label: L, source: S) -> CustomTileShader where L: Into<String>,
+                                                                      S: Into<String> {
+        CustomTileShader { label: label.into(), source: source.into(), uniform_data: None }
+    }
+}
+
+pub(crate) struct CustomTilePipeline {
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    pub(crate) uniform_buffer: Option<wgpu::Buffer>,
+}
+
+impl CustomTilePipeline {
+    pub(crate) fn new(device: &Device,
+                      prelude: &str,
+                      shader: &CustomTileShader,
+                      format: wgpu::TextureFormat,
+                      blend: Option<wgpu::BlendState>)
+                      -> CustomTilePipeline {
+        let device_ = &device.device;
+        let source = format!("{}\n{}", prelude, shader.source);
+        let module = device_.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&shader.label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let globals_layout = device_.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Custom Tile Globals"),
+            entries: &[dynamic_uniform_layout_entry(0)],
+        });
+        let textures_layout = device_.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Custom Tile Textures"),
+            entries: &[
+                texture_layout_entry(0, wgpu::ShaderStages::VERTEX),
+                texture_layout_entry(1, wgpu::ShaderStages::VERTEX),
+                texture_layout_entry(2, wgpu::ShaderStages::FRAGMENT),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let user_layout = shader.uniform_data.as_ref().map(|_| {
+            device_.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Custom Tile User Uniforms"),
+                entries: &[uniform_layout_entry(0)],
+            })
+        });
+
+        let mut bind_group_layouts = vec![Some(&globals_layout), Some(&textures_layout)];
+        if let Some(ref user_layout) = user_layout {
+            bind_group_layouts.push(Some(user_layout));
+        }
+        let pipeline_layout = device_.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Custom Tile Layout"),
+            bind_group_layouts: &bind_group_layouts,
+            immediate_size: 0,
+        });
+
+        // Must match the layout of `TileObjectPrimitive`.
+        let instance_attributes = [
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Sint16x2,
+                offset: 0,
+                shader_location: 1,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Uint8x4,
+                offset: 4,
+                shader_location: 2,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Sint32,
+                offset: 8,
+                shader_location: 3,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Sint8x2,
+                offset: 12,
+                shader_location: 4,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Uint16,
+                offset: 14,
+                shader_location: 5,
+            },
+        ];
+
+        let pipeline = device_.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&shader.label),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: 4,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Uint16x2],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: TILE_INSTANCE_SIZE as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &instance_attributes,
+                    },
+                ],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let uniform_buffer = shader.uniform_data.as_ref().map(|data| {
+            device_.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Custom Tile User Uniforms"),
+                contents: data,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            })
+        });
+
+        CustomTilePipeline { pipeline, uniform_buffer }
+    }
+}
+
+fn uniform_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn texture_layout_entry(binding: u32, visibility: wgpu::ShaderStages)
+                        -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
@@ -10,4 +10,6 @@
 
 //! A hybrid CPU-GPU renderer that only relies on functionality available in Direct3D 9.
 
+pub mod custom;
+pub mod paint_server;
 pub mod renderer;
@@ -0,0 +1,174 @@
+// pathfinder/renderer/src/gpu/d3d9/paint_server.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Procedural paints, evaluated per pixel in a shader.
+//!
+//! A paint server computes the color of a paint at each pixel from a short WGSL function and a
+//! small uniform block, so simple procedural fills like checkerboards, stripes, or SDF-based
+//! patterns need no texture. Register one for a paint with `Renderer::set_paint_server()`; paths
+//! filled with that paint are then colored by the paint server instead of the paint itself.
+//!
+//! A paint server's source must define:
+//!
+//! ```wgsl
+//! fn paintServerColor(position: vec2<f32>, baseColor: vec4<f32>) -> vec4<f32>
+//! ```
+//!
+//! returning a color that isn't premultiplied. `position` is in framebuffer pixels, and
+//! `baseColor` is the base color of the paint. If `uniform_data()` returns data, the source must
+//! also define `struct PaintServerUniforms`, whose contents are available as `uPaintServer`.
+//! Everything in the custom tile prelude (see `custom`) is available too.
+//!
+//! Paint servers are built on custom tile shaders, so they're only supported by the D3D9 level
+//! renderer.
+
+use crate::gpu::d3d9::custom::CustomTileShader;
+use pathfinder_color::ColorF;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_gpu::uniform_block;
+use pathfinder_resources::ResourceLoader;
+
+pub(crate) const PAINT_SERVER_PATH: &str = "shaders/d3d9/paint_server.wgsl";
+pub(crate) const CHECKERBOARD_PAINT_PATH: &str = "shaders/d3d9/checkerboard_paint.wgsl";
+pub(crate) const STRIPE_PAINT_PATH: &str = "shaders/d3d9/stripe_paint.wgsl";
+
+const PAINT_SERVER_UNIFORMS_DECLARATION: &str =
+    "@group(2) @binding(0) var<uniform> uPaintServer: PaintServerUniforms;";
+
+/// A procedural paint, evaluated in a shader.
+pub trait PaintServer {
+    /// A label for debugging tools.
+    fn label(&self) -> &str;
+
+    /// The WGSL source that defines `paintServerColor()`, and `PaintServerUniforms` if there are
+    /// uniforms.
+    fn source(&self, resources: &dyn ResourceLoader) -> String;
+
+    /// The contents of the `PaintServerUniforms` block, if there is one.
+    ///
+    /// The size must stay the same for as long as the paint server is registered.
+    fn uniform_data(&self) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+impl CustomTileShader {
+    /// Creates a custom tile shader that fills paths with the given paint server.
+    pub fn from_paint_server(server: &dyn PaintServer, resources: &dyn ResourceLoader)
+                             -> CustomTileShader {
+        let wrapper = resources.slurp(PAINT_SERVER_PATH).expect("Failed to load paint server");
+        let uniform_data = server.uniform_data();
+        let declaration = match uniform_data {
+            Some(_) => PAINT_SERVER_UNIFORMS_DECLARATION,
+            None => "",
+        };
+        let source = format!("{}\n{}\n{}",
+                             String::from_utf8_lossy(&wrapper),
+                             declaration,
+                             server.source(resources));
+        CustomTileShader { label: server.label().to_owned(), source, uniform_data }
+    }
+}
+
+uniform_block! {
+    /// The uniforms of `checkerboard_paint.wgsl`.
+    pub(crate) struct CheckerboardPaintUniforms: "PaintServerUniforms" {
+        colorA: [f32; 4],
+        colorB: [f32; 4],
+        origin: [f32; 2],
+        cellSize: [f32; 2],
+    }
+}
+
+uniform_block! {
+    /// The uniforms of `stripe_paint.wgsl`.
+    pub(crate) struct StripePaintUniforms: "PaintServerUniforms" {
+        colorA: [f32; 4],
+        colorB: [f32; 4],
+        normal: [f32; 2],
+        duty: f32,
+        phase: f32,
+    }
+}
+
+/// A checkerboard of two alternating colors.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CheckerboardPaint {
+    /// The colors of the cells. The cell at `origin` has the first color.
+    pub colors: [ColorF; 2],
+    /// The corner of a cell, in framebuffer pixels.
+    pub origin: Vector2F,
+    /// The size of each cell, in pixels.
+    pub cell_size: Vector2F,
+}
+
+/// Parallel stripes of two alternating colors, with anti-aliased edges.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StripePaint {
+    /// The colors of the stripes.
+    pub colors: [ColorF; 2],
+    /// The direction across the stripes, as an angle in radians from the positive X axis.
+    pub angle: f32,
+    /// The width of a stripe of each color together, in pixels.
+    pub period: f32,
+    /// The fraction of each period that is the first color.
+    pub duty: f32,
+    /// The offset of the stripes across their direction, as a fraction of the period.
+    pub phase: f32,
+}
+
+impl PaintServer for CheckerboardPaint {
+    fn label(&self) -> &str {
+        "Checkerboard Paint"
+    }
+
+    fn source(&self, resources: &dyn ResourceLoader) -> String {
+        let source = resources.slurp(CHECKERBOARD_PAINT_PATH)
+                              .expect("Failed to load checkerboard paint");
+        String::from_utf8_lossy(&source).into_owned()
+    }
+
+    fn uniform_data(&self) -> Option<Vec<u8>> {
+        let uniforms = CheckerboardPaintUniforms {
+            colorA: color_array(self.colors[0]),
+            colorB: color_array(self.colors[1]),
+            origin: [self.origin.x(), self.origin.y()],
+            cellSize: [self.cell_size.x().max(1.0), self.cell_size.y().max(1.0)],
+        };
+        Some(bytemuck::bytes_of(&uniforms).to_vec())
+    }
+}
+
+impl PaintServer for StripePaint {
+    fn label(&self) -> &str {
+        "Stripe Paint"
+    }
+
+    fn source(&self, resources: &dyn ResourceLoader) -> String {
+        let source = resources.slurp(STRIPE_PAINT_PATH).expect("Failed to load stripe paint");
+        String::from_utf8_lossy(&source).into_owned()
+    }
+
+    fn uniform_data(&self) -> Option<Vec<u8>> {
+        let normal = Vector2F::new(self.angle.cos(), self.angle.sin()) / self.period.max(1.0);
+        let uniforms = StripePaintUniforms {
+            colorA: color_array(self.colors[0]),
+            colorB: color_array(self.colors[1]),
+            normal: [normal.x(), normal.y()],
+            duty: self.duty.max(0.0).min(1.0),
+            phase: self.phase,
+        };
+        Some(bytemuck::bytes_of(&uniforms).to_vec())
+    }
+}
+
+fn color_array(color: ColorF) -> [f32; 4] {
+    [color.r(), color.g(), color.b(), color.a()]
+}
@@ -10,24 +10,43 @@
 
 //! A hybrid CPU-GPU renderer that only relies on functionality available in Direct3D 9.
 
-use crate::gpu::renderer::RendererCore;
+use crate::gpu::bind_groups::{BindGroupCache, BindGroupKey};
+use crate::gpu::d3d9::custom::{CUSTOM_TILE_PRELUDE_PATH, CustomTilePipeline, CustomTileShader};
+use crate::gpu::d3d9::custom::{TILE_COVERAGE_PATH, TILE_ID_PATH, TILE_OVERDRAW_PATH};
+use crate::gpu::options::{CoverageFormat, DepthOptions};
+use crate::gpu::renderer::{RendererCore, wgpu_color};
 use crate::gpu::renderer::{MaskStorageFlags, MASK_TEXTURE_HEIGHT, MASK_TEXTURE_WIDTH};
-use crate::gpu_data::{Clip, DrawTileBatchD3D9, Fill, TileBatchTexture, TileObjectPrimitive};
+use crate::gpu::uniforms::DynamicUniformBuffer;
+use crate::gpu_data::{Clip, DrawTileBatchD3D9, Fill, RoundedRectClip, TileBatchTexture};
+use crate::gpu_data::TileObjectPrimitive;
+use crate::paint::PaintId;
+use crate::scene::DrawPathId;
 use crate::tile_map::DenseTileMap;
 use crate::tiles::{TILE_HEIGHT, TILE_WIDTH};
 use byte_slice_cast::AsByteSlice;
+use fxhash::{FxHashMap, FxHashSet};
 use pathfinder_color::ColorF;
 use pathfinder_content::effects::BlendMode;
-use pathfinder_geometry::rect::RectI;
+use pathfinder_geometry::rect::{RectF, RectI};
 use pathfinder_geometry::transform3d::Transform4F;
 use pathfinder_geometry::vector::{vec2i, Vector2I, Vector4F};
 use pathfinder_gpu::allocator::{BufferTag, GeneralBufferID, IndexBufferID, TextureID, TextureTag};
+use pathfinder_gpu::sampler::SamplerOptions;
+use pathfinder_gpu::uniform_block;
 use pathfinder_resources::ResourceLoader;
+use std::borrow::Cow;
+use std::mem;
 use wgpu::util::DeviceExt;
 use crate::gpu::perf::TimeCategory;
 
 const MAX_FILLS_PER_BATCH: usize = 0x10000;
 
+const ADDITIVE_BLEND: wgpu::BlendComponent = wgpu::BlendComponent {
+    src_factor: wgpu::BlendFactor::One,
+    dst_factor: wgpu::BlendFactor::One,
+    operation: wgpu::BlendOperation::Add,
+};
+
 pub(crate) struct RendererD3D9 {
     // Basic data
     fill_pipeline: wgpu::RenderPipeline,
@@ -44,6 +63,63 @@ pub(crate) struct RendererD3D9 {
 
     // Temporary texture
     dest_blend_texture_id: TextureID,
+
+    // Tile globals for every tile batch in the frame, bound at dynamic offsets.
+    tile_globals: DynamicUniformBuffer<TileGlobals>,
+    // Texture bind groups, reused across batches.
+    bind_groups: BindGroupCache,
+
+    // User-supplied tile shaders, keyed by the paint they replace.
+    custom_tile_prelude: String,
+    custom_tile_pipelines: FxHashMap<PaintId, CustomTilePipeline>,
+
+    // Coverage output mode.
+    tile_coverage_source: String,
+    coverage_output: Option<CoverageOutput>,
+    // The draw paths whose coverage is output, or `None` for all of them.
+    coverage_paths: Option<FxHashSet<DrawPathId>>,
+
+    // Overdraw visualization.
+    tile_overdraw_source: String,
+    overdraw_output: Option<OverdrawOutput>,
+
+    // Path ID output, for picking.
+    tile_id_source: String,
+    id_output: Option<IdOutput>,
+
+    // Depth buffer interop.
+    depth_output: Option<DepthOutput>,
+}
+
+struct DepthOutput {
+    tile_pipeline: wgpu::RenderPipeline,
+    options: DepthOptions,
+}
+
+struct CoverageOutput {
+    pipeline: CustomTilePipeline,
+    texture_id: TextureID,
+    needs_clear: bool,
+}
+
+struct OverdrawOutput {
+    pipeline: CustomTilePipeline,
+    needs_clear: bool,
+}
+
+struct IdOutput {
+    pipeline: CustomTilePipeline,
+    texture_id: TextureID,
+    needs_clear: bool,
+}
+
+// Which pipeline and destination `draw_custom_tiles()` uses.
+#[derive(Clone, Copy)]
+enum CustomTileDest {
+    Paint(PaintId),
+    Coverage,
+    Overdraw,
+    Id,
 }
 
 impl RendererD3D9 {
@@ -73,6 +149,22 @@ impl RendererD3D9 {
             TextureTag("DestBlendD3D9"),
         );
 
+        let tile_globals = DynamicUniformBuffer::new(&core.device, "Tile Globals");
+        let bind_groups = BindGroupCache::new(&core.device);
+
+        let custom_tile_prelude = resources.slurp(CUSTOM_TILE_PRELUDE_PATH)
+                                           .expect("Failed to load custom tile prelude");
+        let custom_tile_prelude = String::from_utf8_lossy(&custom_tile_prelude).into_owned();
+        let tile_coverage_source = resources.slurp(TILE_COVERAGE_PATH)
+                                            .expect("Failed to load tile coverage shader");
+        let tile_coverage_source = String::from_utf8_lossy(&tile_coverage_source).into_owned();
+        let tile_overdraw_source = resources.slurp(TILE_OVERDRAW_PATH)
+                                            .expect("Failed to load tile overdraw shader");
+        let tile_overdraw_source = String::from_utf8_lossy(&tile_overdraw_source).into_owned();
+        let tile_id_source = resources.slurp(TILE_ID_PATH)
+                                      .expect("Failed to load tile ID shader");
+        let tile_id_source = String::from_utf8_lossy(&tile_id_source).into_owned();
+
         RendererD3D9 {
             fill_pipeline,
             tile_pipeline,
@@ -86,14 +178,204 @@ impl RendererD3D9 {
             pending_fills: vec![],
 
             dest_blend_texture_id,
+
+            tile_globals,
+            bind_groups,
+
+            custom_tile_prelude,
+            custom_tile_pipelines: FxHashMap::default(),
+
+            tile_coverage_source,
+            coverage_output: None,
+            coverage_paths: None,
+
+            tile_overdraw_source,
+            overdraw_output: None,
+
+            tile_id_source,
+            id_output: None,
+
+            depth_output: None,
         }
     }
 
-    pub(crate) fn upload_and_draw_tiles(
+    pub(crate) fn set_depth_options(&mut self,
+                                    core: &RendererCore,
+                                    resources: &dyn ResourceLoader,
+                                    options: Option<DepthOptions>) {
+        let options = match options {
+            None => {
+                self.depth_output = None;
+                return;
+            }
+            Some(options) => options,
+        };
+
+        // Only recompile the pipeline if the depth state changed.
+        let pipeline_is_current = match self.depth_output {
+            Some(ref depth_output) => {
+                depth_output.options.texture.format == options.texture.format &&
+                    depth_output.options.compare == options.compare &&
+                    depth_output.options.write == options.write
+            }
+            None => false,
+        };
+        if pipeline_is_current {
+            self.depth_output.as_mut().unwrap().options = options;
+            return;
+        }
+
+        let depth_stencil = wgpu::DepthStencilState {
+            format: options.texture.format,
+            depth_write_enabled: Some(options.write),
+            depth_compare: Some(options.compare),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        };
+        let tile_pipeline = core.device.create_render_pipeline_with_depth_stencil(
+            resources,
+            "d3d9/tile",
+            None,
+            Some(depth_stencil),
+        );
+        self.depth_output = Some(DepthOutput { tile_pipeline, options });
+    }
+
+    pub(crate) fn begin_scene(&mut self, core: &mut RendererCore) {
+        self.tile_globals.reset();
+        self.bind_groups.begin_frame();
+        if let Some(ref mut coverage_output) = self.coverage_output {
+            coverage_output.needs_clear = true;
+            resize_output_texture(core,
+                                  &mut coverage_output.texture_id,
+                                  TextureTag("CoverageD3D9"));
+        }
+        if let Some(ref mut overdraw_output) = self.overdraw_output {
+            overdraw_output.needs_clear = true;
+        }
+        if let Some(ref mut id_output) = self.id_output {
+            id_output.needs_clear = true;
+            resize_output_texture(core, &mut id_output.texture_id, TextureTag("IdD3D9"));
+        }
+    }
+
+    pub(crate) fn set_coverage_output(&mut self,
+                                      core: &mut RendererCore,
+                                      format: Option<CoverageFormat>) {
+        if let Some(coverage_output) = self.coverage_output.take() {
+            core.allocator.free_texture(coverage_output.texture_id);
+        }
+
+        let format = match format {
+            None => return,
+            Some(format) => format.texture_format(),
+        };
+
+        let shader = CustomTileShader::new("d3d9/tile_coverage", &*self.tile_coverage_source);
+        let blend = wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING;
+        let pipeline = CustomTilePipeline::new(&core.device,
+                                               &self.custom_tile_prelude,
+                                               &shader,
+                                               format,
+                                               Some(blend));
+        let texture_id = allocate_output_texture(core, format, TextureTag("CoverageD3D9"));
+        self.coverage_output = Some(CoverageOutput { pipeline, texture_id, needs_clear: true });
+    }
+
+    pub(crate) fn set_coverage_paths(&mut self, paths: Option<&[DrawPathId]>) {
+        self.coverage_paths = paths.map(|paths| paths.iter().cloned().collect());
+    }
+
+    pub(crate) fn coverage_texture_id(&self) -> Option<TextureID> {
+        self.coverage_output.as_ref().map(|coverage_output| coverage_output.texture_id)
+    }
+
+    pub(crate) fn set_overdraw_visualization(&mut self, core: &RendererCore, enabled: bool) {
+        if !enabled {
+            self.overdraw_output = None;
+            return;
+        }
+        if self.overdraw_output.is_some() {
+            return;
+        }
+
+        let shader = CustomTileShader::new("d3d9/tile_overdraw", &*self.tile_overdraw_source);
+        let blend = wgpu::BlendState { color: ADDITIVE_BLEND, alpha: ADDITIVE_BLEND };
+        let pipeline = CustomTilePipeline::new(&core.device,
+                                               &self.custom_tile_prelude,
+                                               &shader,
+                                               wgpu::TextureFormat::Rgba8Unorm,
+                                               Some(blend));
+        self.overdraw_output = Some(OverdrawOutput { pipeline, needs_clear: true });
+    }
+
+    pub(crate) fn set_id_output(&mut self, core: &mut RendererCore, enabled: bool) {
+        if !enabled {
+            if let Some(id_output) = self.id_output.take() {
+                core.allocator.free_texture(id_output.texture_id);
+            }
+            return;
+        }
+        if self.id_output.is_some() {
+            return;
+        }
+
+        let shader = CustomTileShader::new("d3d9/tile_id", &*self.tile_id_source);
+        let pipeline = CustomTilePipeline::new(&core.device,
+                                               &self.custom_tile_prelude,
+                                               &shader,
+                                               wgpu::TextureFormat::R32Uint,
+                                               None);
+        let texture_id = allocate_output_texture(core,
+                                                 wgpu::TextureFormat::R32Uint,
+                                                 TextureTag("IdD3D9"));
+        self.id_output = Some(IdOutput { pipeline, texture_id, needs_clear: true });
+    }
+
+    pub(crate) fn id_texture_id(&self) -> Option<TextureID> {
+        self.id_output.as_ref().map(|id_output| id_output.texture_id)
+    }
+
+    pub(crate) fn set_custom_tile_shader(&mut self,
+                                         core: &RendererCore,
+                                         paint_id: PaintId,
+                                         shader: Option<&CustomTileShader>) {
+        match shader {
+            None => {
+                self.custom_tile_pipelines.remove(&paint_id);
+            }
+            Some(shader) => {
+                let blend = wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING;
+                let pipeline = CustomTilePipeline::new(&core.device,
+                                                       &self.custom_tile_prelude,
+                                                       shader,
+                                                       wgpu::TextureFormat::Rgba8Unorm,
+                                                       Some(blend));
+                self.custom_tile_pipelines.insert(paint_id, pipeline);
+            }
+        }
+    }
+
+    pub(crate) fn set_custom_tile_uniforms(&self,
+                                           core: &RendererCore,
+                                           paint_id: PaintId,
+                                           data: &[u8]) {
+        let pipeline = self.custom_tile_pipelines
+                           .get(&paint_id)
+                           .expect("No custom tile shader for that paint!");
+        let uniform_buffer = pipeline.uniform_buffer
+                                     .as_ref()
+                                     .expect("Custom tile shader has no user uniforms!");
+        core.device.queue.write_buffer(uniform_buffer, 0, data);
+    }
+
+    // Uploads a batch's tiles and z-buffer, ready for `draw_prepared_tiles()` to draw later in the
+    // frame.
+    pub(crate) fn prepare_tiles(
         &mut self,
         core: &mut RendererCore,
         batch: &DrawTileBatchD3D9,
-    ) {
+    ) -> PreparedTileBatchD3D9 {
         // if !batch.clips.is_empty() {
         //     let clip_buffer_info = self.upload_clip_tiles(core, &batch.clips);
         //     self.clip_tiles(core, &clip_buffer_info);
@@ -101,21 +383,137 @@ impl RendererD3D9 {
         //         .free_general_buffer(clip_buffer_info.clip_buffer_id);
         // }
 
-        let tile_buffer = self.upload_tiles(core, &batch.tiles);
-        let z_buffer_texture_id = self.upload_z_buffer(core, &batch.z_buffer_data);
+        let mut prepared = PreparedTileBatchD3D9 {
+            z_buffer_texture_id: self.upload_z_buffer(core, &batch.z_buffer_data),
+            batch_clip: BatchClip {
+                scissor_rect: batch.scissor_rect,
+                rounded_clip: batch.rounded_clip,
+                ellipse_rect: batch.ellipse_rect,
+            },
+            color_texture: batch.color_texture,
+            blend_mode: batch.blend_mode,
+            draws: vec![],
+        };
+
+        // Path IDs are drawn alongside whatever else is being drawn.
+        if let Some(ref mut id_output) = self.id_output {
+            let clear_dest = mem::replace(&mut id_output.needs_clear, false);
+            let tile_buffer = self.upload_tiles(core, &batch.tiles);
+            prepared.draws.push(PreparedTileDraw {
+                dest: Some(CustomTileDest::Id),
+                tile_count: batch.tiles.len() as u32,
+                tile_vertex_buffer_id: tile_buffer.tile_vertex_buffer_id,
+                clear_dest,
+            });
+        }
 
-        self.draw_tiles(
-            core,
-            batch.tiles.len() as u32,
-            tile_buffer.tile_vertex_buffer_id,
-            batch.color_texture,
-            batch.blend_mode,
-            z_buffer_texture_id,
-        );
+        // In coverage output mode, paints (including custom tile shaders) are ignored, and so
+        // are the tiles of paths outside the filter. A batch with no tiles left doesn't clear the
+        // texture, so that a later one still does.
+        if self.coverage_output.is_some() {
+            let tiles: Cow<[TileObjectPrimitive]> = match self.coverage_paths {
+                None => Cow::Borrowed(&batch.tiles),
+                Some(ref paths) => {
+                    Cow::Owned(batch.tiles.iter().filter(|tile| {
+                        paths.contains(&DrawPathId(tile.path_id.0))
+                    }).cloned().collect())
+                }
+            };
+            if tiles.is_empty() {
+                return prepared;
+            }
+            let coverage_output = self.coverage_output.as_mut().unwrap();
+            let clear_dest = mem::replace(&mut coverage_output.needs_clear, false);
+            let tile_buffer = self.upload_tiles(core, &tiles);
+            prepared.draws.push(PreparedTileDraw {
+                dest: Some(CustomTileDest::Coverage),
+                tile_count: tiles.len() as u32,
+                tile_vertex_buffer_id: tile_buffer.tile_vertex_buffer_id,
+                clear_dest,
+            });
+            return prepared;
+        }
+
+        // When visualizing overdraw, every tile adds heat regardless of its paint.
+        if let Some(ref mut overdraw_output) = self.overdraw_output {
+            let clear_dest = mem::replace(&mut overdraw_output.needs_clear, false);
+            let tile_buffer = self.upload_tiles(core, &batch.tiles);
+            prepared.draws.push(PreparedTileDraw {
+                dest: Some(CustomTileDest::Overdraw),
+                tile_count: batch.tiles.len() as u32,
+                tile_vertex_buffer_id: tile_buffer.tile_vertex_buffer_id,
+                clear_dest,
+            });
+            return prepared;
+        }
 
-        core.allocator.free_texture(z_buffer_texture_id);
-        core.allocator
-            .free_general_buffer(tile_buffer.tile_vertex_buffer_id);
+        // Tiles are in path order, so split the batch into runs that share a shader in order to
+        // preserve the painter's algorithm.
+        let mut run_start = 0;
+        while run_start < batch.tiles.len() {
+            let paint_id = self.custom_tile_paint_id(&batch.tiles[run_start]);
+            let run_length = batch.tiles[run_start..]
+                .iter()
+                .take_while(|tile| self.custom_tile_paint_id(tile) == paint_id)
+                .count();
+            let tiles = &batch.tiles[run_start..(run_start + run_length)];
+            run_start += run_length;
+
+            let tile_buffer = self.upload_tiles(core, tiles);
+            prepared.draws.push(PreparedTileDraw {
+                dest: paint_id.map(CustomTileDest::Paint),
+                tile_count: tiles.len() as u32,
+                tile_vertex_buffer_id: tile_buffer.tile_vertex_buffer_id,
+                clear_dest: false,
+            });
+        }
+
+        prepared
+    }
+
+    pub(crate) fn draw_prepared_tiles(
+        &mut self,
+        core: &mut RendererCore,
+        prepared: PreparedTileBatchD3D9,
+    ) {
+        for draw in prepared.draws {
+            match draw.dest {
+                None => {
+                    self.draw_tiles(
+                        core,
+                        draw.tile_count,
+                        draw.tile_vertex_buffer_id,
+                        prepared.color_texture,
+                        prepared.blend_mode,
+                        prepared.z_buffer_texture_id,
+                        prepared.batch_clip,
+                    );
+                }
+                Some(dest) => {
+                    self.draw_custom_tiles(
+                        core,
+                        dest,
+                        draw.tile_count,
+                        draw.tile_vertex_buffer_id,
+                        prepared.z_buffer_texture_id,
+                        prepared.batch_clip,
+                        draw.clear_dest,
+                    );
+                }
+            }
+            core.allocator.free_general_buffer(draw.tile_vertex_buffer_id);
+        }
+
+        core.allocator.free_texture(prepared.z_buffer_texture_id);
+    }
+
+    fn custom_tile_paint_id(&self, tile: &TileObjectPrimitive) -> Option<PaintId> {
+        let paint_id = PaintId(tile.metadata_id);
+        if self.custom_tile_pipelines.contains_key(&paint_id) {
+            Some(paint_id)
+        } else {
+            None
+        }
     }
 
     fn upload_tiles(
@@ -131,7 +529,7 @@ impl RendererD3D9 {
                 BufferTag("TileD3D9"),
             );
         let tile_vertex_buffer = &core.allocator.get_general_buffer(tile_vertex_buffer_id);
-        core.device.upload_to_buffer(tile_vertex_buffer, 0, tiles);
+        core.uploader.upload_to_buffer(&core.device, tile_vertex_buffer, 0, tiles);
         self.ensure_index_buffer(core, tiles.len());
 
         TileBufferD3D9 {
@@ -169,15 +567,17 @@ impl RendererD3D9 {
         let quads_vertex_indices_buffer = core
             .allocator
             .get_index_buffer(quads_vertex_indices_buffer_id);
-        core.device
-            .upload_to_buffer(quads_vertex_indices_buffer, 0, &indices);
+        core.uploader.upload_to_buffer(&core.device, quads_vertex_indices_buffer, 0, &indices);
         self.quads_vertex_indices_buffer_id = Some(quads_vertex_indices_buffer_id);
         self.quads_vertex_indices_length = length;
     }
 
-    pub(crate) fn add_fills(&mut self, core: &mut RendererCore, fill_batch: &[Fill]) {
+    // Buffers fills for the next fill batch. If the buffer is full, the fills already in it are
+    // uploaded and returned as a batch for `draw_fill_batch()` to draw.
+    pub(crate) fn add_fills(&mut self, core: &mut RendererCore, fill_batch: &[Fill])
+                            -> Option<FillBufferInfoD3D9> {
         if fill_batch.is_empty() {
-            return;
+            return None;
         }
 
         core.stats.fill_count += fill_batch.len();
@@ -194,26 +594,27 @@ impl RendererD3D9 {
 
         core.reallocate_alpha_tile_pages_if_necessary(preserve_alpha_mask_contents);
 
+        let mut full_batch = None;
         if self.buffered_fills.len() + self.pending_fills.len() > MAX_FILLS_PER_BATCH {
-            self.draw_buffered_fills(core);
+            full_batch = self.take_buffered_fills(core);
         }
 
         self.buffered_fills.extend(self.pending_fills.drain(..));
+        full_batch
     }
 
-    pub(crate) fn draw_buffered_fills(&mut self, core: &mut RendererCore) {
+    // Uploads the buffered fills as a batch, if there are any.
+    pub(crate) fn take_buffered_fills(&mut self, core: &mut RendererCore)
+                                      -> Option<FillBufferInfoD3D9> {
         if self.buffered_fills.is_empty() {
-            return;
+            return None;
         }
+        Some(self.upload_buffered_fills(core))
+    }
 
-        let fill_storage_info = self.upload_buffered_fills(core);
-        self.draw_fills(
-            core,
-            fill_storage_info.fill_buffer_id,
-            fill_storage_info.fill_count,
-        );
-        core.allocator
-            .free_general_buffer(fill_storage_info.fill_buffer_id);
+    pub(crate) fn draw_fill_batch(&mut self, core: &mut RendererCore, batch: FillBufferInfoD3D9) {
+        self.draw_fills(core, batch.fill_buffer_id, batch.fill_count);
+        core.allocator.free_general_buffer(batch.fill_buffer_id);
     }
 
     fn upload_buffered_fills(&mut self, core: &mut RendererCore) -> FillBufferInfoD3D9 {
@@ -227,8 +628,7 @@ impl RendererD3D9 {
         );
         let fill_vertex_buffer = core.allocator.get_general_buffer(fill_buffer_id);
         debug_assert!(buffered_fills.len() <= u32::MAX as usize);
-        core.device
-            .upload_to_buffer(fill_vertex_buffer, 0, &buffered_fills);
+        core.uploader.upload_to_buffer(&core.device, fill_vertex_buffer, 0, buffered_fills);
 
         let fill_count = buffered_fills.len() as u32;
         buffered_fills.clear();
@@ -276,16 +676,9 @@ impl RendererD3D9 {
             .start_timing_draw_call(&core.device, &core.options);
 
         // Prepare uniforms
-        #[repr(C)]
-        #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-        struct FillGlobals {
-            tile_size: [f32; 2],
-            mask_size: [f32; 2],
-        }
-
         let globals = FillGlobals {
             tile_size: [TILE_WIDTH as f32, TILE_HEIGHT as f32],
-            mask_size: [
+            framebuffer_size: [
                 mask_viewport.size().x() as f32,
                 mask_viewport.size().y() as f32,
             ],
@@ -301,10 +694,7 @@ impl RendererD3D9 {
                 });
 
         let area_lut_view = &area_lut_texture.view;
-        let sampler = core
-            .device
-            .device
-            .create_sampler(&wgpu::SamplerDescriptor::default());
+        let sampler = core.device.sampler(SamplerOptions::default());
 
         let bind_group = core
             .device
@@ -380,6 +770,7 @@ impl RendererD3D9 {
             render_pass.draw_indexed(0..6, 0, 0..fill_count);
         }
 
+        core.uploader.flush(&core.device);
         core.device.queue.submit(Some(encoder.finish()));
 
         core.stats.drawcall_count += 1;
@@ -603,7 +994,7 @@ impl RendererD3D9 {
             BufferTag("ClipD3D9"),
         );
         let clip_buffer = core.allocator.get_general_buffer(clip_buffer_id);
-        core.device.upload_to_buffer(clip_buffer, 0, clips);
+        core.uploader.upload_to_buffer(&core.device, clip_buffer, 0, clips);
         ClipBufferInfo {
             clip_buffer_id,
             clip_count: clips.len() as u32,
@@ -618,6 +1009,7 @@ impl RendererD3D9 {
         _color_texture_0: Option<TileBatchTexture>,
         _blend_mode: BlendMode,
         z_buffer_texture_id: TextureID,
+        batch_clip: BatchClip,
     ) {
         if tile_count == 0 {
             return;
@@ -626,94 +1018,35 @@ impl RendererD3D9 {
         let mut timer_query = core
             .timer_query_cache
             .start_timing_draw_call(&core.device, &core.options);
-
-        let tile_pipeline = &self.tile_pipeline;
-        let device = &core.device.device;
+        let load_op = Self::dest_load_op(core);
 
         // 1. Prepare Tile Globals
-        #[repr(C)]
-        #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-        struct TileGlobals {
-            tile_size: [f32; 2],
-            texture_metadata_size: [i32; 2],
-            z_buffer_size: [i32; 2],
-            mask_texture_size0: [f32; 2],
-            color_texture_size0: [f32; 2],
-            framebuffer_size: [f32; 2],
-            transform: [f32; 16],
-        }
-
-        let transform = self.tile_transform(core);
+        let globals_offset = self.push_tile_globals(core, z_buffer_texture_id, batch_clip);
+        let tile_pipeline = match self.depth_output {
+            Some(ref depth_output) => &depth_output.tile_pipeline,
+            None => &self.tile_pipeline,
+        };
+        let device = &core.device.device;
         let draw_viewport = core.draw_viewport();
-        let mask_viewport = self.mask_viewport(core);
-
         let metadata_texture = core.allocator.get_texture(core.texture_metadata_texture_id);
         let z_buffer_texture = core.allocator.get_texture(z_buffer_texture_id);
 
-        let globals = TileGlobals {
-            transform: [
-                transform.c0.x(),
-                transform.c0.y(),
-                transform.c0.z(),
-                transform.c0.w(),
-                transform.c1.x(),
-                transform.c1.y(),
-                transform.c1.z(),
-                transform.c1.w(),
-                transform.c2.x(),
-                transform.c2.y(),
-                transform.c2.z(),
-                transform.c2.w(),
-                transform.c3.x(),
-                transform.c3.y(),
-                transform.c3.z(),
-                transform.c3.w(),
-            ],
-            tile_size: [TILE_WIDTH as f32, TILE_HEIGHT as i32 as f32],
-            framebuffer_size: [
-                draw_viewport.size().x() as f32,
-                draw_viewport.size().y() as f32,
-            ],
-            texture_metadata_size: [1024, 1024], // Placeholder
-            z_buffer_size: [z_buffer_texture.size.x(), z_buffer_texture.size.y()],
-            color_texture_size0: [1024.0, 1024.0], // Placeholder
-            mask_texture_size0: [
-                mask_viewport.size().x() as f32,
-                mask_viewport.size().y() as f32,
-            ],
-        };
-
-        let globals_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Tile Globals"),
-            contents: bytemuck::cast_slice(&[globals]),
-            usage: wgpu::BufferUsages::UNIFORM,
-        });
-
         // 2. Create Bind Groups
-        let bind_group_0 = core
-            .device
-            .device
-            .create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &tile_pipeline.get_bind_group_layout(0),
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: globals_buffer.as_entire_binding(),
-                }],
-            });
-
-        let sampler = core
-            .device
-            .device
-            .create_sampler(&wgpu::SamplerDescriptor::default());
         let mask_storage = core.mask_storage.as_ref().unwrap();
         let mask_texture = core.allocator.get_texture(mask_storage.texture_id);
         let gamma_lut_texture = core.allocator.get_texture(core.gamma_lut_texture_id);
 
-        let bind_group_1 = core
-            .device
-            .device
-            .create_bind_group(&wgpu::BindGroupDescriptor {
+        let key = BindGroupKey {
+            layout: "d3d9/tile textures",
+            textures: vec![
+                core.texture_metadata_texture_id,
+                z_buffer_texture_id,
+                mask_storage.texture_id,
+                core.gamma_lut_texture_id,
+            ],
+        };
+        let bind_group_1 = self.bind_groups.get_or_create(key, |sampler| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
                 label: None,
                 layout: &tile_pipeline.get_bind_group_layout(1),
                 entries: &[
@@ -743,10 +1076,11 @@ impl RendererD3D9 {
                     },
                     wgpu::BindGroupEntry {
                         binding: 6,
-                        resource: wgpu::BindingResource::Sampler(&sampler),
+                        resource: wgpu::BindingResource::Sampler(sampler),
                     },
                 ],
-            });
+            })
+        });
 
         // 3. Draw
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -757,18 +1091,6 @@ impl RendererD3D9 {
                 .allocator
                 .get_texture(core.intermediate_dest_texture_id);
 
-            let clear_color = core.clear_color_for_draw_operation();
-            let load_op = if let Some(color) = clear_color {
-                wgpu::LoadOp::Clear(wgpu::Color {
-                    r: color.r() as f64,
-                    g: color.g() as f64,
-                    b: color.b() as f64,
-                    a: color.a() as f64,
-                })
-            } else {
-                wgpu::LoadOp::Load
-            };
-
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Tile Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -780,15 +1102,205 @@ impl RendererD3D9 {
                     },
                     depth_slice: None,
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: self.depth_output.as_ref().map(|depth_output| {
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: &depth_output.options.texture.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: if depth_output.options.write {
+                                wgpu::StoreOp::Store
+                            } else {
+                                wgpu::StoreOp::Discard
+                            },
+                        }),
+                        stencil_ops: None,
+                    }
+                }),
                 timestamp_writes: None,
                 occlusion_query_set: None,
                 multiview_mask: None,
             });
 
             render_pass.set_pipeline(&tile_pipeline);
-            render_pass.set_bind_group(0, &bind_group_0, &[]);
+            render_pass.set_bind_group(0, self.tile_globals.bind_group(), &[globals_offset]);
+            render_pass.set_bind_group(1, &bind_group_1, &[]);
+
+            let tile_vertex_buffer = core.allocator.get_general_buffer(tile_vertex_buffer_id);
+            let quad_vertex_positions_buffer = core
+                .allocator
+                .get_general_buffer(core.quad_vertex_positions_buffer_id);
+            let quad_vertex_indices_buffer = core
+                .allocator
+                .get_index_buffer(core.quad_vertex_indices_buffer_id);
+
+            render_pass.set_vertex_buffer(0, quad_vertex_positions_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, tile_vertex_buffer.slice(..));
+            render_pass.set_index_buffer(
+                quad_vertex_indices_buffer.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+            render_pass.set_viewport(
+                0.0,
+                0.0,
+                draw_viewport.size().x() as f32,
+                draw_viewport.size().y() as f32,
+                0.0,
+                1.0,
+            );
+            set_scissor_rect(&mut render_pass, batch_clip.scissor_rect, draw_viewport.size());
+            render_pass.draw_indexed(0..6, 0, 0..tile_count);
+        }
+
+        core.uploader.flush(&core.device);
+        core.device.queue.submit(Some(encoder.finish()));
+
+        core.stats.total_tile_count += tile_count as usize;
+        core.stats.drawcall_count += 1;
+        core.finish_timing_draw_call(&mut timer_query);
+        core.current_timer
+            .as_mut()
+            .unwrap()
+            .push_query(TimeCategory::Composite, timer_query);
+        core.preserve_draw_framebuffer();
+    }
+
+    fn draw_custom_tiles(
+        &mut self,
+        core: &mut RendererCore,
+        dest: CustomTileDest,
+        tile_count: u32,
+        tile_vertex_buffer_id: GeneralBufferID,
+        z_buffer_texture_id: TextureID,
+        batch_clip: BatchClip,
+        clear_dest: bool,
+    ) {
+        if tile_count == 0 {
+            return;
+        }
+
+        let mut timer_query = core
+            .timer_query_cache
+            .start_timing_draw_call(&core.device, &core.options);
+
+        let globals_offset = self.push_tile_globals(core, z_buffer_texture_id, batch_clip);
+        let (custom_pipeline, dest_texture_id, load_op) = match dest {
+            CustomTileDest::Paint(paint_id) => {
+                (&self.custom_tile_pipelines[&paint_id],
+                 core.intermediate_dest_texture_id,
+                 Self::dest_load_op(core))
+            }
+            CustomTileDest::Coverage => {
+                let coverage_output = self.coverage_output.as_ref().unwrap();
+                let load_op = if clear_dest {
+                    wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+                } else {
+                    wgpu::LoadOp::Load
+                };
+                (&coverage_output.pipeline, coverage_output.texture_id, load_op)
+            }
+            CustomTileDest::Overdraw => {
+                // The heat map replaces the destination's own clear.
+                core.dest_needs_clear = false;
+                let load_op = if clear_dest {
+                    wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                } else {
+                    wgpu::LoadOp::Load
+                };
+                (&self.overdraw_output.as_ref().unwrap().pipeline,
+                 core.intermediate_dest_texture_id,
+                 load_op)
+            }
+            CustomTileDest::Id => {
+                let id_output = self.id_output.as_ref().unwrap();
+                let load_op = if clear_dest {
+                    wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+                } else {
+                    wgpu::LoadOp::Load
+                };
+                (&id_output.pipeline, id_output.texture_id, load_op)
+            }
+        };
+        let pipeline = &custom_pipeline.pipeline;
+        let device = &core.device.device;
+        let draw_viewport = core.draw_viewport();
+
+        let metadata_texture = core.allocator.get_texture(core.texture_metadata_texture_id);
+        let z_buffer_texture = core.allocator.get_texture(z_buffer_texture_id);
+        let mask_storage = core.mask_storage.as_ref().unwrap();
+        let mask_texture = core.allocator.get_texture(mask_storage.texture_id);
+        let key = BindGroupKey {
+            layout: "custom tile textures",
+            textures: vec![
+                core.texture_metadata_texture_id,
+                z_buffer_texture_id,
+                mask_storage.texture_id,
+            ],
+        };
+        let bind_group_1 = self.bind_groups.get_or_create(key, |sampler| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Custom Tile Textures BG"),
+                layout: &pipeline.get_bind_group_layout(1),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&metadata_texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&z_buffer_texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&mask_texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            })
+        });
+
+        let bind_group_2 = custom_pipeline.uniform_buffer.as_ref().map(|uniform_buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Custom Tile User Uniforms BG"),
+                layout: &pipeline.get_bind_group_layout(2),
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }],
+            })
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Custom Tile Encoder"),
+        });
+        {
+            let dest_texture = core.allocator.get_texture(dest_texture_id);
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Custom Tile Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &dest_texture.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: load_op,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, self.tile_globals.bind_group(), &[globals_offset]);
             render_pass.set_bind_group(1, &bind_group_1, &[]);
+            if let Some(ref bind_group_2) = bind_group_2 {
+                render_pass.set_bind_group(2, bind_group_2, &[]);
+            }
 
             let tile_vertex_buffer = core.allocator.get_general_buffer(tile_vertex_buffer_id);
             let quad_vertex_positions_buffer = core
@@ -812,9 +1324,11 @@ impl RendererD3D9 {
                 0.0,
                 1.0,
             );
+            set_scissor_rect(&mut render_pass, batch_clip.scissor_rect, draw_viewport.size());
             render_pass.draw_indexed(0..6, 0, 0..tile_count);
         }
 
+        core.uploader.flush(&core.device);
         core.device.queue.submit(Some(encoder.finish()));
 
         core.stats.total_tile_count += tile_count as usize;
@@ -827,6 +1341,78 @@ impl RendererD3D9 {
         core.preserve_draw_framebuffer();
     }
 
+    // Only the first draw to the destination in a frame clears it; later ones draw on top.
+    fn dest_load_op(core: &mut RendererCore) -> wgpu::LoadOp<wgpu::Color> {
+        match core.take_dest_clear_color() {
+            Some(color) => wgpu::LoadOp::Clear(wgpu_color(color)),
+            None => wgpu::LoadOp::Load,
+        }
+    }
+
+    // Shared by the built-in tile shader and custom tile shaders. Returns the dynamic offset of
+    // the globals in `tile_globals`.
+    fn push_tile_globals(&mut self,
+                         core: &RendererCore,
+                         z_buffer_texture_id: TextureID,
+                         batch_clip: BatchClip)
+                         -> u32 {
+        let transform = self.tile_transform(core);
+        let draw_viewport = core.draw_viewport();
+        let mask_viewport = self.mask_viewport(core);
+        let z_buffer_texture = core.allocator.get_texture(z_buffer_texture_id);
+        let [inverse_gamma, contrast] = core.coverage_curve.to_shader_params();
+
+        let mut globals = TileGlobals {
+            uTransform: [
+                transform.c0.x(),
+                transform.c0.y(),
+                transform.c0.z(),
+                transform.c0.w(),
+                transform.c1.x(),
+                transform.c1.y(),
+                transform.c1.z(),
+                transform.c1.w(),
+                transform.c2.x(),
+                transform.c2.y(),
+                transform.c2.z(),
+                transform.c2.w(),
+                transform.c3.x(),
+                transform.c3.y(),
+                transform.c3.z(),
+                transform.c3.w(),
+            ],
+            uTileSize: [TILE_WIDTH as f32, TILE_HEIGHT as i32 as f32],
+            uFramebufferSize: [
+                draw_viewport.size().x() as f32,
+                draw_viewport.size().y() as f32,
+            ],
+            uTextureMetadataSize: [1024, 1024], // Placeholder
+            uZBufferSize: [z_buffer_texture.size.x(), z_buffer_texture.size.y()],
+            uColorTextureSize0: [1024.0, 1024.0], // Placeholder
+            uMaskTextureSize0: [
+                mask_viewport.size().x() as f32,
+                mask_viewport.size().y() as f32,
+            ],
+            uClipRect: [0.0; 4],
+            uClipParams: [0.0; 4],
+            uEllipseRect: [0.0; 4],
+            uCoverageCurve: [inverse_gamma, contrast, 0.0, 0.0],
+        };
+
+        if let Some(rounded_clip) = batch_clip.rounded_clip {
+            let rect = rounded_clip.rect;
+            globals.uClipRect = [rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y()];
+            globals.uClipParams[0] = rounded_clip.radius;
+            globals.uClipParams[1] = 1.0;
+        }
+        if let Some(rect) = batch_clip.ellipse_rect {
+            globals.uEllipseRect = [rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y()];
+            globals.uClipParams[2] = 1.0;
+        }
+
+        self.tile_globals.push(&core.device, &globals)
+    }
+
     fn copy_alpha_tiles_to_dest_blend_texture(
         &mut self,
         core: &mut RendererCore,
@@ -848,10 +1434,98 @@ impl RendererD3D9 {
     fn tile_transform(&self, core: &RendererCore) -> Transform4F {
         let draw_viewport = core.draw_viewport().size().to_f32();
         let scale = Vector4F::new(2.0 / draw_viewport.x(), -2.0 / draw_viewport.y(), 1.0, 1.0);
-        Transform4F::from_scale(scale).translate(Vector4F::new(-1.0, 1.0, 0.0, 1.0))
+        // All vector content lies on a plane at the configured depth, if any.
+        let depth = match self.depth_output {
+            Some(ref depth_output) => depth_output.options.depth,
+            None => 0.0,
+        };
+        Transform4F::from_scale(scale).translate(Vector4F::new(-1.0, 1.0, depth, 1.0))
     }
 }
 
+// Allocates a texture the size of the destination framebuffer, for an output mode to draw to.
+fn allocate_output_texture(core: &mut RendererCore, format: wgpu::TextureFormat, tag: TextureTag)
+                           -> TextureID {
+    core.allocator.allocate_texture(
+        &core.device,
+        core.options.dest.window_size(&core.device),
+        format,
+        wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
+        tag,
+    )
+}
+
+// Reallocates an output mode's texture if the destination framebuffer has changed size since it
+// was allocated. The contents are lost, but the texture is cleared at the start of each scene.
+fn resize_output_texture(core: &mut RendererCore, texture_id: &mut TextureID, tag: TextureTag) {
+    let window_size = core.options.dest.window_size(&core.device);
+    let texture = core.allocator.get_texture(*texture_id);
+    if texture.size == window_size {
+        return;
+    }
+    let format = texture.format;
+    core.allocator.free_texture(*texture_id);
+    *texture_id = allocate_output_texture(core, format, tag);
+}
+
+// Restricts drawing to `scissor_rect`, if any. wgpu requires the rectangle to lie within the
+// render target, so it's clamped to the viewport.
+fn set_scissor_rect(render_pass: &mut wgpu::RenderPass,
+                    scissor_rect: Option<RectI>,
+                    viewport_size: Vector2I) {
+    let scissor_rect = match scissor_rect {
+        None => return,
+        Some(scissor_rect) => scissor_rect,
+    };
+    let viewport = RectI::new(Vector2I::zero(), viewport_size);
+    let rect = scissor_rect.intersection(viewport).unwrap_or_default();
+    render_pass.set_scissor_rect(rect.origin_x() as u32,
+                                 rect.origin_y() as u32,
+                                 rect.width() as u32,
+                                 rect.height() as u32);
+}
+
+uniform_block! {
+    /// The globals of `d3d9/tile.wgsl`.
+    pub(crate) struct TileGlobals: "Globals" {
+        uTileSize: [f32; 2],
+        uTextureMetadataSize: [i32; 2],
+        uZBufferSize: [i32; 2],
+        uMaskTextureSize0: [f32; 2],
+        uColorTextureSize0: [f32; 2],
+        uFramebufferSize: [f32; 2],
+        uTransform: [f32; 16],
+        // The rounded clip rectangle, as (min x, min y, max x, max y).
+        uClipRect: [f32; 4],
+        // The rounded clip's corner radius in x, 1 in y if the clip applies, and 1 in z if the
+        // ellipse applies.
+        uClipParams: [f32; 4],
+        // The rectangle that the ellipse is inscribed in, as (min x, min y, max x, max y).
+        uEllipseRect: [f32; 4],
+        // The reciprocal of the coverage gamma in x, and the coverage contrast in y.
+        uCoverageCurve: [f32; 4],
+    }
+}
+
+uniform_block! {
+    /// The globals of `d3d9/fill.wgsl`.
+    pub(crate) struct FillGlobals: "Globals" {
+        tile_size: [f32; 2],
+        // The size of the mask framebuffer.
+        framebuffer_size: [f32; 2],
+    }
+}
+
+// The clips that apply to a whole batch of tiles.
+#[derive(Clone, Copy)]
+struct BatchClip {
+    scissor_rect: Option<RectI>,
+    rounded_clip: Option<RoundedRectClip>,
+    ellipse_rect: Option<RectF>,
+}
+
 #[derive(Clone)]
 pub(crate) struct TileBatchInfoD3D9 {
     pub(crate) tile_count: u32,
@@ -860,11 +1534,28 @@ pub(crate) struct TileBatchInfoD3D9 {
 }
 
 #[derive(Clone)]
-struct FillBufferInfoD3D9 {
+pub(crate) struct FillBufferInfoD3D9 {
     fill_buffer_id: GeneralBufferID,
     fill_count: u32,
 }
 
+// A batch of tiles whose data has been uploaded, waiting for its pass to draw it.
+pub(crate) struct PreparedTileBatchD3D9 {
+    z_buffer_texture_id: TextureID,
+    batch_clip: BatchClip,
+    color_texture: Option<TileBatchTexture>,
+    blend_mode: BlendMode,
+    draws: Vec<PreparedTileDraw>,
+}
+
+struct PreparedTileDraw {
+    // `None` draws with the built-in tile shader.
+    dest: Option<CustomTileDest>,
+    tile_count: u32,
+    tile_vertex_buffer_id: GeneralBufferID,
+    clear_dest: bool,
+}
+
 struct TileBufferD3D9 {
     tile_vertex_buffer_id: GeneralBufferID,
 }
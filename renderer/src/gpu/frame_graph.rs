@@ -0,0 +1,368 @@
+// pathfinder/renderer/src/gpu/frame_graph.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A small frame graph: the passes that render a frame, and the resources each one uses.
+//!
+//! The renderer records a pass for each GPU operation as the scene's commands arrive, and runs
+//! them all when the frame finishes. Each pass declares the resources it reads and writes. That
+//! lets the graph place passes relative to one another by name, drop passes whose output nothing
+//! uses, and back transient textures whose lifetimes don't overlap with the same texture.
+
+use fxhash::FxHashMap;
+use pathfinder_geometry::vector::Vector2I;
+use pathfinder_gpu::allocator::TextureID;
+use pathfinder_gpu::{Device, Texture};
+
+/// The name of the passes that rasterize path edges into the mask texture.
+pub const FILLS_PASS: &str = "Fills";
+/// The name of the passes that draw tiles to the destination.
+pub const TILES_PASS: &str = "Tiles";
+/// The name of the pass that clears the destination if nothing drew to it.
+pub const CLEAR_DEST_PASS: &str = "ClearDest";
+/// The name of the pass that converts the finished frame to straight alpha.
+pub const STRAIGHT_ALPHA_PASS: &str = "StraightAlpha";
+
+/// A resource that passes read or write.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FrameResource(u32);
+
+/// The texture that backs a transient resource.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TransientTextureDescriptor {
+    pub size: Vector2I,
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+}
+
+/// Where a pass runs relative to the other passes in the frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PassPlacement {
+    /// In the order the passes were added.
+    InOrder,
+    /// Just before the first pass with the given name. If there's no such pass this frame, the
+    /// pass runs in order.
+    Before(&'static str),
+    /// Just after the last pass with the given name. If there's no such pass this frame, the pass
+    /// runs in order.
+    After(&'static str),
+}
+
+impl PassPlacement {
+    /// Before the scene's tiles are composited to the destination.
+    pub const BEFORE_COMPOSITE: PassPlacement = PassPlacement::Before(TILES_PASS);
+    /// After the scene's tiles have been composited to the destination.
+    pub const AFTER_COMPOSITE: PassPlacement = PassPlacement::After(TILES_PASS);
+}
+
+/// Identifies a pass added with `Renderer::add_user_pass()`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UserPassId(pub(crate) u32);
+
+/// What a pass added with `Renderer::add_user_pass()` draws with.
+pub struct UserPassContext<'a> {
+    /// The device to encode and submit the pass's work with.
+    pub device: &'a Device,
+    /// The texture the frame is rendered to. It's already been cleared, if the frame clears it.
+    pub dest: &'a Texture,
+    /// For passes added with `Renderer::add_post_process_pass()`, a copy of `dest` as it was when
+    /// the pass started, to read from while drawing to `dest`.
+    pub source: Option<&'a Texture>,
+    /// The coverage of the alpha tiles rasterized so far this frame, if any have been.
+    pub mask: Option<&'a Texture>,
+}
+
+/// The textures backing the transient resources, as seen by a running pass.
+pub struct PassResources {
+    textures: Vec<Option<TextureID>>,
+}
+
+impl PassResources {
+    /// Returns the texture backing a transient resource that the pass declared.
+    ///
+    /// Panics if the resource isn't transient.
+    #[inline]
+    pub fn texture(&self, resource: FrameResource) -> TextureID {
+        self.textures[resource.0 as usize].expect("Only transient resources have textures!")
+    }
+}
+
+type PassFn<C> = Box<dyn FnOnce(&mut C, &PassResources)>;
+
+struct Pass<C> {
+    name: &'static str,
+    placement: PassPlacement,
+    reads: Vec<FrameResource>,
+    writes: Vec<FrameResource>,
+    execute: PassFn<C>,
+}
+
+struct Resource {
+    transient: Option<TransientTextureDescriptor>,
+}
+
+/// The passes of a frame, which run against a context of type `C`.
+pub struct FrameGraph<C> {
+    resources: Vec<Resource>,
+    passes: Vec<Pass<C>>,
+}
+
+/// Declares what a pass uses before adding it to the graph.
+#[must_use]
+pub struct PassBuilder<'a, C> {
+    graph: &'a mut FrameGraph<C>,
+    name: &'static str,
+    placement: PassPlacement,
+    reads: Vec<FrameResource>,
+    writes: Vec<FrameResource>,
+}
+
+/// A frame graph whose passes have been ordered and whose transient textures have been assigned.
+pub struct CompiledFrameGraph<C> {
+    passes: Vec<Pass<C>>,
+    resources: Vec<Resource>,
+    // The texture slot of each transient resource, by resource.
+    resource_slots: Vec<Option<usize>>,
+    slots: Vec<TextureSlot>,
+}
+
+// A texture shared by transient resources whose lifetimes don't overlap.
+struct TextureSlot {
+    descriptor: TransientTextureDescriptor,
+    first_pass: usize,
+    last_pass: usize,
+}
+
+impl<C> FrameGraph<C> {
+    #[inline]
+    pub fn new() -> FrameGraph<C> {
+        FrameGraph { resources: vec![], passes: vec![] }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Declares a resource that lives outside the frame, such as the destination. Passes that
+    /// write imported resources always run.
+    pub fn import(&mut self) -> FrameResource {
+        self.push_resource(None)
+    }
+
+    /// Declares a texture that only lives during the frame. It's allocated just before the first
+    /// pass that uses it and freed after the last one.
+    pub fn create_texture(&mut self, descriptor: TransientTextureDescriptor) -> FrameResource {
+        self.push_resource(Some(descriptor))
+    }
+
+    fn push_resource(&mut self, transient: Option<TransientTextureDescriptor>) -> FrameResource {
+        let resource = FrameResource(self.resources.len() as u32);
+        self.resources.push(Resource { transient });
+        resource
+    }
+
+    /// Starts declaring a pass. The pass is added when its `execute()` closure is supplied.
+    pub fn add_pass(&mut self, name: &'static str) -> PassBuilder<C> {
+        PassBuilder {
+            graph: self,
+            name,
+            placement: PassPlacement::InOrder,
+            reads: vec![],
+            writes: vec![],
+        }
+    }
+
+    /// Orders the passes, drops those whose output is unused, and assigns textures to the
+    /// transient resources.
+    pub fn compile(self) -> CompiledFrameGraph<C> {
+        let FrameGraph { resources, passes } = self;
+
+        let mut passes = place_passes(passes);
+        cull_passes(&resources, &mut passes);
+
+        // Find the lifetime of each transient resource, in passes.
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; resources.len()];
+        for (pass_index, pass) in passes.iter().enumerate() {
+            for resource in pass.reads.iter().chain(pass.writes.iter()) {
+                if resources[resource.0 as usize].transient.is_none() {
+                    continue;
+                }
+                let lifetime = &mut lifetimes[resource.0 as usize];
+                *lifetime = match *lifetime {
+                    None => Some((pass_index, pass_index)),
+                    Some((first, _)) => Some((first, pass_index)),
+                };
+            }
+        }
+
+        // Give each transient resource the first texture with the same descriptor that's free by
+        // the time it's needed, in order of first use.
+        let mut transients: Vec<usize> = (0..resources.len()).filter(|&index| {
+            lifetimes[index].is_some()
+        }).collect();
+        transients.sort_by_key(|&index| lifetimes[index].unwrap().0);
+
+        let mut resource_slots = vec![None; resources.len()];
+        let mut slots: Vec<TextureSlot> = vec![];
+        for resource_index in transients {
+            let descriptor = resources[resource_index].transient.unwrap();
+            let (first_pass, last_pass) = lifetimes[resource_index].unwrap();
+            let slot_index = slots.iter().position(|slot| {
+                slot.descriptor == descriptor && slot.last_pass < first_pass
+            });
+            let slot_index = match slot_index {
+                Some(slot_index) => {
+                    slots[slot_index].last_pass = last_pass;
+                    slot_index
+                }
+                None => {
+                    slots.push(TextureSlot { descriptor, first_pass, last_pass });
+                    slots.len() - 1
+                }
+            };
+            resource_slots[resource_index] = Some(slot_index);
+        }
+
+        CompiledFrameGraph { passes, resources, resource_slots, slots }
+    }
+}
+
+impl<C> Default for FrameGraph<C> {
+    #[inline]
+    fn default() -> FrameGraph<C> {
+        FrameGraph::new()
+    }
+}
+
+impl<'a, C> PassBuilder<'a, C> {
+    #[inline]
+    pub fn read(mut self, resource: FrameResource) -> PassBuilder<'a, C> {
+        self.reads.push(resource);
+        self
+    }
+
+    #[inline]
+    pub fn write(mut self, resource: FrameResource) -> PassBuilder<'a, C> {
+        self.writes.push(resource);
+        self
+    }
+
+    #[inline]
+    pub fn placement(mut self, placement: PassPlacement) -> PassBuilder<'a, C> {
+        self.placement = placement;
+        self
+    }
+
+    /// Adds the pass to the graph. `execute` runs when the graph does.
+    pub fn execute<F>(self, execute: F) where F: FnOnce(&mut C, &PassResources) + 'static {
+        self.graph.passes.push(Pass {
+            name: self.name,
+            placement: self.placement,
+            reads: self.reads,
+            writes: self.writes,
+            execute: Box::new(execute),
+        });
+    }
+}
+
+impl<C> CompiledFrameGraph<C> {
+    /// The names of the passes that will run, in order.
+    pub fn pass_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.passes.iter().map(|pass| pass.name)
+    }
+
+    /// The number of textures that back the transient resources.
+    #[inline]
+    pub fn transient_texture_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Runs the passes in order. Transient textures are allocated with `allocate` just before
+    /// they're first used and released with `free` after they're last used.
+    pub fn execute<A, F>(self, context: &mut C, mut allocate: A, mut free: F)
+                         where A: FnMut(&mut C, &TransientTextureDescriptor) -> TextureID,
+                               F: FnMut(&mut C, TextureID) {
+        let CompiledFrameGraph { passes, resources, resource_slots, slots } = self;
+        let mut slot_textures: Vec<Option<TextureID>> = vec![None; slots.len()];
+        let mut pass_resources = PassResources { textures: vec![None; resources.len()] };
+
+        for (pass_index, pass) in passes.into_iter().enumerate() {
+            for (slot_index, slot) in slots.iter().enumerate() {
+                if slot.first_pass == pass_index {
+                    slot_textures[slot_index] = Some(allocate(context, &slot.descriptor));
+                }
+            }
+            for (resource_index, slot_index) in resource_slots.iter().enumerate() {
+                if let Some(slot_index) = *slot_index {
+                    pass_resources.textures[resource_index] = slot_textures[slot_index];
+                }
+            }
+
+            (pass.execute)(context, &pass_resources);
+
+            for (slot_index, slot) in slots.iter().enumerate() {
+                if slot.last_pass == pass_index {
+                    if let Some(texture_id) = slot_textures[slot_index].take() {
+                        free(context, texture_id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Puts the passes in the order they'll run: passes placed in order keep the order they were added
+// in, and the others are moved next to the passes they name.
+fn place_passes<C>(passes: Vec<Pass<C>>) -> Vec<Pass<C>> {
+    let (mut ordered, placed): (Vec<Pass<C>>, Vec<Pass<C>>) =
+        passes.into_iter().partition(|pass| pass.placement == PassPlacement::InOrder);
+
+    // Passes placed after the same pass keep the order they were added in.
+    let mut placed_after_counts: FxHashMap<&'static str, usize> = FxHashMap::default();
+    for pass in placed {
+        let index = match pass.placement {
+            PassPlacement::InOrder => None,
+            PassPlacement::Before(name) => ordered.iter().position(|other| other.name == name),
+            PassPlacement::After(name) => {
+                ordered.iter().rposition(|other| other.name == name).map(|index| {
+                    let count = placed_after_counts.entry(name).or_insert(0);
+                    *count += 1;
+                    index + *count
+                })
+            }
+        };
+        match index {
+            Some(index) => ordered.insert(index, pass),
+            None => ordered.push(pass),
+        }
+    }
+    ordered
+}
+
+// Removes passes that only write transient resources that no later pass reads.
+fn cull_passes<C>(resources: &[Resource], passes: &mut Vec<Pass<C>>) {
+    let mut needed = vec![false; resources.len()];
+    let mut live = vec![false; passes.len()];
+    for (pass_index, pass) in passes.iter().enumerate().rev() {
+        let is_live = pass.writes.is_empty() || pass.writes.iter().any(|resource| {
+            resources[resource.0 as usize].transient.is_none() || needed[resource.0 as usize]
+        });
+        if !is_live {
+            continue;
+        }
+        live[pass_index] = true;
+        for resource in &pass.reads {
+            needed[resource.0 as usize] = true;
+        }
+    }
+
+    let mut live = live.into_iter();
+    passes.retain(|_| live.next().unwrap());
+}
@@ -16,8 +16,16 @@ pub mod d3d9;
 pub mod d3d11;
 #[cfg(feature="debug")]
 pub mod debug;
+pub mod frame_graph;
+pub mod multiview;
 pub mod options;
 pub mod perf;
 pub mod renderer;
+pub mod scheduler;
+pub mod transition;
 
+#[cfg(feature="d3d9")]
+pub(crate) mod bind_groups;
 pub(crate) mod blend;
+#[cfg(feature="d3d9")]
+pub(crate) mod uniforms;
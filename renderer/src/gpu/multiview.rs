@@ -0,0 +1,253 @@
+// pathfinder/renderer/src/gpu/multiview.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Draws a rendered scene into several views at once, for stereo and VR overlays.
+//!
+//! The scene is tiled and rendered once; each view then receives the result as a quad
+//! transformed by that view's matrix. Where the device supports `wgpu::Features::MULTIVIEW`, all
+//! views are drawn in a single render pass.
+
+use fxhash::FxHashMap;
+use pathfinder_color::ColorF;
+use pathfinder_geometry::transform3d::Transform4F;
+use pathfinder_geometry::vector::Vector2I;
+use pathfinder_gpu::Device;
+use pathfinder_gpu::sampler::SamplerOptions;
+use pathfinder_resources::ResourceLoader;
+use std::num::NonZeroU32;
+use wgpu::util::DeviceExt;
+
+/// The maximum number of views that can be drawn to at once.
+pub const MAX_VIEWS: usize = 8;
+
+/// A set of views to draw a rendered scene into.
+pub struct MultiviewTarget<'a> {
+    /// A 2D array texture with at least as many layers as there are views, in `Rgba8Unorm`
+    /// format and with `RENDER_ATTACHMENT` usage. View *i* is drawn into layer *i*.
+    pub texture: &'a wgpu::Texture,
+    /// The transform of each view, from scene pixel coordinates to clip space.
+    pub transforms: &'a [Transform4F],
+    /// The color to clear each layer to first, or `None` to draw over the existing contents.
+    pub clear_color: Option<ColorF>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ViewsUniform {
+    transforms: [[f32; 16]; MAX_VIEWS],
+    scene_size: [f32; 2],
+    view_index: u32,
+    pad: u32,
+}
+
+pub(crate) struct MultiviewCompositor {
+    module: wgpu::ShaderModule,
+    native_multiview: bool,
+    // Keyed by view mask, since a multiview pipeline must match the pass it's used in.
+    pipelines: FxHashMap<u32, wgpu::RenderPipeline>,
+}
+
+impl MultiviewCompositor {
+    pub(crate) fn new(device: &Device, resources: &dyn ResourceLoader) -> MultiviewCompositor {
+        let native_multiview = device.device.features().contains(wgpu::Features::MULTIVIEW);
+        let path = if native_multiview {
+            "shaders/multiview/composite_multiview.wgsl"
+        } else {
+            "shaders/multiview/composite.wgsl"
+        };
+
+        let source = resources.slurp(path).expect("Failed to load shader");
+        let module = device.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(path),
+            source: wgpu::ShaderSource::Wgsl(String::from_utf8_lossy(&source).into()),
+        });
+
+        MultiviewCompositor { module, native_multiview, pipelines: FxHashMap::default() }
+    }
+
+    fn pipeline(&mut self, device: &Device, view_mask: u32) -> &wgpu::RenderPipeline {
+        let module = &self.module;
+        self.pipelines.entry(view_mask).or_insert_with(|| {
+            create_pipeline(device, module, NonZeroU32::new(view_mask))
+        })
+    }
+
+    pub(crate) fn draw(&mut self,
+                       device: &Device,
+                       scene_view: &wgpu::TextureView,
+                       scene_size: Vector2I,
+                       quad_vertex_positions_buffer: &wgpu::Buffer,
+                       quad_vertex_indices_buffer: &wgpu::Buffer,
+                       target: &MultiviewTarget) {
+        let view_count = target.transforms.len();
+        assert!(view_count <= MAX_VIEWS, "Too many views!");
+        if view_count == 0 {
+            return;
+        }
+
+        let view_mask = if self.native_multiview { (1 << view_count) - 1 } else { 0 };
+        let native_multiview = self.native_multiview;
+        let pipeline = self.pipeline(device, view_mask);
+
+        let mut uniform = ViewsUniform {
+            transforms: [[0.0; 16]; MAX_VIEWS],
+            scene_size: [scene_size.x() as f32, scene_size.y() as f32],
+            view_index: 0,
+            pad: 0,
+        };
+        for (dest, transform) in uniform.transforms.iter_mut().zip(target.transforms) {
+            *dest = transform_to_array(transform);
+        }
+
+        let sampler = device.sampler(SamplerOptions::linear());
+        let texture_bind_group = device.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Multiview Texture BG"),
+            layout: &pipeline.get_bind_group_layout(1),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(scene_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let load = match target.clear_color {
+            Some(color) => wgpu::LoadOp::Clear(wgpu::Color {
+                r: color.r() as f64,
+                g: color.g() as f64,
+                b: color.b() as f64,
+                a: color.a() as f64,
+            }),
+            None => wgpu::LoadOp::Load,
+        };
+
+        let mut encoder = device.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Multiview Encoder"),
+        });
+
+        // One pass for all views if the hardware can do it; otherwise one pass per view.
+        let passes = if native_multiview { 1 } else { view_count };
+        for pass_index in 0..passes {
+            uniform.view_index = pass_index as u32;
+            let views_buffer =
+                device.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Multiview Views"),
+                    contents: bytemuck::cast_slice(&[uniform]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+            let views_bind_group = device.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Multiview Views BG"),
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: views_buffer.as_entire_binding(),
+                }],
+            });
+
+            let (base_array_layer, array_layer_count, multiview_mask) = if native_multiview {
+                (0, view_count as u32, NonZeroU32::new(view_mask))
+            } else {
+                (pass_index as u32, 1, None)
+            };
+            let dest_view = target.texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Multiview Dest"),
+                dimension: Some(if native_multiview {
+                    wgpu::TextureViewDimension::D2Array
+                } else {
+                    wgpu::TextureViewDimension::D2
+                }),
+                base_array_layer,
+                array_layer_count: Some(array_layer_count),
+                ..Default::default()
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Multiview Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &dest_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load, store: wgpu::StoreOp::Store },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &views_bind_group, &[]);
+            render_pass.set_bind_group(1, &texture_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, quad_vertex_positions_buffer.slice(..));
+            render_pass.set_index_buffer(quad_vertex_indices_buffer.slice(..),
+                                         wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..6, 0, 0..1);
+        }
+
+        device.queue.submit(Some(encoder.finish()));
+    }
+}
+
+fn create_pipeline(device: &Device,
+                   module: &wgpu::ShaderModule,
+                   multiview_mask: Option<NonZeroU32>)
+                   -> wgpu::RenderPipeline {
+    let premultiplied_over = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    // Let wgpu derive the bind group layouts from the shader.
+    device.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Multiview Composite"),
+        layout: None,
+        vertex: wgpu::VertexState {
+            module,
+            entry_point: Some("vs_main"),
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: 4,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Uint16x2],
+            }],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                blend: Some(wgpu::BlendState {
+                    color: premultiplied_over,
+                    alpha: premultiplied_over,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask,
+        cache: None,
+    })
+}
+
+fn transform_to_array(transform: &Transform4F) -> [f32; 16] {
+    [
+        transform.c0.x(), transform.c0.y(), transform.c0.z(), transform.c0.w(),
+        transform.c1.x(), transform.c1.y(), transform.c1.z(), transform.c1.w(),
+        transform.c2.x(), transform.c2.y(), transform.c2.z(), transform.c2.w(),
+        transform.c3.x(), transform.c3.y(), transform.c3.z(), transform.c3.w(),
+    ]
+}
@@ -27,12 +27,42 @@ pub struct RendererOptions {
     /// Where the rendering should go: either to the default framebuffer (i.e. screen) or to a
     /// custom framebuffer.
     pub dest: DestFramebuffer,
-    /// The background color. If not present, transparent is assumed.
+    /// The background color, with straight alpha. If not present, transparent is assumed.
+    ///
+    /// A transparent or translucent background leaves the destination with premultiplied alpha,
+    /// ready to be composited over whatever lies beneath it, such as native UI or video.
     pub background_color: Option<ColorF>,
+    /// What happens to the destination's previous contents at the start of each frame.
+    pub load_action: LoadAction,
+    /// Whether the finished frame has premultiplied or straight alpha.
+    pub alpha_mode: AlphaMode,
     /// Whether to display the debug UI.
     pub show_debug_ui: bool,
 }
 
+/// What happens to a render target's previous contents when a frame starts drawing to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoadAction {
+    /// The contents are cleared to the background color, even if nothing is drawn.
+    Clear,
+    /// The contents are kept, and the frame is drawn over them.
+    Load,
+}
+
+/// How the color of a finished frame relates to its alpha.
+///
+/// Pathfinder blends with premultiplied alpha, so straight alpha costs an extra pass at the end of
+/// each frame. Straight alpha suits image files, which would otherwise get dark fringes wherever
+/// the frame is translucent, but it can't be drawn over again, so it doesn't mix with
+/// `LoadAction::Load`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    /// The color is multiplied by the alpha, as compositors usually expect.
+    Premultiplied,
+    /// The color is independent of the alpha.
+    Straight,
+}
+
 /// The GPU API level that Pathfinder will use.
 ///
 /// Note that this is a *level*, not a *backend*. Levels describe rough GPU feature requirements
@@ -46,6 +76,36 @@ pub enum RendererLevel {
     D3D11,
 }
 
+/// Options for drawing vector content into a scene that has a depth buffer, so that 3D geometry
+/// can occlude it.
+///
+/// See `Renderer::set_depth_options()`.
+#[derive(Clone)]
+pub struct DepthOptions {
+    /// The depth buffer, which must be the same size as the destination framebuffer.
+    ///
+    /// It must have a depth-only format (e.g. `Depth32Float`) and `RENDER_ATTACHMENT` usage.
+    pub texture: Texture,
+    /// The depth value, in normalized device coordinates, at which all vector content lies.
+    pub depth: f32,
+    /// How the depth of vector content is compared against the depth buffer. Use
+    /// `CompareFunction::Always` to write depth without testing.
+    pub compare: wgpu::CompareFunction,
+    /// Whether vector content writes its depth to the depth buffer.
+    pub write: bool,
+}
+
+/// The format of the coverage masks produced in coverage output mode.
+///
+/// See `Renderer::set_coverage_output()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoverageFormat {
+    /// 8-bit unsigned normalized coverage.
+    R8,
+    /// 16-bit floating point coverage.
+    R16F,
+}
+
 impl RendererMode {
     /// Creates a new `RendererMode` with a suitable API level for the given GPU device.
     #[inline]
@@ -60,11 +120,22 @@ impl Default for RendererOptions {
         RendererOptions {
             dest: DestFramebuffer::default(),
             background_color: None,
+            load_action: LoadAction::Clear,
+            alpha_mode: AlphaMode::Premultiplied,
             show_debug_ui: false,
         }
     }
 }
 
+impl CoverageFormat {
+    pub(crate) fn texture_format(self) -> wgpu::TextureFormat {
+        match self {
+            CoverageFormat::R8 => wgpu::TextureFormat::R8Unorm,
+            CoverageFormat::R16F => wgpu::TextureFormat::R16Float,
+        }
+    }
+}
+
 impl RendererLevel {
     /// Returns a suitable renderer level for the given device.
     pub fn default_for_device(_device: &Device) -> RendererLevel {
@@ -11,6 +11,7 @@
 //! Performance monitoring infrastructure.
 
 use crate::gpu::options::RendererOptions;
+use crate::options::Degradation;
 use pathfinder_gpu::{Device, TimerQuery};
 use std::mem;
 use std::ops::{Add, Div};
@@ -41,6 +42,8 @@ pub struct RenderStats {
     pub gpu_bytes_allocated: u64,
     /// The number of bytes of VRAM Pathfinder actually used for the frame.
     pub gpu_bytes_committed: u64,
+    /// How scene building reduced quality to fit `BuildOptions::memory_budget`.
+    pub degradation: Degradation,
 }
 
 impl Add<RenderStats> for RenderStats {
@@ -55,6 +58,14 @@ impl Add<RenderStats> for RenderStats {
             drawcall_count: self.drawcall_count + other.drawcall_count,
             gpu_bytes_allocated: self.gpu_bytes_allocated + other.gpu_bytes_allocated,
             gpu_bytes_committed: self.gpu_bytes_committed + other.gpu_bytes_committed,
+            degradation: Degradation {
+                tolerance_coarsening_steps: self.degradation.tolerance_coarsening_steps.max(
+                    other.degradation.tolerance_coarsening_steps),
+                pattern_filters_skipped: self.degradation.pattern_filters_skipped ||
+                    other.degradation.pattern_filters_skipped,
+                culled_path_count: self.degradation.culled_path_count +
+                    other.degradation.culled_path_count,
+            },
         }
     }
 }
@@ -71,10 +82,40 @@ impl Div<usize> for RenderStats {
             drawcall_count: self.drawcall_count / divisor as u32,
             gpu_bytes_allocated: self.gpu_bytes_allocated / divisor as u64,
             gpu_bytes_committed: self.gpu_bytes_committed / divisor as u64,
+            degradation: Degradation {
+                culled_path_count: self.degradation.culled_path_count / divisor,
+                ..self.degradation
+            },
         }
     }
 }
 
+/// CPU-side timings of the stages of a frame, for embedders to show alongside the GPU timings.
+///
+/// The renderer returns these from `Renderer::frame_stats()` after each frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// The time spent building the scene's paints and finding the paths it hides.
+    pub scene_build_time: Duration,
+    /// The time spent converting strokes to fills, as recorded in the scene while it was drawn.
+    pub stroke_time: Duration,
+    /// The time spent tiling paths on the CPU.
+    pub tile_time: Duration,
+    /// The time spent sorting tiles into batches and sending them to the renderer.
+    pub batch_time: Duration,
+    /// The time the renderer spent encoding GPU commands for the frame.
+    pub encode_time: Duration,
+}
+
+impl FrameStats {
+    /// Returns the total CPU time of all stages.
+    #[inline]
+    pub fn total_time(&self) -> Duration {
+        self.scene_build_time + self.stroke_time + self.tile_time + self.batch_time +
+            self.encode_time
+    }
+}
+
 pub(crate) struct TimerQueryCache {
     free_queries: Vec<TimerQuery>,
 }
@@ -14,34 +14,63 @@ use crate::gpu::blend::ToCompositeCtrl;
 #[cfg(feature = "d3d11")]
 use crate::gpu::d3d11::renderer::RendererD3D11;
 #[cfg(feature = "d3d9")]
-use crate::gpu::d3d9::renderer::RendererD3D9;
+use crate::gpu::d3d9::custom::CustomTileShader;
+#[cfg(feature = "d3d9")]
+use crate::gpu::d3d9::paint_server::PaintServer;
+#[cfg(feature = "d3d9")]
+use crate::gpu::d3d9::renderer::{FillBufferInfoD3D9, RendererD3D9};
 #[cfg(feature = "ui")]
 use crate::gpu::debug::DebugUiPresenter;
-use crate::gpu::options::{DestFramebuffer, RendererLevel, RendererMode, RendererOptions};
-use crate::gpu::perf::{PendingTimer, RenderStats, RenderTime, TimerQueryCache};
+#[cfg(feature = "d3d9")]
+use crate::gpu::frame_graph::{FILLS_PASS, TILES_PASS};
+use crate::gpu::frame_graph::{CLEAR_DEST_PASS, STRAIGHT_ALPHA_PASS, FrameGraph, FrameResource};
+use crate::gpu::frame_graph::{PassPlacement, TransientTextureDescriptor};
+use crate::gpu::frame_graph::{UserPassContext, UserPassId};
+#[cfg(feature = "d3d9")]
+use crate::gpu::options::{CoverageFormat, DepthOptions};
+use crate::gpu::multiview::{MultiviewCompositor, MultiviewTarget};
+use crate::gpu::options::{AlphaMode, DestFramebuffer, LoadAction, RendererLevel, RendererMode};
+use crate::gpu::options::RendererOptions;
+use crate::gpu::perf::{FrameStats, PendingTimer, RenderStats, RenderTime, TimerQueryCache};
+use crate::gpu::transition::{SceneSnapshot, Transition, TransitionCompositor};
+use crate::gpu::transition::TransitionGlobals;
 use crate::gpu_data::{
     ColorCombineMode, RenderCommand, TextureLocation, TextureMetadataEntry, TexturePageDescriptor,
     TexturePageId,
 };
 use crate::options::BoundingQuad;
+use crate::paint::PaintTextureManager;
+#[cfg(feature = "d3d9")]
+use crate::paint::PaintId;
+use crate::scene::CoverageCurve;
+#[cfg(feature = "d3d9")]
+use crate::scene::DrawPathId;
 use crate::tiles::{TILE_HEIGHT, TILE_WIDTH};
 use fxhash::FxHashMap;
 use half::f16;
+use instant::Instant;
 use pathfinder_color::{ColorF, ColorU};
 use pathfinder_content::effects::{BlendMode, BlurDirection, Filter, PatternFilter};
 use pathfinder_content::render_target::RenderTargetId;
-use pathfinder_geometry::rect::RectI;
+use pathfinder_geometry::rect::{RectF, RectI};
 use pathfinder_geometry::transform3d::Transform4F;
 use pathfinder_geometry::util;
 use pathfinder_geometry::vector::{vec2f, vec2i, Vector2I};
 use pathfinder_gpu::allocator::{BufferTag, GeneralBufferID};
 use pathfinder_gpu::allocator::{GpuMemoryAllocator, IndexBufferID, TextureID, TextureTag};
+use pathfinder_gpu::sampler::SamplerOptions;
+use pathfinder_gpu::staging::StagingUploader;
+use pathfinder_gpu::uniform_block::{self, UniformBlock};
 use pathfinder_gpu::Device;
 use pathfinder_gpu::RenderTarget;
 use pathfinder_gpu::Texture;
+#[cfg(feature = "d3d9")]
+use pathfinder_gpu::TextureData;
 use pathfinder_resources::ResourceLoader;
+use pathfinder_resources::manifest::{self, ResourceMismatch};
 use pathfinder_simd::default::{F32x2, F32x4};
 use std::collections::VecDeque;
+use std::mem;
 use std::time::Duration;
 use wgpu;
 use wgpu::util::DeviceExt;
@@ -82,6 +111,7 @@ struct FilterParams {
     ctrl: i32,
 }
 
+#[cfg(feature = "ui")]
 pub struct DebugUiPresenterInfo<'a> {
     pub device: &'a Device,
     pub allocator: &'a mut GpuMemoryAllocator,
@@ -92,10 +122,24 @@ pub struct DebugUiPresenterInfo<'a> {
 pub struct Renderer {
     pub(crate) core: RendererCore,
 
+    // How the resources the renderer was created with differ from those it was built against.
+    resource_mismatches: Vec<ResourceMismatch>,
+
     blit_pipeline: wgpu::RenderPipeline,
     clear_pipeline: wgpu::RenderPipeline,
     stencil_pipeline: wgpu::RenderPipeline,
     reprojection_pipeline: wgpu::RenderPipeline,
+    multiview_compositor: MultiviewCompositor,
+    transition_compositor: TransitionCompositor,
+
+    // The paint textures of scenes built with `Scene::build_and_render()`, kept between builds.
+    pub(crate) paint_texture_manager: PaintTextureManager,
+
+    // The passes of the frame being rendered, which run when it finishes.
+    frame_graph: FrameGraph<Renderer>,
+    frame_resources: FrameResources,
+    user_passes: Vec<UserPass>,
+    next_user_pass_id: u32,
 
     #[cfg(feature = "d3d11")]
     d3d11_renderer: RendererD3D11,
@@ -120,7 +164,12 @@ pub struct Renderer {
 pub(crate) struct RendererCore {
     pub(crate) device: Device,
     pub(crate) mode: RendererMode,
+    // The level the current scene was built at, which may be lower than the mode's level.
+    pub(crate) scene_level: RendererLevel,
+    // How the current scene maps edge coverage to alpha.
+    pub(crate) coverage_curve: CoverageCurve,
     pub(crate) allocator: GpuMemoryAllocator,
+    pub(crate) uploader: StagingUploader,
     pub(crate) options: RendererOptions,
     pub(crate) timer_query_cache: TimerQueryCache,
     pub(crate) quad_vertex_positions_buffer_id: GeneralBufferID,
@@ -130,6 +179,8 @@ pub(crate) struct RendererCore {
     pub(crate) renderer_flags: RendererFlags,
     pub(crate) mask_storage_flags: MaskStorageFlags,
     pub(crate) stats: RenderStats,
+    // CPU timings of the frame being rendered, or of the last one after `end_scene()`.
+    pub(crate) frame_stats: FrameStats,
     pub(crate) current_timer: Option<PendingTimer>,
     pub(crate) alpha_tile_count: u32,
     pub(crate) mask_storage: Option<MaskStorage>,
@@ -142,6 +193,10 @@ pub(crate) struct RendererCore {
     pub(crate) render_target_stack: Vec<RenderTargetId>,
     // Mapping from render target ID to texture location
     pub(crate) render_target_textures: FxHashMap<RenderTargetId, TextureLocation>,
+    // True if the embedder's opaque regions cover the whole destination this frame.
+    pub(crate) dest_is_covered: bool,
+    // True if the destination has yet to be cleared this frame.
+    pub(crate) dest_needs_clear: bool,
 }
 
 impl RendererCore {
@@ -164,8 +219,48 @@ impl RendererCore {
         }
     }
 
-    pub fn clear_color_for_draw_operation(&self) -> Option<ColorF> {
-        self.options.background_color
+    // Returns the color, with premultiplied alpha, to clear the destination to as part of the
+    // next draw to it, if it's still waiting to be cleared this frame. The caller must clear it.
+    pub(crate) fn take_dest_clear_color(&mut self) -> Option<ColorF> {
+        if !mem::replace(&mut self.dest_needs_clear, false) {
+            return None;
+        }
+        let color = self.options.background_color.unwrap_or_default();
+        let alpha = color.a();
+        Some(ColorF::new(color.r() * alpha, color.g() * alpha, color.b() * alpha, alpha))
+    }
+
+    // Clears the destination on its own if nothing has drawn to it yet this frame, as when a frame
+    // is empty or the drawing doesn't cover all of it.
+    pub(crate) fn clear_dest_if_needed(&mut self) {
+        let color = match self.take_dest_clear_color() {
+            Some(color) => color,
+            None => return,
+        };
+        let dest_texture = self.allocator.get_texture(self.intermediate_dest_texture_id);
+        let mut encoder =
+            self.device
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Clear Dest Encoder"),
+                });
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Clear Dest Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &dest_texture.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu_color(color)),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+        self.device.queue.submit(Some(encoder.finish()));
     }
 
     pub fn preserve_draw_framebuffer(&mut self) {
@@ -186,7 +281,7 @@ impl RendererCore {
     }
 
     pub(crate) fn mask_texture_format(&self) -> wgpu::TextureFormat {
-        match self.mode.level {
+        match self.scene_level {
             RendererLevel::D3D9 => wgpu::TextureFormat::Rgba16Float,
             RendererLevel::D3D11 => wgpu::TextureFormat::Rgba8Unorm,
         }
@@ -200,6 +295,7 @@ impl RendererCore {
         let current_page_count = self
             .mask_storage
             .as_ref()
+            .filter(|s| s.format == self.mask_texture_format())
             .map(|s| s.allocated_page_count)
             .unwrap_or(0);
         if needed_page_count <= current_page_count {
@@ -228,6 +324,7 @@ impl RendererCore {
         self.mask_storage = Some(MaskStorage {
             texture_id,
             allocated_page_count: needed_page_count,
+            format: self.mask_texture_format(),
         });
 
         self.mask_storage_flags
@@ -244,9 +341,31 @@ impl RendererCore {
     }
 }
 
+// The resources the renderer's own passes use.
+#[derive(Clone, Copy)]
+struct FrameResources {
+    dest: FrameResource,
+    mask: FrameResource,
+}
+
+impl FrameResources {
+    fn import(frame_graph: &mut FrameGraph<Renderer>) -> FrameResources {
+        FrameResources { dest: frame_graph.import(), mask: frame_graph.import() }
+    }
+}
+
+struct UserPass {
+    id: UserPassId,
+    name: &'static str,
+    placement: PassPlacement,
+    copies_dest: bool,
+    execute: Box<dyn FnMut(&mut UserPassContext)>,
+}
+
 pub(crate) struct MaskStorage {
     pub(crate) texture_id: TextureID,
     pub(crate) allocated_page_count: u32,
+    pub(crate) format: wgpu::TextureFormat,
 }
 
 bitflags! {
@@ -285,6 +404,13 @@ impl Renderer {
         mode: RendererMode,
         options: RendererOptions,
     ) -> Renderer {
+        // Stale resources otherwise show up as obscure shader errors or misrendering.
+        let resource_mismatches = manifest::verify(resources);
+        for mismatch in &resource_mismatches {
+            warn!("{}", mismatch);
+        }
+        check_uniform_blocks(resources);
+
         let mut allocator = GpuMemoryAllocator::new();
 
         let quad_vertex_positions_buffer_id = allocator.allocate_general_buffer::<u16>(
@@ -364,7 +490,10 @@ impl Renderer {
         let mut core = RendererCore {
             device: device.clone(),
             mode: mode.clone(),
+            scene_level: mode.level,
+            coverage_curve: CoverageCurve::default(),
             allocator,
+            uploader: StagingUploader::new(&device),
             options,
             timer_query_cache: TimerQueryCache::new(),
             quad_vertex_positions_buffer_id,
@@ -375,6 +504,7 @@ impl Renderer {
             renderer_flags: RendererFlags::empty(),
             mask_storage_flags: MaskStorageFlags::empty(),
             stats: RenderStats::default(),
+            frame_stats: FrameStats::default(),
             current_timer: None,
             alpha_tile_count: 0,
             mask_storage: None,
@@ -382,12 +512,16 @@ impl Renderer {
             texture_pages: FxHashMap::default(),
             render_target_stack: Vec::new(),
             render_target_textures: FxHashMap::default(),
+            dest_is_covered: false,
+            dest_needs_clear: false,
         };
 
         let blit_pipeline = device.create_render_pipeline(resources, "blit", None);
         let clear_pipeline = device.create_render_pipeline(resources, "clear", None);
         let stencil_pipeline = device.create_render_pipeline(resources, "stencil", None);
         let reprojection_pipeline = device.create_render_pipeline(resources, "reproject", None);
+        let multiview_compositor = MultiviewCompositor::new(&device, resources);
+        let transition_compositor = TransitionCompositor::new(&device, resources);
 
         #[cfg(feature = "d3d11")]
         let d3d11_renderer = RendererD3D11::new(&core, resources);
@@ -396,12 +530,23 @@ impl Renderer {
         #[cfg(feature = "d3d9")]
         let d3d9_renderer = RendererD3D9::new(&mut core_mut, resources);
 
+        let mut frame_graph = FrameGraph::new();
+        let frame_resources = FrameResources::import(&mut frame_graph);
+
         Renderer {
             core: core_mut,
+            resource_mismatches,
             blit_pipeline,
             clear_pipeline,
             stencil_pipeline,
             reprojection_pipeline,
+            multiview_compositor,
+            transition_compositor,
+            paint_texture_manager: PaintTextureManager::new(),
+            frame_graph,
+            frame_resources,
+            user_passes: vec![],
+            next_user_pass_id: 0,
             #[cfg(feature = "d3d11")]
             d3d11_renderer,
             #[cfg(feature = "d3d9")]
@@ -431,6 +576,15 @@ impl Renderer {
         &mut self.core.device
     }
 
+    /// Returns how the resources this renderer was created with differ from those the library
+    /// was built against, such as shaders from an older checkout. This is empty if they match.
+    ///
+    /// Mismatches are also logged as warnings when the renderer is created.
+    #[inline]
+    pub fn resource_mismatches(&self) -> &[ResourceMismatch] {
+        &self.resource_mismatches
+    }
+
     pub fn options(&self) -> &RendererOptions {
         &self.core.options
     }
@@ -438,6 +592,162 @@ impl Renderer {
         &mut self.core.options
     }
 
+    /// Draws every tile of every path filled with `paint_id` with the given custom shader
+    /// instead of the built-in one, or restores the built-in shader if `shader` is `None`.
+    ///
+    /// The shader is compiled immediately. See `pathfinder_renderer::gpu::d3d9::custom` for the
+    /// interface that the shader must conform to. This has no effect at the D3D11 level, or on
+    /// paths with a tint other than white.
+    #[cfg(feature = "d3d9")]
+    pub fn set_custom_tile_shader(&mut self,
+                                  paint_id: PaintId,
+                                  shader: Option<&CustomTileShader>) {
+        self.d3d9_renderer.set_custom_tile_shader(&self.core, paint_id, shader);
+    }
+
+    /// Updates the contents of the user uniform buffer of the custom tile shader registered for
+    /// `paint_id`.
+    ///
+    /// Panics if there's no such shader, or if it was created without user uniforms.
+    #[cfg(feature = "d3d9")]
+    pub fn set_custom_tile_uniforms(&mut self, paint_id: PaintId, data: &[u8]) {
+        self.d3d9_renderer.set_custom_tile_uniforms(&self.core, paint_id, data);
+    }
+
+    /// Fills every path painted with `paint_id` with the given procedural paint, or restores the
+    /// paint itself if `server` is `None`.
+    ///
+    /// This replaces any custom tile shader registered for the paint. See
+    /// `pathfinder_renderer::gpu::d3d9::paint_server` for how paint servers are written. Like
+    /// custom tile shaders, this has no effect at the D3D11 level.
+    #[cfg(feature = "d3d9")]
+    pub fn set_paint_server(&mut self,
+                            paint_id: PaintId,
+                            server: Option<&dyn PaintServer>,
+                            resources: &dyn ResourceLoader) {
+        let shader = server.map(|server| CustomTileShader::from_paint_server(server, resources));
+        self.d3d9_renderer.set_custom_tile_shader(&self.core, paint_id, shader.as_ref());
+    }
+
+    /// Updates the uniforms of the paint server registered for `paint_id` from `server`, which
+    /// is typically the same paint server with new parameters.
+    ///
+    /// Panics if there's no paint server for that paint.
+    #[cfg(feature = "d3d9")]
+    pub fn update_paint_server(&mut self, paint_id: PaintId, server: &dyn PaintServer) {
+        if let Some(data) = server.uniform_data() {
+            self.d3d9_renderer.set_custom_tile_uniforms(&self.core, paint_id, &data);
+        }
+    }
+
+    /// Switches the renderer into or out of coverage output mode.
+    ///
+    /// In coverage output mode, the renderer ignores paints and doesn't composite anything to the
+    /// destination framebuffer. Instead, it writes the anti-aliased coverage of the union of all
+    /// paths in the scene to a single-channel texture of the given format, available from
+    /// `coverage_texture()` once the scene has been rendered. This is useful for image-processing
+    /// pipelines that only need masks. Pass `None` to return to normal rendering.
+    ///
+    /// The texture is the size of the destination framebuffer, and is reallocated at the start
+    /// of a scene if the destination has been resized. Coverage output is only supported at the
+    /// D3D9 level.
+    #[cfg(feature = "d3d9")]
+    pub fn set_coverage_output(&mut self, format: Option<CoverageFormat>) {
+        self.d3d9_renderer.set_coverage_output(&mut self.core, format);
+    }
+
+    /// Limits coverage output to the given draw paths, or to all paths again if `paths` is
+    /// `None`.
+    ///
+    /// The coverage texture then holds the mask of just those paths, without having to build a
+    /// separate scene for them. The limit applies to every scene rendered until it's changed,
+    /// and survives changes of coverage format.
+    #[cfg(feature = "d3d9")]
+    pub fn set_coverage_paths(&mut self, paths: Option<&[DrawPathId]>) {
+        self.d3d9_renderer.set_coverage_paths(paths);
+    }
+
+    /// Draws vector content at a fixed depth against the given depth buffer, so that 3D geometry
+    /// already in the depth buffer occludes it, or returns to drawing on top of everything if
+    /// `options` is `None`.
+    ///
+    /// `resources` is used to compile the depth-enabled tile pipeline, which is only recompiled
+    /// when the depth format, compare function, or write flag changes. Custom tile shaders and
+    /// coverage output don't participate in depth testing. Only supported at the D3D9 level.
+    #[cfg(feature = "d3d9")]
+    pub fn set_depth_options(&mut self,
+                             resources: &dyn ResourceLoader,
+                             options: Option<DepthOptions>) {
+        self.d3d9_renderer.set_depth_options(&self.core, resources, options);
+    }
+
+    /// Returns the texture that coverage masks are written to, if coverage output mode is on.
+    #[cfg(feature = "d3d9")]
+    pub fn coverage_texture(&self) -> Option<&Texture> {
+        self.d3d9_renderer
+            .coverage_texture_id()
+            .map(|texture_id| self.core.allocator.get_texture(texture_id))
+    }
+
+    /// Turns path ID output on or off, for picking.
+    ///
+    /// While on, every scene rendered also writes a 32-bit ID for each pixel into a parallel
+    /// `R32Uint` texture, available from `id_texture()`: the index of the topmost draw path that
+    /// covers at least half of the pixel, plus one, or zero if no path does. IDs aren't
+    /// antialiased, and paths count even if their paint is transparent. `pick_path()` reads back
+    /// a single pixel, which is much cheaper than hit testing dense scenes on the CPU.
+    ///
+    /// Like the coverage texture, the ID texture follows the size of the destination framebuffer.
+    /// Only supported at the D3D9 level.
+    #[cfg(feature = "d3d9")]
+    pub fn set_id_output(&mut self, enabled: bool) {
+        self.d3d9_renderer.set_id_output(&mut self.core, enabled);
+    }
+
+    /// Returns the texture that path IDs are written to, if path ID output is on.
+    #[cfg(feature = "d3d9")]
+    pub fn id_texture(&self) -> Option<&Texture> {
+        self.d3d9_renderer
+            .id_texture_id()
+            .map(|texture_id| self.core.allocator.get_texture(texture_id))
+    }
+
+    /// Returns the topmost draw path at the given pixel in the most recently rendered scene, by
+    /// reading back one texel of the ID texture.
+    ///
+    /// This blocks until the GPU has finished rendering. Returns `None` if no path covers the
+    /// pixel, if the position is outside the viewport, or if path ID output is off.
+    #[cfg(feature = "d3d9")]
+    pub fn pick_path(&self, position: Vector2I) -> Option<DrawPathId> {
+        let id_texture = self.id_texture()?;
+        if !RectI::new(Vector2I::zero(), id_texture.size).contains_point(position) {
+            return None;
+        }
+        let bytes = match self.core.device.read_texture(id_texture,
+                                                        RectI::new(position, Vector2I::splat(1))) {
+            TextureData::U8(bytes) => bytes,
+            _ => panic!("Unexpected pixel format for the ID texture!"),
+        };
+        match u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) {
+            0 => None,
+            id => Some(DrawPathId(id - 1)),
+        }
+    }
+
+    /// Turns the overdraw visualization debug mode on or off.
+    ///
+    /// In this mode, paints are ignored and every tile drawn adds heat to the pixels under it, so
+    /// the destination shows a heat map of how many paths were composited at each pixel, from
+    /// black (none) through red, yellow, and white (64 or more). Tiles culled because an opaque
+    /// path covers them aren't counted, since they cost nothing to draw. Use it to find
+    /// expensive overlapping content.
+    ///
+    /// Coverage output mode takes precedence over this mode. Only supported at the D3D9 level.
+    #[cfg(feature = "d3d9")]
+    pub fn set_overdraw_visualization(&mut self, enabled: bool) {
+        self.d3d9_renderer.set_overdraw_visualization(&self.core, enabled);
+    }
+
     pub fn draw_viewport(&self) -> RectI {
         self.core.draw_viewport()
     }
@@ -462,23 +772,129 @@ impl Renderer {
             .get_texture(self.core.intermediate_dest_texture_id)
     }
 
+    /// Draws the most recently rendered scene into each of the given views, e.g. both eyes of a
+    /// VR headset.
+    ///
+    /// The scene is only tiled and rendered once, by `Scene::build_and_render()`; this then draws
+    /// the result as a quad into each layer of the target texture with that view's transform. If
+    /// the device has `wgpu::Features::MULTIVIEW` enabled, all views are drawn in one pass.
+    pub fn draw_to_views(&mut self, target: &MultiviewTarget) {
+        let scene_texture = self
+            .core
+            .allocator
+            .get_texture(self.core.intermediate_dest_texture_id);
+        let quad_vertex_positions_buffer = self
+            .core
+            .allocator
+            .get_general_buffer(self.core.quad_vertex_positions_buffer_id);
+        let quad_vertex_indices_buffer = self
+            .core
+            .allocator
+            .get_index_buffer(self.core.quad_vertex_indices_buffer_id);
+        self.multiview_compositor.draw(
+            &self.core.device,
+            &scene_texture.view,
+            scene_texture.size,
+            quad_vertex_positions_buffer,
+            quad_vertex_indices_buffer,
+            target,
+        );
+    }
+
+    /// Copies the most recently rendered scene, so that later frames can transition away from it
+    /// with `draw_transition()`.
+    pub fn snapshot_scene(&self) -> SceneSnapshot {
+        let scene_texture = self.intermediate_dest_texture();
+        self.transition_compositor.snapshot(&self.core.device, scene_texture)
+    }
+
+    /// Replaces the most recently rendered scene with a blend from `from` to it.
+    ///
+    /// `progress` runs from 0, which shows only `from`, to 1, which shows only the new scene.
+    /// Call this after `Scene::build_and_render()` and before presenting, once per frame of the
+    /// transition.
+    pub fn draw_transition(&mut self,
+                           from: &SceneSnapshot,
+                           transition: Transition,
+                           progress: f32) {
+        let dest_size = self.intermediate_dest_texture().size;
+        let copy_texture_id = self.core.allocator.allocate_texture(
+            &self.core.device,
+            dest_size,
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            TextureTag("TransitionCopy"),
+        );
+
+        {
+            let dest_texture = self.intermediate_dest_texture();
+            let copy_texture = self.core.allocator.get_texture(copy_texture_id);
+            let mut encoder =
+                self.core
+                    .device
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("Transition Encoder"),
+                    });
+            encoder.copy_texture_to_texture(dest_texture.texture.as_image_copy(),
+                                            copy_texture.texture.as_image_copy(),
+                                            copy_texture.texture.size());
+            self.transition_compositor.encode_draw(&self.core.device,
+                                                   &mut encoder,
+                                                   from,
+                                                   &copy_texture.view,
+                                                   &dest_texture.view,
+                                                   transition,
+                                                   progress);
+            self.core.device.queue.submit(Some(encoder.finish()));
+        }
+
+        self.core.allocator.free_texture(copy_texture_id);
+    }
+
     /// Blit the intermediate destination texture to the given surface texture view.
     /// Uses the blit pipeline (blit.wgsl) to perform the copy via a render pass.
+    ///
+    /// The surface is cleared to transparent first, and the frame keeps its alpha mode, so a
+    /// surface configured with the matching `CompositeAlphaMode` shows what lies beneath it
+    /// wherever the background is transparent.
     pub fn blit_to_surface(&self, surface_view: &wgpu::TextureView, surface_size: Vector2I) {
-        let device = &self.core.device.device;
-        let queue = &self.core.device.queue;
-
         let intermediate_texture = self.intermediate_dest_texture();
-        let intermediate_size = intermediate_texture.size;
+        let mut encoder =
+            self.core
+                .device
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Blit encoder"),
+                });
+        self.encode_blit(&mut encoder,
+                         &intermediate_texture.view,
+                         intermediate_texture.size,
+                         surface_view,
+                         surface_size,
+                         false);
+        self.core.device.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    // Draws `src_view` over all of `dest_view`, which is cleared to transparent first. If
+    // `unpremultiply` is true, the color is divided by the alpha on the way.
+    fn encode_blit(&self,
+                   encoder: &mut wgpu::CommandEncoder,
+                   src_view: &wgpu::TextureView,
+                   src_size: Vector2I,
+                   dest_view: &wgpu::TextureView,
+                   dest_size: Vector2I,
+                   unpremultiply: bool) {
+        let device = &self.core.device.device;
 
         let globals_data = [
             0.0f32,
             0.0f32,
-            intermediate_size.x() as f32,
-            intermediate_size.y() as f32,
-            surface_size.x() as f32,
-            surface_size.y() as f32,
-            0.0f32,
+            src_size.x() as f32,
+            src_size.y() as f32,
+            dest_size.x() as f32,
+            dest_size.y() as f32,
+            if unpremultiply { 1.0 } else { 0.0 },
             0.0f32,
         ];
 
@@ -488,16 +904,7 @@ impl Renderer {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("Blit Sampler"),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
-            ..Default::default()
-        });
+        let sampler = self.core.device.sampler(SamplerOptions::linear());
 
         let bg0_layout = self.blit_pipeline.get_bind_group_layout(0);
         let bg1_layout = self.blit_pipeline.get_bind_group_layout(1);
@@ -516,7 +923,7 @@ impl Renderer {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&intermediate_texture.view),
+                    resource: wgpu::BindingResource::TextureView(src_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
@@ -525,40 +932,145 @@ impl Renderer {
             ],
         });
 
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Blit encoder"),
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blit pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: dest_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
         });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Blit pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: surface_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 0.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None,
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-                multiview_mask: None,
-            });
+        render_pass.set_pipeline(&self.blit_pipeline);
+        render_pass.set_bind_group(0, &bg0, &[]);
+        render_pass.set_bind_group(1, &bg1, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
 
-            render_pass.set_pipeline(&self.blit_pipeline);
-            render_pass.set_bind_group(0, &bg0, &[]);
-            render_pass.set_bind_group(1, &bg1, &[]);
-            render_pass.draw(0..3, 0..1);
+    // Converts the finished frame to straight alpha. The frame is copied aside and blitted back,
+    // since a texture can't be read while it's drawn to.
+    fn convert_dest_to_straight_alpha(&mut self, copy_texture_id: TextureID) {
+        let dest_size = self.intermediate_dest_texture().size;
+        let dest_texture = self.intermediate_dest_texture();
+        let copy_texture = self.core.allocator.get_texture(copy_texture_id);
+        let mut encoder =
+            self.core
+                .device
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Straight Alpha Encoder"),
+                });
+        encoder.copy_texture_to_texture(dest_texture.texture.as_image_copy(),
+                                        copy_texture.texture.as_image_copy(),
+                                        copy_texture.texture.size());
+        self.encode_blit(&mut encoder,
+                         &copy_texture.view,
+                         dest_size,
+                         &dest_texture.view,
+                         dest_size,
+                         true);
+        self.core.device.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Adds a pass that runs every frame, which draws to the destination with its own commands.
+    ///
+    /// By default, the pass runs after the scene has been drawn and before the frame is
+    /// finished. `placement` can instead put it next to the renderer's own passes, whose names are
+    /// the constants in the `frame_graph` module.
+    pub fn add_user_pass<F>(&mut self, name: &'static str, placement: PassPlacement, execute: F)
+                            -> UserPassId
+                            where F: FnMut(&mut UserPassContext) + 'static {
+        self.push_user_pass(name, placement, false, Box::new(execute))
+    }
+
+    /// Adds a pass that runs every frame, which filters the destination, as for vignettes or CRT
+    /// effects. The pass reads the frame from `UserPassContext::source` and draws the result to
+    /// the destination.
+    ///
+    /// Use `PassPlacement::AFTER_COMPOSITE` to filter the scene alone, or
+    /// `PassPlacement::InOrder` to filter it along with the other user passes.
+    pub fn add_post_process_pass<F>(&mut self,
+                                    name: &'static str,
+                                    placement: PassPlacement,
+                                    execute: F)
+                                    -> UserPassId
+                                    where F: FnMut(&mut UserPassContext) + 'static {
+        self.push_user_pass(name, placement, true, Box::new(execute))
+    }
+
+    fn push_user_pass(&mut self,
+                      name: &'static str,
+                      placement: PassPlacement,
+                      copies_dest: bool,
+                      execute: Box<dyn FnMut(&mut UserPassContext)>)
+                      -> UserPassId {
+        let id = UserPassId(self.next_user_pass_id);
+        self.next_user_pass_id += 1;
+        self.user_passes.push(UserPass { id, name, placement, copies_dest, execute });
+        id
+    }
+
+    pub fn remove_user_pass(&mut self, id: UserPassId) {
+        self.user_passes.retain(|user_pass| user_pass.id != id);
+    }
+
+    fn run_user_pass(&mut self, index: usize, source_texture_id: Option<TextureID>) {
+        // User passes draw over the background, so it has to be there first.
+        self.core.clear_dest_if_needed();
+
+        // The context borrows the core while the pass itself is borrowed mutably.
+        let core = &self.core;
+        let user_pass = &mut self.user_passes[index];
+
+        let dest = core.allocator.get_texture(core.intermediate_dest_texture_id);
+        let source = source_texture_id.map(|texture_id| core.allocator.get_texture(texture_id));
+        if let Some(source) = source {
+            let mut encoder =
+                core.device
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("User Pass Source Encoder"),
+                    });
+            encoder.copy_texture_to_texture(dest.texture.as_image_copy(),
+                                            source.texture.as_image_copy(),
+                                            source.texture.size());
+            core.device.queue.submit(Some(encoder.finish()));
         }
 
-        queue.submit(std::iter::once(encoder.finish()));
+        let mask = core.mask_storage.as_ref().map(|mask_storage| {
+            core.allocator.get_texture(mask_storage.texture_id)
+        });
+        let mut context = UserPassContext { device: &core.device, dest, source, mask };
+        (user_pass.execute)(&mut context);
+    }
+
+    // Swaps in an empty frame graph, returning the one with the passes recorded so far.
+    fn take_frame_graph(&mut self) -> FrameGraph<Renderer> {
+        let mut frame_graph = FrameGraph::new();
+        self.frame_resources = FrameResources::import(&mut frame_graph);
+        mem::replace(&mut self.frame_graph, frame_graph)
+    }
+
+    fn run_frame_graph(&mut self) {
+        if self.frame_graph.is_empty() {
+            return;
+        }
+        let frame_graph = self.take_frame_graph();
+        frame_graph.compile().execute(self, |renderer, descriptor| {
+            renderer.core.allocator.allocate_texture(&renderer.core.device,
+                                                     descriptor.size,
+                                                     descriptor.format,
+                                                     descriptor.usage,
+                                                     TextureTag("FrameGraphTransient"))
+        }, |renderer, texture_id| renderer.core.allocator.free_texture(texture_id));
     }
 
     #[cfg(feature = "ui")]
@@ -592,23 +1104,51 @@ impl Renderer {
     }
 
     pub fn begin_scene(&mut self) {
+        self.core.uploader.begin_frame(&self.core.device);
         self.core.allocator.begin_frame();
         self.core.stats = RenderStats::default();
+        self.core.frame_stats = FrameStats::default();
         self.core.alpha_tile_count = 0;
         self.core
             .mask_storage_flags
             .remove(MaskStorageFlags::MASK_TEXTURE_IS_DIRTY);
         self.core.current_timer = Some(PendingTimer::new());
+        self.take_frame_graph();
+
+        #[cfg(feature = "d3d9")]
+        self.d3d9_renderer.begin_scene(&mut self.core);
     }
 
     pub fn render_command(&mut self, command: &RenderCommand) {
+        let start_time = Instant::now();
+        self.encode_command(command);
+        self.core.frame_stats.encode_time += Instant::now() - start_time;
+    }
+
+    /// Returns the CPU timings of the stages of the last frame, once `end_scene()` has been called.
+    ///
+    /// The encoding time covers the commands passed to `render_command()`.
+    #[inline]
+    pub fn frame_stats(&self) -> FrameStats {
+        self.core.frame_stats
+    }
+
+    fn encode_command(&mut self, command: &RenderCommand) {
         match command {
             RenderCommand::Start {
                 path_count,
                 bounding_quad,
                 needs_readable_framebuffer,
+                opaque_regions,
+                renderer_level,
+                coverage_curve,
             } => {
-                self.start_rendering(*path_count, *bounding_quad, *needs_readable_framebuffer);
+                self.core.scene_level = *renderer_level;
+                self.core.coverage_curve = *coverage_curve;
+                self.start_rendering(*path_count,
+                                     *bounding_quad,
+                                     *needs_readable_framebuffer,
+                                     opaque_regions);
             }
             RenderCommand::AllocateTexturePage {
                 page_id,
@@ -631,8 +1171,13 @@ impl Renderer {
             RenderCommand::PopRenderTarget => {
                 self.pop_render_target();
             }
-            RenderCommand::Finish { cpu_build_time } => {
+            RenderCommand::Finish { cpu_build_time, degradation, frame_stats } => {
                 self.core.stats.cpu_build_time = *cpu_build_time;
+                self.core.stats.degradation = *degradation;
+                self.core.frame_stats = FrameStats {
+                    encode_time: self.core.frame_stats.encode_time,
+                    ..*frame_stats
+                };
                 self.finish_frame();
             }
             #[cfg(feature = "d3d11")]
@@ -655,20 +1200,42 @@ impl Renderer {
             }
             #[cfg(feature = "d3d9")]
             RenderCommand::AddFillsD3D9(fills) => {
-                self.d3d9_renderer.add_fills(&mut self.core, fills);
+                if let Some(fill_batch) = self.d3d9_renderer.add_fills(&mut self.core, fills) {
+                    self.add_fills_pass(fill_batch);
+                }
             }
             #[cfg(feature = "d3d9")]
             RenderCommand::FlushFillsD3D9 => {
-                self.d3d9_renderer.draw_buffered_fills(&mut self.core);
+                if let Some(fill_batch) = self.d3d9_renderer.take_buffered_fills(&mut self.core) {
+                    self.add_fills_pass(fill_batch);
+                }
             }
             #[cfg(feature = "d3d9")]
             RenderCommand::DrawTilesD3D9(batch) => {
-                self.d3d9_renderer
-                    .upload_and_draw_tiles(&mut self.core, batch);
+                let batch = self.d3d9_renderer.prepare_tiles(&mut self.core, batch);
+                let resources = self.frame_resources;
+                self.frame_graph
+                    .add_pass(TILES_PASS)
+                    .read(resources.mask)
+                    .write(resources.dest)
+                    .execute(move |renderer, _| {
+                        renderer.d3d9_renderer.draw_prepared_tiles(&mut renderer.core, batch);
+                    });
             }
         }
     }
 
+    #[cfg(feature = "d3d9")]
+    fn add_fills_pass(&mut self, fill_batch: FillBufferInfoD3D9) {
+        let resources = self.frame_resources;
+        self.frame_graph
+            .add_pass(FILLS_PASS)
+            .write(resources.mask)
+            .execute(move |renderer, _| {
+                renderer.d3d9_renderer.draw_fill_batch(&mut renderer.core, fill_batch);
+            });
+    }
+
     fn allocate_texture_page(
         &mut self,
         page_id: &TexturePageId,
@@ -712,11 +1279,22 @@ impl Renderer {
 
         for entry in metadata {
             let base_color = entry.base_color.to_f32();
+            let color_0_tint = entry.color_0_tint.to_f32();
             let filter_params = self.compute_filter_params(
                 &entry.filter,
                 entry.blend_mode,
                 entry.color_0_combine_mode,
             );
+            // Half floats only hold the texture offset to about a thousandth of the texture, so
+            // what they lose is stored separately and added back by the shader. Otherwise, drawn
+            // at fractional scales, repeated patterns drift against their tile edges.
+            let offset = entry.color_0_transform.translation();
+            let offset_x = f16::from_f32(offset.x());
+            let offset_y = f16::from_f32(offset.y());
+            let (seamless, sampling_flags) = match entry.color_0_sampling {
+                None => (0.0, 0.0),
+                Some(sampling_flags) => (1.0, sampling_flags.bits() as f32),
+            };
             texels.extend_from_slice(&[
                 // 0
                 f16::from_f32(entry.color_0_transform.m11()),
@@ -724,10 +1302,10 @@ impl Renderer {
                 f16::from_f32(entry.color_0_transform.m12()),
                 f16::from_f32(entry.color_0_transform.m22()),
                 // 1
-                f16::from_f32(entry.color_0_transform.m13()),
-                f16::from_f32(entry.color_0_transform.m23()),
-                f16::default(),
-                f16::default(),
+                offset_x,
+                offset_y,
+                f16::from_f32(offset.x() - offset_x.to_f32()),
+                f16::from_f32(offset.y() - offset_y.to_f32()),
                 // 2
                 f16::from_f32(base_color.r()),
                 f16::from_f32(base_color.g()),
@@ -760,12 +1338,12 @@ impl Renderer {
                 f16::from_f32(filter_params.p4.w()),
                 // 8
                 f16::from_f32(filter_params.ctrl as f32),
-                f16::default(),
-                f16::default(),
-                f16::default(),
+                f16::from_f32(color_0_tint.r()),
+                f16::from_f32(color_0_tint.g()),
+                f16::from_f32(color_0_tint.b()),
                 // 9
-                f16::default(),
-                f16::default(),
+                f16::from_f32(seamless),
+                f16::from_f32(sampling_flags),
                 f16::default(),
                 f16::default(),
             ]);
@@ -889,12 +1467,69 @@ impl Renderer {
     }
 
     fn finish_frame(&mut self) {
+        let resources = self.frame_resources;
+        self.frame_graph
+            .add_pass(CLEAR_DEST_PASS)
+            .write(resources.dest)
+            .execute(|renderer, _| renderer.core.clear_dest_if_needed());
+
+        let dest_size = self.intermediate_dest_texture().size;
+        for (index, user_pass) in self.user_passes.iter().enumerate() {
+            // Post-processing passes that don't overlap share one copy of the destination.
+            let source = if user_pass.copies_dest {
+                Some(self.frame_graph.create_texture(TransientTextureDescriptor {
+                    size: dest_size,
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                }))
+            } else {
+                None
+            };
+
+            let pass = self.frame_graph
+                           .add_pass(user_pass.name)
+                           .placement(user_pass.placement)
+                           .read(resources.dest)
+                           .read(resources.mask)
+                           .write(resources.dest);
+            match source {
+                None => pass.execute(move |renderer, _| renderer.run_user_pass(index, None)),
+                Some(source) => {
+                    pass.write(source).execute(move |renderer, pass_resources| {
+                        renderer.run_user_pass(index, Some(pass_resources.texture(source)));
+                    })
+                }
+            }
+        }
+
+        // Straight alpha is what the embedder asked for, so this comes last.
+        if self.core.options.alpha_mode == AlphaMode::Straight {
+            let copy = self.frame_graph.create_texture(TransientTextureDescriptor {
+                size: self.intermediate_dest_texture().size,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            });
+            self.frame_graph
+                .add_pass(STRAIGHT_ALPHA_PASS)
+                .read(resources.dest)
+                .write(copy)
+                .write(resources.dest)
+                .execute(move |renderer, pass_resources| {
+                    renderer.convert_dest_to_straight_alpha(pass_resources.texture(copy));
+                });
+        }
+
+        self.run_frame_graph();
+
         // The intermediate texture is ready for presentation.
         // The actual blit to screen surface is handled by WindowImpl::present_texture().
         // No additional work needed here.
     }
 
     pub fn end_scene(&mut self) {
+        // Run anything recorded after the last `Finish` command.
+        self.run_frame_graph();
+
         self.core.stats.gpu_bytes_allocated = self.core.allocator.bytes_allocated();
         self.core.stats.gpu_bytes_committed = self.core.allocator.bytes_committed();
 
@@ -923,6 +1558,7 @@ impl Renderer {
             }
         }
 
+        self.core.uploader.end_frame(&self.core.device);
         self.core.allocator.purge_if_needed();
     }
 
@@ -931,6 +1567,7 @@ impl Renderer {
         path_count: usize,
         bounding_quad: BoundingQuad,
         needs_readable_framebuffer: bool,
+        opaque_regions: &[RectF],
     ) {
         // match (&self.core.options.dest, self.core.mode.level) {
         //     (&DestFramebuffer::Other(_), _) => {
@@ -957,6 +1594,14 @@ impl Renderer {
 
         self.core.stats.path_count = path_count;
 
+        // If the embedder will cover the whole destination, there's no point clearing it.
+        let viewport_rect = RectI::new(Vector2I::zero(), self.core.draw_viewport().size());
+        self.core.dest_is_covered = opaque_regions.iter().any(|region| {
+            region.contains_rect(viewport_rect.to_f32())
+        });
+        self.core.dest_needs_clear = self.core.options.load_action == LoadAction::Clear &&
+            !self.core.dest_is_covered;
+
         // self.core.render_targets.clear();
     }
 
@@ -1009,3 +1654,49 @@ impl Renderer {
         self.core.draw_render_target()
     }
 }
+
+// Checks the uniform structs that the renderers write against the shaders that read them.
+fn check_uniform_blocks(resources: &dyn ResourceLoader) {
+    fn check<T>(resources: &dyn ResourceLoader, path: &str) where T: UniformBlock {
+        check_as::<T>(resources, path, T::WGSL_NAME)
+    }
+
+    fn check_as<T>(resources: &dyn ResourceLoader, path: &str, wgsl_name: &'static str)
+                   where T: UniformBlock {
+        let source = match resources.slurp(path) {
+            Ok(source) => source,
+            Err(_) => return,
+        };
+        let source = String::from_utf8_lossy(&source);
+        for error in uniform_block::check_uniform_block_as::<T>(&source, wgsl_name) {
+            warn!("uniform struct `{}` of {}: {}", wgsl_name, path, error);
+        }
+    }
+
+    check::<TransitionGlobals>(resources, "shaders/transition.wgsl");
+
+    #[cfg(feature = "d3d9")]
+    {
+        use crate::gpu::d3d9::custom::CUSTOM_TILE_PRELUDE_PATH;
+        use crate::gpu::d3d9::paint_server::{CHECKERBOARD_PAINT_PATH, STRIPE_PAINT_PATH};
+        use crate::gpu::d3d9::paint_server::{CheckerboardPaintUniforms, StripePaintUniforms};
+        use crate::gpu::d3d9::renderer::{FillGlobals, TileGlobals};
+        check::<FillGlobals>(resources, "shaders/d3d9/fill.wgsl");
+        check::<TileGlobals>(resources, "shaders/d3d9/tile.wgsl");
+        // Custom tile shaders are bound to the same globals as the built-in tile shader.
+        check_as::<TileGlobals>(resources, CUSTOM_TILE_PRELUDE_PATH, "CustomTileGlobals");
+        check::<CheckerboardPaintUniforms>(resources, CHECKERBOARD_PAINT_PATH);
+        check::<StripePaintUniforms>(resources, STRIPE_PAINT_PATH);
+    }
+    #[cfg(feature = "d3d11")]
+    check::<crate::gpu::d3d11::renderer::TileGlobals>(resources, "shaders/d3d11/tile.wgsl");
+}
+
+pub(crate) fn wgpu_color(color: ColorF) -> wgpu::Color {
+    wgpu::Color {
+        r: color.r() as f64,
+        g: color.g() as f64,
+        b: color.b() as f64,
+        a: color.a() as f64,
+    }
+}
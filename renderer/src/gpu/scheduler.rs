@@ -0,0 +1,151 @@
+// pathfinder/renderer/src/gpu/scheduler.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Shares one device and queue between the interactive scene and background renders, such as
+//! exports and thumbnails, without letting the background work hold up the viewport.
+//!
+//! Background scenes are built ahead of time, e.g. with a `BuildJob`, and queued on a
+//! `RenderScheduler` together with renderers of their own. Each frame, `render_frame()` renders
+//! the interactive scene first, and then renders the commands of queued background scenes, oldest
+//! first, until the frame's background time budget is spent. A background scene that doesn't fit
+//! in one frame picks up where it left off in the next.
+
+use crate::concurrent::build_job::BuiltScene;
+use crate::gpu::renderer::Renderer;
+use instant::Instant;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// The default CPU time spent on background renders each frame: a quarter of a 60 FPS frame.
+pub const DEFAULT_BACKGROUND_TIME_PER_FRAME: Duration = Duration::from_millis(4);
+
+/// Renders an interactive scene every frame, and background scenes in the time left over.
+pub struct RenderScheduler {
+    background_time_per_frame: Duration,
+    background_renders: VecDeque<BackgroundRender>,
+    next_background_render_id: u32,
+}
+
+/// Identifies a background render queued on a `RenderScheduler`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct BackgroundRenderId(pub u32);
+
+struct BackgroundRender {
+    id: BackgroundRenderId,
+    renderer: Renderer,
+    built_scene: BuiltScene,
+    next_command_index: usize,
+}
+
+impl RenderScheduler {
+    /// Creates a scheduler that spends at most about `background_time_per_frame` of CPU time on
+    /// background renders each frame.
+    #[inline]
+    pub fn new(background_time_per_frame: Duration) -> RenderScheduler {
+        RenderScheduler {
+            background_time_per_frame,
+            background_renders: VecDeque::new(),
+            next_background_render_id: 0,
+        }
+    }
+
+    /// Returns the CPU time that background renders may take each frame.
+    #[inline]
+    pub fn background_time_per_frame(&self) -> Duration {
+        self.background_time_per_frame
+    }
+
+    /// Changes the CPU time that background renders may take each frame.
+    #[inline]
+    pub fn set_background_time_per_frame(&mut self, new_time: Duration) {
+        self.background_time_per_frame = new_time
+    }
+
+    /// Queues a built scene to be rendered in the background with the given renderer, which
+    /// should be on the same device as the interactive one. The renderer is returned by
+    /// `render_frame()` once the scene has been rendered.
+    pub fn push_background(&mut self, renderer: Renderer, built_scene: BuiltScene)
+                           -> BackgroundRenderId {
+        let id = BackgroundRenderId(self.next_background_render_id);
+        self.next_background_render_id += 1;
+        self.background_renders.push_back(BackgroundRender {
+            id,
+            renderer,
+            built_scene,
+            next_command_index: 0,
+        });
+        id
+    }
+
+    /// Removes a background render from the queue, and returns its renderer, or `None` if it has
+    /// already finished.
+    ///
+    /// If the render had started, the renderer's destination holds a partly rendered scene.
+    pub fn cancel_background(&mut self, id: BackgroundRenderId) -> Option<Renderer> {
+        let index = self.background_renders.iter().position(|render| render.id == id)?;
+        let mut background_render = self.background_renders.remove(index)?;
+        if background_render.next_command_index > 0 {
+            background_render.renderer.end_scene();
+        }
+        Some(background_render.renderer)
+    }
+
+    /// Returns the number of background renders that haven't finished yet.
+    #[inline]
+    pub fn background_render_count(&self) -> usize {
+        self.background_renders.len()
+    }
+
+    /// Renders a frame: first the interactive scene, by calling `render_interactive`, and then
+    /// as much of the queued background work as fits in the background time budget.
+    ///
+    /// At least one command of background work is rendered each frame, so that background
+    /// renders always make progress. The budget counts CPU time spent encoding and submitting
+    /// commands, not GPU time. Returns the background renders that finished this frame, with
+    /// their renderers, whose destinations now hold the rendered scenes.
+    pub fn render_frame<F>(&mut self, render_interactive: F) -> Vec<(BackgroundRenderId, Renderer)>
+                           where F: FnOnce() {
+        render_interactive();
+
+        let start_time = Instant::now();
+        let mut finished_renders = vec![];
+        let mut rendered_any = false;
+        while let Some(background_render) = self.background_renders.front_mut() {
+            if rendered_any && Instant::now() - start_time >= self.background_time_per_frame {
+                break;
+            }
+
+            let commands = &background_render.built_scene.commands;
+            if background_render.next_command_index == 0 {
+                background_render.renderer.begin_scene();
+            }
+            if let Some(command) = commands.get(background_render.next_command_index) {
+                background_render.renderer.render_command(command);
+                background_render.next_command_index += 1;
+                rendered_any = true;
+            }
+            if background_render.next_command_index < commands.len() {
+                continue;
+            }
+
+            background_render.renderer.end_scene();
+            let background_render = self.background_renders.pop_front().unwrap();
+            finished_renders.push((background_render.id, background_render.renderer));
+        }
+        finished_renders
+    }
+}
+
+impl Default for RenderScheduler {
+    #[inline]
+    fn default() -> RenderScheduler {
+        RenderScheduler::new(DEFAULT_BACKGROUND_TIME_PER_FRAME)
+    }
+}
@@ -0,0 +1,209 @@
+// pathfinder/renderer/src/gpu/transition.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Cross-fades and wipes from one rendered scene to another, for slideshows and presentations.
+//!
+//! The scene being transitioned away from is copied aside with `Renderer::snapshot_scene()`.
+//! Each frame of the transition then renders the new scene as usual and calls
+//! `Renderer::draw_transition()`, which replaces the rendered frame with a blend of the snapshot
+//! and the new scene. The new scene may be animated, since it's rendered every frame.
+
+use pathfinder_geometry::vector::{Vector2F, Vector2I};
+use pathfinder_gpu::sampler::SamplerOptions;
+use pathfinder_gpu::{Device, Texture, uniform_block};
+use pathfinder_resources::ResourceLoader;
+use wgpu::util::DeviceExt;
+
+const TRANSITION_KIND_CROSS_FADE: i32 = 0;
+const TRANSITION_KIND_WIPE: i32 = 1;
+
+/// How one scene gives way to another.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Transition {
+    /// The old scene fades out as the new one fades in.
+    CrossFade,
+    /// The new scene is revealed behind an edge that sweeps across the frame.
+    Wipe {
+        /// The direction the edge moves in, with Y pointing down. For example, `vec2f(1.0, 0.0)`
+        /// wipes from left to right. Diagonal directions sweep from corner to corner.
+        direction: Vector2F,
+        /// How wide the edge's blend is, as a fraction of the distance the edge travels. Zero
+        /// makes a hard edge.
+        softness: f32,
+    },
+}
+
+/// A copy of a rendered scene, to transition away from.
+pub struct SceneSnapshot {
+    texture: Texture,
+}
+
+impl SceneSnapshot {
+    /// The size of the snapshot in pixels. Snapshots of a different size than the frame they're
+    /// blended into are stretched to fit.
+    #[inline]
+    pub fn size(&self) -> Vector2I {
+        self.texture.size
+    }
+}
+
+uniform_block! {
+    /// The globals of `transition.wgsl`.
+    pub(crate) struct TransitionGlobals: "Globals" {
+        uProgress: f32,
+        uKind: i32,
+        uSoftness: f32,
+        uPad0: f32,
+        uWipeDirection: [f32; 2],
+        uPad1: [f32; 2],
+    }
+}
+
+pub(crate) struct TransitionCompositor {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl TransitionCompositor {
+    pub(crate) fn new(device: &Device, resources: &dyn ResourceLoader) -> TransitionCompositor {
+        let path = "shaders/transition.wgsl";
+        let source = resources.slurp(path).expect("Failed to load shader");
+        let module = device.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(path),
+            source: wgpu::ShaderSource::Wgsl(String::from_utf8_lossy(&source).into()),
+        });
+
+        // Let wgpu derive the bind group layouts from the shader.
+        let pipeline = device.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Transition"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        TransitionCompositor { pipeline }
+    }
+
+    pub(crate) fn snapshot(&self, device: &Device, scene_texture: &Texture) -> SceneSnapshot {
+        let texture = device.create_texture(scene_texture.format,
+                                            scene_texture.size,
+                                            wgpu::TextureUsages::TEXTURE_BINDING |
+                                            wgpu::TextureUsages::COPY_DST);
+        let mut encoder = device.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Scene Snapshot Encoder"),
+        });
+        encoder.copy_texture_to_texture(scene_texture.texture.as_image_copy(),
+                                        texture.texture.as_image_copy(),
+                                        texture.texture.size());
+        device.queue.submit(Some(encoder.finish()));
+        SceneSnapshot { texture }
+    }
+
+    // Draws a blend of `from` and `to_view` over all of `dest_view`.
+    pub(crate) fn encode_draw(&self,
+                              device: &Device,
+                              encoder: &mut wgpu::CommandEncoder,
+                              from: &SceneSnapshot,
+                              to_view: &wgpu::TextureView,
+                              dest_view: &wgpu::TextureView,
+                              transition: Transition,
+                              progress: f32) {
+        let mut globals = TransitionGlobals {
+            uProgress: progress.max(0.0).min(1.0),
+            uKind: TRANSITION_KIND_CROSS_FADE,
+            uSoftness: 0.0,
+            uPad0: 0.0,
+            uWipeDirection: [1.0, 0.0],
+            uPad1: [0.0; 2],
+        };
+        if let Transition::Wipe { direction, softness } = transition {
+            let length = direction.length();
+            if length > 0.0 {
+                let direction = direction / length;
+                globals.uWipeDirection = [direction.x(), direction.y()];
+            }
+            globals.uKind = TRANSITION_KIND_WIPE;
+            globals.uSoftness = softness.max(0.0);
+        }
+
+        let globals_buffer = device.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Transition Globals"),
+            contents: bytemuck::cast_slice(&[globals]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let globals_bind_group = device.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Transition Globals BG"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: globals_buffer.as_entire_binding(),
+            }],
+        });
+
+        let sampler = device.sampler(SamplerOptions::linear());
+        let texture_bind_group = device.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Transition Textures BG"),
+            layout: &self.pipeline.get_bind_group_layout(1),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&from.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(to_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Transition Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: dest_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &globals_bind_group, &[]);
+        render_pass.set_bind_group(1, &texture_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
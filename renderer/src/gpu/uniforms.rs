@@ -0,0 +1,139 @@
+// pathfinder/renderer/src/gpu/uniforms.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A per-frame uniform buffer bound with dynamic offsets.
+//!
+//! Batches that only differ in their uniforms push them here and bind the same bind group with a
+//! different offset, instead of each creating a buffer and a bind group of their own. Dynamic
+//! offsets are available on every backend, unlike push constants.
+
+use pathfinder_gpu::Device;
+use std::marker::PhantomData;
+use std::mem;
+use std::num::NonZeroU64;
+
+const INITIAL_CAPACITY: u64 = 64 * 1024;
+
+/// A growable uniform buffer holding one `T` per batch, reset at the start of each scene.
+///
+/// The bind group layout has a single dynamic uniform buffer at binding 0. wgpu treats bind group
+/// layouts with identical entries as interchangeable, so `bind_group()` can be used with any
+/// pipeline whose group declares the same entry (see `dynamic_uniform_layout_entry()`).
+pub(crate) struct DynamicUniformBuffer<T> {
+    label: String,
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    capacity: u64,
+    cursor: u64,
+    stride: u64,
+    phantom: PhantomData<T>,
+}
+
+impl<T> DynamicUniformBuffer<T> where T: bytemuck::Pod {
+    pub(crate) fn new(device: &Device, label: &str) -> DynamicUniformBuffer<T> {
+        let alignment = device.device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = align_up(mem::size_of::<T>() as u64, alignment);
+        let layout = device.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &[dynamic_uniform_layout_entry(0)],
+        });
+        let capacity = align_up(INITIAL_CAPACITY, stride);
+        let (buffer, bind_group) = create_buffer::<T>(device, &layout, label, capacity);
+        DynamicUniformBuffer {
+            label: label.to_owned(),
+            layout,
+            buffer,
+            bind_group,
+            capacity,
+            cursor: 0,
+            stride,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Forgets all values pushed so far. Call once the work that reads them has been submitted.
+    #[inline]
+    pub(crate) fn reset(&mut self) {
+        self.cursor = 0;
+    }
+
+    /// Writes `value` to the buffer and returns the dynamic offset to bind it at.
+    ///
+    /// If the buffer is full, it's replaced with one twice the size. Work already submitted keeps
+    /// the old buffer alive, so this is only safe between submissions, which is how the D3D9
+    /// renderer draws.
+    pub(crate) fn push(&mut self, device: &Device, value: &T) -> u32 {
+        if self.cursor + self.stride > self.capacity {
+            self.capacity *= 2;
+            let (buffer, bind_group) =
+                create_buffer::<T>(device, &self.layout, &self.label, self.capacity);
+            self.buffer = buffer;
+            self.bind_group = bind_group;
+            self.cursor = 0;
+        }
+
+        let offset = self.cursor;
+        device.queue.write_buffer(&self.buffer, offset, bytemuck::bytes_of(value));
+        self.cursor += self.stride;
+        offset as u32
+    }
+
+    /// The bind group to pass to `set_bind_group()` along with an offset from `push()`.
+    #[inline]
+    pub(crate) fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+/// A layout entry for a uniform buffer bound with a dynamic offset.
+pub(crate) fn dynamic_uniform_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: true,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn create_buffer<T>(device: &Device,
+                    layout: &wgpu::BindGroupLayout,
+                    label: &str,
+                    capacity: u64)
+                    -> (wgpu::Buffer, wgpu::BindGroup) {
+    let buffer = device.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: capacity,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = device.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &buffer,
+                offset: 0,
+                size: NonZeroU64::new(mem::size_of::<T>() as u64),
+            }),
+        }],
+    });
+    (buffer, bind_group)
+}
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
@@ -11,15 +11,17 @@
 //! Packed data ready to be sent to the GPU.
 
 use crate::builder::{ALPHA_TILES_PER_LEVEL, ALPHA_TILE_LEVEL_COUNT};
-use crate::options::BoundingQuad;
+use crate::gpu::options::RendererLevel;
+use crate::gpu::perf::FrameStats;
+use crate::options::{BoundingQuad, Degradation};
 use crate::paint::PaintCompositeOp;
-use crate::scene::PathId;
+use crate::scene::{CoverageCurve, PathId};
 use crate::tile_map::DenseTileMap;
 use pathfinder_color::ColorU;
 use pathfinder_content::effects::{BlendMode, Filter};
 use pathfinder_content::render_target::RenderTargetId;
 use pathfinder_geometry::line_segment::{LineSegment2F, LineSegmentU16};
-use pathfinder_geometry::rect::RectI;
+use pathfinder_geometry::rect::{RectF, RectI};
 use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::vector::{Vector2F, Vector2I};
 use pathfinder_gpu::TextureSamplingFlags;
@@ -34,6 +36,12 @@ pub const TILE_CTRL_MASK_EVEN_ODD: i32 = 0x2;
 
 pub const TILE_CTRL_MASK_0_SHIFT: i32 = 0;
 
+// Doubles edge coverage, so that adjacent paths leave no seam.
+pub const TILE_CTRL_WATERTIGHT: i32 = 0x4;
+
+// Rounds edge coverage to none or full, for paths drawn without antialiasing.
+pub const TILE_CTRL_ALIASED: i32 = 0x8;
+
 pub enum RenderCommand {
     // Starts rendering a frame.
     Start {
@@ -48,6 +56,17 @@ pub enum RenderCommand {
         /// This is needed if a path that renders directly to the output framebuffer (i.e. not to a
         /// render target) uses one of the more exotic blend modes.
         needs_readable_framebuffer: bool,
+
+        /// Whole-pixel rectangles that the embedder will cover with opaque content after
+        /// rendering, from `BuildOptions::opaque_regions`.
+        opaque_regions: Vec<RectF>,
+
+        /// The level the scene was built at. A D3D11-level renderer may be sent scenes tiled on
+        /// the CPU, at the D3D9 level, per `BuildOptions::tiling_mode`.
+        renderer_level: RendererLevel,
+
+        /// How antialiased edge coverage maps to alpha, from `Scene::set_coverage_curve()`.
+        coverage_curve: CoverageCurve,
     },
 
     // Allocates a texture page.
@@ -112,6 +131,10 @@ pub enum RenderCommand {
     // Presents a rendered frame.
     Finish {
         cpu_build_time: Duration,
+        // How quality was reduced to fit the memory budget.
+        degradation: Degradation,
+        // How long the stages of building took. The renderer fills in the encoding time.
+        frame_stats: FrameStats,
     },
 }
 
@@ -176,6 +199,9 @@ pub struct PrepareTilesInfoD3D11 {
 
     /// A transform to apply to the segments.
     pub transform: Transform2F,
+
+    /// The maximum distance, in device pixels, between a curve and the microlines it's diced into.
+    pub flattening_tolerance: f32,
 }
 
 #[derive(Clone, Debug)]
@@ -245,6 +271,24 @@ pub struct DrawTileBatchD3D9 {
     pub filter: Filter,
     /// The blend mode to composite these tiles with.
     pub blend_mode: BlendMode,
+    /// The rectangle, in device pixels, to restrict drawing to, if a rectangular clip applies.
+    pub scissor_rect: Option<RectI>,
+    /// The rounded rectangle to clip to, if one applies.
+    pub rounded_clip: Option<RoundedRectClip>,
+    /// The rectangle, in device pixels, that the batch's ellipse is inscribed in, if its path is
+    /// an ellipse. The tiles cover the pixels around the ellipse, and the tile shader evaluates
+    /// its edge analytically.
+    pub ellipse_rect: Option<RectF>,
+}
+
+/// A rounded rectangle clip, which the D3D9 tile shaders evaluate analytically instead of drawing
+/// clip tiles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoundedRectClip {
+    /// The rectangle, in device pixels.
+    pub rect: RectF,
+    /// The radius of the corners, in device pixels.
+    pub radius: f32,
 }
 
 /// Information needed to draw a batch of tiles in D3D11.
@@ -341,14 +385,20 @@ pub struct DiceMetadataD3D11 {
     pub global_path_id: PathId,
     pub first_global_segment_index: u32,
     pub first_batch_segment_index: u32,
-    pub pad: u32,
+    /// The factor that the batch's flattening tolerance is multiplied by for this path.
+    pub tolerance_scale: f32,
 }
 
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct TextureMetadataEntry {
     pub color_0_transform: Transform2F,
+    /// How the shader wraps and filters the color texture itself, for seamless patterns, or
+    /// `None` if the sampler does.
+    pub color_0_sampling: Option<TextureSamplingFlags>,
     pub color_0_combine_mode: ColorCombineMode,
+    /// Multiplies the color texture's RGB. The base color has already been multiplied.
+    pub color_0_tint: ColorU,
     pub base_color: ColorU,
     pub filter: Filter,
     pub blend_mode: BlendMode,
@@ -562,7 +612,7 @@ impl Debug for RenderCommand {
                     batch.tile_batch_data.batch_id, batch.color_texture
                 )
             }
-            RenderCommand::Finish { cpu_build_time } => {
+            RenderCommand::Finish { cpu_build_time, .. } => {
                 write!(
                     formatter,
                     "Finish({} ms)",
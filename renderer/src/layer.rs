@@ -0,0 +1,133 @@
+// pathfinder/renderer/src/layer.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Named groups of paths that can be reordered, shown, hidden, and faded after they're drawn.
+//!
+//! Paths drawn between `Scene::push_layer()` and `Scene::pop_layer()` belong to that layer. Each
+//! layer keeps its own list of display items, and the scene's display list holds a slot for each
+//! layer, in z-order. Reordering or hiding layers rebuilds only the display list from those slots,
+//! and changing a layer's opacity only rewrites the tints of its paths, so neither touches the
+//! paths themselves and nothing has to be drawn again by the front end.
+
+use crate::scene::{DisplayItem, DrawPathId};
+
+/// Identifies a layer within a scene.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct LayerId(pub u32);
+
+#[derive(Clone, Debug)]
+pub(crate) struct Layer {
+    pub(crate) name: String,
+    pub(crate) display_list: Vec<DisplayItem>,
+    pub(crate) visible: bool,
+    pub(crate) opacity: f32,
+    // The tint alphas of the layer's draw paths at full opacity, in drawing order.
+    pub(crate) tint_alphas: Vec<u8>,
+}
+
+impl Layer {
+    pub(crate) fn new(name: String) -> Layer {
+        Layer { name, display_list: vec![], visible: true, opacity: 1.0, tint_alphas: vec![] }
+    }
+
+    // Returns the IDs of the draw paths in this layer, in drawing order.
+    pub(crate) fn draw_path_ids<'a>(&'a self) -> impl Iterator<Item = DrawPathId> + 'a {
+        self.display_list.iter().flat_map(|display_item| {
+            let range = match *display_item {
+                DisplayItem::DrawPaths(ref range) => range.start.0..range.end.0,
+                DisplayItem::PushRenderTarget(_) | DisplayItem::PopRenderTarget => 0..0,
+            };
+            range.map(DrawPathId)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LayerId;
+    use crate::paint::{Paint, PaintId};
+    use crate::scene::{DisplayItem, DrawPath, DrawPathId, Scene};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+
+    fn push_square(scene: &mut Scene, paint: PaintId, x: f32, alpha: u8) -> DrawPathId {
+        let square = Outline::from_rect(RectF::new(vec2f(x, 0.0), vec2f(10.0, 10.0)));
+        let mut draw_path = DrawPath::new(square, paint);
+        draw_path.set_tint(ColorU::new(255, 255, 255, alpha));
+        scene.push_draw_path(draw_path)
+    }
+
+    fn draw_path_ranges(scene: &Scene) -> Vec<(u32, u32)> {
+        scene.display_list().iter().map(|display_item| {
+            match *display_item {
+                DisplayItem::DrawPaths(ref range) => (range.start.0, range.end.0),
+                _ => panic!("Unexpected display item!"),
+            }
+        }).collect()
+    }
+
+    fn layered_scene() -> (Scene, LayerId, LayerId) {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        push_square(&mut scene, paint, 0.0, 255);
+        let background = scene.push_layer("background".to_owned());
+        push_square(&mut scene, paint, 10.0, 255);
+        push_square(&mut scene, paint, 20.0, 128);
+        scene.pop_layer();
+        let foreground = scene.push_layer("foreground".to_owned());
+        push_square(&mut scene, paint, 30.0, 255);
+        scene.pop_layer();
+        push_square(&mut scene, paint, 40.0, 255);
+        (scene, background, foreground)
+    }
+
+    #[test]
+    fn test_layers_are_named_and_kept_apart() {
+        let (scene, background, foreground) = layered_scene();
+        assert_eq!(scene.layer_id("foreground"), Some(foreground));
+        assert_eq!(scene.layer_id("midground"), None);
+        assert_eq!(scene.layer_name(background), "background");
+        assert_eq!(scene.layer_order(), &[background, foreground]);
+        assert_eq!(draw_path_ranges(&scene), vec![(0, 1), (1, 3), (3, 4), (4, 5)]);
+    }
+
+    #[test]
+    fn test_layers_can_be_reordered_and_hidden() {
+        let (mut scene, background, foreground) = layered_scene();
+        scene.move_layer(foreground, 0);
+        assert_eq!(scene.layer_order(), &[foreground, background]);
+        assert_eq!(draw_path_ranges(&scene), vec![(0, 1), (3, 4), (1, 3), (4, 5)]);
+
+        scene.set_layer_visible(foreground, false);
+        assert!(!scene.layer_visible(foreground));
+        assert_eq!(draw_path_ranges(&scene), vec![(0, 1), (1, 3), (4, 5)]);
+
+        scene.set_layer_order(vec![background, foreground]);
+        scene.set_layer_visible(foreground, true);
+        assert_eq!(draw_path_ranges(&scene), vec![(0, 1), (1, 3), (3, 4), (4, 5)]);
+    }
+
+    #[test]
+    fn test_layer_opacity_scales_tints() {
+        let (mut scene, background, _) = layered_scene();
+        scene.set_layer_opacity(background, 0.5);
+        assert_eq!(scene.layer_opacity(background), 0.5);
+        assert_eq!(scene.get_draw_path(DrawPathId(0)).tint.a, 255);
+        assert_eq!(scene.get_draw_path(DrawPathId(1)).tint.a, 128);
+        assert_eq!(scene.get_draw_path(DrawPathId(2)).tint.a, 64);
+        assert_eq!(scene.get_draw_path(DrawPathId(3)).tint.a, 255);
+
+        scene.set_layer_opacity(background, 1.0);
+        assert_eq!(scene.get_draw_path(DrawPathId(1)).tint.a, 255);
+        assert_eq!(scene.get_draw_path(DrawPathId(2)).tint.a, 128);
+    }
+}
@@ -17,15 +17,27 @@ extern crate bitflags;
 #[macro_use]
 extern crate log;
 
+pub mod accessibility;
+pub mod animation;
+pub mod brush;
 pub mod concurrent;
+pub mod flattened;
+#[cfg(feature="fuzzing")]
+pub mod fuzzing;
 pub mod gpu;
+pub mod layer;
+#[cfg(feature="lyon")]
+pub mod lyon;
+pub mod marker;
 pub mod options;
 pub mod paint;
 pub mod scene;
+pub mod symbol;
 
 mod allocator;
 mod builder;
 mod gpu_data;
+mod occlusion;
 mod tile_map;
 mod tiler;
 mod tiles;
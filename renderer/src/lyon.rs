@@ -0,0 +1,92 @@
+// pathfinder/renderer/src/lyon.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversions between scene paths and `lyon_path` paths.
+//!
+//! Outline-level conversions live in `pathfinder_content::lyon`; these build on them so that
+//! paths can move between a scene and lyon's tooling along with their fill rules.
+
+use crate::paint::PaintId;
+use crate::scene::{ClipPath, DrawPath, DrawPathId, Scene};
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::lyon::lyon_path::{FillRule as LyonFillRule, Path};
+use pathfinder_content::outline::Outline;
+
+impl DrawPath {
+    /// Creates a draw path from a lyon path, filled with the given paint using the winding rule.
+    #[inline]
+    pub fn from_lyon_path(path: &Path, paint: PaintId) -> DrawPath {
+        DrawPath::new(Outline::from(path), paint)
+    }
+
+    /// Returns the outline of this path as a lyon path.
+    #[inline]
+    pub fn to_lyon_path(&self) -> Path {
+        Path::from(&self.outline)
+    }
+
+    /// Returns the fill rule of this path in lyon's terms, e.g. for tessellation.
+    #[inline]
+    pub fn lyon_fill_rule(&self) -> LyonFillRule {
+        to_lyon_fill_rule(self.fill_rule)
+    }
+}
+
+impl ClipPath {
+    /// Creates a clip path from a lyon path, using the winding rule.
+    #[inline]
+    pub fn from_lyon_path(path: &Path) -> ClipPath {
+        ClipPath::new(Outline::from(path))
+    }
+
+    /// Returns the outline of this clip path as a lyon path.
+    #[inline]
+    pub fn to_lyon_path(&self) -> Path {
+        Path::from(&self.outline)
+    }
+
+    /// Returns the fill rule of this clip path in lyon's terms.
+    #[inline]
+    pub fn lyon_fill_rule(&self) -> LyonFillRule {
+        to_lyon_fill_rule(self.fill_rule)
+    }
+}
+
+impl Scene {
+    /// Adds a lyon path to the scene, filled with the given paint and fill rule.
+    pub fn push_lyon_path(&mut self, path: &Path, fill_rule: FillRule, paint: PaintId)
+                          -> DrawPathId {
+        let mut draw_path = DrawPath::from_lyon_path(path, paint);
+        draw_path.set_fill_rule(fill_rule);
+        self.push_draw_path(draw_path)
+    }
+
+    /// Returns an iterator over every draw path in the scene as a lyon path, with its ID.
+    pub fn lyon_draw_paths(&self) -> impl Iterator<Item = (DrawPathId, Path)> + '_ {
+        (0..self.draw_path_count()).map(move |index| {
+            let draw_path_id = DrawPathId(index);
+            (draw_path_id, self.get_draw_path(draw_path_id).to_lyon_path())
+        })
+    }
+}
+
+impl From<&Path> for ClipPath {
+    #[inline]
+    fn from(path: &Path) -> ClipPath {
+        ClipPath::from_lyon_path(path)
+    }
+}
+
+fn to_lyon_fill_rule(fill_rule: FillRule) -> LyonFillRule {
+    match fill_rule {
+        FillRule::Winding => LyonFillRule::NonZero,
+        FillRule::EvenOdd => LyonFillRule::EvenOdd,
+    }
+}
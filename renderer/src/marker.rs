@@ -0,0 +1,257 @@
+// pathfinder/renderer/src/marker.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Symbols placed at the vertices of a path, like SVG markers.
+//!
+//! `Scene::push_path_markers()` instances a symbol at the first vertex of a path, another at the
+//! last, and another at every vertex in between, each optionally turned to follow the path. As in
+//! SVG, the vertices are the ends of every line and curve, a closed contour ends where it starts,
+//! and a marker that follows the path points along the bisector of the directions into and out of
+//! its vertex.
+
+use crate::symbol::{SymbolId, SymbolInstance};
+use pathfinder_color::ColorU;
+use pathfinder_content::outline::{ContourIterFlags, Outline};
+use pathfinder_content::segment::Segment;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::unit_vector::UnitVector;
+use pathfinder_geometry::vector::Vector2F;
+
+const EPSILON: f32 = 0.0001;
+
+/// The markers to place along a path. Each is optional.
+#[derive(Clone, Debug, Default)]
+pub struct PathMarkers {
+    /// The marker at the first vertex of the path.
+    pub start: Option<Marker>,
+    /// The marker at every vertex other than the first and last.
+    pub mid: Option<Marker>,
+    /// The marker at the last vertex of the path.
+    pub end: Option<Marker>,
+}
+
+/// A symbol to place at vertices of a path.
+#[derive(Clone, Copy, Debug)]
+pub struct Marker {
+    /// The symbol to draw, defined with `Scene::define_symbol()`.
+    pub symbol: SymbolId,
+    /// The point in symbol space that's placed on the vertex, like SVG's `refX` and `refY`.
+    pub anchor: Vector2F,
+    /// How the symbol is rotated.
+    pub orientation: MarkerOrientation,
+    /// A scale applied to the symbol, such as the stroke width for markers sized relative to it.
+    pub scale: f32,
+    /// A color that the marker is multiplied by.
+    pub tint: ColorU,
+}
+
+/// How markers are rotated.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MarkerOrientation {
+    /// Rotated by a fixed angle, in radians, whatever the direction of the path.
+    Angle(f32),
+    /// Rotated so that the symbol's positive X axis follows the path.
+    Auto,
+    /// Like `Auto`, but a start marker points backward, so that one arrowhead symbol can serve at
+    /// both ends.
+    AutoStartReverse,
+}
+
+impl Marker {
+    /// Creates a marker that places the symbol's origin on each vertex, follows the path, and is
+    /// neither scaled nor tinted.
+    #[inline]
+    pub fn new(symbol: SymbolId) -> Marker {
+        Marker {
+            symbol,
+            anchor: Vector2F::zero(),
+            orientation: MarkerOrientation::Auto,
+            scale: 1.0,
+            tint: ColorU::white(),
+        }
+    }
+}
+
+// A vertex of a path, with the directions of the path into and out of it.
+struct Vertex {
+    position: Vector2F,
+    incoming: Option<Vector2F>,
+    outgoing: Option<Vector2F>,
+}
+
+impl Vertex {
+    // The unit direction a marker following the path points in here.
+    fn direction(&self) -> Vector2F {
+        match (self.incoming, self.outgoing) {
+            (Some(incoming), Some(outgoing)) => {
+                let bisector = incoming + outgoing;
+                if bisector.square_length() < EPSILON { outgoing } else { bisector.normalize() }
+            }
+            (Some(direction), None) | (None, Some(direction)) => direction,
+            (None, None) => Vector2F::new(1.0, 0.0),
+        }
+    }
+}
+
+/// Returns the symbol instances that draw `markers` along `outline`, in path order.
+pub(crate) fn marker_instances(outline: &Outline, markers: &PathMarkers)
+                               -> Vec<(SymbolId, SymbolInstance)> {
+    let vertices = vertices(outline);
+    let mut instances = vec![];
+    for (vertex_index, vertex) in vertices.iter().enumerate() {
+        let (marker, is_start) = if vertex_index == 0 {
+            (&markers.start, true)
+        } else if vertex_index + 1 == vertices.len() {
+            (&markers.end, false)
+        } else {
+            (&markers.mid, false)
+        };
+        let marker = match *marker {
+            None => continue,
+            Some(ref marker) => marker,
+        };
+
+        let rotation = match marker.orientation {
+            MarkerOrientation::Angle(angle) => Transform2F::from_rotation(angle),
+            MarkerOrientation::Auto | MarkerOrientation::AutoStartReverse => {
+                let mut direction = vertex.direction();
+                if is_start && marker.orientation == MarkerOrientation::AutoStartReverse {
+                    direction = -direction;
+                }
+                Transform2F::from_rotation_vector(UnitVector(direction))
+            }
+        };
+
+        let transform = Transform2F::from_translation(vertex.position) *
+            rotation *
+            Transform2F::from_scale(marker.scale) *
+            Transform2F::from_translation(-marker.anchor);
+        let mut instance = SymbolInstance::new(transform);
+        instance.set_tint(marker.tint);
+        instances.push((marker.symbol, instance));
+    }
+    instances
+}
+
+fn vertices(outline: &Outline) -> Vec<Vertex> {
+    let mut vertices = vec![];
+    for contour in outline.contours() {
+        let segments: Vec<Segment> = contour.iter(ContourIterFlags::empty())
+                                            .filter(|segment| !segment.is_tiny())
+                                            .collect();
+        let (first_segment, last_segment) = match (segments.first(), segments.last()) {
+            (Some(first_segment), Some(last_segment)) => (first_segment, last_segment),
+            _ => {
+                // A lone point is still a vertex.
+                if let Some(position) = contour.first_position() {
+                    vertices.push(Vertex { position, incoming: None, outgoing: None });
+                }
+                continue;
+            }
+        };
+
+        let closed = contour.is_closed();
+        for (segment_index, segment) in segments.iter().enumerate() {
+            let incoming = if segment_index > 0 {
+                Some(end_direction(&segments[segment_index - 1]))
+            } else if closed {
+                Some(end_direction(last_segment))
+            } else {
+                None
+            };
+            vertices.push(Vertex {
+                position: segment.baseline.from(),
+                incoming,
+                outgoing: Some(start_direction(segment)),
+            });
+        }
+        vertices.push(Vertex {
+            position: last_segment.baseline.to(),
+            incoming: Some(end_direction(last_segment)),
+            outgoing: if closed { Some(start_direction(first_segment)) } else { None },
+        });
+    }
+    vertices
+}
+
+// The unit tangent at the start of a segment. Control points that coincide with the endpoint are
+// skipped.
+fn start_direction(segment: &Segment) -> Vector2F {
+    let mut candidates = vec![];
+    if !segment.is_line() {
+        let cubic = segment.to_cubic();
+        candidates.push(cubic.ctrl.from());
+        candidates.push(cubic.ctrl.to());
+    }
+    candidates.push(segment.baseline.to());
+    direction_to_first_distinct(segment.baseline.from(), &candidates)
+}
+
+// The unit tangent at the end of a segment.
+fn end_direction(segment: &Segment) -> Vector2F {
+    let mut candidates = vec![];
+    if !segment.is_line() {
+        let cubic = segment.to_cubic();
+        candidates.push(cubic.ctrl.to());
+        candidates.push(cubic.ctrl.from());
+    }
+    candidates.push(segment.baseline.from());
+    -direction_to_first_distinct(segment.baseline.to(), &candidates)
+}
+
+fn direction_to_first_distinct(from: Vector2F, candidates: &[Vector2F]) -> Vector2F {
+    for &candidate in candidates {
+        let vector = candidate - from;
+        if vector.square_length() >= EPSILON {
+            return vector.normalize();
+        }
+    }
+    Vector2F::new(1.0, 0.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Marker, MarkerOrientation, PathMarkers, marker_instances};
+    use crate::symbol::SymbolId;
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_geometry::vector::{Vector2F, vec2f};
+
+    fn assert_near(a: Vector2F, b: Vector2F) {
+        assert!((a - b).length() < 0.0001, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_markers_follow_path() {
+        // Right along the top, then down.
+        let mut contour = Contour::new();
+        contour.push_endpoint(vec2f(0.0, 0.0));
+        contour.push_endpoint(vec2f(10.0, 0.0));
+        contour.push_endpoint(vec2f(10.0, 10.0));
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let arrow = Marker::new(SymbolId(0));
+        let mut start = Marker::new(SymbolId(1));
+        start.orientation = MarkerOrientation::AutoStartReverse;
+        let markers = PathMarkers { start: Some(start), mid: Some(arrow), end: Some(arrow) };
+        let instances = marker_instances(&outline, &markers);
+        assert_eq!(instances.len(), 3);
+        assert_eq!(instances[0].0, SymbolId(1));
+
+        // Each marker's X axis points along the path; at the corner, halfway between both legs.
+        let x_axes: Vec<Vector2F> = instances.iter().map(|&(_, ref instance)| {
+            instance.transform * vec2f(1.0, 0.0) - instance.transform * Vector2F::zero()
+        }).collect();
+        assert_near(x_axes[0], vec2f(-1.0, 0.0));
+        assert_near(x_axes[1], vec2f(1.0, 1.0).normalize());
+        assert_near(x_axes[2], vec2f(0.0, 1.0));
+        assert_near(instances[2].1.transform * Vector2F::zero(), vec2f(10.0, 10.0));
+    }
+}
@@ -0,0 +1,193 @@
+// pathfinder/renderer/src/occlusion.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conservative CPU occlusion culling of draw paths.
+//!
+//! Scenes made of stacked layers, such as the pages of a document, often draw whole paths that
+//! later opaque rectangles cover completely. Such paths can't affect the output, so the builder
+//! skips tiling them. The test is deliberately simple: only unclipped, opaque, axis-aligned
+//! rectangles occlude, and a path is hidden only if its bounds lie entirely within the pixels one
+//! of them covers fully.
+//!
+//! The embedder's opaque regions from `BuildOptions::opaque_regions` occlude everything drawn to
+//! the destination framebuffer, as if they were drawn last.
+
+use crate::options::{PreparedBuildOptions, PreparedRenderTransform};
+use crate::paint::PaintMetadata;
+use crate::scene::{DisplayItem, DrawPath, DrawPathId, Scene};
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::Vector2F;
+use std::ops::Range;
+
+// The number of most recent occluders tested against each path, which bounds the cost of the
+// pass on scenes with many rectangles.
+const MAX_OCCLUDERS: usize = 16;
+
+/// Returns, for each draw path in the scene, whether later paths are known to hide it completely.
+pub(crate) fn find_hidden_draw_paths(scene: &Scene,
+                                     built_options: &PreparedBuildOptions,
+                                     paint_metadata: &[PaintMetadata])
+                                     -> Vec<bool> {
+    let mut hidden = vec![false; scene.draw_paths().len()];
+
+    // Dilation and subpixel AA widen coverage past the outline, and perspective makes rectangles
+    // non-rectangular; don't bother with any of them.
+    if built_options.subpixel_aa_enabled || !built_options.dilation.is_zero() {
+        return hidden;
+    }
+    let transform = match built_options.transform {
+        PreparedRenderTransform::None => Transform2F::default(),
+        PreparedRenderTransform::Transform2D(transform) => transform,
+        PreparedRenderTransform::Perspective { .. } => return hidden,
+    };
+
+    // Paths only occlude paths drawn to the same render target. Each push starts a new target,
+    // while the target below it keeps its contents.
+    let mut layers: Vec<Vec<Range<DrawPathId>>> = vec![vec![]];
+    let mut layer_stack = vec![0];
+    for display_item in scene.display_list() {
+        match *display_item {
+            DisplayItem::PushRenderTarget(_) => {
+                layer_stack.push(layers.len());
+                layers.push(vec![]);
+            }
+            DisplayItem::PopRenderTarget => {
+                layer_stack.pop();
+            }
+            DisplayItem::DrawPaths(ref draw_path_id_range) => {
+                let layer_index = *layer_stack.last().expect("Popped too many render targets!");
+                layers[layer_index].push(draw_path_id_range.clone());
+            }
+        }
+    }
+
+    for (layer_index, layer) in layers.iter().enumerate() {
+        // The embedder's regions don't count towards the limit, since they're few and fixed.
+        let mut occluders: Vec<RectF> = vec![];
+        let embedder_occluders: &[RectF] = if layer_index == 0 {
+            &built_options.opaque_regions
+        } else {
+            &[]
+        };
+        for draw_path_id_range in layer.iter().rev() {
+            for draw_path_id in (draw_path_id_range.start.0..draw_path_id_range.end.0).rev() {
+                let draw_path = scene.get_draw_path(DrawPathId(draw_path_id));
+                let bounds = (transform * draw_path.outline().bounds()).round_out();
+                if occluders.iter().chain(embedder_occluders.iter())
+                                    .any(|occluder| occluder.contains_rect(bounds)) {
+                    hidden[draw_path_id as usize] = true;
+                    continue;
+                }
+
+                if occluders.len() < MAX_OCCLUDERS && is_opaque(draw_path, paint_metadata) {
+                    if let Some(rect) = covered_rect(draw_path.outline(), &transform) {
+                        occluders.push(rect);
+                    }
+                }
+            }
+        }
+    }
+
+    hidden
+}
+
+fn is_opaque(draw_path: &DrawPath, paint_metadata: &[PaintMetadata]) -> bool {
+    draw_path.clip_path().is_none() &&
+        draw_path.blend_mode().occludes_backdrop() &&
+        draw_path.tint.is_opaque() &&
+        paint_metadata[draw_path.paint().0 as usize].is_opaque
+}
+
+/// If `outline`, transformed, is a single axis-aligned rectangle, returns the pixels it covers
+/// fully.
+fn covered_rect(outline: &Outline, transform: &Transform2F) -> Option<RectF> {
+    let bounds = transformed_rect(outline, transform)?;
+    let covered = RectF::from_points(bounds.origin().ceil(), bounds.lower_right().floor());
+    if covered.width() <= 0.0 || covered.height() <= 0.0 {
+        return None;
+    }
+    Some(covered)
+}
+
+/// If `outline`, transformed, is a single axis-aligned rectangle, returns that rectangle.
+pub(crate) fn transformed_rect(outline: &Outline, transform: &Transform2F) -> Option<RectF> {
+    let contour = match *outline.contours() {
+        [ref contour] => contour,
+        _ => return None,
+    };
+    let points = transformed_rect_corners(contour, transform)?;
+    Some(RectF::from_points(points[0].min(points[2]), points[0].max(points[2])))
+}
+
+/// If `contour`, transformed, is an axis-aligned rectangle, returns its corners in contour order.
+pub(crate) fn transformed_rect_corners(contour: &Contour, transform: &Transform2F)
+                                       -> Option<[Vector2F; 4]> {
+    // Accept four corners, optionally with the first repeated at the end.
+    let mut point_count = contour.len();
+    if point_count == 5 && contour.position_of(0) == contour.position_of(4) {
+        point_count = 4;
+    }
+    if point_count != 4 {
+        return None;
+    }
+
+    let mut points = [Vector2F::zero(); 4];
+    for (point_index, point) in points.iter_mut().enumerate() {
+        if !contour.point_is_endpoint(point_index as u32) {
+            return None;
+        }
+        *point = *transform * contour.position_of(point_index as u32);
+    }
+
+    // Every edge must be either horizontal or vertical, alternating between the two, which leaves
+    // only rectangles.
+    let first_edge_is_horizontal = points[0].y() == points[1].y();
+    for edge_index in 0..4 {
+        let (from, to) = (points[edge_index], points[(edge_index + 1) % 4]);
+        let (horizontal, vertical) = (from.y() == to.y(), from.x() == to.x());
+        let should_be_horizontal = first_edge_is_horizontal == (edge_index % 2 == 0);
+        if horizontal == vertical || horizontal != should_be_horizontal {
+            return None;
+        }
+    }
+
+    Some(points)
+}
+
+#[cfg(test)]
+mod test {
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::vec2f;
+
+    use super::covered_rect;
+
+    #[test]
+    fn test_covered_rect_shrinks_to_whole_pixels() {
+        let outline = Outline::from_rect(RectF::new(vec2f(0.5, 1.0), vec2f(10.0, 4.25)));
+        let covered = covered_rect(&outline, &Transform2F::default());
+        assert_eq!(covered, Some(RectF::from_points(vec2f(1.0, 1.0), vec2f(10.0, 5.0))));
+
+        let rotated = Transform2F::from_rotation(0.1);
+        assert_eq!(covered_rect(&outline, &rotated), None);
+
+        let mut contour = Contour::new();
+        for &(x, y) in &[(0.0, 0.0), (8.0, 8.0), (8.0, 0.0), (0.0, 8.0)] {
+            contour.push_endpoint(vec2f(x, y));
+        }
+        contour.close();
+        let mut bowtie = Outline::new();
+        bowtie.push_contour(contour);
+        assert_eq!(covered_rect(&bowtie, &Transform2F::default()), None);
+    }
+}
@@ -10,6 +10,7 @@
 
 //! Options that control how rendering is to be performed.
 
+use crate::concurrent::build_job::CancellationToken;
 use crate::gpu::options::RendererLevel;
 use crate::gpu_data::RenderCommand;
 use pathfinder_geometry::rect::RectF;
@@ -20,6 +21,24 @@ use pathfinder_geometry::vector::{Vector2F, Vector4F};
 #[allow(deprecated)]
 use pathfinder_content::clip::PolygonClipper3D;
 
+/// The default maximum distance, in device pixels, between a curve and the lines it's flattened
+/// into.
+pub const DEFAULT_FLATTENING_TOLERANCE: f32 = 0.25;
+
+/// How much `BuildOptions::thumbnail()` scales scenes down: thumbnails are rendered at half the
+/// resolution of the full-quality frame.
+pub const THUMBNAIL_SCALE: f32 = 0.5;
+
+/// The flattening tolerance of `BuildOptions::thumbnail()`, in device pixels of the thumbnail.
+pub const THUMBNAIL_FLATTENING_TOLERANCE: f32 = 1.0;
+
+// Smaller tolerances would only produce more lines than there are pixels to put them in.
+const MIN_FLATTENING_TOLERANCE: f32 = 1.0 / 64.0;
+
+// The largest scenes that `TilingMode::auto()` tiles on the CPU.
+const DEFAULT_MAX_CPU_PATH_COUNT: usize = 512;
+const DEFAULT_MAX_CPU_SEGMENT_COUNT: usize = 32768;
+
 /// A sink for the render commands that scenes build.
 /// 
 /// In single-threaded operation, this object typically buffers commands into an array and then,
@@ -49,7 +68,7 @@ impl<'a> RenderCommandListener<'a> {
 }
 
 /// Options that influence scene building.
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct BuildOptions {
     /// A global transform to be applied to the scene.
     pub transform: RenderTransform,
@@ -58,20 +77,192 @@ pub struct BuildOptions {
     pub dilation: Vector2F,
     /// True if subpixel antialiasing for LCD screens is to be performed.
     pub subpixel_aa_enabled: bool,
+    /// True if building the same scene must always produce the same render commands.
+    ///
+    /// Paths are then tiled on the calling thread regardless of the executor, so that fills and
+    /// alpha tiles come out in path order instead of in whatever order worker threads finish. At
+    /// the D3D9 level this makes rendered images byte-identical from run to run. At the D3D11
+    /// level, binning happens on the GPU, whose scheduling this can't control.
+    pub deterministic: bool,
+    /// Rectangles, in device pixels, that the embedder will cover with opaque content of its own
+    /// after the scene is rendered, such as UI panels drawn over a map.
+    ///
+    /// Nothing under these rectangles can show, so paths that lie entirely beneath them aren't
+    /// built, and at the D3D9 level tiles beneath them aren't drawn. If one rectangle covers the
+    /// whole destination, it isn't cleared to the background color either. Only whole pixels
+    /// inside each rectangle count. Content drawn into render targets is unaffected.
+    pub opaque_regions: Vec<RectF>,
+    /// The maximum distance, in device pixels, between a curve and the lines it's flattened into
+    /// before rasterization. Defaults to `DEFAULT_FLATTENING_TOLERANCE`.
+    ///
+    /// Curves are flattened after the global transform is applied, so the tolerance follows the
+    /// zoom level: zooming in splits curves into more lines, keeping them smooth, and zooming out
+    /// splits them into fewer. Raise it to trade smoothness for speed. Values below 1/64 of a
+    /// pixel are treated as 1/64.
+    pub flattening_tolerance: f32,
+    /// Limits on the GPU memory that tiling this scene may use. Unlimited by default.
+    ///
+    /// Rather than failing when a scene exceeds its budget, building degrades quality until it
+    /// fits: first it flattens curves more coarsely and skips pattern filters, and as a last
+    /// resort it leaves out the last paths in drawing order. `RenderStats::degradation` reports
+    /// what was done. Budgets only apply where paths are tiled on the CPU, which is the case at
+    /// the D3D9 level.
+    pub memory_budget: MemoryBudget,
+    /// Whether to tile this scene's paths on the CPU or on the GPU. Defaults to
+    /// `TilingMode::ByLevel`.
+    pub tiling_mode: TilingMode,
+    /// True if pattern filters, such as blurs and text gamma correction, are applied. Defaults to
+    /// true.
+    ///
+    /// Turning them off skips their render passes; the patterns are drawn unfiltered.
+    pub pattern_filters_enabled: bool,
+    /// A token that stops the build early when it's cancelled, or `None` if the build can't be
+    /// cancelled.
+    ///
+    /// A cancelled build still ends with a `Finish` command, so renderers waiting for one don't
+    /// hang, but the commands before it are incomplete and shouldn't be rendered. See
+    /// `BuildJob`, which takes care of this.
+    pub cancellation_token: Option<CancellationToken>,
+}
+
+/// Where a scene's paths are tiled.
+///
+/// Tiling on the CPU has little fixed cost, so it's faster for small scenes, while tiling on the
+/// GPU with compute shaders scales better to large ones. Only renderers at the D3D11 level can
+/// tile on the GPU, and only builds with the `d3d9` feature can tile on the CPU; where the chosen
+/// mode isn't available, the renderer's level decides.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TilingMode {
+    /// Tiles on the CPU at the D3D9 level and on the GPU at the D3D11 level.
+    ByLevel,
+    /// Tiles on the CPU.
+    CPU,
+    /// Tiles on the GPU.
+    GPU,
+    /// Tiles on the CPU if the scene is no larger than both limits, and on the GPU otherwise.
+    Auto {
+        /// The most draw and clip paths a scene tiled on the CPU may have.
+        max_cpu_path_count: usize,
+        /// The most lines and curves, across all paths, a scene tiled on the CPU may have.
+        max_cpu_segment_count: usize,
+    },
+}
+
+/// Limits on the GPU memory that building a scene may use.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct MemoryBudget {
+    /// The maximum number of 16x16 device pixel tile masks, or `None` for no limit.
+    pub max_alpha_tiles: Option<usize>,
+    /// The maximum number of fills, each a single edge in one tile, or `None` for no limit.
+    pub max_fills: Option<usize>,
+}
+
+/// The ways in which building a scene reduced quality to fit its memory budget.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Degradation {
+    /// The number of times curve flattening tolerances were quadrupled.
+    pub tolerance_coarsening_steps: u32,
+    /// True if pattern filters, such as blurs and text gamma correction, were skipped.
+    pub pattern_filters_skipped: bool,
+    /// The number of draw paths, the last in drawing order, that were left out entirely.
+    pub culled_path_count: usize,
+}
+
+impl Default for BuildOptions {
+    #[inline]
+    fn default() -> BuildOptions {
+        BuildOptions {
+            transform: RenderTransform::default(),
+            dilation: Vector2F::zero(),
+            subpixel_aa_enabled: false,
+            deterministic: false,
+            opaque_regions: vec![],
+            flattening_tolerance: DEFAULT_FLATTENING_TOLERANCE,
+            memory_budget: MemoryBudget::default(),
+            tiling_mode: TilingMode::ByLevel,
+            pattern_filters_enabled: true,
+            cancellation_token: None,
+        }
+    }
 }
 
 impl BuildOptions {
+    /// Returns options for a fast, low-quality preview of a scene, such as a thumbnail in a
+    /// document list, given the transform its full-quality frame is drawn with.
+    ///
+    /// The scene is drawn at `THUMBNAIL_SCALE` times the size, so the destination should be that
+    /// much smaller too, with curves flattened to `THUMBNAIL_FLATTENING_TOLERANCE` and no pattern
+    /// filters. Thumbnails can be rendered alongside the active document by a second renderer on
+    /// the same device; since building needs the scene mutably, build a clone of it if that
+    /// happens on another thread.
+    pub fn thumbnail(transform: Transform2F) -> BuildOptions {
+        BuildOptions {
+            transform: RenderTransform::Transform2D(Transform2F::from_scale(THUMBNAIL_SCALE) *
+                                                    transform),
+            flattening_tolerance: THUMBNAIL_FLATTENING_TOLERANCE,
+            pattern_filters_enabled: false,
+            ..BuildOptions::default()
+        }
+    }
+
     pub(crate) fn prepare(self, bounds: RectF) -> PreparedBuildOptions {
         PreparedBuildOptions {
             transform: self.transform.prepare(bounds),
             dilation: self.dilation,
             subpixel_aa_enabled: self.subpixel_aa_enabled,
+            deterministic: self.deterministic,
+            opaque_regions: self.opaque_regions.iter().filter_map(|&region| {
+                let covered = RectF::from_points(region.origin().ceil(),
+                                                 region.lower_right().floor());
+                if covered.width() > 0.0 && covered.height() > 0.0 {
+                    Some(covered)
+                } else {
+                    None
+                }
+            }).collect(),
+            flattening_tolerance: self.flattening_tolerance.max(MIN_FLATTENING_TOLERANCE),
+            memory_budget: self.memory_budget,
+            tiling_mode: self.tiling_mode,
+            pattern_filters_enabled: self.pattern_filters_enabled,
+            cancellation_token: self.cancellation_token,
+        }
+    }
+}
+
+impl TilingMode {
+    /// Returns an automatic mode with limits suited to typical scenes.
+    #[inline]
+    pub fn auto() -> TilingMode {
+        TilingMode::Auto {
+            max_cpu_path_count: DEFAULT_MAX_CPU_PATH_COUNT,
+            max_cpu_segment_count: DEFAULT_MAX_CPU_SEGMENT_COUNT,
         }
     }
 }
 
+impl MemoryBudget {
+    #[inline]
+    pub(crate) fn is_unlimited(&self) -> bool {
+        self.max_alpha_tiles.is_none() && self.max_fills.is_none()
+    }
+
+    #[inline]
+    pub(crate) fn allows(&self, alpha_tile_count: usize, fill_count: usize) -> bool {
+        self.max_alpha_tiles.map_or(true, |max| alpha_tile_count <= max) &&
+            self.max_fills.map_or(true, |max| fill_count <= max)
+    }
+}
+
+impl Degradation {
+    /// Returns true if any quality was given up.
+    #[inline]
+    pub fn is_degraded(&self) -> bool {
+        *self != Degradation::default()
+    }
+}
+
 /// A global transform to apply to the scene.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum RenderTransform {
     /// A 2D transform.
     Transform2D(Transform2F),
@@ -143,6 +334,14 @@ pub(crate) struct PreparedBuildOptions {
     pub(crate) transform: PreparedRenderTransform,
     pub(crate) dilation: Vector2F,
     pub(crate) subpixel_aa_enabled: bool,
+    pub(crate) deterministic: bool,
+    // The embedder's opaque regions, shrunk to the whole pixels they cover.
+    pub(crate) opaque_regions: Vec<RectF>,
+    pub(crate) flattening_tolerance: f32,
+    pub(crate) memory_budget: MemoryBudget,
+    pub(crate) tiling_mode: TilingMode,
+    pub(crate) pattern_filters_enabled: bool,
+    pub(crate) cancellation_token: Option<CancellationToken>,
 }
 
 #[derive(Clone, Copy)]
@@ -153,6 +352,12 @@ pub(crate) enum PrepareMode {
 }
 
 impl PreparedBuildOptions {
+    // Returns true if the build has been cancelled and should stop early.
+    #[inline]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation_token.as_ref().map_or(false, |token| token.is_cancelled())
+    }
+
     #[inline]
     pub(crate) fn bounding_quad(&self) -> BoundingQuad {
         match self.transform {
@@ -15,10 +15,10 @@ use crate::gpu_data::{ColorCombineMode, RenderCommand, TextureLocation, TextureM
 use crate::gpu_data::{TexturePageDescriptor, TexturePageId, TileBatchTexture};
 use crate::scene::{RenderTarget, SceneId};
 use hashbrown::{HashMap, HashSet};
-use pathfinder_color::ColorU;
+use pathfinder_color::{ColorCmyk, ColorU};
 use pathfinder_content::effects::{BlendMode, Filter, PatternFilter};
-use pathfinder_content::gradient::{Gradient, GradientGeometry, GradientWrap};
-use pathfinder_content::pattern::{ImageHash, Pattern, PatternSource};
+use pathfinder_content::gradient::{ColorStop, Gradient, GradientGeometry, GradientWrap};
+use pathfinder_content::pattern::{Image, ImageHash, Pattern, PatternSource};
 use pathfinder_content::render_target::RenderTargetId;
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::rect::{RectF, RectI};
@@ -28,26 +28,40 @@ use pathfinder_gpu::TextureSamplingFlags;
 use pathfinder_simd::default::{F32x2, F32x4};
 use std::f32;
 use std::fmt::{self, Debug, Formatter};
+use std::mem;
 use std::sync::Arc;
 
+// The most texture metadata entries, paints and tinted variants together, that a scene can have.
+const MAX_TEXTURE_METADATA_ENTRIES: usize = 65536;
+
 // The size of a gradient tile.
 //
 // TODO(pcwalton): Choose this size dynamically!
 const GRADIENT_TILE_LENGTH: u32 = 256;
 
+// How many builds a cached gradient ramp may go unused before its row is freed. Scenes that
+// alternate, such as the screens of an app, keep their ramps between visits.
+const GRADIENT_RAMP_MAX_IDLE_BUILDS: u64 = 60;
+
 // Stores all paints in a scene.
 #[derive(Clone)]
 pub(crate) struct Palette {
     pub(crate) paints: Vec<Paint>,
     render_targets: Vec<RenderTarget>,
     cache: HashMap<Paint, PaintId>,
+    names: HashMap<String, PaintId>,
+    tints: Vec<(PaintId, ColorU)>,
+    tint_metadata_ids: HashMap<(PaintId, ColorU), u16>,
     scene_id: SceneId,
 }
 
-// Caches texture images from scene to scene.
+// Caches texture images and gradient ramps from scene to scene.
 pub(crate) struct PaintTextureManager {
     allocator: TextureAllocator,
     cached_images: HashMap<ImageHash, TextureLocation>,
+    // The pixels of images not in sRGB, converted to it.
+    converted_images: HashMap<ImageHash, Arc<Vec<ColorU>>>,
+    gradient_ramps: GradientRampCache,
 }
 
 /// Defines how a path is to be filled: with a solid color, gradient, or pattern.
@@ -55,6 +69,7 @@ pub(crate) struct PaintTextureManager {
 pub struct Paint {
     base_color: ColorU,
     overlay: Option<PaintOverlay>,
+    print_color: Option<ColorCmyk>,
 }
 
 /// What is to be overlaid on top of a base color.
@@ -93,6 +108,16 @@ pub enum PaintCompositeOp {
     DestIn,
 }
 
+/// A set of paints keyed by name, used to swap the colors of a scene at runtime.
+///
+/// Paints defined in a scene with `Scene::push_named_paint()` can be replaced all at once by
+/// applying a named palette with `Scene::apply_named_palette()`. This allows e.g. switching
+/// between light and dark themes without rebuilding any scene geometry.
+#[derive(Clone, Default, Debug)]
+pub struct NamedPalette {
+    paints: HashMap<String, Paint>,
+}
+
 impl Debug for PaintContents {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match *self {
@@ -109,6 +134,9 @@ impl Palette {
             paints: vec![],
             render_targets: vec![],
             cache: HashMap::new(),
+            names: HashMap::new(),
+            tints: vec![],
+            tint_metadata_ids: HashMap::new(),
             scene_id,
         }
     }
@@ -118,7 +146,7 @@ impl Paint {
     /// Creates a simple paint from a single base color.
     #[inline]
     pub fn from_color(color: ColorU) -> Paint {
-        Paint { base_color: color, overlay: None }
+        Paint { base_color: color, overlay: None, print_color: None }
     }
 
     /// Creates a paint from a gradient.
@@ -130,6 +158,7 @@ impl Paint {
                 composite_op: PaintCompositeOp::SrcIn,
                 contents: PaintContents::Gradient(gradient),
             }),
+            print_color: None,
         }
     }
 
@@ -142,9 +171,17 @@ impl Paint {
                 composite_op: PaintCompositeOp::SrcIn,
                 contents: PaintContents::Pattern(pattern),
             }),
+            print_color: None,
         }
     }
 
+    /// Creates a solid color paint that's drawn with `color` on screen and printed with the
+    /// process color `print_color`.
+    #[inline]
+    pub fn from_color_with_print_color(color: ColorU, print_color: ColorCmyk) -> Paint {
+        Paint { base_color: color, overlay: None, print_color: Some(print_color) }
+    }
+
     /// A convenience function to create a solid black paint.
     #[inline]
     pub fn black() -> Paint {
@@ -233,6 +270,22 @@ impl Paint {
         self.base_color = new_base_color;
     }
 
+    /// Returns the CMYK color that this paint is printed with, if one was specified.
+    ///
+    /// The print color only affects export to print formats like PDF. Rendering always uses the
+    /// base color.
+    #[inline]
+    pub fn print_color(&self) -> Option<ColorCmyk> {
+        self.print_color
+    }
+
+    /// Specifies the CMYK color that this paint is printed with, or `None` to print the base
+    /// color as RGB.
+    #[inline]
+    pub fn set_print_color(&mut self, new_print_color: Option<ColorCmyk>) {
+        self.print_color = new_print_color;
+    }
+
     /// Returns the paint overlay, which is the portion of the paint on top of the base color.
     #[inline]
     pub fn overlay(&self) -> &Option<PaintOverlay> {
@@ -289,6 +342,38 @@ impl Paint {
     }
 }
 
+impl NamedPalette {
+    /// Creates a new empty named palette.
+    #[inline]
+    pub fn new() -> NamedPalette {
+        NamedPalette::default()
+    }
+
+    /// Assigns a paint to the given name, replacing any paint previously assigned to it.
+    #[inline]
+    pub fn insert(&mut self, name: String, paint: Paint) {
+        self.paints.insert(name, paint);
+    }
+
+    /// A convenience method to assign a solid color to the given name.
+    #[inline]
+    pub fn insert_color(&mut self, name: String, color: ColorU) {
+        self.insert(name, Paint::from_color(color))
+    }
+
+    /// Returns the paint assigned to the given name, if any.
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&Paint> {
+        self.paints.get(name)
+    }
+
+    /// Returns an iterator over all names and their paints, in no particular order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Paint)> {
+        self.paints.iter().map(|(name, paint)| (&**name, paint))
+    }
+}
+
 impl PaintOverlay {
     #[inline]
     pub(crate) fn contents(&self) -> &PaintContents {
@@ -340,6 +425,8 @@ pub(crate) struct PaintColorTextureMetadata {
     pub(crate) transform: Transform2F,
     /// The sampling mode for the texture.
     pub(crate) sampling_flags: TextureSamplingFlags,
+    /// True if the shader wraps and filters the texture itself instead of the sampler.
+    pub(crate) seamless: bool,
     /// The filter to be applied to this paint.
     pub(crate) filter: PaintFilter,
     /// How the color texture is to be composited over the base color.
@@ -389,30 +476,105 @@ impl Palette {
         paint_id
     }
 
+    // Named paints always get their own slot and are never entered into the deduplication cache,
+    // so that replacing one never affects paths that merely happened to use the same paint.
+    pub(crate) fn push_named_paint(&mut self, name: String, paint: &Paint) -> PaintId {
+        if let Some(&paint_id) = self.names.get(&name) {
+            self.paints[paint_id.0 as usize] = (*paint).clone();
+            return paint_id;
+        }
+
+        let paint_id = PaintId(self.paints.len() as u16);
+        self.paints.push((*paint).clone());
+        self.names.insert(name, paint_id);
+        paint_id
+    }
+
+    #[inline]
+    pub(crate) fn named_paint_id(&self, name: &str) -> Option<PaintId> {
+        self.names.get(name).cloned()
+    }
+
+    pub(crate) fn set_paint(&mut self, paint_id: PaintId, new_paint: &Paint) {
+        let old_paint = mem::replace(&mut self.paints[paint_id.0 as usize], (*new_paint).clone());
+        if self.cache.get(&old_paint) == Some(&paint_id) {
+            self.cache.remove(&old_paint);
+            if !self.cache.contains_key(new_paint) {
+                self.cache.insert((*new_paint).clone(), paint_id);
+            }
+        }
+    }
+
+    // Detaches the paint from its name, if it has one, so that appending this palette to another
+    // gives it a slot of its own instead of replacing the other palette's paint of that name.
+    pub(crate) fn unname_paint(&mut self, paint_id: PaintId) {
+        self.names.retain(|_, &mut named_paint_id| named_paint_id != paint_id);
+    }
+
+    pub(crate) fn apply_named_palette(&mut self, named_palette: &NamedPalette) {
+        for (name, paint) in named_palette.iter() {
+            if let Some(&paint_id) = self.names.get(name) {
+                self.paints[paint_id.0 as usize] = (*paint).clone();
+            }
+        }
+    }
+
+    // Tinted paints share their paint's texture, but get a texture metadata entry of their own,
+    // after those of the untinted paints.
+    fn assign_tint_metadata_ids<I>(&mut self, tints: I)
+                                   where I: Iterator<Item = (PaintId, ColorU)> {
+        self.tints.clear();
+        self.tint_metadata_ids.clear();
+        for (paint_id, tint) in tints {
+            if tint == ColorU::white() || self.tint_metadata_ids.contains_key(&(paint_id, tint)) {
+                continue;
+            }
+            let metadata_id = self.paints.len() + self.tints.len();
+            if metadata_id >= MAX_TEXTURE_METADATA_ENTRIES {
+                warn!("Too many tinted paints; some tints will be ignored!");
+                break;
+            }
+            self.tint_metadata_ids.insert((paint_id, tint), metadata_id as u16);
+            self.tints.push((paint_id, tint));
+        }
+    }
+
+    // Returns the texture metadata entry that draws the given paint with the given tint.
+    #[inline]
+    pub(crate) fn metadata_id(&self, paint_id: PaintId, tint: ColorU) -> u16 {
+        if tint == ColorU::white() {
+            return paint_id.0;
+        }
+        self.tint_metadata_ids.get(&(paint_id, tint)).cloned().unwrap_or(paint_id.0)
+    }
+
     pub(crate) fn push_render_target(&mut self, render_target: RenderTarget) -> RenderTargetId {
         let id = self.render_targets.len() as u32;
         self.render_targets.push(render_target);
         RenderTargetId { scene: self.scene_id.0, render_target: id }
     }
 
-    pub(crate) fn build_paint_info(&mut self,
-                                   texture_manager: &mut PaintTextureManager,
-                                   render_transform: Transform2F)
-                                   -> PaintInfo {
+    pub(crate) fn build_paint_info<I>(&mut self,
+                                      texture_manager: &mut PaintTextureManager,
+                                      render_transform: Transform2F,
+                                      tints: I)
+                                      -> PaintInfo
+                                      where I: Iterator<Item = (PaintId, ColorU)> {
+        // Assign metadata entries to tinted paints.
+        self.assign_tint_metadata_ids(tints);
+
         // Assign render target locations.
         let mut transient_paint_locations = vec![];
         let render_target_metadata =
             self.assign_render_target_locations(texture_manager, &mut transient_paint_locations);
 
         // Assign paint locations.
+        texture_manager.gradient_ramps.begin_build();
         let PaintLocationsInfo {
             mut paint_metadata,
-            gradient_tile_builder,
             image_texel_info,
             used_image_hashes,
-        } = self.assign_paint_locations(&render_target_metadata,
-                                        texture_manager,
-                                        &mut transient_paint_locations);
+        } = self.assign_paint_locations(&render_target_metadata, texture_manager);
 
         // Calculate texture transforms.
         self.calculate_texture_transforms(&mut paint_metadata, texture_manager, render_transform);
@@ -427,12 +589,14 @@ impl Palette {
         // Create render commands.
         self.create_render_commands(&mut render_commands,
                                     render_target_metadata,
-                                    gradient_tile_builder,
+                                    &mut texture_manager.gradient_ramps,
                                     image_texel_info);
 
-        // Free transient locations and unused images, now that they're no longer needed.
+        // Free transient locations, unused images, and idle gradient ramps, now that they're no
+        // longer needed.
         self.free_transient_locations(texture_manager, transient_paint_locations);
         self.free_unused_images(texture_manager, used_image_hashes);
+        texture_manager.gradient_ramps.free_idle_ramps(&mut texture_manager.allocator);
 
         PaintInfo { render_commands, paint_metadata }
     }
@@ -452,11 +616,9 @@ impl Palette {
 
     fn assign_paint_locations(&self,
                               render_target_metadata: &[RenderTargetMetadata],
-                              texture_manager: &mut PaintTextureManager,
-                              transient_paint_locations: &mut Vec<TextureLocation>)
+                              texture_manager: &mut PaintTextureManager)
                               -> PaintLocationsInfo {
         let mut paint_metadata = vec![];
-        let mut gradient_tile_builder = GradientTileBuilder::new();
         let mut image_texel_info = vec![];
         let mut used_image_hashes = HashSet::new();
         for paint in &self.paints {
@@ -477,13 +639,12 @@ impl Palette {
                             // FIXME(pcwalton): The gradient size might not be big enough. Detect
                             // this.
                             let location =
-                                gradient_tile_builder.allocate(allocator,
-                                                               transient_paint_locations,
-                                                               gradient);
+                                texture_manager.gradient_ramps.ramp_location(allocator, gradient);
                             Some(PaintColorTextureMetadata {
                                 location,
                                 page_scale: allocator.page_scale(location.page),
                                 sampling_flags,
+                                seamless: false,
                                 filter: match gradient.geometry {
                                     GradientGeometry::Linear(_) => PaintFilter::None,
                                     GradientGeometry::Radial { line, radii, .. } => {
@@ -512,7 +673,6 @@ impl Palette {
                                     match texture_manager.cached_images.get(&image_hash) {
                                         Some(cached_location) => {
                                             location = *cached_location;
-                                            used_image_hashes.insert(image_hash);
                                         }
                                         None => {
                                             // Leave a pixel of border on the side.
@@ -524,12 +684,16 @@ impl Palette {
                                                                                  location);
                                         }
                                     }
+                                    used_image_hashes.insert(image_hash);
                                     image_texel_info.push(ImageTexelInfo {
                                         location: TextureLocation {
                                             page: location.page,
                                             rect: location.rect.contract(border),
                                         },
-                                        texels: (*image.pixels()).clone(),
+                                        texels: srgb_image_pixels(
+                                            &mut texture_manager.converted_images,
+                                            image_hash,
+                                            image),
                                     });
                                 }
                             }
@@ -555,6 +719,7 @@ impl Palette {
                                 location,
                                 page_scale: allocator.page_scale(location.page),
                                 sampling_flags,
+                                seamless: pattern.seamless(),
                                 filter,
                                 transform: Transform2F::from_translation(border.to_f32()),
                                 composite_op: overlay.composite_op(),
@@ -576,7 +741,6 @@ impl Palette {
 
         PaintLocationsInfo {
             paint_metadata,
-            gradient_tile_builder,
             image_texel_info,
             used_image_hashes,
         }
@@ -640,18 +804,29 @@ impl Palette {
 
     fn create_texture_metadata(&self, paint_metadata: &[PaintMetadata])
                                -> Vec<TextureMetadataEntry> {
-        paint_metadata.iter().map(|paint_metadata| {
+        let untinted = paint_metadata.iter().map(|metadata| (metadata, ColorU::white()));
+        let tinted = self.tints.iter().map(|&(paint_id, tint)| {
+            (&paint_metadata[paint_id.0 as usize], tint)
+        });
+        untinted.chain(tinted).map(|(paint_metadata, tint)| {
             TextureMetadataEntry {
                 color_0_transform: match paint_metadata.color_texture_metadata {
                     None => Transform2F::default(),
                     Some(ref color_texture_metadata) => color_texture_metadata.transform,
                 },
+                color_0_sampling: match paint_metadata.color_texture_metadata {
+                    Some(ref color_texture_metadata) if color_texture_metadata.seamless => {
+                        Some(color_texture_metadata.sampling_flags)
+                    }
+                    _ => None,
+                },
                 color_0_combine_mode: if paint_metadata.color_texture_metadata.is_some() {
                     ColorCombineMode::SrcIn
                 } else {
                     ColorCombineMode::None
                 },
-                base_color: paint_metadata.base_color,
+                color_0_tint: tint,
+                base_color: multiply_colors(paint_metadata.base_color, tint),
                 filter: paint_metadata.filter(),
                 blend_mode: paint_metadata.blend_mode,
             }
@@ -675,7 +850,7 @@ impl Palette {
     fn create_render_commands(&self,
                               render_commands: &mut Vec<RenderCommand>,
                               render_target_metadata: Vec<RenderTargetMetadata>,
-                              gradient_tile_builder: GradientTileBuilder,
+                              gradient_ramps: &mut GradientRampCache,
                               image_texel_info: Vec<ImageTexelInfo>) {
         for (index, metadata) in render_target_metadata.iter().enumerate() {
             let id = RenderTargetId { scene: self.scene_id.0, render_target: index as u32 };
@@ -684,7 +859,7 @@ impl Palette {
                 location: metadata.location,
             });
         }
-        gradient_tile_builder.create_render_commands(render_commands);
+        gradient_ramps.create_render_commands(render_commands);
         for image_texel_info in image_texel_info {
             render_commands.push(RenderCommand::UploadTexelData {
                 texels: image_texel_info.texels,
@@ -705,15 +880,25 @@ impl Palette {
     fn free_unused_images(&self,
                           texture_manager: &mut PaintTextureManager,
                           used_image_hashes: HashSet<ImageHash>) {
-        let cached_images = &mut texture_manager.cached_images;
-        let allocator = &mut texture_manager.allocator;
-        cached_images.retain(|image_hash, location| {
+        // Free in a fixed order, since the order affects where later images are allocated and the
+        // cache's iteration order isn't stable.
+        let mut unused_locations = vec![];
+        texture_manager.cached_images.retain(|image_hash, location| {
             let keep = used_image_hashes.contains(image_hash);
             if !keep {
-                allocator.free(*location);
+                unused_locations.push(*location);
             }
             keep
         });
+        unused_locations.sort_by_key(|location| {
+            (location.page.0, location.rect.min_y(), location.rect.min_x())
+        });
+        for location in unused_locations {
+            texture_manager.allocator.free(location);
+        }
+        texture_manager.converted_images.retain(|image_hash, _| {
+            used_image_hashes.contains(image_hash)
+        });
     }
 
     pub(crate) fn append_palette(&mut self, palette: Palette) -> MergedPaletteInfo {
@@ -730,12 +915,16 @@ impl Palette {
             render_target_mapping.insert(old_render_target_id, new_render_target_id);
         }
 
-        // Merge paints.
+        // Merge paints, preserving names.
+        let mut old_paint_names = HashMap::new();
+        for (name, paint_id) in palette.names {
+            old_paint_names.insert(paint_id, name);
+        }
         let mut paint_mapping = HashMap::new();
         for (old_paint_index, old_paint) in palette.paints.iter().enumerate() {
             let old_paint_id = PaintId(old_paint_index as u16);
-            let new_paint_id = match *old_paint.overlay() {
-                None => self.push_paint(old_paint),
+            let new_paint = match *old_paint.overlay() {
+                None => None,
                 Some(ref overlay) => {
                     match *overlay.contents() {
                         PaintContents::Pattern(ref pattern) => {
@@ -748,15 +937,21 @@ impl Palette {
                                     new_pattern.set_repeat_x(pattern.repeat_x());
                                     new_pattern.set_repeat_y(pattern.repeat_y());
                                     new_pattern.set_smoothing_enabled(pattern.smoothing_enabled());
-                                    self.push_paint(&Paint::from_pattern(new_pattern))
+                                    new_pattern.set_seamless(pattern.seamless());
+                                    Some(Paint::from_pattern(new_pattern))
                                 }
-                                _ => self.push_paint(old_paint),
+                                _ => None,
                             }
                         }
-                        _ => self.push_paint(old_paint),
+                        _ => None,
                     }
                 }
             };
+            let new_paint = new_paint.as_ref().unwrap_or(old_paint);
+            let new_paint_id = match old_paint_names.remove(&old_paint_id) {
+                Some(name) => self.push_named_paint(name, new_paint),
+                None => self.push_paint(new_paint),
+            };
             paint_mapping.insert(old_paint_id, new_paint_id);
         }
 
@@ -764,15 +959,34 @@ impl Palette {
     }
 }
 
+pub(crate) fn multiply_colors(a: ColorU, b: ColorU) -> ColorU {
+    let multiply = |a: u8, b: u8| ((a as u32 * b as u32 + 127) / 255) as u8;
+    ColorU::new(multiply(a.r, b.r), multiply(a.g, b.g), multiply(a.b, b.b), multiply(a.a, b.a))
+}
+
 impl PaintTextureManager {
     pub(crate) fn new() -> PaintTextureManager {
         PaintTextureManager {
             allocator: TextureAllocator::new(),
             cached_images: HashMap::new(),
+            converted_images: HashMap::new(),
+            gradient_ramps: GradientRampCache::new(),
         }
     }
 }
 
+// Returns an image's pixels in sRGB. Images in other color spaces are converted the first time
+// they're used, and the result is kept for as long as they're used.
+fn srgb_image_pixels(converted_images: &mut HashMap<ImageHash, Arc<Vec<ColorU>>>,
+                     image_hash: ImageHash,
+                     image: &Image)
+                     -> Arc<Vec<ColorU>> {
+    if image.color_space().is_srgb() {
+        return image.pixels().clone();
+    }
+    converted_images.entry(image_hash).or_insert_with(|| image.srgb_pixels()).clone()
+}
+
 pub(crate) struct MergedPaletteInfo {
     pub(crate) render_target_mapping: HashMap<RenderTargetId, RenderTargetId>,
     pub(crate) paint_mapping: HashMap<PaintId, PaintId>,
@@ -810,76 +1024,128 @@ fn rect_to_uv(rect: RectI, texture_scale: Vector2F) -> RectF {
 
 // Gradient allocation
 
-struct GradientTileBuilder {
-    tiles: Vec<GradientTile>,
+// Caches gradient ramps from build to build, keyed by their color stops.
+//
+// Every gradient is interpolated the same way, so its ramp depends on nothing but its stops: not
+// on its geometry, which the shader applies, nor its wrap mode, which the sampler does. Each ramp
+// is a row of a page of `GRADIENT_TILE_LENGTH` rows, rasterized and uploaded when it's first
+// used and kept until it has been idle for `GRADIENT_RAMP_MAX_IDLE_BUILDS` builds.
+struct GradientRampCache {
+    ramps: HashMap<Vec<ColorStop>, CachedGradientRamp>,
+    pages: Vec<GradientRampPage>,
+    build_index: u64,
+    pending_uploads: Vec<(TextureLocation, Vec<ColorU>)>,
+}
+
+struct CachedGradientRamp {
+    location: TextureLocation,
+    last_used_build_index: u64,
 }
 
-struct GradientTile {
-    texels: Vec<ColorU>,
-    page: TexturePageId,
-    next_index: u32,
+struct GradientRampPage {
+    location: TextureLocation,
+    used_rows: Vec<bool>,
 }
 
-impl GradientTileBuilder {
-    fn new() -> GradientTileBuilder {
-        GradientTileBuilder { tiles: vec![] }
+impl GradientRampCache {
+    fn new() -> GradientRampCache {
+        GradientRampCache {
+            ramps: HashMap::new(),
+            pages: vec![],
+            build_index: 0,
+            pending_uploads: vec![],
+        }
     }
 
-    fn allocate(&mut self,
-                allocator: &mut TextureAllocator,
-                transient_paint_locations: &mut Vec<TextureLocation>,
-                gradient: &Gradient)
-                -> TextureLocation {
-        if self.tiles.is_empty() ||
-                self.tiles.last().unwrap().next_index == GRADIENT_TILE_LENGTH {
-            let size = Vector2I::splat(GRADIENT_TILE_LENGTH as i32);
-            let area = size.x() as usize * size.y() as usize;
-            let page_location = allocator.allocate(size, AllocationMode::OwnPage);
-            transient_paint_locations.push(page_location);
-            self.tiles.push(GradientTile {
-                texels: vec![ColorU::black(); area],
-                page: page_location.page,
-                next_index: 0,
-            })
+    fn begin_build(&mut self) {
+        self.build_index += 1;
+    }
+
+    // Returns the row holding the ramp of the gradient's stops, rasterizing it if it isn't cached.
+    fn ramp_location(&mut self, allocator: &mut TextureAllocator, gradient: &Gradient)
+                     -> TextureLocation {
+        if let Some(ramp) = self.ramps.get_mut(gradient.stops()) {
+            ramp.last_used_build_index = self.build_index;
+            return ramp.location;
         }
 
-        let mut data = self.tiles.last_mut().unwrap();
-        let location = TextureLocation {
-            page: data.page,
-            rect: RectI::new(vec2i(0, data.next_index as i32),
-                             vec2i(GRADIENT_TILE_LENGTH as i32, 1)),
-        };
-        data.next_index += 1;
+        let location = self.allocate_row(allocator);
 
         // FIXME(pcwalton): Paint transparent if gradient line has zero size, per spec.
         // TODO(pcwalton): Optimize this:
         // 1. Calculate ∇t up front and use differencing in the inner loop.
         // 2. Go four pixels at a time with SIMD.
-        let first_address = location.rect.origin_y() as usize * GRADIENT_TILE_LENGTH as usize;
-        for x in 0..(GRADIENT_TILE_LENGTH as i32) {
-            let t = (x as f32 + 0.5) / GRADIENT_TILE_LENGTH as f32;
-            data.texels[first_address + x as usize] = gradient.sample(t);
-        }
-
+        let texels = (0..GRADIENT_TILE_LENGTH).map(|x| {
+            gradient.sample((x as f32 + 0.5) / GRADIENT_TILE_LENGTH as f32)
+        }).collect();
+        self.pending_uploads.push((location, texels));
+
+        self.ramps.insert(gradient.stops().to_vec(), CachedGradientRamp {
+            location,
+            last_used_build_index: self.build_index,
+        });
         location
     }
 
-    fn create_render_commands(self, render_commands: &mut Vec<RenderCommand>) {
-        for tile in self.tiles {
+    fn allocate_row(&mut self, allocator: &mut TextureAllocator) -> TextureLocation {
+        let free_row = self.pages.iter_mut().find_map(|page| {
+            let row = page.used_rows.iter().position(|&used| !used)?;
+            page.used_rows[row] = true;
+            Some((page.location.page, row))
+        });
+        let (page, row) = match free_row {
+            Some(free_row) => free_row,
+            None => {
+                let size = Vector2I::splat(GRADIENT_TILE_LENGTH as i32);
+                let location = allocator.allocate(size, AllocationMode::OwnPage);
+                let mut used_rows = vec![false; GRADIENT_TILE_LENGTH as usize];
+                used_rows[0] = true;
+                self.pages.push(GradientRampPage { location, used_rows });
+                (location.page, 0)
+            }
+        };
+        TextureLocation {
+            page,
+            rect: RectI::new(vec2i(0, row as i32), vec2i(GRADIENT_TILE_LENGTH as i32, 1)),
+        }
+    }
+
+    // Uploads the ramps rasterized during this build.
+    fn create_render_commands(&mut self, render_commands: &mut Vec<RenderCommand>) {
+        for (location, texels) in self.pending_uploads.drain(..) {
             render_commands.push(RenderCommand::UploadTexelData {
-                texels: Arc::new(tile.texels),
-                location: TextureLocation {
-                    rect: RectI::new(vec2i(0, 0), Vector2I::splat(GRADIENT_TILE_LENGTH as i32)),
-                    page: tile.page,
-                },
+                texels: Arc::new(texels),
+                location,
             });
         }
     }
+
+    // Frees the rows of ramps that have been idle too long, and pages left without ramps.
+    fn free_idle_ramps(&mut self, allocator: &mut TextureAllocator) {
+        let build_index = self.build_index;
+        let pages = &mut self.pages;
+        self.ramps.retain(|_, ramp| {
+            let keep = build_index - ramp.last_used_build_index < GRADIENT_RAMP_MAX_IDLE_BUILDS;
+            if !keep {
+                let page = pages.iter_mut().find(|page| {
+                    page.location.page == ramp.location.page
+                }).unwrap();
+                page.used_rows[ramp.location.rect.origin_y() as usize] = false;
+            }
+            keep
+        });
+        pages.retain(|page| {
+            let keep = page.used_rows.iter().any(|&used| used);
+            if !keep {
+                allocator.free(page.location);
+            }
+            keep
+        });
+    }
 }
 
 struct PaintLocationsInfo {
     paint_metadata: Vec<PaintMetadata>,
-    gradient_tile_builder: GradientTileBuilder,
     image_texel_info: Vec<ImageTexelInfo>,
     used_image_hashes: HashSet<ImageHash>,
 }
@@ -898,3 +1164,114 @@ impl PaintColorTextureMetadata {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::gpu_data::RenderCommand;
+    use crate::scene::SceneId;
+    use pathfinder_color::{ColorCmyk, ColorU};
+    use pathfinder_content::gradient::Gradient;
+    use pathfinder_geometry::line_segment::LineSegment2F;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::vec2f;
+    use std::iter;
+
+    use super::{NamedPalette, Paint, PaintTextureManager, Palette, multiply_colors};
+
+    fn gradient(line: LineSegment2F, end_color: ColorU) -> Gradient {
+        let mut gradient = Gradient::linear(line);
+        gradient.add_color_stop(ColorU::white(), 0.0);
+        gradient.add_color_stop(end_color, 1.0);
+        gradient
+    }
+
+    fn uploaded_ramp_count(palette: &mut Palette, texture_manager: &mut PaintTextureManager)
+                           -> usize {
+        let paint_info =
+            palette.build_paint_info(texture_manager, Transform2F::default(), iter::empty());
+        paint_info.render_commands.iter().filter(|command| {
+            matches!(command, RenderCommand::UploadTexelData { .. })
+        }).count()
+    }
+
+    #[test]
+    fn test_named_paints_are_not_deduplicated() {
+        let mut palette = Palette::new(SceneId(0));
+        let red = Paint::from_color(ColorU::new(255, 0, 0, 255));
+        let anonymous_id = palette.push_paint(&red);
+        assert_eq!(palette.push_paint(&red), anonymous_id);
+
+        let named_id = palette.push_named_paint("accent".to_owned(), &red);
+        assert_ne!(named_id, anonymous_id);
+        assert_eq!(palette.named_paint_id("accent"), Some(named_id));
+
+        let mut theme = NamedPalette::new();
+        theme.insert_color("accent".to_owned(), ColorU::new(0, 0, 255, 255));
+        palette.apply_named_palette(&theme);
+        assert_eq!(palette.paints[named_id.0 as usize].base_color(), ColorU::new(0, 0, 255, 255));
+        assert_eq!(palette.paints[anonymous_id.0 as usize], red);
+    }
+
+    #[test]
+    fn test_print_colors_keep_paints_distinct() {
+        let mut palette = Palette::new(SceneId(0));
+        let red = ColorU::new(237, 28, 36, 255);
+        let rgb_id = palette.push_paint(&Paint::from_color(red));
+        let cmyk = ColorCmyk::from_percentages(0.0, 100.0, 100.0, 0.0);
+        let brand = Paint::from_color_with_print_color(red, cmyk);
+        let brand_id = palette.push_paint(&brand);
+        assert_ne!(brand_id, rgb_id);
+        assert_eq!(palette.push_paint(&brand), brand_id);
+        assert_eq!(palette.paints[brand_id.0 as usize].base_color(), red);
+        assert_eq!(palette.paints[brand_id.0 as usize].print_color(),
+                   Some(ColorCmyk::new(0.0, 1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_set_paint_updates_cache() {
+        let mut palette = Palette::new(SceneId(0));
+        let red = Paint::from_color(ColorU::new(255, 0, 0, 255));
+        let green = Paint::from_color(ColorU::new(0, 255, 0, 255));
+        let paint_id = palette.push_paint(&red);
+        palette.set_paint(paint_id, &green);
+        assert_eq!(palette.push_paint(&green), paint_id);
+        assert_ne!(palette.push_paint(&red), paint_id);
+    }
+    #[test]
+    fn test_tinted_paints_get_own_metadata() {
+        let mut palette = Palette::new(SceneId(0));
+        let red = palette.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let green = palette.push_paint(&Paint::from_color(ColorU::new(0, 255, 0, 255)));
+        let half = ColorU::new(128, 128, 128, 128);
+        palette.assign_tint_metadata_ids(vec![
+            (red, ColorU::white()),
+            (green, half),
+            (red, half),
+            (green, half),
+        ].into_iter());
+
+        assert_eq!(palette.metadata_id(red, ColorU::white()), 0);
+        assert_eq!(palette.metadata_id(green, half), 2);
+        assert_eq!(palette.metadata_id(red, half), 3);
+        assert_eq!(multiply_colors(ColorU::new(255, 0, 0, 255), half), ColorU::new(128, 0, 0, 128));
+    }
+
+    #[test]
+    fn test_gradient_ramps_are_cached_across_builds() {
+        let line_a = LineSegment2F::new(vec2f(0.0, 0.0), vec2f(10.0, 0.0));
+        let line_b = LineSegment2F::new(vec2f(5.0, 5.0), vec2f(0.0, 20.0));
+        let red = ColorU::new(255, 0, 0, 255);
+
+        let mut texture_manager = PaintTextureManager::new();
+        let mut first_scene = Palette::new(SceneId(0));
+        first_scene.push_paint(&Paint::from_gradient(gradient(line_a, ColorU::black())));
+        assert_eq!(uploaded_ramp_count(&mut first_scene, &mut texture_manager), 1);
+
+        // A gradient with the same stops along a different line reuses the ramp.
+        let mut second_scene = Palette::new(SceneId(1));
+        second_scene.push_paint(&Paint::from_gradient(gradient(line_b, ColorU::black())));
+        second_scene.push_paint(&Paint::from_gradient(gradient(line_a, red)));
+        assert_eq!(uploaded_ramp_count(&mut second_scene, &mut texture_manager), 1);
+        assert_eq!(uploaded_ramp_count(&mut first_scene, &mut texture_manager), 0);
+    }
+}
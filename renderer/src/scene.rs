@@ -10,30 +10,50 @@
 
 //! The vector scene to be rendered.
 
+use crate::accessibility::{AccessibilityNodeId, AccessibilityRole, AccessibilityTag};
+use crate::accessibility::{AccessibilityTree, TagExtent};
 use crate::builder::SceneBuilder;
 use crate::concurrent::executor::Executor;
 use crate::gpu::options::RendererLevel;
 use crate::gpu::renderer::Renderer;
 use crate::gpu_data::RenderCommand;
+use crate::layer::{Layer, LayerId};
 use crate::options::{BuildOptions, PreparedBuildOptions};
 use crate::options::{PreparedRenderTransform, RenderCommandListener};
-use crate::paint::{MergedPaletteInfo, Paint, PaintId, PaintInfo, PaintTextureManager, Palette};
+use crate::marker::{self, PathMarkers};
+use crate::paint::{MergedPaletteInfo, NamedPalette, Paint, PaintId, PaintInfo};
+use crate::paint::{PaintTextureManager, Palette, multiply_colors};
+use crate::symbol::{MAX_STAMP_SIZE, Stamp, StampKey, Symbol, SymbolId, SymbolInstance};
+use crate::symbol::SymbolMode;
+use pathfinder_color::ColorU;
 use pathfinder_content::effects::BlendMode;
 use pathfinder_content::fill::FillRule;
-use pathfinder_content::outline::Outline;
+use pathfinder_content::orientation::Orientation;
+use pathfinder_content::outline::{Contour, Outline, PointFlags};
+use pathfinder_content::pattern::Pattern;
 use pathfinder_content::render_target::RenderTargetId;
 use pathfinder_geometry::rect::RectF;
 use pathfinder_geometry::transform2d::Transform2F;
-use pathfinder_geometry::vector::{Vector2I, vec2f};
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f};
 use pathfinder_gpu::Device;
+use std::borrow::Cow;
 use std::mem;
 use std::ops::Range;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::u64;
 
 static NEXT_SCENE_ID: AtomicUsize = AtomicUsize::new(0);
 
+// How far `Scene::optimize()` treats paths as overlapping beyond their bounds, to account for
+// antialiasing.
+const OPTIMIZE_OVERLAP_MARGIN: f32 = 1.0;
+// How many earlier paths `Scene::optimize()` looks through for one drawn the same way.
+const MAX_OPTIMIZE_SEARCH_DISTANCE: usize = 256;
+// Merged paths are tiled on one thread, so `Scene::optimize()` stops merging at this size.
+const MAX_MERGED_SEGMENT_COUNT: usize = 4096;
+
 /// The vector scene to be rendered.
 #[derive(Clone)]
 pub struct Scene {
@@ -43,8 +63,19 @@ pub struct Scene {
     palette: Palette,
     bounds: RectF,
     view_box: RectF,
+    symbols: Vec<Symbol>,
     id: SceneId,
     epoch: SceneEpoch,
+    stroke_time: Duration,
+    layers: Vec<Layer>,
+    // The layers that have been popped, from bottom to top.
+    layer_order: Vec<LayerId>,
+    // The range of the display list that holds each layer in `layer_order`.
+    layer_slots: Vec<Range<usize>>,
+    open_layer: Option<LayerId>,
+    coverage_curve: CoverageCurve,
+    accessibility_tags: Vec<AccessibilityTag>,
+    open_accessibility_nodes: Vec<AccessibilityNodeId>,
 }
 
 /// A globally-unique identifier for the scene.
@@ -63,15 +94,25 @@ impl Scene {
             palette: Palette::new(scene_id),
             bounds: RectF::default(),
             view_box: RectF::default(),
+            symbols: vec![],
             id: scene_id,
             epoch: SceneEpoch::new(0, 1),
+            stroke_time: Duration::default(),
+            layers: vec![],
+            layer_order: vec![],
+            layer_slots: vec![],
+            open_layer: None,
+            coverage_curve: CoverageCurve::default(),
+            accessibility_tags: vec![],
+            open_accessibility_nodes: vec![],
         }
     }
 
     /// Adds a path to the scene, to be drawn on top of all previously-added paths.
     ///
     /// If a render target is on the stack (see `push_render_target()`), the path goes to the
-    /// render target. Otherwise, it goes to the main output.
+    /// render target. Otherwise, it goes to the main output. If a layer is open (see
+    /// `push_layer()`), the path belongs to that layer.
     ///
     /// Returns an ID which can later be used to retrieve the path via `get_draw_path()`.
     pub fn push_draw_path(&mut self, draw_path: DrawPath) -> DrawPathId {
@@ -85,15 +126,31 @@ impl Scene {
         let new_path_bounds = self.draw_paths[draw_path_id.0 as usize].outline.bounds();
         self.bounds = self.bounds.union_rect(new_path_bounds);
 
+        // Paths outside layers mustn't be merged into the slot of the layer below them.
         let end_path_id = DrawPathId(draw_path_id.0 + 1);
-        match self.display_list.last_mut() {
-            Some(DisplayItem::DrawPaths(ref mut range)) => range.end = end_path_id,
-            _ => self.display_list.push(DisplayItem::DrawPaths(draw_path_id..end_path_id)),
+        let first_mergeable_index = match self.open_layer {
+            Some(_) => 0,
+            None => self.layer_slots.last().map_or(0, |slot| slot.end),
+        };
+        let display_list = self.open_display_list();
+        let mergeable = display_list.len() > first_mergeable_index;
+        match display_list.last_mut() {
+            Some(DisplayItem::DrawPaths(ref mut range)) if mergeable => range.end = end_path_id,
+            _ => display_list.push(DisplayItem::DrawPaths(draw_path_id..end_path_id)),
         }
 
         self.epoch.next();
     }
 
+    // Returns the display list that new display items go to: that of the open layer, if any, or
+    // else the scene's.
+    fn open_display_list(&mut self) -> &mut Vec<DisplayItem> {
+        match self.open_layer {
+            Some(layer_id) => &mut self.layers[layer_id.0 as usize].display_list,
+            None => &mut self.display_list,
+        }
+    }
+
     /// Defines a clip path. Returns an ID that can be used to later clip draw paths.
     pub fn push_clip_path(&mut self, clip_path: ClipPath) -> ClipPathId {
         self.bounds = self.bounds.union_rect(clip_path.outline.bounds());
@@ -109,7 +166,7 @@ impl Scene {
     /// top of the stack.
     pub fn push_render_target(&mut self, render_target: RenderTarget) -> RenderTargetId {
         let render_target_id = self.palette.push_render_target(render_target);
-        self.display_list.push(DisplayItem::PushRenderTarget(render_target_id));
+        self.open_display_list().push(DisplayItem::PushRenderTarget(render_target_id));
         self.epoch.next();
         render_target_id
     }
@@ -119,12 +176,13 @@ impl Scene {
     /// After calling this method, drawing will go to the previous render target. If no render
     /// targets remain on the stack, drawing goes to the main output.
     pub fn pop_render_target(&mut self) {
-        self.display_list.push(DisplayItem::PopRenderTarget);
+        self.open_display_list().push(DisplayItem::PopRenderTarget);
     }
 
     /// Adds all elements in a scene to this one.
     ///
-    /// This includes draw paths, clip paths, render targets, and paints.
+    /// This includes draw paths, clip paths, render targets, and paints. The layers of the other
+    /// scene aren't kept; their visible contents are appended in their current order.
     pub fn append_scene(&mut self, scene: Scene) {
         let MergedPaletteInfo {
             render_target_mapping,
@@ -139,6 +197,7 @@ impl Scene {
         }
 
         // Merge draw paths.
+        let draw_path_base = self.draw_paths.len() as u32;
         let mut draw_path_mapping = Vec::with_capacity(scene.draw_paths.len());
         for draw_path in scene.draw_paths {
             draw_path_mapping.push(self.draw_paths.len() as u32);
@@ -150,6 +209,10 @@ impl Scene {
                 }),
                 fill_rule: draw_path.fill_rule,
                 blend_mode: draw_path.blend_mode,
+                tint: draw_path.tint,
+                watertight: draw_path.watertight,
+                antialiased: draw_path.antialiased,
+                quality: draw_path.quality,
                 name: draw_path.name,
             });
         }
@@ -159,10 +222,11 @@ impl Scene {
             match display_item {
                 DisplayItem::PushRenderTarget(old_render_target_id) => {
                     let new_render_target_id = render_target_mapping[&old_render_target_id];
-                    self.display_list.push(DisplayItem::PushRenderTarget(new_render_target_id));
+                    let new_display_item = DisplayItem::PushRenderTarget(new_render_target_id);
+                    self.open_display_list().push(new_display_item);
                 }
                 DisplayItem::PopRenderTarget => {
-                    self.display_list.push(DisplayItem::PopRenderTarget);
+                    self.open_display_list().push(DisplayItem::PopRenderTarget);
                 }
                 DisplayItem::DrawPaths(range) => {
                     for old_path_index in (range.start.0 as usize)..(range.end.0 as usize) {
@@ -173,16 +237,533 @@ impl Scene {
             }
         }
 
+        // Merge accessibility nodes. Draw paths were appended in order, so their ranges shift.
+        // Nodes that were left open in the appended scene are closed.
+        let node_offset = self.accessibility_tags.len() as u32;
+        let open_parent = self.open_accessibility_nodes.last().cloned();
+        for mut tag in scene.accessibility_tags {
+            tag.parent = match tag.parent {
+                Some(parent) => Some(AccessibilityNodeId(parent.0 + node_offset)),
+                None => open_parent,
+            };
+            if let TagExtent::DrawPaths(ref mut draw_paths) = tag.extent {
+                draw_paths.start += draw_path_base;
+                draw_paths.end += draw_path_base;
+            }
+            self.accessibility_tags.push(tag);
+        }
+        for open_node in scene.open_accessibility_nodes {
+            let tag = &mut self.accessibility_tags[(open_node.0 + node_offset) as usize];
+            if let TagExtent::DrawPaths(ref mut draw_paths) = tag.extent {
+                draw_paths.end = self.draw_paths.len() as u32;
+            }
+        }
+
+        self.stroke_time += scene.stroke_time;
+
         // Bump epoch.
         self.epoch.next();
     }
 
+    /// Rewrites the scene so that it renders faster, for content that won't change afterward.
+    ///
+    /// Paths that can't be seen are dropped: those with empty outlines, fully transparent paints
+    /// or tints, or outlines entirely outside the view box, unless their blend modes erase what's
+    /// beneath them. Then paths that are drawn the same way are moved next to one another, so
+    /// that they're drawn in fewer batches, and neighbors among them are merged into single
+    /// paths. A path is only moved past, or merged with, paths it doesn't overlap, so the picture
+    /// stays the same. Finally, clip paths that no path uses are dropped.
+    ///
+    /// Transforms are baked into outlines as paths are added, so there are no nested transforms
+    /// left to flatten. Culling to the view box assumes that the scene is built without a
+    /// transform that moves it; if the view box is empty, nothing is culled. Layers are flattened
+    /// in their current order, and the IDs of draw and clip paths change.
+    pub fn optimize(&mut self) {
+        assert!(self.open_layer.is_none(), "Can't optimize a scene while a layer is open!");
+        assert!(self.open_accessibility_nodes.is_empty(),
+                "Can't optimize a scene while an accessibility node is open!");
+        self.fix_accessibility_bounds();
+        let bounds = self.bounds;
+        let mut draw_paths: Vec<Option<DrawPath>> =
+            mem::replace(&mut self.draw_paths, vec![]).into_iter().map(Some).collect();
+        let display_list = mem::replace(&mut self.display_list, vec![]);
+        self.layers.clear();
+        self.layer_order.clear();
+        self.layer_slots.clear();
+
+        for display_item in display_list {
+            match display_item {
+                DisplayItem::DrawPaths(range) => {
+                    let visible_draw_paths: Vec<_> = (range.start.0..range.end.0).filter_map(|id| {
+                        draw_paths[id as usize].take()
+                    }).filter(|draw_path| self.draw_path_is_visible(draw_path)).collect();
+                    for draw_path in batch_draw_paths(visible_draw_paths) {
+                        self.push_draw_path(draw_path);
+                    }
+                }
+                DisplayItem::PushRenderTarget(_) | DisplayItem::PopRenderTarget => {
+                    self.display_list.push(display_item);
+                }
+            }
+        }
+
+        self.drop_unused_clip_paths();
+        self.bounds = bounds;
+        self.epoch.next();
+    }
+
+    // Returns false if the draw path is certain not to change the picture.
+    fn draw_path_is_visible(&self, draw_path: &DrawPath) -> bool {
+        if draw_path.blend_mode.is_destructive() {
+            return true;
+        }
+        if draw_path.outline.is_empty() || draw_path.tint.is_fully_transparent() ||
+                self.get_paint(draw_path.paint).is_fully_transparent() {
+            return false;
+        }
+        self.view_box.is_empty() || self.view_box.intersects(draw_path.outline.bounds())
+    }
+
+    // Removes the clip paths that neither draw paths nor other clip paths use, and renumbers the
+    // rest.
+    fn drop_unused_clip_paths(&mut self) {
+        let mut used = vec![false; self.clip_paths.len()];
+        let mut pending: Vec<ClipPathId> =
+            self.draw_paths.iter().filter_map(|draw_path| draw_path.clip_path).collect();
+        while let Some(clip_path_id) = pending.pop() {
+            if !mem::replace(&mut used[clip_path_id.0 as usize], true) {
+                pending.extend(self.clip_paths[clip_path_id.0 as usize].clip_path);
+            }
+        }
+
+        let mut clip_path_mapping = Vec::with_capacity(used.len());
+        let old_clip_paths = mem::replace(&mut self.clip_paths, vec![]);
+        for (clip_path, used) in old_clip_paths.into_iter().zip(used) {
+            if used {
+                clip_path_mapping.push(Some(ClipPathId(self.clip_paths.len() as u32)));
+                self.clip_paths.push(clip_path);
+            } else {
+                clip_path_mapping.push(None);
+            }
+        }
+
+        let remap = |clip_path_id: &mut Option<ClipPathId>| {
+            *clip_path_id = clip_path_id.and_then(|id| clip_path_mapping[id.0 as usize]);
+        };
+        self.clip_paths.iter_mut().for_each(|clip_path| remap(&mut clip_path.clip_path));
+        self.draw_paths.iter_mut().for_each(|draw_path| remap(&mut draw_path.clip_path));
+    }
+
+    /// Starts a node that tells assistive technology what the paths drawn until the matching
+    /// `pop_accessibility_node()` are, such as a run of text or a button.
+    ///
+    /// Nodes may be nested, and a node that's started while another is open becomes its child.
+    /// See `accessibility_tree()`.
+    pub fn push_accessibility_node(&mut self, role: AccessibilityRole, label: String)
+                                   -> AccessibilityNodeId {
+        let node_id = AccessibilityNodeId(self.accessibility_tags.len() as u32);
+        let first_draw_path = self.draw_paths.len() as u32;
+        self.accessibility_tags.push(AccessibilityTag {
+            role,
+            label,
+            parent: self.open_accessibility_nodes.last().cloned(),
+            extent: TagExtent::DrawPaths(first_draw_path..first_draw_path),
+        });
+        self.open_accessibility_nodes.push(node_id);
+        node_id
+    }
+
+    /// Ends the accessibility node started by the most recent `push_accessibility_node()`.
+    pub fn pop_accessibility_node(&mut self) {
+        let node_id = self.open_accessibility_nodes.pop().expect("No accessibility node is open!");
+        let draw_path_count = self.draw_paths.len() as u32;
+        if let TagExtent::DrawPaths(ref mut draw_paths) =
+                self.accessibility_tags[node_id.0 as usize].extent {
+            draw_paths.end = draw_path_count;
+        }
+    }
+
+    /// Returns the accessibility nodes of this scene, with the bounds of what they drew as they
+    /// appear on screen under `transform`, which is usually the transform of the build options.
+    ///
+    /// Bounds follow later changes to the scene: paths in hidden layers are left out, and clip
+    /// paths and replaced outlines are taken into account. Nodes that are still open cover
+    /// everything drawn so far.
+    pub fn accessibility_tree(&self, transform: &Transform2F) -> AccessibilityTree {
+        let hidden = self.hidden_draw_paths();
+        AccessibilityTree::new(&self.accessibility_tags,
+                               &self.open_accessibility_nodes,
+                               self.draw_paths.len() as u32,
+                               |range| self.visible_draw_path_bounds(range, &hidden, transform),
+                               |bounds| *transform * bounds)
+    }
+
+    // Records the scene-space bounds of each accessibility node, before its draw paths are
+    // renumbered.
+    fn fix_accessibility_bounds(&mut self) {
+        let hidden = self.hidden_draw_paths();
+        let fixed_extents: Vec<_> = self.accessibility_tags.iter().map(|tag| {
+            match tag.extent {
+                TagExtent::Fixed(bounds) => TagExtent::Fixed(bounds),
+                TagExtent::DrawPaths(ref draw_paths) => {
+                    let range = DrawPathId(draw_paths.start)..DrawPathId(draw_paths.end);
+                    let transform = Transform2F::default();
+                    TagExtent::Fixed(self.visible_draw_path_bounds(range, &hidden, &transform))
+                }
+            }
+        }).collect();
+        for (tag, extent) in self.accessibility_tags.iter_mut().zip(fixed_extents) {
+            tag.extent = extent;
+        }
+    }
+
+    // Returns, for each draw path, true if it's in a hidden layer.
+    fn hidden_draw_paths(&self) -> Vec<bool> {
+        let mut hidden = vec![false; self.draw_paths.len()];
+        for layer in self.layers.iter().filter(|layer| !layer.visible) {
+            for draw_path_id in layer.draw_path_ids() {
+                hidden[draw_path_id.0 as usize] = true;
+            }
+        }
+        hidden
+    }
+
+    fn visible_draw_path_bounds(&self,
+                                range: Range<DrawPathId>,
+                                hidden: &[bool],
+                                transform: &Transform2F)
+                                -> Option<RectF> {
+        let mut bounds: Option<RectF> = None;
+        for index in range.start.0..range.end.0 {
+            let draw_path = &self.draw_paths[index as usize];
+            if hidden[index as usize] || draw_path.outline.is_empty() {
+                continue;
+            }
+            let mut path_bounds = draw_path.outline.bounds();
+            if let Some(clip_path_id) = draw_path.clip_path {
+                let clip_bounds = self.get_clip_path(clip_path_id).outline.bounds();
+                match path_bounds.intersection(clip_bounds) {
+                    Some(clipped_bounds) => path_bounds = clipped_bounds,
+                    None => continue,
+                }
+            }
+            let path_bounds = *transform * path_bounds;
+            bounds = Some(bounds.map_or(path_bounds, |bounds| bounds.union_rect(path_bounds)));
+        }
+        bounds
+    }
+
+    /// Starts a new named layer, on top of all previously-added layers and paths.
+    ///
+    /// Everything drawn until the matching `pop_layer()` belongs to the layer, and can then be
+    /// reordered, hidden, or faded as a group without drawing it again. Layers can't be nested.
+    pub fn push_layer(&mut self, name: String) -> LayerId {
+        assert!(self.open_layer.is_none(), "Layers can't be nested!");
+        let layer_id = LayerId(self.layers.len() as u32);
+        self.layers.push(Layer::new(name));
+        self.open_layer = Some(layer_id);
+        layer_id
+    }
+
+    /// Ends the layer started by the most recent `push_layer()`.
+    pub fn pop_layer(&mut self) {
+        let layer_id = self.open_layer.take().expect("No layer is open!");
+        let layer = &mut self.layers[layer_id.0 as usize];
+        let draw_paths = &self.draw_paths;
+        layer.tint_alphas = layer.draw_path_ids().map(|draw_path_id| {
+            draw_paths[draw_path_id.0 as usize].tint.a
+        }).collect();
+
+        let slot_start = self.display_list.len();
+        if layer.visible {
+            self.display_list.extend_from_slice(&layer.display_list);
+        }
+        self.layer_slots.push(slot_start..self.display_list.len());
+        self.layer_order.push(layer_id);
+        self.apply_layer_opacity(layer_id);
+        self.epoch.next();
+    }
+
+    /// Returns the ID of the first layer with the given name, if there is one.
+    pub fn layer_id(&self, name: &str) -> Option<LayerId> {
+        self.layers
+            .iter()
+            .position(|layer| layer.name == name)
+            .map(|index| LayerId(index as u32))
+    }
+
+    /// Returns the name of the given layer.
+    #[inline]
+    pub fn layer_name(&self, layer_id: LayerId) -> &str {
+        &self.layers[layer_id.0 as usize].name
+    }
+
+    /// Returns the layers from bottom to top.
+    #[inline]
+    pub fn layer_order(&self) -> &[LayerId] {
+        &self.layer_order
+    }
+
+    /// Reorders the layers. `new_order` lists every layer, from bottom to top.
+    ///
+    /// Paths outside layers stay where they were drawn; only the layers trade places.
+    pub fn set_layer_order(&mut self, new_order: Vec<LayerId>) {
+        let mut sorted_order = new_order.clone();
+        sorted_order.sort_by_key(|layer_id| layer_id.0);
+        let mut old_sorted_order = self.layer_order.clone();
+        old_sorted_order.sort_by_key(|layer_id| layer_id.0);
+        assert_eq!(sorted_order, old_sorted_order, "The new order must list every layer once!");
+
+        self.layer_order = new_order;
+        self.rebuild_layer_slots();
+    }
+
+    /// Moves a layer so that it's at the given index from the bottom, keeping the other layers in
+    /// order.
+    pub fn move_layer(&mut self, layer_id: LayerId, new_index: usize) {
+        let mut new_order = self.layer_order.clone();
+        let old_index = new_order.iter()
+                                 .position(|&other_id| other_id == layer_id)
+                                 .expect("No layer with that ID!");
+        new_order.remove(old_index);
+        new_order.insert(new_index.min(new_order.len()), layer_id);
+        self.set_layer_order(new_order);
+    }
+
+    /// Shows or hides a layer.
+    pub fn set_layer_visible(&mut self, layer_id: LayerId, visible: bool) {
+        self.layers[layer_id.0 as usize].visible = visible;
+        self.rebuild_layer_slots();
+    }
+
+    /// Returns true if the layer is shown.
+    #[inline]
+    pub fn layer_visible(&self, layer_id: LayerId) -> bool {
+        self.layers[layer_id.0 as usize].visible
+    }
+
+    /// Sets the opacity of a layer, from 0.0 to 1.0, by scaling the tints of its paths.
+    ///
+    /// Each path is faded separately, so where paths in the layer overlap, the ones below show
+    /// through. Use a render target to fade overlapping paths as one.
+    pub fn set_layer_opacity(&mut self, layer_id: LayerId, opacity: f32) {
+        self.layers[layer_id.0 as usize].opacity = opacity.max(0.0).min(1.0);
+        self.apply_layer_opacity(layer_id);
+        self.epoch.next();
+    }
+
+    /// Returns the opacity of the layer.
+    #[inline]
+    pub fn layer_opacity(&self, layer_id: LayerId) -> f32 {
+        self.layers[layer_id.0 as usize].opacity
+    }
+
+    /// Returns the bounds of the outlines of the paths in the layer, whether or not it's shown,
+    /// or `None` if it has no paths with outlines.
+    pub fn layer_bounds(&self, layer_id: LayerId) -> Option<RectF> {
+        self.layers[layer_id.0 as usize].draw_path_ids().filter_map(|draw_path_id| {
+            let outline = &self.draw_paths[draw_path_id.0 as usize].outline;
+            if outline.is_empty() { None } else { Some(outline.bounds()) }
+        }).fold(None, |bounds: Option<RectF>, path_bounds| {
+            Some(bounds.map_or(path_bounds, |bounds| bounds.union_rect(path_bounds)))
+        })
+    }
+
+    fn apply_layer_opacity(&mut self, layer_id: LayerId) {
+        let layer = &self.layers[layer_id.0 as usize];
+        for (draw_path_id, &alpha) in layer.draw_path_ids().zip(layer.tint_alphas.iter()) {
+            let tint = &mut self.draw_paths[draw_path_id.0 as usize].tint;
+            tint.a = (alpha as f32 * layer.opacity).round() as u8;
+        }
+    }
+
+    // Rebuilds the display list after layers are reordered, shown, or hidden, copying the items
+    // between layer slots as they are and each visible layer into its new slot.
+    fn rebuild_layer_slots(&mut self) {
+        let mut display_list = Vec::with_capacity(self.display_list.len());
+        let mut layer_slots = Vec::with_capacity(self.layer_slots.len());
+        let mut next_item_index = 0;
+        for (slot, layer_id) in self.layer_slots.iter().zip(self.layer_order.iter()) {
+            display_list.extend_from_slice(&self.display_list[next_item_index..slot.start]);
+            let layer = &self.layers[layer_id.0 as usize];
+            let slot_start = display_list.len();
+            if layer.visible {
+                display_list.extend_from_slice(&layer.display_list);
+            }
+            layer_slots.push(slot_start..display_list.len());
+            next_item_index = slot.end;
+        }
+        display_list.extend_from_slice(&self.display_list[next_item_index..]);
+
+        self.display_list = display_list;
+        self.layer_slots = layer_slots;
+        self.epoch.next();
+    }
+
+    /// Defines a symbol: a scene that can be drawn many times with `push_symbol_instance()`.
+    ///
+    /// The symbol scene is kept as-is; nothing is drawn until it is instanced. See the `symbol`
+    /// module for how the mode affects the output.
+    pub fn define_symbol(&mut self, symbol: Scene, mode: SymbolMode) -> SymbolId {
+        let symbol_id = SymbolId(self.symbols.len() as u32);
+        self.symbols.push(Symbol::new(symbol, mode));
+        symbol_id
+    }
+
+    /// Draws a previously-defined symbol on top of all previously-added paths.
+    pub fn push_symbol_instance(&mut self, symbol_id: SymbolId, instance: &SymbolInstance) {
+        let mode = self.symbols[symbol_id.0 as usize].mode;
+        let stamped = match (mode, instance.uniform_scale()) {
+            (SymbolMode::Stamp { buckets_per_octave }, Some(scale)) if buckets_per_octave > 0 => {
+                self.push_stamped_symbol_instance(symbol_id, instance, scale, buckets_per_octave)
+            }
+            _ => false,
+        };
+        if !stamped {
+            self.push_expanded_symbol_instance(symbol_id, instance);
+        }
+    }
+
+    /// Draws symbols at the vertices of a path, turned to follow it if requested, on top of all
+    /// previously-added paths.
+    ///
+    /// `outline` is in scene space, and is usually the path the markers decorate. See the `marker`
+    /// module for where markers are placed.
+    pub fn push_path_markers(&mut self, outline: &Outline, markers: &PathMarkers) {
+        for (symbol_id, instance) in marker::marker_instances(outline, markers) {
+            self.push_symbol_instance(symbol_id, &instance);
+        }
+    }
+
+    fn push_expanded_symbol_instance(&mut self, symbol_id: SymbolId, instance: &SymbolInstance) {
+        let mut symbol = self.symbols[symbol_id.0 as usize].scene.clone();
+        symbol.apply_paint_overrides(&instance.paint_overrides);
+        symbol.transform_contents(&instance.transform);
+
+        let (first_draw_path, first_clip_path) = (self.draw_paths.len(), self.clip_paths.len());
+        self.append_scene(symbol);
+
+        if instance.tint != ColorU::white() {
+            for draw_path in &mut self.draw_paths[first_draw_path..] {
+                draw_path.tint = multiply_colors(draw_path.tint, instance.tint);
+            }
+        }
+
+        // Clip the paths that the symbol itself left unclipped; the rest are clipped by clip
+        // paths that are now clipped in turn.
+        if let Some(clip_path_id) = instance.clip_path {
+            for draw_path in &mut self.draw_paths[first_draw_path..] {
+                draw_path.clip_path = draw_path.clip_path.or(Some(clip_path_id));
+            }
+            for clip_path in &mut self.clip_paths[first_clip_path..] {
+                clip_path.clip_path = clip_path.clip_path.or(Some(clip_path_id));
+            }
+        }
+    }
+
+    // Returns false if the symbol is too large to be stamped at this scale.
+    fn push_stamped_symbol_instance(&mut self,
+                                    symbol_id: SymbolId,
+                                    instance: &SymbolInstance,
+                                    scale: f32,
+                                    buckets_per_octave: u32)
+                                    -> bool {
+        let translation = instance.transform.translation();
+        let key = StampKey::new(scale, translation, buckets_per_octave, &instance.paint_overrides);
+        let stamp = match self.symbols[symbol_id.0 as usize].stamps.get(&key) {
+            Some(&stamp) => stamp,
+            None => {
+                let stamp = self.create_stamp(symbol_id, &key);
+                self.symbols[symbol_id.0 as usize].stamps.insert(key, stamp);
+                stamp
+            }
+        };
+        let stamp = match stamp {
+            None => return false,
+            Some(stamp) => stamp,
+        };
+
+        // Map stamp pixels back to symbol space, then into place.
+        let ratio = scale / stamp.scale;
+        let transform = Transform2F::from_translation(translation) *
+            Transform2F::from_scale(ratio) *
+            Transform2F::from_translation(stamp.origin - stamp.subpixel_offset);
+        let mut pattern = Pattern::from_render_target(stamp.render_target, stamp.size);
+        pattern.apply_transform(transform);
+        pattern.set_smoothing_enabled(ratio != 1.0);
+        let paint_id = self.push_paint(&Paint::from_pattern(pattern));
+
+        let outline = Outline::from_rect(RectF::new(Vector2F::zero(), stamp.size.to_f32()));
+        let mut draw_path = DrawPath::new(outline.transformed(&transform), paint_id);
+        draw_path.set_clip_path(instance.clip_path);
+        draw_path.set_tint(instance.tint);
+        self.push_draw_path(draw_path);
+        true
+    }
+
+    fn create_stamp(&mut self, symbol_id: SymbolId, key: &StampKey) -> Option<Stamp> {
+        let symbol = &self.symbols[symbol_id.0 as usize].scene;
+        let (scale, subpixel_offset) = (key.scale(), key.subpixel_offset());
+        let transform = Transform2F::from_translation(subpixel_offset) *
+            Transform2F::from_scale(scale);
+        let rect = (transform * symbol.bounds).round_out();
+        let size = rect.size().to_i32();
+        if size.x() <= 0 || size.y() <= 0 || size.x() > MAX_STAMP_SIZE ||
+                size.y() > MAX_STAMP_SIZE {
+            return None;
+        }
+
+        let mut stamp_scene = symbol.clone();
+        stamp_scene.apply_paint_overrides(&key.paint_overrides);
+        stamp_scene.transform_contents(&(Transform2F::from_translation(-rect.origin()) *
+                                         transform));
+
+        let render_target = self.push_render_target(RenderTarget::new(size, String::new()));
+        self.append_scene(stamp_scene);
+        self.pop_render_target();
+        Some(Stamp { render_target, size, origin: rect.origin(), scale, subpixel_offset })
+    }
+
+    fn apply_paint_overrides(&mut self, paint_overrides: &[(PaintId, Paint)]) {
+        for &(paint_id, ref paint) in paint_overrides {
+            self.palette.set_paint(paint_id, paint);
+            self.palette.unname_paint(paint_id);
+        }
+    }
+
+    // Transforms all paths and paints, in preparation for appending this scene to another.
+    fn transform_contents(&mut self, transform: &Transform2F) {
+        for draw_path in &mut self.draw_paths {
+            draw_path.outline.transform(transform);
+        }
+        for clip_path in &mut self.clip_paths {
+            clip_path.outline.transform(transform);
+        }
+        for paint_index in 0..self.palette.paints.len() {
+            let paint_id = PaintId(paint_index as u16);
+            let mut paint = self.palette.paints[paint_index].clone();
+            paint.apply_transform(transform);
+            if paint != self.palette.paints[paint_index] {
+                self.palette.set_paint(paint_id, &paint);
+                self.palette.unname_paint(paint_id);
+            }
+        }
+        self.bounds = *transform * self.bounds;
+    }
+
     #[inline]
     pub(crate) fn build_paint_info(&mut self,
                                    texture_manager: &mut PaintTextureManager,
                                    render_transform: Transform2F)
                                    -> PaintInfo {
-        self.palette.build_paint_info(texture_manager, render_transform)
+        let tints = self.draw_paths.iter().map(|draw_path| (draw_path.paint, draw_path.tint));
+        self.palette.build_paint_info(texture_manager, render_transform, tints)
+    }
+
+    // Returns the texture metadata entry that the tiles of the given draw path refer to.
+    #[inline]
+    pub(crate) fn paint_metadata_id(&self, draw_path: &DrawPath) -> u16 {
+        self.palette.metadata_id(draw_path.paint, draw_path.tint)
     }
 
     /// Defines a new paint, which specifies how paths are to be filled or stroked. Returns a paint
@@ -194,6 +775,44 @@ impl Scene {
         paint_id
     }
 
+    /// Defines a paint under the given name and returns its ID.
+    ///
+    /// Unlike `push_paint()`, named paints are never deduplicated with other paints, so they can
+    /// later be replaced via `set_paint()` or `apply_named_palette()` without affecting any other
+    /// draw paths. If a paint with this name already exists, it is replaced and its ID returned.
+    pub fn push_named_paint(&mut self, name: String, paint: &Paint) -> PaintId {
+        let paint_id = self.palette.push_named_paint(name, paint);
+        self.epoch.next();
+        paint_id
+    }
+
+    /// Returns the ID of the paint previously defined with the given name, if any.
+    #[inline]
+    pub fn named_paint_id(&self, name: &str) -> Option<PaintId> {
+        self.palette.named_paint_id(name)
+    }
+
+    /// Replaces the paint with the given ID.
+    ///
+    /// Every draw path referencing this paint will use the new paint the next time the scene is
+    /// built. Because paints are deduplicated, this also affects draw paths that were pushed with
+    /// an identical, but separately-created, paint; use `push_named_paint()` to avoid this.
+    ///
+    /// Scene geometry is not invalidated by this call.
+    #[inline]
+    pub fn set_paint(&mut self, paint_id: PaintId, new_paint: &Paint) {
+        self.palette.set_paint(paint_id, new_paint)
+    }
+
+    /// Replaces every named paint in this scene that has an entry in the given palette.
+    ///
+    /// Names in the palette that don't correspond to any paint in this scene are ignored. As with
+    /// `set_paint()`, scene geometry is not invalidated, so this is a cheap way to switch themes.
+    #[inline]
+    pub fn apply_named_palette(&mut self, named_palette: &NamedPalette) {
+        self.palette.apply_named_palette(named_palette)
+    }
+
     /// Returns a rectangle that should enclose all objects in the scene.
     ///
     /// FIXME(pcwalton): Is this really needed?
@@ -224,6 +843,42 @@ impl Scene {
         self.epoch.next();
     }
 
+    /// Returns how antialiased edge coverage maps to alpha in this scene.
+    #[inline]
+    pub fn coverage_curve(&self) -> CoverageCurve {
+        self.coverage_curve
+    }
+
+    /// Changes how antialiased edge coverage maps to alpha when alpha tiles are resolved.
+    ///
+    /// This can make thin or diagonal strokes read heavier or crisper. Paths drawn without
+    /// antialiasing are unaffected.
+    #[inline]
+    pub fn set_coverage_curve(&mut self, new_coverage_curve: CoverageCurve) {
+        self.coverage_curve = new_coverage_curve;
+        self.epoch.next();
+    }
+
+    // Returns the outline that the clip path fills. For an inverted clip path, that's the outline
+    // inside a rectangle enclosing everything drawn in the scene, wound the other way.
+    pub(crate) fn clip_path_outline<'a>(&self, clip_path: &'a ClipPath) -> Cow<'a, Outline> {
+        if !clip_path.inverted {
+            return Cow::Borrowed(&clip_path.outline);
+        }
+
+        let enclosing_rect = self.bounds.union_rect(clip_path.outline.bounds()).dilate(1.0);
+        let mut enclosing_contour = Contour::from_rect(enclosing_rect);
+        let enclosing_orientation = Orientation::from_contour(&enclosing_contour);
+        if clip_path.fill_rule == FillRule::Winding &&
+                Orientation::from_outline(&clip_path.outline) == enclosing_orientation {
+            enclosing_contour.reverse();
+        }
+
+        let mut outline = clip_path.outline.clone();
+        outline.push_contour(enclosing_contour);
+        Cow::Owned(outline)
+    }
+
     #[allow(deprecated)]
     pub(crate) fn apply_render_options(&self,
                                        original_outline: &Outline,
@@ -296,8 +951,10 @@ impl Scene {
         SceneBuilder::new(self, &prepared_options, sink).build(executor)
     }
 
+    /// Returns the high-level drawing commands: which ranges of draw paths go to which render
+    /// targets.
     #[inline]
-    pub(crate) fn display_list(&self) -> &[DisplayItem] {
+    pub fn display_list(&self) -> &[DisplayItem] {
         &self.display_list
     }
 
@@ -311,6 +968,13 @@ impl Scene {
         &self.clip_paths
     }
 
+    // Returns the number of lines and curves in all of this scene's draw and clip paths.
+    pub(crate) fn segment_count(&self) -> usize {
+        let draw_outlines = self.draw_paths.iter().map(|draw_path| &draw_path.outline);
+        let clip_outlines = self.clip_paths.iter().map(|clip_path| &clip_path.outline);
+        draw_outlines.chain(clip_outlines).map(outline_segment_count).sum()
+    }
+
     /// Returns the number of draw paths in this scene.
     #[inline]
     pub fn draw_path_count(&self) -> u32 {
@@ -323,6 +987,15 @@ impl Scene {
         &self.draw_paths[draw_path_id.0 as usize]
     }
 
+    /// Replaces the outline of a draw path, keeping its paint, clip, and place in the scene.
+    ///
+    /// The scene bounds grow to enclose the new outline but never shrink.
+    pub fn set_draw_path_outline(&mut self, draw_path_id: DrawPathId, outline: Outline) {
+        self.bounds = self.bounds.union_rect(outline.bounds());
+        self.draw_paths[draw_path_id.0 as usize].outline = outline;
+        self.epoch.next();
+    }
+
     /// Returns the clip path with the given ID.
     #[inline]
     pub fn get_clip_path(&self, clip_path_id: ClipPathId) -> &ClipPath {
@@ -341,6 +1014,20 @@ impl Scene {
         self.id
     }
 
+    /// Returns the CPU time spent converting strokes to fills to draw this scene, which is
+    /// reported in `FrameStats::stroke_time`.
+    #[inline]
+    pub fn stroke_time(&self) -> Duration {
+        self.stroke_time
+    }
+
+    /// Adds to the CPU time spent converting strokes to fills. Front ends that stroke paths call
+    /// this so that the time shows up in the frame statistics.
+    #[inline]
+    pub fn add_stroke_time(&mut self, time: Duration) {
+        self.stroke_time += time;
+    }
+
     #[inline]
     pub(crate) fn epoch(&self) -> SceneEpoch {
         self.epoch
@@ -353,15 +1040,8 @@ impl Scene {
                                    executor: E)
                                    -> Vec<RenderCommand>
                                    where E: Executor {
-        let commands = Arc::new(Mutex::new(vec![]));
-        let commands_for_listener = commands.clone();
-        let listener = RenderCommandListener::new(Box::new(move |command| {
-            commands_for_listener.lock().unwrap().push(command)
-        }));
-        let mut sink = SceneSink::new(listener, renderer.mode().level);
-        self.build(build_options, &mut sink, &executor);
-        let mut commands = commands.lock().unwrap();
-        mem::replace(&mut *commands, vec![])
+        let level = renderer.mode().level;
+        self.build_commands(level, build_options, executor, PaintTextureManager::new()).0
     }
 
     /// A convenience method to build a scene and send the resulting commands to the given
@@ -371,11 +1051,39 @@ impl Scene {
                                   build_options: BuildOptions,
                                   executor: E)
                                   where E: Executor {
-        let commands = self.build_into_vector(renderer, build_options, executor);
+        // The renderer keeps the paint textures of the scenes built into it, so that images and
+        // gradient ramps that earlier scenes uploaded aren't uploaded again.
+        let level = renderer.mode().level;
+        let texture_manager = mem::replace(&mut renderer.paint_texture_manager,
+                                           PaintTextureManager::new());
+        let (commands, texture_manager) =
+            self.build_commands(level, build_options, executor, texture_manager);
+        renderer.paint_texture_manager = texture_manager;
         renderer.begin_scene();
         commands.into_iter().for_each(|command| renderer.render_command(&command));
         renderer.end_scene();
     }
+
+    // Builds the scene into a vector of commands, allocating paint textures with
+    // `texture_manager`, which is returned afterward.
+    fn build_commands<E>(&mut self,
+                         level: RendererLevel,
+                         build_options: BuildOptions,
+                         executor: E,
+                         texture_manager: PaintTextureManager)
+                         -> (Vec<RenderCommand>, PaintTextureManager)
+                         where E: Executor {
+        let commands = Arc::new(Mutex::new(vec![]));
+        let commands_for_listener = commands.clone();
+        let listener = RenderCommandListener::new(Box::new(move |command| {
+            commands_for_listener.lock().unwrap().push(command)
+        }));
+        let mut sink = SceneSink::new(listener, level);
+        sink.paint_texture_manager = texture_manager;
+        self.build(build_options, &mut sink, &executor);
+        let mut commands = commands.lock().unwrap();
+        (mem::replace(&mut *commands, vec![]), sink.paint_texture_manager)
+    }
 }
 
 /// Receives render commands and delivers them to a `RenderCommandListener`.
@@ -436,6 +1144,57 @@ impl<'a> SceneSink<'a> {
     }
 }
 
+// Returns the number of lines and curves in an outline, counting each closed contour's closing
+// line.
+pub(crate) fn outline_segment_count(outline: &Outline) -> usize {
+    outline.contours().iter().map(|contour| {
+        (0..contour.len()).filter(|&point_index| {
+            !contour.flags_of(point_index).intersects(PointFlags::CONTROL_POINT_0 |
+                                                      PointFlags::CONTROL_POINT_1)
+        }).count()
+    }).sum()
+}
+
+// Moves draw paths next to earlier ones that are drawn the same way, where no path in between
+// overlaps them, and merges them with those that they don't overlap either. See
+// `Scene::optimize()`.
+fn batch_draw_paths(draw_paths: Vec<DrawPath>) -> Vec<DrawPath> {
+    // Each group is a draw path and its bounds, grown to take antialiasing into account.
+    let mut groups: Vec<(DrawPath, RectF)> = Vec::with_capacity(draw_paths.len());
+    for draw_path in draw_paths {
+        let bounds = draw_path.outline.bounds().dilate(OPTIMIZE_OVERLAP_MARGIN);
+        let segment_count = outline_segment_count(&draw_path.outline);
+
+        // Find the latest group drawn the same way that the path can move next to.
+        let (mut group_index, mut merge) = (groups.len(), false);
+        let search_start = groups.len().saturating_sub(MAX_OPTIMIZE_SEARCH_DISTANCE);
+        for index in (search_start..groups.len()).rev() {
+            let (ref group_path, group_bounds) = groups[index];
+            let overlaps = group_bounds.intersects(bounds);
+            if group_path.is_drawn_like(&draw_path) {
+                merge = !overlaps && outline_segment_count(&group_path.outline) + segment_count <=
+                    MAX_MERGED_SEGMENT_COUNT;
+                group_index = if merge { index } else { index + 1 };
+                break;
+            }
+            if overlaps {
+                break;
+            }
+        }
+
+        if merge {
+            let (ref mut group_path, ref mut group_bounds) = groups[group_index];
+            for contour in draw_path.outline.into_contours() {
+                group_path.outline.push_contour(contour);
+            }
+            *group_bounds = group_bounds.union_rect(bounds);
+        } else {
+            groups.insert(group_index, (draw_path, bounds));
+        }
+    }
+    groups.into_iter().map(|(draw_path, _)| draw_path).collect()
+}
+
 /// A path drawn to the output or to a render target.
 #[derive(Clone, Debug)]
 pub struct DrawPath {
@@ -449,12 +1208,91 @@ pub struct DrawPath {
     pub fill_rule: FillRule,
     /// How to blend this path with everything below it.
     pub blend_mode: BlendMode,
+    /// A color that the paint is multiplied by when this path is composited.
+    ///
+    /// Paths that share a paint but not a tint still share the paint's texture, so this is a
+    /// cheap way to draw many differently-colored copies of one gradient or pattern. White leaves
+    /// the paint unchanged.
+    pub tint: ColorU,
+    /// True if this path's antialiased edge coverage is doubled, up to full coverage.
+    ///
+    /// This avoids the hairline seams that analytic antialiasing otherwise leaves where two paths
+    /// share an edge, such as adjacent polygons on a map: where two watertight paths meet, the
+    /// pixels on the shared edge are always fully covered by one or both of them. The tradeoff is
+    /// that outer edges look up to half a pixel bolder and less smooth.
+    pub watertight: bool,
+    /// False if this path's edges are drawn without antialiasing, so that each pixel is either
+    /// covered or not.
+    ///
+    /// This keeps pixel-aligned artwork such as grid lines and pixel art crisp alongside
+    /// antialiased paths in the same scene. A pixel is covered if the path covers at least half
+    /// of it, including any clip path.
+    pub antialiased: bool,
+    /// How much effort to spend rasterizing this path, relative to the scene's build options.
+    pub quality: PathQuality,
     /// The name of this path, for debugging.
     ///
     /// Pass the empty string (which does not allocate) if debugging is not needed.
     pub name: String,
 }
 
+/// How accurately a draw path is rasterized.
+///
+/// This lets cheap background decoration and pristine foreground artwork share one scene.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PathQuality {
+    /// Flattens curves with four times the scene's tolerance and draws edges without
+    /// antialiasing.
+    Low,
+    /// Flattens curves with the scene's tolerance. This is the default.
+    Medium,
+    /// Flattens curves with a quarter of the scene's tolerance.
+    High,
+}
+
+/// Maps the antialiased coverage of path edges to alpha.
+///
+/// Coverage is first raised to the power of `1 / gamma`, and then pushed toward a smoothstep
+/// curve by `contrast`. Full and zero coverage are left alone, so only edges change.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CoverageCurve {
+    /// Values above 1.0 make edges heavier, and values below 1.0 make them lighter. Must be
+    /// positive. The default is 1.0.
+    pub gamma: f32,
+    /// From -1.0 to 1.0. Positive values sharpen edges, and negative values soften them. The
+    /// default is 0.0.
+    pub contrast: f32,
+}
+
+impl Default for CoverageCurve {
+    #[inline]
+    fn default() -> CoverageCurve {
+        CoverageCurve { gamma: 1.0, contrast: 0.0 }
+    }
+}
+
+impl CoverageCurve {
+    /// Returns true if this curve leaves coverage unchanged.
+    #[inline]
+    pub fn is_identity(&self) -> bool {
+        *self == CoverageCurve::default()
+    }
+
+    /// Maps a coverage value from 0.0 to 1.0 to alpha, as the tile shaders do.
+    pub fn apply(&self, coverage: f32) -> f32 {
+        let [inverse_gamma, contrast] = self.to_shader_params();
+        let coverage = coverage.max(0.0).min(1.0).powf(inverse_gamma);
+        let smoothed = coverage * coverage * (3.0 - 2.0 * coverage);
+        (coverage + (smoothed - coverage) * contrast).max(0.0).min(1.0)
+    }
+
+    // The parameters passed to the tile shaders: the reciprocal of the gamma, and the contrast,
+    // both clamped to their valid ranges.
+    pub(crate) fn to_shader_params(&self) -> [f32; 2] {
+        [1.0 / self.gamma.max(f32::EPSILON), self.contrast.max(-1.0).min(1.0)]
+    }
+}
+
 /// Describes a path that can be used to clip other paths.
 #[derive(Clone, Debug)]
 pub struct ClipPath {
@@ -466,6 +1304,13 @@ pub struct ClipPath {
     pub clip_path: Option<ClipPathId>,
     /// How to fill this path (winding or even-odd).
     pub fill_rule: FillRule,
+    /// True if this clip path keeps everything outside its outline instead of inside.
+    ///
+    /// An inverted clip is filled as though its outline were surrounded by a rectangle enclosing
+    /// the whole scene and wound the opposite way. So with the winding fill rule, parts of the
+    /// outline that wind around more than once are clipped out only where the winding number is
+    /// exactly one; even-odd outlines are always inverted exactly.
+    pub inverted: bool,
     /// The name of this clip path, for debugging.
     ///
     /// Pass the empty string (which does not allocate) if debugging is not needed.
@@ -512,7 +1357,8 @@ impl DrawPath {
     /// Creates a new draw path with the given outline and paint.
     ///
     /// Initially, there is no clip path, the fill rule is set to winding, the blend mode is set to
-    /// source-over, and the path has no name.
+    /// source-over, the tint is white, the path isn't watertight, the path is antialiased, the
+    /// quality is medium, and the path has no name.
     #[inline]
     pub fn new(outline: Outline, paint: PaintId) -> DrawPath {
         DrawPath {
@@ -521,6 +1367,10 @@ impl DrawPath {
             clip_path: None,
             fill_rule: FillRule::Winding,
             blend_mode: BlendMode::SrcOver,
+            tint: ColorU::white(),
+            watertight: false,
+            antialiased: true,
+            quality: PathQuality::Medium,
             name: String::new(),
         }
     }
@@ -573,6 +1423,46 @@ impl DrawPath {
         self.blend_mode = new_blend_mode
     }
 
+    // Returns true if this path is drawn with the same paint and state as the other one, so
+    // that the two can be drawn in the same batch, or as one path.
+    fn is_drawn_like(&self, other: &DrawPath) -> bool {
+        self.paint == other.paint && self.clip_path == other.clip_path &&
+            self.fill_rule == other.fill_rule && self.blend_mode == other.blend_mode &&
+            self.tint == other.tint && self.watertight == other.watertight &&
+            self.antialiased == other.antialiased && self.quality == other.quality
+    }
+
+    /// Sets the color that the paint is multiplied by when this path is composited.
+    #[inline]
+    pub fn set_tint(&mut self, new_tint: ColorU) {
+        self.tint = new_tint
+    }
+
+    /// Sets whether this path's edge coverage is doubled, to avoid seams with adjacent paths.
+    #[inline]
+    pub fn set_watertight(&mut self, watertight: bool) {
+        self.watertight = watertight
+    }
+
+    /// Sets whether this path's edges are antialiased. Aliased paths have crisp, pixel-snapped
+    /// edges.
+    #[inline]
+    pub fn set_antialiased(&mut self, antialiased: bool) {
+        self.antialiased = antialiased
+    }
+
+    /// Returns true if this path's edges are antialiased, taking its quality into account.
+    #[inline]
+    pub fn is_antialiased(&self) -> bool {
+        self.antialiased && self.quality != PathQuality::Low
+    }
+
+    /// Sets how accurately this path is rasterized.
+    #[inline]
+    pub fn set_quality(&mut self, new_quality: PathQuality) {
+        self.quality = new_quality
+    }
+
     /// Assigns a name to this path, for debugging.
     #[inline]
     pub fn set_name(&mut self, new_name: String) {
@@ -580,14 +1470,40 @@ impl DrawPath {
     }
 }
 
+impl Default for PathQuality {
+    #[inline]
+    fn default() -> PathQuality {
+        PathQuality::Medium
+    }
+}
+
+impl PathQuality {
+    // The factor that the scene's flattening tolerance is multiplied by for paths of this
+    // quality.
+    #[inline]
+    pub(crate) fn flattening_tolerance_scale(self) -> f32 {
+        match self {
+            PathQuality::Low => 4.0,
+            PathQuality::Medium => 1.0,
+            PathQuality::High => 0.25,
+        }
+    }
+}
+
 impl ClipPath {
     /// Creates a new clip path with the given outline.
     ///
-    /// Initially, there is no nested clip path, the fill rule is set to winding, and the clip path
-    /// has no name.
+    /// Initially, there is no nested clip path, the fill rule is set to winding, the clip path
+    /// isn't inverted, and the clip path has no name.
     #[inline]
     pub fn new(outline: Outline) -> ClipPath {
-        ClipPath { outline, clip_path: None, fill_rule: FillRule::Winding, name: String::new() }
+        ClipPath {
+            outline,
+            clip_path: None,
+            fill_rule: FillRule::Winding,
+            inverted: false,
+            name: String::new(),
+        }
     }
 
     /// Returns the outline of this clip path, which defines its vector commands.
@@ -621,6 +1537,13 @@ impl ClipPath {
         self.fill_rule = new_fill_rule
     }
 
+    /// Sets whether this clip path keeps everything outside its outline instead of inside, as for
+    /// spotlight and cutout overlays.
+    #[inline]
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted
+    }
+
     /// Assigns a name to this clip path, for debugging.
     #[inline]
     pub fn set_name(&mut self, new_name: String) {
@@ -670,3 +1593,68 @@ impl PathId {
         DrawPathId(self.0)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{ClipPath, ClipPathId, CoverageCurve, DrawPath, DrawPathId, Scene};
+    use crate::paint::{Paint, PaintId};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+
+    fn square(x: f32, y: f32) -> Outline {
+        Outline::from_rect(RectF::new(vec2f(x, y), vec2f(10.0, 10.0)))
+    }
+
+    #[test]
+    fn test_optimize_drops_reorders_and_merges_paths() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), vec2f(100.0, 100.0)));
+        let red = scene.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let blue = scene.push_paint(&Paint::from_color(ColorU::new(0, 0, 255, 255)));
+        let clear = scene.push_paint(&Paint::transparent_black());
+        scene.push_clip_path(ClipPath::new(square(80.0, 80.0)));
+        let clip_path = scene.push_clip_path(ClipPath::new(square(0.0, 0.0)));
+
+        scene.push_draw_path(DrawPath::new(square(0.0, 0.0), red));
+        scene.push_draw_path(DrawPath::new(square(20.0, 0.0), blue));
+        scene.push_draw_path(DrawPath::new(square(40.0, 0.0), red));
+        scene.push_draw_path(DrawPath::new(square(60.0, 0.0), clear));
+        scene.push_draw_path(DrawPath::new(square(200.0, 200.0), red));
+        scene.push_draw_path(DrawPath::new(square(0.0, 0.0), blue));
+        let mut clipped_path = DrawPath::new(square(5.0, 5.0), red);
+        clipped_path.set_clip_path(Some(clip_path));
+        scene.push_draw_path(clipped_path);
+        scene.optimize();
+
+        // The second red square joins the first, and the second blue square joins the first,
+        // which it doesn't overlap. The clipped square overlaps the blue ones, so it stays last.
+        let draw_paths: Vec<(PaintId, usize)> = (0..scene.draw_path_count()).map(|index| {
+            let draw_path = scene.get_draw_path(DrawPathId(index));
+            (draw_path.paint, draw_path.outline.contours().len())
+        }).collect();
+        assert_eq!(draw_paths, vec![(red, 2), (blue, 2), (red, 1)]);
+        assert_eq!(scene.clip_paths().len(), 1);
+        assert_eq!(scene.get_draw_path(DrawPathId(2)).clip_path, Some(ClipPathId(0)));
+        assert_eq!(scene.display_list().len(), 1);
+    }
+
+    #[test]
+    fn test_coverage_curve_only_changes_edges() {
+        let identity = CoverageCurve::default();
+        assert!(identity.is_identity());
+        assert_eq!(identity.apply(0.25), 0.25);
+
+        let heavier = CoverageCurve { gamma: 2.0, contrast: 0.0 };
+        assert!((heavier.apply(0.25) - 0.5).abs() < 1.0e-6);
+        let sharper = CoverageCurve { gamma: 1.0, contrast: 1.0 };
+        assert!(sharper.apply(0.25) < 0.25 && sharper.apply(0.75) > 0.75);
+        let softer = CoverageCurve { gamma: 1.0, contrast: -1.0 };
+        assert!(softer.apply(0.25) > 0.25 && softer.apply(0.75) < 0.75);
+        for curve in [heavier, sharper, softer] {
+            assert_eq!(curve.apply(0.0), 0.0);
+            assert_eq!(curve.apply(1.0), 1.0);
+        }
+    }
+}
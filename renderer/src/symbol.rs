@@ -0,0 +1,258 @@
+// pathfinder/renderer/src/symbol.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Sub-scenes that are defined once and instantiated many times, like SVG `<symbol>` and `<use>`.
+//!
+//! A symbol is an ordinary `Scene` handed to `Scene::define_symbol()`. Each call to
+//! `Scene::push_symbol_instance()` then draws it with a transform, optional replacements for some
+//! of its paints, and an optional clip.
+//!
+//! In `SymbolMode::Expand`, every instance copies the symbol's paths into the scene, so output is
+//! exact at any transform. In `SymbolMode::Stamp`, instances whose transform is a uniform scale
+//! plus a translation share rasterized copies of the symbol ("stamps"). Scales are bucketed
+//! logarithmically and translations are snapped to quarter pixels, and all instances that land in
+//! the same bucket with the same paint overrides draw the same render target. This turns the cost
+//! of 10,000 identical markers into the cost of one marker plus 10,000 textured rectangles. Other
+//! transforms fall back to expansion.
+//!
+//! Instances can also be tinted. A tint multiplies the instance's colors when it's composited, so
+//! differently-colored instances share paints and stamps.
+
+use crate::paint::{Paint, PaintId};
+use crate::scene::{ClipPathId, Scene};
+use hashbrown::HashMap;
+use pathfinder_color::ColorU;
+use pathfinder_content::render_target::RenderTargetId;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f};
+
+/// The largest stamp, in device pixels along either axis, that will be allocated. Instances that
+/// would need a larger one are expanded instead.
+pub(crate) const MAX_STAMP_SIZE: i32 = 2048;
+
+// How finely stamp translations are quantized, in subdivisions per pixel.
+const SUBPIXEL_STEPS: f32 = 4.0;
+
+/// The ID of a symbol, unique to the scene that defined it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SymbolId(pub u32);
+
+/// How instances of a symbol are to be drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SymbolMode {
+    /// Every instance copies the symbol's paths into the scene, transformed.
+    Expand,
+    /// Instances that are only scaled and translated reuse rasterized stamps of the symbol.
+    Stamp {
+        /// The number of scale buckets per doubling of scale.
+        ///
+        /// Higher values give sharper results at the cost of more render targets. Scale factors
+        /// within a bucket are stretched from a single stamp.
+        buckets_per_octave: u32,
+    },
+}
+
+/// One placement of a symbol.
+#[derive(Clone, Debug)]
+pub struct SymbolInstance {
+    /// The transform from symbol space to scene space.
+    pub transform: Transform2F,
+    /// Paints, by their ID in the symbol's scene, to be replaced for this instance.
+    pub paint_overrides: Vec<(PaintId, Paint)>,
+    /// A clip path, in the instancing scene, applied to the whole instance.
+    pub clip_path: Option<ClipPathId>,
+    /// A color that the whole instance is multiplied by.
+    ///
+    /// Unlike paint overrides, tints don't create new paints, and stamped instances with different
+    /// tints share stamps.
+    pub tint: ColorU,
+}
+
+#[derive(Clone)]
+pub(crate) struct Symbol {
+    pub(crate) scene: Scene,
+    pub(crate) mode: SymbolMode,
+    pub(crate) stamps: HashMap<StampKey, Option<Stamp>>,
+}
+
+/// Identifies the instances that can share a stamp.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct StampKey {
+    scale_bucket: i32,
+    buckets_per_octave: u32,
+    subpixel_steps: [i32; 2],
+    pub(crate) paint_overrides: Vec<(PaintId, Paint)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Stamp {
+    pub(crate) render_target: RenderTargetId,
+    pub(crate) size: Vector2I,
+    /// The position of the stamp's upper left corner in scaled, offset symbol space.
+    pub(crate) origin: Vector2F,
+    pub(crate) scale: f32,
+    pub(crate) subpixel_offset: Vector2F,
+}
+
+impl SymbolInstance {
+    /// Creates a new instance with the given transform, no paint overrides, no clip, and a white
+    /// tint.
+    #[inline]
+    pub fn new(transform: Transform2F) -> SymbolInstance {
+        SymbolInstance {
+            transform,
+            paint_overrides: vec![],
+            clip_path: None,
+            tint: ColorU::white(),
+        }
+    }
+
+    /// Replaces the symbol's paint with the given ID for this instance only.
+    pub fn set_paint_override(&mut self, paint_id: PaintId, paint: Paint) {
+        match self.paint_overrides.iter_mut().find(|&&mut (id, _)| id == paint_id) {
+            Some(&mut (_, ref mut old_paint)) => *old_paint = paint,
+            None => self.paint_overrides.push((paint_id, paint)),
+        }
+    }
+
+    /// Clips this instance to a clip path previously defined in the instancing scene.
+    #[inline]
+    pub fn set_clip_path(&mut self, new_clip_path: Option<ClipPathId>) {
+        self.clip_path = new_clip_path
+    }
+
+    /// Sets the color that the whole instance is multiplied by.
+    #[inline]
+    pub fn set_tint(&mut self, new_tint: ColorU) {
+        self.tint = new_tint
+    }
+
+    // Returns the scale factor if this instance's transform is a uniform, positive scale plus a
+    // translation.
+    pub(crate) fn uniform_scale(&self) -> Option<f32> {
+        let scale = self.transform.m11();
+        if self.transform.m12() == 0.0 && self.transform.m21() == 0.0 &&
+                self.transform.m22() == scale && scale > 0.0 && scale.is_finite() {
+            Some(scale)
+        } else {
+            None
+        }
+    }
+}
+
+impl Symbol {
+    #[inline]
+    pub(crate) fn new(scene: Scene, mode: SymbolMode) -> Symbol {
+        Symbol { scene, mode, stamps: HashMap::new() }
+    }
+}
+
+impl StampKey {
+    pub(crate) fn new(scale: f32,
+                      translation: Vector2F,
+                      buckets_per_octave: u32,
+                      paint_overrides: &[(PaintId, Paint)])
+                      -> StampKey {
+        let scale_bucket = (scale.log2() * buckets_per_octave as f32).round() as i32;
+        let steps = (translation * SUBPIXEL_STEPS).round().to_i32();
+        let subpixel_steps = [
+            steps.x().rem_euclid(SUBPIXEL_STEPS as i32),
+            steps.y().rem_euclid(SUBPIXEL_STEPS as i32),
+        ];
+        StampKey {
+            scale_bucket,
+            buckets_per_octave,
+            subpixel_steps,
+            paint_overrides: paint_overrides.to_vec(),
+        }
+    }
+
+    /// The scale at which the stamp for this key is rasterized.
+    #[inline]
+    pub(crate) fn scale(&self) -> f32 {
+        (self.scale_bucket as f32 / self.buckets_per_octave as f32).exp2()
+    }
+
+    /// The fractional pixel offset at which the stamp for this key is rasterized.
+    #[inline]
+    pub(crate) fn subpixel_offset(&self) -> Vector2F {
+        let [x, y] = self.subpixel_steps;
+        vec2f(x as f32, y as f32) * (1.0 / SUBPIXEL_STEPS)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StampKey, SymbolInstance, SymbolMode};
+    use crate::paint::{Paint, PaintId};
+    use crate::scene::{DisplayItem, DrawPath, DrawPathId, Scene};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::vec2f;
+
+    fn square_symbol() -> Scene {
+        let mut symbol = Scene::new();
+        let paint = symbol.push_paint(&Paint::from_color(ColorU::new(255, 0, 0, 255)));
+        let square = Outline::from_rect(RectF::new(vec2f(0.0, 0.0), vec2f(10.0, 10.0)));
+        symbol.push_draw_path(DrawPath::new(square, paint));
+        symbol
+    }
+
+    #[test]
+    fn test_stamp_key_buckets() {
+        let key = StampKey::new(1.0, vec2f(10.3, -2.6), 4, &[]);
+        assert_eq!(key, StampKey::new(1.05, vec2f(3.24, 7.4), 4, &[]));
+        assert_ne!(key, StampKey::new(1.2, vec2f(3.24, 7.4), 4, &[]));
+        assert_eq!(key.scale(), 1.0);
+        assert_eq!(key.subpixel_offset(), vec2f(0.25, 0.5));
+        assert_eq!(StampKey::new(3.9, vec2f(0.0, 0.0), 1, &[]).scale(), 4.0);
+    }
+    #[test]
+    fn test_expanded_instances() {
+        let mut scene = Scene::new();
+        let symbol = scene.define_symbol(square_symbol(), SymbolMode::Expand);
+        let mut instance = SymbolInstance::new(Transform2F::from_translation(vec2f(20.0, 0.0)));
+        let blue = Paint::from_color(ColorU::new(0, 0, 255, 255));
+        instance.set_paint_override(PaintId(0), blue.clone());
+        scene.push_symbol_instance(symbol, &instance);
+        scene.push_symbol_instance(symbol, &SymbolInstance::new(Transform2F::from_scale(2.0)));
+
+        assert_eq!(scene.draw_path_count(), 2);
+        let first = scene.get_draw_path(DrawPathId(0));
+        assert_eq!(first.outline.bounds(), RectF::new(vec2f(20.0, 0.0), vec2f(10.0, 10.0)));
+        assert_eq!(*scene.get_paint(first.paint), blue);
+        let second = scene.get_draw_path(DrawPathId(1));
+        assert_eq!(second.outline.bounds(), RectF::new(vec2f(0.0, 0.0), vec2f(20.0, 20.0)));
+        assert_eq!(scene.get_paint(second.paint).base_color(), ColorU::new(255, 0, 0, 255));
+    }
+
+    #[test]
+    fn test_stamped_instances_share_render_targets() {
+        let mut scene = Scene::new();
+        let mode = SymbolMode::Stamp { buckets_per_octave: 2 };
+        let symbol = scene.define_symbol(square_symbol(), mode);
+        for &(scale, x) in &[(1.0, 0.0), (1.02, 50.0), (1.0, 100.25), (3.0, 0.0)] {
+            let transform = Transform2F::from_translation(vec2f(x, 0.0)) *
+                Transform2F::from_scale(scale);
+            scene.push_symbol_instance(symbol, &SymbolInstance::new(transform));
+        }
+
+        // Two scale buckets and two subpixel offsets make three stamps.
+        let render_target_count = scene.display_list().iter().filter(|item| {
+            matches!(**item, DisplayItem::PushRenderTarget(_))
+        }).count();
+        assert_eq!(render_target_count, 3);
+
+        // Each stamp draws the square once, and each instance draws one rectangle.
+        assert_eq!(scene.draw_path_count(), 7);
+    }
+}
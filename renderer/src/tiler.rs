@@ -13,7 +13,8 @@
 
 use crate::builder::{BuiltPath, BuiltPathBinCPUData, BuiltPathData, ObjectBuilder, SceneBuilder};
 use crate::gpu::options::RendererLevel;
-use crate::gpu_data::AlphaTileId;
+use crate::gpu_data::{AlphaTileId, Fill};
+use crate::occlusion;
 use crate::options::PrepareMode;
 use crate::scene::{ClipPathId, PathId};
 use crate::tiles::{TILE_HEIGHT, TILE_WIDTH, TilingPathInfo};
@@ -23,17 +24,21 @@ use pathfinder_content::outline::{ContourIterFlags, Outline};
 use pathfinder_content::segment::Segment;
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f, vec2i};
 use pathfinder_simd::default::{F32x2, U32x2};
 use std::f32::NEG_INFINITY;
+use std::ops::Range;
 
-const FLATTENING_TOLERANCE: f32 = 0.25;
+// How many segments are tiled between checks for cancellation.
+const CANCELLATION_CHECK_SEGMENT_COUNT: usize = 256;
 
 pub(crate) struct Tiler<'a, 'b, 'c, 'd> {
     scene_builder: &'a SceneBuilder<'b, 'a, 'c, 'd>,
     pub(crate) object_builder: ObjectBuilder,
     outline: &'a Outline,
     clip_path: Option<&'a BuiltPath>,
+    flattening_tolerance: f32,
 }
 
 impl<'a, 'b, 'c, 'd> Tiler<'a, 'b, 'c, 'd> {
@@ -45,7 +50,8 @@ impl<'a, 'b, 'c, 'd> Tiler<'a, 'b, 'c, 'd> {
                       prepare_mode: &PrepareMode,
                       clip_path_id: Option<ClipPathId>,
                       built_clip_paths: &'a [BuiltPath],
-                      path_info: TilingPathInfo)
+                      path_info: TilingPathInfo,
+                      flattening_tolerance: f32)
                       -> Tiler<'a, 'b, 'c, 'd> {
         let bounds = outline.bounds().intersection(view_box).unwrap_or(RectF::default());
 
@@ -62,7 +68,7 @@ impl<'a, 'b, 'c, 'd> Tiler<'a, 'b, 'c, 'd> {
                                                 clip_path_id,
                                                 &path_info);
 
-        Tiler { scene_builder, object_builder, outline, clip_path }
+        Tiler { scene_builder, object_builder, outline, clip_path, flattening_tolerance }
     }
 
     pub(crate) fn generate_tiles(&mut self) {
@@ -81,15 +87,75 @@ impl<'a, 'b, 'c, 'd> Tiler<'a, 'b, 'c, 'd> {
     }
 
     fn generate_fills(&mut self) {
-        debug_assert_eq!(self.scene_builder.sink.renderer_level, RendererLevel::D3D9);
+        debug_assert_eq!(self.scene_builder.renderer_level, RendererLevel::D3D9);
 
+        if self.generate_rect_fills() {
+            return;
+        }
+
+        let mut segment_index = 0;
+        for contour in self.outline.contours() {
+            for segment in contour.iter(ContourIterFlags::empty()) {
+                if segment_index % CANCELLATION_CHECK_SEGMENT_COUNT == 0 &&
+                        self.scene_builder.is_cancelled() {
+                    return;
+                }
+                process_segment(&segment,
+                                self.flattening_tolerance,
+                                self.scene_builder,
+                                &mut self.object_builder);
+                segment_index += 1;
+            }
+        }
+    }
+
+    // Outlines made only of axis-aligned rectangles, which are most of what UI draws, don't need
+    // lattice clipping. Returns false without doing anything if the outline isn't one of those.
+    fn generate_rect_fills(&mut self) -> bool {
+        let mut rects = Vec::with_capacity(self.outline.contours().len());
+        for contour in self.outline.contours() {
+            match occlusion::transformed_rect_corners(contour, &Transform2F::default()) {
+                None => return false,
+                Some(corners) => rects.push(corners),
+            }
+        }
+        for corners in rects {
+            self.object_builder.add_rect(self.scene_builder, corners);
+        }
+        true
+    }
+
+    // Generates fills for just the given range of the outline's segments, for a chunk of a path
+    // that's split up to tile in parallel. The chunks are combined with
+    // `generate_tiles_from_chunks()`.
+    pub(crate) fn generate_chunk_fills(&mut self, segment_range: Range<usize>) {
+        debug_assert_eq!(self.scene_builder.renderer_level, RendererLevel::D3D9);
+
+        self.object_builder.local_alpha_tile_ids = true;
+        let mut segment_index = 0;
         for contour in self.outline.contours() {
             for segment in contour.iter(ContourIterFlags::empty()) {
-                process_segment(&segment, self.scene_builder, &mut self.object_builder);
+                if segment_range.contains(&segment_index) {
+                    let chunk_segment_index = segment_index - segment_range.start;
+                    if chunk_segment_index % CANCELLATION_CHECK_SEGMENT_COUNT == 0 &&
+                            self.scene_builder.is_cancelled() {
+                        return;
+                    }
+                    process_segment(&segment,
+                                    self.flattening_tolerance,
+                                    self.scene_builder,
+                                    &mut self.object_builder);
+                }
+                segment_index += 1;
             }
         }
     }
 
+    pub(crate) fn generate_tiles_from_chunks(&mut self, chunks: Vec<(BuiltPath, Vec<Fill>)>) {
+        self.object_builder.merge_chunks(self.scene_builder, chunks);
+        self.prepare_tiles();
+    }
+
     fn prepare_tiles(&mut self) {
         // Don't do this here if the GPU will do it.
         let (backdrops, tiles, clips) = match self.object_builder.built_path.data {
@@ -164,23 +230,33 @@ impl<'a, 'b, 'c, 'd> Tiler<'a, 'b, 'c, 'd> {
 }
 
 fn process_segment(segment: &Segment,
+                   tolerance: f32,
                    scene_builder: &SceneBuilder,
                    object_builder: &mut ObjectBuilder) {
+    flatten_segment(segment, tolerance, &mut |line_segment| {
+        process_line_segment(line_segment, scene_builder, object_builder)
+    });
+}
+
+/// Flattens a segment into lines exactly as the tiler does, calling `emit` for each line.
+pub(crate) fn flatten_segment(segment: &Segment,
+                              tolerance: f32,
+                              emit: &mut dyn FnMut(LineSegment2F)) {
     // TODO(pcwalton): Stop degree elevating.
     if segment.is_quadratic() {
         let cubic = segment.to_cubic();
-        return process_segment(&cubic, scene_builder, object_builder);
+        return flatten_segment(&cubic, tolerance, emit);
     }
 
     if segment.is_line() ||
-            (segment.is_cubic() && segment.as_cubic_segment().is_flat(FLATTENING_TOLERANCE)) {
-        return process_line_segment(segment.baseline, scene_builder, object_builder);
+            (segment.is_cubic() && segment.as_cubic_segment().is_flat(tolerance)) {
+        return emit(segment.baseline);
     }
 
     // TODO(pcwalton): Use a smarter flattening algorithm.
     let (prev, next) = segment.split(0.5);
-    process_segment(&prev, scene_builder, object_builder);
-    process_segment(&next, scene_builder, object_builder);
+    flatten_segment(&prev, tolerance, emit);
+    flatten_segment(&next, tolerance, emit);
 }
 
 // This is the meat of the technique. It implements the fast lattice-clipping algorithm from
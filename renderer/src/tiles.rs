@@ -9,8 +9,8 @@
 // except according to those terms.
 
 use crate::gpu_data::{TILE_CTRL_MASK_0_SHIFT, TILE_CTRL_MASK_EVEN_ODD};
-use crate::gpu_data::{TILE_CTRL_MASK_WINDING, TileObjectPrimitive};
-use crate::paint::PaintId;
+use crate::gpu_data::{TILE_CTRL_ALIASED, TILE_CTRL_MASK_WINDING, TILE_CTRL_WATERTIGHT};
+use crate::gpu_data::TileObjectPrimitive;
 use pathfinder_content::effects::BlendMode;
 use pathfinder_content::fill::FillRule;
 use pathfinder_geometry::rect::{RectF, RectI};
@@ -27,9 +27,11 @@ pub(crate) enum TilingPathInfo {
 
 #[derive(Clone, Copy)]
 pub(crate) struct DrawTilingPathInfo {
-    pub(crate) paint_id: PaintId,
+    pub(crate) metadata_id: u16,
     pub(crate) blend_mode: BlendMode,
     pub(crate) fill_rule: FillRule,
+    pub(crate) watertight: bool,
+    pub(crate) antialiased: bool,
 }
 
 impl TilingPathInfo {
@@ -54,6 +56,12 @@ impl TilingPathInfo {
                         ctrl |= (TILE_CTRL_MASK_WINDING << TILE_CTRL_MASK_0_SHIFT) as u8
                     }
                 }
+                if draw_tiling_path_info.watertight {
+                    ctrl |= TILE_CTRL_WATERTIGHT as u8;
+                }
+                if !draw_tiling_path_info.antialiased {
+                    ctrl |= TILE_CTRL_ALIASED as u8;
+                }
             }
             TilingPathInfo::Clip => {}
         }
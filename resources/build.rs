@@ -9,19 +9,28 @@
 // except according to those terms.
 
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
 
+// FNV-1a. This must match `manifest::hash_resource()`.
+fn hash_resource(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 fn main() {
     let out_dir = env::var_os("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("manifest.rs");
     let mut dest = File::create(dest_path).unwrap();
     let cwd = env::current_dir().unwrap();
+    let mut version = None;
+    let mut hashes = vec![];
 
     writeln!(&mut dest, "// Generated by `pathfinder/resources/build.rs`. Do not edit!\n").unwrap();
     writeln!(&mut dest,
-             "pub static RESOURCES: &'static [(&'static str, &'static [u8])] = &[").unwrap();
+             "pub static RESOURCES: &[(&str, &[u8])] = &[").unwrap();
 
     let mut add_manifest = |path: &str| {
         let src = BufReader::new(File::open(path).unwrap());
@@ -31,11 +40,18 @@ fn main() {
             if line.is_empty() || line.starts_with("#") {
                 continue;
             }
+            if let Some(version_number) = line.strip_prefix("version ") {
+                version = Some(version_number.trim().parse::<u32>().unwrap_or_else(|_| {
+                    panic!("`{}` has a malformed version line: `{}`", path, line)
+                }));
+                continue;
+            }
     
             let escaped_path = line.escape_default().to_string();
             let mut full_path = cwd.clone();
             full_path.push(line);
             let escaped_full_path = full_path.to_str().unwrap().escape_default().to_string();
+            hashes.push((escaped_path.clone(), hash_resource(&fs::read(&full_path).unwrap())));
     
             writeln!(&mut dest,
                      "    (\"{}\", include_bytes!(\"{}\")),",
@@ -54,6 +70,20 @@ fn main() {
 
     writeln!(&mut dest, "];").unwrap();
 
+    let hashes_path = Path::new(&out_dir).join("hashes.rs");
+    let mut hashes_dest = File::create(hashes_path).unwrap();
+    writeln!(&mut hashes_dest, "// Generated by `pathfinder/resources/build.rs`. Do not edit!\n")
+        .unwrap();
+    writeln!(&mut hashes_dest,
+             "pub const RESOURCES_VERSION: u32 = {};",
+             version.expect("`MANIFEST` has no version!")).unwrap();
+    writeln!(&mut hashes_dest,
+             "pub static RESOURCE_HASHES: &[(&str, u64)] = &[").unwrap();
+    for (escaped_path, hash) in hashes {
+        writeln!(&mut hashes_dest, "    (\"{}\", {:#018x}),", escaped_path, hash).unwrap();
+    }
+    writeln!(&mut hashes_dest, "];").unwrap();
+
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=MANIFEST");
 }
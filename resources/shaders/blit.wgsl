@@ -3,7 +3,8 @@
 struct Globals {
     uDestRect: vec4<f32>,        // [x_min, y_min, x_max, y_max] in pixel coordinates
     uFramebufferSize: vec2<f32>, // [width, height] of the backend framebuffer
-    uPad0: vec2<f32>,
+    uUnpremultiply: f32,         // 1.0 to convert premultiplied alpha to straight alpha
+    uPad0: f32,
 };
 
 @group(0) @binding(0) var<uniform> globals: Globals;
@@ -48,5 +49,9 @@ fn fs_main(@location(0) vTexCoord: vec2<f32>) -> @location(0) vec4<f32> {
     if (vTexCoord.x > 1.0 || vTexCoord.y > 1.0) {
         discard;
     }
-    return textureSample(uSrc, smp, vTexCoord);
+    let color = textureSample(uSrc, smp, vTexCoord);
+    if (globals.uUnpremultiply > 0.5 && color.a > 0.0) {
+        return vec4<f32>(color.rgb / color.a, color.a);
+    }
+    return color;
 }
\ No newline at end of file
@@ -11,7 +11,7 @@ struct BatchUniform {
     uPathCount: i32,
     uLastBatchSegmentIndex: i32,
     uMaxMicrolineCount: i32,
-    uPad1: i32,
+    uTolerance: f32,
 };
 
 @group(0) @binding(5) var<uniform> uUniform0: TransformUniform;
@@ -30,7 +30,7 @@ struct DiceMetadata {
     // x: global path ID
     // y: first global segment index
     // z: first batch segment index
-    // w: unused
+    // w: flattening tolerance scale, as f32 bits
     data: array<vec4<u32>>,
 };
 @group(1) @binding(1) var<storage, read> bDiceMetadata: DiceMetadata;
@@ -62,7 +62,6 @@ const FLAGS_PATH_INDEX_CURVE_IS_CUBIC: u32 = 0x40000000u;
 
 const BIN_INDIRECT_DRAW_PARAMS_MICROLINE_COUNT_INDEX: u32 = 3u;
 
-const TOLERANCE: f32 = 0.25;
 const MICROLINE_LENGTH: f32 = 16.0;
 
 /// Save the obtained microline.
@@ -91,7 +90,7 @@ fn curveIsFlat(baseline: vec4<f32>, ctrl: vec4<f32>) -> bool {
     var uv = vec4<f32>(3.0) * ctrl - vec4<f32>(2.0) * baseline - baseline.zwxy;
     uv = uv * uv;
     uv = max(uv, uv.zwxy);
-    return uv.x + uv.y <= 16.0 * TOLERANCE * TOLERANCE;
+    return uv.x + uv.y <= 16.0 * uUniform1.uTolerance * uUniform1.uTolerance;
 }
 
 // 修复点：WGSL不支持 out 传参，此处重构为标准的指针 ptr 传参
@@ -180,6 +179,7 @@ fn cs_main(
     let diceMetadata = bDiceMetadata.data[batchPathIndex];
     let firstGlobalSegmentIndexInPath = diceMetadata.y;
     let firstBatchSegmentIndexInPath = diceMetadata.z;
+    let tolerance = uUniform1.uTolerance * bitcast<f32>(diceMetadata.w);
     let globalSegmentIndex = batchSegmentIndex - firstBatchSegmentIndexInPath + firstGlobalSegmentIndexInPath;
 
     let inputIndices = bInputIndices.data[globalSegmentIndex];
@@ -216,7 +216,7 @@ fn cs_main(
         }
         let bound = vec2<f32>(6.0) * max(abs(ctrl.zw - 2.0 * ctrl.xy + baseline.xy),
                                          abs(baseline.zw - 2.0 * ctrl.zw + ctrl.xy));
-        segmentCountF = sqrt(length(bound) / (8.0 * TOLERANCE));
+        segmentCountF = sqrt(length(bound) / (8.0 * tolerance));
     } else {
         segmentCountF = length(baseline.zw - baseline.xy) / MICROLINE_LENGTH;
     }
@@ -22,6 +22,7 @@ struct Globals {
     uFramebufferTileSize: vec2<i32>,
     uMaskTextureSize0: vec2<f32>,
     uColorTextureSize0: vec2<f32>,
+    uCoverageCurve: vec4<f32>, // Reciprocal of the coverage gamma in x, coverage contrast in y.
 };
 
 @group(0) @binding(0) var<uniform> globals: Globals;
@@ -61,6 +62,9 @@ const TILE_CTRL_MASK_EVEN_ODD: u32 = 0x2u;
 
 const TILE_CTRL_MASK_0_SHIFT: u32 = 0u;
 
+const TILE_CTRL_WATERTIGHT: i32 = 0x4;
+const TILE_CTRL_ALIASED: i32 = 0x8;
+
 const COMBINER_CTRL_COLOR_COMBINE_MASK: i32 = 0x3;
 const COMBINER_CTRL_COLOR_COMBINE_SRC_IN: i32 = 0x1;
 const COMBINER_CTRL_COLOR_COMBINE_DEST_IN: i32 = 0x2;
@@ -89,6 +93,12 @@ const COMBINER_CTRL_COMPOSITE_SATURATION: i32 = 0xd;
 const COMBINER_CTRL_COMPOSITE_COLOR: i32 = 0xe;
 const COMBINER_CTRL_COMPOSITE_LUMINOSITY: i32 = 0xf;
 
+// The `TextureSamplingFlags` of seamless patterns.
+const SAMPLING_REPEAT_U: i32 = 0x1;
+const SAMPLING_REPEAT_V: i32 = 0x2;
+const SAMPLING_NEAREST_MIN: i32 = 0x4;
+const SAMPLING_NEAREST_MAG: i32 = 0x8;
+
 const COMBINER_CTRL_COLOR_FILTER_SHIFT: u32 = 4u;
 const COMBINER_CTRL_COLOR_COMBINE_SHIFT: u32 = 8u;
 const COMBINER_CTRL_COMPOSITE_SHIFT: u32 = 10u;
@@ -404,6 +414,36 @@ fn filterNone(colorTexCoord: vec2<f32>, colorTexture: texture_2d<f32>) -> vec4<f
     return textureSampleLevel(colorTexture, smp, colorTexCoord, 0.0); [cite: 89]
 }
 
+// Wraps or clamps texel coordinates to the texture, per axis.
+fn wrapTexel(coord: vec2<i32>, size: vec2<i32>, repeat: vec2<bool>) -> vec2<i32> {
+    let wrapped = ((coord % size) + size) % size;
+    let clamped = clamp(coord, vec2<i32>(0), size - 1);
+    return select(clamped, wrapped, repeat);
+}
+
+// Samples a seamless pattern, wrapping and filtering here rather than in the sampler. See the
+// D3D9 tile shader.
+fn sampleSeamless(colorTexCoord: vec2<f32>, colorTexture: texture_2d<f32>, samplingFlags: i32) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(colorTexture));
+    let repeat = vec2<bool>((samplingFlags & SAMPLING_REPEAT_U) != 0, (samplingFlags & SAMPLING_REPEAT_V) != 0);
+    let sizeF = vec2<f32>(size);
+    let wrappedTexCoord = select(colorTexCoord, fract(colorTexCoord), repeat);
+    let texelCoord = wrappedTexCoord * sizeF - 0.5;
+
+    if ((samplingFlags & (SAMPLING_NEAREST_MIN | SAMPLING_NEAREST_MAG)) != 0) {
+        return textureLoad(colorTexture, wrapTexel(vec2<i32>(floor(texelCoord + 0.5)), size, repeat), 0);
+    }
+
+    let texelOrigin = floor(texelCoord);
+    let t = texelCoord - texelOrigin;
+    let origin = vec2<i32>(texelOrigin);
+    let c00 = textureLoad(colorTexture, wrapTexel(origin, size, repeat), 0);
+    let c10 = textureLoad(colorTexture, wrapTexel(origin + vec2<i32>(1, 0), size, repeat), 0);
+    let c01 = textureLoad(colorTexture, wrapTexel(origin + vec2<i32>(0, 1), size, repeat), 0);
+    let c11 = textureLoad(colorTexture, wrapTexel(origin + vec2<i32>(1, 1), size, repeat), 0);
+    return mix(mix(c00, c10, t.x), mix(c01, c11, t.x), t.y);
+}
+
 fn filterColor(
     colorTexCoord: vec2<f32>,
     colorTexture: texture_2d<f32>,
@@ -564,6 +604,12 @@ fn sampleMask(maskAlpha: f32, maskTexture: texture_2d<f32>, maskTextureSize: vec
     return min(maskAlpha, coverage); [cite: 134]
 }
 
+// Maps antialiased coverage to alpha per the scene's coverage curve. See `CoverageCurve`.
+fn applyCoverageCurve(coverage: f32) -> f32 {
+    let curved = pow(clamp(coverage, 0.0, 1.0), globals.uCoverageCurve.x);
+    return clamp(mix(curved, smoothstep(0.0, 1.0, curved), globals.uCoverageCurve.y), 0.0, 1.0);
+}
+
 // Main helper function
 fn calculateColor(
     fragCoord: vec2<f32>,
@@ -583,6 +629,8 @@ fn calculateColor(
     maskTexCoord0: vec3<f32>,
     colorTexCoord0: vec2<f32>,
     baseColor: vec4<f32>,
+    color0Tint: vec3<f32>,
+    color0Sampling: vec2<f32>,
     tileCtrl: i32
 ) -> vec4<f32> {
     // Sample mask. [cite: 134]
@@ -590,6 +638,19 @@ fn calculateColor(
     var maskAlpha: f32 = 1.0; [cite: 135]
     maskAlpha = sampleMask(maskAlpha, maskTexture0, maskTextureSize0, maskTexCoord0, maskCtrl0); [cite: 135]
 
+    // Map edge coverage to alpha.
+    maskAlpha = applyCoverageCurve(maskAlpha);
+
+    // Double edge coverage so that adjacent paths leave no seam.
+    if ((tileCtrl & TILE_CTRL_WATERTIGHT) != 0) {
+        maskAlpha = min(maskAlpha * 2.0, 1.0);
+    }
+
+    // Round coverage to none or full for paths drawn without antialiasing.
+    if ((tileCtrl & TILE_CTRL_ALIASED) != 0) {
+        maskAlpha = step(0.5, maskAlpha);
+    }
+
     // Sample color. [cite: 136]
     var color: vec4<f32> = baseColor; [cite: 136]
 
@@ -600,12 +661,18 @@ fn calculateColor(
         // Get color filter flag. [cite: 137]
         let color0Filter: i32 = (ctrl >> COMBINER_CTRL_COLOR_FILTER_SHIFT) & COMBINER_CTRL_FILTER_MASK; [cite: 138]
 
-        // Do filtering. [cite: 138]
-        let color0: vec4<f32> = filterColor(
-            colorTexCoord0, colorTexture0, gammaLUT, colorTextureSize0, fragCoord, framebufferSize, [cite: 139]
-            filterParams0, filterParams1, filterParams2, filterParams3, filterParams4, color0Filter [cite: 139]
-        );
-        color = combineColor0(color, color0, color0Combine); [cite: 140]
+        // Do filtering. Seamless patterns are only sampled specially without a filter.
+        var color0: vec4<f32>;
+        if (color0Sampling.x != 0.0 && color0Filter == 0) {
+            color0 = sampleSeamless(colorTexCoord0, colorTexture0, i32(color0Sampling.y));
+        } else {
+            color0 = filterColor(
+                colorTexCoord0, colorTexture0, gammaLUT, colorTextureSize0, fragCoord, framebufferSize, [cite: 139]
+                filterParams0, filterParams1, filterParams2, filterParams3, filterParams4, color0Filter [cite: 139]
+            );
+        }
+        let tintedColor0: vec4<f32> = vec4<f32>(color0.rgb * color0Tint, color0.a);
+        color = combineColor0(color, tintedColor0, color0Combine); [cite: 140]
     }
 
     // Apply mask. [cite: 141]
@@ -633,6 +700,8 @@ fn computeTileVaryings(
     textureMetadataSize: vec2<f32>,
     outColorTexCoord0: ptr<function, vec2<f32>>,
     outBaseColor: ptr<function, vec4<f32>>,
+    outColor0Tint: ptr<function, vec3<f32>>,
+    outColor0Sampling: ptr<function, vec2<f32>>,
     outFilterParams0: ptr<function, vec4<f32>>,
     outFilterParams1: ptr<function, vec4<f32>>,
     outFilterParams2: ptr<function, vec4<f32>>,
@@ -651,10 +720,14 @@ fn computeTileVaryings(
     let filterParams3: vec4<f32>   = fetchUnscaled(textureMetadata, metadataScale, metadataEntryCoord, 6); [cite: 151]
     let filterParams4: vec4<f32>   = fetchUnscaled(textureMetadata, metadataScale, metadataEntryCoord, 7); [cite: 152]
     let extra: vec4<f32>           = fetchUnscaled(textureMetadata, metadataScale, metadataEntryCoord, 8); [cite: 152]
+    let colorSampling: vec4<f32>   = fetchUnscaled(textureMetadata, metadataScale, metadataEntryCoord, 9);
 
+    // The offset is split in two to make up for the precision of half floats.
     let matrix: mat2x2<f32> = mat2x2<f32>(colorTexMatrix0.xy, colorTexMatrix0.zw); [cite: 153]
-    *outColorTexCoord0 = matrix * position + colorTexOffsets.xy; [cite: 153]
+    *outColorTexCoord0 = matrix * position + colorTexOffsets.xy + colorTexOffsets.zw;
     *outBaseColor = baseColor; [cite: 153]
+    *outColor0Tint = extra.yzw;
+    *outColor0Sampling = colorSampling.xy;
     *outFilterParams0 = filterParams0; [cite: 153]
     *outFilterParams1 = filterParams1; [cite: 153]
     *outFilterParams2 = filterParams2; [cite: 153]
@@ -742,6 +815,8 @@ fn cs_main(
             let maskTexCoord0: vec3<f32> = vec3<f32>(vec2<f32>(maskTileCoord) + vec2<f32>(tileSubCoord), f32(backdrop)); [cite: 176]
             var colorTexCoord0: vec2<f32>; [cite: 177]
             var baseColor: vec4<f32>; [cite: 177]
+            var color0Tint: vec3<f32>;
+            var color0Sampling: vec2<f32>;
             var filterParams0: vec4<f32>; [cite: 177]
             var filterParams1: vec4<f32>; [cite: 177]
             var filterParams2: vec4<f32>; [cite: 177]
@@ -751,7 +826,7 @@ fn cs_main(
 
             computeTileVaryings(
                 fragCoord, i32(colorEntry), uTextureMetadata, globals.uTextureMetadataSize,
-                &colorTexCoord0, &baseColor, &filterParams0, &filterParams1, &filterParams2, &filterParams3, &filterParams4, &ctrl
+                &colorTexCoord0, &baseColor, &color0Tint, &color0Sampling, &filterParams0, &filterParams1, &filterParams2, &filterParams3, &filterParams4, &ctrl
             ); [cite: 178, 179]
 
             // FIXME(pcwalton): The `uColorTexture0` below is a placeholder and needs to be replaced! [cite: 180]
@@ -760,7 +835,8 @@ fn cs_main(
                 fragCoord, uColorTexture0, uMaskTexture0, uColorTexture0, uGammaLUT,
                 globals.uColorTextureSize0, globals.uMaskTextureSize0,
                 filterParams0, filterParams1, filterParams2, filterParams3, filterParams4,
-                globals.uFramebufferSize, ctrl, maskTexCoord0, colorTexCoord0, baseColor, tileCtrl
+                globals.uFramebufferSize, ctrl, maskTexCoord0, colorTexCoord0, baseColor, color0Tint, color0Sampling,
+                tileCtrl
             ); [cite: 181, 182, 183]
 
             destColors[subY] = destColors[subY] * (1.0 - srcColor.a) + srcColor; [cite: 184]
@@ -0,0 +1,27 @@
+// pathfinder/resources/shaders/d3d9/checkerboard_paint.wgsl
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// The paint server behind `CheckerboardPaint`. Appended to `paint_server.wgsl`.
+
+struct PaintServerUniforms {
+    colorA: vec4<f32>,
+    colorB: vec4<f32>,
+    // The corner of a cell of color A, in pixels.
+    origin: vec2<f32>,
+    cellSize: vec2<f32>,
+};
+
+fn paintServerColor(position: vec2<f32>, baseColor: vec4<f32>) -> vec4<f32> {
+    let cell = vec2<i32>(floor((position - uPaintServer.origin) / uPaintServer.cellSize));
+    if (((cell.x + cell.y) & 1) == 0) {
+        return uPaintServer.colorA;
+    }
+    return uPaintServer.colorB;
+}
@@ -0,0 +1,172 @@
+// pathfinder/resources/shaders/d3d9/custom_tile_prelude.wgsl
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Prepended to user-supplied custom tile shaders (see `pathfinder_renderer::gpu::d3d9::custom`).
+//
+// The user source must define:
+//
+//     @fragment fn fs_main(input: CustomTileVarying) -> @location(0) vec4<f32>
+//
+// returning a premultiplied color. Call `customTileCoverage(input)` to get the anti-aliased
+// coverage of the path at the fragment, in [0, 1], with any rounded clip or ellipse applied.
+//
+// Bind group interface:
+//
+//     @group(0) @binding(0)  CustomTileGlobals uniform
+//     @group(1) @binding(0)  texture metadata (paint colors, one row of 10 texels per paint)
+//     @group(1) @binding(1)  Z buffer (used by the vertex shader for occlusion culling)
+//     @group(1) @binding(2)  alpha tile coverage (mask) texture
+//     @group(1) @binding(3)  filtering sampler
+//     @group(2) @binding(0)  user uniform buffer, if one was supplied
+
+struct CustomTileGlobals {
+    uTileSize: vec2<f32>,
+    uTextureMetadataSize: vec2<i32>,
+    uZBufferSize: vec2<i32>,
+    uMaskTextureSize0: vec2<f32>,
+    uColorTextureSize0: vec2<f32>,
+    uFramebufferSize: vec2<f32>,
+    uTransform: mat4x4<f32>,
+    // The rounded clip rectangle, as (min, max), in pixels.
+    uClipRect: vec4<f32>,
+    // The rounded clip radius in x; y is nonzero if the clip applies, and z if the ellipse does.
+    uClipParams: vec4<f32>,
+    // The rectangle that the path's ellipse is inscribed in, as (min, max), in pixels.
+    uEllipseRect: vec4<f32>,
+    // The reciprocal of the scene's coverage gamma in x, and its coverage contrast in y.
+    uCoverageCurve: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> globals: CustomTileGlobals;
+@group(1) @binding(0) var uTextureMetadata: texture_2d<f32>;
+@group(1) @binding(1) var uZBuffer: texture_2d<f32>;
+@group(1) @binding(2) var uMaskTexture0: texture_2d<f32>;
+@group(1) @binding(3) var smp: sampler;
+
+struct CustomTileInput {
+    @location(0) aTileOffset: vec2<u32>,
+    @location(1) aTileOrigin: vec2<i32>,
+    @location(2) aMaskTexCoord0: vec4<u32>,
+    @location(3) aPathIndex: i32,
+    @location(4) aCtrlBackdrop: vec2<i32>,
+    @location(5) aMetadataIndex: u32,
+};
+
+struct CustomTileVarying {
+    @builtin(position) position: vec4<f32>,
+    // Mask texel coordinates in xy, backdrop in z.
+    @location(0) vMaskTexCoord0: vec3<f32>,
+    // Position in the framebuffer, in pixels.
+    @location(1) vFramebufferCoord: vec2<f32>,
+    // The base color of the paint.
+    @location(2) vBaseColor: vec4<f32>,
+    @location(3) @interpolate(flat) vTileCtrl: i32,
+    @location(4) @interpolate(flat) vPathIndex: i32,
+};
+
+const CUSTOM_TILE_CTRL_MASK_MASK: i32 = 0x3;
+const CUSTOM_TILE_CTRL_MASK_WINDING: i32 = 0x1;
+
+@vertex
+fn vs_main(input: CustomTileInput) -> CustomTileVarying {
+    var out: CustomTileVarying;
+
+    let tileOffset = vec2<f32>(input.aTileOffset);
+    let position = (vec2<f32>(input.aTileOrigin) + tileOffset) * globals.uTileSize;
+
+    // Cull tiles hidden under an opaque tile, as the built-in tile shader does.
+    let zValue = textureLoad(uZBuffer, input.aTileOrigin, 0);
+    let unpackedZ = i32(u32(zValue.r * 255.0) | (u32(zValue.g * 255.0) << 8u) |
+                        (u32(zValue.b * 255.0) << 16u) | (u32(zValue.a * 255.0) << 24u));
+    if (input.aPathIndex < unpackedZ ||
+            (input.aCtrlBackdrop.y == 0 && input.aMaskTexCoord0.w != 0u)) {
+        out.position = vec4<f32>(0.0);
+        return out;
+    }
+
+    let maskTileCoord = vec2<u32>(input.aMaskTexCoord0.x,
+                                  input.aMaskTexCoord0.y + 256u * input.aMaskTexCoord0.z);
+    let maskTexCoord0 = (vec2<f32>(maskTileCoord) + tileOffset) * globals.uTileSize;
+
+    let metadataCoord = vec2<i32>(i32(input.aMetadataIndex % 128u * 10u),
+                                  i32(input.aMetadataIndex / 128u));
+
+    out.vMaskTexCoord0 = vec3<f32>(maskTexCoord0, f32(input.aCtrlBackdrop.y));
+    out.vFramebufferCoord = position;
+    out.vBaseColor = textureLoad(uTextureMetadata, metadataCoord + vec2<i32>(2, 0), 0);
+    out.vTileCtrl = input.aCtrlBackdrop.x;
+    out.vPathIndex = input.aPathIndex;
+    out.position = globals.uTransform * vec4<f32>(position, 0.0, 1.0);
+    return out;
+}
+
+// Returns the coverage of the batch's rounded clip rectangle at `position`, in pixels, or 1 if
+// there is none. The distance to the rounded rectangle's edge is evaluated analytically.
+fn customTileRoundedClipCoverage(position: vec2<f32>) -> f32 {
+    if (globals.uClipParams.y == 0.0) {
+        return 1.0;
+    }
+    let center = (globals.uClipRect.xy + globals.uClipRect.zw) * 0.5;
+    let halfSize = (globals.uClipRect.zw - globals.uClipRect.xy) * 0.5;
+    let radius = globals.uClipParams.x;
+    let q = abs(position - center) - halfSize + radius;
+    let distance = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+    return clamp(0.5 - distance, 0.0, 1.0);
+}
+
+// Returns the coverage of the path's ellipse at `position`, in pixels, or 1 if the path isn't
+// one. The distance to the ellipse's edge is approximated from its implicit function.
+fn customTileEllipseCoverage(position: vec2<f32>) -> f32 {
+    if (globals.uClipParams.z == 0.0) {
+        return 1.0;
+    }
+    let center = (globals.uEllipseRect.xy + globals.uEllipseRect.zw) * 0.5;
+    let radii = (globals.uEllipseRect.zw - globals.uEllipseRect.xy) * 0.5;
+    let p = position - center;
+    let k0 = length(p / radii);
+    let k1 = length(p / (radii * radii));
+    var distance = -min(radii.x, radii.y);
+    if (k1 > 0.0) {
+        distance = k0 * (k0 - 1.0) / k1;
+    }
+    return clamp(0.5 - distance, 0.0, 1.0);
+}
+
+// Maps antialiased coverage to alpha per the scene's coverage curve.
+fn customTileCoverageCurve(coverage: f32) -> f32 {
+    let curved = pow(clamp(coverage, 0.0, 1.0), globals.uCoverageCurve.x);
+    return clamp(mix(curved, smoothstep(0.0, 1.0, curved), globals.uCoverageCurve.y), 0.0, 1.0);
+}
+
+// Returns the coverage of the path at this fragment, with the path's fill rule and the scene's
+// coverage curve applied.
+fn customTileCoverage(input: CustomTileVarying) -> f32 {
+    let clipCoverage = customTileRoundedClipCoverage(input.vFramebufferCoord) *
+        customTileEllipseCoverage(input.vFramebufferCoord);
+    let maskCtrl = input.vTileCtrl & CUSTOM_TILE_CTRL_MASK_MASK;
+    if (maskCtrl == 0) {
+        // Solid tile.
+        return clipCoverage;
+    }
+
+    // The mask packs four rows of coverage into the channels of each texel.
+    let maskTexCoordI = vec2<i32>(floor(input.vMaskTexCoord0.xy));
+    let texel = textureLoad(uMaskTexture0, maskTexCoordI / vec2<i32>(1, 4), 0);
+    var coverage = texel[maskTexCoordI.y % 4] + input.vMaskTexCoord0.z;
+    if ((maskCtrl & CUSTOM_TILE_CTRL_MASK_WINDING) != 0) {
+        coverage = abs(coverage);
+    } else {
+        coverage = 1.0 - abs(1.0 - (coverage - 2.0 * floor(coverage / 2.0)));
+    }
+    return customTileCoverageCurve(coverage) * clipCoverage;
+}
+
+// ---- User source follows. ----
+
@@ -0,0 +1,28 @@
+// pathfinder/resources/shaders/d3d9/paint_server.wgsl
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Fills paths with a procedural paint (see `pathfinder_renderer::gpu::d3d9::paint_server`).
+// Appended to `custom_tile_prelude.wgsl`, and followed by the paint server's source, which must
+// define:
+//
+//     fn paintServerColor(position: vec2<f32>, baseColor: vec4<f32>) -> vec4<f32>
+//
+// returning a color that isn't premultiplied. `position` is the fragment's position in the
+// framebuffer, in pixels, and `baseColor` is the base color of the paint. If the paint server has
+// uniforms, its source must also define `struct PaintServerUniforms`, which is bound to
+// `uPaintServer`.
+
+@fragment
+fn fs_main(input: CustomTileVarying) -> @location(0) vec4<f32> {
+    let color = paintServerColor(input.vFramebufferCoord, input.vBaseColor);
+    return vec4<f32>(color.rgb * color.a, color.a) * customTileCoverage(input);
+}
+
+// ---- Paint server source follows. ----
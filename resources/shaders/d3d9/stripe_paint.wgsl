@@ -0,0 +1,34 @@
+// pathfinder/resources/shaders/d3d9/stripe_paint.wgsl
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// The paint server behind `StripePaint`. Appended to `paint_server.wgsl`.
+
+struct PaintServerUniforms {
+    colorA: vec4<f32>,
+    colorB: vec4<f32>,
+    // The direction across the stripes, divided by the period of the pattern in pixels.
+    normal: vec2<f32>,
+    // The fraction of each period that is color A.
+    duty: f32,
+    // The offset of the pattern, as a fraction of the period.
+    phase: f32,
+};
+
+fn paintServerColor(position: vec2<f32>, baseColor: vec4<f32>) -> vec4<f32> {
+    let t = dot(position, uPaintServer.normal) + uPaintServer.phase;
+
+    // Find the distance to the middle of the nearest stripe of color A, in periods, and
+    // anti-alias its edges over one pixel.
+    let halfWidth = uPaintServer.duty * 0.5;
+    let distance = abs(fract(t - halfWidth + 0.5) - 0.5);
+    let pixelWidth = max(length(uPaintServer.normal), 1.0e-6);
+    let coverage = clamp((halfWidth - distance) / pixelWidth + 0.5, 0.0, 1.0);
+    return mix(uPaintServer.colorB, uPaintServer.colorA, coverage);
+}
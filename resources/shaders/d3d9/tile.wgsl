@@ -16,6 +16,10 @@ struct Globals {
     uColorTextureSize0: vec2<f32>,
     uFramebufferSize: vec2<f32>, // Dst framebuffer.
     uTransform: mat4x4<f32>,
+    uClipRect: vec4<f32>, // Rounded clip rectangle, as (min, max), in pixels.
+    uClipParams: vec4<f32>, // Rounded clip radius in x; y is nonzero if the clip applies, z if the ellipse does.
+    uEllipseRect: vec4<f32>, // Rectangle the ellipse is inscribed in, as (min, max), in pixels.
+    uCoverageCurve: vec4<f32>, // Reciprocal of the coverage gamma in x, coverage contrast in y.
 };
 
 @group(0) @binding(0) var<uniform> globals: Globals;
@@ -48,6 +52,8 @@ struct VertexOutput {
     @location(7) vFilterParams3: vec4<f32>,
     @location(8) vFilterParams4: vec4<f32>,
     @location(9) vCtrl: f32,
+    @location(10) vColor0Tint: vec3<f32>,
+    @location(11) vColor0Sampling: vec2<f32>, // x is nonzero if the pattern is seamless, y its sampling flags.
 };
 
 const FRAC_6_PI: f32 = 1.9098593171027443;
@@ -59,6 +65,9 @@ const TILE_CTRL_MASK_EVEN_ODD: i32 = 0x2;
 
 const TILE_CTRL_MASK_0_SHIFT: u32 = 0u;
 
+const TILE_CTRL_WATERTIGHT: i32 = 0x4;
+const TILE_CTRL_ALIASED: i32 = 0x8;
+
 const COMBINER_CTRL_COLOR_COMBINE_MASK: i32 = 0x3;
 const COMBINER_CTRL_COLOR_COMBINE_SRC_IN: i32 = 0x1;
 const COMBINER_CTRL_COLOR_COMBINE_DEST_IN: i32 = 0x2;
@@ -87,6 +96,12 @@ const COMBINER_CTRL_COMPOSITE_SATURATION: i32 = 0xd;
 const COMBINER_CTRL_COMPOSITE_COLOR: i32 = 0xe;
 const COMBINER_CTRL_COMPOSITE_LUMINOSITY: i32 = 0xf;
 
+// The `TextureSamplingFlags` of seamless patterns.
+const SAMPLING_REPEAT_U: i32 = 0x1;
+const SAMPLING_REPEAT_V: i32 = 0x2;
+const SAMPLING_NEAREST_MIN: i32 = 0x4;
+const SAMPLING_NEAREST_MAG: i32 = 0x8;
+
 const COMBINER_CTRL_COLOR_FILTER_SHIFT: u32 = 4u;
 const COMBINER_CTRL_COLOR_COMBINE_SHIFT: u32 = 8u;
 const COMBINER_CTRL_COMPOSITE_SHIFT: u32 = 10u;
@@ -149,13 +164,20 @@ fn vs_main(input: VertexInput) -> VertexOutput {
     let filterParams3   = fetchUnscaled(uTextureMetadata, metadataEntryCoord, 6);
     let filterParams4   = fetchUnscaled(uTextureMetadata, metadataEntryCoord, 7);
     let extra           = fetchUnscaled(uTextureMetadata, metadataEntryCoord, 8);
+    let colorSampling   = fetchUnscaled(uTextureMetadata, metadataEntryCoord, 9);
 
-    // Set color texture coordinates.
-    out.vColorTexCoord0 = mat2x2<f32>(colorTexMatrix0.xy, colorTexMatrix0.zw) * position + colorTexOffsets.xy;
+    // Set color texture coordinates. The offset is split in two to make up for the precision of
+    // half floats.
+    let colorTexOffset = colorTexOffsets.xy + colorTexOffsets.zw;
+    out.vColorTexCoord0 = mat2x2<f32>(colorTexMatrix0.xy, colorTexMatrix0.zw) * position + colorTexOffset;
+    out.vColor0Sampling = colorSampling.xy;
 
-    // Set base color.
+    // Set base color. Any tint has already been applied to it.
     out.vBaseColor = baseColor;
 
+    // Set the tint for the color texture.
+    out.vColor0Tint = extra.yzw;
+
     // Debug
 //    out.vBaseColor = vec4<f32>(1.0, 0.0, 0.0, 1.0);
 
@@ -326,6 +348,40 @@ fn filterNone(colorTexCoord: vec2<f32>, colorTexture: texture_2d<f32>) -> vec4<f
     return textureSample(colorTexture, smp, colorTexCoord);
 }
 
+// Wraps or clamps texel coordinates to the texture, per axis.
+fn wrapTexel(coord: vec2<i32>, size: vec2<i32>, repeat: vec2<bool>) -> vec2<i32> {
+    let wrapped = ((coord % size) + size) % size;
+    let clamped = clamp(coord, vec2<i32>(0), size - 1);
+    return select(clamped, wrapped, repeat);
+}
+
+// Samples a seamless pattern, wrapping and filtering here rather than in the sampler.
+//
+// The texture coordinates are wrapped in texels before the filter weights are taken, so the
+// weights keep their precision however many times the pattern has repeated, and each of the four
+// texels is wrapped on its own, so texels on the pattern's edges blend with those on the opposite
+// edge instead of with whatever the interpolated coordinates happen to round to.
+fn sampleSeamless(colorTexCoord: vec2<f32>, colorTexture: texture_2d<f32>, samplingFlags: i32) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(colorTexture));
+    let repeat = vec2<bool>((samplingFlags & SAMPLING_REPEAT_U) != 0, (samplingFlags & SAMPLING_REPEAT_V) != 0);
+    let sizeF = vec2<f32>(size);
+    let wrappedTexCoord = select(colorTexCoord, fract(colorTexCoord), repeat);
+    let texelCoord = wrappedTexCoord * sizeF - 0.5;
+
+    if ((samplingFlags & (SAMPLING_NEAREST_MIN | SAMPLING_NEAREST_MAG)) != 0) {
+        return textureLoad(colorTexture, wrapTexel(vec2<i32>(floor(texelCoord + 0.5)), size, repeat), 0);
+    }
+
+    let texelOrigin = floor(texelCoord);
+    let t = texelCoord - texelOrigin;
+    let origin = vec2<i32>(texelOrigin);
+    let c00 = textureLoad(colorTexture, wrapTexel(origin, size, repeat), 0);
+    let c10 = textureLoad(colorTexture, wrapTexel(origin + vec2<i32>(1, 0), size, repeat), 0);
+    let c01 = textureLoad(colorTexture, wrapTexel(origin + vec2<i32>(0, 1), size, repeat), 0);
+    let c11 = textureLoad(colorTexture, wrapTexel(origin + vec2<i32>(1, 1), size, repeat), 0);
+    return mix(mix(c00, c10, t.x), mix(c01, c11, t.x), t.y);
+}
+
 fn filterColor(
     colorTexCoord: vec2<f32>, colorTexture: texture_2d<f32>, gammaLUT: texture_2d<f32>, colorTextureSize: vec2<f32>,
     filterParams0: vec4<f32>, filterParams1: vec4<f32>, filterParams2: vec4<f32>, filterParams3: vec4<f32>, filterParams4: vec4<f32>,
@@ -498,6 +554,44 @@ fn composite(srcColor: vec4<f32>, destTexture: texture_2d<f32>, destTextureSize:
     );
 }
 
+// Returns the coverage of the batch's rounded clip rectangle at `position`, in pixels, or 1 if
+// there is none. The distance to the rounded rectangle's edge is evaluated analytically.
+fn roundedClipCoverage(position: vec2<f32>) -> f32 {
+    if (globals.uClipParams.y == 0.0) {
+        return 1.0;
+    }
+    let center = (globals.uClipRect.xy + globals.uClipRect.zw) * 0.5;
+    let halfSize = (globals.uClipRect.zw - globals.uClipRect.xy) * 0.5;
+    let radius = globals.uClipParams.x;
+    let q = abs(position - center) - halfSize + radius;
+    let distance = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+    return clamp(0.5 - distance, 0.0, 1.0);
+}
+
+// Returns the coverage of the batch's ellipse at `position`, in pixels, or 1 if there is none.
+// The distance to the ellipse's edge is approximated from its implicit function and gradient.
+fn ellipseCoverage(position: vec2<f32>) -> f32 {
+    if (globals.uClipParams.z == 0.0) {
+        return 1.0;
+    }
+    let center = (globals.uEllipseRect.xy + globals.uEllipseRect.zw) * 0.5;
+    let radii = (globals.uEllipseRect.zw - globals.uEllipseRect.xy) * 0.5;
+    let p = position - center;
+    let k0 = length(p / radii);
+    let k1 = length(p / (radii * radii));
+    var distance = -min(radii.x, radii.y);
+    if (k1 > 0.0) {
+        distance = k0 * (k0 - 1.0) / k1;
+    }
+    return clamp(0.5 - distance, 0.0, 1.0);
+}
+
+// Maps antialiased coverage to alpha per the scene's coverage curve. See `CoverageCurve`.
+fn applyCoverageCurve(coverage: f32) -> f32 {
+    let curved = pow(clamp(coverage, 0.0, 1.0), globals.uCoverageCurve.x);
+    return clamp(mix(curved, smoothstep(0.0, 1.0, curved), globals.uCoverageCurve.y), 0.0, 1.0);
+}
+
 // Masks
 fn sampleMask(maskAlpha: f32, maskTexture: texture_2d<f32>, maskTextureSize: vec2<f32>, maskTexCoord: vec3<f32>, maskCtrl: i32) -> f32 {
     if (maskCtrl == 0) { return maskAlpha; }
@@ -526,6 +620,25 @@ fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
     var maskAlpha = 1.0;
     maskAlpha = sampleMask(maskAlpha, uMaskTexture0, globals.uMaskTextureSize0, input.vMaskTexCoord0, maskCtrl0);
 
+    // Evaluate the edge of the ellipse, if the path is one.
+    maskAlpha *= ellipseCoverage(fragCoord.xy);
+
+    // Map edge coverage to alpha.
+    maskAlpha = applyCoverageCurve(maskAlpha);
+
+    // Double edge coverage so that adjacent paths leave no seam.
+    if ((tileCtrl & TILE_CTRL_WATERTIGHT) != 0) {
+        maskAlpha = min(maskAlpha * 2.0, 1.0);
+    }
+
+    // Round coverage to none or full for paths drawn without antialiasing.
+    if ((tileCtrl & TILE_CTRL_ALIASED) != 0) {
+        maskAlpha = step(0.5, maskAlpha);
+    }
+
+    // Apply the rounded clip, if any.
+    maskAlpha *= roundedClipCoverage(fragCoord.xy);
+
     // Get base color.
     var color = input.vBaseColor;
 
@@ -537,13 +650,19 @@ fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
         // Get color filter flag.
         let color0Filter = (ctrl >> COMBINER_CTRL_COLOR_FILTER_SHIFT) & COMBINER_CTRL_FILTER_MASK;
 
-        // Do filtering.
-        let color0 = filterColor(
-            input.vColorTexCoord0, uColorTexture0, uGammaLUT, globals.uColorTextureSize0,
-            input.vFilterParams0, input.vFilterParams1, input.vFilterParams2, input.vFilterParams3, input.vFilterParams4,
-            color0Filter
-        );
-        color = combineColor0(color, color0, color0Combine);
+        // Do filtering. Seamless patterns are only sampled specially without a filter.
+        var color0: vec4<f32>;
+        if (input.vColor0Sampling.x != 0.0 && color0Filter == 0) {
+            color0 = sampleSeamless(input.vColorTexCoord0, uColorTexture0, i32(input.vColor0Sampling.y));
+        } else {
+            color0 = filterColor(
+                input.vColorTexCoord0, uColorTexture0, uGammaLUT, globals.uColorTextureSize0,
+                input.vFilterParams0, input.vFilterParams1, input.vFilterParams2, input.vFilterParams3, input.vFilterParams4,
+                color0Filter
+            );
+        }
+        let tintedColor0 = vec4<f32>(color0.rgb * input.vColor0Tint, color0.a);
+        color = combineColor0(color, tintedColor0, color0Combine);
     }
 
     // Apply mask alpha.
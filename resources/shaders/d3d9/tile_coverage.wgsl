@@ -0,0 +1,19 @@
+// pathfinder/resources/shaders/d3d9/tile_coverage.wgsl
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Writes the coverage of each path, ignoring its paint. Appended to
+// `custom_tile_prelude.wgsl`.
+//
+// With premultiplied source-over blending, overlapping paths accumulate coverage as their union.
+
+@fragment
+fn fs_main(input: CustomTileVarying) -> @location(0) vec4<f32> {
+    return vec4<f32>(customTileCoverage(input));
+}
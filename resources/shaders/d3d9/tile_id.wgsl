@@ -0,0 +1,23 @@
+// pathfinder/resources/shaders/d3d9/tile_id.wgsl
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Writes the index of each path, plus one, to the pixels it covers at least half of. Appended to
+// `custom_tile_prelude.wgsl`.
+//
+// Without blending, later paths overwrite earlier ones, so each pixel ends up with the topmost
+// path. Zero means no path.
+
+@fragment
+fn fs_main(input: CustomTileVarying) -> @location(0) vec4<u32> {
+    if (customTileCoverage(input) < 0.5) {
+        discard;
+    }
+    return vec4<u32>(u32(input.vPathIndex) + 1u, 0u, 0u, 0u);
+}
@@ -0,0 +1,22 @@
+// pathfinder/resources/shaders/d3d9/tile_overdraw.wgsl
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Counts the tiles drawn over each pixel, ignoring paints. Appended to
+// `custom_tile_prelude.wgsl`.
+//
+// With additive blending, each channel saturates at a different count, so the result runs from
+// dark red (1 tile) through red (4), yellow (16), and white (64 or more).
+
+const OVERDRAW_HEAT_STEP: vec4<f32> = vec4<f32>(1.0 / 4.0, 1.0 / 16.0, 1.0 / 64.0, 1.0);
+
+@fragment
+fn fs_main(input: CustomTileVarying) -> @location(0) vec4<f32> {
+    return OVERDRAW_HEAT_STEP;
+}
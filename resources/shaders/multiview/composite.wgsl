@@ -0,0 +1,42 @@
+// pathfinder/resources/shaders/multiview/composite.wgsl
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Draws the rendered scene as a quad into one view, one render pass per view. See
+// `composite_multiview.wgsl` for the single-pass version.
+
+struct Views {
+    uTransforms: array<mat4x4<f32>, 8>,
+    uSceneSize: vec2<f32>,
+    uViewIndex: u32,
+    uPad: u32,
+};
+
+@group(0) @binding(0) var<uniform> views: Views;
+@group(1) @binding(0) var uTexture: texture_2d<f32>;
+@group(1) @binding(1) var smp: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) vTexCoord: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) aPosition: vec2<u32>) -> VertexOutput {
+    var out: VertexOutput;
+    let texCoord = vec2<f32>(aPosition);
+    out.vTexCoord = texCoord;
+    out.position = views.uTransforms[views.uViewIndex] * vec4<f32>(texCoord * views.uSceneSize, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(uTexture, smp, input.vTexCoord);
+}
@@ -0,0 +1,66 @@
+// pathfinder/resources/shaders/transition.wgsl
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Draws one frame of a transition between two rendered scenes. Both are premultiplied, so they
+// can be mixed directly.
+
+const TRANSITION_KIND_CROSS_FADE: i32 = 0;
+const TRANSITION_KIND_WIPE: i32 = 1;
+
+struct Globals {
+    uProgress: f32, // 0 shows only the old scene, 1 only the new one.
+    uKind: i32,
+    uSoftness: f32, // The width of the wipe's edge, as a fraction of the distance it travels.
+    uPad0: f32,
+    uWipeDirection: vec2<f32>, // The unit direction the wipe's edge moves in, Y down.
+    uPad1: vec2<f32>,
+};
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(1) @binding(0) var uFrom: texture_2d<f32>;
+@group(1) @binding(1) var uTo: texture_2d<f32>;
+@group(1) @binding(2) var smp: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) vTexCoord: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertexIndex: u32) -> VertexOutput {
+    // One triangle that covers the whole target.
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((vertexIndex << 1u) & 2u), f32(vertexIndex & 2u));
+    out.vTexCoord = uv;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(@location(0) vTexCoord: vec2<f32>) -> @location(0) vec4<f32> {
+    let fromColor = textureSample(uFrom, smp, vTexCoord);
+    let toColor = textureSample(uTo, smp, vTexCoord);
+
+    var t = globals.uProgress;
+    if (globals.uKind == TRANSITION_KIND_WIPE) {
+        // How far along the wipe's path this fragment is, from 0 at the corner where the wipe
+        // starts to 1 at the corner where it ends.
+        let direction = globals.uWipeDirection;
+        let extent = abs(direction.x) + abs(direction.y);
+        let position = (dot(vTexCoord - vec2<f32>(0.5), direction) + 0.5 * extent) / extent;
+
+        // The edge starts just before the path and ends just after it, so that progress 0 and 1
+        // show one scene each even with a soft edge.
+        let softness = max(globals.uSoftness, 0.0001);
+        let edge = globals.uProgress * (1.0 + softness);
+        t = clamp((edge - position) / softness, 0.0, 1.0);
+    }
+    return mix(fromColor, toColor, t);
+}
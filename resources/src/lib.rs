@@ -21,6 +21,7 @@ use std::io::Error as IOError;
 
 pub mod embedded;
 pub mod fs;
+pub mod manifest;
 
 pub trait ResourceLoader {
     /// This is deliberately not a `Path`, because these are virtual paths
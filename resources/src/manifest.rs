@@ -0,0 +1,138 @@
+// pathfinder/resources/src/manifest.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The resources the library was built against, identified by the hashes of their contents.
+//!
+//! The build script hashes every resource listed in `MANIFEST` (and in `MANIFEST.debug`, with the
+//! `debug` feature) and records the hashes here, along with the manifest's version. Resources
+//! embedded in the binary always match, but a `resources/` directory read by
+//! `FilesystemResourceLoader` can be left over from another checkout or release, which otherwise
+//! shows up as shaders that fail to compile or, worse, silently draw the wrong thing. `verify()`
+//! finds such mismatches, and renderers check for them at startup and report them through
+//! `Renderer::resource_mismatches()`.
+
+use crate::ResourceLoader;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+include!(concat!(env!("OUT_DIR"), "/hashes.rs"));
+
+/// The virtual path of the manifest itself, which only filesystem loaders can read.
+pub const MANIFEST_PATH: &str = "MANIFEST";
+
+/// The ways a loader's resources can differ from those the library was built against.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ResourceMismatch {
+    /// The loader's manifest has a different version.
+    Version { expected: u32, found: u32 },
+    /// The loader doesn't have a resource.
+    Missing(&'static str),
+    /// The loader's copy of a resource has different contents.
+    Changed(&'static str),
+}
+
+/// Returns the hash of a resource's contents, as recorded in the manifest.
+pub fn hash_resource(data: &[u8]) -> u64 {
+    // FNV-1a. This must match the build script.
+    data.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Returns the hash that the library expects a resource to have, or `None` if the resource
+/// isn't in the manifest.
+pub fn expected_hash(virtual_path: &str) -> Option<u64> {
+    RESOURCE_HASHES.iter().find(|&&(path, _)| path == virtual_path).map(|&(_, hash)| hash)
+}
+
+/// Checks a loader's resources against the manifest, and returns every mismatch found.
+///
+/// This reads every resource, so it's best done once at startup.
+pub fn verify(loader: &dyn ResourceLoader) -> Vec<ResourceMismatch> {
+    let mut mismatches = vec![];
+
+    // Only loaders backed by a resources directory have a manifest of their own.
+    if let Ok(manifest) = loader.slurp(MANIFEST_PATH) {
+        let found = String::from_utf8_lossy(&manifest).lines().find_map(|line| {
+            line.trim().strip_prefix("version ").and_then(|version| version.trim().parse().ok())
+        });
+        match found {
+            Some(found) if found == RESOURCES_VERSION => {}
+            found => {
+                mismatches.push(ResourceMismatch::Version {
+                    expected: RESOURCES_VERSION,
+                    found: found.unwrap_or(0),
+                })
+            }
+        }
+    }
+
+    for &(path, hash) in RESOURCE_HASHES {
+        match loader.slurp(path) {
+            Err(_) => mismatches.push(ResourceMismatch::Missing(path)),
+            Ok(data) if hash_resource(&data) != hash => {
+                mismatches.push(ResourceMismatch::Changed(path))
+            }
+            Ok(_) => {}
+        }
+    }
+    mismatches
+}
+
+impl Display for ResourceMismatch {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            ResourceMismatch::Version { expected, found } => {
+                write!(formatter,
+                       "resources are version {}, but version {} was expected",
+                       found,
+                       expected)
+            }
+            ResourceMismatch::Missing(path) => write!(formatter, "resource `{}` is missing", path),
+            ResourceMismatch::Changed(path) => {
+                write!(formatter, "resource `{}` differs from the one built against", path)
+            }
+        }
+    }
+}
+
+impl Error for ResourceMismatch {}
+
+#[cfg(test)]
+mod test {
+    use super::{RESOURCE_HASHES, ResourceMismatch};
+    use crate::ResourceLoader;
+    use crate::embedded::EmbeddedResourceLoader;
+    use std::io::{Error as IOError, ErrorKind};
+
+    // Serves the embedded resources, with one changed, one missing, and an old manifest.
+    struct StaleResourceLoader;
+
+    impl ResourceLoader for StaleResourceLoader {
+        fn slurp(&self, path: &str) -> Result<Vec<u8>, IOError> {
+            match path {
+                "MANIFEST" => Ok(b"# Resources\nversion 0\nshaders/blit.wgsl\n".to_vec()),
+                "shaders/blit.wgsl" => Ok(b"// Old blit shader".to_vec()),
+                "shaders/clear.wgsl" => Err(IOError::new(ErrorKind::NotFound, path)),
+                _ => EmbeddedResourceLoader::new().slurp(path),
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_finds_stale_resources() {
+        assert!(super::verify(&EmbeddedResourceLoader::new()).is_empty());
+        assert_eq!(super::verify(&StaleResourceLoader),
+                   vec![ResourceMismatch::Version { expected: super::RESOURCES_VERSION, found: 0 },
+                        ResourceMismatch::Changed("shaders/blit.wgsl"),
+                        ResourceMismatch::Missing("shaders/clear.wgsl")]);
+        assert!(RESOURCE_HASHES.iter().any(|&(path, _)| path == "textures/area-lut.png"));
+    }
+}
@@ -0,0 +1,354 @@
+// pathfinder/skia/src/canvas.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Drawing, as in `SkCanvas`.
+
+use crate::color::Color;
+use crate::geometry::{Matrix, Point, Rect, Size, Vector};
+use crate::paint::{BlendMode, Paint, Style};
+use crate::path::Path;
+use pathfinder_canvas::{Canvas as HtmlCanvas, CanvasFontContext, CanvasRenderingContext2D};
+use pathfinder_geometry::vector::vec2f;
+use pathfinder_renderer::scene::Scene;
+
+/// Records drawing commands into a Pathfinder scene.
+///
+/// As in Skia, the canvas has a current matrix and clip, which `save()` and `restore()` push and
+/// pop. Unlike `skia-safe`, drawing methods take `&mut self`.
+pub struct Canvas {
+    context: CanvasRenderingContext2D,
+    save_count: usize,
+}
+
+impl Canvas {
+    /// Creates a canvas of the given size with an empty scene.
+    pub fn new<S>(size: S) -> Canvas where S: Into<Size> {
+        let canvas = HtmlCanvas::new(size.into().to_vector2f());
+        Canvas::from_context(canvas.get_context_2d(CanvasFontContext::from_system_source()))
+    }
+
+    /// Wraps an existing HTML canvas context, keeping its current transform and clip as the base
+    /// state.
+    #[inline]
+    pub fn from_context(context: CanvasRenderingContext2D) -> Canvas {
+        Canvas { context, save_count: 1 }
+    }
+
+    /// Returns the underlying HTML canvas context, for features this API doesn't cover.
+    #[inline]
+    pub fn context_mut(&mut self) -> &mut CanvasRenderingContext2D {
+        &mut self.context
+    }
+
+    #[inline]
+    pub fn into_context(self) -> CanvasRenderingContext2D {
+        self.context
+    }
+
+    /// Returns the recorded scene.
+    #[inline]
+    pub fn scene(&self) -> &Scene {
+        self.context.canvas().scene()
+    }
+
+    /// Destroys this canvas and returns the recorded scene.
+    #[inline]
+    pub fn into_scene(self) -> Scene {
+        self.context.into_canvas().into_scene()
+    }
+
+    #[inline]
+    pub fn base_layer_size(&self) -> Size {
+        self.context.canvas().scene().view_box().size().into()
+    }
+
+    // State
+
+    /// Saves the matrix and clip, returning the save count from before the call.
+    pub fn save(&mut self) -> usize {
+        self.context.save();
+        self.save_count += 1;
+        self.save_count - 1
+    }
+
+    /// Restores the matrix and clip saved by the last unmatched `save()`. Does nothing if there
+    /// is none.
+    pub fn restore(&mut self) -> &mut Self {
+        if self.save_count > 1 {
+            self.context.restore();
+            self.save_count -= 1;
+        }
+        self
+    }
+
+    /// Returns the number of saved states plus one, as in Skia.
+    #[inline]
+    pub fn save_count(&self) -> usize {
+        self.save_count
+    }
+
+    /// Restores states until the save count is `count`.
+    pub fn restore_to_count(&mut self, count: usize) -> &mut Self {
+        while self.save_count > count.max(1) {
+            self.restore();
+        }
+        self
+    }
+
+    // Transformations
+
+    #[inline]
+    pub fn translate<V>(&mut self, offset: V) -> &mut Self where V: Into<Vector> {
+        self.context.translate(offset.into().to_vector2f());
+        self
+    }
+
+    #[inline]
+    pub fn scale(&mut self, scale: (f32, f32)) -> &mut Self {
+        self.context.scale(vec2f(scale.0, scale.1));
+        self
+    }
+
+    /// Rotates by `degrees` clockwise, around `pivot` if given and the origin otherwise.
+    pub fn rotate(&mut self, degrees: f32, pivot: Option<Point>) -> &mut Self {
+        let pivot = pivot.unwrap_or_default();
+        self.translate(pivot);
+        self.context.rotate(degrees.to_radians());
+        self.translate(-pivot)
+    }
+
+    #[inline]
+    pub fn skew(&mut self, skew: (f32, f32)) -> &mut Self {
+        self.concat(&Matrix::new_trans_scale_skew(1.0, skew.0, 0.0, skew.1, 1.0, 0.0))
+    }
+
+    /// Applies `matrix` before the current matrix.
+    #[inline]
+    pub fn concat(&mut self, matrix: &Matrix) -> &mut Self {
+        let transform = self.context.transform() * matrix.to_transform2f();
+        self.context.set_transform(&transform);
+        self
+    }
+
+    #[inline]
+    pub fn set_matrix(&mut self, matrix: &Matrix) -> &mut Self {
+        self.context.set_transform(&matrix.to_transform2f());
+        self
+    }
+
+    #[inline]
+    pub fn reset_matrix(&mut self) -> &mut Self {
+        self.context.reset_transform();
+        self
+    }
+
+    #[inline]
+    pub fn total_matrix(&self) -> Matrix {
+        self.context.transform().into()
+    }
+
+    // Clipping
+
+    /// Intersects the clip with the rectangle, in local coordinates. Clips are always
+    /// antialiased, so `do_anti_alias` is ignored.
+    pub fn clip_rect<R>(&mut self, rect: R, do_anti_alias: bool) -> &mut Self
+                        where R: AsRef<Rect> {
+        self.clip_path(&Path::rect(rect), do_anti_alias)
+    }
+
+    /// Intersects the clip with the path, in local coordinates, using the path's fill type.
+    pub fn clip_path(&mut self, path: &Path, _do_anti_alias: bool) -> &mut Self {
+        self.context.clip_path(path.to_path2d(), path.fill_rule());
+        self
+    }
+
+    // Drawing
+
+    /// Replaces every pixel with the given color, ignoring the matrix but not the clip.
+    #[inline]
+    pub fn clear<C>(&mut self, color: C) -> &mut Self where C: Into<Color> {
+        self.draw_color(color, BlendMode::Src)
+    }
+
+    /// Fills the whole clip with the given color, ignoring the matrix.
+    pub fn draw_color<C>(&mut self, color: C, mode: BlendMode) -> &mut Self
+                         where C: Into<Color> {
+        let mut paint = Paint::default();
+        paint.set_color(color).set_blend_mode(mode);
+        self.draw_paint(&paint)
+    }
+
+    /// Fills the whole clip with the paint, ignoring the matrix.
+    pub fn draw_paint(&mut self, paint: &Paint) -> &mut Self {
+        let bounds = Rect::from(self.scene().view_box());
+        let transform = self.context.transform();
+        self.context.reset_transform();
+        let mut fill = paint.clone();
+        fill.set_style(Style::Fill);
+        self.draw_path(&Path::rect(bounds), &fill);
+        self.context.set_transform(&transform);
+        self
+    }
+
+    pub fn draw_rect<R>(&mut self, rect: R, paint: &Paint) -> &mut Self where R: AsRef<Rect> {
+        self.draw_path(&Path::rect(rect), paint)
+    }
+
+    pub fn draw_oval<R>(&mut self, oval: R, paint: &Paint) -> &mut Self where R: AsRef<Rect> {
+        self.draw_path(&Path::oval(oval), paint)
+    }
+
+    pub fn draw_circle<P>(&mut self, center: P, radius: f32, paint: &Paint) -> &mut Self
+                          where P: Into<Point> {
+        self.draw_path(&Path::circle(center, radius), paint)
+    }
+
+    pub fn draw_round_rect<R>(&mut self, rect: R, rx: f32, ry: f32, paint: &Paint) -> &mut Self
+                              where R: AsRef<Rect> {
+        let mut path = Path::new();
+        path.add_round_rect(rect, (rx, ry));
+        self.draw_path(&path, paint)
+    }
+
+    /// Draws the arc of the ellipse inscribed in `oval`, with angles in degrees. If `use_center`
+    /// is true, the arc is closed through the center of the oval, making a wedge.
+    pub fn draw_arc<R>(&mut self,
+                       oval: R,
+                       start_angle: f32,
+                       sweep_angle: f32,
+                       use_center: bool,
+                       paint: &Paint)
+                       -> &mut Self
+                       where R: AsRef<Rect> {
+        let oval = *oval.as_ref();
+        let mut path = Path::new();
+        path.add_arc(oval, start_angle, sweep_angle);
+        if use_center {
+            path.line_to(oval.sorted().center()).close();
+        }
+        self.draw_path(&path, paint)
+    }
+
+    /// Strokes a line between two points. Lines are always stroked, whatever the paint's style.
+    pub fn draw_line<P, Q>(&mut self, p0: P, p1: Q, paint: &Paint) -> &mut Self
+                           where P: Into<Point>, Q: Into<Point> {
+        let mut path = Path::new();
+        path.move_to(p0).line_to(p1);
+        let mut stroke = paint.clone();
+        stroke.set_style(Style::Stroke);
+        self.draw_path(&path, &stroke)
+    }
+
+    /// Fills and/or strokes the path according to the paint's style.
+    pub fn draw_path(&mut self, path: &Path, paint: &Paint) -> &mut Self {
+        let (composite_operation, color) = match paint.blend_mode().to_composite_operation() {
+            None => return self,
+            Some(operation) if paint.blend_mode() == BlendMode::Clear => {
+                (operation, Color::BLACK)
+            }
+            Some(operation) => (operation, paint.color()),
+        };
+
+        self.context.save();
+        self.context.set_global_composite_operation(composite_operation);
+        self.context.set_global_alpha(1.0);
+        if paint.style() != Style::Stroke {
+            self.context.set_fill_style(color.to_color_u());
+            self.context.fill_path(path.to_path2d(), path.fill_rule());
+        }
+        if paint.style() != Style::Fill {
+            self.context.set_stroke_style(color.to_color_u());
+            self.context.set_line_width(paint.stroke_width());
+            self.context.set_line_cap(paint.stroke_cap().to_line_cap());
+            self.context.set_line_join(paint.stroke_join().to_line_join());
+            self.context.set_miter_limit(paint.stroke_miter());
+            self.context.set_line_dash(vec![]);
+            self.context.stroke_path(path.to_path2d());
+        }
+        self.context.restore();
+        self
+    }
+
+    /// Draws a sequence of points as round dots, or as connected or separate lines, as with
+    /// Skia's `drawPoints`.
+    pub fn draw_points(&mut self, mode: PointMode, points: &[Point], paint: &Paint) -> &mut Self {
+        let mut path = Path::new();
+        match mode {
+            PointMode::Points => {
+                let radius = paint.stroke_width().max(1.0) * 0.5;
+                for &point in points {
+                    path.add_circle(point, radius);
+                }
+                let mut fill = paint.clone();
+                fill.set_style(Style::Fill);
+                return self.draw_path(&path, &fill);
+            }
+            PointMode::Lines => {
+                for pair in points.chunks_exact(2) {
+                    path.move_to(pair[0]).line_to(pair[1]);
+                }
+            }
+            PointMode::Polygon => {
+                path.add_poly(points, false);
+            }
+        }
+        let mut stroke = paint.clone();
+        stroke.set_style(Style::Stroke);
+        self.draw_path(&path, &stroke)
+    }
+}
+
+/// How `Canvas::draw_points()` interprets its points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PointMode {
+    /// Each point is drawn as a dot.
+    Points,
+    /// Each pair of points is drawn as a line segment.
+    Lines,
+    /// The points are drawn as one open polyline.
+    Polygon,
+}
+
+#[cfg(test)]
+mod test {
+    use super::Canvas;
+    use crate::color::Color;
+    use crate::geometry::Rect;
+    use crate::paint::{BlendMode, Paint};
+
+    #[test]
+    fn test_save_count() {
+        let mut canvas = Canvas::new((100.0, 100.0));
+        assert_eq!(canvas.save_count(), 1);
+        assert_eq!(canvas.save(), 1);
+        canvas.translate((10.0, 20.0));
+        assert_eq!(canvas.save(), 2);
+        canvas.scale((2.0, 2.0));
+        canvas.restore_to_count(2);
+        assert_eq!(canvas.total_matrix().trans_x(), 10.0);
+        assert_eq!(canvas.total_matrix().scale_x(), 1.0);
+        canvas.restore().restore();
+        assert_eq!(canvas.save_count(), 1);
+        assert!(canvas.total_matrix().is_identity());
+    }
+
+    #[test]
+    fn test_dst_blend_mode_draws_nothing() {
+        let mut canvas = Canvas::new((100.0, 100.0));
+        let mut paint = Paint::default();
+        paint.set_color(Color::BLUE).set_blend_mode(BlendMode::Dst);
+        canvas.draw_rect(Rect::from_xywh(10.0, 10.0, 20.0, 20.0), &paint);
+        assert_eq!(canvas.scene().draw_path_count(), 0);
+
+        paint.set_blend_mode(BlendMode::SrcOver);
+        canvas.draw_rect(Rect::from_xywh(10.0, 10.0, 20.0, 20.0), &paint);
+        assert_eq!(canvas.scene().draw_path_count(), 1);
+    }
+}
@@ -0,0 +1,107 @@
+// pathfinder/skia/src/color.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! 32-bit ARGB colors, as in `SkColor`.
+
+use pathfinder_color::ColorU;
+
+/// An unpremultiplied color packed as `0xAARRGGBB`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Color(u32);
+
+impl Color {
+    pub const TRANSPARENT: Color = Color(0x0000_0000);
+    pub const BLACK: Color = Color(0xff00_0000);
+    pub const DARK_GRAY: Color = Color(0xff44_4444);
+    pub const GRAY: Color = Color(0xff88_8888);
+    pub const LIGHT_GRAY: Color = Color(0xffcc_cccc);
+    pub const WHITE: Color = Color(0xffff_ffff);
+    pub const RED: Color = Color(0xffff_0000);
+    pub const GREEN: Color = Color(0xff00_ff00);
+    pub const BLUE: Color = Color(0xff00_00ff);
+    pub const YELLOW: Color = Color(0xffff_ff00);
+    pub const CYAN: Color = Color(0xff00_ffff);
+    pub const MAGENTA: Color = Color(0xffff_00ff);
+
+    /// Creates a color from its packed `0xAARRGGBB` value.
+    #[inline]
+    pub const fn new(argb: u32) -> Color {
+        Color(argb)
+    }
+
+    #[inline]
+    pub const fn from_argb(a: u8, r: u8, g: u8, b: u8) -> Color {
+        Color((a as u32) << 24 | (r as u32) << 16 | (g as u32) << 8 | b as u32)
+    }
+
+    /// Creates an opaque color.
+    #[inline]
+    pub const fn from_rgb(r: u8, g: u8, b: u8) -> Color {
+        Color::from_argb(0xff, r, g, b)
+    }
+
+    #[inline]
+    pub fn a(self) -> u8 {
+        (self.0 >> 24) as u8
+    }
+
+    #[inline]
+    pub fn r(self) -> u8 {
+        (self.0 >> 16) as u8
+    }
+
+    #[inline]
+    pub fn g(self) -> u8 {
+        (self.0 >> 8) as u8
+    }
+
+    #[inline]
+    pub fn b(self) -> u8 {
+        self.0 as u8
+    }
+
+    /// Returns this color with its alpha replaced.
+    #[inline]
+    pub fn with_a(self, a: u8) -> Color {
+        Color::from_argb(a, self.r(), self.g(), self.b())
+    }
+
+    /// Returns the packed `0xAARRGGBB` value.
+    #[inline]
+    pub fn into_u32(self) -> u32 {
+        self.0
+    }
+
+    #[inline]
+    pub fn to_color_u(self) -> ColorU {
+        ColorU::new(self.r(), self.g(), self.b(), self.a())
+    }
+}
+
+impl From<u32> for Color {
+    #[inline]
+    fn from(argb: u32) -> Color {
+        Color(argb)
+    }
+}
+
+impl From<ColorU> for Color {
+    #[inline]
+    fn from(color: ColorU) -> Color {
+        Color::from_argb(color.a, color.r, color.g, color.b)
+    }
+}
+
+impl From<Color> for ColorU {
+    #[inline]
+    fn from(color: Color) -> ColorU {
+        color.to_color_u()
+    }
+}
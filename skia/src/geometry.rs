@@ -0,0 +1,499 @@
+// pathfinder/skia/src/geometry.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Points, sizes, rectangles, and matrices, as in `SkPoint`, `SkSize`, `SkRect`, and `SkMatrix`.
+
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use std::ops::{Add, Mul, Neg, Sub};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Skia uses the same type for points and offsets.
+pub type Vector = Point;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Size {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// An axis-aligned rectangle given by its edges.
+///
+/// Like Skia's, a rectangle is sorted when `left <= right` and `top <= bottom`; methods that
+/// produce rectangles from points sort them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+/// An affine transform.
+///
+/// Perspective matrices aren't supported. As in Skia, `pre_` methods apply the new transform
+/// before this one (in local coordinates) and `post_` methods apply it after.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Matrix(Transform2F);
+
+impl Point {
+    #[inline]
+    pub fn new(x: f32, y: f32) -> Point {
+        Point { x, y }
+    }
+
+    #[inline]
+    pub fn length(self) -> f32 {
+        self.to_vector2f().length()
+    }
+
+    #[inline]
+    pub fn to_vector2f(self) -> Vector2F {
+        vec2f(self.x, self.y)
+    }
+}
+
+impl Size {
+    #[inline]
+    pub fn new(width: f32, height: f32) -> Size {
+        Size { width, height }
+    }
+
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        !(self.width > 0.0 && self.height > 0.0)
+    }
+
+    #[inline]
+    pub fn to_vector2f(self) -> Vector2F {
+        vec2f(self.width, self.height)
+    }
+}
+
+impl Rect {
+    #[inline]
+    pub fn new(left: f32, top: f32, right: f32, bottom: f32) -> Rect {
+        Rect { left, top, right, bottom }
+    }
+
+    #[inline]
+    pub fn from_xywh(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect::new(x, y, x + width, y + height)
+    }
+
+    #[inline]
+    pub fn from_wh(width: f32, height: f32) -> Rect {
+        Rect::new(0.0, 0.0, width, height)
+    }
+
+    #[inline]
+    pub fn from_size<S>(size: S) -> Rect where S: Into<Size> {
+        let size = size.into();
+        Rect::from_wh(size.width, size.height)
+    }
+
+    #[inline]
+    pub fn from_point_and_size<P, S>(point: P, size: S) -> Rect
+                                     where P: Into<Point>, S: Into<Size> {
+        let (point, size) = (point.into(), size.into());
+        Rect::from_xywh(point.x, point.y, size.width, size.height)
+    }
+
+    /// Returns the smallest sorted rectangle containing both points.
+    pub fn from_points<P, Q>(a: P, b: Q) -> Rect where P: Into<Point>, Q: Into<Point> {
+        let (a, b) = (a.into(), b.into());
+        Rect::new(a.x.min(b.x), a.y.min(b.y), a.x.max(b.x), a.y.max(b.y))
+    }
+
+    #[inline]
+    pub fn x(&self) -> f32 {
+        self.left
+    }
+
+    #[inline]
+    pub fn y(&self) -> f32 {
+        self.top
+    }
+
+    #[inline]
+    pub fn width(&self) -> f32 {
+        self.right - self.left
+    }
+
+    #[inline]
+    pub fn height(&self) -> f32 {
+        self.bottom - self.top
+    }
+
+    #[inline]
+    pub fn center_x(&self) -> f32 {
+        (self.left + self.right) * 0.5
+    }
+
+    #[inline]
+    pub fn center_y(&self) -> f32 {
+        (self.top + self.bottom) * 0.5
+    }
+
+    #[inline]
+    pub fn center(&self) -> Point {
+        Point::new(self.center_x(), self.center_y())
+    }
+
+    /// Returns true if the rectangle has no area or is unsorted.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        !(self.left < self.right && self.top < self.bottom)
+    }
+
+    #[inline]
+    pub fn is_sorted(&self) -> bool {
+        self.left <= self.right && self.top <= self.bottom
+    }
+
+    /// Returns this rectangle with its edges swapped as needed to make it sorted.
+    #[inline]
+    pub fn sorted(&self) -> Rect {
+        Rect::from_points((self.left, self.top), (self.right, self.bottom))
+    }
+
+    #[inline]
+    pub fn contains<P>(&self, point: P) -> bool where P: Into<Point> {
+        let point = point.into();
+        point.x >= self.left && point.x < self.right &&
+            point.y >= self.top && point.y < self.bottom
+    }
+
+    #[inline]
+    pub fn with_offset<V>(&self, offset: V) -> Rect where V: Into<Vector> {
+        let offset = offset.into();
+        Rect::new(self.left + offset.x,
+                  self.top + offset.y,
+                  self.right + offset.x,
+                  self.bottom + offset.y)
+    }
+
+    /// Returns this rectangle moved inward by `delta.x` horizontally and `delta.y` vertically;
+    /// negative values move outward.
+    #[inline]
+    pub fn with_inset<V>(&self, delta: V) -> Rect where V: Into<Vector> {
+        let delta = delta.into();
+        Rect::new(self.left + delta.x,
+                  self.top + delta.y,
+                  self.right - delta.x,
+                  self.bottom - delta.y)
+    }
+
+    /// Returns this rectangle moved outward by `delta.x` horizontally and `delta.y` vertically.
+    #[inline]
+    pub fn with_outset<V>(&self, delta: V) -> Rect where V: Into<Vector> {
+        self.with_inset(-delta.into())
+    }
+
+    #[inline]
+    pub fn to_rect_f(&self) -> RectF {
+        RectF::from_points(vec2f(self.left, self.top), vec2f(self.right, self.bottom))
+    }
+}
+
+impl Matrix {
+    #[inline]
+    pub fn new_identity() -> Matrix {
+        Matrix(Transform2F::default())
+    }
+
+    /// Creates a matrix from its six affine entries, in Skia's order.
+    #[inline]
+    pub fn new_trans_scale_skew(scale_x: f32,
+                                skew_x: f32,
+                                trans_x: f32,
+                                skew_y: f32,
+                                scale_y: f32,
+                                trans_y: f32)
+                                -> Matrix {
+        Matrix(Transform2F::row_major(scale_x, skew_x, trans_x, skew_y, scale_y, trans_y))
+    }
+
+    #[inline]
+    pub fn translate<V>(offset: V) -> Matrix where V: Into<Vector> {
+        Matrix(Transform2F::from_translation(offset.into().to_vector2f()))
+    }
+
+    #[inline]
+    pub fn scale(scale: (f32, f32)) -> Matrix {
+        Matrix(Transform2F::from_scale(vec2f(scale.0, scale.1)))
+    }
+
+    /// Creates a rotation by the given angle in degrees, clockwise in Skia's Y-down space.
+    #[inline]
+    pub fn rotate_deg(degrees: f32) -> Matrix {
+        Matrix::rotate_rad(degrees.to_radians())
+    }
+
+    #[inline]
+    pub fn rotate_rad(radians: f32) -> Matrix {
+        Matrix(Transform2F::from_rotation(radians))
+    }
+
+    /// Returns `a * b`, which applies `b` first.
+    #[inline]
+    pub fn concat(a: &Matrix, b: &Matrix) -> Matrix {
+        Matrix(a.0 * b.0)
+    }
+
+    #[inline]
+    pub fn scale_x(&self) -> f32 {
+        self.0.m11()
+    }
+
+    #[inline]
+    pub fn skew_x(&self) -> f32 {
+        self.0.m12()
+    }
+
+    #[inline]
+    pub fn trans_x(&self) -> f32 {
+        self.0.m13()
+    }
+
+    #[inline]
+    pub fn skew_y(&self) -> f32 {
+        self.0.m21()
+    }
+
+    #[inline]
+    pub fn scale_y(&self) -> f32 {
+        self.0.m22()
+    }
+
+    #[inline]
+    pub fn trans_y(&self) -> f32 {
+        self.0.m23()
+    }
+
+    #[inline]
+    pub fn is_identity(&self) -> bool {
+        self.0.is_identity()
+    }
+
+    #[inline]
+    pub fn reset(&mut self) -> &mut Self {
+        self.0 = Transform2F::default();
+        self
+    }
+
+    #[inline]
+    pub fn pre_concat(&mut self, other: &Matrix) -> &mut Self {
+        self.0 = self.0 * other.0;
+        self
+    }
+
+    #[inline]
+    pub fn post_concat(&mut self, other: &Matrix) -> &mut Self {
+        self.0 = other.0 * self.0;
+        self
+    }
+
+    #[inline]
+    pub fn pre_translate<V>(&mut self, offset: V) -> &mut Self where V: Into<Vector> {
+        self.pre_concat(&Matrix::translate(offset))
+    }
+
+    #[inline]
+    pub fn post_translate<V>(&mut self, offset: V) -> &mut Self where V: Into<Vector> {
+        self.post_concat(&Matrix::translate(offset))
+    }
+
+    #[inline]
+    pub fn pre_scale(&mut self, scale: (f32, f32)) -> &mut Self {
+        self.pre_concat(&Matrix::scale(scale))
+    }
+
+    #[inline]
+    pub fn post_scale(&mut self, scale: (f32, f32)) -> &mut Self {
+        self.post_concat(&Matrix::scale(scale))
+    }
+
+    #[inline]
+    pub fn pre_rotate(&mut self, degrees: f32) -> &mut Self {
+        self.pre_concat(&Matrix::rotate_deg(degrees))
+    }
+
+    #[inline]
+    pub fn post_rotate(&mut self, degrees: f32) -> &mut Self {
+        self.post_concat(&Matrix::rotate_deg(degrees))
+    }
+
+    /// Returns the inverse of this matrix, or `None` if it isn't invertible.
+    pub fn invert(&self) -> Option<Matrix> {
+        let det = self.scale_x() * self.scale_y() - self.skew_x() * self.skew_y();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        Some(Matrix(self.0.inverse()))
+    }
+
+    #[inline]
+    pub fn map_point<P>(&self, point: P) -> Point where P: Into<Point> {
+        (self.0 * point.into().to_vector2f()).into()
+    }
+
+    /// Transforms a rectangle and returns the bounds of the result.
+    #[inline]
+    pub fn map_rect<R>(&self, rect: R) -> Rect where R: AsRef<Rect> {
+        (self.0 * rect.as_ref().to_rect_f()).into()
+    }
+
+    #[inline]
+    pub fn to_transform2f(&self) -> Transform2F {
+        self.0
+    }
+}
+
+impl From<(f32, f32)> for Point {
+    #[inline]
+    fn from((x, y): (f32, f32)) -> Point {
+        Point::new(x, y)
+    }
+}
+
+impl From<(i32, i32)> for Point {
+    #[inline]
+    fn from((x, y): (i32, i32)) -> Point {
+        Point::new(x as f32, y as f32)
+    }
+}
+
+impl From<Vector2F> for Point {
+    #[inline]
+    fn from(vector: Vector2F) -> Point {
+        Point::new(vector.x(), vector.y())
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+    #[inline]
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+    #[inline]
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Neg for Point {
+    type Output = Point;
+    #[inline]
+    fn neg(self) -> Point {
+        Point::new(-self.x, -self.y)
+    }
+}
+
+impl Mul<f32> for Point {
+    type Output = Point;
+    #[inline]
+    fn mul(self, factor: f32) -> Point {
+        Point::new(self.x * factor, self.y * factor)
+    }
+}
+
+impl From<(f32, f32)> for Size {
+    #[inline]
+    fn from((width, height): (f32, f32)) -> Size {
+        Size::new(width, height)
+    }
+}
+
+impl From<(i32, i32)> for Size {
+    #[inline]
+    fn from((width, height): (i32, i32)) -> Size {
+        Size::new(width as f32, height as f32)
+    }
+}
+
+impl From<Vector2F> for Size {
+    #[inline]
+    fn from(vector: Vector2F) -> Size {
+        Size::new(vector.x(), vector.y())
+    }
+}
+
+impl AsRef<Rect> for Rect {
+    #[inline]
+    fn as_ref(&self) -> &Rect {
+        self
+    }
+}
+
+impl From<RectF> for Rect {
+    #[inline]
+    fn from(rect: RectF) -> Rect {
+        Rect::new(rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y())
+    }
+}
+
+impl From<Transform2F> for Matrix {
+    #[inline]
+    fn from(transform: Transform2F) -> Matrix {
+        Matrix(transform)
+    }
+}
+
+impl Mul for Matrix {
+    type Output = Matrix;
+    #[inline]
+    fn mul(self, other: Matrix) -> Matrix {
+        Matrix::concat(&self, &other)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Matrix, Point, Rect};
+
+    #[test]
+    fn test_pre_and_post_concat_order() {
+        // Scale, then translate.
+        let mut matrix = Matrix::scale((2.0, 2.0));
+        matrix.post_translate((10.0, 0.0));
+        assert_eq!(matrix.map_point((1.0, 1.0)), Point::new(12.0, 2.0));
+
+        // Translate, then scale.
+        let mut matrix = Matrix::scale((2.0, 2.0));
+        matrix.pre_translate((10.0, 0.0));
+        assert_eq!(matrix.map_point((1.0, 1.0)), Point::new(22.0, 2.0));
+
+        let inverse = matrix.invert().unwrap();
+        let round_trip = inverse.map_point(matrix.map_point((3.0, 4.0)));
+        assert!((round_trip - Point::new(3.0, 4.0)).length() < 1e-5);
+        assert!(Matrix::scale((0.0, 1.0)).invert().is_none());
+    }
+
+    #[test]
+    fn test_rect() {
+        let rect = Rect::from_points((10.0, 8.0), (2.0, 4.0));
+        assert_eq!(rect, Rect::from_xywh(2.0, 4.0, 8.0, 4.0));
+        assert_eq!(rect.center(), Point::new(6.0, 6.0));
+        assert!(Rect::new(5.0, 0.0, 1.0, 1.0).is_empty());
+        assert_eq!(rect.with_outset((1.0, 2.0)), Rect::new(1.0, 2.0, 11.0, 10.0));
+    }
+}
@@ -0,0 +1,34 @@
+// pathfinder/skia/src/lib.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A subset of the `skia-safe` API, implemented on top of Pathfinder.
+//!
+//! This is meant to ease porting code written against Skia: the types and methods here have the
+//! same names and semantics as their `skia-safe` counterparts, so most drawing code only needs its
+//! imports changed. A `Canvas` records into a Pathfinder scene, which can then be rendered like any
+//! other.
+//!
+//! Only vector drawing is covered: paths, rectangles, ovals, and lines, filled or stroked with
+//! solid colors, plus transforms, clipping, and blend modes. Shaders, path effects, images, and
+//! text are not. Since Pathfinder always antialiases, the antialiasing flag on paints is accepted
+//! but ignored.
+
+pub mod canvas;
+mod color;
+mod geometry;
+pub mod paint;
+mod path;
+
+pub use crate::canvas::{Canvas, PointMode};
+pub use crate::color::Color;
+pub use crate::geometry::{Matrix, Point, Rect, Size, Vector};
+pub use crate::paint::{BlendMode, Paint};
+pub use crate::paint::{Cap as PaintCap, Join as PaintJoin, Style as PaintStyle};
+pub use crate::path::{Path, PathFillType};
@@ -0,0 +1,317 @@
+// pathfinder/skia/src/paint.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! How shapes are drawn, as in `SkPaint`.
+
+use crate::color::Color;
+use pathfinder_canvas::{CompositeOperation, LineCap, LineJoin};
+
+/// The default miter limit, matching Skia's.
+pub const DEFAULT_MITER_LIMIT: f32 = 4.0;
+
+/// Whether a shape is filled, stroked, or both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Style {
+    Fill,
+    Stroke,
+    StrokeAndFill,
+}
+
+/// The shape of the ends of open stroked contours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Cap {
+    Butt,
+    Round,
+    Square,
+}
+
+/// The shape of the corners of stroked contours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Join {
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// How drawn pixels combine with the pixels already there.
+///
+/// All of Skia's modes are accepted. `Modulate`, which Pathfinder lacks, is drawn as `Multiply`,
+/// which gives the same result wherever the destination is opaque.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    Clear,
+    Src,
+    Dst,
+    SrcOver,
+    DstOver,
+    SrcIn,
+    DstIn,
+    SrcOut,
+    DstOut,
+    SrcATop,
+    DstATop,
+    Xor,
+    Plus,
+    Modulate,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Multiply,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+/// The color, style, and blend mode to draw with.
+///
+/// Setters return `&mut Self` so that they can be chained, as in `skia-safe`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Paint {
+    color: Color,
+    anti_alias: bool,
+    style: Style,
+    stroke_width: f32,
+    stroke_miter: f32,
+    stroke_cap: Cap,
+    stroke_join: Join,
+    blend_mode: BlendMode,
+}
+
+impl Default for Paint {
+    /// An opaque black fill, as in Skia.
+    #[inline]
+    fn default() -> Paint {
+        Paint {
+            color: Color::BLACK,
+            anti_alias: false,
+            style: Style::Fill,
+            stroke_width: 0.0,
+            stroke_miter: DEFAULT_MITER_LIMIT,
+            stroke_cap: Cap::Butt,
+            stroke_join: Join::Miter,
+            blend_mode: BlendMode::SrcOver,
+        }
+    }
+}
+
+impl Paint {
+    #[inline]
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    #[inline]
+    pub fn set_color<C>(&mut self, color: C) -> &mut Self where C: Into<Color> {
+        self.color = color.into();
+        self
+    }
+
+    #[inline]
+    pub fn set_argb(&mut self, a: u8, r: u8, g: u8, b: u8) -> &mut Self {
+        self.set_color(Color::from_argb(a, r, g, b))
+    }
+
+    #[inline]
+    pub fn alpha(&self) -> u8 {
+        self.color.a()
+    }
+
+    #[inline]
+    pub fn set_alpha(&mut self, alpha: u8) -> &mut Self {
+        self.color = self.color.with_a(alpha);
+        self
+    }
+
+    #[inline]
+    pub fn alpha_f(&self) -> f32 {
+        self.alpha() as f32 / 255.0
+    }
+
+    /// Sets the alpha from a value between 0.0 and 1.0.
+    #[inline]
+    pub fn set_alpha_f(&mut self, alpha: f32) -> &mut Self {
+        self.set_alpha((alpha.max(0.0).min(1.0) * 255.0).round() as u8)
+    }
+
+    #[inline]
+    pub fn is_anti_alias(&self) -> bool {
+        self.anti_alias
+    }
+
+    /// Records the antialiasing flag. Pathfinder always antialiases, so it has no effect.
+    #[inline]
+    pub fn set_anti_alias(&mut self, anti_alias: bool) -> &mut Self {
+        self.anti_alias = anti_alias;
+        self
+    }
+
+    #[inline]
+    pub fn style(&self) -> Style {
+        self.style
+    }
+
+    #[inline]
+    pub fn set_style(&mut self, style: Style) -> &mut Self {
+        self.style = style;
+        self
+    }
+
+    /// Sets the style to `Stroke` if `stroke` is true and `Fill` otherwise.
+    #[inline]
+    pub fn set_stroke(&mut self, stroke: bool) -> &mut Self {
+        self.set_style(if stroke { Style::Stroke } else { Style::Fill })
+    }
+
+    #[inline]
+    pub fn stroke_width(&self) -> f32 {
+        self.stroke_width
+    }
+
+    /// Sets the stroke width. Zero, the default, draws hairlines.
+    #[inline]
+    pub fn set_stroke_width(&mut self, width: f32) -> &mut Self {
+        if width >= 0.0 {
+            self.stroke_width = width;
+        }
+        self
+    }
+
+    #[inline]
+    pub fn stroke_miter(&self) -> f32 {
+        self.stroke_miter
+    }
+
+    #[inline]
+    pub fn set_stroke_miter(&mut self, miter: f32) -> &mut Self {
+        if miter >= 0.0 {
+            self.stroke_miter = miter;
+        }
+        self
+    }
+
+    #[inline]
+    pub fn stroke_cap(&self) -> Cap {
+        self.stroke_cap
+    }
+
+    #[inline]
+    pub fn set_stroke_cap(&mut self, cap: Cap) -> &mut Self {
+        self.stroke_cap = cap;
+        self
+    }
+
+    #[inline]
+    pub fn stroke_join(&self) -> Join {
+        self.stroke_join
+    }
+
+    #[inline]
+    pub fn set_stroke_join(&mut self, join: Join) -> &mut Self {
+        self.stroke_join = join;
+        self
+    }
+
+    #[inline]
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    #[inline]
+    pub fn set_blend_mode(&mut self, mode: BlendMode) -> &mut Self {
+        self.blend_mode = mode;
+        self
+    }
+}
+
+impl Cap {
+    pub(crate) fn to_line_cap(self) -> LineCap {
+        match self {
+            Cap::Butt => LineCap::Butt,
+            Cap::Round => LineCap::Round,
+            Cap::Square => LineCap::Square,
+        }
+    }
+}
+
+impl Join {
+    pub(crate) fn to_line_join(self) -> LineJoin {
+        match self {
+            Join::Miter => LineJoin::Miter,
+            Join::Round => LineJoin::Round,
+            Join::Bevel => LineJoin::Bevel,
+        }
+    }
+}
+
+impl BlendMode {
+    /// Returns the canvas composite operation for this mode, or `None` for `Dst`, which leaves
+    /// the destination untouched.
+    ///
+    /// `Clear` maps to `DestinationOut`, which clears wherever the source is opaque, so the
+    /// caller must draw it with an opaque color.
+    pub(crate) fn to_composite_operation(self) -> Option<CompositeOperation> {
+        Some(match self {
+            BlendMode::Dst => return None,
+            BlendMode::Clear | BlendMode::DstOut => CompositeOperation::DestinationOut,
+            BlendMode::Src => CompositeOperation::Copy,
+            BlendMode::SrcOver => CompositeOperation::SourceOver,
+            BlendMode::DstOver => CompositeOperation::DestinationOver,
+            BlendMode::SrcIn => CompositeOperation::SourceIn,
+            BlendMode::DstIn => CompositeOperation::DestinationIn,
+            BlendMode::SrcOut => CompositeOperation::SourceOut,
+            BlendMode::SrcATop => CompositeOperation::SourceAtop,
+            BlendMode::DstATop => CompositeOperation::DestinationAtop,
+            BlendMode::Xor => CompositeOperation::Xor,
+            BlendMode::Plus => CompositeOperation::Lighter,
+            BlendMode::Modulate | BlendMode::Multiply => CompositeOperation::Multiply,
+            BlendMode::Screen => CompositeOperation::Screen,
+            BlendMode::Overlay => CompositeOperation::Overlay,
+            BlendMode::Darken => CompositeOperation::Darken,
+            BlendMode::Lighten => CompositeOperation::Lighten,
+            BlendMode::ColorDodge => CompositeOperation::ColorDodge,
+            BlendMode::ColorBurn => CompositeOperation::ColorBurn,
+            BlendMode::HardLight => CompositeOperation::HardLight,
+            BlendMode::SoftLight => CompositeOperation::SoftLight,
+            BlendMode::Difference => CompositeOperation::Difference,
+            BlendMode::Exclusion => CompositeOperation::Exclusion,
+            BlendMode::Hue => CompositeOperation::Hue,
+            BlendMode::Saturation => CompositeOperation::Saturation,
+            BlendMode::Color => CompositeOperation::Color,
+            BlendMode::Luminosity => CompositeOperation::Luminosity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Paint, Style};
+    use crate::color::Color;
+
+    #[test]
+    fn test_setters_chain() {
+        let mut paint = Paint::default();
+        paint.set_color(Color::RED).set_alpha_f(0.5).set_stroke(true).set_stroke_width(3.0);
+        assert_eq!(paint.color(), Color::from_argb(128, 255, 0, 0));
+        assert_eq!(paint.style(), Style::Stroke);
+        assert_eq!(paint.stroke_width(), 3.0);
+
+        // Negative widths are ignored, as in Skia.
+        paint.set_stroke_width(-1.0);
+        assert_eq!(paint.stroke_width(), 3.0);
+    }
+}
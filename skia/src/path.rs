@@ -0,0 +1,307 @@
+// pathfinder/skia/src/path.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Paths, as in `SkPath`.
+
+use crate::geometry::{Matrix, Point, Rect, Vector};
+use pathfinder_canvas::{FillRule, Path2D};
+use pathfinder_content::outline::Outline;
+use pathfinder_geometry::vector::vec2f;
+use std::f32::consts::PI;
+
+// The distance of cubic control points from the corner for a quarter ellipse of unit radius.
+const QUARTER_ARC_CONTROL: f32 = 0.552_284_8;
+
+/// The rule that decides which areas a path encloses.
+///
+/// Skia's inverse fill types have no Pathfinder equivalent and aren't provided.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PathFillType {
+    Winding,
+    EvenOdd,
+}
+
+/// A sequence of contours built from lines and Bézier curves.
+///
+/// Builder methods return `&mut Self` so that they can be chained, as in `skia-safe`.
+#[derive(Clone, Debug)]
+pub struct Path {
+    path: Path2D,
+    fill_type: PathFillType,
+}
+
+impl Default for Path {
+    #[inline]
+    fn default() -> Path {
+        Path::new()
+    }
+}
+
+impl Path {
+    #[inline]
+    pub fn new() -> Path {
+        Path { path: Path2D::new(), fill_type: PathFillType::Winding }
+    }
+
+    pub fn rect<R>(rect: R) -> Path where R: AsRef<Rect> {
+        let mut path = Path::new();
+        path.add_rect(rect);
+        path
+    }
+
+    pub fn oval<R>(oval: R) -> Path where R: AsRef<Rect> {
+        let mut path = Path::new();
+        path.add_oval(oval);
+        path
+    }
+
+    pub fn circle<P>(center: P, radius: f32) -> Path where P: Into<Point> {
+        let mut path = Path::new();
+        path.add_circle(center, radius);
+        path
+    }
+
+    /// Creates a path through the given points, closing it if `close` is true.
+    pub fn polygon(points: &[Point], close: bool) -> Path {
+        let mut path = Path::new();
+        path.add_poly(points, close);
+        path
+    }
+
+    #[inline]
+    pub fn fill_type(&self) -> PathFillType {
+        self.fill_type
+    }
+
+    #[inline]
+    pub fn set_fill_type(&mut self, fill_type: PathFillType) -> &mut Self {
+        self.fill_type = fill_type;
+        self
+    }
+
+    /// Returns true if the path has no contours.
+    pub fn is_empty(&self) -> bool {
+        self.to_outline().is_empty()
+    }
+
+    /// Returns the bounds of the path's points, including control points, or an empty rectangle
+    /// if there are none.
+    pub fn bounds(&self) -> Rect {
+        let outline = self.to_outline();
+        if outline.is_empty() {
+            Rect::default()
+        } else {
+            outline.bounds().into()
+        }
+    }
+
+    /// Removes every contour, keeping the fill type.
+    #[inline]
+    pub fn reset(&mut self) -> &mut Self {
+        self.path = Path2D::new();
+        self
+    }
+
+    #[inline]
+    pub fn move_to<P>(&mut self, point: P) -> &mut Self where P: Into<Point> {
+        self.path.move_to(point.into().to_vector2f());
+        self
+    }
+
+    #[inline]
+    pub fn line_to<P>(&mut self, point: P) -> &mut Self where P: Into<Point> {
+        self.path.line_to(point.into().to_vector2f());
+        self
+    }
+
+    #[inline]
+    pub fn quad_to<P, Q>(&mut self, ctrl: P, to: Q) -> &mut Self
+                         where P: Into<Point>, Q: Into<Point> {
+        self.path.quadratic_curve_to(ctrl.into().to_vector2f(), to.into().to_vector2f());
+        self
+    }
+
+    #[inline]
+    pub fn cubic_to<P, Q, R>(&mut self, ctrl0: P, ctrl1: Q, to: R) -> &mut Self
+                             where P: Into<Point>, Q: Into<Point>, R: Into<Point> {
+        self.path.bezier_curve_to(ctrl0.into().to_vector2f(),
+                                  ctrl1.into().to_vector2f(),
+                                  to.into().to_vector2f());
+        self
+    }
+
+    #[inline]
+    pub fn close(&mut self) -> &mut Self {
+        self.path.close_path();
+        self
+    }
+
+    /// Adds a closed contour around the rectangle, clockwise from the top left.
+    pub fn add_rect<R>(&mut self, rect: R) -> &mut Self where R: AsRef<Rect> {
+        self.path.rect(rect.as_ref().sorted().to_rect_f());
+        self
+    }
+
+    /// Adds a closed ellipse inscribed in the rectangle.
+    pub fn add_oval<R>(&mut self, oval: R) -> &mut Self where R: AsRef<Rect> {
+        let oval = oval.as_ref().sorted();
+        let radii = vec2f(oval.width(), oval.height()) * 0.5;
+        self.path.ellipse(oval.center().to_vector2f(), radii, 0.0, 0.0, 2.0 * PI);
+        self
+    }
+
+    #[inline]
+    pub fn add_circle<P>(&mut self, center: P, radius: f32) -> &mut Self where P: Into<Point> {
+        let center = center.into();
+        self.add_oval(Rect::new(center.x - radius,
+                                center.y - radius,
+                                center.x + radius,
+                                center.y + radius))
+    }
+
+    /// Adds the arc of the ellipse inscribed in `oval` starting at `start_angle` and sweeping
+    /// through `sweep_angle`, both in degrees clockwise from the positive X axis, as a new open
+    /// contour.
+    pub fn add_arc<R>(&mut self, oval: R, start_angle: f32, sweep_angle: f32) -> &mut Self
+                      where R: AsRef<Rect> {
+        let oval = oval.as_ref().sorted();
+        let radii = vec2f(oval.width(), oval.height()) * 0.5;
+        let (mut start, mut end) = (start_angle, start_angle + sweep_angle);
+        if end < start {
+            std::mem::swap(&mut start, &mut end);
+        }
+        self.path.ellipse(oval.center().to_vector2f(),
+                          radii,
+                          0.0,
+                          start.to_radians(),
+                          end.to_radians());
+        self
+    }
+
+    /// Adds a closed rectangle with elliptical corners of the given radii.
+    pub fn add_round_rect<R>(&mut self, rect: R, radii: (f32, f32)) -> &mut Self
+                             where R: AsRef<Rect> {
+        let rect = rect.as_ref().sorted();
+        let rx = radii.0.max(0.0).min(rect.width() * 0.5);
+        let ry = radii.1.max(0.0).min(rect.height() * 0.5);
+        if rx == 0.0 || ry == 0.0 {
+            return self.add_rect(rect);
+        }
+
+        let (cx, cy) = (rx * QUARTER_ARC_CONTROL, ry * QUARTER_ARC_CONTROL);
+        let (left, top, right, bottom) = (rect.left, rect.top, rect.right, rect.bottom);
+        self.move_to((left + rx, top))
+            .line_to((right - rx, top))
+            .cubic_to((right - rx + cx, top), (right, top + ry - cy), (right, top + ry))
+            .line_to((right, bottom - ry))
+            .cubic_to((right, bottom - ry + cy), (right - rx + cx, bottom), (right - rx, bottom))
+            .line_to((left + rx, bottom))
+            .cubic_to((left + rx - cx, bottom), (left, bottom - ry + cy), (left, bottom - ry))
+            .line_to((left, top + ry))
+            .cubic_to((left, top + ry - cy), (left + rx - cx, top), (left + rx, top))
+            .close()
+    }
+
+    /// Adds a contour through the given points, closing it if `close` is true.
+    pub fn add_poly(&mut self, points: &[Point], close: bool) -> &mut Self {
+        if let Some((&first, rest)) = points.split_first() {
+            self.move_to(first);
+            for &point in rest {
+                self.line_to(point);
+            }
+            if close {
+                self.close();
+            }
+        }
+        self
+    }
+
+    /// Adds the contours of another path, offset by the given amount.
+    #[inline]
+    pub fn add_path<V>(&mut self, path: &Path, offset: V) -> &mut Self where V: Into<Vector> {
+        self.add_path_matrix(path, &Matrix::translate(offset))
+    }
+
+    /// Adds the contours of another path, transformed by the given matrix.
+    pub fn add_path_matrix(&mut self, path: &Path, matrix: &Matrix) -> &mut Self {
+        self.path.add_path(path.path.clone(), &matrix.to_transform2f());
+        self
+    }
+
+    /// Transforms every point in this path.
+    pub fn transform(&mut self, matrix: &Matrix) -> &mut Self {
+        let mut path = Path2D::new();
+        path.add_path(self.path.clone(), &matrix.to_transform2f());
+        self.path = path;
+        self
+    }
+
+    /// Returns a copy of this path with every point transformed.
+    pub fn with_transform(&self, matrix: &Matrix) -> Path {
+        let mut path = self.clone();
+        path.transform(matrix);
+        path
+    }
+
+    /// Returns a copy of this path with every point offset by the given amount.
+    #[inline]
+    pub fn with_offset<V>(&self, offset: V) -> Path where V: Into<Vector> {
+        self.with_transform(&Matrix::translate(offset))
+    }
+
+    #[inline]
+    pub(crate) fn to_path2d(&self) -> Path2D {
+        self.path.clone()
+    }
+
+    #[inline]
+    pub(crate) fn fill_rule(&self) -> FillRule {
+        match self.fill_type {
+            PathFillType::Winding => FillRule::Winding,
+            PathFillType::EvenOdd => FillRule::EvenOdd,
+        }
+    }
+
+    /// Returns the path as a Pathfinder outline.
+    #[inline]
+    pub fn to_outline(&self) -> Outline {
+        self.path.clone().into_outline()
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::Path;
+    use crate::geometry::{Matrix, Rect};
+
+    #[test]
+    fn test_bounds() {
+        let mut path = Path::new();
+        assert!(path.is_empty());
+        assert_eq!(path.bounds(), Rect::default());
+
+        path.move_to((1.0, 2.0)).line_to((5.0, 2.0)).line_to((3.0, 7.0)).close();
+        assert_eq!(path.bounds(), Rect::new(1.0, 2.0, 5.0, 7.0));
+
+        path.transform(&Matrix::translate((10.0, 0.0)));
+        assert_eq!(path.bounds(), Rect::new(11.0, 2.0, 15.0, 7.0));
+    }
+
+    #[test]
+    fn test_round_rect() {
+        let mut path = Path::new();
+        path.add_round_rect(Rect::from_xywh(0.0, 0.0, 20.0, 10.0), (4.0, 4.0));
+        let outline = path.to_outline();
+        assert_eq!(outline.contours().len(), 1);
+        assert!(outline.contours()[0].is_closed());
+        assert_eq!(path.bounds(), Rect::new(0.0, 0.0, 20.0, 10.0));
+    }
+}
@@ -19,6 +19,7 @@ use pathfinder_content::dash::OutlineDash;
 use pathfinder_content::fill::FillRule;
 use pathfinder_content::gradient::{ColorStop, Gradient, GradientWrap};
 use pathfinder_content::outline::Outline;
+use pathfinder_content::sanitize::{OutlineSanitizer, SanitizeOptions};
 use pathfinder_content::segment::{Segment, SegmentFlags};
 use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
 use pathfinder_content::transform::Transform2FPathIter;
@@ -56,6 +57,7 @@ bitflags! {
         const UNSUPPORTED_LINK_PAINT             = 0x0020;
         const UNSUPPORTED_FILTER_ATTR            = 0x0040;
         const UNSUPPORTED_MASK_ATTR              = 0x0080;
+        const INVALID_PATH_DATA                  = 0x0100;
     }
 }
 
@@ -68,6 +70,21 @@ impl SVGScene {
 
     // TODO(pcwalton): Allow a global transform to be set.
     pub fn from_tree_and_scene(tree: &Tree, scene: Scene) -> SVGScene {
+        SVGScene::from_tree_children(tree, scene, |_| true)
+    }
+
+    /// Builds a scene from a single element of a tree, such as one glyph of an OpenType SVG
+    /// document that holds several. `node` must be a child of the tree's root; the tree's
+    /// definitions, such as gradients and clip paths, are still available to it.
+    pub fn from_tree_node(tree: &Tree, node: &Node) -> SVGScene {
+        SVGScene::from_tree_children(tree, Scene::new(), |kid| {
+            kid == node || matches!(*kid.borrow(), NodeKind::Defs)
+        })
+    }
+
+    // Builds a scene from the children of the tree's root for which `include` returns true.
+    fn from_tree_children<F>(tree: &Tree, scene: Scene, mut include: F) -> SVGScene
+                             where F: FnMut(&Node) -> bool {
         // TODO(pcwalton): Maybe have a `SVGBuilder` type to hold the clip path IDs and other
         // transient data separate from `SVGScene`?
         let mut built_svg = SVGScene {
@@ -82,7 +99,9 @@ impl SVGScene {
             NodeKind::Svg(ref svg) => {
                 built_svg.scene.set_view_box(usvg_rect_to_euclid_rect(&svg.view_box.rect));
                 for kid in root.children() {
-                    built_svg.process_node(&kid, &State::new(), &mut None);
+                    if include(&kid) {
+                        built_svg.process_node(&kid, &State::new(), &mut None);
+                    }
                 }
             }
             _ => unreachable!(),
@@ -128,13 +147,16 @@ impl SVGScene {
                 if clip_outline.is_some() {
                     self.result_flags.insert(BuildResultFlags::UNSUPPORTED_MULTIPLE_CLIP_PATHS);
                 }
-                *clip_outline = Some(Outline::from_segments(path));
+                let outline = Outline::from_segments_unchecked(path);
+                let outline = self.sanitize_outline(outline, true);
+                *clip_outline = Some(outline);
             }
             NodeKind::Path(ref path) if state.path_destination == PathDestination::Draw &&
                     path.visibility == Visibility::Visible => {
                 if let Some(ref fill) = path.fill {
                     let path = UsvgPathToSegments::new(path.data.iter().cloned());
-                    let outline = Outline::from_segments(path);
+                    let outline = Outline::from_segments_unchecked(path);
+                    let outline = self.sanitize_outline(outline, true);
 
                     let name = format!("Fill({})", node.id());
                     self.push_draw_path(outline,
@@ -154,7 +176,8 @@ impl SVGScene {
                     };
 
                     let path = UsvgPathToSegments::new(path.data.iter().cloned());
-                    let mut outline = Outline::from_segments(path);
+                    let outline = Outline::from_segments_unchecked(path);
+                    let mut outline = self.sanitize_outline(outline, false);
 
                     if let Some(ref dash_array) = stroke.dasharray {
                         let dash_array: Vec<f32> = dash_array.iter().map(|&x| x as f32).collect();
@@ -264,6 +287,19 @@ impl SVGScene {
         self.gradients.insert(id, GradientInfo { gradient, transform });
     }
 
+    // Repairs malformed path data so that it can't crash the tiler, noting it if so.
+    fn sanitize_outline(&mut self, outline: Outline, is_fill: bool) -> Outline {
+        let mut options = SanitizeOptions::default();
+        options.close_open_contours = is_fill;
+        let mut sanitizer = OutlineSanitizer::new(&outline, options);
+        sanitizer.sanitize();
+        let report = sanitizer.report();
+        if report.non_finite_points > 0 || report.clamped_points > 0 {
+            self.result_flags.insert(BuildResultFlags::INVALID_PATH_DATA);
+        }
+        sanitizer.into_outline()
+    }
+
     fn push_draw_path(&mut self,
                       mut outline: Outline,
                       name: String,
@@ -318,6 +354,7 @@ impl Display for BuildResultFlags {
             "non-color paint",
             "filter attribute",
             "mask attribute",
+            "invalid path data",
         ];
     }
 }
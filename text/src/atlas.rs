@@ -0,0 +1,254 @@
+// pathfinder/text/src/atlas.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Baking glyphs into a texture atlas, for engines that draw text from prerendered bitmaps.
+//!
+//! `GlyphAtlas::new()` packs a set of characters at one or more sizes into a single atlas and
+//! returns a scene that draws them, plus the metrics needed to lay them out. Render the scene with
+//! any renderer (e.g. `pathfinder_headless`) to get the atlas bitmap. Glyphs are drawn in white
+//! on a transparent background, so the alpha channel is the coverage.
+//!
+//! The metrics serialize to the same JSON layout as the debug UI font, with one entry per size.
+
+use crate::OutlinePathBuilder;
+use font_kit::error::GlyphLoadingError;
+use font_kit::hinting::HintingOptions;
+use font_kit::loader::Loader;
+use pathfinder_color::ColorU;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2I, vec2f, vec2i};
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{DrawPath, Scene};
+use std::collections::BTreeMap;
+
+/// Which glyphs to bake, and how to lay them out.
+#[derive(Clone, Debug)]
+pub struct GlyphAtlasOptions {
+    /// The sizes to bake each character at, in pixels per em.
+    pub sizes: Vec<f32>,
+    /// The characters to bake. Characters that the font doesn't contain are skipped.
+    pub characters: Vec<char>,
+    /// The number of empty pixels around each glyph, to avoid bleeding when sampling.
+    pub padding: i32,
+    /// The maximum width of the atlas, in pixels. The atlas grows downward as needed.
+    pub max_width: i32,
+}
+
+/// A baked atlas: the scene that draws it, and the metrics of each glyph.
+pub struct GlyphAtlas {
+    /// A scene drawing every glyph in white, with its view box set to the atlas size.
+    pub scene: Scene,
+    /// The location and metrics of every glyph in the atlas.
+    pub metrics: GlyphAtlasMetrics,
+}
+
+/// The location and metrics of every glyph in an atlas.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "pf-serde", derive(Serialize))]
+pub struct GlyphAtlasMetrics {
+    /// The full name of the font.
+    pub name: String,
+    /// The width of the atlas, in pixels.
+    pub width: i32,
+    /// The height of the atlas, in pixels.
+    pub height: i32,
+    /// The glyphs at each size, in the order the sizes were requested.
+    pub sizes: Vec<GlyphAtlasSize>,
+}
+
+/// The glyphs baked at one size.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "pf-serde", derive(Serialize))]
+pub struct GlyphAtlasSize {
+    /// The size, in pixels per em.
+    pub size: f32,
+    /// The distance from the baseline to the top of the tallest glyphs, in pixels.
+    pub ascent: f32,
+    /// The distance from the baseline to the bottom of the lowest glyphs, in pixels. This is
+    /// usually negative.
+    pub descent: f32,
+    /// The recommended extra space between lines, in pixels.
+    #[cfg_attr(feature = "pf-serde", serde(rename = "lineGap"))]
+    pub line_gap: f32,
+    /// The glyph for each baked character.
+    pub characters: BTreeMap<char, GlyphAtlasEntry>,
+}
+
+/// The location of a single glyph in the atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "pf-serde", derive(Serialize))]
+pub struct GlyphAtlasEntry {
+    /// The glyph ID in the font.
+    #[cfg_attr(feature = "pf-serde", serde(rename = "glyphId"))]
+    pub glyph_id: u32,
+    /// The left edge of the glyph's rectangle in the atlas, in pixels.
+    pub x: i32,
+    /// The top edge of the glyph's rectangle in the atlas, in pixels.
+    pub y: i32,
+    /// The width of the glyph's rectangle, including padding. Zero for blank glyphs.
+    pub width: i32,
+    /// The height of the glyph's rectangle, including padding. Zero for blank glyphs.
+    pub height: i32,
+    /// The distance from the left edge of the rectangle to the pen position.
+    #[cfg_attr(feature = "pf-serde", serde(rename = "originX"))]
+    pub origin_x: i32,
+    /// The distance from the top edge of the rectangle to the baseline.
+    #[cfg_attr(feature = "pf-serde", serde(rename = "originY"))]
+    pub origin_y: i32,
+    /// The distance to advance the pen after drawing this glyph, in pixels.
+    pub advance: f32,
+}
+
+impl Default for GlyphAtlasOptions {
+    /// Printable ASCII at 16 pixels per em.
+    fn default() -> GlyphAtlasOptions {
+        GlyphAtlasOptions {
+            sizes: vec![16.0],
+            characters: (' '..='~').collect(),
+            padding: 1,
+            max_width: 1024,
+        }
+    }
+}
+
+impl GlyphAtlas {
+    /// Lays out the requested glyphs of `font` and builds a scene that draws them.
+    pub fn new<F>(font: &F, options: &GlyphAtlasOptions) -> Result<GlyphAtlas, GlyphLoadingError>
+                  where F: Loader {
+        let font_metrics = font.metrics();
+        let units_per_em = font_metrics.units_per_em as f32;
+
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::from_color(ColorU::white()));
+
+        let mut packer = ShelfPacker::new(options.max_width);
+        let mut sizes = Vec::with_capacity(options.sizes.len());
+        for &size in &options.sizes {
+            let scale = size / units_per_em;
+            let mut characters = BTreeMap::new();
+            for &character in &options.characters {
+                let glyph_id = match font.glyph_for_char(character) {
+                    None => continue,
+                    Some(glyph_id) => glyph_id,
+                };
+
+                // Flip to Y-down, with the pen position at the origin.
+                let mut outline_builder =
+                    OutlinePathBuilder::new(&Transform2F::from_scale(vec2f(scale, -scale)));
+                font.outline(glyph_id, HintingOptions::None, &mut outline_builder)?;
+                let mut outline = outline_builder.build();
+                let advance = font.advance(glyph_id)?.x() * scale;
+
+                let mut entry = GlyphAtlasEntry {
+                    glyph_id,
+                    x: 0,
+                    y: 0,
+                    width: 0,
+                    height: 0,
+                    origin_x: 0,
+                    origin_y: 0,
+                    advance,
+                };
+
+                let bounds = outline.bounds().round_out().to_i32();
+                if !outline.is_empty() && bounds.width() > 0 && bounds.height() > 0 {
+                    let slot_size = bounds.size() + Vector2I::splat(options.padding * 2);
+                    let slot_origin = packer.allocate(slot_size);
+                    let glyph_origin = slot_origin + Vector2I::splat(options.padding) -
+                        bounds.origin();
+                    outline.transform(&Transform2F::from_translation(glyph_origin.to_f32()));
+                    scene.push_draw_path(DrawPath::new(outline, paint));
+
+                    entry.x = slot_origin.x();
+                    entry.y = slot_origin.y();
+                    entry.width = slot_size.x();
+                    entry.height = slot_size.y();
+                    entry.origin_x = glyph_origin.x() - slot_origin.x();
+                    entry.origin_y = glyph_origin.y() - slot_origin.y();
+                }
+
+                characters.insert(character, entry);
+            }
+
+            sizes.push(GlyphAtlasSize {
+                size,
+                ascent: font_metrics.ascent * scale,
+                descent: font_metrics.descent * scale,
+                line_gap: font_metrics.line_gap * scale,
+                characters,
+            });
+        }
+
+        let atlas_size = packer.size();
+        scene.set_view_box(RectF::new(vec2f(0.0, 0.0), atlas_size.to_f32()));
+
+        let metrics = GlyphAtlasMetrics {
+            name: font.full_name(),
+            width: atlas_size.x(),
+            height: atlas_size.y(),
+            sizes,
+        };
+        Ok(GlyphAtlas { scene, metrics })
+    }
+}
+
+#[cfg(feature = "pf-serde")]
+impl GlyphAtlasMetrics {
+    /// Serializes the metrics to pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+// Packs rectangles left to right into rows ("shelves"), starting a new row when one is full.
+struct ShelfPacker {
+    max_width: i32,
+    cursor: Vector2I,
+    shelf_height: i32,
+    used_width: i32,
+}
+
+impl ShelfPacker {
+    fn new(max_width: i32) -> ShelfPacker {
+        ShelfPacker { max_width, cursor: Vector2I::zero(), shelf_height: 0, used_width: 0 }
+    }
+
+    fn allocate(&mut self, size: Vector2I) -> Vector2I {
+        if self.cursor.x() > 0 && self.cursor.x() + size.x() > self.max_width {
+            self.cursor = vec2i(0, self.cursor.y() + self.shelf_height);
+            self.shelf_height = 0;
+        }
+        let origin = self.cursor;
+        self.cursor = self.cursor + vec2i(size.x(), 0);
+        self.shelf_height = self.shelf_height.max(size.y());
+        self.used_width = self.used_width.max(self.cursor.x());
+        origin
+    }
+
+    fn size(&self) -> Vector2I {
+        vec2i(self.used_width.max(1), (self.cursor.y() + self.shelf_height).max(1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ShelfPacker;
+    use pathfinder_geometry::vector::vec2i;
+
+    #[test]
+    fn test_shelf_packer() {
+        let mut packer = ShelfPacker::new(10);
+        assert_eq!(packer.allocate(vec2i(4, 3)), vec2i(0, 0));
+        assert_eq!(packer.allocate(vec2i(4, 5)), vec2i(4, 0));
+        assert_eq!(packer.allocate(vec2i(4, 2)), vec2i(0, 5));
+        assert_eq!(packer.size(), vec2i(8, 7));
+    }
+}
@@ -0,0 +1,378 @@
+// pathfinder/text/src/bmfont.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Bitmap fonts in the BMFont format, drawn with image paints.
+//!
+//! Pixel-style games often draw text from hand-drawn bitmap fonts rather than outline fonts, and
+//! still want Pathfinder for the vector parts of the screen. `BitmapFont::parse()` reads the text
+//! variant of AngelCode's BMFont descriptors (`.fnt` files). The page images the descriptor names
+//! are loaded by the caller, with whatever image library it already uses, and attached with
+//! `BitmapFont::set_page()`. `BitmapFont::push_text()` then draws each glyph as a rectangle
+//! filled with its region of the page, sampled without smoothing so that pixels stay crisp.
+//!
+//! The binary and XML variants of the format aren't supported, and neither are fonts that pack
+//! glyphs into separate color channels of their pages.
+
+use pathfinder_color::ColorU;
+use pathfinder_content::effects::BlendMode;
+use pathfinder_content::outline::Outline;
+use pathfinder_content::pattern::{Image, Pattern};
+use pathfinder_geometry::rect::{RectF, RectI};
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f, vec2i};
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{ClipPathId, DrawPath, Scene};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// A bitmap font: the location of each glyph in the font's page images, and how to lay it out.
+#[derive(Clone)]
+pub struct BitmapFont {
+    line_height: i32,
+    base: i32,
+    page_files: Vec<String>,
+    pages: Vec<Option<Image>>,
+    glyphs: HashMap<char, BitmapGlyph>,
+    kerning: HashMap<(char, char), i32>,
+}
+
+/// Where a glyph of a bitmap font is, and how it's laid out, in pixels.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BitmapGlyph {
+    /// The glyph's rectangle in its page image.
+    pub rect: RectI,
+    /// The offset of the glyph's upper left corner from the pen position at the top of the line.
+    pub offset: Vector2I,
+    /// How far the pen moves to the right after the glyph.
+    pub advance: i32,
+    /// The index of the page image that holds the glyph.
+    pub page: u32,
+}
+
+/// How to draw text in a bitmap font.
+#[derive(Clone, Copy, Debug)]
+pub struct BitmapTextOptions {
+    /// The transform from font pixels to scene space. Integer scales keep pixel art crisp.
+    pub transform: Transform2F,
+    /// The color that the page images are multiplied by. Fonts drawn in white take on this
+    /// color; white leaves the images unchanged.
+    pub color: ColorU,
+    /// The clip path to clip the glyphs to, if any.
+    pub clip_path: Option<ClipPathId>,
+    /// How to blend the glyphs with what's beneath them.
+    pub blend_mode: BlendMode,
+}
+
+/// The reasons a BMFont descriptor can fail to parse.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BitmapFontError {
+    /// A line is malformed or lacks a required attribute. Lines are numbered from 1.
+    InvalidLine(usize),
+    /// The descriptor has no `common` line, which holds the line height.
+    MissingCommon,
+}
+
+impl Default for BitmapTextOptions {
+    #[inline]
+    fn default() -> BitmapTextOptions {
+        BitmapTextOptions {
+            transform: Transform2F::default(),
+            color: ColorU::white(),
+            clip_path: None,
+            blend_mode: BlendMode::SrcOver,
+        }
+    }
+}
+
+impl BitmapFont {
+    /// Parses a BMFont descriptor in the text format.
+    ///
+    /// The font has no page images yet; see `page_files()` and `set_page()`.
+    pub fn parse(descriptor: &str) -> Result<BitmapFont, BitmapFontError> {
+        let mut common = None;
+        let mut page_files = vec![];
+        let mut glyphs = HashMap::new();
+        let mut kerning = HashMap::new();
+        for (line_index, line) in descriptor.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = BitmapFontError::InvalidLine(line_index + 1);
+            let (tag, attributes) = parse_line(line).ok_or(invalid)?;
+            let attribute = |name: &str| {
+                attributes.iter().find(|&&(key, _)| key == name).map(|&(_, value)| value)
+            };
+            let number = |name: &str| {
+                attribute(name).and_then(|value| value.parse::<i32>().ok()).ok_or(invalid)
+            };
+            let character = |name: &str| {
+                number(name).map(|id| if id < 0 { None } else { std::char::from_u32(id as u32) })
+            };
+
+            match tag {
+                "common" => common = Some((number("lineHeight")?, number("base")?)),
+                "page" => {
+                    let id = number("id")?;
+                    let file = attribute("file").ok_or(invalid)?;
+                    if id < 0 {
+                        return Err(invalid);
+                    }
+                    if page_files.len() <= id as usize {
+                        page_files.resize(id as usize + 1, String::new());
+                    }
+                    page_files[id as usize] = file.to_owned();
+                }
+                "char" => {
+                    let glyph = BitmapGlyph {
+                        rect: RectI::new(vec2i(number("x")?, number("y")?),
+                                         vec2i(number("width")?, number("height")?)),
+                        offset: vec2i(number("xoffset")?, number("yoffset")?),
+                        advance: number("xadvance")?,
+                        page: number("page")?.max(0) as u32,
+                    };
+                    // Some tools emit a glyph for an invalid character ID, used as a fallback.
+                    if let Some(character) = character("id")? {
+                        glyphs.insert(character, glyph);
+                    }
+                }
+                "kerning" => {
+                    let amount = number("amount")?;
+                    if let (Some(first), Some(second)) = (character("first")?,
+                                                          character("second")?) {
+                        kerning.insert((first, second), amount);
+                    }
+                }
+                // `info`, `chars`, and `kernings` lines hold nothing needed for drawing.
+                _ => {}
+            }
+        }
+
+        let (line_height, base) = common.ok_or(BitmapFontError::MissingCommon)?;
+        let pages = vec![None; page_files.len()];
+        Ok(BitmapFont { line_height, base, page_files, pages, glyphs, kerning })
+    }
+
+    /// Returns the file names of the font's page images, by page index, as the descriptor names
+    /// them.
+    #[inline]
+    pub fn page_files(&self) -> &[String] {
+        &self.page_files
+    }
+
+    /// Attaches the image of a page.
+    pub fn set_page(&mut self, page: u32, image: Image) {
+        if self.pages.len() <= page as usize {
+            self.pages.resize(page as usize + 1, None);
+        }
+        self.pages[page as usize] = Some(image);
+    }
+
+    /// Returns the distance between the tops of successive lines, in pixels.
+    #[inline]
+    pub fn line_height(&self) -> i32 {
+        self.line_height
+    }
+
+    /// Returns the distance from the top of a line to its baseline, in pixels.
+    #[inline]
+    pub fn base(&self) -> i32 {
+        self.base
+    }
+
+    /// Returns the glyph for a character, if the font has one.
+    #[inline]
+    pub fn glyph(&self, character: char) -> Option<&BitmapGlyph> {
+        self.glyphs.get(&character)
+    }
+
+    /// Returns how far to move the pen between two characters, beyond the first one's advance.
+    #[inline]
+    pub fn kerning(&self, first: char, second: char) -> i32 {
+        self.kerning.get(&(first, second)).cloned().unwrap_or(0)
+    }
+
+    /// Returns the size of the text in pixels: the width of its longest line, and the height of
+    /// all of its lines.
+    pub fn measure(&self, text: &str) -> Vector2I {
+        let mut width = 0;
+        let line_count = text.split('\n').map(|line| {
+            width = width.max(self.layout_line(line).last().map_or(0, |&(_, _, end)| end));
+        }).count();
+        vec2i(width, line_count as i32 * self.line_height)
+    }
+
+    /// Draws text on top of all previously-added paths. `origin` is the left end of the first
+    /// line's baseline, in font pixels, before the transform. Lines are separated by `\n`.
+    ///
+    /// Characters the font has no glyph for, and glyphs whose page images haven't been set, are
+    /// skipped.
+    pub fn push_text(&self,
+                     scene: &mut Scene,
+                     text: &str,
+                     origin: Vector2F,
+                     options: &BitmapTextOptions) {
+        for (line_index, line) in text.split('\n').enumerate() {
+            let line_top = origin.y() - self.base as f32 +
+                (line_index as i32 * self.line_height) as f32;
+            for (glyph, pen_x, _) in self.layout_line(line) {
+                let page = match self.pages.get(glyph.page as usize) {
+                    Some(&Some(ref page)) => page,
+                    _ => continue,
+                };
+                if glyph.rect.width() <= 0 || glyph.rect.height() <= 0 {
+                    continue;
+                }
+
+                let glyph_origin = vec2f(origin.x() + pen_x as f32, line_top) +
+                    glyph.offset.to_f32();
+                let rect = RectF::new(glyph_origin, glyph.rect.size().to_f32());
+                let mut pattern = Pattern::from_image(page.clone());
+                pattern.apply_transform(options.transform *
+                    Transform2F::from_translation(glyph_origin - glyph.rect.origin().to_f32()));
+                pattern.set_smoothing_enabled(false);
+                let paint_id = scene.push_paint(&Paint::from_pattern(pattern));
+
+                let outline = Outline::from_rect(rect).transformed(&options.transform);
+                let mut path = DrawPath::new(outline, paint_id);
+                path.set_clip_path(options.clip_path);
+                path.set_blend_mode(options.blend_mode);
+                path.set_tint(options.color);
+                scene.push_draw_path(path);
+            }
+        }
+    }
+
+    // Returns each glyph of a line with the pen position it's drawn at and the position of the
+    // pen after it.
+    fn layout_line(&self, line: &str) -> Vec<(BitmapGlyph, i32, i32)> {
+        let mut glyphs = vec![];
+        let (mut pen_x, mut previous_character) = (0, None);
+        for character in line.chars() {
+            let glyph = match self.glyphs.get(&character) {
+                Some(glyph) => *glyph,
+                None => continue,
+            };
+            if let Some(previous_character) = previous_character {
+                pen_x += self.kerning(previous_character, character);
+            }
+            glyphs.push((glyph, pen_x, pen_x + glyph.advance));
+            pen_x += glyph.advance;
+            previous_character = Some(character);
+        }
+        glyphs
+    }
+}
+
+// Splits a descriptor line into its tag and its `key=value` attributes. Values may be quoted.
+fn parse_line(line: &str) -> Option<(&str, Vec<(&str, &str)>)> {
+    let line = line.trim();
+    let tag_end = line.find(char::is_whitespace).unwrap_or(line.len());
+    let (tag, mut rest) = (&line[..tag_end], &line[tag_end..]);
+    let mut attributes = vec![];
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Some((tag, attributes));
+        }
+        let equals = rest.find('=')?;
+        let key = &rest[..equals];
+        rest = &rest[(equals + 1)..];
+        let value_end = if rest.starts_with('"') {
+            rest = &rest[1..];
+            let value_end = rest.find('"')?;
+            attributes.push((key, &rest[..value_end]));
+            value_end + 1
+        } else {
+            let value_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            attributes.push((key, &rest[..value_end]));
+            value_end
+        };
+        rest = &rest[value_end..];
+    }
+}
+
+impl Display for BitmapFontError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            BitmapFontError::InvalidLine(line) => write!(formatter, "line {}: invalid", line),
+            BitmapFontError::MissingCommon => write!(formatter, "missing `common` line"),
+        }
+    }
+}
+
+impl Error for BitmapFontError {}
+
+#[cfg(test)]
+mod test {
+    use super::{BitmapFont, BitmapFontError, BitmapTextOptions};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::pattern::Image;
+    use pathfinder_geometry::rect::{RectF, RectI};
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::{vec2f, vec2i};
+    use pathfinder_renderer::scene::{DrawPathId, Scene};
+    use std::sync::Arc;
+
+    const DESCRIPTOR: &str = r#"info face="Pixel Font" size=8 bold=0 italic=0
+common lineHeight=10 base=8 scaleW=32 scaleH=32 pages=1 packed=0
+page id=0 file="pixel font_0.png"
+chars count=2
+char id=65 x=0 y=0 width=6 height=8 xoffset=0 yoffset=0 xadvance=7 page=0 chnl=15
+char id=86 x=8 y=0 width=6 height=8 xoffset=1 yoffset=0 xadvance=7 page=0 chnl=15
+kernings count=1
+kerning first=65 second=86 amount=-1
+"#;
+
+    #[test]
+    fn test_parse_and_measure() {
+        let font = BitmapFont::parse(DESCRIPTOR).unwrap();
+        assert_eq!(font.page_files(), &["pixel font_0.png".to_owned()]);
+        assert_eq!((font.line_height(), font.base()), (10, 8));
+        let glyph = font.glyph('V').unwrap();
+        assert_eq!(glyph.rect, RectI::new(vec2i(8, 0), vec2i(6, 8)));
+        assert_eq!(glyph.offset, vec2i(1, 0));
+        assert_eq!(font.kerning('A', 'V'), -1);
+        assert_eq!(font.kerning('V', 'A'), 0);
+
+        // "AV" is kerned together, and "?" has no glyph.
+        assert_eq!(font.measure("AV\nA?"), vec2i(13, 20));
+
+        assert_eq!(BitmapFont::parse("char id=65 x=0").err(),
+                   Some(BitmapFontError::InvalidLine(1)));
+        assert_eq!(BitmapFont::parse("info size=8").err(), Some(BitmapFontError::MissingCommon));
+    }
+
+    #[test]
+    fn test_push_text_draws_page_regions() {
+        let mut font = BitmapFont::parse(DESCRIPTOR).unwrap();
+        let mut scene = Scene::new();
+        let options = BitmapTextOptions {
+            transform: Transform2F::from_scale(2.0),
+            color: ColorU::new(255, 0, 0, 255),
+            ..BitmapTextOptions::default()
+        };
+
+        // Nothing is drawn until the page image is set.
+        font.push_text(&mut scene, "AV", vec2f(0.0, 8.0), &options);
+        assert_eq!(scene.draw_path_count(), 0);
+        font.set_page(0, Image::new(vec2i(32, 32), Arc::new(vec![ColorU::white(); 32 * 32])));
+        font.push_text(&mut scene, "AV", vec2f(0.0, 8.0), &options);
+        assert_eq!(scene.draw_path_count(), 2);
+
+        // The "V" is kerned one pixel left and offset one pixel right, then scaled up.
+        let draw_path = scene.get_draw_path(DrawPathId(1));
+        assert_eq!(draw_path.outline.bounds(), RectF::new(vec2f(14.0, 0.0), vec2f(12.0, 16.0)));
+        assert_eq!(draw_path.tint, ColorU::new(255, 0, 0, 255));
+        let pattern = scene.get_paint(draw_path.paint).pattern().unwrap();
+        assert_eq!(pattern.transform(),
+                   Transform2F::from_scale(2.0) * Transform2F::from_translation(vec2f(-1.0, 0.0)));
+    }
+}
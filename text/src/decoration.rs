@@ -0,0 +1,215 @@
+// pathfinder/text/src/decoration.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Underlines, strikethroughs, and overlines drawn along runs of text.
+//!
+//! Decorations are positioned from the metrics of the first font in the run, the way CSS uses the
+//! primary font. Underlines use the font's own underline position and thickness. Fonts don't
+//! describe strikethroughs or overlines here, so those use the same thickness, centered halfway up
+//! the x-height and sitting on the ascent respectively.
+
+use crate::OutlinePathBuilder;
+use font_kit::error::GlyphLoadingError;
+use font_kit::hinting::HintingOptions;
+use font_kit::loader::Loader;
+use pathfinder_content::outline::{ContourIterFlags, Outline};
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::vec2f;
+use skribo::Layout;
+
+// The number of line segments each curve is flattened into when looking for ink.
+const INK_CURVE_STEPS: u32 = 16;
+
+/// Which lines to draw along a run of text.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TextDecorations {
+    /// Draw a line below the baseline.
+    pub underline: bool,
+    /// Draw a line through the middle of lowercase letters.
+    pub strikethrough: bool,
+    /// Draw a line along the top of the text.
+    pub overline: bool,
+    /// Break underlines and overlines where glyphs cross them, like the descenders of "g" and "y".
+    pub skip_ink: bool,
+}
+
+impl TextDecorations {
+    /// Returns true if no lines are drawn.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        !self.underline && !self.strikethrough && !self.overline
+    }
+}
+
+/// The rectangles of the decorations of a run, in layout space.
+pub(crate) struct DecorationRects {
+    /// Underlines and overlines, which are drawn beneath the glyphs.
+    pub(crate) below: Vec<RectF>,
+    /// Strikethroughs, which are drawn over the glyphs.
+    pub(crate) above: Vec<RectF>,
+}
+
+impl DecorationRects {
+    pub(crate) fn new(layout: &Layout, decorations: &TextDecorations)
+                      -> Result<DecorationRects, GlyphLoadingError> {
+        let mut rects = DecorationRects { below: vec![], above: vec![] };
+        let first_glyph = match layout.glyphs.first() {
+            Some(first_glyph) if !decorations.is_empty() => first_glyph,
+            _ => return Ok(rects),
+        };
+
+        let metrics = first_glyph.font.font.metrics();
+        let scale = layout.size / metrics.units_per_em as f32;
+        let thickness = if metrics.underline_thickness > 0.0 {
+            metrics.underline_thickness * scale
+        } else {
+            layout.size / 14.0
+        };
+
+        // Decorations run from the pen's start to its end, past the last glyph's advance.
+        let last_glyph = layout.glyphs.last().unwrap();
+        let last_scale = layout.size / last_glyph.font.font.metrics().units_per_em as f32;
+        let last_advance = last_glyph.font.font.advance(last_glyph.glyph_id)?.x() * last_scale;
+        let (start_x, end_x) = (0.0, last_glyph.offset.x() + last_advance);
+
+        // Layout space is Y-down, while font metrics are Y-up.
+        let mut lines_below = vec![];
+        if decorations.underline {
+            lines_below.push(-metrics.underline_position * scale);
+        }
+        if decorations.overline {
+            lines_below.push(-metrics.ascent * scale);
+        }
+        for top in lines_below {
+            let mut pieces = vec![(start_x, end_x)];
+            if decorations.skip_ink {
+                skip_ink(layout, &mut pieces, top, thickness)?;
+            }
+            rects.below.extend(pieces.into_iter().map(|(min_x, max_x)| {
+                RectF::from_points(vec2f(min_x, top), vec2f(max_x, top + thickness))
+            }));
+        }
+
+        if decorations.strikethrough {
+            let x_height = if metrics.x_height > 0.0 {
+                metrics.x_height
+            } else {
+                metrics.ascent * 0.5
+            };
+            let top = -x_height * 0.5 * scale - thickness * 0.5;
+            rects.above.push(RectF::from_points(vec2f(start_x, top),
+                                                vec2f(end_x, top + thickness)));
+        }
+
+        Ok(rects)
+    }
+}
+
+// Removes the parts of the line `pieces` where glyphs come within one line thickness of the line,
+// leaving a gap of the same size around each crossing.
+fn skip_ink(layout: &Layout, pieces: &mut Vec<(f32, f32)>, top: f32, thickness: f32)
+            -> Result<(), GlyphLoadingError> {
+    let (min_y, max_y) = (top - thickness, top + thickness * 2.0);
+    for glyph in &layout.glyphs {
+        let font = &glyph.font.font;
+        let scale = layout.size / font.metrics().units_per_em as f32;
+        let transform = Transform2F::from_scale(vec2f(scale, -scale)).translate(glyph.offset);
+        let mut outline_builder = OutlinePathBuilder::new(&transform);
+        font.outline(glyph.glyph_id, HintingOptions::None, &mut outline_builder)?;
+        let outline = outline_builder.build();
+
+        if let Some((ink_min_x, ink_max_x)) = ink_extent(&outline, min_y, max_y) {
+            subtract_range(pieces, ink_min_x - thickness, ink_max_x + thickness);
+        }
+    }
+    Ok(())
+}
+
+// Returns the horizontal extent of the parts of `outline` between `min_y` and `max_y`.
+fn ink_extent(outline: &Outline, min_y: f32, max_y: f32) -> Option<(f32, f32)> {
+    let bounds = outline.bounds();
+    if outline.is_empty() || bounds.max_y() < min_y || bounds.min_y() > max_y {
+        return None;
+    }
+
+    let mut extent: Option<(f32, f32)> = None;
+    for contour in outline.contours() {
+        for segment in contour.iter(ContourIterFlags::empty()) {
+            let steps = if segment.is_line() { 1 } else { INK_CURVE_STEPS };
+            let mut from = segment.sample(0.0);
+            for step in 1..=steps {
+                let to = segment.sample(step as f32 / steps as f32);
+
+                // Clip the flattened edge to the band.
+                let (top, bottom) = if from.y() <= to.y() { (from, to) } else { (to, from) };
+                if bottom.y() >= min_y && top.y() <= max_y {
+                    let (x0, x1) = if bottom.y() == top.y() {
+                        (top.x(), bottom.x())
+                    } else {
+                        let x_at = |y: f32| {
+                            let t = ((y - top.y()) / (bottom.y() - top.y())).max(0.0).min(1.0);
+                            top.x() + (bottom.x() - top.x()) * t
+                        };
+                        (x_at(min_y), x_at(max_y))
+                    };
+                    let (edge_min_x, edge_max_x) = (x0.min(x1), x0.max(x1));
+                    extent = Some(match extent {
+                        None => (edge_min_x, edge_max_x),
+                        Some((min_x, max_x)) => (min_x.min(edge_min_x), max_x.max(edge_max_x)),
+                    });
+                }
+
+                from = to;
+            }
+        }
+    }
+    extent
+}
+
+// Removes the range from `min_x` to `max_x` from each of the line pieces.
+fn subtract_range(pieces: &mut Vec<(f32, f32)>, min_x: f32, max_x: f32) {
+    let mut remaining = Vec::with_capacity(pieces.len() + 1);
+    for &(piece_min_x, piece_max_x) in pieces.iter() {
+        if max_x <= piece_min_x || min_x >= piece_max_x {
+            remaining.push((piece_min_x, piece_max_x));
+            continue;
+        }
+        if min_x > piece_min_x {
+            remaining.push((piece_min_x, min_x));
+        }
+        if max_x < piece_max_x {
+            remaining.push((max_x, piece_max_x));
+        }
+    }
+    *pieces = remaining;
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ink_extent, subtract_range};
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_ink_is_skipped() {
+        // A descender crossing the underline, and a glyph that stays above it.
+        let descender = Outline::from_rect(RectF::new(vec2f(10.0, -8.0), vec2f(2.0, 12.0)));
+        assert_eq!(ink_extent(&descender, 1.0, 3.0), Some((10.0, 12.0)));
+        let above = Outline::from_rect(RectF::new(vec2f(20.0, -8.0), vec2f(4.0, 8.0)));
+        assert_eq!(ink_extent(&above, 1.0, 3.0), None);
+
+        let mut pieces = vec![(0.0, 40.0)];
+        subtract_range(&mut pieces, 9.0, 13.0);
+        subtract_range(&mut pieces, 30.0, 50.0);
+        assert_eq!(pieces, vec![(0.0, 9.0), (13.0, 30.0)]);
+    }
+}
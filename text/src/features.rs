@@ -0,0 +1,308 @@
+// pathfinder/text/src/features.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! OpenType feature control, such as tabular numbers, small caps, and stylistic sets.
+//!
+//! `skribo::layout()` maps characters straight to glyphs, so `layout_with_features()` applies the
+//! requested features on top of it, span by span, from the font's `GSUB` table. Only single
+//! substitutions (one glyph replaced by another) are supported, which covers `tnum`, `lnum`,
+//! `onum`, `smcp`, `c2sc`, `zero`, and the stylistic sets `ss01` through `ss20` in most fonts.
+//! Features are looked up regardless of script and language. Glyphs after a substitution are
+//! moved to account for its change in advance.
+
+use crate::font_context::glyph_text_indices;
+use font_kit::loader::Loader;
+use font_kit::loaders::default::Font as DefaultLoader;
+use pathfinder_geometry::vector::vec2f;
+use skribo::{FontCollection, Layout, TextStyle};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+const GSUB_TAG: u32 = 0x47535542;
+
+const LOOKUP_TYPE_SINGLE: u16 = 1;
+const LOOKUP_TYPE_EXTENSION: u16 = 7;
+
+/// An OpenType feature to turn on, identified by its four-character tag.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FontFeature(pub [u8; 4]);
+
+impl FontFeature {
+    /// Tabular numbers, which all have the same width so that columns of figures line up.
+    pub const TABULAR_NUMBERS: FontFeature = FontFeature(*b"tnum");
+    /// Small capitals in place of lowercase letters.
+    pub const SMALL_CAPS: FontFeature = FontFeature(*b"smcp");
+
+    /// Returns the stylistic set with the given number, from 1 to 20.
+    #[inline]
+    pub fn stylistic_set(number: u8) -> FontFeature {
+        debug_assert!(number >= 1 && number <= 20);
+        FontFeature([b's', b's', b'0' + number / 10, b'0' + number % 10])
+    }
+
+    #[inline]
+    fn tag(self) -> u32 {
+        u32::from_be_bytes(self.0)
+    }
+}
+
+/// A range of text, in UTF-8 bytes, and the features to turn on in it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextSpan {
+    pub range: Range<usize>,
+    pub features: Vec<FontFeature>,
+}
+
+/// Lays out `text` like `skribo::layout()`, then applies the features of each span to the glyphs
+/// of the characters in it.
+pub fn layout_with_features(style: &TextStyle,
+                            collection: &FontCollection,
+                            text: &str,
+                            spans: &[TextSpan])
+                            -> Layout {
+    let mut layout = skribo::layout(style, collection, text);
+    apply_features(&mut layout, text, spans);
+    layout
+}
+
+/// Applies the features of each span to the glyphs of `layout`, which must have been created
+/// from `text` with `skribo::layout()`.
+pub fn apply_features(layout: &mut Layout, text: &str, spans: &[TextSpan]) {
+    if spans.iter().all(|span| span.features.is_empty()) {
+        return;
+    }
+
+    // Substitutions are loaded once for each font and set of features.
+    let mut substitutions: HashMap<(*const DefaultLoader, Vec<FontFeature>), Substitutions> =
+        HashMap::new();
+
+    let text_indices = glyph_text_indices(layout, text);
+    let mut shift = 0.0;
+    for (glyph, text_index) in layout.glyphs.iter_mut().zip(text_indices) {
+        let font: Arc<DefaultLoader> = glyph.font.font.clone();
+
+        glyph.offset += vec2f(shift, 0.0);
+        let mut features: Vec<FontFeature> = match text_index {
+            None => continue,
+            Some(text_index) => {
+                spans.iter()
+                     .filter(|span| span.range.contains(&text_index))
+                     .flat_map(|span| span.features.iter().cloned())
+                     .collect()
+            }
+        };
+        if features.is_empty() {
+            continue;
+        }
+        features.sort_by_key(|feature| feature.tag());
+        features.dedup();
+
+        let key = (Arc::as_ptr(&font), features);
+        let substitutions = substitutions.entry(key).or_insert_with_key(|(_, features)| {
+            match font.load_font_table(GSUB_TAG) {
+                None => Substitutions::default(),
+                Some(gsub) => Substitutions::new(&gsub, features),
+            }
+        });
+
+        let new_glyph_id = substitutions.apply(glyph.glyph_id);
+        if new_glyph_id == glyph.glyph_id {
+            continue;
+        }
+        let scale = layout.size / font.metrics().units_per_em as f32;
+        let advance = |glyph_id| font.advance(glyph_id).map(|advance| advance.x()).unwrap_or(0.0);
+        shift += (advance(new_glyph_id) - advance(glyph.glyph_id)) * scale;
+        glyph.glyph_id = new_glyph_id;
+    }
+}
+
+// The single substitutions of a set of features, one map per lookup, in the order they apply.
+#[derive(Default)]
+struct Substitutions {
+    lookups: Vec<HashMap<u16, u16>>,
+}
+
+impl Substitutions {
+    fn new(gsub: &[u8], features: &[FontFeature]) -> Substitutions {
+        let lookups = GsubReader(gsub).single_substitutions(features).unwrap_or_default();
+        Substitutions { lookups }
+    }
+
+    fn apply(&self, glyph_id: u32) -> u32 {
+        let mut glyph_id = glyph_id as u16;
+        for lookup in &self.lookups {
+            if let Some(&new_glyph_id) = lookup.get(&glyph_id) {
+                glyph_id = new_glyph_id;
+            }
+        }
+        glyph_id as u32
+    }
+}
+
+// Reads the parts of a `GSUB` table needed for single substitutions. Every read is bounds-checked,
+// so malformed tables yield `None` rather than panicking.
+struct GsubReader<'a>(&'a [u8]);
+
+impl<'a> GsubReader<'a> {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.0.get(offset..(offset + 2))?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.0.get(offset..(offset + 4))?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn single_substitutions(&self, features: &[FontFeature]) -> Option<Vec<HashMap<u16, u16>>> {
+        let feature_list = self.u16(6)? as usize;
+        let lookup_list = self.u16(8)? as usize;
+
+        // Gather the lookups of every requested feature. Lookups apply in lookup list order.
+        let mut lookup_indices = vec![];
+        let feature_count = self.u16(feature_list)? as usize;
+        for feature_index in 0..feature_count {
+            let record = feature_list + 2 + feature_index * 6;
+            if !features.iter().any(|feature| Some(feature.tag()) == self.u32(record)) {
+                continue;
+            }
+            let feature = feature_list + self.u16(record + 4)? as usize;
+            let lookup_index_count = self.u16(feature + 2)? as usize;
+            for lookup_index_index in 0..lookup_index_count {
+                lookup_indices.push(self.u16(feature + 4 + lookup_index_index * 2)?);
+            }
+        }
+        lookup_indices.sort_unstable();
+        lookup_indices.dedup();
+
+        let mut lookups = vec![];
+        for lookup_index in lookup_indices {
+            let lookup = lookup_list + self.u16(lookup_list + 2 + lookup_index as usize * 2)?
+                as usize;
+            let lookup_type = self.u16(lookup)?;
+            let subtable_count = self.u16(lookup + 4)? as usize;
+            let mut substitutions = HashMap::new();
+            for subtable_index in 0..subtable_count {
+                let mut subtable = lookup + self.u16(lookup + 6 + subtable_index * 2)? as usize;
+                let mut subtable_type = lookup_type;
+                if subtable_type == LOOKUP_TYPE_EXTENSION {
+                    subtable_type = self.u16(subtable + 2)?;
+                    subtable += self.u32(subtable + 4)? as usize;
+                }
+                if subtable_type == LOOKUP_TYPE_SINGLE {
+                    self.read_single_substitution(subtable, &mut substitutions)?;
+                }
+            }
+            lookups.push(substitutions);
+        }
+        Some(lookups)
+    }
+
+    fn read_single_substitution(&self, subtable: usize, substitutions: &mut HashMap<u16, u16>)
+                                -> Option<()> {
+        let format = self.u16(subtable)?;
+        let coverage = subtable + self.u16(subtable + 2)? as usize;
+        for (coverage_index, glyph_id) in self.coverage(coverage)? {
+            let new_glyph_id = match format {
+                1 => glyph_id.wrapping_add(self.u16(subtable + 4)?),
+                2 => self.u16(subtable + 6 + coverage_index * 2)?,
+                _ => return None,
+            };
+            // Earlier subtables of a lookup take precedence.
+            substitutions.entry(glyph_id).or_insert(new_glyph_id);
+        }
+        Some(())
+    }
+
+    // Returns the glyphs in a coverage table, with their coverage indices.
+    fn coverage(&self, coverage: usize) -> Option<Vec<(usize, u16)>> {
+        let mut glyphs = vec![];
+        match self.u16(coverage)? {
+            1 => {
+                let glyph_count = self.u16(coverage + 2)? as usize;
+                for coverage_index in 0..glyph_count {
+                    glyphs.push((coverage_index, self.u16(coverage + 4 + coverage_index * 2)?));
+                }
+            }
+            2 => {
+                let range_count = self.u16(coverage + 2)? as usize;
+                for range_index in 0..range_count {
+                    let record = coverage + 4 + range_index * 6;
+                    let (start, end) = (self.u16(record)?, self.u16(record + 2)?);
+                    let start_coverage_index = self.u16(record + 4)? as usize;
+                    for glyph_id in start..=end {
+                        glyphs.push((start_coverage_index + (glyph_id - start) as usize,
+                                     glyph_id));
+                    }
+                }
+            }
+            _ => return None,
+        }
+        Some(glyphs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FontFeature, GsubReader};
+
+    fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+        bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    #[test]
+    fn test_single_substitutions() {
+        let mut gsub = vec![];
+        // Header: version 1.0, then the script, feature, and lookup list offsets.
+        for &value in &[1, 0, 10, 10, 36] {
+            push_u16(&mut gsub, value);
+        }
+        // Feature list at 10: "smcp" using lookup 0, and "tnum" using lookup 1.
+        push_u16(&mut gsub, 2);
+        gsub.extend_from_slice(b"smcp");
+        push_u16(&mut gsub, 14);
+        gsub.extend_from_slice(b"tnum");
+        push_u16(&mut gsub, 20);
+        for &value in &[0, 1, 0] {
+            push_u16(&mut gsub, value);
+        }
+        for &value in &[0, 1, 1] {
+            push_u16(&mut gsub, value);
+        }
+        assert_eq!(gsub.len(), 36);
+        // Lookup list at 36, with lookups at 42 and 66.
+        for &value in &[2, 6, 30] {
+            push_u16(&mut gsub, value);
+        }
+        // Lookup 0: format 1, adding 100 to glyphs 5 through 7, from a range coverage table.
+        for &value in &[1, 0, 1, 8, 1, 6, 100, 2, 1, 5, 7, 0] {
+            push_u16(&mut gsub, value);
+        }
+        // Lookup 1: format 2, mapping glyphs 20 and 21 to 40 and 41.
+        for &value in &[1, 0, 1, 8, 2, 10, 2, 40, 41, 1, 2, 20, 21] {
+            push_u16(&mut gsub, value);
+        }
+
+        let reader = GsubReader(&gsub);
+        let lookups = reader.single_substitutions(&[FontFeature::SMALL_CAPS]).unwrap();
+        assert_eq!(lookups.len(), 1);
+        assert_eq!(lookups[0].get(&6), Some(&106));
+        assert_eq!(lookups[0].get(&8), None);
+
+        let lookups = reader.single_substitutions(&[FontFeature::TABULAR_NUMBERS]).unwrap();
+        assert_eq!(lookups.len(), 1);
+        assert_eq!(lookups[0].get(&21), Some(&41));
+
+        assert_eq!(FontFeature::stylistic_set(7), FontFeature(*b"ss07"));
+        assert!(GsubReader(&gsub[..20]).single_substitutions(&[FontFeature::SMALL_CAPS])
+                                        .is_none());
+    }
+}
@@ -0,0 +1,831 @@
+// pathfinder/text/src/font_context.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Drawing text from fonts loaded with `font-kit` and laid out with `skribo`.
+
+use crate::decoration::{DecorationRects, TextDecorations};
+#[cfg(feature = "svg-glyphs")]
+use crate::svg_glyphs::SvgGlyphs;
+use crate::synthesis::{SYNTHETIC_BOLD_DILATION, SYNTHETIC_OBLIQUE_SKEW, Synthesis};
+use crate::{GlyphEffect, GlyphEffectId, GlyphId, GlyphOutline, GlyphRenderStrategy};
+use crate::{PositionedGlyph, SubpixelPositions, TextRenderMode};
+use font_kit::canvas::{Canvas, Format, RasterizationOptions};
+use font_kit::error::GlyphLoadingError;
+use font_kit::hinting::HintingOptions;
+use font_kit::loader::Loader;
+use font_kit::loaders::default::Font as DefaultLoader;
+use font_kit::metrics::Metrics;
+use font_kit::outline::OutlineSink;
+use pathfinder_color::ColorU;
+use pathfinder_content::effects::BlendMode;
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_content::pattern::{Image, Pattern};
+use pathfinder_content::stroke::OutlineStrokeToFill;
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::rect::{RectF, RectI};
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I, vec2f};
+use pathfinder_renderer::paint::{Paint, PaintId};
+use pathfinder_renderer::scene::{ClipPathId, DrawPath, Scene};
+#[cfg(feature = "svg-glyphs")]
+use pathfinder_renderer::symbol::{SymbolId, SymbolInstance, SymbolMode};
+use skribo::{FontCollection, Layout, TextStyle};
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct FontContext<F> where F: Loader {
+    font_info: HashMap<String, FontInfo<F>>, 
+    glyph_effects: Vec<GlyphEffect>,
+    // The synthetic bold effects added so far, keyed by the effect they're applied on top of.
+    synthetic_bold_effects: HashMap<Option<GlyphEffectId>, GlyphEffectId>,
+}
+
+#[derive(Clone)]
+struct FontInfo<F> where F: Loader {
+    font: F,
+    metrics: Metrics,
+    // Outlines are cached after their glyph effects, if any, have been applied.
+    outline_cache: HashMap<(GlyphId, Option<GlyphEffectId>), Outline>,
+    // Blank glyphs are cached as `None`.
+    raster_cache: HashMap<GlyphRasterKey, Option<GlyphRaster>>,
+    #[cfg(feature = "svg-glyphs")]
+    svg_glyphs: Option<SvgGlyphs>,
+    // The symbols that SVG glyphs have been defined as, keyed by the ID of the defining scene.
+    #[cfg(feature = "svg-glyphs")]
+    svg_glyph_symbols: HashMap<(u32, GlyphId), SymbolId>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphRasterKey {
+    glyph_id: GlyphId,
+    pixel_size_bits: u32,
+    subpixel_positions: SubpixelPositions,
+    // The index of the subpixel position along each axis.
+    subpixel_steps: [u8; 2],
+}
+
+#[derive(Clone)]
+struct GlyphRaster {
+    // The offset of the upper left corner of the image from the pen position, in pixels.
+    origin: Vector2I,
+    image: Image,
+}
+
+#[derive(Clone, Copy)]
+pub struct FontRenderOptions {
+    pub transform: Transform2F,
+    pub render_mode: TextRenderMode,
+    pub hinting_options: HintingOptions,
+    pub clip_path: Option<ClipPathId>,
+    pub blend_mode: BlendMode,
+    pub paint_id: PaintId,
+    pub decorations: TextDecorations,
+    pub glyph_strategy: GlyphRenderStrategy,
+    /// The effect that post-processes the glyph outlines, if any.
+    pub glyph_effect: Option<GlyphEffectId>,
+    /// The positions within a pixel that glyphs drawn from rasters are snapped to.
+    pub subpixel_positions: SubpixelPositions,
+}
+
+impl Default for FontRenderOptions {
+    #[inline]
+    fn default() -> FontRenderOptions {
+        FontRenderOptions {
+            transform: Transform2F::default(),
+            render_mode: TextRenderMode::Fill,
+            hinting_options: HintingOptions::None,
+            clip_path: None,
+            blend_mode: BlendMode::SrcOver,
+            paint_id: PaintId(0),
+            decorations: TextDecorations::default(),
+            glyph_strategy: GlyphRenderStrategy::Outlines,
+            glyph_effect: None,
+            subpixel_positions: SubpixelPositions::default(),
+        }
+    }
+}
+
+enum FontInfoRefMut<'a, F> where F: Loader {
+    Ref(&'a mut FontInfo<F>),
+    Owned(FontInfo<F>),
+}
+
+impl<F> FontContext<F> where F: Loader {
+    #[inline]
+    pub fn new() -> FontContext<F> {
+        FontContext {
+            font_info: HashMap::new(),
+            glyph_effects: vec![],
+            synthetic_bold_effects: HashMap::new(),
+        }
+    }
+
+    /// Adds an effect that post-processes glyph outlines, and returns its ID. Text drawn with
+    /// `FontRenderOptions::glyph_effect` set to that ID has its glyph outlines passed through the
+    /// effect before they're transformed, stroked, and tiled.
+    ///
+    /// Effects can add contours to embolden glyphs, replace glyphs with their strokes, distort
+    /// them, and so on. Their results are cached like the plain outlines, per glyph and effect,
+    /// so an effect should always return the same outline for the same glyph. Glyphs drawn with
+    /// an effect are always filled as outlines, whatever the glyph strategy.
+    pub fn push_glyph_effect<E>(&mut self, effect: E) -> GlyphEffectId
+                                where E: Fn(GlyphId, Outline, u32) -> Outline +
+                                         Send + Sync + 'static {
+        let id = GlyphEffectId(self.glyph_effects.len() as u32);
+        self.glyph_effects.push(Arc::new(effect));
+        id
+    }
+
+    // Returns the effect that emboldens glyphs after applying `base_effect`, adding it if needed.
+    fn synthetic_bold_effect(&mut self, base_effect_id: Option<GlyphEffectId>) -> GlyphEffectId {
+        if let Some(&effect_id) = self.synthetic_bold_effects.get(&base_effect_id) {
+            return effect_id;
+        }
+        let base_effect = base_effect_id.map(|id| self.glyph_effects[id.0 as usize].clone());
+        let effect_id = self.push_glyph_effect(move |glyph_id, mut outline, units_per_em| {
+            if let Some(ref base_effect) = base_effect {
+                outline = base_effect(glyph_id, outline, units_per_em);
+            }
+            // Keep the left side bearing, since the advance grows by both sides' dilation.
+            let dilation = units_per_em as f32 * SYNTHETIC_BOLD_DILATION;
+            outline.dilate(vec2f(dilation, dilation));
+            outline.transform(&Transform2F::from_translation(vec2f(dilation, 0.0)));
+            outline
+        });
+        self.synthetic_bold_effects.insert(base_effect_id, effect_id);
+        effect_id
+    }
+
+    // Returns the glyph effect that the render options select, if any.
+    fn glyph_effect(&self, render_options: &FontRenderOptions) -> Option<GlyphEffect> {
+        render_options.glyph_effect.map(|id| self.glyph_effects[id.0 as usize].clone())
+    }
+
+    fn push_glyph(&mut self,
+                  scene: &mut Scene,
+                  font: &F,
+                  font_key: Option<&str>,
+                  glyph_id: GlyphId,
+                  glyph_offset: Vector2F,
+                  font_size: f32,
+                  render_options: &FontRenderOptions)
+                  -> Result<(), GlyphLoadingError> {
+        let glyph_effect = self.glyph_effect(render_options);
+        let mut font_info = self.font_info(font, font_key);
+        let font_info = font_info.get_mut();
+
+        #[cfg(feature = "svg-glyphs")]
+        {
+            if font_info.push_svg_glyph(scene, glyph_id, glyph_offset, font_size, render_options) {
+                return Ok(());
+            }
+        }
+
+        if let Some(pixel_size) = raster_pixel_size(scene, font_size, render_options) {
+            return push_glyph_raster(scene,
+                                     font,
+                                     &mut font_info.raster_cache,
+                                     glyph_id,
+                                     glyph_offset,
+                                     pixel_size,
+                                     render_options);
+        }
+
+        let outline = font_info.glyph_outline(font,
+                                              glyph_id,
+                                              glyph_offset,
+                                              font_size,
+                                              render_options,
+                                              glyph_effect.as_ref())?;
+        let mut path = DrawPath::new(outline, render_options.paint_id);
+        path.set_clip_path(render_options.clip_path);
+        path.set_blend_mode(render_options.blend_mode);
+
+        scene.push_draw_path(path);
+        Ok(())
+    }
+
+    // Returns the cached information for a font, inserting it into the cache if needed.
+    fn font_info(&mut self, font: &F, font_key: Option<&str>) -> FontInfoRefMut<F> {
+        match font_key {
+            Some(font_key) => {
+                if !self.font_info.contains_key(&*font_key) {
+                    self.font_info.insert(font_key.to_owned(), FontInfo::new((*font).clone()));
+                }
+                FontInfoRefMut::Ref(self.font_info.get_mut(&*font_key).unwrap())
+            }
+            None => {
+                // FIXME(pcwalton): This slow path can be removed once we have a unique font ID in
+                // `font-kit`.
+                FontInfoRefMut::Owned(FontInfo::new((*font).clone()))
+            }
+        }
+    }
+
+    /// Attempts to look up a font in the font cache.
+    #[inline]
+    pub fn get_cached_font(&self, postscript_name: &str) -> Option<&F> {
+        self.font_info.get(postscript_name).map(|font_info| &font_info.font)
+    }
+}
+
+impl FontContext<DefaultLoader> {
+    pub fn push_layout(&mut self,
+                       scene: &mut Scene,
+                       layout: &Layout,
+                       style: &TextStyle,
+                       render_options: &FontRenderOptions)
+                       -> Result<(), GlyphLoadingError> {
+        self.push_layout_with_glyph_transforms(scene,
+                                               layout,
+                                               style,
+                                               render_options,
+                                               |_| Transform2F::default())
+    }
+
+    /// Draws a laid-out run like `push_layout()`, but transforms each glyph by the transform
+    /// that `glyph_transform` returns for it.
+    ///
+    /// The transform is in the run's coordinate space, before the render transform, with its
+    /// origin at the glyph's origin on the baseline: rotations turn the glyph about that point,
+    /// skews slant it as fake italics do, and offsets that follow a sine of the glyph's X
+    /// position make wavy text. Glyphs whose transforms aren't translations are filled as
+    /// outlines even if the glyph strategy would draw them from rasters. Decorations aren't
+    /// transformed.
+    pub fn push_layout_with_glyph_transforms<T>(&mut self,
+                                                scene: &mut Scene,
+                                                layout: &Layout,
+                                                style: &TextStyle,
+                                                render_options: &FontRenderOptions,
+                                                mut glyph_transform: T)
+                                                -> Result<(), GlyphLoadingError>
+                                                where T: FnMut(&PositionedGlyph) -> Transform2F {
+        self.push_layout_with_glyph_options(scene, layout, style, render_options, |glyph| {
+            transform_glyph(render_options, glyph.offset, glyph_transform(glyph))
+        })
+    }
+
+    /// Draws a laid-out run like `push_layout()`, synthesizing bold and oblique glyphs where
+    /// `synthesis` asks for them. `synthesis` holds the styles to synthesize for each glyph, as
+    /// returned by `synthesis::layout_synthesis()`.
+    ///
+    /// Emboldened glyphs are widened by a glyph effect, applied after the render options' own
+    /// effect if they have one, and oblique glyphs are sheared about their origins. Decorations
+    /// keep the run's original extent.
+    pub fn push_layout_with_synthesis(&mut self,
+                                      scene: &mut Scene,
+                                      layout: &Layout,
+                                      style: &TextStyle,
+                                      synthesis: &[Synthesis],
+                                      render_options: &FontRenderOptions)
+                                      -> Result<(), GlyphLoadingError> {
+        let bold_effect = if synthesis.iter().any(|synthesis| synthesis.bold) {
+            Some(self.synthetic_bold_effect(render_options.glyph_effect))
+        } else {
+            None
+        };
+
+        // Each emboldened glyph pushes the glyphs after it along by its extra width.
+        let bold_advance = 2.0 * SYNTHETIC_BOLD_DILATION * style.size;
+        let mut shift = 0.0;
+        self.push_layout_with_glyph_options(scene, layout, style, render_options, |glyph| {
+            let synthesis = synthesis.get(glyph.index).cloned().unwrap_or_default();
+            let mut transform = Transform2F::from_translation(vec2f(shift, 0.0));
+            if synthesis.oblique {
+                transform = transform *
+                    Transform2F::row_major(1.0, -SYNTHETIC_OBLIQUE_SKEW, 0.0, 0.0, 1.0, 0.0);
+            }
+            let mut glyph_render_options = transform_glyph(render_options, glyph.offset, transform);
+            if synthesis.bold {
+                glyph_render_options.glyph_effect = bold_effect;
+                shift += bold_advance;
+            }
+            glyph_render_options
+        })
+    }
+
+    // Draws a laid-out run like `push_layout()`, with render options of each glyph's own.
+    fn push_layout_with_glyph_options<O>(&mut self,
+                                         scene: &mut Scene,
+                                         layout: &Layout,
+                                         style: &TextStyle,
+                                         render_options: &FontRenderOptions,
+                                         mut glyph_options: O)
+                                         -> Result<(), GlyphLoadingError>
+                                         where O: FnMut(&PositionedGlyph) -> FontRenderOptions {
+        // Underlines and overlines go beneath the glyphs, and strikethroughs over them.
+        let decoration_rects = DecorationRects::new(layout, &render_options.decorations)?;
+        push_decoration_rects(scene, &decoration_rects.below, render_options);
+
+        let mut index = 0;
+        for_each_layout_glyph(layout, |font, font_key, glyph_id, offset| {
+            let glyph_render_options = glyph_options(&PositionedGlyph { index, glyph_id, offset });
+            index += 1;
+            self.push_glyph(scene,
+                            font,
+                            font_key,
+                            glyph_id,
+                            offset,
+                            style.size,
+                            &glyph_render_options)
+        })?;
+
+        push_decoration_rects(scene, &decoration_rects.above, render_options);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn push_text(&mut self,
+                     scene: &mut Scene,
+                     text: &str,
+                     style: &TextStyle,
+                     collection: &FontCollection,
+                     render_options: &FontRenderOptions)
+                     -> Result<(), GlyphLoadingError> {
+        let layout = skribo::layout(style, collection, text);
+        self.push_layout(scene, &layout, style, render_options)
+    }
+
+    /// Returns the outlines of the glyphs of a laid-out run, exactly as `push_layout()` would
+    /// fill them, without drawing anything.
+    ///
+    /// The outlines come from the same fonts, hinting, and transform as drawn text, so they can
+    /// be combined with boolean operations, extruded, used as physics shapes, and so on. In
+    /// stroke mode, each outline is the fill that draws the glyph's stroke. Glyphs are always
+    /// returned as outlines, whatever the glyph strategy, and paints, clips, blend modes, and
+    /// decorations are ignored.
+    pub fn layout_glyph_outlines(&mut self,
+                                 layout: &Layout,
+                                 style: &TextStyle,
+                                 render_options: &FontRenderOptions)
+                                 -> Result<Vec<GlyphOutline>, GlyphLoadingError> {
+        let glyph_effect = self.glyph_effect(render_options);
+        let mut glyph_outlines = Vec::with_capacity(layout.glyphs.len());
+        for_each_layout_glyph(layout, |font, font_key, glyph_id, offset| {
+            let mut font_info = self.font_info(font, font_key);
+            let font_info = font_info.get_mut();
+            let outline = font_info.glyph_outline(font,
+                                                  glyph_id,
+                                                  offset,
+                                                  style.size,
+                                                  render_options,
+                                                  glyph_effect.as_ref())?;
+            glyph_outlines.push(GlyphOutline { glyph_id, offset, outline });
+            Ok(())
+        })?;
+        Ok(glyph_outlines)
+    }
+
+    /// Lays out text and returns the outlines of its glyphs. See `layout_glyph_outlines()`.
+    #[inline]
+    pub fn text_glyph_outlines(&mut self,
+                               text: &str,
+                               style: &TextStyle,
+                               collection: &FontCollection,
+                               render_options: &FontRenderOptions)
+                               -> Result<Vec<GlyphOutline>, GlyphLoadingError> {
+        let layout = skribo::layout(style, collection, text);
+        self.layout_glyph_outlines(&layout, style, render_options)
+    }
+
+    /// Returns a laid-out run as a single outline: the outlines of all of its glyphs, as from
+    /// `layout_glyph_outlines()`, together with its decorations.
+    pub fn layout_outline(&mut self,
+                          layout: &Layout,
+                          style: &TextStyle,
+                          render_options: &FontRenderOptions)
+                          -> Result<Outline, GlyphLoadingError> {
+        let decoration_rects = DecorationRects::new(layout, &render_options.decorations)?;
+        let glyph_outlines = self.layout_glyph_outlines(layout, style, render_options)?;
+
+        let mut outline = Outline::new();
+        let decoration_rects = decoration_rects.below.iter().chain(&decoration_rects.above);
+        let decoration_outlines = decoration_rects.map(|&rect| {
+            Outline::from_rect(rect).transformed(&render_options.transform)
+        });
+        let glyph_outlines = glyph_outlines.into_iter().map(|glyph_outline| glyph_outline.outline);
+        for part in glyph_outlines.chain(decoration_outlines) {
+            for contour in part.into_contours() {
+                outline.push_contour(contour);
+            }
+        }
+        Ok(outline)
+    }
+}
+
+// Returns render options that apply `transform` to a glyph about its origin, ahead of the render
+// transform.
+fn transform_glyph(render_options: &FontRenderOptions, offset: Vector2F, transform: Transform2F)
+                   -> FontRenderOptions {
+    FontRenderOptions {
+        transform: render_options.transform *
+            Transform2F::from_translation(offset) *
+            transform *
+            Transform2F::from_translation(-offset),
+        ..*render_options
+    }
+}
+
+// Returns the byte index in `text` of the character that each glyph of `layout` was made from,
+// where `layout` was created from `text` with `skribo::layout()`. Characters that no font maps
+// have no glyph.
+pub(crate) fn glyph_text_indices(layout: &Layout, text: &str) -> Vec<Option<usize>> {
+    let mut characters = text.char_indices();
+    layout.glyphs.iter().map(|glyph| {
+        loop {
+            match characters.next() {
+                None => break None,
+                Some((text_index, character))
+                        if glyph.font.font.glyph_for_char(character).is_some() => {
+                    break Some(text_index)
+                }
+                Some(_) => {}
+            }
+        }
+    }).collect()
+}
+
+// Calls `f` with each glyph of a layout, along with its font and the font's cache key, and its
+// offset.
+fn for_each_layout_glyph<F>(layout: &Layout, mut f: F) -> Result<(), GlyphLoadingError>
+                            where F: FnMut(&DefaultLoader, Option<&str>, GlyphId, Vector2F)
+                                           -> Result<(), GlyphLoadingError> {
+    let mut cached_font_key: Option<CachedFontKey<DefaultLoader>> = None;
+    for glyph in &layout.glyphs {
+        match cached_font_key {
+            Some(ref cached_font_key) if Arc::ptr_eq(&cached_font_key.font,
+                                                     &glyph.font.font) => {}
+            _ => {
+                cached_font_key = Some(CachedFontKey {
+                    font: glyph.font.font.clone(),
+                    key: glyph.font.font.postscript_name(),
+                });
+            }
+        }
+        let cached_font_key = cached_font_key.as_ref().unwrap();
+        f(&*cached_font_key.font,
+          cached_font_key.key.as_ref().map(|key| &**key),
+          GlyphId(glyph.glyph_id),
+          glyph.offset)?;
+    }
+    Ok(())
+}
+
+// Returns the size, in pixels per em, to draw a glyph from a raster at, or `None` if its outline
+// should be filled instead.
+fn raster_pixel_size(scene: &Scene, font_size: f32, render_options: &FontRenderOptions)
+                     -> Option<f32> {
+    let crossover = match render_options.glyph_strategy {
+        GlyphRenderStrategy::Outlines => return None,
+        GlyphRenderStrategy::Auto { crossover } => crossover,
+    };
+
+    // Glyph effects apply to outlines, not to the rasters that the font rasterizes itself.
+    if render_options.glyph_effect.is_some() {
+        return None;
+    }
+
+    // Rasters are drawn pixel-aligned and tinted, which only works for translations, uniform
+    // scales, and solid colors.
+    let transform = &render_options.transform;
+    let scale = transform.m11();
+    if render_options.render_mode != TextRenderMode::Fill || transform.m12() != 0.0 ||
+            transform.m21() != 0.0 || transform.m22() != scale || scale <= 0.0 {
+        return None;
+    }
+    let pixel_size = font_size * scale;
+    if pixel_size > crossover || !scene.get_paint(render_options.paint_id).is_color() {
+        return None;
+    }
+    Some(pixel_size)
+}
+
+fn push_glyph_raster<F>(scene: &mut Scene,
+                        font: &F,
+                        raster_cache: &mut HashMap<GlyphRasterKey, Option<GlyphRaster>>,
+                        glyph_id: GlyphId,
+                        glyph_offset: Vector2F,
+                        pixel_size: f32,
+                        render_options: &FontRenderOptions)
+                        -> Result<(), GlyphLoadingError>
+                        where F: Loader {
+    // Snap the pen to the nearest subpixel position, and reuse the raster made for it.
+    let pen = render_options.transform * glyph_offset;
+    let subpixel_positions = render_options.subpixel_positions;
+    let (pen_pixel, subpixel_steps) = subpixel_positions.snap(pen);
+
+    let key = GlyphRasterKey {
+        glyph_id,
+        pixel_size_bits: pixel_size.to_bits(),
+        subpixel_positions,
+        subpixel_steps,
+    };
+    if !raster_cache.contains_key(&key) {
+        let subpixel_offset = subpixel_positions.offset(subpixel_steps);
+        raster_cache.insert(key, rasterize_glyph(font, glyph_id, pixel_size, subpixel_offset)?);
+    }
+    let raster = match raster_cache[&key] {
+        None => return Ok(()),
+        Some(ref raster) => raster,
+    };
+
+    // The renderer packs pattern images into its own texture atlas.
+    let rect = RectI::new(pen_pixel + raster.origin, raster.image.size());
+    let mut pattern = Pattern::from_image(raster.image.clone());
+    pattern.apply_transform(Transform2F::from_translation(rect.origin().to_f32()));
+    pattern.set_smoothing_enabled(false);
+    let color = scene.get_paint(render_options.paint_id).base_color();
+    let paint_id = scene.push_paint(&Paint::from_pattern(pattern));
+
+    let mut path = DrawPath::new(Outline::from_rect(rect.to_f32()), paint_id);
+    path.set_clip_path(render_options.clip_path);
+    path.set_blend_mode(render_options.blend_mode);
+    path.set_tint(color);
+    scene.push_draw_path(path);
+    Ok(())
+}
+
+// Rasterizes a glyph in white, with its coverage in the alpha channel.
+fn rasterize_glyph<F>(font: &F, glyph_id: GlyphId, pixel_size: f32, subpixel_offset: Vector2F)
+                      -> Result<Option<GlyphRaster>, GlyphLoadingError>
+                      where F: Loader {
+    let transform = Transform2F::from_translation(subpixel_offset);
+    let bounds = font.raster_bounds(glyph_id.0,
+                                    pixel_size,
+                                    transform,
+                                    HintingOptions::None,
+                                    RasterizationOptions::GrayscaleAa)?;
+    if bounds.width() <= 0 || bounds.height() <= 0 {
+        return Ok(None);
+    }
+
+    let mut canvas = Canvas::new(bounds.size(), Format::A8);
+    font.rasterize_glyph(&mut canvas,
+                         glyph_id.0,
+                         pixel_size,
+                         Transform2F::from_translation(-bounds.origin().to_f32()) * transform,
+                         HintingOptions::None,
+                         RasterizationOptions::GrayscaleAa)?;
+
+    let width = bounds.width() as usize;
+    let mut pixels = Vec::with_capacity(width * bounds.height() as usize);
+    for row in canvas.pixels.chunks(canvas.stride) {
+        pixels.extend(row[..width].iter().map(|&alpha| ColorU::new(255, 255, 255, alpha)));
+    }
+    let image = Image::new(bounds.size(), Arc::new(pixels));
+    Ok(Some(GlyphRaster { origin: bounds.origin(), image }))
+}
+
+fn push_decoration_rects(scene: &mut Scene, rects: &[RectF], render_options: &FontRenderOptions) {
+    for &rect in rects {
+        let outline = Outline::from_rect(rect).transformed(&render_options.transform);
+        let mut path = DrawPath::new(outline, render_options.paint_id);
+        path.set_clip_path(render_options.clip_path);
+        path.set_blend_mode(render_options.blend_mode);
+        scene.push_draw_path(path);
+    }
+}
+
+struct CachedFontKey<F> where F: Loader {
+    font: Arc<F>,
+    key: Option<String>,
+}
+
+impl<F> FontInfo<F> where F: Loader {
+    fn new(font: F) -> FontInfo<F> {
+        let metrics = font.metrics();
+        #[cfg(feature = "svg-glyphs")]
+        let svg_glyphs = SvgGlyphs::from_font(&font);
+        FontInfo {
+            font,
+            metrics,
+            outline_cache: HashMap::new(),
+            raster_cache: HashMap::new(),
+            #[cfg(feature = "svg-glyphs")]
+            svg_glyphs,
+            #[cfg(feature = "svg-glyphs")]
+            svg_glyph_symbols: HashMap::new(),
+        }
+    }
+
+    // Draws a glyph from the font's `SVG ` table, if it's there, and returns true. The glyph's
+    // scene is defined as a symbol in each scene it's drawn in, the first time it's drawn there.
+    // SVG glyphs have their own colors, so the paint and blend mode are ignored.
+    #[cfg(feature = "svg-glyphs")]
+    fn push_svg_glyph(&mut self,
+                      scene: &mut Scene,
+                      glyph_id: GlyphId,
+                      glyph_offset: Vector2F,
+                      font_size: f32,
+                      render_options: &FontRenderOptions)
+                      -> bool {
+        let svg_glyphs = match self.svg_glyphs {
+            Some(ref mut svg_glyphs) => svg_glyphs,
+            None => return false,
+        };
+        let symbol_key = (scene.id().0, glyph_id);
+        let symbol_id = match self.svg_glyph_symbols.get(&symbol_key) {
+            Some(&symbol_id) => symbol_id,
+            None => {
+                let glyph_scene = match svg_glyphs.glyph_scene(glyph_id) {
+                    Some(glyph_scene) => glyph_scene,
+                    None => return false,
+                };
+                let symbol_id = scene.define_symbol((*glyph_scene).clone(), SymbolMode::Expand);
+                self.svg_glyph_symbols.insert(symbol_key, symbol_id);
+                symbol_id
+            }
+        };
+
+        // SVG glyphs are in font units, with the Y axis already pointing down.
+        let font_scale = font_size / self.metrics.units_per_em as f32;
+        let mut instance = SymbolInstance::new(render_options.transform *
+                                               Transform2F::from_translation(glyph_offset) *
+                                               Transform2F::from_scale(font_scale));
+        instance.clip_path = render_options.clip_path;
+        scene.push_symbol_instance(symbol_id, &instance);
+        true
+    }
+
+    // Returns the outline of a glyph, passed through its effect if any, scaled to the font size,
+    // placed at the glyph offset, and transformed, as it's filled in the scene.
+    fn glyph_outline(&mut self,
+                     font: &F,
+                     glyph_id: GlyphId,
+                     glyph_offset: Vector2F,
+                     font_size: f32,
+                     render_options: &FontRenderOptions,
+                     glyph_effect: Option<&GlyphEffect>)
+                     -> Result<Outline, GlyphLoadingError> {
+        // See if we have a cached outline.
+        //
+        // TODO(pcwalton): Cache hinted outlines too.
+        let mut cached_outline = None;
+        let can_cache_outline = render_options.hinting_options == HintingOptions::None;
+        let cache_key = (glyph_id, render_options.glyph_effect);
+        if can_cache_outline {
+            if let Some(ref outline) = self.outline_cache.get(&cache_key) {
+                cached_outline = Some((*outline).clone());
+            }
+        }
+
+        let metrics = &self.metrics;
+        let font_scale = font_size / metrics.units_per_em as f32;
+        let render_transform = render_options.transform *
+            Transform2F::from_scale(vec2f(font_scale, -font_scale)).translate(glyph_offset);
+
+        let mut outline = match cached_outline {
+            Some(mut cached_outline) => {
+                let scale = 1.0 / metrics.units_per_em as f32;
+                cached_outline.transform(&(render_transform * Transform2F::from_scale(scale)));
+                cached_outline
+            }
+            None => {
+                let untransformed = can_cache_outline || glyph_effect.is_some();
+                let transform = if untransformed {
+                    Transform2F::from_scale(metrics.units_per_em as f32)
+                } else {
+                    render_transform
+                };
+                let mut outline_builder = OutlinePathBuilder::new(&transform);
+                font.outline(glyph_id.0, render_options.hinting_options, &mut outline_builder)?;
+                let mut outline = outline_builder.build();
+                let scale = 1.0 / metrics.units_per_em as f32;
+                if let Some(glyph_effect) = glyph_effect {
+                    // Effects see the outline in font units.
+                    outline.transform(&Transform2F::from_scale(scale));
+                    outline = glyph_effect(glyph_id, outline, metrics.units_per_em);
+                    outline.transform(&Transform2F::from_scale(metrics.units_per_em as f32));
+                }
+                if can_cache_outline {
+                    self.outline_cache.insert(cache_key, outline.clone());
+                }
+                if untransformed {
+                    outline.transform(&(render_transform * Transform2F::from_scale(scale)));
+                }
+                outline
+            }
+        };
+
+        if let TextRenderMode::Stroke(stroke_style) = render_options.render_mode {
+            let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, stroke_style);
+            stroke_to_fill.offset();
+            outline = stroke_to_fill.into_outline();
+        }
+        Ok(outline)
+    }
+}
+
+impl<'a, F> FontInfoRefMut<'a, F> where F: Loader {
+    fn get_mut(&mut self) -> &mut FontInfo<F> {
+        match *self {
+            FontInfoRefMut::Ref(ref mut reference) => &mut **reference,
+            FontInfoRefMut::Owned(ref mut info) => info,
+        }
+    }
+}
+
+impl SubpixelPositions {
+    // Snaps a pen position to the nearest subpixel position. Returns the whole pixel, and the
+    // index of the position within it along each axis.
+    fn snap(&self, pen: Vector2F) -> (Vector2I, [u8; 2]) {
+        let counts = self.counts();
+        let steps = (pen * counts).round();
+        let pixel = (steps / counts).floor();
+        let index = steps - pixel * counts;
+        (pixel.to_i32(), [index.x() as u8, index.y() as u8])
+    }
+
+    // The offset of the subpixel position with the given indices from the pixel's corner.
+    fn offset(&self, steps: [u8; 2]) -> Vector2F {
+        vec2f(steps[0] as f32, steps[1] as f32) / self.counts()
+    }
+
+    fn counts(&self) -> Vector2F {
+        vec2f(self.horizontal.max(1) as f32, self.vertical.max(1) as f32)
+    }
+}
+
+/// Collects the outline of a glyph from a font, applying a transform to every point.
+pub struct OutlinePathBuilder {
+    outline: Outline,
+    current_contour: Contour,
+    transform: Transform2F,
+}
+
+impl OutlinePathBuilder {
+    /// Creates a builder that applies `transform` to the glyph's points, which are in font units.
+    pub fn new(transform: &Transform2F) -> OutlinePathBuilder {
+        OutlinePathBuilder {
+            outline: Outline::new(),
+            current_contour: Contour::new(),
+            transform: *transform,
+        }
+    }
+
+    fn flush_current_contour(&mut self) {
+        if !self.current_contour.is_empty() {
+            self.outline.push_contour(mem::replace(&mut self.current_contour, Contour::new()));
+        }
+    }
+
+    /// Returns the collected outline.
+    pub fn build(mut self) -> Outline {
+        self.flush_current_contour();
+        self.outline
+    }
+}
+
+impl OutlineSink for OutlinePathBuilder {
+    fn move_to(&mut self, to: Vector2F) {
+        self.flush_current_contour();
+        self.current_contour.push_endpoint(self.transform * to);
+    }
+
+    fn line_to(&mut self, to: Vector2F) {
+        self.current_contour.push_endpoint(self.transform * to);
+    }
+
+    fn quadratic_curve_to(&mut self, ctrl: Vector2F, to: Vector2F) {
+        self.current_contour.push_quadratic(self.transform * ctrl, self.transform * to);
+    }
+
+    fn cubic_curve_to(&mut self, ctrl: LineSegment2F, to: Vector2F) {
+        self.current_contour.push_cubic(self.transform * ctrl.from(),
+                                        self.transform * ctrl.to(),
+                                        self.transform * to);
+    }
+
+    fn close(&mut self) {
+        self.current_contour.close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SubpixelPositions;
+    use pathfinder_geometry::vector::{vec2f, vec2i};
+
+    #[test]
+    fn test_subpixel_positions_snap_to_the_nearest() {
+        let positions = SubpixelPositions::default();
+        assert_eq!(positions.snap(vec2f(10.3, 5.4)), (vec2i(10, 5), [1, 0]));
+        // Rounding past the last position moves on to the next pixel.
+        assert_eq!(positions.snap(vec2f(10.9, 5.6)), (vec2i(11, 6), [0, 0]));
+        assert_eq!(positions.snap(vec2f(-0.3, 0.0)), (vec2i(-1, 0), [3, 0]));
+        assert_eq!(positions.offset([3, 0]), vec2f(0.75, 0.0));
+
+        let positions = SubpixelPositions { horizontal: 2, vertical: 2 };
+        assert_eq!(positions.snap(vec2f(10.3, 5.4)), (vec2i(10, 5), [1, 1]));
+        assert_eq!(positions.offset([1, 1]), vec2f(0.5, 0.5));
+    }
+}
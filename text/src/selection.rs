@@ -0,0 +1,151 @@
+// pathfinder/text/src/selection.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Caret and selection geometry for laid-out text.
+//!
+//! Editors draw carets and selection highlights around the glyphs and map clicks back to
+//! positions in the text. `TextCarets` computes both from the same layout that
+//! `FontContext::push_layout()` draws, so they line up with the rendered glyphs.
+//!
+//! All geometry is in layout space: the pen starts at the origin on the baseline, and Y points
+//! down. Apply the `transform` from `FontRenderOptions` to place it in the scene. Indices into the
+//! text are UTF-8 byte offsets, and the ones returned always fall on character boundaries.
+
+use font_kit::loader::Loader;
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::vector::{Vector2F, vec2f};
+use skribo::Layout;
+use std::cmp::Ordering;
+use std::ops::Range;
+
+/// The caret positions in a single line of laid-out text.
+#[derive(Clone, Debug)]
+pub struct TextCarets {
+    // One stop per character boundary, sorted by index. The first is at index 0 and the last at
+    // the length of the text.
+    stops: Vec<CaretStop>,
+    // The top and bottom of the line, from the fonts' ascent and descent.
+    top: f32,
+    bottom: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CaretStop {
+    index: usize,
+    x: f32,
+}
+
+impl TextCarets {
+    /// Computes the caret positions in `text`, which `layout` must have been created from with
+    /// `skribo::layout()` or `layout_with_features()`.
+    ///
+    /// Those emit one glyph for each character the fonts map, in order, so glyphs are matched
+    /// back to characters through each font's character map. Characters without a glyph take up
+    /// no space. Empty text has a single caret of zero height.
+    pub fn new(layout: &Layout, text: &str) -> TextCarets {
+        let mut stops = Vec::with_capacity(text.len() + 1);
+        let (mut top, mut bottom) = (0.0, 0.0);
+        let mut glyphs = layout.glyphs.iter().peekable();
+        let mut pen_x = 0.0;
+        for (index, character) in text.char_indices() {
+            let glyph = match glyphs.peek() {
+                Some(glyph) if glyph.font.font.glyph_for_char(character).is_some() => *glyph,
+                _ => {
+                    stops.push(CaretStop { index, x: pen_x });
+                    continue;
+                }
+            };
+            glyphs.next();
+
+            let font = &glyph.font.font;
+            let metrics = font.metrics();
+            let scale = layout.size / metrics.units_per_em as f32;
+            let advance = font.advance(glyph.glyph_id).map(|advance| advance.x()).unwrap_or(0.0);
+            stops.push(CaretStop { index, x: glyph.offset.x() });
+            pen_x = glyph.offset.x() + advance * scale;
+            top = f32::min(top, -metrics.ascent * scale);
+            bottom = f32::max(bottom, -metrics.descent * scale);
+        }
+        stops.push(CaretStop { index: text.len(), x: pen_x });
+
+        TextCarets { stops, top, bottom }
+    }
+
+    /// Returns the caret before the character at byte `index`, running from the top of the line
+    /// to the bottom.
+    ///
+    /// Indices inside a character snap back to its start, and indices past the end of the text
+    /// snap to the end.
+    pub fn caret(&self, index: usize) -> LineSegment2F {
+        let x = self.stops[self.stop_index(index)].x;
+        LineSegment2F::new(vec2f(x, self.top), vec2f(x, self.bottom))
+    }
+
+    /// Returns the rectangle that highlights the characters in the byte range `range`, running
+    /// from the top of the line to the bottom.
+    pub fn selection_rect(&self, range: Range<usize>) -> RectF {
+        let start_x = self.stops[self.stop_index(range.start)].x;
+        let end_x = self.stops[self.stop_index(range.end)].x;
+        RectF::from_points(vec2f(start_x.min(end_x), self.top),
+                           vec2f(start_x.max(end_x), self.bottom))
+    }
+
+    /// Returns the byte index of the character boundary nearest to `position`.
+    ///
+    /// A click on a character places the caret at whichever of its edges is nearer, as text
+    /// editors do. Only the X coordinate is considered, since the text is a single line.
+    pub fn hit_test(&self, position: Vector2F) -> usize {
+        let distance = |stop: &CaretStop| (stop.x - position.x()).abs();
+        self.stops
+            .iter()
+            .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap_or(Ordering::Equal))
+            .unwrap()
+            .index
+    }
+
+    fn stop_index(&self, index: usize) -> usize {
+        match self.stops.binary_search_by_key(&index, |stop| stop.index) {
+            Ok(stop_index) => stop_index,
+            Err(stop_index) => stop_index - 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CaretStop, TextCarets};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::vec2f;
+
+    #[test]
+    fn test_carets_and_hit_testing() {
+        // "héllo", where "é" is two bytes long and every glyph is 10 pixels wide.
+        let stops = [(0, 0.0), (1, 10.0), (3, 20.0), (4, 30.0), (5, 40.0), (6, 50.0)];
+        let carets = TextCarets {
+            stops: stops.iter().map(|&(index, x)| CaretStop { index, x }).collect(),
+            top: -12.0,
+            bottom: 4.0,
+        };
+
+        assert_eq!(carets.caret(3).from(), vec2f(20.0, -12.0));
+        assert_eq!(carets.caret(3).to(), vec2f(20.0, 4.0));
+        assert_eq!(carets.caret(2).from(), vec2f(10.0, -12.0));
+        assert_eq!(carets.caret(100).from(), vec2f(50.0, -12.0));
+
+        let selection = carets.selection_rect(1..4);
+        assert_eq!(selection, RectF::from_points(vec2f(10.0, -12.0), vec2f(30.0, 4.0)));
+
+        assert_eq!(carets.hit_test(vec2f(14.0, 0.0)), 1);
+        assert_eq!(carets.hit_test(vec2f(16.0, 0.0)), 3);
+        assert_eq!(carets.hit_test(vec2f(-5.0, 0.0)), 0);
+        assert_eq!(carets.hit_test(vec2f(80.0, 0.0)), 6);
+    }
+}
@@ -0,0 +1,181 @@
+// pathfinder/text/src/svg_glyphs.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Color glyphs from the OpenType `SVG ` table, as used by many emoji and icon fonts.
+//!
+//! The table maps ranges of glyph IDs to SVG documents. Each glyph is the element with the ID
+//! `glyph<ID>` in its document, drawn in font units with the Y axis pointing down and the origin
+//! at the glyph's origin on the baseline. `SvgGlyphs` imports the element through
+//! `pathfinder_svg` the first time the glyph is requested, and caches the resulting scene.
+//!
+//! Documents are parsed again for each glyph they hold, so fonts that pack many glyphs into one
+//! document are slow the first time each glyph is drawn. Glyph elements must be children of the
+//! document's root.
+
+use crate::GlyphId;
+use font_kit::loader::Loader;
+use pathfinder_renderer::scene::Scene;
+use pathfinder_svg::SVGScene;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+use usvg::{Options, Tree};
+
+const SVG_TAG: u32 = 0x53564720;
+
+/// The SVG documents of a font's `SVG ` table, and the scenes of the glyphs imported so far.
+#[derive(Clone)]
+pub struct SvgGlyphs {
+    table: Box<[u8]>,
+    records: Vec<SvgDocumentRecord>,
+    // Glyphs whose documents fail to import are cached as `None`.
+    scenes: HashMap<GlyphId, Option<Arc<Scene>>>,
+}
+
+#[derive(Clone)]
+struct SvgDocumentRecord {
+    glyph_ids: Range<u32>,
+    // The byte range of the document within the table.
+    document: Range<usize>,
+}
+
+impl SvgGlyphs {
+    /// Loads the `SVG ` table of a font, if it has a valid one.
+    pub fn from_font<F>(font: &F) -> Option<SvgGlyphs> where F: Loader {
+        SvgGlyphs::from_table(font.load_font_table(SVG_TAG)?)
+    }
+
+    /// Reads the raw contents of an `SVG ` table. Returns `None` if it's malformed.
+    pub fn from_table(table: Box<[u8]>) -> Option<SvgGlyphs> {
+        let records = read_document_records(&table)?;
+        Some(SvgGlyphs { table, records, scenes: HashMap::new() })
+    }
+
+    /// Returns true if the table has an SVG document for the glyph.
+    #[inline]
+    pub fn has_glyph(&self, glyph_id: GlyphId) -> bool {
+        self.record(glyph_id).is_some()
+    }
+
+    /// Returns the SVG document that holds the glyph, which may be gzip-compressed.
+    pub fn document(&self, glyph_id: GlyphId) -> Option<&[u8]> {
+        self.record(glyph_id).map(|record| &self.table[record.document.clone()])
+    }
+
+    /// Returns the glyph as a scene in font units, importing it if it hasn't been already.
+    ///
+    /// Returns `None` if the font has no SVG document for the glyph, or if the document can't be
+    /// parsed or doesn't contain the glyph.
+    pub fn glyph_scene(&mut self, glyph_id: GlyphId) -> Option<Arc<Scene>> {
+        if !self.scenes.contains_key(&glyph_id) {
+            let scene = self.import_glyph(glyph_id).map(Arc::new);
+            self.scenes.insert(glyph_id, scene);
+        }
+        self.scenes[&glyph_id].clone()
+    }
+
+    fn import_glyph(&self, glyph_id: GlyphId) -> Option<Scene> {
+        let record = self.record(glyph_id)?;
+
+        // Glyph elements are usually groups, which `usvg` would otherwise flatten away.
+        let options = Options { keep_named_groups: true, ..Options::default() };
+        let tree = Tree::from_data(&self.table[record.document.clone()], &options.to_ref()).ok()?;
+        let svg_scene = match tree.node_by_id(&format!("glyph{}", glyph_id.0)) {
+            Some(node) => SVGScene::from_tree_node(&tree, &node),
+            // Tolerate single-glyph documents that leave out the ID.
+            None if record.glyph_ids.len() == 1 => SVGScene::from_tree(&tree),
+            None => return None,
+        };
+        Some(svg_scene.scene)
+    }
+
+    fn record(&self, glyph_id: GlyphId) -> Option<&SvgDocumentRecord> {
+        self.records.iter().find(|record| record.glyph_ids.contains(&glyph_id.0))
+    }
+}
+
+// Reads the document index of an `SVG ` table. Every read is bounds-checked, so malformed tables
+// yield `None` rather than panicking.
+fn read_document_records(table: &[u8]) -> Option<Vec<SvgDocumentRecord>> {
+    let u16_at = |offset: usize| {
+        let bytes = table.get(offset..(offset + 2))?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    };
+    let u32_at = |offset: usize| {
+        let bytes = table.get(offset..(offset + 4))?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    let document_list = u32_at(2)? as usize;
+    let record_count = u16_at(document_list)? as usize;
+    let mut records = Vec::with_capacity(record_count);
+    for record_index in 0..record_count {
+        let record = document_list + 2 + record_index * 12;
+        let (start_glyph_id, end_glyph_id) = (u16_at(record)? as u32, u16_at(record + 2)? as u32);
+        let document_start = document_list + u32_at(record + 4)? as usize;
+        let document = document_start..(document_start + u32_at(record + 8)? as usize);
+        if start_glyph_id > end_glyph_id || table.get(document.clone()).is_none() {
+            return None;
+        }
+        records.push(SvgDocumentRecord { glyph_ids: start_glyph_id..(end_glyph_id + 1), document });
+    }
+    Some(records)
+}
+
+#[cfg(test)]
+mod test {
+    use super::SvgGlyphs;
+    use crate::GlyphId;
+
+    const DOCUMENT: &str = r#"<svg xmlns="http://www.w3.org/2000/svg">
+<g id="glyph3"><rect x="0" y="-700" width="500" height="700" fill="red"/></g>
+<g id="glyph4"><rect x="0" y="-700" width="600" height="700" fill="blue"/></g>
+</svg>"#;
+
+    fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+        bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+        bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    #[test]
+    fn test_svg_glyphs_import_their_own_elements() {
+        // Header: version 0, the document list at 10, and a reserved word.
+        let mut table = vec![];
+        push_u16(&mut table, 0);
+        push_u32(&mut table, 10);
+        push_u32(&mut table, 0);
+        // Document list: glyphs 3 through 5 in the document right after the one record.
+        push_u16(&mut table, 1);
+        push_u16(&mut table, 3);
+        push_u16(&mut table, 5);
+        push_u32(&mut table, 14);
+        push_u32(&mut table, DOCUMENT.len() as u32);
+        table.extend_from_slice(DOCUMENT.as_bytes());
+
+        let mut svg_glyphs = SvgGlyphs::from_table(table.clone().into_boxed_slice()).unwrap();
+        assert!(svg_glyphs.has_glyph(GlyphId(5)));
+        assert!(!svg_glyphs.has_glyph(GlyphId(6)));
+        assert_eq!(svg_glyphs.document(GlyphId(3)), Some(DOCUMENT.as_bytes()));
+        assert!(svg_glyphs.glyph_scene(GlyphId(2)).is_none());
+
+        let scene = svg_glyphs.glyph_scene(GlyphId(3)).unwrap();
+        assert_eq!(scene.draw_path_count(), 1);
+        assert_eq!(scene.bounds().max_x(), 500.0);
+
+        // Glyph 5 is in the document's range, but the document has no element for it.
+        assert!(svg_glyphs.glyph_scene(GlyphId(5)).is_none());
+
+        let truncated = table[..20].to_vec().into_boxed_slice();
+        assert!(SvgGlyphs::from_table(truncated).is_none());
+    }
+}
@@ -0,0 +1,110 @@
+// pathfinder/text/src/synthesis.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Faux bold and faux italics, for text whose font family lacks the requested weight or style.
+//!
+//! Each span of text requests font properties. Where the font that a glyph was laid out with is
+//! lighter than a bold request, or upright under an italic or oblique request,
+//! `layout_synthesis()` marks the glyph for synthesis, and
+//! `FontContext::push_layout_with_synthesis()` draws it emboldened, by offsetting its outline, or
+//! slanted, by shearing it. Emboldened glyphs are widened, and the glyphs after them are moved
+//! along to make room.
+
+use crate::font_context::glyph_text_indices;
+use font_kit::loader::Loader;
+use font_kit::properties::{Properties, Style, Weight};
+use skribo::Layout;
+use std::ops::Range;
+
+/// How far synthetic bold pushes each side of a glyph's outline out, in ems.
+pub const SYNTHETIC_BOLD_DILATION: f32 = 1.0 / 48.0;
+/// How far synthetic oblique slants glyphs: the horizontal shift per unit of height.
+pub const SYNTHETIC_OBLIQUE_SKEW: f32 = 0.21;
+
+/// A range of text, in UTF-8 bytes, and the font properties requested for it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct StyleSpan {
+    pub range: Range<usize>,
+    pub properties: Properties,
+}
+
+/// Which styles to synthesize for a glyph.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Synthesis {
+    /// Embolden the glyph by offsetting its outline.
+    pub bold: bool,
+    /// Slant the glyph by shearing it.
+    pub oblique: bool,
+}
+
+impl Synthesis {
+    /// Returns the styles to synthesize when `requested` properties are drawn with a font that
+    /// has the `available` ones.
+    ///
+    /// Bold is synthesized for requests of semibold or heavier with fonts lighter than semibold,
+    /// and oblique for italic or oblique requests with upright fonts.
+    pub fn new(requested: &Properties, available: &Properties) -> Synthesis {
+        Synthesis {
+            bold: requested.weight >= Weight::SEMIBOLD && available.weight < Weight::SEMIBOLD,
+            oblique: requested.style != Style::Normal && available.style == Style::Normal,
+        }
+    }
+
+    /// Returns true if nothing is synthesized.
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        !self.bold && !self.oblique
+    }
+}
+
+/// Returns the styles to synthesize for each glyph of `layout`, which must have been created
+/// from `text` with `skribo::layout()`, given the properties each span of the text requests.
+///
+/// Where spans overlap, the last one wins. Glyphs outside every span are drawn as they are.
+pub fn layout_synthesis(layout: &Layout, text: &str, spans: &[StyleSpan]) -> Vec<Synthesis> {
+    let text_indices = glyph_text_indices(layout, text);
+    layout.glyphs.iter().zip(text_indices).map(|(glyph, text_index)| {
+        let span = text_index.and_then(|text_index| {
+            spans.iter().rev().find(|span| span.range.contains(&text_index))
+        });
+        match span {
+            None => Synthesis::default(),
+            Some(span) => Synthesis::new(&span.properties, &glyph.font.font.properties()),
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Synthesis;
+    use font_kit::properties::{Properties, Style, Weight};
+
+    #[test]
+    fn test_synthesis_only_fills_in_missing_styles() {
+        let regular = Properties::new();
+        let bold = *Properties::new().weight(Weight::BOLD);
+        let italic = *Properties::new().style(Style::Italic);
+        let bold_italic = *Properties::new().weight(Weight::BOLD).style(Style::Italic);
+
+        assert!(Synthesis::new(&regular, &regular).is_none());
+        assert!(Synthesis::new(&bold_italic, &bold_italic).is_none());
+        assert!(Synthesis::new(&regular, &bold_italic).is_none());
+        assert_eq!(Synthesis::new(&bold, &regular), Synthesis { bold: true, oblique: false });
+        assert_eq!(Synthesis::new(&bold_italic, &bold), Synthesis { bold: false, oblique: true });
+        assert_eq!(Synthesis::new(&bold_italic, &italic),
+                   Synthesis { bold: true, oblique: false });
+
+        // Medium isn't bold enough to synthesize, and oblique fonts satisfy italic requests.
+        let medium = *Properties::new().weight(Weight::MEDIUM);
+        let oblique = *Properties::new().style(Style::Oblique);
+        assert!(Synthesis::new(&medium, &regular).is_none());
+        assert!(Synthesis::new(&italic, &oblique).is_none());
+    }
+}
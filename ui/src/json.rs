@@ -0,0 +1,196 @@
+// pathfinder/ui/src/json.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reads the debug font's metrics in builds without serde.
+//!
+//! This only understands the JSON that the font file uses: objects, strings, numbers, and
+//! booleans.
+
+use crate::{DebugCharacter, DebugFont};
+use hashbrown::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+enum Value {
+    Object(Vec<(String, Value)>),
+    String(String),
+    Number(f64),
+    Bool(bool),
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+pub(crate) fn parse_debug_font(json: &str) -> Option<DebugFont> {
+    let mut parser = Parser { chars: json.chars().peekable() };
+    let mut font = DebugFont {
+        name: String::new(),
+        size: 0,
+        bold: false,
+        italic: false,
+        width: 0,
+        height: 0,
+        characters: HashMap::new(),
+    };
+
+    for (key, value) in parser.object()? {
+        match (&*key, value) {
+            ("name", Value::String(name)) => font.name = name,
+            ("size", Value::Number(size)) => font.size = size as i32,
+            ("bold", Value::Bool(bold)) => font.bold = bold,
+            ("italic", Value::Bool(italic)) => font.italic = italic,
+            ("width", Value::Number(width)) => font.width = width as u32,
+            ("height", Value::Number(height)) => font.height = height as u32,
+            ("characters", Value::Object(characters)) => {
+                for (character, value) in characters {
+                    let mut chars = character.chars();
+                    match (chars.next(), chars.next(), value) {
+                        (Some(character), None, Value::Object(fields)) => {
+                            font.characters.insert(character, parse_debug_character(fields));
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(font)
+}
+
+fn parse_debug_character(fields: Vec<(String, Value)>) -> DebugCharacter {
+    let mut character = DebugCharacter {
+        x: 0,
+        y: 0,
+        width: 0,
+        height: 0,
+        origin_x: 0,
+        origin_y: 0,
+        advance: 0,
+    };
+    for (key, value) in fields {
+        let value = match value {
+            Value::Number(value) => value as i32,
+            _ => continue,
+        };
+        match &*key {
+            "x" => character.x = value,
+            "y" => character.y = value,
+            "width" => character.width = value,
+            "height" => character.height = value,
+            "originX" => character.origin_x = value,
+            "originY" => character.origin_y = value,
+            "advance" => character.advance = value,
+            _ => {}
+        }
+    }
+    character
+}
+
+impl<'a> Parser<'a> {
+    fn value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        match *self.chars.peek()? {
+            '{' => self.object().map(Value::Object),
+            '"' => self.string().map(Value::String),
+            't' | 'f' => self.boolean().map(Value::Bool),
+            _ => self.number().map(Value::Number),
+        }
+    }
+
+    fn object(&mut self) -> Option<Vec<(String, Value)>> {
+        self.expect('{')?;
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Some(members);
+        }
+
+        loop {
+            let key = self.string()?;
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.chars.next()? {
+                ',' => {}
+                '}' => return Some(members),
+                _ => return None,
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(string),
+                '\\' => {
+                    string.push(match self.chars.next()? {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => {
+                            let code: String = (0..4).map(|_| self.chars.next())
+                                                     .collect::<Option<_>>()?;
+                            std::char::from_u32(u32::from_str_radix(&code, 16).ok()?)?
+                        }
+                        character => character,
+                    });
+                }
+                character => string.push(character),
+            }
+        }
+    }
+
+    fn boolean(&mut self) -> Option<bool> {
+        match &*self.take_while(|character| character.is_ascii_alphabetic()) {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        let is_number_char = |character: char| "+-.eE".contains(character) ||
+            character.is_ascii_digit();
+        self.take_while(is_number_char).parse().ok()
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut string = String::new();
+        while let Some(&character) = self.chars.peek() {
+            if !predicate(character) {
+                break;
+            }
+            string.push(character);
+            self.chars.next();
+        }
+        string
+    }
+
+    fn expect(&mut self, expected: char) -> Option<()> {
+        self.skip_whitespace();
+        if self.chars.next()? == expected {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+}
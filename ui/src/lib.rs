@@ -13,6 +13,7 @@
 //! This can be used in your own applications as an ultra-minimal lightweight
 //! alternative to dear imgui, Conrod, etc.
 
+#[cfg(feature = "pf-serde")]
 #[macro_use]
 extern crate serde_derive;
 
@@ -21,12 +22,15 @@ use pathfinder_color::ColorU;
 use pathfinder_geometry::rect::RectI;
 use pathfinder_geometry::vector::{vec2i, Vector2F, Vector2I};
 use pathfinder_gpu::allocator::{BufferTag, GpuMemoryAllocator};
-use pathfinder_gpu::{Device, Texture, TextureDataRef, UniformData, RenderTarget};
+use pathfinder_gpu::sampler::SamplerOptions;
+use pathfinder_gpu::{Device, Texture, UniformData, RenderTarget};
 use pathfinder_resources::ResourceLoader;
 use pathfinder_simd::default::F32x4;
-use serde_json;
 use std::mem;
 
+#[cfg(not(feature = "pf-serde"))]
+mod json;
+
 pub const PADDING: i32 = 12;
 
 pub const LINE_HEIGHT: i32 = 42;
@@ -121,42 +125,18 @@ impl UIPresenter {
         let solid_filled_pipeline = device.create_render_pipeline(resources, "debug/solid", None);
         let solid_outline_pipeline = device.create_render_pipeline(resources, "debug/solid", Some("outline"));
 
-        // create_texture_from_png was likely a method on Device or an extension.
-        // We'll assume for now it's still available or we'll need to adapt.
-        let font_data = resources.slurp(&format!("textures/{}.png", FONT_PNG_NAME)).unwrap();
-        let font_image = image::load_from_memory_with_format(&font_data, image::ImageFormat::Png).unwrap();
-        let font_image = font_image.to_luma8();
-        let font_size = vec2i(font_image.width() as i32, font_image.height() as i32);
-        let font_texture = device.create_texture(wgpu::TextureFormat::R8Unorm, font_size, wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC);
-        let font_rect = RectI::new(Vector2I::default(), font_size);
-        device.upload_to_texture(&font_texture, font_rect, TextureDataRef::U8(&font_image));
-        
-        let corner_fill_data = resources.slurp(&format!("textures/{}.png", CORNER_FILL_PNG_NAME)).unwrap();
-        let corner_fill_image = image::load_from_memory_with_format(&corner_fill_data, image::ImageFormat::Png).unwrap();
-        let corner_fill_image = corner_fill_image.to_luma8();
-        let corner_fill_size = vec2i(corner_fill_image.width() as i32, corner_fill_image.height() as i32);
-        let corner_fill_texture = device.create_texture(wgpu::TextureFormat::R8Unorm, corner_fill_size, wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC);
-        let corner_fill_rect = RectI::new(Vector2I::default(), corner_fill_size);
-        device.upload_to_texture(&corner_fill_texture, corner_fill_rect, TextureDataRef::U8(&corner_fill_image));
-
-        let corner_outline_data = resources.slurp(&format!("textures/{}.png", CORNER_OUTLINE_PNG_NAME)).unwrap();
-        let corner_outline_image = image::load_from_memory_with_format(&corner_outline_data, image::ImageFormat::Png).unwrap();
-        let corner_outline_image = corner_outline_image.to_luma8();
-        let corner_outline_size = vec2i(corner_outline_image.width() as i32, corner_outline_image.height() as i32);
-        let corner_outline_texture = device.create_texture(wgpu::TextureFormat::R8Unorm, corner_outline_size, wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC);
-        let corner_outline_rect = RectI::new(Vector2I::default(), corner_outline_size);
-        device.upload_to_texture(&corner_outline_texture, corner_outline_rect, TextureDataRef::U8(&corner_outline_image));
-
-        let cached_sampler = device.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: None,
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
-            ..Default::default()
-        });
+        let texture_usage = wgpu::TextureUsages::TEXTURE_BINDING |
+            wgpu::TextureUsages::RENDER_ATTACHMENT |
+            wgpu::TextureUsages::COPY_SRC;
+        let load_texture = |name: &str| {
+            let data = resources.slurp(&format!("textures/{}.png", name)).unwrap();
+            device.create_texture_from_png(&data, wgpu::TextureFormat::R8Unorm, texture_usage)
+        };
+        let font_texture = load_texture(FONT_PNG_NAME);
+        let corner_fill_texture = load_texture(CORNER_FILL_PNG_NAME);
+        let corner_outline_texture = load_texture(CORNER_OUTLINE_PNG_NAME);
+
+        let cached_sampler = device.sampler(SamplerOptions::default());
 
         let transform = [
             2.0 / framebuffer_size.x() as f32, 0.0, 0.0, 0.0,
@@ -1050,7 +1030,7 @@ pub struct MousePosition {
     pub relative: Vector2I,
 }
 
-#[derive(Deserialize)]
+#[cfg_attr(feature = "pf-serde", derive(Deserialize))]
 #[allow(dead_code)]
 pub struct DebugFont {
     name: String,
@@ -1062,22 +1042,29 @@ pub struct DebugFont {
     characters: HashMap<char, DebugCharacter>,
 }
 
-#[derive(Deserialize)]
+#[cfg_attr(feature = "pf-serde", derive(Deserialize))]
 struct DebugCharacter {
     x: i32,
     y: i32,
     width: i32,
     height: i32,
-    #[serde(rename = "originX")]
+    #[cfg_attr(feature = "pf-serde", serde(rename = "originX"))]
     origin_x: i32,
-    #[serde(rename = "originY")]
+    #[cfg_attr(feature = "pf-serde", serde(rename = "originY"))]
     origin_y: i32,
     advance: i32,
 }
 
 impl DebugFont {
+    #[cfg(feature = "pf-serde")]
     #[inline]
     fn load(resources: &dyn ResourceLoader) -> DebugFont {
         serde_json::from_slice(&resources.slurp(FONT_JSON_VIRTUAL_PATH).unwrap()).unwrap()
     }
+
+    #[cfg(not(feature = "pf-serde"))]
+    fn load(resources: &dyn ResourceLoader) -> DebugFont {
+        let data = resources.slurp(FONT_JSON_VIRTUAL_PATH).unwrap();
+        json::parse_debug_font(std::str::from_utf8(&data).unwrap()).unwrap()
+    }
 }
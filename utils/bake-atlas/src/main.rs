@@ -0,0 +1,51 @@
+// pathfinder/utils/bake-atlas/src/main.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Bakes a glyph atlas from a font file.
+//!
+//! Usage: `bake-atlas FONT OUTPUT SIZE...`
+//!
+//! Writes `OUTPUT.png` and `OUTPUT.json`, containing printable ASCII at each of the given sizes.
+
+use font_kit::font::Font;
+use pathfinder_geometry::vector::vec2i;
+use pathfinder_headless::HeadlessRenderer;
+use pathfinder_text::atlas::{GlyphAtlas, GlyphAtlasOptions};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args_os().skip(1);
+    let input = PathBuf::from(args.next().expect("no font given"));
+    let output = PathBuf::from(args.next().expect("no output given"));
+    let sizes = args.map(|size| size.to_string_lossy().parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()?;
+    if sizes.is_empty() {
+        return Err("at least one size must be given".into());
+    }
+
+    let mut data = Vec::new();
+    File::open(input)?.read_to_end(&mut data)?;
+    let font = Font::from_bytes(Arc::new(data), 0)?;
+
+    let options = GlyphAtlasOptions { sizes, ..GlyphAtlasOptions::default() };
+    let mut atlas = GlyphAtlas::new(&font, &options)?;
+
+    let size = vec2i(atlas.metrics.width, atlas.metrics.height);
+    let mut renderer = HeadlessRenderer::new(size, None)?;
+    let image = renderer.render(&mut atlas.scene);
+
+    image.save(output.with_extension("png"))?;
+    fs::write(output.with_extension("json"), atlas.metrics.to_json())?;
+    Ok(())
+}